@@ -0,0 +1,2237 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Context, Result};
+use catalog::db::{BoundingBox, CatalogDb, DatePeriod, Image};
+use catalog::services::capture_time::{
+    correct_capture_times, format_offset, list_capture_time_corrections, parse_offset, parse_time,
+    parse_zone, undo_capture_time_correction,
+};
+use catalog::services::contact_sheet::write_contact_sheet;
+use catalog::services::export::{export_images, BitDepth, ExportCallbacks};
+use catalog::services::external_catalog::{migrate_external_catalog, ImageChangeKind};
+use catalog::services::gallery::{generate_gallery, import_gallery_picks};
+use catalog::services::geocode::geocode_images;
+use catalog::services::geotag::{
+    apply_geotags, list_geotag_batches, place_images, preview_geotags, undo_geotags,
+};
+use catalog::services::gpx::read_gpx;
+use catalog::services::import::{
+    collect_image_paths, import_files, parse_keywords, CancellationFlag, DuplicateStrategy,
+    ImportCallbacks, ImportMethod,
+};
+use catalog::services::merge::merge_catalog;
+use catalog::services::portable::export_portable_catalog;
+use catalog::services::sort::sort_images;
+use catalog::services::{
+    CaptureTimeAdjustment, CaptureTimeSettings, CatalogService, ConflictPolicy,
+    ContactSheetCaptions, ContactSheetSettings, ContactSheetSource, ExportFormat, ExportSettings,
+    ExternalSource, GalleryCaptions, GallerySettings, GalleryTemplate, GeocodeSettings,
+    GeotagSettings, ImageSort, MergeSettings, MetadataPolicy, NamedExportPreset, PageOrientation,
+    PageSize, PathRemap, PortableCatalogSettings, ResizeMode, RightsInfo, SharpenAmount,
+    SharpenMedium, SortKey, WatermarkAnchor, WatermarkDefinition, WatermarkKind,
+};
+use catalog::CatalogPath;
+use chrono::{DateTime, Utc};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use engine::ImageEngine;
+use serde_json::{json, Value};
+
+#[derive(Parser)]
+#[command(
+    name = "zenithphoto-cli",
+    version,
+    about = "Headless ZenithPhoto catalog operations"
+)]
+struct Cli {
+    /// Catalog file to operate on.
+    #[arg(short, long, global = true)]
+    catalog: Option<PathBuf>,
+
+    /// Print machine-readable JSON instead of text.
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create a new, empty catalog.
+    Create,
+    /// Open the catalog and print a summary.
+    Info,
+    /// Import every supported image under a directory.
+    Import {
+        dir: PathBuf,
+        /// Comma-separated keywords applied to every imported image.
+        #[arg(short, long)]
+        keywords: Option<String>,
+        /// Copy files into this directory before cataloging them.
+        #[arg(long, conflicts_with = "move_to")]
+        copy_to: Option<PathBuf>,
+        /// Move files into this directory before cataloging them.
+        #[arg(long)]
+        move_to: Option<PathBuf>,
+        /// Import files even when their content is already in the catalog.
+        #[arg(long)]
+        allow_duplicates: bool,
+    },
+    /// Migrate ratings, keywords, collections and edits from another application's catalog.
+    Migrate {
+        #[arg(value_enum)]
+        from: MigrationSource,
+        /// The source catalog file.
+        source: PathBuf,
+        /// Print what would change without writing to the catalog.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Merge another ZenithPhoto catalog into this one.
+    Merge(MergeArgs),
+    /// Write the selected images to a new standalone catalog.
+    ExportCatalog(ExportCatalogArgs),
+    /// Full-text search over filenames, paths and metadata.
+    Search {
+        query: String,
+        #[arg(long)]
+        limit: Option<usize>,
+        /// Order the results by this instead of capture time.
+        #[arg(long, value_enum)]
+        sort: Option<SortArg>,
+        /// Reverse the sort order; images missing the sort value stay last.
+        #[arg(long)]
+        descending: bool,
+    },
+    /// Set the star rating (0-5) of one or more images.
+    Rate {
+        #[arg(value_parser = clap::value_parser!(i32).range(0..=5))]
+        rating: i32,
+        #[arg(required = true)]
+        ids: Vec<i64>,
+    },
+    /// Set the pick flag of one or more images.
+    Flag {
+        #[arg(value_enum)]
+        flag: FlagArg,
+        #[arg(required = true)]
+        ids: Vec<i64>,
+    },
+    /// Set the color label of one or more images ("none" clears it).
+    Label {
+        label: String,
+        #[arg(required = true)]
+        ids: Vec<i64>,
+    },
+    /// Replace the keywords of an image with a comma-separated list.
+    Keywords {
+        id: i64,
+        keywords: String,
+        /// Add to the existing keywords instead of replacing them.
+        #[arg(long)]
+        add: bool,
+    },
+    /// List catalog folders.
+    Folders,
+    /// List collections.
+    Collections,
+    /// Set a collection's manual order; the listed images go first, the rest keep theirs.
+    Reorder {
+        collection: i64,
+        #[arg(required = true)]
+        ids: Vec<i64>,
+    },
+    /// Rebuild the full-text search and spatial indexes.
+    RebuildFts,
+    /// Compact the catalog database.
+    Vacuum,
+    /// Check catalog integrity and optionally repair what can be fixed.
+    Verify {
+        #[arg(long)]
+        repair: bool,
+    },
+    /// Render the selected images with their edits into a directory.
+    Export(ExportArgs),
+    /// Manage named export presets.
+    #[command(subcommand)]
+    Presets(PresetCommand),
+    /// Build a static HTML proofing gallery from the selected images.
+    Gallery(GalleryArgs),
+    /// Flag the images a client picked in a gallery.
+    ImportPicks {
+        /// The gallery's picks.json or an edited manifest.json.
+        file: PathBuf,
+    },
+    /// Write a PDF contact sheet of the selected images.
+    ContactSheet(ContactSheetArgs),
+    /// Manage named watermarks used by exports.
+    #[command(subcommand)]
+    Watermarks(WatermarkCommand),
+    /// Manage the named roots folders are stored relative to.
+    #[command(subcommand)]
+    Roots(RootCommand),
+    /// Correct the capture times of the selected images, or undo a correction.
+    #[command(subcommand)]
+    CaptureTime(CaptureTimeCommand),
+    /// Geotag the selected images from a GPX track, or undo a geotagging run.
+    #[command(subcommand)]
+    Geotag(GeotagCommand),
+    /// Look up the city, region and country of geotagged images from the bundled gazetteer.
+    Locate(LocateArgs),
+    /// Find images by position: near a point, inside a box, or counted per map cell.
+    #[command(subcommand)]
+    Geo(GeoCommand),
+    /// Count images per year, or per month or day of a period, by the date they were taken.
+    Dates {
+        /// A YYYY year or YYYY-MM month to break down; a YYYY-MM-DD day lists its images.
+        period: Option<DatePeriod>,
+        /// List the images taken in the period instead of counting them.
+        #[arg(long, requires = "period")]
+        images: bool,
+    },
+}
+
+#[derive(Args)]
+struct ExportArgs {
+    /// Destination directory; defaults to the preset's destination.
+    dest: Option<PathBuf>,
+    /// Image ids to export.
+    #[arg(long, num_args = 1.., required_unless_present = "query")]
+    ids: Vec<i64>,
+    /// Export the results of a search instead of explicit ids.
+    #[arg(long)]
+    query: Option<String>,
+    /// Start from a saved export preset; other options override its values.
+    #[arg(long)]
+    preset: Option<String>,
+    #[command(flatten)]
+    settings: SettingsArgs,
+}
+
+#[derive(Args)]
+struct GalleryArgs {
+    /// Folder to write the gallery into.
+    dest: PathBuf,
+    /// Image ids to include.
+    #[arg(long, num_args = 1.., required_unless_present_any = ["query", "collection"])]
+    ids: Vec<i64>,
+    /// Include the results of a search.
+    #[arg(long, conflicts_with = "collection")]
+    query: Option<String>,
+    /// Include every image in a collection, by id.
+    #[arg(long)]
+    collection: Option<i64>,
+    #[arg(long, default_value = "Gallery")]
+    title: String,
+    #[arg(long, value_enum, default_value = "classic")]
+    template: TemplateArg,
+    /// Long edge of the lightbox images.
+    #[arg(long, default_value_t = 1600)]
+    image_size: u32,
+    /// Long edge of the thumbnails.
+    #[arg(long, default_value_t = 400)]
+    thumbnail_size: u32,
+    /// JPEG quality, 1-100.
+    #[arg(long, default_value_t = 85, value_parser = clap::value_parser!(u8).range(1..=100))]
+    quality: u8,
+    /// Caption fields to show; pass none for no captions.
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "filename,keywords,rating"
+    )]
+    captions: Vec<CaptionArg>,
+    /// Creator and copyright embedded in the gallery images.
+    #[arg(long)]
+    creator: Option<String>,
+    #[arg(long)]
+    copyright: Option<String>,
+    /// Composite a saved watermark onto the lightbox images.
+    #[arg(long)]
+    watermark: Option<String>,
+}
+
+#[derive(Args)]
+struct ContactSheetArgs {
+    /// PDF file to write.
+    output: PathBuf,
+    /// Image ids to include.
+    #[arg(long, num_args = 1.., required_unless_present_any = ["query", "collection"])]
+    ids: Vec<i64>,
+    /// Include the results of a search.
+    #[arg(long, conflicts_with = "collection")]
+    query: Option<String>,
+    /// Include every image in a collection, by id.
+    #[arg(long)]
+    collection: Option<i64>,
+    /// Printed at the top of each page; pass "" for none.
+    #[arg(long, default_value = "Contact Sheet")]
+    title: String,
+    #[arg(long, value_enum, default_value = "a4")]
+    page_size: PageSizeArg,
+    #[arg(long)]
+    landscape: bool,
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..=12))]
+    columns: u32,
+    #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u32).range(1..=16))]
+    rows: u32,
+    /// Caption fields under each photo; pass none for no captions.
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "filename,date,rating,label"
+    )]
+    captions: Vec<SheetCaptionArg>,
+    /// Render every image with its edits instead of using stored thumbnails.
+    #[arg(long)]
+    render: bool,
+}
+
+/// Export options shared by `export` and `presets save`. Unset options keep their base value.
+#[derive(Args)]
+struct SettingsArgs {
+    #[arg(long, value_enum)]
+    format: Option<FormatArg>,
+    /// JPEG quality, 1-100.
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=100))]
+    quality: Option<u8>,
+    /// Bits per channel for PNG and TIFF.
+    #[arg(long, value_enum)]
+    bit_depth: Option<BitDepthArg>,
+    /// Scale so the longer side is at most this many pixels.
+    #[arg(long, group = "resize")]
+    long_edge: Option<u32>,
+    /// Scale to at most this many megapixels.
+    #[arg(long, group = "resize")]
+    megapixels: Option<f64>,
+    /// Fit inside a box, given as WIDTHxHEIGHT.
+    #[arg(long, group = "resize", value_parser = parse_box)]
+    fit: Option<(u32, u32)>,
+    /// Keep the original size even when the preset resizes.
+    #[arg(long, group = "resize")]
+    original_size: bool,
+    /// Output filename template, e.g. "{date}_{seq:3}".
+    #[arg(long)]
+    template: Option<String>,
+    /// Replace existing files instead of adding a numeric suffix.
+    #[arg(long)]
+    overwrite: bool,
+    /// Which catalog metadata is written into JPEG and TIFF files; GPS and the camera
+    /// serial number are only written with `all`.
+    #[arg(long, value_enum)]
+    metadata: Option<MetadataArg>,
+    /// Creator name written as EXIF Artist, XMP and IPTC by-line.
+    #[arg(long)]
+    creator: Option<String>,
+    /// Copyright notice.
+    #[arg(long)]
+    copyright: Option<String>,
+    /// Contact email address or URL.
+    #[arg(long)]
+    contact: Option<String>,
+    /// Output sharpening after resizing.
+    #[arg(long, value_enum)]
+    sharpen: Option<SharpenArg>,
+    /// Sharpening strength.
+    #[arg(long, value_enum)]
+    sharpen_amount: Option<AmountArg>,
+    /// Printing resolution used to scale sharpening for paper output.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(72..=2400))]
+    print_ppi: Option<u32>,
+    /// Composite a saved watermark onto every image.
+    #[arg(long, conflicts_with = "no_watermark")]
+    watermark: Option<String>,
+    /// Export without the preset's watermark.
+    #[arg(long)]
+    no_watermark: bool,
+}
+
+#[derive(Subcommand)]
+enum PresetCommand {
+    /// List saved export presets.
+    List,
+    /// Save export options under a name; options not given keep the preset's current values.
+    Save {
+        name: String,
+        /// Default destination directory for exports using this preset.
+        #[arg(long)]
+        dest: Option<PathBuf>,
+        #[command(flatten)]
+        settings: SettingsArgs,
+    },
+    /// Delete a saved preset.
+    Delete { name: String },
+    /// Add the presets from a shared JSON file, replacing presets with the same names.
+    Import { file: PathBuf },
+    /// Write presets to a JSON file for sharing; all presets unless names are given.
+    Export { file: PathBuf, names: Vec<String> },
+}
+
+#[derive(Subcommand)]
+enum RootCommand {
+    /// List roots with their folder and image counts.
+    List,
+    /// Add a root; folders below it are moved from broader roots.
+    Add {
+        path: PathBuf,
+        /// Defaults to the directory name.
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// Rename a root.
+    Rename { root: String, name: String },
+    /// Point a root at a new location, e.g. after copying a drive to a NAS.
+    Relocate { root: String, path: PathBuf },
+}
+
+#[derive(Subcommand)]
+enum CaptureTimeCommand {
+    /// Shift by an offset such as +9h or -1h30m.
+    Shift {
+        #[arg(allow_hyphen_values = true, value_parser = parse_offset_arg)]
+        offset: i64,
+        #[command(flatten)]
+        target: CaptureTimeTarget,
+    },
+    /// Move the earliest image to a time, shifting the rest by the same amount.
+    SetFirst {
+        #[arg(value_parser = parse_time_arg)]
+        time: DateTime<Utc>,
+        #[command(flatten)]
+        target: CaptureTimeTarget,
+    },
+    /// Fix a camera clock set to one UTC offset while shooting in another, e.g. +01:00 -05:00.
+    Zone {
+        #[arg(allow_hyphen_values = true, value_parser = parse_zone_arg)]
+        camera: i32,
+        #[arg(allow_hyphen_values = true, value_parser = parse_zone_arg)]
+        actual: i32,
+        #[command(flatten)]
+        target: CaptureTimeTarget,
+    },
+    /// Shift by the time zone offset that lines the images up with a GPX track.
+    Sync {
+        track: PathBuf,
+        #[command(flatten)]
+        target: CaptureTimeTarget,
+    },
+    /// Undo a correction, the most recent one unless an id is given.
+    Undo { id: Option<i64> },
+    /// List corrections that can be undone.
+    History,
+}
+
+#[derive(Args)]
+struct CaptureTimeTarget {
+    /// Image ids to correct.
+    #[arg(long, num_args = 1.., required_unless_present_any = ["query", "collection"])]
+    ids: Vec<i64>,
+    /// Correct the results of a search.
+    #[arg(long, conflicts_with = "collection")]
+    query: Option<String>,
+    /// Correct every image in a collection, by id.
+    #[arg(long)]
+    collection: Option<i64>,
+    /// Also write the corrected time into XMP sidecars, creating them where missing.
+    #[arg(long)]
+    write_sidecars: bool,
+}
+
+#[derive(Subcommand)]
+enum GeotagCommand {
+    /// Set each image's position from the track at its capture time.
+    Apply {
+        track: PathBuf,
+        /// Image ids to geotag.
+        #[arg(long, num_args = 1.., required_unless_present_any = ["query", "collection"])]
+        ids: Vec<i64>,
+        /// Geotag the results of a search.
+        #[arg(long, conflicts_with = "collection")]
+        query: Option<String>,
+        /// Geotag every image in a collection, by id.
+        #[arg(long)]
+        collection: Option<i64>,
+        /// Added to capture times to get UTC, e.g. -2h for a camera set to UTC+2.
+        #[arg(long, default_value = "0s", allow_hyphen_values = true, value_parser = parse_offset_arg)]
+        offset: i64,
+        /// Longest time between track points to interpolate across, e.g. 5m.
+        #[arg(long, default_value = "5m", value_parser = parse_offset_arg)]
+        max_gap: i64,
+        /// Replace positions images already have.
+        #[arg(long)]
+        overwrite: bool,
+        /// Print the matched and unmatched images without writing to the catalog.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Put the selected images at one position.
+    Place {
+        #[arg(allow_hyphen_values = true)]
+        latitude: f64,
+        #[arg(allow_hyphen_values = true)]
+        longitude: f64,
+        /// Image ids to place.
+        #[arg(long, num_args = 1.., required_unless_present_any = ["query", "collection"])]
+        ids: Vec<i64>,
+        /// Place the results of a search.
+        #[arg(long, conflicts_with = "collection")]
+        query: Option<String>,
+        /// Place every image in a collection, by id.
+        #[arg(long)]
+        collection: Option<i64>,
+    },
+    /// Undo a geotagging run, the most recent one unless an id is given.
+    Undo { id: Option<i64> },
+    /// List geotagging runs that can be undone.
+    History,
+}
+
+#[derive(Args)]
+struct LocateArgs {
+    /// Image ids to locate.
+    #[arg(long, num_args = 1.., required_unless_present_any = ["query", "collection"])]
+    ids: Vec<i64>,
+    /// Locate the results of a search.
+    #[arg(long, conflicts_with = "collection")]
+    query: Option<String>,
+    /// Locate every image in a collection, by id.
+    #[arg(long)]
+    collection: Option<i64>,
+    /// Leave images further than this many kilometres from a known place unlocated.
+    #[arg(long, default_value_t = 50.0)]
+    max_distance: f64,
+    /// Don't assign Places|Country|Region|City keywords.
+    #[arg(long)]
+    no_keywords: bool,
+    /// Look up images that already have a location again.
+    #[arg(long)]
+    overwrite: bool,
+}
+
+#[derive(Subcommand)]
+enum GeoCommand {
+    /// List images within a radius of a point, nearest first.
+    Near {
+        #[arg(allow_hyphen_values = true)]
+        latitude: f64,
+        #[arg(allow_hyphen_values = true)]
+        longitude: f64,
+        /// Search radius in kilometres.
+        #[arg(long, default_value_t = 1.0)]
+        radius: f64,
+    },
+    /// List images inside a SOUTH,WEST,NORTH,EAST box; WEST greater than EAST crosses the
+    /// antimeridian.
+    Within {
+        #[arg(allow_hyphen_values = true, value_parser = parse_bounds)]
+        bounds: BoundingBox,
+    },
+    /// Count images per map grid cell at a zoom level.
+    Clusters {
+        /// 0 counts the whole world as one cell; each level halves the cell size.
+        #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u8).range(0..=24))]
+        zoom: u8,
+        /// Only count images inside this SOUTH,WEST,NORTH,EAST box.
+        #[arg(long, allow_hyphen_values = true, value_parser = parse_bounds)]
+        bounds: Option<BoundingBox>,
+    },
+}
+
+#[derive(Subcommand)]
+enum WatermarkCommand {
+    /// List saved watermarks.
+    List,
+    /// Save a text or logo watermark; options not given keep the watermark's current values.
+    Save {
+        name: String,
+        /// Text to draw with the bundled font.
+        #[arg(long, conflicts_with = "logo")]
+        text: Option<String>,
+        /// PNG logo composited with its own transparency.
+        #[arg(long)]
+        logo: Option<PathBuf>,
+        /// Text color as #RRGGBB.
+        #[arg(long)]
+        color: Option<String>,
+        #[arg(long, value_enum)]
+        anchor: Option<AnchorArg>,
+        /// Text line height or logo size, in percent of the image's short edge.
+        #[arg(long)]
+        size: Option<f32>,
+        /// Opacity in percent.
+        #[arg(long)]
+        opacity: Option<f32>,
+        /// Distance from the image edges, in percent of the short edge.
+        #[arg(long)]
+        margin: Option<f32>,
+    },
+    /// Delete a watermark that no preset uses.
+    Delete { name: String },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum AnchorArg {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum FormatArg {
+    Jpeg,
+    Png,
+    Tiff,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum BitDepthArg {
+    #[value(name = "8")]
+    Eight,
+    #[value(name = "16")]
+    Sixteen,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum MetadataArg {
+    /// No metadata.
+    Strip,
+    /// Creator, copyright and contact only.
+    Copyright,
+    /// Everything except GPS and camera serial number.
+    NoLocation,
+    /// Everything, including keywords, rating, GPS and camera serial number.
+    All,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum TemplateArg {
+    Classic,
+    Dark,
+    Minimal,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum CaptionArg {
+    None,
+    Filename,
+    Keywords,
+    Rating,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum PageSizeArg {
+    A4,
+    Letter,
+    A3,
+    Legal,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SheetCaptionArg {
+    None,
+    Filename,
+    /// Capture date and time.
+    Date,
+    Rating,
+    /// Color label swatch.
+    Label,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SharpenArg {
+    /// No output sharpening.
+    None,
+    Screen,
+    Matte,
+    Glossy,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum AmountArg {
+    Low,
+    Standard,
+    High,
+}
+
+#[derive(Args)]
+struct MergeArgs {
+    /// The catalog to merge in.
+    source: PathBuf,
+    /// Rewrite source paths starting with FROM to start with TO (repeatable).
+    #[arg(long, value_name = "FROM=TO", value_parser = parse_remap)]
+    remap: Vec<PathRemap>,
+    /// Which side wins for ratings, flags and color labels.
+    #[arg(long, value_enum, default_value = "newest")]
+    ratings: PolicyArg,
+    #[arg(long, value_enum, default_value = "newest")]
+    keywords: PolicyArg,
+    #[arg(long, value_enum, default_value = "newest")]
+    edits: PolicyArg,
+    /// Which side wins for membership of collections both catalogs have.
+    #[arg(long, value_enum, default_value = "newest")]
+    collections: PolicyArg,
+}
+
+#[derive(Args)]
+struct ExportCatalogArgs {
+    /// Path of the new catalog.
+    dest: PathBuf,
+    /// Image ids to include.
+    #[arg(long, num_args = 1.., required_unless_present_any = ["query", "collection"])]
+    ids: Vec<i64>,
+    /// Include the results of a search.
+    #[arg(long, conflicts_with = "collection")]
+    query: Option<String>,
+    /// Include every image in a collection, by id.
+    #[arg(long)]
+    collection: Option<i64>,
+    /// Copy the originals into a folder next to the new catalog.
+    #[arg(long)]
+    copy_originals: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum PolicyArg {
+    /// Keep whichever side was changed last.
+    Newest,
+    /// Prefer the merged catalog.
+    Source,
+    /// Prefer this catalog.
+    Target,
+}
+
+impl From<PolicyArg> for ConflictPolicy {
+    fn from(value: PolicyArg) -> Self {
+        match value {
+            PolicyArg::Newest => ConflictPolicy::Newest,
+            PolicyArg::Source => ConflictPolicy::PreferSource,
+            PolicyArg::Target => ConflictPolicy::PreferTarget,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SortArg {
+    Captured,
+    Imported,
+    Filename,
+    Rating,
+    /// Color label, in picker order.
+    Label,
+    Size,
+    Camera,
+    /// When the develop settings last changed.
+    Edited,
+}
+
+impl From<SortArg> for SortKey {
+    fn from(value: SortArg) -> Self {
+        match value {
+            SortArg::Captured => SortKey::CaptureTime,
+            SortArg::Imported => SortKey::ImportTime,
+            SortArg::Filename => SortKey::Filename,
+            SortArg::Rating => SortKey::Rating,
+            SortArg::Label => SortKey::ColorLabel,
+            SortArg::Size => SortKey::FileSize,
+            SortArg::Camera => SortKey::Camera,
+            SortArg::Edited => SortKey::EditTime,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum MigrationSource {
+    /// A Lightroom Classic `.lrcat`.
+    Lightroom,
+    /// A darktable `library.db`.
+    Darktable,
+    /// A digiKam `digikam4.db`.
+    Digikam,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum FlagArg {
+    Picked,
+    Rejected,
+    None,
+}
+
+impl FlagArg {
+    fn as_str(self) -> &'static str {
+        match self {
+            FlagArg::Picked => "picked",
+            FlagArg::Rejected => "rejected",
+            FlagArg::None => "none",
+        }
+    }
+}
+
+/// Result of a command: a JSON value for scripts and plain lines for people.
+struct Output {
+    json: Value,
+    text: Vec<String>,
+}
+
+impl Output {
+    fn new(json: Value, text: impl Into<String>) -> Self {
+        Self {
+            json,
+            text: vec![text.into()],
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let as_json = cli.json;
+    match run(cli) {
+        Ok(output) => {
+            if as_json {
+                println!("{}", output.json);
+            } else {
+                for line in output.text {
+                    println!("{line}");
+                }
+            }
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            if as_json {
+                println!("{}", json!({ "error": format!("{err:#}") }));
+            } else {
+                eprintln!("error: {err:#}");
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<Output> {
+    let catalog_path = cli
+        .catalog
+        .map(|path| CatalogPath::new(path).into_path())
+        .ok_or_else(|| anyhow!("--catalog is required"))?;
+
+    if let Command::Create = cli.command {
+        return create_catalog(&catalog_path);
+    }
+
+    let service = open_catalog(&catalog_path)?;
+    match cli.command {
+        Command::Create => unreachable!("handled above"),
+        Command::Info => catalog_info(&service, &catalog_path),
+        Command::Import {
+            dir,
+            keywords,
+            copy_to,
+            move_to,
+            allow_duplicates,
+        } => import_directory(
+            &service,
+            &dir,
+            keywords.as_deref(),
+            copy_to,
+            move_to,
+            allow_duplicates,
+        ),
+        Command::Search {
+            query,
+            limit,
+            sort,
+            descending,
+        } => {
+            let mut images = service.search(&query)?;
+            if sort.is_some() || descending {
+                let sort = ImageSort {
+                    key: sort.map(SortKey::from).unwrap_or_default(),
+                    descending,
+                };
+                sort_images(&service, &mut images, &sort, None)?;
+            }
+            if let Some(limit) = limit {
+                images.truncate(limit);
+            }
+            Ok(image_list(images))
+        }
+        Command::Rate { rating, ids } => {
+            update_images(&service, &ids, "rating", json!(rating), |id| {
+                service.update_rating(id, rating)
+            })
+        }
+        Command::Flag { flag, ids } => {
+            update_images(&service, &ids, "flag", json!(flag.as_str()), |id| {
+                service.update_flag(id, flag.as_str())
+            })
+        }
+        Command::Label { label, ids } => {
+            update_images(&service, &ids, "color_label", json!(label), |id| {
+                service.update_color_label(id, &label)
+            })
+        }
+        Command::Keywords { id, keywords, add } => set_keywords(&service, id, &keywords, add),
+        Command::Folders => {
+            let folders = service.list_folders()?;
+            let text = folders
+                .iter()
+                .map(|folder| format!("{:>6}  {}", folder.id, folder.path))
+                .collect();
+            Ok(Output {
+                json: serde_json::to_value(&folders)?,
+                text,
+            })
+        }
+        Command::Collections => {
+            let collections = service.list_collections()?;
+            let mut text = Vec::with_capacity(collections.len());
+            for collection in &collections {
+                let count = service.list_images_in_collection(collection.id)?.len();
+                text.push(format!(
+                    "{:>6}  {} ({count} images)",
+                    collection.id, collection.name
+                ));
+            }
+            Ok(Output {
+                json: serde_json::to_value(&collections)?,
+                text,
+            })
+        }
+        Command::Reorder { collection, ids } => {
+            service.reorder_collection(collection, &ids)?;
+            let order: Vec<i64> = service
+                .list_images_in_collection(collection)?
+                .iter()
+                .map(|image| image.id)
+                .collect();
+            Ok(Output::new(
+                json!({ "collection": collection, "order": order }),
+                format!(
+                    "Reordered {} image(s) in collection {collection}",
+                    order.len()
+                ),
+            ))
+        }
+        Command::RebuildFts => {
+            service.rebuild_search_index()?;
+            Ok(Output::new(
+                json!({ "rebuilt": true }),
+                "Search index rebuilt",
+            ))
+        }
+        Command::Vacuum => {
+            service.vacuum()?;
+            Ok(Output::new(
+                json!({ "vacuumed": true }),
+                "Catalog compacted",
+            ))
+        }
+        Command::Migrate {
+            from,
+            source,
+            dry_run,
+        } => migrate(&service, from, &source, dry_run),
+        Command::Merge(args) => merge(&service, args),
+        Command::ExportCatalog(args) => export_catalog(&service, args),
+        Command::Verify { repair } => verify_catalog(&service, repair),
+        Command::Export(args) => export(&service, args),
+        Command::Presets(command) => presets(&service, command),
+        Command::Gallery(args) => gallery(&service, args),
+        Command::ImportPicks { file } => {
+            let result = import_gallery_picks(&service, &file)?;
+            let mut text = format!("Flagged {} image(s) as picked", result.picked.len());
+            if !result.skipped.is_empty() {
+                text.push_str(&format!(
+                    "; skipped {} not matching this catalog",
+                    result.skipped.len()
+                ));
+            }
+            Ok(Output::new(serde_json::to_value(&result)?, text))
+        }
+        Command::ContactSheet(args) => contact_sheet(&service, args),
+        Command::Watermarks(command) => watermarks(&service, command),
+        Command::Roots(command) => roots(&service, command),
+        Command::CaptureTime(command) => capture_time(&service, command),
+        Command::Geotag(command) => geotag(&service, command),
+        Command::Locate(args) => locate(&service, args),
+        Command::Geo(command) => geo(&service, command),
+        Command::Dates { period, images } => dates(&service, period, images),
+    }
+}
+
+fn create_catalog(path: &Path) -> Result<Output> {
+    if path.exists() {
+        bail!("catalog already exists at {}", path.display());
+    }
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create catalog directory {}", parent.display()))?;
+    }
+    open_db(path)?;
+    Ok(Output::new(
+        json!({ "catalog": path, "created": true }),
+        format!("Created catalog {}", path.display()),
+    ))
+}
+
+fn open_catalog(path: &Path) -> Result<CatalogService> {
+    if !path.exists() {
+        bail!("catalog not found at {}", path.display());
+    }
+    // Pass the home folder in so folders get the same roots as in the desktop app.
+    let home = directories::BaseDirs::new().map(|dirs| dirs.home_dir().to_path_buf());
+    let service = CatalogService::new(open_db(path)?).with_home_dir(home);
+    service.assign_folder_roots()?;
+    Ok(service)
+}
+
+fn open_db(path: &Path) -> Result<CatalogDb> {
+    let db_path = path
+        .to_str()
+        .ok_or_else(|| anyhow!("invalid catalog path"))?;
+    CatalogDb::open(db_path)
+}
+
+fn catalog_info(service: &CatalogService, path: &Path) -> Result<Output> {
+    let images = service.count_images()?;
+    let folders = service.list_folders()?.len();
+    let collections = service.list_collections()?.len();
+    Ok(Output {
+        json: json!({
+            "catalog": path,
+            "images": images,
+            "folders": folders,
+            "collections": collections,
+        }),
+        text: vec![
+            format!("Catalog:     {}", path.display()),
+            format!("Images:      {images}"),
+            format!("Folders:     {folders}"),
+            format!("Collections: {collections}"),
+        ],
+    })
+}
+
+fn import_directory(
+    service: &CatalogService,
+    dir: &Path,
+    keywords: Option<&str>,
+    copy_to: Option<PathBuf>,
+    move_to: Option<PathBuf>,
+    allow_duplicates: bool,
+) -> Result<Output> {
+    if !dir.is_dir() {
+        bail!("{} is not a directory", dir.display());
+    }
+
+    let (method, destination) = match (copy_to, move_to) {
+        (Some(dest), _) => (ImportMethod::Copy, Some(dest)),
+        (None, Some(dest)) => (ImportMethod::Move, Some(dest)),
+        (None, None) => (ImportMethod::Add, None),
+    };
+    let keywords = keywords.map(parse_keywords).unwrap_or_default();
+    let paths = collect_image_paths(dir, &CancellationFlag::default());
+
+    let callbacks = ImportCallbacks {
+        on_error: Some(Arc::new(|path, err| {
+            eprintln!("{}: {err}", path.display());
+        })),
+        duplicate_strategy: if allow_duplicates {
+            DuplicateStrategy::ImportAnyway
+        } else {
+            DuplicateStrategy::Skip
+        },
+        ..ImportCallbacks::default()
+    };
+    let report = import_files(service, &paths, &keywords, method, destination, callbacks)?;
+
+    let failed: Vec<Value> = report
+        .failed
+        .iter()
+        .map(|(path, err)| json!({ "path": path, "error": err }))
+        .collect();
+    Ok(Output::new(
+        json!({
+            "scanned": paths.len(),
+            "imported": report.imported,
+            "duplicates": report.duplicates,
+            "failed": failed,
+            "batch_started_at": report.batch_started_at,
+        }),
+        format!(
+            "Imported {} of {} files ({} duplicates, {} failed)",
+            report.imported,
+            paths.len(),
+            report.duplicates.len(),
+            report.failed.len()
+        ),
+    ))
+}
+
+fn migrate(
+    service: &CatalogService,
+    from: MigrationSource,
+    source: &Path,
+    dry_run: bool,
+) -> Result<Output> {
+    let callbacks = ImportCallbacks {
+        on_error: Some(Arc::new(|path, err| {
+            eprintln!("{}: {err}", path.display());
+        })),
+        ..ImportCallbacks::default()
+    };
+    let from = match from {
+        MigrationSource::Lightroom => ExternalSource::Lightroom,
+        MigrationSource::Darktable => ExternalSource::Darktable,
+        MigrationSource::Digikam => ExternalSource::Digikam,
+    };
+    let report = migrate_external_catalog(service, from, source, &callbacks, dry_run)?;
+
+    let mut text = Vec::new();
+    if dry_run {
+        for change in &report.changes {
+            let kind = match change.kind {
+                ImageChangeKind::New => "new",
+                ImageChangeKind::Offline => "offline",
+                ImageChangeKind::Updated => "update",
+            };
+            text.push(format!("{kind:<8} {}", change.path.display()));
+            text.extend(
+                change
+                    .fields
+                    .iter()
+                    .map(|field| format!("         {field}")),
+            );
+        }
+    }
+    text.push(format!(
+        "{} {} new and {} existing image(s), {} collection(s), {} keyword(s), edits for {} image(s)",
+        if dry_run { "Would migrate" } else { "Migrated" },
+        report.imported,
+        report.updated,
+        report.collections,
+        report.keywords,
+        report.edits
+    ));
+    if !report.offline.is_empty() {
+        text.push(format!(
+            "{} image(s) {} cataloged offline because their files were not found",
+            report.offline.len(),
+            if dry_run { "would be" } else { "were" }
+        ));
+    }
+    if !report.failed.is_empty() {
+        text.push(format!("{} image(s) failed", report.failed.len()));
+    }
+    if !report.unmapped.is_empty() {
+        text.push("Not migrated:".to_string());
+        text.extend(
+            report
+                .unmapped
+                .iter()
+                .map(|(feature, count)| format!("  {feature}: {count}")),
+        );
+    }
+    Ok(Output {
+        json: serde_json::to_value(&report)?,
+        text,
+    })
+}
+
+fn image_list(images: Vec<Image>) -> Output {
+    let text = images
+        .iter()
+        .map(|image| {
+            format!(
+                "{:>6}  {}  {:<8}  {}",
+                image.id,
+                "*".repeat(image.rating.unwrap_or(0).clamp(0, 5) as usize),
+                image.flag.as_deref().unwrap_or("-"),
+                image.original_path
+            )
+        })
+        .collect();
+    Output {
+        json: json!(images),
+        text,
+    }
+}
+
+fn update_images(
+    service: &CatalogService,
+    ids: &[i64],
+    field: &str,
+    value: Value,
+    mut apply: impl FnMut(i64) -> Result<()>,
+) -> Result<Output> {
+    // Updates match by id, so check up front rather than silently touching nothing.
+    for &id in ids {
+        service.load_metadata(id)?;
+    }
+    for &id in ids {
+        apply(id)?;
+    }
+    Ok(Output::new(
+        json!({ "updated": ids, "field": field, "value": value }),
+        format!("Updated {field} on {} image(s)", ids.len()),
+    ))
+}
+
+fn set_keywords(service: &CatalogService, id: i64, raw: &str, add: bool) -> Result<Output> {
+    let mut keywords = parse_keywords(raw);
+    if add {
+        let mut existing = service.load_metadata(id)?.keywords;
+        existing.append(&mut keywords);
+        keywords = existing;
+    }
+    service.update_keywords(id, &keywords)?;
+
+    let mut stored = service.load_metadata(id)?.keywords;
+    stored.sort();
+    Ok(Output::new(
+        json!({ "image_id": id, "keywords": stored }),
+        format!("Keywords for {id}: {}", stored.join(", ")),
+    ))
+}
+
+fn verify_catalog(service: &CatalogService, repair: bool) -> Result<Output> {
+    let report = service.verify_catalog()?;
+    let mut text: Vec<String> = report
+        .findings
+        .iter()
+        .map(|finding| format!("{:?}: {}", finding.kind, finding.message))
+        .collect();
+
+    if !repair || report.is_clean() {
+        text.push(if report.is_clean() {
+            "Catalog verified: no problems found".to_string()
+        } else {
+            format!(
+                "Catalog verified: {} problem(s) found",
+                report.findings.len()
+            )
+        });
+        return Ok(Output {
+            json: serde_json::to_value(&report)?,
+            text,
+        });
+    }
+
+    let summary = service.repair_catalog(&report)?;
+    let after = service.verify_catalog()?;
+    text.push(format!(
+        "Repaired: {} rows removed, {} images relinked, {} JSON values cleared, {} left for manual repair",
+        summary.rows_deleted, summary.images_relinked, summary.json_cleared, summary.skipped
+    ));
+    Ok(Output {
+        json: json!({ "report": report, "repair": summary, "after": after }),
+        text,
+    })
+}
+
+fn merge(service: &CatalogService, args: MergeArgs) -> Result<Output> {
+    let settings = MergeSettings {
+        remaps: args.remap,
+        ratings: args.ratings.into(),
+        keywords: args.keywords.into(),
+        edits: args.edits.into(),
+        collections: args.collections.into(),
+    };
+    let callbacks = ImportCallbacks {
+        on_error: Some(Arc::new(|path, err| {
+            eprintln!("{}: {err}", path.display());
+        })),
+        ..ImportCallbacks::default()
+    };
+    let report = merge_catalog(service, &args.source, &settings, &callbacks)?;
+
+    let mut text = vec![
+        format!(
+            "Added {} image(s); matched {} by path and {} by content",
+            report.added, report.matched_by_path, report.matched_by_hash
+        ),
+        format!(
+            "Updated ratings of {}, keywords of {} and edits of {} image(s)",
+            report.ratings_updated, report.keywords_updated, report.edits_updated
+        ),
+        format!(
+            "Created {} and updated {} collection(s); copied {} thumbnail(s)",
+            report.collections_created, report.collections_updated, report.thumbnails_copied
+        ),
+    ];
+    if !report.failed.is_empty() {
+        text.push(format!("{} image(s) failed", report.failed.len()));
+    }
+    Ok(Output {
+        json: serde_json::to_value(&report)?,
+        text,
+    })
+}
+
+fn export_catalog(service: &CatalogService, args: ExportCatalogArgs) -> Result<Output> {
+    let ids = selected_ids(service, &args.ids, args.query.as_deref(), args.collection)?;
+    let settings = PortableCatalogSettings {
+        copy_originals: args.copy_originals,
+    };
+    let callbacks = ImportCallbacks {
+        on_error: Some(Arc::new(|path, err| {
+            eprintln!("{}: {err}", path.display());
+        })),
+        ..ImportCallbacks::default()
+    };
+    let report = export_portable_catalog(service, &ids, &args.dest, &settings, &callbacks)?;
+
+    let mut text = vec![format!(
+        "Wrote {} image(s) and {} collection(s) to {}",
+        report.images,
+        report.collections,
+        report.catalog.display()
+    )];
+    if let Some(dir) = &report.originals_dir {
+        text.push(format!(
+            "Copied {} original(s) to {}",
+            report.originals_copied,
+            dir.display()
+        ));
+    }
+    if !report.failed.is_empty() {
+        text.push(format!(
+            "{} original(s) could not be copied and keep their current path",
+            report.failed.len()
+        ));
+    }
+    Ok(Output {
+        json: serde_json::to_value(&report)?,
+        text,
+    })
+}
+
+fn parse_remap(raw: &str) -> std::result::Result<PathRemap, String> {
+    let (from, to) = raw
+        .split_once('=')
+        .ok_or_else(|| "expected FROM=TO".to_string())?;
+    Ok(PathRemap {
+        from: PathBuf::from(from),
+        to: PathBuf::from(to),
+    })
+}
+
+fn parse_offset_arg(raw: &str) -> std::result::Result<i64, String> {
+    parse_offset(raw).map_err(|err| err.to_string())
+}
+
+fn parse_time_arg(raw: &str) -> std::result::Result<DateTime<Utc>, String> {
+    parse_time(raw).map_err(|err| err.to_string())
+}
+
+fn parse_zone_arg(raw: &str) -> std::result::Result<i32, String> {
+    parse_zone(raw).map_err(|err| err.to_string())
+}
+
+fn parse_bounds(raw: &str) -> std::result::Result<BoundingBox, String> {
+    let parts = raw
+        .split(',')
+        .map(|v| v.trim().parse::<f64>().map_err(|err| err.to_string()))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    let [south, west, north, east] = parts[..] else {
+        return Err("expected SOUTH,WEST,NORTH,EAST".to_string());
+    };
+    BoundingBox::new(south, west, north, east).map_err(|err| err.to_string())
+}
+
+fn parse_box(raw: &str) -> std::result::Result<(u32, u32), String> {
+    let (w, h) = raw
+        .split_once(['x', 'X'])
+        .ok_or_else(|| "expected WIDTHxHEIGHT".to_string())?;
+    let parse = |v: &str| v.trim().parse::<u32>().map_err(|err| err.to_string());
+    Ok((parse(w)?, parse(h)?))
+}
+
+fn export(service: &CatalogService, args: ExportArgs) -> Result<Output> {
+    let ids = match &args.query {
+        Some(query) => service
+            .search(query)?
+            .iter()
+            .map(|image| image.id)
+            .collect(),
+        None => args.ids.clone(),
+    };
+
+    let preset = match &args.preset {
+        Some(name) => Some(
+            service
+                .find_export_preset(name)?
+                .ok_or_else(|| anyhow!("no export preset named {name}"))?,
+        ),
+        None => None,
+    };
+    let dest = args
+        .dest
+        .clone()
+        .or_else(|| preset.as_ref().and_then(|p| p.destination.clone()))
+        .ok_or_else(|| anyhow!("an export destination is required"))?;
+    let mut settings = preset.map(|p| p.settings).unwrap_or_default();
+    args.settings.apply(&mut settings);
+
+    let callbacks = ExportCallbacks {
+        on_error: Some(Arc::new(|image_id, err| {
+            eprintln!("image {image_id}: {err}");
+        })),
+        ..ExportCallbacks::default()
+    };
+    let report = export_images(
+        service,
+        &ImageEngine::new(),
+        &ids,
+        &dest,
+        &settings,
+        callbacks,
+    )?;
+    for (image_id, warning) in &report.warnings {
+        eprintln!("image {image_id}: {warning}");
+    }
+
+    let text = format!(
+        "Exported {} of {} image(s) to {}",
+        report.exported.len(),
+        ids.len(),
+        dest.display()
+    );
+    if report.exported.is_empty() && !report.failed.is_empty() {
+        bail!("{text}");
+    }
+    Ok(Output::new(serde_json::to_value(&report)?, text))
+}
+
+fn gallery(service: &CatalogService, args: GalleryArgs) -> Result<Output> {
+    let ids = selected_ids(service, &args.ids, args.query.as_deref(), args.collection)?;
+    let settings = GallerySettings {
+        title: args.title,
+        template: match args.template {
+            TemplateArg::Classic => GalleryTemplate::Classic,
+            TemplateArg::Dark => GalleryTemplate::Dark,
+            TemplateArg::Minimal => GalleryTemplate::Minimal,
+        },
+        image_size: args.image_size,
+        thumbnail_size: args.thumbnail_size,
+        quality: args.quality,
+        captions: GalleryCaptions {
+            filename: args.captions.contains(&CaptionArg::Filename),
+            keywords: args.captions.contains(&CaptionArg::Keywords),
+            rating: args.captions.contains(&CaptionArg::Rating),
+        },
+        rights: RightsInfo {
+            creator: args.creator,
+            copyright: args.copyright,
+            contact: None,
+        },
+        watermark: args.watermark,
+        ..GallerySettings::default()
+    };
+    let callbacks = ExportCallbacks {
+        on_error: Some(Arc::new(|image_id, err| {
+            eprintln!("image {image_id}: {err}");
+        })),
+        ..ExportCallbacks::default()
+    };
+    let report = generate_gallery(
+        service,
+        &ImageEngine::new(),
+        &ids,
+        &args.dest,
+        &settings,
+        callbacks,
+    )?;
+    let text = format!(
+        "Wrote gallery with {} image(s) to {}",
+        report.images,
+        report.index.display()
+    );
+    if report.images == 0 {
+        bail!("{text}");
+    }
+    Ok(Output::new(serde_json::to_value(&report)?, text))
+}
+
+fn contact_sheet(service: &CatalogService, args: ContactSheetArgs) -> Result<Output> {
+    let ids = selected_ids(service, &args.ids, args.query.as_deref(), args.collection)?;
+    let settings = ContactSheetSettings {
+        title: args.title,
+        page_size: match args.page_size {
+            PageSizeArg::A4 => PageSize::A4,
+            PageSizeArg::Letter => PageSize::Letter,
+            PageSizeArg::A3 => PageSize::A3,
+            PageSizeArg::Legal => PageSize::Legal,
+        },
+        orientation: if args.landscape {
+            PageOrientation::Landscape
+        } else {
+            PageOrientation::Portrait
+        },
+        columns: args.columns,
+        rows: args.rows,
+        captions: ContactSheetCaptions {
+            filename: args.captions.contains(&SheetCaptionArg::Filename),
+            captured_at: args.captions.contains(&SheetCaptionArg::Date),
+            rating: args.captions.contains(&SheetCaptionArg::Rating),
+            color_label: args.captions.contains(&SheetCaptionArg::Label),
+        },
+        source: if args.render {
+            ContactSheetSource::Renders
+        } else {
+            ContactSheetSource::Thumbnails
+        },
+        ..ContactSheetSettings::default()
+    };
+    let callbacks = ExportCallbacks {
+        on_error: Some(Arc::new(|image_id, err| {
+            eprintln!("image {image_id}: {err}");
+        })),
+        ..ExportCallbacks::default()
+    };
+    let report = write_contact_sheet(
+        service,
+        &ImageEngine::new(),
+        &ids,
+        &args.output,
+        &settings,
+        callbacks,
+    )?;
+    let text = format!(
+        "Wrote {} image(s) on {} page(s) to {}",
+        report.images,
+        report.pages,
+        report.path.display()
+    );
+    Ok(Output::new(serde_json::to_value(&report)?, text))
+}
+
+/// Explicit ids, or the results of a search or collection when one is given.
+fn selected_ids(
+    service: &CatalogService,
+    ids: &[i64],
+    query: Option<&str>,
+    collection: Option<i64>,
+) -> Result<Vec<i64>> {
+    Ok(match (query, collection) {
+        (Some(query), _) => service.search(query)?.iter().map(|i| i.id).collect(),
+        (None, Some(collection)) => service
+            .list_images_in_collection(collection)?
+            .iter()
+            .map(|i| i.id)
+            .collect(),
+        (None, None) => ids.to_vec(),
+    })
+}
+
+impl SettingsArgs {
+    fn apply(&self, settings: &mut ExportSettings) {
+        if let Some(format) = self.format {
+            settings.format = match format {
+                FormatArg::Jpeg => ExportFormat::Jpeg,
+                FormatArg::Png => ExportFormat::Png,
+                FormatArg::Tiff => ExportFormat::Tiff,
+            };
+        }
+        if let Some(quality) = self.quality {
+            settings.quality = quality;
+        }
+        if let Some(bit_depth) = self.bit_depth {
+            settings.bit_depth = match bit_depth {
+                BitDepthArg::Eight => BitDepth::Eight,
+                BitDepthArg::Sixteen => BitDepth::Sixteen,
+            };
+        }
+        match (self.long_edge, self.megapixels, self.fit) {
+            (Some(pixels), _, _) => settings.resize = ResizeMode::LongEdge { pixels },
+            (_, Some(megapixels), _) => settings.resize = ResizeMode::Megapixels { megapixels },
+            (_, _, Some((width, height))) => settings.resize = ResizeMode::FitBox { width, height },
+            _ if self.original_size => settings.resize = ResizeMode::Original,
+            _ => {}
+        }
+        if let Some(template) = &self.template {
+            settings.filename_template = template.clone();
+        }
+        if self.overwrite {
+            settings.overwrite = true;
+        }
+        if let Some(metadata) = self.metadata {
+            settings.metadata = match metadata {
+                MetadataArg::Strip => MetadataPolicy::Strip,
+                MetadataArg::Copyright => MetadataPolicy::CopyrightOnly,
+                MetadataArg::NoLocation => MetadataPolicy::AllExceptLocation,
+                MetadataArg::All => MetadataPolicy::All,
+            };
+        }
+        if let Some(creator) = &self.creator {
+            settings.rights.creator = Some(creator.clone());
+        }
+        if let Some(copyright) = &self.copyright {
+            settings.rights.copyright = Some(copyright.clone());
+        }
+        if let Some(contact) = &self.contact {
+            settings.rights.contact = Some(contact.clone());
+        }
+        match self.sharpen {
+            Some(SharpenArg::None) => settings.sharpening = None,
+            Some(medium) => {
+                let medium = match medium {
+                    SharpenArg::Matte => SharpenMedium::MattePaper,
+                    SharpenArg::Glossy => SharpenMedium::GlossyPaper,
+                    _ => SharpenMedium::Screen,
+                };
+                let sharpening = settings.sharpening.get_or_insert_with(Default::default);
+                sharpening.medium = medium;
+            }
+            None => {}
+        }
+        if let Some(sharpening) = settings.sharpening.as_mut() {
+            if let Some(amount) = self.sharpen_amount {
+                sharpening.amount = match amount {
+                    AmountArg::Low => SharpenAmount::Low,
+                    AmountArg::Standard => SharpenAmount::Standard,
+                    AmountArg::High => SharpenAmount::High,
+                };
+            }
+            if let Some(ppi) = self.print_ppi {
+                sharpening.ppi = ppi;
+            }
+        }
+        if let Some(watermark) = &self.watermark {
+            settings.watermark = Some(watermark.trim().to_string());
+        }
+        if self.no_watermark {
+            settings.watermark = None;
+        }
+    }
+}
+
+fn presets(service: &CatalogService, command: PresetCommand) -> Result<Output> {
+    match command {
+        PresetCommand::List => {
+            let presets = service.list_export_presets()?;
+            let text = presets
+                .iter()
+                .map(|preset| {
+                    let dest = preset
+                        .destination
+                        .as_ref()
+                        .map(|d| format!(" -> {}", d.display()))
+                        .unwrap_or_default();
+                    format!(
+                        "{}: {}{dest}",
+                        preset.name,
+                        describe_settings(&preset.settings)
+                    )
+                })
+                .collect();
+            Ok(Output {
+                json: serde_json::to_value(&presets)?,
+                text,
+            })
+        }
+        PresetCommand::Save {
+            name,
+            dest,
+            settings,
+        } => {
+            let mut preset =
+                service
+                    .find_export_preset(&name)?
+                    .unwrap_or_else(|| NamedExportPreset {
+                        name: name.clone(),
+                        destination: None,
+                        settings: ExportSettings::default(),
+                    });
+            preset.name = name.trim().to_string();
+            if dest.is_some() {
+                preset.destination = dest;
+            }
+            settings.apply(&mut preset.settings);
+            service.save_export_preset(&preset)?;
+            let text = format!("Saved export preset {}", preset.name);
+            Ok(Output::new(serde_json::to_value(&preset)?, text))
+        }
+        PresetCommand::Delete { name } => {
+            if !service.delete_export_preset(&name)? {
+                bail!("no export preset named {name}");
+            }
+            Ok(Output::new(
+                json!({ "deleted": name }),
+                format!("Deleted export preset {name}"),
+            ))
+        }
+        PresetCommand::Import { file } => {
+            let names = service.import_export_presets(&file)?;
+            let text = format!("Imported {} preset(s): {}", names.len(), names.join(", "));
+            Ok(Output::new(json!({ "imported": names }), text))
+        }
+        PresetCommand::Export { file, names } => {
+            let count = service.write_export_presets(&file, &names)?;
+            Ok(Output::new(
+                json!({ "exported": count, "file": file }),
+                format!("Wrote {count} preset(s) to {}", file.display()),
+            ))
+        }
+    }
+}
+
+fn capture_time(service: &CatalogService, command: CaptureTimeCommand) -> Result<Output> {
+    let (adjustment, target) = match command {
+        CaptureTimeCommand::Shift { offset, target } => {
+            (CaptureTimeAdjustment::Shift { seconds: offset }, target)
+        }
+        CaptureTimeCommand::SetFirst { time, target } => {
+            (CaptureTimeAdjustment::SetFirst { time }, target)
+        }
+        CaptureTimeCommand::Zone {
+            camera,
+            actual,
+            target,
+        } => (
+            CaptureTimeAdjustment::TimeZone {
+                camera_minutes: camera,
+                actual_minutes: actual,
+            },
+            target,
+        ),
+        CaptureTimeCommand::Sync { track, target } => {
+            (CaptureTimeAdjustment::SyncToTrack { track }, target)
+        }
+        CaptureTimeCommand::Undo { id } => {
+            let undo = undo_capture_time_correction(service, id)?;
+            let mut text = vec![format!(
+                "Undid \"{}\": restored {} image(s)",
+                undo.correction.description, undo.restored
+            )];
+            if undo.changed_since > 0 {
+                text.push(format!(
+                    "{} image(s) were corrected again since and were left alone",
+                    undo.changed_since
+                ));
+            }
+            return Ok(Output {
+                json: serde_json::to_value(&undo)?,
+                text,
+            });
+        }
+        CaptureTimeCommand::History => {
+            let corrections = list_capture_time_corrections(service)?;
+            let text = corrections
+                .iter()
+                .map(|correction| {
+                    format!(
+                        "{}: {} ({})",
+                        correction.id,
+                        correction.description,
+                        correction.created_at.format("%Y-%m-%d %H:%M")
+                    )
+                })
+                .collect();
+            return Ok(Output {
+                json: serde_json::to_value(&corrections)?,
+                text,
+            });
+        }
+    };
+
+    let ids = selected_ids(
+        service,
+        &target.ids,
+        target.query.as_deref(),
+        target.collection,
+    )?;
+    let settings = CaptureTimeSettings {
+        adjustment,
+        write_sidecars: target.write_sidecars,
+    };
+    let report = correct_capture_times(service, &ids, &settings)?;
+    let mut text = vec![match report.correction_id {
+        Some(id) => format!(
+            "Shifted {} image(s) by {} (correction {id})",
+            report.updated,
+            format_offset(report.offset_seconds)
+        ),
+        None => "No capture times changed".to_string(),
+    }];
+    if report.sidecars_written > 0 {
+        text.push(format!("Wrote {} sidecar(s)", report.sidecars_written));
+    }
+    if !report.skipped.is_empty() {
+        text.push(format!(
+            "Skipped {} image(s) without a capture time",
+            report.skipped.len()
+        ));
+    }
+    for (path, err) in &report.failed {
+        text.push(format!("{}: {err}", path.display()));
+    }
+    Ok(Output {
+        json: serde_json::to_value(&report)?,
+        text,
+    })
+}
+
+fn geotag(service: &CatalogService, command: GeotagCommand) -> Result<Output> {
+    match command {
+        GeotagCommand::Apply {
+            track,
+            ids,
+            query,
+            collection,
+            offset,
+            max_gap,
+            overwrite,
+            dry_run,
+        } => {
+            let ids = selected_ids(service, &ids, query.as_deref(), collection)?;
+            let gpx = read_gpx(&track)?;
+            let settings = GeotagSettings {
+                clock_offset_seconds: offset,
+                max_gap_seconds: max_gap,
+                overwrite,
+            };
+            let preview = preview_geotags(service, &gpx, &ids, &settings)?;
+            let mut text = Vec::new();
+            for matched in &preview.matched {
+                text.push(format!(
+                    "{}: {:.6}, {:.6}",
+                    matched.path.display(),
+                    matched.latitude,
+                    matched.longitude
+                ));
+            }
+            for miss in &preview.unmatched {
+                text.push(format!("{}: {}", miss.path.display(), miss.reason.label()));
+            }
+            let batch_id = if dry_run {
+                None
+            } else {
+                let name = track
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| track.display().to_string());
+                let description =
+                    format!("Geotagged {} image(s) from {name}", preview.matched.len());
+                apply_geotags(service, &preview, &description)?
+            };
+            text.push(match batch_id {
+                Some(id) => format!(
+                    "Geotagged {} image(s), {} unmatched (run {id})",
+                    preview.matched.len(),
+                    preview.unmatched.len()
+                ),
+                None if dry_run => format!(
+                    "Would geotag {} image(s), {} unmatched",
+                    preview.matched.len(),
+                    preview.unmatched.len()
+                ),
+                None => "No images matched the track".to_string(),
+            });
+            Ok(Output {
+                json: json!({ "batch_id": batch_id, "preview": preview }),
+                text,
+            })
+        }
+        GeotagCommand::Place {
+            latitude,
+            longitude,
+            ids,
+            query,
+            collection,
+        } => {
+            let ids = selected_ids(service, &ids, query.as_deref(), collection)?;
+            let description = format!(
+                "Placed {} image(s) at {latitude:.6}, {longitude:.6}",
+                ids.len()
+            );
+            let batch_id = place_images(service, &ids, latitude, longitude, &description)?;
+            let text = vec![match batch_id {
+                Some(id) => format!("{description} (run {id})"),
+                None => "No images to place".to_string(),
+            }];
+            Ok(Output {
+                json: json!({ "batch_id": batch_id, "placed": ids }),
+                text,
+            })
+        }
+        GeotagCommand::Undo { id } => {
+            let undo = undo_geotags(service, id)?;
+            let mut text = vec![format!(
+                "Undid \"{}\": restored {} image(s)",
+                undo.batch.description, undo.restored
+            )];
+            if undo.changed_since > 0 {
+                text.push(format!(
+                    "{} image(s) were moved again since and were left alone",
+                    undo.changed_since
+                ));
+            }
+            Ok(Output {
+                json: serde_json::to_value(&undo)?,
+                text,
+            })
+        }
+        GeotagCommand::History => {
+            let batches = list_geotag_batches(service)?;
+            let text = batches
+                .iter()
+                .map(|batch| {
+                    format!(
+                        "{}: {} ({})",
+                        batch.id,
+                        batch.description,
+                        batch.created_at.format("%Y-%m-%d %H:%M")
+                    )
+                })
+                .collect();
+            Ok(Output {
+                json: serde_json::to_value(&batches)?,
+                text,
+            })
+        }
+    }
+}
+
+fn locate(service: &CatalogService, args: LocateArgs) -> Result<Output> {
+    let ids = selected_ids(service, &args.ids, args.query.as_deref(), args.collection)?;
+    let settings = GeocodeSettings {
+        max_distance_km: args.max_distance,
+        add_keywords: !args.no_keywords,
+        overwrite: args.overwrite,
+    };
+    let report = geocode_images(service, &ids, &settings)?;
+    let mut text: Vec<String> = report
+        .located
+        .iter()
+        .map(|location| {
+            format!(
+                "{}: {} ({:.1} km)",
+                location.image_id,
+                location.label(),
+                location.distance_km
+            )
+        })
+        .collect();
+    text.push(format!(
+        "Located {} image(s), added {} keyword(s)",
+        report.located.len(),
+        report.keywords_added
+    ));
+    if report.already_located > 0 {
+        text.push(format!(
+            "{} image(s) already had a location",
+            report.already_located
+        ));
+    }
+    if report.without_position > 0 {
+        text.push(format!(
+            "{} image(s) have no GPS position",
+            report.without_position
+        ));
+    }
+    if report.too_far > 0 {
+        text.push(format!(
+            "{} image(s) are more than {} km from a known place",
+            report.too_far, args.max_distance
+        ));
+    }
+    Ok(Output {
+        json: serde_json::to_value(&report)?,
+        text,
+    })
+}
+
+fn geo(service: &CatalogService, command: GeoCommand) -> Result<Output> {
+    match command {
+        GeoCommand::Near {
+            latitude,
+            longitude,
+            radius,
+        } => {
+            let found = service.images_near(latitude, longitude, radius)?;
+            let text = found
+                .iter()
+                .map(|(image, km)| {
+                    format!("{:>6}  {:>8.2} km  {}", image.id, km, image.original_path)
+                })
+                .collect();
+            let json = found
+                .iter()
+                .map(|(image, km)| json!({ "distance_km": km, "image": image }))
+                .collect();
+            Ok(Output {
+                json: Value::Array(json),
+                text,
+            })
+        }
+        GeoCommand::Within { bounds } => Ok(image_list(service.images_in_bounds(&bounds)?)),
+        GeoCommand::Clusters { zoom, bounds } => {
+            let clusters = service.cluster_images(&bounds.unwrap_or(BoundingBox::WORLD), zoom)?;
+            let text = clusters
+                .iter()
+                .map(|cluster| {
+                    format!(
+                        "{:>9.4},{:>10.4}  {:>6} image(s)  e.g. {}",
+                        cluster.latitude, cluster.longitude, cluster.count, cluster.image_id
+                    )
+                })
+                .collect();
+            Ok(Output {
+                json: serde_json::to_value(&clusters)?,
+                text,
+            })
+        }
+    }
+}
+
+fn dates(service: &CatalogService, period: Option<DatePeriod>, images: bool) -> Result<Output> {
+    let years = service.date_hierarchy()?;
+    let rows: Vec<(String, usize)> = match period {
+        Some(period @ DatePeriod::Day(_)) => {
+            return Ok(image_list(service.list_images_taken_in(&period)?))
+        }
+        Some(period) if images => return Ok(image_list(service.list_images_taken_in(&period)?)),
+        None => years
+            .iter()
+            .map(|year| (year.year.to_string(), year.count))
+            .collect(),
+        Some(DatePeriod::Year(wanted)) => years
+            .iter()
+            .filter(|year| year.year == wanted)
+            .flat_map(|year| &year.months)
+            .map(|month| (format!("{wanted:04}-{:02}", month.month), month.count))
+            .collect(),
+        Some(DatePeriod::Month(wanted_year, wanted_month)) => years
+            .iter()
+            .filter(|year| year.year == wanted_year)
+            .flat_map(|year| &year.months)
+            .filter(|month| month.month == wanted_month)
+            .flat_map(|month| &month.days)
+            .map(|day| (day.date.to_string(), day.count))
+            .collect(),
+    };
+    let text = rows
+        .iter()
+        .map(|(period, count)| format!("{period:<10}  {count:>6} image(s)"))
+        .collect();
+    let json = rows
+        .iter()
+        .map(|(period, count)| json!({ "period": period, "count": count }))
+        .collect();
+    Ok(Output {
+        json: Value::Array(json),
+        text,
+    })
+}
+
+fn roots(service: &CatalogService, command: RootCommand) -> Result<Output> {
+    match command {
+        RootCommand::List => {
+            let roots = service.list_roots()?;
+            let text = roots
+                .iter()
+                .map(|summary| {
+                    format!(
+                        "{}: {} ({} folder(s), {} image(s)){}",
+                        summary.root.name,
+                        summary.root.path,
+                        summary.folders,
+                        summary.images,
+                        if summary.online { "" } else { " [offline]" }
+                    )
+                })
+                .collect();
+            Ok(Output {
+                json: serde_json::to_value(&roots)?,
+                text,
+            })
+        }
+        RootCommand::Add { path, name } => {
+            let path = fs::canonicalize(&path)
+                .with_context(|| format!("failed to resolve {}", path.display()))?;
+            let name = match name {
+                Some(name) => name,
+                None => service.suggest_root_name(&path)?,
+            };
+            let root = service.add_root(&name, &path)?;
+            let text = format!("Added root {} at {}", root.name, root.path);
+            Ok(Output::new(serde_json::to_value(&root)?, text))
+        }
+        RootCommand::Rename { root, name } => {
+            let root = service.find_root(&root)?;
+            let renamed = service.rename_root(root.id, &name)?;
+            let text = format!("Renamed root {} to {}", root.name, renamed.name);
+            Ok(Output::new(serde_json::to_value(&renamed)?, text))
+        }
+        RootCommand::Relocate { root, path } => {
+            let root = service.find_root(&root)?;
+            // The new location may not be mounted yet, so only resolve it when it is.
+            let path = fs::canonicalize(&path).unwrap_or(path);
+            let relocation = service.relocate_root(root.id, &path)?;
+            let mut text = format!(
+                "Relocated root {} to {}: {} folder(s), {} image(s)",
+                root.name,
+                path.display(),
+                relocation.folders,
+                relocation.images
+            );
+            if relocation.missing > 0 {
+                text.push_str(&format!("; {} file(s) not found there", relocation.missing));
+            }
+            Ok(Output::new(serde_json::to_value(&relocation)?, text))
+        }
+    }
+}
+
+fn watermarks(service: &CatalogService, command: WatermarkCommand) -> Result<Output> {
+    match command {
+        WatermarkCommand::List => {
+            let watermarks = service.list_watermarks()?;
+            let text = watermarks
+                .iter()
+                .map(|watermark| format!("{}: {}", watermark.name, describe_watermark(watermark)))
+                .collect();
+            Ok(Output {
+                json: serde_json::to_value(&watermarks)?,
+                text,
+            })
+        }
+        WatermarkCommand::Save {
+            name,
+            text,
+            logo,
+            color,
+            anchor,
+            size,
+            opacity,
+            margin,
+        } => {
+            let mut watermark = match service.find_watermark(&name)? {
+                Some(existing) => existing,
+                None => match (&text, &logo) {
+                    (None, None) => bail!("a new watermark needs --text or --logo"),
+                    _ => WatermarkDefinition::text(name.clone(), ""),
+                },
+            };
+            watermark.name = name.trim().to_string();
+            if let Some(text) = text {
+                let color = match &watermark.kind {
+                    WatermarkKind::Text { color, .. } => color.clone(),
+                    WatermarkKind::Logo { .. } => "#ffffff".to_string(),
+                };
+                watermark.kind = WatermarkKind::Text { text, color };
+            }
+            if let Some(logo) = logo {
+                let path = fs::canonicalize(&logo)
+                    .with_context(|| format!("logo {} not found", logo.display()))?;
+                watermark.kind = WatermarkKind::Logo { path };
+            }
+            if let Some(new_color) = color {
+                match &mut watermark.kind {
+                    WatermarkKind::Text { color, .. } => *color = new_color,
+                    WatermarkKind::Logo { .. } => bail!("--color only applies to text watermarks"),
+                }
+            }
+            if let Some(anchor) = anchor {
+                watermark.anchor = WatermarkAnchor::ALL[anchor as usize];
+            }
+            if let Some(size) = size {
+                watermark.size = size;
+            }
+            if let Some(opacity) = opacity {
+                watermark.opacity = opacity;
+            }
+            if let Some(margin) = margin {
+                watermark.margin = margin;
+            }
+            service.save_watermark(&watermark)?;
+            let text = format!("Saved watermark {}", watermark.name);
+            Ok(Output::new(serde_json::to_value(&watermark)?, text))
+        }
+        WatermarkCommand::Delete { name } => {
+            if !service.delete_watermark(&name)? {
+                bail!("no watermark named {name}");
+            }
+            Ok(Output::new(
+                json!({ "deleted": name }),
+                format!("Deleted watermark {name}"),
+            ))
+        }
+    }
+}
+
+fn describe_watermark(watermark: &WatermarkDefinition) -> String {
+    let content = match &watermark.kind {
+        WatermarkKind::Text { text, color } => format!("text {text:?} in {color}"),
+        WatermarkKind::Logo { path } => format!("logo {}", path.display()),
+    };
+    format!(
+        "{content}, {}, {}% size, {}% opacity, {}% margin",
+        watermark.anchor.label(),
+        watermark.size,
+        watermark.opacity,
+        watermark.margin
+    )
+}
+
+fn describe_settings(settings: &ExportSettings) -> String {
+    let format = match settings.format {
+        ExportFormat::Jpeg => format!("JPEG q{}", settings.quality),
+        ExportFormat::Png => "PNG".to_string(),
+        ExportFormat::Tiff => "TIFF".to_string(),
+    };
+    let depth = match (settings.format, settings.bit_depth) {
+        (ExportFormat::Jpeg, _) | (_, BitDepth::Eight) => "",
+        (_, BitDepth::Sixteen) => " 16-bit",
+    };
+    let size = match settings.resize {
+        ResizeMode::Original => "original size".to_string(),
+        ResizeMode::LongEdge { pixels } => format!("long edge {pixels}px"),
+        ResizeMode::Megapixels { megapixels } => format!("{megapixels} MP"),
+        ResizeMode::FitBox { width, height } => format!("fit {width}x{height}"),
+    };
+    let metadata = match settings.metadata {
+        MetadataPolicy::Strip => "no metadata",
+        MetadataPolicy::CopyrightOnly => "copyright only",
+        MetadataPolicy::AllExceptLocation => "metadata without location",
+        MetadataPolicy::All => "all metadata",
+    };
+    let sharpening = match settings.sharpening {
+        None => String::new(),
+        Some(sharpening) => {
+            let amount = match sharpening.amount {
+                SharpenAmount::Low => "low",
+                SharpenAmount::Standard => "standard",
+                SharpenAmount::High => "high",
+            };
+            match sharpening.medium {
+                SharpenMedium::Screen => format!(", {amount} screen sharpening"),
+                SharpenMedium::MattePaper => {
+                    format!(", {amount} matte sharpening at {} ppi", sharpening.ppi)
+                }
+                SharpenMedium::GlossyPaper => {
+                    format!(", {amount} glossy sharpening at {} ppi", sharpening.ppi)
+                }
+            }
+        }
+    };
+    let watermark = settings
+        .watermark
+        .as_ref()
+        .map(|name| format!(", watermark {name}"))
+        .unwrap_or_default();
+    format!(
+        "{format}{depth}, {size}{sharpening}, {metadata}{watermark}, template {}",
+        settings.filename_template
+    )
+}
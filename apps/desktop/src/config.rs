@@ -1,3 +1,4 @@
+use catalog::services::{BackupSettings, ImageSort};
 use catalog::CatalogPath;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -25,6 +26,11 @@ pub struct FolioLastSelection {
     pub kind: String,
     #[serde(default)]
     pub folder_path: Option<PathBuf>,
+    /// The `YYYY`, `YYYY-MM` or `YYYY-MM-DD` timeline period, for the "date" kind.
+    #[serde(default)]
+    pub date: Option<String>,
+    #[serde(default)]
+    pub collection_id: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,6 +41,12 @@ pub struct AppConfig {
     pub folio_last_selection: Option<FolioLastSelection>,
     #[serde(default)]
     pub last_import_timestamps: HashMap<PathBuf, String>,
+    #[serde(default)]
+    pub backup: BackupSettings,
+    /// The Folio grid's sort order for each selection that has been sorted, keyed by
+    /// catalog and then like `folder:/photos/2024` or `collection:3`.
+    #[serde(default)]
+    pub catalog_folio_sorts: HashMap<PathBuf, HashMap<String, ImageSort>>,
 }
 
 impl Default for AppConfig {
@@ -44,6 +56,8 @@ impl Default for AppConfig {
             last_catalog: None,
             folio_last_selection: None,
             last_import_timestamps: HashMap::new(),
+            backup: BackupSettings::default(),
+            catalog_folio_sorts: HashMap::new(),
         }
     }
 }
@@ -128,6 +142,33 @@ impl ConfigStore {
             .clone()
     }
 
+    pub fn set_folio_sort(
+        &self,
+        catalog: impl AsRef<Path>,
+        scope: &str,
+        sort: ImageSort,
+    ) -> Result<AppConfig> {
+        let normalized = CatalogPath::new(catalog).into_path();
+        self.update(|cfg| {
+            cfg.catalog_folio_sorts
+                .entry(normalized.clone())
+                .or_default()
+                .insert(scope.to_string(), sort)
+                != Some(sort)
+        })
+    }
+
+    pub fn folio_sort(&self, catalog: impl AsRef<Path>, scope: &str) -> Option<ImageSort> {
+        let normalized = CatalogPath::new(catalog).into_path();
+        self.inner
+            .lock()
+            .expect("config poisoned")
+            .catalog_folio_sorts
+            .get(&normalized)?
+            .get(scope)
+            .copied()
+    }
+
     pub fn record_last_import(
         &self,
         catalog: impl AsRef<Path>,
@@ -151,6 +192,14 @@ impl ConfigStore {
             .cloned()
     }
 
+    pub fn backup_settings(&self) -> BackupSettings {
+        self.inner
+            .lock()
+            .expect("config poisoned")
+            .backup
+            .clone()
+    }
+
     fn update<F>(&self, mut fun: F) -> Result<AppConfig>
     where
         F: FnMut(&mut AppConfig) -> bool,
@@ -0,0 +1,271 @@
+//! The model behind the Folio grid.
+//!
+//! Rows hold everything a thumbnail card shows except the picture itself. Pictures are
+//! decoded only when the grid reports their rows on screen, and the most recently shown
+//! ones are kept in a [`LruCache`]; rows whose picture has been dropped show the
+//! placeholder again, so memory stays bounded however far the grid is scrolled.
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::ops::Range;
+
+use slint::{Model, ModelNotify, ModelTracker};
+
+use crate::ThumbnailItem;
+
+/// How many decoded thumbnails the grid keeps, a few screens' worth.
+const THUMBNAIL_CACHE_SIZE: usize = 600;
+
+/// A map that forgets its least recently used entries beyond `capacity`.
+pub struct LruCache<K, V> {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<K, (V, u64)>,
+}
+
+impl<K: Copy + Eq + Hash, V> LruCache<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            tick: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// The value for `key`, marking it as just used.
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        self.tick += 1;
+        let tick = self.tick;
+        self.entries.get_mut(key).map(|(value, used)| {
+            *used = tick;
+            &*value
+        })
+    }
+
+    /// The value for `key`, leaving its age alone.
+    pub fn peek(&self, key: &K) -> Option<&V> {
+        self.entries.get(key).map(|(value, _)| value)
+    }
+
+    /// Store `value` under `key` and return the keys dropped to make room.
+    pub fn insert(&mut self, key: K, value: V) -> Vec<K> {
+        self.tick += 1;
+        self.entries.insert(key, (value, self.tick));
+        let mut evicted = Vec::new();
+        while self.entries.len() > self.capacity {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| *key)
+            else {
+                break;
+            };
+            self.entries.remove(&oldest);
+            evicted.push(oldest);
+        }
+        evicted
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Rows don't hold on to pictures; the cache decides which stay in memory.
+fn without_thumbnail(item: ThumbnailItem) -> ThumbnailItem {
+    ThumbnailItem {
+        display_thumb: slint::Image::default(),
+        ..item
+    }
+}
+
+pub struct FolioModel {
+    rows: RefCell<Vec<ThumbnailItem>>,
+    /// Row of each image id, to refresh the rows a cache change affects.
+    row_of: RefCell<HashMap<i32, usize>>,
+    thumbnails: RefCell<LruCache<i32, slint::Image>>,
+    placeholder: slint::Image,
+    notify: ModelNotify,
+}
+
+impl FolioModel {
+    pub fn new(placeholder: slint::Image) -> Self {
+        Self {
+            rows: RefCell::new(Vec::new()),
+            row_of: RefCell::new(HashMap::new()),
+            thumbnails: RefCell::new(LruCache::new(THUMBNAIL_CACHE_SIZE)),
+            placeholder,
+            notify: ModelNotify::default(),
+        }
+    }
+
+    /// Replace every row. Decoded thumbnails are kept, since a reload usually shows many
+    /// of the same images again.
+    pub fn set_vec(&self, items: Vec<ThumbnailItem>) {
+        let items: Vec<ThumbnailItem> = items.into_iter().map(without_thumbnail).collect();
+        *self.row_of.borrow_mut() = items
+            .iter()
+            .enumerate()
+            .map(|(row, item)| (item.id, row))
+            .collect();
+        *self.rows.borrow_mut() = items;
+        self.notify.reset();
+    }
+
+    pub fn append(&self, items: Vec<ThumbnailItem>) {
+        if items.is_empty() {
+            return;
+        }
+        let start = self.rows.borrow().len();
+        let count = items.len();
+        {
+            let mut row_of = self.row_of.borrow_mut();
+            for (offset, item) in items.iter().enumerate() {
+                row_of.insert(item.id, start + offset);
+            }
+        }
+        self.rows
+            .borrow_mut()
+            .extend(items.into_iter().map(without_thumbnail));
+        self.notify.row_added(start, count);
+    }
+
+    /// Put `item` in place of the row at `row`, which may show another image.
+    pub fn replace_row(&self, row: usize, item: ThumbnailItem) {
+        {
+            let mut rows = self.rows.borrow_mut();
+            let Some(slot) = rows.get_mut(row) else {
+                return;
+            };
+            let mut row_of = self.row_of.borrow_mut();
+            row_of.remove(&slot.id);
+            row_of.insert(item.id, row);
+            *slot = without_thumbnail(item);
+        }
+        self.notify.row_changed(row);
+    }
+
+    /// Use `image` as the thumbnail of `image_id`, e.g. after it was regenerated.
+    pub fn set_thumbnail(&self, image_id: i32, image: slint::Image) {
+        let evicted = self.thumbnails.borrow_mut().insert(image_id, image);
+        self.refresh_ids(std::iter::once(image_id).chain(evicted));
+    }
+
+    /// Make sure the thumbnails of `rows` are decoded, using `load` with each missing
+    /// image's id and path.
+    pub fn decode_rows(
+        &self,
+        rows: Range<usize>,
+        mut load: impl FnMut(i64, &str) -> Option<slint::Image>,
+    ) {
+        let wanted: Vec<(i32, String)> = {
+            let items = self.rows.borrow();
+            let end = rows.end.min(items.len());
+            items[rows.start.min(end)..end]
+                .iter()
+                .map(|item| (item.id, item.path.to_string()))
+                .collect()
+        };
+        let mut changed = Vec::new();
+        for (id, path) in wanted {
+            if self.thumbnails.borrow_mut().get(&id).is_some() {
+                continue;
+            }
+            let image = load(id as i64, &path).unwrap_or_else(|| self.placeholder.clone());
+            changed.push(id);
+            changed.extend(self.thumbnails.borrow_mut().insert(id, image));
+        }
+        self.refresh_ids(changed);
+    }
+
+    /// Drop every decoded thumbnail, e.g. when another catalog is opened.
+    pub fn clear_thumbnails(&self) {
+        self.thumbnails.borrow_mut().clear();
+        self.notify.reset();
+    }
+
+    fn refresh_ids(&self, ids: impl IntoIterator<Item = i32>) {
+        let rows: Vec<usize> = {
+            let row_of = self.row_of.borrow();
+            ids.into_iter()
+                .filter_map(|id| row_of.get(&id).copied())
+                .collect()
+        };
+        for row in rows {
+            self.notify.row_changed(row);
+        }
+    }
+}
+
+impl Model for FolioModel {
+    type Data = ThumbnailItem;
+
+    fn row_count(&self) -> usize {
+        self.rows.borrow().len()
+    }
+
+    fn row_data(&self, row: usize) -> Option<ThumbnailItem> {
+        let mut item = self.rows.borrow().get(row).cloned()?;
+        item.display_thumb = self
+            .thumbnails
+            .borrow()
+            .peek(&item.id)
+            .cloned()
+            .unwrap_or_else(|| self.placeholder.clone());
+        Some(item)
+    }
+
+    /// Update a row's details; its thumbnail comes from the cache, not from `data`.
+    fn set_row_data(&self, row: usize, data: ThumbnailItem) {
+        self.replace_row(row, data);
+    }
+
+    fn model_tracker(&self) -> &dyn ModelTracker {
+        &self.notify
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = LruCache::new(3);
+        for key in 1..=3 {
+            assert!(cache.insert(key, key * 10).is_empty());
+        }
+        assert_eq!(cache.get(&1), Some(&10));
+        assert_eq!(cache.insert(4, 40), [2]);
+        assert_eq!(cache.peek(&2), None);
+
+        // Peeking doesn't count as a use.
+        assert_eq!(cache.peek(&3), Some(&30));
+        assert_eq!(cache.insert(5, 50), [3]);
+        assert_eq!(cache.insert(1, 11), Vec::<i32>::new());
+        assert_eq!(cache.get(&1), Some(&11));
+        assert_eq!(cache.insert(6, 60), [4]);
+    }
+
+    #[test]
+    fn setting_row_data_reindexes_the_row() {
+        let item = |id| ThumbnailItem {
+            id,
+            ..ThumbnailItem::default()
+        };
+        let model = FolioModel::new(slint::Image::default());
+        model.set_vec(vec![item(1), item(2)]);
+        model.set_row_data(0, item(7));
+        let row_of = model.row_of.borrow();
+        assert_eq!(row_of.get(&7), Some(&0));
+        assert_eq!(row_of.get(&1), None);
+        assert_eq!(row_of.get(&2), Some(&1));
+    }
+}
@@ -1,21 +1,17 @@
-use std::fs;
 use std::path::{Path, PathBuf};
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc,
-};
+use std::sync::Arc;
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::Result;
+use catalog::services::import::{collect_image_paths, import_files};
 use catalog::services::CatalogService;
-use chrono::{DateTime, Utc};
 use slint::{Image as SlintImage, Rgba8Pixel, SharedPixelBuffer};
-use walkdir::WalkDir;
 
-const THUMBNAIL_MAX_DIM: u32 = 256;
+pub use catalog::services::import::{
+    is_already_imported, parse_keywords, CancellationFlag, DuplicateStrategy, ImportCallbacks,
+    ImportMethod, ImportProgress, ImportReport, ImportStage,
+};
 
-const SUPPORTED_EXTENSIONS: &[&str] = &[
-    "jpg", "jpeg", "png", "tiff", "tif", "jxl", "heif", "heic", "dng", "cr2", "nef", "raf", "arw",
-];
+const THUMBNAIL_MAX_DIM: u32 = 256;
 
 #[derive(Clone)]
 pub struct ImportCandidate {
@@ -23,104 +19,12 @@ pub struct ImportCandidate {
     pub thumb: Option<SlintImage>,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum ImportMethod {
-    Add,
-    Copy,
-    Move,
-}
-
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum DuplicateStrategy {
-    Skip,
-    ImportAnyway,
-}
-
-impl Default for DuplicateStrategy {
-    fn default() -> Self {
-        Self::Skip
-    }
-}
-
-#[derive(Clone, Default)]
-pub struct ImportCallbacks {
-    pub progress: Option<Arc<dyn Fn(ImportProgress)>>,
-    pub on_error: Option<Arc<dyn Fn(PathBuf, String)>>,
-    pub duplicate_strategy: DuplicateStrategy,
-    pub cancel: CancellationFlag,
-}
-
-impl ImportCallbacks {
-    fn emit_progress(
-        &self,
-        stage: ImportStage,
-        completed: usize,
-        total: usize,
-        message: impl Into<Option<String>>,
-    ) {
-        if let Some(cb) = &self.progress {
-            cb(ImportProgress {
-                stage,
-                completed,
-                total,
-                message: message.into(),
-            });
-        }
-    }
-
-    fn emit_error(&self, path: PathBuf, err: impl Into<String>) {
-        if let Some(cb) = &self.on_error {
-            cb(path, err.into());
-        }
-    }
-}
-
 #[derive(Clone, Default)]
 pub struct ScanOptions {
     pub on_candidate: Option<Arc<dyn Fn(ImportCandidate)>>,
     pub cancel: CancellationFlag,
 }
 
-#[derive(Clone, Debug, Default)]
-pub struct ImportReport {
-    pub imported: usize,
-    pub duplicates: Vec<PathBuf>,
-    pub failed: Vec<(PathBuf, String)>,
-    pub canceled: bool,
-    pub batch_started_at: Option<DateTime<Utc>>,
-}
-
-#[derive(Clone, Debug)]
-pub struct ImportProgress {
-    pub stage: ImportStage,
-    pub completed: usize,
-    pub total: usize,
-    pub message: Option<String>,
-}
-
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum ImportStage {
-    Scanning,
-    Copying,
-    Moving,
-    Cataloging,
-    Thumbnailing,
-    Keywords,
-}
-
-#[derive(Clone, Default, Debug)]
-pub struct CancellationFlag(Arc<AtomicBool>);
-
-impl CancellationFlag {
-    pub fn cancel(&self) {
-        self.0.store(true, Ordering::SeqCst);
-    }
-
-    pub fn is_canceled(&self) -> bool {
-        self.0.load(Ordering::SeqCst)
-    }
-}
-
 #[cfg(test)]
 pub async fn scan_directory(path: &Path) -> Result<Vec<ImportCandidate>> {
     scan_directory_with_options(path, ScanOptions::default()).await
@@ -135,31 +39,14 @@ pub async fn scan_directory_with_options(
 
 fn scan_directory_blocking(path: &Path, options: ScanOptions) -> Result<Vec<ImportCandidate>> {
     let mut out = Vec::new();
-    for entry in WalkDir::new(path).into_iter().filter_map(|e| e.ok()) {
+    for file_path in collect_image_paths(path, &options.cancel) {
         if options.cancel.is_canceled() {
             break;
         }
 
-        if !entry.file_type().is_file() {
-            continue;
-        }
-
-        let Some(ext) = entry
-            .path()
-            .extension()
-            .and_then(|s| s.to_str())
-            .map(|s| s.to_ascii_lowercase())
-        else {
-            continue;
-        };
-
-        if !is_supported_extension(&ext) {
-            continue;
-        }
-
-        let thumb = decode_thumbnail(entry.path());
+        let thumb = decode_thumbnail(&file_path);
         let candidate = ImportCandidate {
-            path: entry.path().to_path_buf(),
+            path: file_path,
             thumb,
         };
 
@@ -181,187 +68,14 @@ pub async fn import_images_with_callbacks(
     destination: Option<PathBuf>,
     callbacks: ImportCallbacks,
 ) -> Result<ImportReport> {
-    let mut report = ImportReport::default();
-    let batch_started_at = Utc::now();
-    let normalized_keywords = normalize_keywords(keywords);
-    let dest_dir = match method {
-        ImportMethod::Add => None,
-        ImportMethod::Copy | ImportMethod::Move => Some(
-            destination
-                .as_ref()
-                .map(PathBuf::from)
-                .ok_or_else(|| anyhow!("destination directory is required for copy or move"))?,
-        ),
-    };
-
-    if let Some(dest_dir) = dest_dir.as_ref() {
-        fs::create_dir_all(dest_dir)
-            .with_context(|| format!("failed to create destination {}", dest_dir.display()))?;
-    }
-
-    let total = file_paths.len();
-    for (idx, src) in file_paths.iter().enumerate() {
-        if callbacks.cancel.is_canceled() {
-            report.canceled = true;
-            break;
-        }
-
-        callbacks.emit_progress(
-            ImportStage::Scanning,
-            idx,
-            total,
-            format!("Hashing {}", src.display()),
-        );
-
-        let hash = CatalogService::compute_file_hash(src)
-            .with_context(|| format!("failed to hash file {}", src.display()))?;
-
-        if let Some(existing) = service.find_image_by_hash(&hash)? {
-            report.duplicates.push(src.clone());
-            callbacks.emit_error(
-                src.clone(),
-                format!("duplicate detected (matches image id={})", existing.id),
-            );
-
-            if callbacks.duplicate_strategy == DuplicateStrategy::Skip {
-                continue;
-            }
-        }
-
-        let target_path = match method {
-            ImportMethod::Add => src.to_path_buf(),
-            ImportMethod::Copy | ImportMethod::Move => {
-                let dest_dir = dest_dir.as_ref().expect("validated destination");
-                callbacks.emit_progress(
-                    ImportStage::Copying,
-                    idx,
-                    total,
-                    format!("Copying {}", src.display()),
-                );
-                copy_into_destination(src, dest_dir)?
-            }
-        };
-
-        if let Some(existing) = service.find_image_by_original_path(&target_path)? {
-            report.duplicates.push(target_path.clone());
-            callbacks.emit_error(
-                target_path.clone(),
-                format!("already imported as image id={}", existing.id),
-            );
-            continue;
-        }
-
-        callbacks.emit_progress(
-            ImportStage::Cataloging,
-            idx,
-            total,
-            format!("Cataloging {}", target_path.display()),
-        );
-
-        let image = match service.import_image_at(&target_path, batch_started_at) {
-            Ok(img) => img,
-            Err(err) => {
-                callbacks.emit_error(target_path.clone(), err.to_string());
-                report
-                    .failed
-                    .push((target_path.clone(), format!("import failed: {err}")));
-                if matches!(method, ImportMethod::Copy | ImportMethod::Move) {
-                    let _ = fs::remove_file(&target_path);
-                }
-                continue;
-            }
-        };
-
-        callbacks.emit_progress(
-            ImportStage::Thumbnailing,
-            idx,
-            total,
-            format!("Generating thumbnail for {}", target_path.display()),
-        );
-        if let Err(err) = service.generate_thumbnail(image.id, &target_path) {
-            callbacks.emit_error(target_path.clone(), format!("thumbnail failed: {err}"));
-        }
-
-        callbacks.emit_progress(
-            ImportStage::Keywords,
-            idx,
-            total,
-            Some("Applying keywords".to_string()),
-        );
-        for kw in &normalized_keywords {
-            if let Err(err) = service.add_keyword_to_image(image.id, kw) {
-                callbacks.emit_error(target_path.clone(), format!("keyword '{kw}' failed: {err}"));
-            }
-        }
-
-        if method == ImportMethod::Move {
-            callbacks.emit_progress(
-                ImportStage::Moving,
-                idx,
-                total,
-                format!("Removing {}", src.display()),
-            );
-            if let Err(err) = fs::remove_file(src) {
-                callbacks.emit_error(src.clone(), format!("failed to remove source: {err}"));
-            }
-        }
-
-        report.imported += 1;
-        report.batch_started_at.get_or_insert(batch_started_at);
-    }
-
-    Ok(report)
-}
-
-pub fn parse_keywords(raw: &str) -> Vec<String> {
-    raw.split(',')
-        .flat_map(|chunk| chunk.split('\n'))
-        .map(|kw| kw.trim().to_string())
-        .filter(|kw| !kw.is_empty())
-        .collect()
-}
-
-/// Returns true when the file already exists in the catalog by path or hash.
-pub fn is_already_imported(service: &CatalogService, path: &Path) -> bool {
-    if let Ok(Some(_)) = service.find_image_by_original_path(path) {
-        return true;
-    }
-
-    if let Ok(hash) = CatalogService::compute_file_hash(path) {
-        if let Ok(Some(_)) = service.find_image_by_hash(&hash) {
-            return true;
-        }
-    }
-
-    false
-}
-
-fn normalize_keywords(keywords: &[String]) -> Vec<String> {
-    let mut out = Vec::new();
-    for kw in keywords {
-        let trimmed = kw.trim();
-        if !trimmed.is_empty() && !out.iter().any(|existing: &String| existing == trimmed) {
-            out.push(trimmed.to_string());
-        }
-    }
-    out
-}
-
-fn copy_into_destination(src: &Path, dest_dir: &Path) -> Result<PathBuf> {
-    let filename = src
-        .file_name()
-        .ok_or_else(|| anyhow!("source file is missing a filename: {}", src.display()))?;
-    let dest_path = dest_dir.join(filename);
-    fs::create_dir_all(dest_dir)
-        .with_context(|| format!("failed to create destination {}", dest_dir.display()))?;
-    fs::copy(src, &dest_path).with_context(|| {
-        format!(
-            "failed to copy {} to {}",
-            src.display(),
-            dest_path.display()
-        )
-    })?;
-    Ok(dest_path)
+    import_files(
+        service,
+        file_paths,
+        keywords,
+        method,
+        destination,
+        callbacks,
+    )
 }
 
 fn decode_thumbnail(path: &Path) -> Option<SlintImage> {
@@ -373,12 +87,6 @@ fn decode_thumbnail(path: &Path) -> Option<SlintImage> {
     Some(SlintImage::from_rgba8(buf))
 }
 
-fn is_supported_extension(ext: &str) -> bool {
-    SUPPORTED_EXTENSIONS
-        .iter()
-        .any(|candidate| candidate.eq_ignore_ascii_case(ext))
-}
-
 fn letterbox_thumbnail(img: &image::DynamicImage, max_dim: u32) -> image::RgbaImage {
     let resized = img
         .resize(max_dim, max_dim, image::imageops::FilterType::Lanczos3)
@@ -402,6 +110,7 @@ mod tests {
     use catalog::db::CatalogDb;
     use catalog::schema::initialize_schema;
     use futures::executor::block_on;
+    use std::fs;
     use tempfile::tempdir;
 
     fn write_test_image(path: &Path) {
@@ -1,17 +1,50 @@
 #![cfg_attr(target_os = "windows", windows_subsystem = "windows")]
 
 mod config;
+mod folio_model;
 mod import;
+mod map;
+mod timeline;
 
 slint::include_modules!(); // from build.rs compiled ui/main.slint and catalog_dialog.slint
 
 use anyhow::{anyhow, Context};
-use catalog::db::{CatalogDb, Folder, Image as CatalogImage, Thumbnail};
-use catalog::services::{CatalogService, Edits};
+use catalog::db::roots::{relative_path, volume_for_path};
+use catalog::db::{
+    CatalogDb, DatePeriod, DayCount, Folder, GeoCluster, Image as CatalogImage, ImageLocation,
+    Thumbnail,
+};
+use catalog::services::backup::{backup_dir_for, list_backups, restore_backup, rotate_backups};
+use catalog::services::capture_time::{
+    correct_capture_times, format_offset, list_capture_time_corrections, parse_offset, parse_time,
+    parse_zone, undo_capture_time_correction,
+};
+use catalog::services::contact_sheet::write_contact_sheet;
+use catalog::services::export::{export_images, BitDepth, ExportCallbacks, ExportProgress};
+use catalog::services::external_catalog::migrate_external_catalog;
+use catalog::services::gallery::{generate_gallery, import_gallery_picks};
+use catalog::services::geocode::geocode_images;
+use catalog::services::geotag::{
+    apply_geotags, list_geotag_batches, place_images, preview_geotags, undo_geotags,
+};
+use catalog::services::gpx::read_gpx;
+use catalog::services::merge::merge_catalog;
+use catalog::services::portable::export_portable_catalog;
+use catalog::services::{
+    BackupInfo, CaptureTimeAdjustment, CaptureTimeSettings, CatalogService, ContactSheetCaptions,
+    ContactSheetSettings, ContactSheetSource, Edits, ExportFormat, ExportSettings, ExternalSource,
+    GalleryCaptions, GallerySettings, GalleryTemplate, GeocodeSettings, GeotagPreview,
+    GeotagSettings, ImageScope, ImageSort, MergeSettings, MetadataPolicy, NamedExportPreset,
+    OutputSharpening, PageCursor, PageOrientation, PageSize, PortableCatalogSettings, ResizeMode,
+    RightsInfo, RootSummary, SharpenAmount, SharpenMedium, SortKey, StackMembership,
+    WatermarkAnchor, WatermarkDefinition, WatermarkKind,
+};
 use catalog::{Catalog, CatalogPath};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Datelike, Local, Utc};
 use config::{ConfigStore, FolioLastSelection};
 use engine::ImageEngine;
+use folio_model::FolioModel;
+use map::Viewport;
 use import::{
     import_images_with_callbacks, is_already_imported, parse_keywords, scan_directory_with_options,
     CancellationFlag, DuplicateStrategy, ImportCallbacks, ImportMethod, ImportProgress,
@@ -62,7 +95,10 @@ struct FilterState {
 enum FolioSelection {
     AllPhotos,
     LastImport,
+    Duplicates,
     Folder(String),
+    Date(DatePeriod),
+    Collection(i64),
 }
 
 impl FolioSelection {
@@ -70,7 +106,20 @@ impl FolioSelection {
         match self {
             FolioSelection::AllPhotos => "all_photos",
             FolioSelection::LastImport => "last_import",
+            FolioSelection::Duplicates => "duplicates",
             FolioSelection::Folder(_) => "folder",
+            FolioSelection::Date(_) => "date",
+            FolioSelection::Collection(_) => "collection",
+        }
+    }
+
+    /// The key the selection's sort order is remembered under.
+    fn sort_scope(&self) -> String {
+        match self {
+            FolioSelection::Folder(path) => format!("folder:{path}"),
+            FolioSelection::Date(period) => format!("date:{period}"),
+            FolioSelection::Collection(id) => format!("collection:{id}"),
+            _ => self.kind().to_string(),
         }
     }
 }
@@ -101,12 +150,24 @@ struct FolioState {
     volumes: Rc<VecModel<VolumeNode>>,
     volume_tree: Vec<VolumeTree>,
     virtual_collections: Rc<VecModel<VirtualCollectionItem>>,
-    thumbnails: Rc<VecModel<ThumbnailItem>>,
+    thumbnails: Rc<FolioModel>,
+    /// Reads further pages of the grid's list as it scrolls.
+    feed: Option<FolioFeed>,
+    /// First row and number of rows the grid shows.
+    visible_rows: (usize, usize),
     selection: Vec<i32>,
     selection_anchor: Option<usize>,
     filters: FilterState,
     current_selection: Option<FolioSelection>,
     last_import_timestamp: Option<String>,
+    keep_scroll_on_reload: bool,
+    duplicate_threshold: u32,
+    date_nodes: Rc<VecModel<DateNode>>,
+    date_counts: Vec<DayCount>,
+    expanded_dates: HashSet<String>,
+    calendar_year: i32,
+    collections: Rc<VecModel<VirtualCollectionItem>>,
+    sort: ImageSort,
 }
 
 impl FolioState {
@@ -120,12 +181,18 @@ impl FolioState {
                 id: SharedString::from("last_import"),
                 label: SharedString::from("Last Import"),
             },
+            VirtualCollectionItem {
+                id: SharedString::from("duplicates"),
+                label: SharedString::from("Near Duplicates"),
+            },
         ]));
         Self {
             volumes: Rc::new(VecModel::default()),
             volume_tree: Vec::new(),
             virtual_collections,
-            thumbnails: Rc::new(VecModel::default()),
+            thumbnails: Rc::new(FolioModel::new(placeholder_image())),
+            feed: None,
+            visible_rows: (0, 0),
             selection: Vec::new(),
             selection_anchor: None,
             filters: FilterState {
@@ -136,6 +203,14 @@ impl FolioState {
             },
             current_selection: None,
             last_import_timestamp: None,
+            keep_scroll_on_reload: false,
+            duplicate_threshold: 6,
+            date_nodes: Rc::new(VecModel::default()),
+            date_counts: Vec::new(),
+            expanded_dates: HashSet::new(),
+            calendar_year: Local::now().year(),
+            collections: Rc::new(VecModel::default()),
+            sort: ImageSort::default(),
         }
     }
 
@@ -166,6 +241,7 @@ fn empty_metadata() -> ImageMetadata {
         iso: "".into(),
         gps_lat: "".into(),
         gps_lon: "".into(),
+        location: "".into(),
         rating: 0,
         flag: "none".into(),
         color_label: "none".into(),
@@ -207,7 +283,8 @@ fn main() -> Result<(), slint::PlatformError> {
     }
 
     if catalog_pair.is_none() {
-        catalog_pair = prompt_for_catalog_dialog(startup_error)?;
+        let restore_hint = config_store.last_catalog().or_else(Catalog::last_used);
+        catalog_pair = prompt_for_catalog_dialog(startup_error, restore_hint)?;
     }
 
     let Some((catalog_service, catalog_path)) = catalog_pair else {
@@ -240,12 +317,24 @@ fn main() -> Result<(), slint::PlatformError> {
     ))));
     let engine = Arc::new(ImageEngine::new());
     let active_import_ui: Rc<RefCell<Option<ImportPhotosScreen>>> = Rc::new(RefCell::new(None));
+    let active_restore_dialog: Rc<RefCell<Option<CatalogDialog>>> = Rc::new(RefCell::new(None));
+    let active_export_dialog: Rc<RefCell<Option<ExportDialog>>> = Rc::new(RefCell::new(None));
+    let active_gallery_dialog: Rc<RefCell<Option<GalleryDialog>>> = Rc::new(RefCell::new(None));
+    let active_contact_sheet_dialog: Rc<RefCell<Option<ContactSheetDialog>>> =
+        Rc::new(RefCell::new(None));
+    let active_capture_time_dialog: Rc<RefCell<Option<CaptureTimeDialog>>> =
+        Rc::new(RefCell::new(None));
+    let active_geotag_dialog: Rc<RefCell<Option<GeotagDialog>>> = Rc::new(RefCell::new(None));
     let folio_state = Rc::new(RefCell::new(FolioState::new()));
+    let map_state = Rc::new(RefCell::new(MapState::default()));
 
     {
         let folio_guard = folio_state.borrow();
         ui.set_volumes(folio_guard.volumes.clone().into());
         ui.set_virtual_collections(folio_guard.virtual_collections.clone().into());
+        ui.set_collections(folio_guard.collections.clone().into());
+        ui.set_date_nodes(folio_guard.date_nodes.clone().into());
+        ui.set_selected_date("".into());
         ui.set_selected_folder_path("".into());
         ui.set_selected_virtual_collection("".into());
         ui.set_catalog_name("".into());
@@ -270,6 +359,9 @@ fn main() -> Result<(), slint::PlatformError> {
     ui.set_refine_shadows(0.0);
     ui.set_refine_whites(0.0);
     ui.set_refine_blacks(0.0);
+    ui.set_map_basemap(rgba_to_image(&map::render_basemap(map::BASEMAP_WIDTH)));
+    ui.set_map_pins(map_state.borrow().pins.clone().into());
+    ui.set_map_summary("".into());
 
     let catalog_display_name = catalog_path
         .file_stem()
@@ -309,6 +401,57 @@ fn main() -> Result<(), slint::PlatformError> {
         });
     }
 
+    {
+        let ui_weak = ui_weak.clone();
+        let catalog_state = catalog_state.clone();
+        let folio_state = folio_state.clone();
+        let config_store = config_store.clone();
+        ui.on_root_relocate_requested(move |root_id| {
+            let ui_weak = ui_weak.clone();
+            let catalog_state = catalog_state.clone();
+            let folio_state = folio_state.clone();
+            let config_store = config_store.clone();
+            let _ = slint::spawn_local(async move {
+                let Some(handle) = AsyncFileDialog::new()
+                    .set_title("Relocate Root")
+                    .pick_folder()
+                    .await
+                else {
+                    return;
+                };
+                let result = match catalog_state.borrow().as_ref() {
+                    Some(session) => session.service.relocate_root(root_id as i64, handle.path()),
+                    None => return,
+                };
+                let status = match result {
+                    Ok(relocation) => {
+                        let mut status = format!(
+                            "Relocated {} folder(s) and {} photo(s) to {}",
+                            relocation.folders,
+                            relocation.images,
+                            handle.path().to_string_lossy()
+                        );
+                        if relocation.missing > 0 {
+                            status.push_str(&format!(", {} not found there", relocation.missing));
+                        }
+                        refresh_folio_tree(&ui_weak, &catalog_state, &folio_state);
+                        reload_current_selection(
+                            &catalog_state,
+                            &folio_state,
+                            &ui_weak,
+                            &config_store,
+                        );
+                        status
+                    }
+                    Err(err) => format!("Relocation failed: {err}"),
+                };
+                if let Some(ui) = ui_weak.upgrade() {
+                    ui.set_status_text(status.into());
+                }
+            });
+        });
+    }
+
     {
         let ui_weak = ui_weak.clone();
         let catalog_state = catalog_state.clone();
@@ -317,7 +460,12 @@ fn main() -> Result<(), slint::PlatformError> {
         ui.on_virtual_collection_selected(move |kind| {
             let selection = match kind.as_str() {
                 "last_import" => FolioSelection::LastImport,
-                _ => FolioSelection::AllPhotos,
+                "duplicates" => FolioSelection::Duplicates,
+                other => other
+                    .strip_prefix("collection:")
+                    .and_then(|id| id.parse().ok())
+                    .map(FolioSelection::Collection)
+                    .unwrap_or(FolioSelection::AllPhotos),
             };
             apply_selection(
                 selection,
@@ -329,9 +477,97 @@ fn main() -> Result<(), slint::PlatformError> {
         });
     }
 
+    {
+        let ui_weak = ui_weak.clone();
+        let catalog_state = catalog_state.clone();
+        let folio_state = folio_state.clone();
+        let config_store = config_store.clone();
+        ui.on_date_selected(move |key| {
+            let period = match key.parse::<DatePeriod>() {
+                Ok(period) => period,
+                Err(err) => {
+                    eprintln!("Ignoring timeline selection '{key}': {err}");
+                    return;
+                }
+            };
+            apply_selection(
+                FolioSelection::Date(period),
+                &catalog_state,
+                &folio_state,
+                &ui_weak,
+                &config_store,
+            );
+        });
+    }
+
+    {
+        let ui_weak = ui_weak.clone();
+        let folio_state = folio_state.clone();
+        ui.on_date_toggled(move |key| {
+            {
+                let mut guard = folio_state.borrow_mut();
+                let key = key.to_string();
+                if !guard.expanded_dates.remove(&key) {
+                    guard.expanded_dates.insert(key);
+                }
+            }
+            show_timeline(&ui_weak, &folio_state);
+        });
+    }
+
+    {
+        let ui_weak = ui_weak.clone();
+        let folio_state = folio_state.clone();
+        ui.on_calendar_year_changed(move |year| {
+            folio_state.borrow_mut().calendar_year = year;
+            show_timeline(&ui_weak, &folio_state);
+        });
+    }
+
+    {
+        let ui_weak = ui_weak.clone();
+        let catalog_state = catalog_state.clone();
+        let folio_state = folio_state.clone();
+        ui.on_thumbnails_viewport_changed(move |first, count| {
+            let visible = (first.max(0) as usize, count.max(0) as usize);
+            if folio_state.borrow().visible_rows == visible {
+                return;
+            }
+            folio_state.borrow_mut().visible_rows = visible;
+            show_visible_thumbnails(&catalog_state, &folio_state, &ui_weak);
+        });
+    }
+
+    {
+        let ui_weak = ui_weak.clone();
+        let catalog_state = catalog_state.clone();
+        let folio_state = folio_state.clone();
+        let config_store = config_store.clone();
+        ui.on_sort_changed(move |index, descending| {
+            let Some(selection) = folio_state.borrow().current_selection.clone() else {
+                return;
+            };
+            // The offered keys are `SortKey::ALL` in order, at most without the trailing
+            // manual order.
+            let Some(&key) = SortKey::ALL.get(index.max(0) as usize) else {
+                return;
+            };
+            let sort = ImageSort { key, descending };
+            if let Some(catalog_path) = current_catalog_path(&catalog_state) {
+                if let Err(err) =
+                    config_store.set_folio_sort(&catalog_path, &selection.sort_scope(), sort)
+                {
+                    eprintln!("Failed to persist folio sort: {err}");
+                }
+            }
+            reload_current_selection(&catalog_state, &folio_state, &ui_weak, &config_store);
+        });
+    }
+
     {
         let catalog_state = catalog_state.clone();
         let folio_state = folio_state.clone();
+        let map_state = map_state.clone();
         let ui_weak = ui_weak.clone();
         ui.on_thumbnail_selected(move |image_id, range_select, toggle| {
             handle_thumbnail_selection(
@@ -342,6 +578,26 @@ fn main() -> Result<(), slint::PlatformError> {
                 &folio_state,
                 &ui_weak,
             );
+            map_state.borrow_mut().clear_selection();
+        });
+    }
+
+    {
+        let catalog_state = catalog_state.clone();
+        let folio_state = folio_state.clone();
+        let ui_weak = ui_weak.clone();
+        let config_store = config_store.clone();
+        ui.on_thumbnail_moved(move |image_id, index| {
+            match move_in_collection(&catalog_state, &folio_state, image_id, index) {
+                Ok(()) => {
+                    reload_current_selection(&catalog_state, &folio_state, &ui_weak, &config_store)
+                }
+                Err(err) => {
+                    if let Some(ui) = ui_weak.upgrade() {
+                        ui.set_status_text(format!("Reordering failed: {err}").into());
+                    }
+                }
+            }
         });
     }
 
@@ -442,6 +698,45 @@ fn main() -> Result<(), slint::PlatformError> {
         });
     }
 
+    {
+        let catalog_state = catalog_state.clone();
+        let folio_state = folio_state.clone();
+        let ui_weak = ui_weak.clone();
+        let config_store = config_store.clone();
+        ui.on_stack_command(move |command, image_id, gap_seconds| {
+            if let Err(err) = apply_stack_command(
+                command.as_str(),
+                image_id,
+                gap_seconds,
+                &catalog_state,
+                &folio_state,
+            ) {
+                eprintln!("Failed to apply stack command '{command}': {err}");
+                return;
+            }
+            folio_state.borrow_mut().keep_scroll_on_reload = true;
+            reload_current_selection(&catalog_state, &folio_state, &ui_weak, &config_store);
+        });
+    }
+
+    {
+        let catalog_state = catalog_state.clone();
+        let folio_state = folio_state.clone();
+        let ui_weak = ui_weak.clone();
+        let config_store = config_store.clone();
+        ui.on_duplicate_command(move |command, max_distance| {
+            folio_state.borrow_mut().duplicate_threshold = max_distance.max(0) as u32;
+            if command.as_str() == "reject_extras" {
+                if let Err(err) = reject_duplicate_extras(&catalog_state, &folio_state) {
+                    eprintln!("Failed to reject duplicates: {err}");
+                    return;
+                }
+                folio_state.borrow_mut().keep_scroll_on_reload = true;
+            }
+            reload_current_selection(&catalog_state, &folio_state, &ui_weak, &config_store);
+        });
+    }
+
     {
         let catalog_state = catalog_state.clone();
         let ui_weak = ui_weak.clone();
@@ -473,6 +768,84 @@ fn main() -> Result<(), slint::PlatformError> {
 
     ui.on_back_to_folio(move || {});
 
+    {
+        let catalog_state = catalog_state.clone();
+        let map_state = map_state.clone();
+        let ui_weak = ui_weak.clone();
+        ui.on_map_viewport_changed(move |center_x, center_y, world_width, view_width, view_height| {
+            map_state.borrow_mut().viewport = Some(Viewport {
+                center_x: f64::from(center_x),
+                center_y: f64::from(center_y),
+                world_width: f64::from(world_width),
+                width: f64::from(view_width),
+                height: f64::from(view_height),
+            });
+            if let Err(err) = refresh_map_pins(&catalog_state, &map_state, &ui_weak) {
+                eprintln!("Failed to load map pins: {err}");
+            }
+        });
+    }
+
+    {
+        let catalog_state = catalog_state.clone();
+        let folio_state = folio_state.clone();
+        let map_state = map_state.clone();
+        let ui_weak = ui_weak.clone();
+        ui.on_map_pin_clicked(move |index, extend| {
+            if let Err(err) = select_map_pin(
+                &catalog_state,
+                &folio_state,
+                &map_state,
+                &ui_weak,
+                index as usize,
+                extend,
+            ) {
+                eprintln!("Failed to select photos from the map: {err}");
+            }
+        });
+    }
+
+    {
+        let catalog_state = catalog_state.clone();
+        let folio_state = folio_state.clone();
+        let map_state = map_state.clone();
+        let ui_weak = ui_weak.clone();
+        ui.on_map_thumbnail_dropped(move |image_id, x, y| {
+            let status = match place_dropped_photos(
+                &catalog_state,
+                &folio_state,
+                image_id,
+                f64::from(x),
+                f64::from(y),
+            ) {
+                Ok(status) => status,
+                Err(err) => format!("Geotagging failed: {err}"),
+            };
+            after_map_geotag(&catalog_state, &folio_state, &map_state, &ui_weak, status);
+        });
+    }
+
+    {
+        let catalog_state = catalog_state.clone();
+        let folio_state = folio_state.clone();
+        let map_state = map_state.clone();
+        let ui_weak = ui_weak.clone();
+        ui.on_map_undo_geotag(move || {
+            let result = match catalog_state.borrow().as_ref() {
+                Some(session) => undo_geotags(&session.service, None),
+                None => return,
+            };
+            let status = match result {
+                Ok(undo) => format!(
+                    "Undid \"{}\": restored {} photo(s)",
+                    undo.batch.description, undo.restored
+                ),
+                Err(err) => format!("Undo failed: {err}"),
+            };
+            after_map_geotag(&catalog_state, &folio_state, &map_state, &ui_weak, status);
+        });
+    }
+
     {
         let ui_weak = ui_weak.clone();
         let catalog_state = catalog_state.clone();
@@ -554,804 +927,3290 @@ fn main() -> Result<(), slint::PlatformError> {
         });
     }
 
-    ui.on_exit_requested(|| {
-        slint::quit_event_loop().ok();
-    });
+    {
+        let ui_weak = ui_weak.clone();
+        let catalog_state = catalog_state.clone();
+        let folio_state = folio_state.clone();
+        let engine = engine.clone();
+        let active_export_dialog = active_export_dialog.clone();
+        ui.on_export_photos_requested(move || {
+            open_export_dialog(
+                &ui_weak,
+                &catalog_state,
+                &folio_state,
+                &engine,
+                &active_export_dialog,
+            );
+        });
+    }
 
-    ui.run()
-}
+    {
+        let ui_weak = ui_weak.clone();
+        let catalog_state = catalog_state.clone();
+        let folio_state = folio_state.clone();
+        let engine = engine.clone();
+        let active_gallery_dialog = active_gallery_dialog.clone();
+        ui.on_web_gallery_requested(move || {
+            open_gallery_dialog(
+                &ui_weak,
+                &catalog_state,
+                &folio_state,
+                &engine,
+                &active_gallery_dialog,
+            );
+        });
+    }
 
-fn spawn_open_catalog_dialog(
-    ui_weak: &slint::Weak<MainWindow>,
-    catalog_state: &CatalogState,
-    config_store: &ConfigStore,
-    recent_model: &Rc<VecModel<SharedString>>,
-    folio_state: &Rc<RefCell<FolioState>>,
-) {
-    let ui_weak = ui_weak.clone();
-    let catalog_state = catalog_state.clone();
-    let config_store = config_store.clone();
-    let recent_model = recent_model.clone();
-    let folio_state = folio_state.clone();
+    {
+        let ui_weak = ui_weak.clone();
+        let catalog_state = catalog_state.clone();
+        let folio_state = folio_state.clone();
+        let engine = engine.clone();
+        let active_contact_sheet_dialog = active_contact_sheet_dialog.clone();
+        ui.on_contact_sheet_requested(move || {
+            open_contact_sheet_dialog(
+                &ui_weak,
+                &catalog_state,
+                &folio_state,
+                &engine,
+                &active_contact_sheet_dialog,
+            );
+        });
+    }
 
-    let _ = slint::spawn_local(async move {
-        if let Some(handle) = AsyncFileDialog::new()
-            .set_title("Open Catalog")
-            .add_filter("SQLite Catalog", &["sqlite", "zenithphotocatalog"])
-            .pick_file()
-            .await
-        {
-            let path = handle.path().to_path_buf();
-            if let Err(err) = load_catalog_from_path(
-                path,
+    {
+        let ui_weak = ui_weak.clone();
+        let catalog_state = catalog_state.clone();
+        let folio_state = folio_state.clone();
+        let config_store = config_store.clone();
+        let active_capture_time_dialog = active_capture_time_dialog.clone();
+        ui.on_capture_time_requested(move || {
+            open_capture_time_dialog(
                 &ui_weak,
                 &catalog_state,
-                &config_store,
-                &recent_model,
                 &folio_state,
-            ) {
-                eprintln!("{err}");
-            }
-        }
-    });
-}
+                &config_store,
+                &active_capture_time_dialog,
+            );
+        });
+    }
 
-fn spawn_new_catalog_dialog(
-    ui_weak: &slint::Weak<MainWindow>,
-    catalog_state: &CatalogState,
-    config_store: &ConfigStore,
-    recent_model: &Rc<VecModel<SharedString>>,
-    folio_state: &Rc<RefCell<FolioState>>,
-) {
-    let ui_weak = ui_weak.clone();
-    let catalog_state = catalog_state.clone();
-    let config_store = config_store.clone();
-    let recent_model = recent_model.clone();
-    let folio_state = folio_state.clone();
+    {
+        let ui_weak = ui_weak.clone();
+        let catalog_state = catalog_state.clone();
+        let folio_state = folio_state.clone();
+        let config_store = config_store.clone();
+        ui.on_locate_requested(move || {
+            let image_ids: Vec<i64> = folio_state
+                .borrow()
+                .selection
+                .iter()
+                .map(|id| *id as i64)
+                .collect();
+            let result = match catalog_state.borrow().as_ref() {
+                Some(session) => {
+                    geocode_images(&session.service, &image_ids, &GeocodeSettings::default())
+                }
+                None => return,
+            };
+            let status = match result {
+                Ok(report) => {
+                    reload_current_selection(&catalog_state, &folio_state, &ui_weak, &config_store);
+                    let mut status = format!("Located {} photo(s)", report.located.len());
+                    if report.already_located > 0 {
+                        status.push_str(&format!(", {} already located", report.already_located));
+                    }
+                    if report.without_position > 0 {
+                        status.push_str(&format!(", {} without GPS", report.without_position));
+                    }
+                    if report.too_far > 0 {
+                        status.push_str(&format!(", {} too far from a city", report.too_far));
+                    }
+                    status
+                }
+                Err(err) => format!("Location lookup failed: {err}"),
+            };
+            if let Some(ui) = ui_weak.upgrade() {
+                ui.set_status_text(status.into());
+            }
+        });
+    }
+
+    {
+        let ui_weak = ui_weak.clone();
+        let catalog_state = catalog_state.clone();
+        let folio_state = folio_state.clone();
+        let config_store = config_store.clone();
+        let active_geotag_dialog = active_geotag_dialog.clone();
+        ui.on_geotag_requested(move || {
+            open_geotag_dialog(
+                &ui_weak,
+                &catalog_state,
+                &folio_state,
+                &config_store,
+                &active_geotag_dialog,
+            );
+        });
+    }
+
+    {
+        let ui_weak = ui_weak.clone();
+        let catalog_state = catalog_state.clone();
+        let folio_state = folio_state.clone();
+        ui.on_import_picks_requested(move || {
+            let ui_weak = ui_weak.clone();
+            let catalog_state = catalog_state.clone();
+            let folio_state = folio_state.clone();
+            let _ = slint::spawn_local(async move {
+                let Some(handle) = AsyncFileDialog::new()
+                    .set_title("Import Client Picks")
+                    .add_filter("Gallery Picks", &["json"])
+                    .pick_file()
+                    .await
+                else {
+                    return;
+                };
+                let result = match catalog_state.borrow().as_ref() {
+                    Some(session) => import_gallery_picks(&session.service, handle.path()),
+                    None => return,
+                };
+                let status = match result {
+                    Ok(result) => {
+                        for image_id in &result.picked {
+                            if let Err(err) =
+                                refresh_thumbnail(&catalog_state, &folio_state, *image_id)
+                            {
+                                eprintln!("Failed to refresh image {image_id}: {err}");
+                            }
+                        }
+                        let mut status =
+                            format!("Flagged {} photo(s) as picked", result.picked.len());
+                        if !result.skipped.is_empty() {
+                            status.push_str(&format!(
+                                ", skipped {} not in this catalog",
+                                result.skipped.len()
+                            ));
+                        }
+                        status
+                    }
+                    Err(err) => format!("Failed to import picks: {err}"),
+                };
+                if let Some(ui) = ui_weak.upgrade() {
+                    ui.set_status_text(status.into());
+                }
+            });
+        });
+    }
+
+    {
+        let ui_weak = ui_weak.clone();
+        let catalog_state = catalog_state.clone();
+        let folio_state = folio_state.clone();
+        let config_store = config_store.clone();
+        ui.on_migrate_catalog_requested(move |source| {
+            let (source, filter_name, extensions): (_, _, &[&str]) = match source.as_str() {
+                "darktable" => (ExternalSource::Darktable, "darktable Library", &["db"]),
+                "digikam" => (ExternalSource::Digikam, "digiKam Database", &["db"]),
+                _ => (ExternalSource::Lightroom, "Lightroom Catalog", &["lrcat"]),
+            };
+            let ui_weak = ui_weak.clone();
+            let catalog_state = catalog_state.clone();
+            let folio_state = folio_state.clone();
+            let config_store = config_store.clone();
+            let _ = slint::spawn_local(async move {
+                let Some(handle) = AsyncFileDialog::new()
+                    .set_title(format!("Migrate from {}", source.label()))
+                    .add_filter(filter_name, extensions)
+                    .pick_file()
+                    .await
+                else {
+                    return;
+                };
+                if let Some(ui) = ui_weak.upgrade() {
+                    ui.set_status_text(format!("Migrating {} catalog…", source.label()).into());
+                }
+                let callbacks = ImportCallbacks {
+                    on_error: Some(Arc::new(|path: PathBuf, err: String| {
+                        eprintln!("Failed to migrate {}: {err}", path.to_string_lossy());
+                    })),
+                    ..ImportCallbacks::default()
+                };
+                let result = match catalog_state.borrow().as_ref() {
+                    Some(session) => migrate_external_catalog(
+                        &session.service,
+                        source,
+                        handle.path(),
+                        &callbacks,
+                        false,
+                    ),
+                    None => return,
+                };
+                let status = match result {
+                    Ok(report) => {
+                        for (feature, count) in &report.unmapped {
+                            eprintln!("Not migrated from {}: {feature} ({count})", source.label());
+                        }
+                        let mut status = format!(
+                            "Migrated {} photo(s) and {} collection(s)",
+                            report.imported + report.updated,
+                            report.collections
+                        );
+                        if !report.offline.is_empty() {
+                            status.push_str(&format!(", {} offline", report.offline.len()));
+                        }
+                        if !report.failed.is_empty() {
+                            status.push_str(&format!(", {} failed", report.failed.len()));
+                        }
+                        if !report.unmapped.is_empty() {
+                            status.push_str(&format!(
+                                "; not migrated: {}",
+                                report
+                                    .unmapped
+                                    .keys()
+                                    .map(String::as_str)
+                                    .collect::<Vec<_>>()
+                                    .join(", ")
+                            ));
+                        }
+                        refresh_folio_tree(&ui_weak, &catalog_state, &folio_state);
+                        reload_current_selection(
+                            &catalog_state,
+                            &folio_state,
+                            &ui_weak,
+                            &config_store,
+                        );
+                        status
+                    }
+                    Err(err) => format!("{} migration failed: {err}", source.label()),
+                };
+                if let Some(ui) = ui_weak.upgrade() {
+                    ui.set_status_text(status.into());
+                }
+            });
+        });
+    }
+
+    {
+        let ui_weak = ui_weak.clone();
+        let catalog_state = catalog_state.clone();
+        let folio_state = folio_state.clone();
+        let config_store = config_store.clone();
+        ui.on_merge_catalog_requested(move || {
+            let ui_weak = ui_weak.clone();
+            let catalog_state = catalog_state.clone();
+            let folio_state = folio_state.clone();
+            let config_store = config_store.clone();
+            let _ = slint::spawn_local(async move {
+                let Some(handle) = AsyncFileDialog::new()
+                    .set_title("Merge Catalog")
+                    .add_filter("Zenith Catalog", &["zenithphotocatalog", "sqlite"])
+                    .pick_file()
+                    .await
+                else {
+                    return;
+                };
+                if let Some(ui) = ui_weak.upgrade() {
+                    ui.set_status_text("Merging catalog…".into());
+                }
+                let callbacks = ImportCallbacks {
+                    on_error: Some(Arc::new(|path: PathBuf, err: String| {
+                        eprintln!("Failed to merge {}: {err}", path.to_string_lossy());
+                    })),
+                    ..ImportCallbacks::default()
+                };
+                let result = match catalog_state.borrow().as_ref() {
+                    Some(session) => merge_catalog(
+                        &session.service,
+                        handle.path(),
+                        &MergeSettings::default(),
+                        &callbacks,
+                    ),
+                    None => return,
+                };
+                let status = match result {
+                    Ok(report) => {
+                        let mut status = format!(
+                            "Merged catalog: {} new photo(s), {} matched, {} new collection(s)",
+                            report.added,
+                            report.matched_by_path + report.matched_by_hash,
+                            report.collections_created
+                        );
+                        if !report.failed.is_empty() {
+                            status.push_str(&format!(", {} failed", report.failed.len()));
+                        }
+                        refresh_folio_tree(&ui_weak, &catalog_state, &folio_state);
+                        reload_current_selection(
+                            &catalog_state,
+                            &folio_state,
+                            &ui_weak,
+                            &config_store,
+                        );
+                        status
+                    }
+                    Err(err) => format!("Merge failed: {err}"),
+                };
+                if let Some(ui) = ui_weak.upgrade() {
+                    ui.set_status_text(status.into());
+                }
+            });
+        });
+    }
+
+    {
+        let ui_weak = ui_weak.clone();
+        let catalog_state = catalog_state.clone();
+        let folio_state = folio_state.clone();
+        ui.on_export_catalog_requested(move |copy_originals| {
+            let image_ids: Vec<i64> = folio_state
+                .borrow()
+                .selection
+                .iter()
+                .map(|id| *id as i64)
+                .collect();
+            if image_ids.is_empty() {
+                return;
+            }
+            let ui_weak = ui_weak.clone();
+            let catalog_state = catalog_state.clone();
+            let _ = slint::spawn_local(async move {
+                let Some(handle) = AsyncFileDialog::new()
+                    .set_title("Export Selection as Catalog")
+                    .set_file_name("Export.zenithphotocatalog")
+                    .add_filter("Zenith Catalog", &["zenithphotocatalog"])
+                    .save_file()
+                    .await
+                else {
+                    return;
+                };
+                if let Some(ui) = ui_weak.upgrade() {
+                    ui.set_status_text(
+                        format!("Exporting {} photo(s) as a catalog…", image_ids.len()).into(),
+                    );
+                }
+                let callbacks = ImportCallbacks {
+                    on_error: Some(Arc::new(|path: PathBuf, err: String| {
+                        eprintln!("Failed to copy {}: {err}", path.to_string_lossy());
+                    })),
+                    ..ImportCallbacks::default()
+                };
+                let result = match catalog_state.borrow().as_ref() {
+                    Some(session) => export_portable_catalog(
+                        &session.service,
+                        &image_ids,
+                        handle.path(),
+                        &PortableCatalogSettings { copy_originals },
+                        &callbacks,
+                    ),
+                    None => return,
+                };
+                let status = match result {
+                    Ok(report) => {
+                        let mut status = format!(
+                            "Exported {} photo(s) to {}",
+                            report.images,
+                            report.catalog.to_string_lossy()
+                        );
+                        if !report.failed.is_empty() {
+                            status.push_str(&format!(
+                                "; {} original(s) could not be copied",
+                                report.failed.len()
+                            ));
+                        }
+                        status
+                    }
+                    Err(err) => format!("Catalog export failed: {err}"),
+                };
+                if let Some(ui) = ui_weak.upgrade() {
+                    ui.set_status_text(status.into());
+                }
+            });
+        });
+    }
+
+    {
+        let ui_weak = ui_weak.clone();
+        let catalog_state = catalog_state.clone();
+        let config_store = config_store.clone();
+        ui.on_backup_catalog_requested(move || {
+            let status = match backup_current_catalog(&catalog_state, &config_store) {
+                Ok(info) => format!("Catalog backed up to {}", info.path.to_string_lossy()),
+                Err(err) => format!("Catalog backup failed: {err}"),
+            };
+            if let Some(ui) = ui_weak.upgrade() {
+                ui.set_status_text(status.into());
+            }
+        });
+    }
+
+    {
+        let ui_weak = ui_weak.clone();
+        let catalog_state = catalog_state.clone();
+        let config_store = config_store.clone();
+        let recent_model = recent_model.clone();
+        let folio_state = folio_state.clone();
+        let active_restore_dialog = active_restore_dialog.clone();
+        ui.on_restore_catalog_requested(move || {
+            open_restore_dialog(
+                &ui_weak,
+                &catalog_state,
+                &config_store,
+                &recent_model,
+                &folio_state,
+                &active_restore_dialog,
+            );
+        });
+    }
+
+    {
+        let ui_weak = ui_weak.clone();
+        let catalog_state = catalog_state.clone();
+        let config_store = config_store.clone();
+        let folio_state = folio_state.clone();
+        ui.on_verify_catalog_requested(move |repair| {
+            let status = match verify_current_catalog(&catalog_state, &config_store, repair) {
+                Ok(status) => status,
+                Err(err) => format!("Catalog verification failed: {err}"),
+            };
+            if repair {
+                reload_current_selection(&catalog_state, &folio_state, &ui_weak, &config_store);
+            }
+            if let Some(ui) = ui_weak.upgrade() {
+                ui.set_status_text(status.into());
+            }
+        });
+    }
+
+    ui.on_exit_requested(|| {
+        slint::quit_event_loop().ok();
+    });
+
+    if let Some(status) = run_scheduled_backup(&catalog_state, &config_store, false) {
+        ui.set_status_text(status.into());
+    }
+
+    let result = ui.run();
+    run_scheduled_backup(&catalog_state, &config_store, true);
+    result
+}
+
+/// Run the configured backup schedule for the open catalog, returning a status message when a
+/// backup was written or failed.
+fn run_scheduled_backup(
+    catalog_state: &CatalogState,
+    config_store: &ConfigStore,
+    on_exit: bool,
+) -> Option<String> {
+    let guard = catalog_state.borrow();
+    let session = guard.as_ref()?;
+    let settings = config_store.backup_settings();
+    match session
+        .service
+        .backup_if_due(&backup_dir_for(&session.path), &settings, on_exit)
+    {
+        Ok(Some(info)) => Some(format!(
+            "Scheduled backup saved to {}",
+            info.path.to_string_lossy()
+        )),
+        Ok(None) => None,
+        Err(err) => {
+            eprintln!("Scheduled catalog backup failed: {err:#}");
+            Some(format!("Scheduled catalog backup failed: {err}"))
+        }
+    }
+}
+
+fn backup_current_catalog(
+    catalog_state: &CatalogState,
+    config_store: &ConfigStore,
+) -> anyhow::Result<BackupInfo> {
+    let guard = catalog_state.borrow();
+    let session = guard.as_ref().context("No catalog open")?;
+    let settings = config_store.backup_settings();
+    let backup_dir = backup_dir_for(&session.path);
+    let info = session
+        .service
+        .create_backup(&backup_dir, settings.compress)?;
+    rotate_backups(&backup_dir, settings.keep)?;
+    Ok(info)
+}
+
+/// Verify the open catalog and, when asked, repair what can be fixed after taking a backup.
+fn verify_current_catalog(
+    catalog_state: &CatalogState,
+    config_store: &ConfigStore,
+    repair: bool,
+) -> anyhow::Result<String> {
+    let report = {
+        let guard = catalog_state.borrow();
+        let session = guard.as_ref().context("No catalog open")?;
+        session.service.verify_catalog()?
+    };
+    for finding in &report.findings {
+        eprintln!("Catalog check: {}", finding.message);
+    }
+    if report.is_clean() {
+        return Ok("Catalog verified: no problems found".into());
+    }
+    if !repair {
+        return Ok(format!(
+            "Catalog verified: {} problem(s) found",
+            report.findings.len()
+        ));
+    }
+
+    let backup = backup_current_catalog(catalog_state, config_store)
+        .context("failed to back up catalog before repair")?;
+    let guard = catalog_state.borrow();
+    let session = guard.as_ref().context("No catalog open")?;
+    let summary = session.service.repair_catalog(&report)?;
+    let mut status = format!(
+        "Catalog repaired ({} rows removed, {} images relinked, {} JSON values cleared); backup at {}",
+        summary.rows_deleted,
+        summary.images_relinked,
+        summary.json_cleared,
+        backup.path.to_string_lossy()
+    );
+    if summary.skipped > 0 {
+        status.push_str(&format!(
+            "; {} problem(s) need manual attention",
+            summary.skipped
+        ));
+    }
+    Ok(status)
+}
+
+fn refresh_backup_list(dialog: &CatalogDialog, catalog_path: Option<&Path>) {
+    let entries: Vec<BackupEntry> = catalog_path
+        .map(|path| {
+            list_backups(&backup_dir_for(path)).unwrap_or_else(|err| {
+                eprintln!("Failed to list backups: {err}");
+                Vec::new()
+            })
+        })
+        .unwrap_or_default()
+        .into_iter()
+        .map(|info| {
+            let images = info
+                .image_count
+                .map(|count| format!("{count} images"))
+                .unwrap_or_else(|| "unknown image count".into());
+            BackupEntry {
+                path: SharedString::from(info.path.to_string_lossy().to_string()),
+                label: SharedString::from(
+                    info.created_at
+                        .with_timezone(&Local)
+                        .format("%Y-%m-%d %H:%M")
+                        .to_string(),
+                ),
+                detail: SharedString::from(format!("{images} · {}", human_size(info.size_bytes))),
+            }
+        })
+        .collect();
+
+    dialog.set_restore_catalog_path(
+        catalog_path
+            .map(|path| path.to_string_lossy().to_string())
+            .unwrap_or_default()
+            .into(),
+    );
+    dialog.set_selected_backup(if entries.is_empty() { -1 } else { 0 });
+    dialog.set_backups(Rc::new(VecModel::from(entries)).into());
+}
+
+/// Wire the restore section of the catalog dialog.
+///
+/// `release` runs before files are replaced so the caller can close its connection to the
+/// target catalog. `on_opened` receives the catalog reopened afterwards, together with whether
+/// the restore itself succeeded.
+fn attach_restore_handlers(
+    dialog: &CatalogDialog,
+    initial_catalog: Option<PathBuf>,
+    release: impl Fn(&Path) + 'static,
+    on_opened: impl Fn(CatalogService, PathBuf, bool) + 'static,
+) {
+    let target = Rc::new(RefCell::new(
+        initial_catalog.map(|path| CatalogPath::new(path).into_path()),
+    ));
+    refresh_backup_list(dialog, target.borrow().as_deref());
+
+    {
+        let dialog_weak = dialog.as_weak();
+        let target = target.clone();
+        dialog.on_choose_restore_catalog(move || {
+            if let Some(path) = FileDialog::new()
+                .add_filter("Zenith Catalog", &["zenithphotocatalog", "sqlite"])
+                .pick_file()
+            {
+                let normalized = CatalogPath::new(&path).into_path();
+                if let Some(dialog) = dialog_weak.upgrade() {
+                    refresh_backup_list(&dialog, Some(&normalized));
+                }
+                *target.borrow_mut() = Some(normalized);
+            }
+        });
+    }
+
+    {
+        let dialog_weak = dialog.as_weak();
+        dialog.on_restore_backup(move |backup_path| {
+            let Some(catalog_path) = target.borrow().clone() else {
+                return;
+            };
+            release(&catalog_path);
+            let restored = restore_backup(Path::new(backup_path.as_str()), &catalog_path);
+            if let Some(dialog) = dialog_weak.upgrade() {
+                let message = match &restored {
+                    Ok(_) => String::new(),
+                    Err(err) => format!("Unable to restore backup: {err:#}"),
+                };
+                dialog.set_error_text(message.into());
+            }
+            match open_catalog_service(&catalog_path) {
+                Ok((service, resolved)) => on_opened(service, resolved, restored.is_ok()),
+                Err(err) => {
+                    if let Some(dialog) = dialog_weak.upgrade() {
+                        dialog.set_error_text(
+                            format!("Unable to open restored catalog: {err}").into(),
+                        );
+                    }
+                }
+            }
+        });
+    }
+}
+
+fn open_restore_dialog(
+    ui_weak: &slint::Weak<MainWindow>,
+    catalog_state: &CatalogState,
+    config_store: &ConfigStore,
+    recent_model: &Rc<VecModel<SharedString>>,
+    folio_state: &Rc<RefCell<FolioState>>,
+    active_dialog: &Rc<RefCell<Option<CatalogDialog>>>,
+) {
+    let dialog = match CatalogDialog::new() {
+        Ok(dialog) => dialog,
+        Err(err) => {
+            eprintln!("Failed to open restore dialog: {err}");
+            return;
+        }
+    };
+    dialog.set_restore_only(true);
+
+    let release = {
+        let catalog_state = catalog_state.clone();
+        move |target: &Path| {
+            let is_open = current_catalog_path(&catalog_state).as_deref() == Some(target);
+            if is_open {
+                // Dropping the session closes the SQLite connection before the file is swapped.
+                *catalog_state.borrow_mut() = None;
+            }
+        }
+    };
+
+    let on_opened = {
+        let ui_weak = ui_weak.clone();
+        let catalog_state = catalog_state.clone();
+        let config_store = config_store.clone();
+        let recent_model = recent_model.clone();
+        let folio_state = folio_state.clone();
+        let dialog_weak = dialog.as_weak();
+        move |service: CatalogService, path: PathBuf, restored: bool| {
+            apply_loaded_catalog(
+                service,
+                path.clone(),
+                &ui_weak,
+                &catalog_state,
+                &config_store,
+                &recent_model,
+                &folio_state,
+            );
+            if restored {
+                if let Some(ui) = ui_weak.upgrade() {
+                    ui.set_status_text(
+                        format!("Restored catalog {}", path.to_string_lossy()).into(),
+                    );
+                }
+                if let Some(dialog) = dialog_weak.upgrade() {
+                    dialog.hide().ok();
+                }
+            }
+        }
+    };
+
+    attach_restore_handlers(
+        &dialog,
+        current_catalog_path(catalog_state),
+        release,
+        on_opened,
+    );
+
+    {
+        let dialog_weak = dialog.as_weak();
+        dialog.on_quit(move || {
+            if let Some(dialog) = dialog_weak.upgrade() {
+                dialog.hide().ok();
+            }
+        });
+    }
+
+    dialog.show().ok();
+    *active_dialog.borrow_mut() = Some(dialog);
+}
+
+fn spawn_open_catalog_dialog(
+    ui_weak: &slint::Weak<MainWindow>,
+    catalog_state: &CatalogState,
+    config_store: &ConfigStore,
+    recent_model: &Rc<VecModel<SharedString>>,
+    folio_state: &Rc<RefCell<FolioState>>,
+) {
+    let ui_weak = ui_weak.clone();
+    let catalog_state = catalog_state.clone();
+    let config_store = config_store.clone();
+    let recent_model = recent_model.clone();
+    let folio_state = folio_state.clone();
+
+    let _ = slint::spawn_local(async move {
+        if let Some(handle) = AsyncFileDialog::new()
+            .set_title("Open Catalog")
+            .add_filter("SQLite Catalog", &["sqlite", "zenithphotocatalog"])
+            .pick_file()
+            .await
+        {
+            let path = handle.path().to_path_buf();
+            if let Err(err) = load_catalog_from_path(
+                path,
+                &ui_weak,
+                &catalog_state,
+                &config_store,
+                &recent_model,
+                &folio_state,
+            ) {
+                eprintln!("{err}");
+            }
+        }
+    });
+}
+
+fn spawn_new_catalog_dialog(
+    ui_weak: &slint::Weak<MainWindow>,
+    catalog_state: &CatalogState,
+    config_store: &ConfigStore,
+    recent_model: &Rc<VecModel<SharedString>>,
+    folio_state: &Rc<RefCell<FolioState>>,
+) {
+    let ui_weak = ui_weak.clone();
+    let catalog_state = catalog_state.clone();
+    let config_store = config_store.clone();
+    let recent_model = recent_model.clone();
+    let folio_state = folio_state.clone();
+
+    let _ = slint::spawn_local(async move {
+        if let Some(handle) = AsyncFileDialog::new()
+            .set_title("New Catalog")
+            .set_file_name("Untitled.sqlite")
+            .add_filter("SQLite Catalog", &["sqlite", "zenithphotocatalog"])
+            .save_file()
+            .await
+        {
+            let requested_path = handle.path().to_path_buf();
+            match create_catalog_service(&requested_path) {
+                Ok((catalog, resolved_path)) => {
+                    apply_loaded_catalog(
+                        catalog,
+                        resolved_path,
+                        &ui_weak,
+                        &catalog_state,
+                        &config_store,
+                        &recent_model,
+                        &folio_state,
+                    );
+                }
+                Err(err) => eprintln!("Failed to create catalog: {err}"),
+            }
+        }
+    });
+}
+
+fn load_catalog_from_path(
+    selected_path: PathBuf,
+    ui_weak: &slint::Weak<MainWindow>,
+    catalog_state: &CatalogState,
+    config_store: &ConfigStore,
+    recent_model: &Rc<VecModel<SharedString>>,
+    folio_state: &Rc<RefCell<FolioState>>,
+) -> Result<(), String> {
+    let normalized_path = CatalogPath::new(&selected_path).into_path();
+    if !normalized_path.exists() {
+        return Err(format!(
+            "Catalog '{}' does not exist",
+            normalized_path.to_string_lossy()
+        ));
+    }
+
+    let (catalog, resolved_path) = open_catalog_service(&normalized_path)
+        .map_err(|err| format!("Unable to open catalog: {err}"))?;
+    apply_loaded_catalog(
+        catalog,
+        resolved_path,
+        ui_weak,
+        catalog_state,
+        config_store,
+        recent_model,
+        folio_state,
+    );
+    Ok(())
+}
+
+fn apply_loaded_catalog(
+    catalog: CatalogService,
+    path: PathBuf,
+    ui_weak: &slint::Weak<MainWindow>,
+    catalog_state: &CatalogState,
+    config_store: &ConfigStore,
+    recent_model: &Rc<VecModel<SharedString>>,
+    folio_state: &Rc<RefCell<FolioState>>,
+) {
+    *catalog_state.borrow_mut() = Some(CatalogSession::new(catalog, path.clone()));
+
+    if let Err(err) = Catalog::set_last_used(&path) {
+        eprintln!("Failed to persist last catalog path: {err}");
+    }
+
+    match config_store.record_catalog(&path) {
+        Ok(snapshot) => refresh_recent_model(recent_model, &snapshot.recent_catalogs),
+        Err(err) => eprintln!("Failed to update catalog history: {err}"),
+    }
+
+    let display_path = path.to_string_lossy().to_string();
+    let catalog_display_name = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default()
+        .to_string();
+    {
+        let mut guard = folio_state.borrow_mut();
+        guard.last_import_timestamp = config_store.last_import_timestamp(&path);
+        guard.thumbnails.clear_thumbnails();
+    }
+    slint::invoke_from_event_loop({
+        let ui_weak = ui_weak.clone();
+        move || {
+            if let Some(ui) = ui_weak.upgrade() {
+                ui.set_current_path(display_path.clone().into());
+                ui.set_catalog_name(catalog_display_name.clone().into());
+            }
+        }
+    })
+    .ok();
+
+    if let Some(status) = run_scheduled_backup(catalog_state, config_store, false) {
+        if let Some(ui) = ui_weak.upgrade() {
+            ui.set_status_text(status.into());
+        }
+    }
+
+    refresh_folio_tree(ui_weak, catalog_state, folio_state);
+    restore_last_selection(ui_weak, catalog_state, folio_state, config_store);
+}
+
+type ImageId = i32;
+
+/// Keeps UI selection (highlighted thumbnails) independent from checkbox state.
+/// The selected_ids set mirrors the visible highlight, while anchor_id preserves
+/// the last focused item for range selection.
+#[derive(Default)]
+struct SelectionState {
+    selected_ids: HashSet<ImageId>,
+    anchor_id: Option<ImageId>,
+}
+
+struct ImportViewState {
+    thumbnails: Rc<VecModel<ImportThumbnail>>,
+    /// Paths that are currently marked for import (checked=true).
+    selected_paths: Rc<VecModel<SharedString>>,
+    errors: Rc<VecModel<SharedString>>,
+    directories: Rc<VecModel<SharedString>>,
+    scan_cancel: CancellationFlag,
+    import_cancel: CancellationFlag,
+    /// Tracks highlighted selection separately from import checkboxes.
+    selection: SelectionState,
+    next_image_id: ImageId,
+}
+
+impl ImportViewState {
+    fn new() -> Self {
+        Self {
+            thumbnails: Rc::new(VecModel::default()),
+            selected_paths: Rc::new(VecModel::default()),
+            errors: Rc::new(VecModel::default()),
+            directories: Rc::new(VecModel::default()),
+            scan_cancel: CancellationFlag::default(),
+            import_cancel: CancellationFlag::default(),
+            selection: SelectionState::default(),
+            next_image_id: 1,
+        }
+    }
+
+    fn reset_for_scan(&mut self) {
+        self.thumbnails.set_vec(Vec::new());
+        self.selected_paths.set_vec(Vec::new());
+        self.errors.set_vec(Vec::new());
+        self.scan_cancel.cancel();
+        self.scan_cancel = CancellationFlag::default();
+        self.selection = SelectionState::default();
+        self.next_image_id = 1;
+    }
+
+    fn is_selectable_id(&self, id: ImageId) -> bool {
+        self.index_of(id)
+            .and_then(|idx| self.thumbnails.row_data(idx))
+            .map(|thumb| thumb.selectable)
+            .unwrap_or(false)
+    }
+
+    fn index_of(&self, id: ImageId) -> Option<usize> {
+        for idx in 0..self.thumbnails.row_count() {
+            if let Some(thumb) = self.thumbnails.row_data(idx) {
+                if thumb.id == id {
+                    return Some(idx);
+                }
+            }
+        }
+        None
+    }
+
+    fn apply_selection_flags(&mut self) {
+        // Keep selection in sync with the model and drop anything non-selectable.
+        let mut invalid = Vec::new();
+        for id in &self.selection.selected_ids {
+            if !self.is_selectable_id(*id) {
+                invalid.push(*id);
+            }
+        }
+        for id in invalid {
+            self.selection.selected_ids.remove(&id);
+        }
+
+        if let Some(anchor) = self.selection.anchor_id {
+            if !self.is_selectable_id(anchor) {
+                self.selection.anchor_id = self.selection.selected_ids.iter().next().copied();
+            }
+        }
+
+        for idx in 0..self.thumbnails.row_count() {
+            if let Some(mut thumb) = self.thumbnails.row_data(idx) {
+                let is_selected = self.selection.selected_ids.contains(&thumb.id);
+                if thumb.selected != is_selected {
+                    thumb.selected = is_selected;
+                    self.thumbnails.set_row_data(idx, thumb);
+                }
+            }
+        }
+    }
+
+    fn rebuild_checked_paths(&self) {
+        let mut paths = Vec::new();
+        let mut seen = HashSet::new();
+        for idx in 0..self.thumbnails.row_count() {
+            if let Some(thumb) = self.thumbnails.row_data(idx) {
+                if thumb.checked && thumb.selectable && seen.insert(thumb.path.clone()) {
+                    paths.push(thumb.path);
+                }
+            }
+        }
+        self.selected_paths.set_vec(paths);
+    }
+}
+
+fn placeholder_image() -> slint::Image {
+    let mut buf = SharedPixelBuffer::<Rgba8Pixel>::new(1, 1);
+    buf.make_mut_bytes().fill(0);
+    slint::Image::from_rgba8(buf)
+}
+
+fn refresh_directory_model(model: &Rc<VecModel<SharedString>>, base: &Path) {
+    let mut entries = vec![SharedString::from(base.to_string_lossy().to_string())];
+    if let Ok(read_dir) = fs::read_dir(base) {
+        for entry in read_dir.flatten() {
+            if entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false) {
+                entries.push(SharedString::from(
+                    entry.path().to_string_lossy().to_string(),
+                ));
+            }
+        }
+    }
+    model.set_vec(entries);
+}
+
+fn select_single(state: &Rc<RefCell<ImportViewState>>, id: ImageId) {
+    let mut guard = state.borrow_mut();
+    if !guard.is_selectable_id(id) {
+        return;
+    }
+    guard.selection.selected_ids.clear();
+    guard.selection.selected_ids.insert(id);
+    guard.selection.anchor_id = Some(id);
+    guard.apply_selection_flags();
+}
+
+fn select_range(state: &Rc<RefCell<ImportViewState>>, id: ImageId) {
+    let mut guard = state.borrow_mut();
+    if !guard.is_selectable_id(id) {
+        return;
+    }
+
+    let Some(anchor) = guard.selection.anchor_id else {
+        guard.selection.selected_ids.clear();
+        guard.selection.selected_ids.insert(id);
+        guard.selection.anchor_id = Some(id);
+        guard.apply_selection_flags();
+        return;
+    };
+
+    let Some(anchor_idx) = guard.index_of(anchor) else {
+        guard.selection.selected_ids.clear();
+        guard.selection.selected_ids.insert(id);
+        guard.selection.anchor_id = Some(id);
+        guard.apply_selection_flags();
+        return;
+    };
+
+    let Some(target_idx) = guard.index_of(id) else {
+        return;
+    };
+
+    let (start, end) = if anchor_idx <= target_idx {
+        (anchor_idx, target_idx)
+    } else {
+        (target_idx, anchor_idx)
+    };
+
+    guard.selection.selected_ids.clear();
+    for idx in start..=end {
+        if let Some(thumb) = guard.thumbnails.row_data(idx) {
+            if thumb.selectable {
+                guard.selection.selected_ids.insert(thumb.id);
+            }
+        }
+    }
+    guard.selection.anchor_id = Some(id);
+    guard.apply_selection_flags();
+}
+
+fn toggle_single(state: &Rc<RefCell<ImportViewState>>, id: ImageId) {
+    let mut guard = state.borrow_mut();
+    if !guard.is_selectable_id(id) {
+        return;
+    }
+
+    if !guard.selection.selected_ids.remove(&id) {
+        guard.selection.selected_ids.insert(id);
+    }
+    guard.apply_selection_flags();
+}
+
+fn select_all_allowed(state: &Rc<RefCell<ImportViewState>>) {
+    let mut guard = state.borrow_mut();
+    guard.selection.selected_ids.clear();
+    let mut anchor: Option<ImageId> = None;
+    for idx in 0..guard.thumbnails.row_count() {
+        if let Some(thumb) = guard.thumbnails.row_data(idx) {
+            if thumb.selectable {
+                guard.selection.selected_ids.insert(thumb.id);
+                if anchor.is_none() {
+                    anchor = Some(thumb.id);
+                }
+            }
+        }
+    }
+    guard.selection.anchor_id = anchor;
+    guard.apply_selection_flags();
+}
+
+fn clear_selection(state: &Rc<RefCell<ImportViewState>>) {
+    let mut guard = state.borrow_mut();
+    guard.selection.selected_ids.clear();
+    guard.apply_selection_flags();
+}
+
+fn set_all_checked(state: &Rc<RefCell<ImportViewState>>, checked: bool) {
+    let guard = state.borrow_mut();
+    for idx in 0..guard.thumbnails.row_count() {
+        if let Some(mut thumb) = guard.thumbnails.row_data(idx) {
+            if thumb.selectable {
+                thumb.checked = checked;
+                guard.thumbnails.set_row_data(idx, thumb);
+            }
+        }
+    }
+    guard.rebuild_checked_paths();
+}
+
+fn apply_checkbox_to_selection(state: &Rc<RefCell<ImportViewState>>, checked: bool) {
+    let mut guard = state.borrow_mut();
+    if guard.selection.selected_ids.is_empty() {
+        if let Some(anchor) = guard.selection.anchor_id {
+            if guard.is_selectable_id(anchor) {
+                guard.selection.selected_ids.insert(anchor);
+            }
+        }
+    }
+
+    guard.apply_selection_flags();
+
+    for idx in 0..guard.thumbnails.row_count() {
+        if let Some(mut thumb) = guard.thumbnails.row_data(idx) {
+            if guard.selection.selected_ids.contains(&thumb.id) && thumb.selectable {
+                thumb.checked = checked;
+                guard.thumbnails.set_row_data(idx, thumb);
+            }
+        }
+    }
+    guard.rebuild_checked_paths();
+}
+
+fn start_scan_for_directory(
+    import_ui: &slint::Weak<ImportPhotosScreen>,
+    state: &Rc<RefCell<ImportViewState>>,
+    catalog_path: PathBuf,
+    path: PathBuf,
+) {
+    if !path.exists() {
+        if let Some(ui) = import_ui.upgrade() {
+            ui.set_status_text("Folder not found".into());
+        }
+        return;
+    }
+
+    {
+        let mut guard = state.borrow_mut();
+        guard.reset_for_scan();
+        refresh_directory_model(&guard.directories, &path);
+    }
+
+    if let Some(ui) = import_ui.upgrade() {
+        ui.set_selected_directory(path.to_string_lossy().to_string().into());
+        ui.set_status_text("Scanning for images…".into());
+        ui.set_importing(true);
+        ui.set_progress(0.0);
+    }
+
+    let db_path = catalog_path.to_string_lossy().to_string();
+    let catalog_for_scan = CatalogDb::open(&db_path)
+        .map(|db| CatalogService::new(db).with_home_dir(home_dir()))
+        .map(Rc::new)
+        .map_err(|err| {
+            if let Some(ui) = import_ui.upgrade() {
+                ui.set_status_text(format!("Unable to check duplicates: {err}").into());
+            }
+            err
+        })
+        .ok();
+
+    let cancel_flag = { state.borrow().scan_cancel.clone() };
+    let state_for_candidates = state.clone();
+    let ui_weak = import_ui.clone();
+    let seen = Rc::new(RefCell::new(HashSet::<PathBuf>::new()));
+    let scan_opts = ScanOptions {
+        on_candidate: Some(Arc::new({
+            let seen = seen.clone();
+            let catalog_for_scan = catalog_for_scan.clone();
+            move |candidate| {
+                if !seen.borrow_mut().insert(candidate.path.clone()) {
+                    return;
+                }
+
+                let already_imported = catalog_for_scan
+                    .as_ref()
+                    .map(|svc| is_already_imported(svc, &candidate.path))
+                    .unwrap_or(false);
+                let selectable = !already_imported;
+                let checked = selectable;
+                let display_thumb = candidate.thumb.unwrap_or_else(placeholder_image);
+                let path_text: SharedString = candidate.path.to_string_lossy().to_string().into();
+
+                let mut guard = state_for_candidates.borrow_mut();
+                let id = guard.next_image_id;
+                guard.next_image_id += 1;
+
+                if selectable && guard.selection.selected_ids.is_empty() {
+                    guard.selection.selected_ids.insert(id);
+                    guard.selection.anchor_id = Some(id);
+                }
+
+                let is_selected = guard.selection.selected_ids.contains(&id);
+
+                guard.thumbnails.push(ImportThumbnail {
+                    id,
+                    path: path_text.clone(),
+                    display_thumb,
+                    selected: is_selected,
+                    checked,
+                    already_imported,
+                    selectable,
+                });
+                guard.apply_selection_flags();
+                guard.rebuild_checked_paths();
+                if let Some(ui) = ui_weak.upgrade() {
+                    ui.set_status_text(format!("Queued {}", candidate.path.display()).into());
+                }
+            }
+        })),
+        cancel: cancel_flag.clone(),
+    };
+    let ui_done = import_ui.clone();
+    let state_done = state.clone();
+    let _ = slint::spawn_local(async move {
+        let result = scan_directory_with_options(&path, scan_opts).await;
+        if let Some(ui) = ui_done.upgrade() {
+            ui.set_importing(false);
+            match result {
+                Ok(list) => ui.set_status_text(format!("Found {} photos", list.len()).into()),
+                Err(err) => ui.set_status_text(format!("Scan failed: {err}").into()),
+            }
+        }
+        let mut guard = state_done.borrow_mut();
+        guard.apply_selection_flags();
+        guard.rebuild_checked_paths();
+    });
+}
+
+fn stage_label(stage: &ImportStage) -> &'static str {
+    match stage {
+        ImportStage::Scanning => "Scanning",
+        ImportStage::Copying => "Copying",
+        ImportStage::Moving => "Moving",
+        ImportStage::Cataloging => "Cataloging",
+        ImportStage::Thumbnailing => "Thumbnails",
+        ImportStage::Keywords => "Keywords",
+    }
+}
+
+fn begin_import(
+    session_path: PathBuf,
+    import_ui: &slint::Weak<ImportPhotosScreen>,
+    state: Rc<RefCell<ImportViewState>>,
+    active_import: Rc<RefCell<Option<ImportPhotosScreen>>>,
+    file_paths: Vec<PathBuf>,
+    keywords: Vec<String>,
+    method: ImportMethod,
+    destination_directory: Option<PathBuf>,
+    allow_duplicates: bool,
+    ui_weak: slint::Weak<MainWindow>,
+    catalog_state: CatalogState,
+    folio_state: Rc<RefCell<FolioState>>,
+    config_store: ConfigStore,
+) {
+    if file_paths.is_empty() {
+        if let Some(ui) = import_ui.upgrade() {
+            ui.set_status_text("Select at least one photo to import".into());
+        }
+        return;
+    }
+
+    if matches!(method, ImportMethod::Copy | ImportMethod::Move) && destination_directory.is_none()
+    {
+        if let Some(ui) = import_ui.upgrade() {
+            ui.set_status_text("Choose a destination for Copy/Move imports".into());
+        }
+        return;
+    }
+
+    let cancel_flag = {
+        let mut guard = state.borrow_mut();
+        guard.import_cancel.cancel();
+        guard.import_cancel = CancellationFlag::default();
+        guard.import_cancel.clone()
+    };
+
+    if let Some(ui) = import_ui.upgrade() {
+        ui.set_importing(true);
+        ui.set_progress(0.0);
+        ui.set_status_text("Importing…".into());
+    }
+
+    let duplicate_strategy = if allow_duplicates {
+        DuplicateStrategy::ImportAnyway
+    } else {
+        DuplicateStrategy::Skip
+    };
+
+    let errors_model = state.borrow().errors.clone();
+    let ui_for_progress = import_ui.clone();
+    let state_for_progress = state.clone();
+    let active_import = active_import.clone();
+
+    let progress_cb = Arc::new(move |progress: ImportProgress| {
+        if let Some(ui) = ui_for_progress.upgrade() {
+            let total = progress.total.max(1);
+            let pct = (progress.completed as f32) / (total as f32);
+            let label = progress
+                .message
+                .as_deref()
+                .unwrap_or(stage_label(&progress.stage));
+            ui.set_progress(pct);
+            ui.set_status_text(
+                format!("{} ({}/{})", label, progress.completed, progress.total).into(),
+            );
+        }
+    });
+
+    let error_ui = import_ui.clone();
+    let on_error_cb = Arc::new(move |path: PathBuf, msg: String| {
+        let msg_clone = msg.clone();
+        let error_text = SharedString::from(format!("{}: {}", path.to_string_lossy(), msg_clone));
+        errors_model.push(error_text);
+        if let Some(ui) = error_ui.upgrade() {
+            ui.set_status_text(format!("Error: {msg_clone}").into());
+        }
+    });
+
+    let callbacks = ImportCallbacks {
+        progress: Some(progress_cb),
+        on_error: Some(on_error_cb),
+        duplicate_strategy,
+        cancel: cancel_flag.clone(),
+    };
+
+    let catalog_state_for_refresh = catalog_state.clone();
+    let folio_state_for_refresh = folio_state.clone();
+    let config_store_for_refresh = config_store.clone();
+    let ui_refresh = ui_weak.clone();
+    let session_path_for_refresh = session_path.clone();
+
+    let ui_done = import_ui.clone();
+    let _ = slint::spawn_local(async move {
+        let db_path = session_path.to_string_lossy().to_string();
+        let service = match CatalogDb::open(&db_path)
+            .map(|db| CatalogService::new(db).with_home_dir(home_dir()))
+        {
+            Ok(service) => service,
+            Err(err) => {
+                if let Some(ui) = ui_done.upgrade() {
+                    ui.set_importing(false);
+                    ui.set_status_text(format!("Unable to open catalog: {err}").into());
+                }
+                return;
+            }
+        };
+
+        let result = import_images_with_callbacks(
+            &service,
+            &file_paths,
+            &keywords,
+            method,
+            destination_directory.clone(),
+            callbacks,
+        )
+        .await;
 
-    let _ = slint::spawn_local(async move {
-        if let Some(handle) = AsyncFileDialog::new()
-            .set_title("New Catalog")
-            .set_file_name("Untitled.sqlite")
-            .add_filter("SQLite Catalog", &["sqlite", "zenithphotocatalog"])
-            .save_file()
-            .await
-        {
-            let requested_path = handle.path().to_path_buf();
-            match create_catalog_service(&requested_path) {
-                Ok((catalog, resolved_path)) => {
-                    apply_loaded_catalog(
-                        catalog,
-                        resolved_path,
-                        &ui_weak,
-                        &catalog_state,
-                        &config_store,
-                        &recent_model,
-                        &folio_state,
+        if let Some(ui) = ui_done.upgrade() {
+            ui.set_importing(false);
+            match result {
+                Ok(report) => {
+                    let mut summary = format!("Imported {}", report.imported);
+                    if !report.duplicates.is_empty() {
+                        summary.push_str(&format!(", skipped {}", report.duplicates.len()));
+                    }
+                    if !report.failed.is_empty() {
+                        summary.push_str(&format!(", {} failed", report.failed.len()));
+                        for (path, msg) in report.failed {
+                            state_for_progress
+                                .borrow()
+                                .errors
+                                .push(format!("{}: {}", path.to_string_lossy(), msg).into());
+                        }
+                    }
+                    if report.canceled {
+                        summary.push_str(" (canceled)");
+                    }
+
+                    let last_import_ts = report.batch_started_at.map(|ts| ts.to_rfc3339());
+                    if let Some(ts) = &last_import_ts {
+                        if let Err(err) =
+                            config_store_for_refresh.record_last_import(&session_path_for_refresh, ts)
+                        {
+                            eprintln!("Failed to persist last import timestamp: {err}");
+                        }
+                    }
+                    if let Some(ts) = last_import_ts {
+                        folio_state_for_refresh
+                            .borrow_mut()
+                            .last_import_timestamp = Some(ts);
+                    }
+                    refresh_folio_tree(&ui_refresh, &catalog_state_for_refresh, &folio_state_for_refresh);
+                    reload_current_selection(
+                        &catalog_state_for_refresh,
+                        &folio_state_for_refresh,
+                        &ui_refresh,
+                        &config_store_for_refresh,
+                    );
+
+                    ui.set_status_text(summary.clone().into());
+                    ui.set_progress(1.0);
+                    ui.hide().ok();
+                    active_import.borrow_mut().take();
+                }
+                Err(err) => {
+                    ui.set_status_text(format!("Import failed: {err}").into());
+                    state_for_progress
+                        .borrow()
+                        .errors
+                        .push(format!("Import failed: {err}").into());
+                }
+            }
+        }
+    });
+}
+
+fn launch_import_flow(
+    ui_weak: &slint::Weak<MainWindow>,
+    catalog_state: &CatalogState,
+    active_import: &Rc<RefCell<Option<ImportPhotosScreen>>>,
+    folio_state: &Rc<RefCell<FolioState>>,
+    config_store: &ConfigStore,
+) {
+    let catalog_path = {
+        let guard = catalog_state.borrow();
+        let Some(session) = guard.as_ref() else {
+            eprintln!("No catalog open; cannot import photos");
+            return;
+        };
+        session.path.clone()
+    };
+
+    let import_ui = match ImportPhotosScreen::new() {
+        Ok(ui) => ui,
+        Err(err) => {
+            eprintln!("Failed to open import window: {err}");
+            return;
+        }
+    };
+
+    import_ui.set_importing(false);
+    import_ui.set_progress(0.0);
+    import_ui.set_status_text("Select a folder to begin".into());
+    import_ui.set_keywords("".into());
+    import_ui.set_destination_directory("".into());
+    import_ui.set_allow_duplicates(false);
+
+    let view_state = Rc::new(RefCell::new(ImportViewState::new()));
+    {
+        let guard = view_state.borrow();
+        import_ui.set_thumbnails(guard.thumbnails.clone().into());
+        import_ui.set_selected_paths(guard.selected_paths.clone().into());
+        import_ui.set_error_messages(guard.errors.clone().into());
+        import_ui.set_directories(guard.directories.clone().into());
+    }
+
+    let import_ui_weak = import_ui.as_weak();
+
+    // Prefill with the user's home directory for convenience.
+    if let Some(home) = home_dir() {
+        refresh_directory_model(&view_state.borrow().directories, &home);
+        import_ui.set_selected_directory(home.to_string_lossy().to_string().into());
+    }
+
+    {
+        let import_ui_weak = import_ui_weak.clone();
+        let view_state = view_state.clone();
+        let catalog_path = catalog_path.clone();
+        import_ui.on_browse_for_directory(move || {
+            let import_ui_weak = import_ui_weak.clone();
+            let view_state = view_state.clone();
+            let catalog_path = catalog_path.clone();
+            let _ = slint::spawn_local(async move {
+                if let Some(handle) = AsyncFileDialog::new().pick_folder().await {
+                    start_scan_for_directory(
+                        &import_ui_weak,
+                        &view_state,
+                        catalog_path.clone(),
+                        handle.path().to_path_buf(),
                     );
                 }
-                Err(err) => eprintln!("Failed to create catalog: {err}"),
-            }
-        }
-    });
-}
+            });
+        });
+    }
+
+    {
+        let import_ui_weak = import_ui_weak.clone();
+        let view_state = view_state.clone();
+        let catalog_path = catalog_path.clone();
+        import_ui.on_directory_selected(move |path| {
+            start_scan_for_directory(
+                &import_ui_weak,
+                &view_state,
+                catalog_path.clone(),
+                PathBuf::from(path.as_str()),
+            );
+        });
+    }
+
+    {
+        let import_ui_weak = import_ui_weak.clone();
+        import_ui.on_browse_for_destination(move || {
+            let import_ui_weak = import_ui_weak.clone();
+            let _ = slint::spawn_local(async move {
+                if let Some(handle) = AsyncFileDialog::new().pick_folder().await {
+                    if let Some(ui) = import_ui_weak.upgrade() {
+                        ui.set_destination_directory(
+                            handle.path().to_string_lossy().to_string().into(),
+                        );
+                    }
+                }
+            });
+        });
+    }
+
+    {
+        let view_state = view_state.clone();
+        import_ui.on_select_all_requested(move |all_selected| {
+            set_all_checked(&view_state, all_selected);
+        });
+    }
+
+    {
+        let view_state = view_state.clone();
+        import_ui.on_thumbnail_clicked(move |image_id, shift, ctrl| {
+            if shift {
+                select_range(&view_state, image_id);
+            } else if ctrl {
+                toggle_single(&view_state, image_id);
+            } else {
+                select_single(&view_state, image_id);
+            }
+        });
+    }
+
+    {
+        let view_state = view_state.clone();
+        import_ui.on_checkbox_clicked(move |checked| {
+            apply_checkbox_to_selection(&view_state, checked);
+        });
+    }
+
+    {
+        let view_state = view_state.clone();
+        import_ui.on_keyboard_select_all(move || {
+            select_all_allowed(&view_state);
+        });
+    }
+
+    {
+        let view_state = view_state.clone();
+        import_ui.on_keyboard_clear_selection(move || {
+            clear_selection(&view_state);
+        });
+    }
+
+    {
+        let import_ui_weak = import_ui_weak.clone();
+        let view_state = view_state.clone();
+        let active_import = active_import.clone();
+        import_ui.on_cancel_import(move || {
+            {
+                let guard = view_state.borrow();
+                guard.scan_cancel.cancel();
+                guard.import_cancel.cancel();
+            }
+            if let Some(ui) = import_ui_weak.upgrade() {
+                ui.hide().ok();
+            }
+            active_import.borrow_mut().take();
+        });
+    }
+
+    {
+        let import_ui_weak = import_ui_weak.clone();
+        let view_state = view_state.clone();
+        let active_import = active_import.clone();
+        let catalog_path = catalog_path.clone();
+        let catalog_state = catalog_state.clone();
+        let folio_state = folio_state.clone();
+        let config_store = config_store.clone();
+        let ui_main = ui_weak.clone();
+        import_ui.on_perform_import(
+            move |_source_dir, destination_dir, paths, keyword_text, method_str| {
+                let allow_duplicates = import_ui_weak
+                    .upgrade()
+                    .map(|ui| ui.get_allow_duplicates())
+                    .unwrap_or(false);
+                let mut seen_files = HashSet::new();
+                let mut files: Vec<PathBuf> = Vec::new();
+                for p in paths.iter() {
+                    let path_buf = PathBuf::from(p.as_str());
+                    if seen_files.insert(path_buf.clone()) {
+                        files.push(path_buf);
+                    }
+                }
+                let keywords = parse_keywords(keyword_text.as_str());
+                let method = match method_str.as_str() {
+                    "Copy" => ImportMethod::Copy,
+                    "Move" => ImportMethod::Move,
+                    _ => ImportMethod::Add,
+                };
+                let destination = match method {
+                    ImportMethod::Copy | ImportMethod::Move => {
+                        if destination_dir.is_empty() {
+                            None
+                        } else {
+                            Some(PathBuf::from(destination_dir.as_str()))
+                        }
+                    }
+                    ImportMethod::Add => None,
+                };
+                begin_import(
+                    catalog_path.clone(),
+                    &import_ui_weak,
+                    view_state.clone(),
+                    active_import.clone(),
+                    files,
+                    keywords,
+                    method,
+                    destination,
+                    allow_duplicates,
+                    ui_main.clone(),
+                    catalog_state.clone(),
+                    folio_state.clone(),
+                    config_store.clone(),
+                );
+            },
+        );
+    }
 
-fn load_catalog_from_path(
-    selected_path: PathBuf,
-    ui_weak: &slint::Weak<MainWindow>,
-    catalog_state: &CatalogState,
-    config_store: &ConfigStore,
-    recent_model: &Rc<VecModel<SharedString>>,
-    folio_state: &Rc<RefCell<FolioState>>,
-) -> Result<(), String> {
-    let normalized_path = CatalogPath::new(&selected_path).into_path();
-    if !normalized_path.exists() {
-        return Err(format!(
-            "Catalog '{}' does not exist",
-            normalized_path.to_string_lossy()
-        ));
+    if let Some(ui) = ui_weak.upgrade() {
+        ui.set_status_text("Import window opened".into());
     }
 
-    let (catalog, resolved_path) = open_catalog_service(&normalized_path)
-        .map_err(|err| format!("Unable to open catalog: {err}"))?;
-    apply_loaded_catalog(
-        catalog,
-        resolved_path,
-        ui_weak,
-        catalog_state,
-        config_store,
-        recent_model,
-        folio_state,
-    );
-    Ok(())
+    import_ui.show().ok();
+    *active_import.borrow_mut() = Some(import_ui);
 }
 
-fn apply_loaded_catalog(
-    catalog: CatalogService,
-    path: PathBuf,
+fn open_export_dialog(
     ui_weak: &slint::Weak<MainWindow>,
     catalog_state: &CatalogState,
-    config_store: &ConfigStore,
-    recent_model: &Rc<VecModel<SharedString>>,
     folio_state: &Rc<RefCell<FolioState>>,
+    engine: &Arc<ImageEngine>,
+    active_export: &Rc<RefCell<Option<ExportDialog>>>,
 ) {
-    *catalog_state.borrow_mut() = Some(CatalogSession::new(catalog, path.clone()));
-
-    if let Err(err) = Catalog::set_last_used(&path) {
-        eprintln!("Failed to persist last catalog path: {err}");
+    if let Some(existing) = active_export.borrow().as_ref() {
+        existing.show().ok();
+        return;
     }
 
-    match config_store.record_catalog(&path) {
-        Ok(snapshot) => refresh_recent_model(recent_model, &snapshot.recent_catalogs),
-        Err(err) => eprintln!("Failed to update catalog history: {err}"),
+    let image_ids: Vec<i64> = folio_state
+        .borrow()
+        .selection
+        .iter()
+        .map(|id| *id as i64)
+        .collect();
+    let catalog_path = match catalog_state.borrow().as_ref() {
+        Some(session) => session.path.clone(),
+        None => return,
+    };
+    if image_ids.is_empty() {
+        if let Some(ui) = ui_weak.upgrade() {
+            ui.set_status_text("Select photos to export".into());
+        }
+        return;
     }
 
-    let display_path = path.to_string_lossy().to_string();
-    let catalog_display_name = path
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .unwrap_or_default()
-        .to_string();
+    let dialog = match ExportDialog::new() {
+        Ok(dialog) => dialog,
+        Err(err) => {
+            eprintln!("Failed to open export dialog: {err}");
+            return;
+        }
+    };
+    dialog.set_image_count(image_ids.len() as i32);
+    refresh_preset_names(&dialog, catalog_state);
+    refresh_watermark_names(&dialog, catalog_state);
+
+    let dialog_weak = dialog.as_weak();
+    let cancel_flag = Rc::new(RefCell::new(CancellationFlag::default()));
+    let active_watermarks: Rc<RefCell<Option<WatermarkDialog>>> = Rc::new(RefCell::new(None));
+
     {
-        let mut guard = folio_state.borrow_mut();
-        guard.last_import_timestamp = config_store.last_import_timestamp(&path);
+        let dialog_weak = dialog_weak.clone();
+        let catalog_state = catalog_state.clone();
+        let active_watermarks = active_watermarks.clone();
+        dialog.on_manage_watermarks(move || {
+            open_watermark_dialog(&dialog_weak, &catalog_state, &active_watermarks);
+        });
     }
-    slint::invoke_from_event_loop({
-        let ui_weak = ui_weak.clone();
-        move || {
-            if let Some(ui) = ui_weak.upgrade() {
-                ui.set_current_path(display_path.clone().into());
-                ui.set_catalog_name(catalog_display_name.clone().into());
+
+    {
+        let dialog_weak = dialog_weak.clone();
+        let catalog_state = catalog_state.clone();
+        dialog.on_preset_selected(move |name| {
+            let Some(dialog) = dialog_weak.upgrade() else {
+                return;
+            };
+            let found = match catalog_state.borrow().as_ref() {
+                Some(session) => session.service.find_export_preset(&name),
+                None => return,
+            };
+            match found {
+                Ok(Some(preset)) => {
+                    apply_preset_to_dialog(&dialog, &preset);
+                    dialog.set_status_text(format!("Loaded preset {}", preset.name).into());
+                }
+                Ok(None) => {
+                    dialog.set_status_text(format!("Preset {name} no longer exists").into())
+                }
+                Err(err) => dialog.set_status_text(format!("Failed to load preset: {err}").into()),
             }
-        }
-    })
-    .ok();
+        });
+    }
 
-    refresh_folio_tree(ui_weak, catalog_state, folio_state);
-    restore_last_selection(ui_weak, catalog_state, folio_state, config_store);
-}
+    {
+        let dialog_weak = dialog_weak.clone();
+        let catalog_state = catalog_state.clone();
+        dialog.on_save_preset(move |name| {
+            let Some(dialog) = dialog_weak.upgrade() else {
+                return;
+            };
+            let name = name.trim().to_string();
+            let destination = dialog.get_destination();
+            let result = export_settings_from_dialog(&dialog).and_then(|settings| {
+                let preset = NamedExportPreset {
+                    name: name.clone(),
+                    destination: (!destination.is_empty())
+                        .then(|| PathBuf::from(destination.as_str())),
+                    settings,
+                };
+                match catalog_state.borrow().as_ref() {
+                    Some(session) => session.service.save_export_preset(&preset),
+                    None => Err(anyhow!("no catalog is open")),
+                }
+            });
+            match result {
+                Ok(()) => {
+                    refresh_preset_names(&dialog, &catalog_state);
+                    dialog.set_selected_preset(name.clone().into());
+                    dialog.set_status_text(format!("Saved preset {name}").into());
+                }
+                Err(err) => dialog.set_status_text(format!("Failed to save preset: {err}").into()),
+            }
+        });
+    }
 
-type ImageId = i32;
+    {
+        let dialog_weak = dialog_weak.clone();
+        let catalog_state = catalog_state.clone();
+        dialog.on_delete_preset(move |name| {
+            let Some(dialog) = dialog_weak.upgrade() else {
+                return;
+            };
+            let result = match catalog_state.borrow().as_ref() {
+                Some(session) => session.service.delete_export_preset(&name),
+                None => return,
+            };
+            if let Err(err) = result {
+                dialog.set_status_text(format!("Failed to delete preset: {err}").into());
+                return;
+            }
+            refresh_preset_names(&dialog, &catalog_state);
+            dialog.set_selected_preset("".into());
+            dialog.set_status_text(format!("Deleted preset {name}").into());
+        });
+    }
 
-/// Keeps UI selection (highlighted thumbnails) independent from checkbox state.
-/// The selected_ids set mirrors the visible highlight, while anchor_id preserves
-/// the last focused item for range selection.
-#[derive(Default)]
-struct SelectionState {
-    selected_ids: HashSet<ImageId>,
-    anchor_id: Option<ImageId>,
-}
+    {
+        let dialog_weak = dialog_weak.clone();
+        let catalog_state = catalog_state.clone();
+        dialog.on_import_presets(move || {
+            let dialog_weak = dialog_weak.clone();
+            let catalog_state = catalog_state.clone();
+            let _ = slint::spawn_local(async move {
+                let Some(handle) = AsyncFileDialog::new()
+                    .set_title("Import Export Presets")
+                    .add_filter("Export Presets", &["json"])
+                    .pick_file()
+                    .await
+                else {
+                    return;
+                };
+                let Some(dialog) = dialog_weak.upgrade() else {
+                    return;
+                };
+                let result = match catalog_state.borrow().as_ref() {
+                    Some(session) => session.service.import_export_presets(handle.path()),
+                    None => return,
+                };
+                match result {
+                    Ok(names) => {
+                        refresh_preset_names(&dialog, &catalog_state);
+                        dialog
+                            .set_status_text(format!("Imported {} preset(s)", names.len()).into());
+                    }
+                    Err(err) => {
+                        dialog.set_status_text(format!("Failed to import presets: {err}").into())
+                    }
+                }
+            });
+        });
+    }
 
-struct ImportViewState {
-    thumbnails: Rc<VecModel<ImportThumbnail>>,
-    /// Paths that are currently marked for import (checked=true).
-    selected_paths: Rc<VecModel<SharedString>>,
-    errors: Rc<VecModel<SharedString>>,
-    directories: Rc<VecModel<SharedString>>,
-    scan_cancel: CancellationFlag,
-    import_cancel: CancellationFlag,
-    /// Tracks highlighted selection separately from import checkboxes.
-    selection: SelectionState,
-    next_image_id: ImageId,
-}
+    {
+        let dialog_weak = dialog_weak.clone();
+        let catalog_state = catalog_state.clone();
+        dialog.on_export_presets(move || {
+            let dialog_weak = dialog_weak.clone();
+            let catalog_state = catalog_state.clone();
+            let _ = slint::spawn_local(async move {
+                let Some(handle) = AsyncFileDialog::new()
+                    .set_title("Share Export Presets")
+                    .set_file_name("export-presets.json")
+                    .add_filter("Export Presets", &["json"])
+                    .save_file()
+                    .await
+                else {
+                    return;
+                };
+                let Some(dialog) = dialog_weak.upgrade() else {
+                    return;
+                };
+                let result = match catalog_state.borrow().as_ref() {
+                    Some(session) => session.service.write_export_presets(handle.path(), &[]),
+                    None => return,
+                };
+                let status = match result {
+                    Ok(count) => format!("Wrote {count} preset(s) to {}", handle.path().display()),
+                    Err(err) => format!("Failed to write presets: {err}"),
+                };
+                dialog.set_status_text(status.into());
+            });
+        });
+    }
 
-impl ImportViewState {
-    fn new() -> Self {
-        Self {
-            thumbnails: Rc::new(VecModel::default()),
-            selected_paths: Rc::new(VecModel::default()),
-            errors: Rc::new(VecModel::default()),
-            directories: Rc::new(VecModel::default()),
-            scan_cancel: CancellationFlag::default(),
-            import_cancel: CancellationFlag::default(),
-            selection: SelectionState::default(),
-            next_image_id: 1,
-        }
+    {
+        let dialog_weak = dialog_weak.clone();
+        dialog.on_choose_destination(move || {
+            let dialog_weak = dialog_weak.clone();
+            let _ = slint::spawn_local(async move {
+                if let Some(handle) = AsyncFileDialog::new()
+                    .set_title("Export To")
+                    .pick_folder()
+                    .await
+                {
+                    if let Some(dialog) = dialog_weak.upgrade() {
+                        dialog.set_destination(handle.path().to_string_lossy().to_string().into());
+                    }
+                }
+            });
+        });
     }
 
-    fn reset_for_scan(&mut self) {
-        self.thumbnails.set_vec(Vec::new());
-        self.selected_paths.set_vec(Vec::new());
-        self.errors.set_vec(Vec::new());
-        self.scan_cancel.cancel();
-        self.scan_cancel = CancellationFlag::default();
-        self.selection = SelectionState::default();
-        self.next_image_id = 1;
+    {
+        let dialog_weak = dialog_weak.clone();
+        let cancel_flag = cancel_flag.clone();
+        let ui_weak = ui_weak.clone();
+        let engine = engine.clone();
+        dialog.on_start_export(move || {
+            let Some(dialog) = dialog_weak.upgrade() else {
+                return;
+            };
+            let settings = match export_settings_from_dialog(&dialog) {
+                Ok(settings) => settings,
+                Err(err) => {
+                    dialog.set_status_text(err.to_string().into());
+                    return;
+                }
+            };
+            let destination = PathBuf::from(dialog.get_destination().as_str());
+
+            let cancel = CancellationFlag::default();
+            *cancel_flag.borrow_mut() = cancel.clone();
+            dialog.set_exporting(true);
+            dialog.set_progress(0.0);
+            dialog.set_status_text("Exporting…".into());
+
+            let catalog_path = catalog_path.clone();
+            let image_ids = image_ids.clone();
+            let engine = engine.clone();
+            let dialog_weak = dialog_weak.clone();
+            let ui_weak = ui_weak.clone();
+            std::thread::spawn(move || {
+                let progress_weak = dialog_weak.clone();
+                let callbacks = ExportCallbacks {
+                    progress: Some(Arc::new(move |progress: ExportProgress| {
+                        let pct = progress.completed as f32 / progress.total.max(1) as f32;
+                        let label = progress.message.unwrap_or_default();
+                        let _ = progress_weak.upgrade_in_event_loop(move |dialog| {
+                            dialog.set_progress(pct);
+                            if !label.is_empty() {
+                                dialog.set_status_text(label.into());
+                            }
+                        });
+                    })),
+                    on_error: Some(Arc::new(|image_id, err| {
+                        eprintln!("Export failed for image {image_id}: {err}");
+                    })),
+                    cancel,
+                };
+
+                let result = CatalogDb::open(&catalog_path.to_string_lossy())
+                    .map(CatalogService::new)
+                    .and_then(|service| {
+                        export_images(
+                            &service,
+                            &engine,
+                            &image_ids,
+                            &destination,
+                            &settings,
+                            callbacks,
+                        )
+                    });
+
+                let summary = match result {
+                    Ok(report) => {
+                        let mut summary = format!(
+                            "Exported {} of {} photo(s) to {}",
+                            report.exported.len(),
+                            image_ids.len(),
+                            destination.display()
+                        );
+                        if !report.failed.is_empty() {
+                            summary.push_str(&format!(", {} failed", report.failed.len()));
+                        }
+                        if !report.warnings.is_empty() {
+                            summary.push_str(&format!(
+                                ", {} with keywords left out",
+                                report.warnings.len()
+                            ));
+                        }
+                        if report.canceled {
+                            summary.push_str(" (canceled)");
+                        }
+                        summary
+                    }
+                    Err(err) => format!("Export failed: {err}"),
+                };
+
+                let status = summary.clone();
+                let _ = dialog_weak.upgrade_in_event_loop(move |dialog| {
+                    dialog.set_exporting(false);
+                    dialog.set_progress(1.0);
+                    dialog.set_status_text(summary.into());
+                });
+                let _ = ui_weak.upgrade_in_event_loop(move |ui| {
+                    ui.set_status_text(status.into());
+                });
+            });
+        });
     }
 
-    fn is_selectable_id(&self, id: ImageId) -> bool {
-        self.index_of(id)
-            .and_then(|idx| self.thumbnails.row_data(idx))
-            .map(|thumb| thumb.selectable)
-            .unwrap_or(false)
+    {
+        let cancel_flag = cancel_flag.clone();
+        dialog.on_cancel_export(move || {
+            cancel_flag.borrow().cancel();
+        });
     }
 
-    fn index_of(&self, id: ImageId) -> Option<usize> {
-        for idx in 0..self.thumbnails.row_count() {
-            if let Some(thumb) = self.thumbnails.row_data(idx) {
-                if thumb.id == id {
-                    return Some(idx);
-                }
+    {
+        let active_export = active_export.clone();
+        dialog.on_close(move || {
+            cancel_flag.borrow().cancel();
+            if let Some(watermarks) = active_watermarks.borrow_mut().take() {
+                watermarks.hide().ok();
             }
-        }
-        None
+            if let Some(dialog) = active_export.borrow_mut().take() {
+                dialog.hide().ok();
+            }
+        });
     }
 
-    fn apply_selection_flags(&mut self) {
-        // Keep selection in sync with the model and drop anything non-selectable.
-        let mut invalid = Vec::new();
-        for id in &self.selection.selected_ids {
-            if !self.is_selectable_id(*id) {
-                invalid.push(*id);
-            }
-        }
-        for id in invalid {
-            self.selection.selected_ids.remove(&id);
-        }
+    dialog.show().ok();
+    *active_export.borrow_mut() = Some(dialog);
+}
 
-        if let Some(anchor) = self.selection.anchor_id {
-            if !self.is_selectable_id(anchor) {
-                self.selection.anchor_id = self.selection.selected_ids.iter().next().copied();
-            }
-        }
+fn refresh_preset_names(dialog: &ExportDialog, catalog_state: &CatalogState) {
+    let presets = match catalog_state.borrow().as_ref() {
+        Some(session) => session.service.list_export_presets().unwrap_or_else(|err| {
+            eprintln!("Failed to list export presets: {err}");
+            Vec::new()
+        }),
+        None => Vec::new(),
+    };
+    let names: Vec<SharedString> = presets
+        .into_iter()
+        .map(|preset| preset.name.into())
+        .collect();
+    dialog.set_preset_names(Rc::new(VecModel::from(names)).into());
+}
 
-        for idx in 0..self.thumbnails.row_count() {
-            if let Some(mut thumb) = self.thumbnails.row_data(idx) {
-                let is_selected = self.selection.selected_ids.contains(&thumb.id);
-                if thumb.selected != is_selected {
-                    thumb.selected = is_selected;
-                    self.thumbnails.set_row_data(idx, thumb);
+fn apply_preset_to_dialog(dialog: &ExportDialog, preset: &NamedExportPreset) {
+    let settings = &preset.settings;
+    dialog.set_format(
+        match settings.format {
+            ExportFormat::Jpeg => "JPEG",
+            ExportFormat::Png => "PNG",
+            ExportFormat::Tiff => "TIFF",
+        }
+        .into(),
+    );
+    dialog.set_quality(settings.quality as i32);
+    dialog.set_sixteen_bit(settings.bit_depth == BitDepth::Sixteen);
+    let (mode, value) = match settings.resize {
+        ResizeMode::Original => ("Original Size", String::new()),
+        ResizeMode::LongEdge { pixels } => ("Long Edge (px)", pixels.to_string()),
+        ResizeMode::Megapixels { megapixels } => ("Megapixels", megapixels.to_string()),
+        ResizeMode::FitBox { width, height } => ("Fit Box (WxH)", format!("{width}x{height}")),
+    };
+    dialog.set_resize_mode(mode.into());
+    dialog.set_resize_value(value.into());
+    dialog.set_filename_template(settings.filename_template.clone().into());
+    dialog.set_overwrite(settings.overwrite);
+    match settings.sharpening {
+        Some(sharpening) => {
+            dialog.set_sharpen_for(
+                match sharpening.medium {
+                    SharpenMedium::Screen => "Screen",
+                    SharpenMedium::MattePaper => "Matte Paper",
+                    SharpenMedium::GlossyPaper => "Glossy Paper",
                 }
-            }
+                .into(),
+            );
+            dialog.set_sharpen_amount(
+                match sharpening.amount {
+                    SharpenAmount::Low => "Low",
+                    SharpenAmount::Standard => "Standard",
+                    SharpenAmount::High => "High",
+                }
+                .into(),
+            );
+            dialog.set_print_ppi(sharpening.ppi as i32);
+        }
+        None => dialog.set_sharpen_for("None".into()),
+    }
+    dialog.set_metadata_policy(
+        match settings.metadata {
+            MetadataPolicy::Strip => "None",
+            MetadataPolicy::CopyrightOnly => "Copyright & Contact Only",
+            MetadataPolicy::AllExceptLocation => "All Except Location & Serial",
+            MetadataPolicy::All => "All Metadata",
         }
+        .into(),
+    );
+    let rights = &settings.rights;
+    dialog.set_creator(rights.creator.clone().unwrap_or_default().into());
+    dialog.set_copyright(rights.copyright.clone().unwrap_or_default().into());
+    dialog.set_contact(rights.contact.clone().unwrap_or_default().into());
+    dialog.set_watermark(settings.watermark.as_deref().unwrap_or("None").into());
+    if let Some(destination) = &preset.destination {
+        dialog.set_destination(destination.to_string_lossy().to_string().into());
     }
+}
 
-    fn rebuild_checked_paths(&self) {
-        let mut paths = Vec::new();
-        let mut seen = HashSet::new();
-        for idx in 0..self.thumbnails.row_count() {
-            if let Some(thumb) = self.thumbnails.row_data(idx) {
-                if thumb.checked && thumb.selectable && seen.insert(thumb.path.clone()) {
-                    paths.push(thumb.path);
-                }
+fn export_settings_from_dialog(dialog: &ExportDialog) -> anyhow::Result<ExportSettings> {
+    let raw = dialog.get_resize_value().trim().to_string();
+    let resize = match dialog.get_resize_mode().as_str() {
+        "Long Edge (px)" => ResizeMode::LongEdge {
+            pixels: raw
+                .parse()
+                .context("long edge must be a whole number of pixels")?,
+        },
+        "Megapixels" => ResizeMode::Megapixels {
+            megapixels: raw.parse().context("megapixels must be a number")?,
+        },
+        "Fit Box (WxH)" => {
+            let (w, h) = raw
+                .split_once(['x', 'X'])
+                .context("fit box must look like 1920x1080")?;
+            ResizeMode::FitBox {
+                width: w.trim().parse().context("invalid box width")?,
+                height: h.trim().parse().context("invalid box height")?,
             }
         }
-        self.selected_paths.set_vec(paths);
-    }
+        _ => ResizeMode::Original,
+    };
+
+    Ok(ExportSettings {
+        format: match dialog.get_format().as_str() {
+            "PNG" => ExportFormat::Png,
+            "TIFF" => ExportFormat::Tiff,
+            _ => ExportFormat::Jpeg,
+        },
+        quality: dialog.get_quality().clamp(1, 100) as u8,
+        bit_depth: if dialog.get_sixteen_bit() {
+            BitDepth::Sixteen
+        } else {
+            BitDepth::Eight
+        },
+        resize,
+        filename_template: dialog.get_filename_template().to_string(),
+        overwrite: dialog.get_overwrite(),
+        sharpening: sharpening_from_dialog(dialog),
+        metadata: match dialog.get_metadata_policy().as_str() {
+            "None" => MetadataPolicy::Strip,
+            "Copyright & Contact Only" => MetadataPolicy::CopyrightOnly,
+            "All Metadata" => MetadataPolicy::All,
+            _ => MetadataPolicy::AllExceptLocation,
+        },
+        rights: RightsInfo {
+            creator: non_empty_text(dialog.get_creator()),
+            copyright: non_empty_text(dialog.get_copyright()),
+            contact: non_empty_text(dialog.get_contact()),
+        },
+        watermark: match dialog.get_watermark().as_str() {
+            "None" => None,
+            name => non_empty_text(name.into()),
+        },
+    })
 }
 
-fn placeholder_image() -> slint::Image {
-    let mut buf = SharedPixelBuffer::<Rgba8Pixel>::new(1, 1);
-    buf.make_mut_bytes().fill(0);
-    slint::Image::from_rgba8(buf)
+fn sharpening_from_dialog(dialog: &ExportDialog) -> Option<OutputSharpening> {
+    let medium = match dialog.get_sharpen_for().as_str() {
+        "Screen" => SharpenMedium::Screen,
+        "Matte Paper" => SharpenMedium::MattePaper,
+        "Glossy Paper" => SharpenMedium::GlossyPaper,
+        _ => return None,
+    };
+    Some(OutputSharpening {
+        medium,
+        amount: match dialog.get_sharpen_amount().as_str() {
+            "Low" => SharpenAmount::Low,
+            "High" => SharpenAmount::High,
+            _ => SharpenAmount::Standard,
+        },
+        ppi: dialog.get_print_ppi().clamp(72, 2400) as u32,
+    })
 }
 
-fn refresh_directory_model(model: &Rc<VecModel<SharedString>>, base: &Path) {
-    let mut entries = vec![SharedString::from(base.to_string_lossy().to_string())];
-    if let Ok(read_dir) = fs::read_dir(base) {
-        for entry in read_dir.flatten() {
-            if entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false) {
-                entries.push(SharedString::from(
-                    entry.path().to_string_lossy().to_string(),
-                ));
-            }
-        }
-    }
-    model.set_vec(entries);
+fn non_empty_text(value: SharedString) -> Option<String> {
+    let trimmed = value.trim();
+    (!trimmed.is_empty()).then(|| trimmed.to_string())
 }
 
-fn select_single(state: &Rc<RefCell<ImportViewState>>, id: ImageId) {
-    let mut guard = state.borrow_mut();
-    if !guard.is_selectable_id(id) {
-        return;
+fn refresh_watermark_names(dialog: &ExportDialog, catalog_state: &CatalogState) {
+    let names = watermark_choices(catalog_state);
+    if !names.contains(&dialog.get_watermark()) {
+        dialog.set_watermark("None".into());
     }
-    guard.selection.selected_ids.clear();
-    guard.selection.selected_ids.insert(id);
-    guard.selection.anchor_id = Some(id);
-    guard.apply_selection_flags();
+    dialog.set_watermark_names(Rc::new(VecModel::from(names)).into());
 }
 
-fn select_range(state: &Rc<RefCell<ImportViewState>>, id: ImageId) {
-    let mut guard = state.borrow_mut();
-    if !guard.is_selectable_id(id) {
+/// "None" followed by the saved watermark names, for watermark pickers.
+fn watermark_choices(catalog_state: &CatalogState) -> Vec<SharedString> {
+    let watermarks = match catalog_state.borrow().as_ref() {
+        Some(session) => session.service.list_watermarks().unwrap_or_else(|err| {
+            eprintln!("Failed to list watermarks: {err}");
+            Vec::new()
+        }),
+        None => Vec::new(),
+    };
+    std::iter::once("None".into())
+        .chain(watermarks.into_iter().map(|w| w.name.into()))
+        .collect()
+}
+
+fn open_gallery_dialog(
+    ui_weak: &slint::Weak<MainWindow>,
+    catalog_state: &CatalogState,
+    folio_state: &Rc<RefCell<FolioState>>,
+    engine: &Arc<ImageEngine>,
+    active_gallery: &Rc<RefCell<Option<GalleryDialog>>>,
+) {
+    if let Some(existing) = active_gallery.borrow().as_ref() {
+        existing.show().ok();
         return;
     }
 
-    let Some(anchor) = guard.selection.anchor_id else {
-        guard.selection.selected_ids.clear();
-        guard.selection.selected_ids.insert(id);
-        guard.selection.anchor_id = Some(id);
-        guard.apply_selection_flags();
-        return;
+    let image_ids: Vec<i64> = folio_state
+        .borrow()
+        .selection
+        .iter()
+        .map(|id| *id as i64)
+        .collect();
+    let catalog_path = match catalog_state.borrow().as_ref() {
+        Some(session) => session.path.clone(),
+        None => return,
     };
-
-    let Some(anchor_idx) = guard.index_of(anchor) else {
-        guard.selection.selected_ids.clear();
-        guard.selection.selected_ids.insert(id);
-        guard.selection.anchor_id = Some(id);
-        guard.apply_selection_flags();
+    if image_ids.is_empty() {
+        if let Some(ui) = ui_weak.upgrade() {
+            ui.set_status_text("Select photos for the gallery".into());
+        }
         return;
-    };
+    }
 
-    let Some(target_idx) = guard.index_of(id) else {
-        return;
+    let dialog = match GalleryDialog::new() {
+        Ok(dialog) => dialog,
+        Err(err) => {
+            eprintln!("Failed to open gallery dialog: {err}");
+            return;
+        }
     };
+    dialog.set_image_count(image_ids.len() as i32);
+    dialog.set_watermark_names(Rc::new(VecModel::from(watermark_choices(catalog_state))).into());
 
-    let (start, end) = if anchor_idx <= target_idx {
-        (anchor_idx, target_idx)
-    } else {
-        (target_idx, anchor_idx)
-    };
+    let dialog_weak = dialog.as_weak();
+    let cancel_flag = Rc::new(RefCell::new(CancellationFlag::default()));
 
-    guard.selection.selected_ids.clear();
-    for idx in start..=end {
-        if let Some(thumb) = guard.thumbnails.row_data(idx) {
-            if thumb.selectable {
-                guard.selection.selected_ids.insert(thumb.id);
-            }
-        }
+    {
+        let dialog_weak = dialog_weak.clone();
+        dialog.on_choose_destination(move || {
+            let dialog_weak = dialog_weak.clone();
+            let _ = slint::spawn_local(async move {
+                if let Some(handle) = AsyncFileDialog::new()
+                    .set_title("Create Gallery In")
+                    .pick_folder()
+                    .await
+                {
+                    if let Some(dialog) = dialog_weak.upgrade() {
+                        dialog.set_destination(handle.path().to_string_lossy().to_string().into());
+                    }
+                }
+            });
+        });
     }
-    guard.selection.anchor_id = Some(id);
-    guard.apply_selection_flags();
-}
 
-fn toggle_single(state: &Rc<RefCell<ImportViewState>>, id: ImageId) {
-    let mut guard = state.borrow_mut();
-    if !guard.is_selectable_id(id) {
-        return;
+    {
+        let dialog_weak = dialog_weak.clone();
+        let cancel_flag = cancel_flag.clone();
+        let ui_weak = ui_weak.clone();
+        let engine = engine.clone();
+        dialog.on_start_gallery(move || {
+            let Some(dialog) = dialog_weak.upgrade() else {
+                return;
+            };
+            let settings = GallerySettings {
+                title: dialog.get_gallery_title().trim().to_string(),
+                template: match dialog.get_template().as_str() {
+                    "Dark" => GalleryTemplate::Dark,
+                    "Minimal" => GalleryTemplate::Minimal,
+                    _ => GalleryTemplate::Classic,
+                },
+                image_size: dialog.get_image_size().max(1) as u32,
+                thumbnail_size: dialog.get_thumbnail_size().max(1) as u32,
+                quality: dialog.get_quality().clamp(1, 100) as u8,
+                captions: GalleryCaptions {
+                    filename: dialog.get_caption_filename(),
+                    keywords: dialog.get_caption_keywords(),
+                    rating: dialog.get_caption_rating(),
+                },
+                watermark: match dialog.get_watermark().as_str() {
+                    "None" => None,
+                    name => Some(name.to_string()),
+                },
+                ..GallerySettings::default()
+            };
+            let destination = PathBuf::from(dialog.get_destination().as_str());
+
+            let cancel = CancellationFlag::default();
+            *cancel_flag.borrow_mut() = cancel.clone();
+            dialog.set_generating(true);
+            dialog.set_progress(0.0);
+            dialog.set_status_text("Creating gallery…".into());
+
+            let catalog_path = catalog_path.clone();
+            let image_ids = image_ids.clone();
+            let engine = engine.clone();
+            let dialog_weak = dialog_weak.clone();
+            let ui_weak = ui_weak.clone();
+            std::thread::spawn(move || {
+                let progress_weak = dialog_weak.clone();
+                let callbacks = ExportCallbacks {
+                    progress: Some(Arc::new(move |progress: ExportProgress| {
+                        let pct = progress.completed as f32 / progress.total.max(1) as f32;
+                        let label = progress.message.unwrap_or_default();
+                        let _ = progress_weak.upgrade_in_event_loop(move |dialog| {
+                            dialog.set_progress(pct);
+                            if !label.is_empty() {
+                                dialog.set_status_text(label.into());
+                            }
+                        });
+                    })),
+                    on_error: Some(Arc::new(|image_id, err| {
+                        eprintln!("Gallery image {image_id} failed: {err}");
+                    })),
+                    cancel,
+                };
+
+                let result = CatalogDb::open(&catalog_path.to_string_lossy())
+                    .map(CatalogService::new)
+                    .and_then(|service| {
+                        generate_gallery(
+                            &service,
+                            &engine,
+                            &image_ids,
+                            &destination,
+                            &settings,
+                            callbacks,
+                        )
+                    });
+
+                let summary = match result {
+                    Ok(report) => {
+                        let mut summary = format!(
+                            "Gallery with {} photo(s) written to {}",
+                            report.images,
+                            report.index.display()
+                        );
+                        if !report.failed.is_empty() {
+                            summary.push_str(&format!(", {} failed", report.failed.len()));
+                        }
+                        if report.canceled {
+                            summary.push_str(" (canceled)");
+                        }
+                        summary
+                    }
+                    Err(err) => format!("Gallery failed: {err}"),
+                };
+
+                let status = summary.clone();
+                let _ = dialog_weak.upgrade_in_event_loop(move |dialog| {
+                    dialog.set_generating(false);
+                    dialog.set_progress(1.0);
+                    dialog.set_status_text(summary.into());
+                });
+                let _ = ui_weak.upgrade_in_event_loop(move |ui| {
+                    ui.set_status_text(status.into());
+                });
+            });
+        });
     }
 
-    if !guard.selection.selected_ids.remove(&id) {
-        guard.selection.selected_ids.insert(id);
+    {
+        let cancel_flag = cancel_flag.clone();
+        dialog.on_cancel_gallery(move || {
+            cancel_flag.borrow().cancel();
+        });
     }
-    guard.apply_selection_flags();
-}
 
-fn select_all_allowed(state: &Rc<RefCell<ImportViewState>>) {
-    let mut guard = state.borrow_mut();
-    guard.selection.selected_ids.clear();
-    let mut anchor: Option<ImageId> = None;
-    for idx in 0..guard.thumbnails.row_count() {
-        if let Some(thumb) = guard.thumbnails.row_data(idx) {
-            if thumb.selectable {
-                guard.selection.selected_ids.insert(thumb.id);
-                if anchor.is_none() {
-                    anchor = Some(thumb.id);
-                }
+    {
+        let active_gallery = active_gallery.clone();
+        dialog.on_close(move || {
+            cancel_flag.borrow().cancel();
+            if let Some(dialog) = active_gallery.borrow_mut().take() {
+                dialog.hide().ok();
             }
-        }
+        });
     }
-    guard.selection.anchor_id = anchor;
-    guard.apply_selection_flags();
-}
 
-fn clear_selection(state: &Rc<RefCell<ImportViewState>>) {
-    let mut guard = state.borrow_mut();
-    guard.selection.selected_ids.clear();
-    guard.apply_selection_flags();
+    dialog.show().ok();
+    *active_gallery.borrow_mut() = Some(dialog);
 }
 
-fn set_all_checked(state: &Rc<RefCell<ImportViewState>>, checked: bool) {
-    let guard = state.borrow_mut();
-    for idx in 0..guard.thumbnails.row_count() {
-        if let Some(mut thumb) = guard.thumbnails.row_data(idx) {
-            if thumb.selectable {
-                thumb.checked = checked;
-                guard.thumbnails.set_row_data(idx, thumb);
-            }
+fn open_contact_sheet_dialog(
+    ui_weak: &slint::Weak<MainWindow>,
+    catalog_state: &CatalogState,
+    folio_state: &Rc<RefCell<FolioState>>,
+    engine: &Arc<ImageEngine>,
+    active_sheet: &Rc<RefCell<Option<ContactSheetDialog>>>,
+) {
+    if let Some(existing) = active_sheet.borrow().as_ref() {
+        existing.show().ok();
+        return;
+    }
+
+    let image_ids: Vec<i64> = folio_state
+        .borrow()
+        .selection
+        .iter()
+        .map(|id| *id as i64)
+        .collect();
+    let catalog_path = match catalog_state.borrow().as_ref() {
+        Some(session) => session.path.clone(),
+        None => return,
+    };
+    if image_ids.is_empty() {
+        if let Some(ui) = ui_weak.upgrade() {
+            ui.set_status_text("Select photos for the contact sheet".into());
         }
+        return;
     }
-    guard.rebuild_checked_paths();
-}
 
-fn apply_checkbox_to_selection(state: &Rc<RefCell<ImportViewState>>, checked: bool) {
-    let mut guard = state.borrow_mut();
-    if guard.selection.selected_ids.is_empty() {
-        if let Some(anchor) = guard.selection.anchor_id {
-            if guard.is_selectable_id(anchor) {
-                guard.selection.selected_ids.insert(anchor);
-            }
+    let dialog = match ContactSheetDialog::new() {
+        Ok(dialog) => dialog,
+        Err(err) => {
+            eprintln!("Failed to open contact sheet dialog: {err}");
+            return;
         }
+    };
+    dialog.set_image_count(image_ids.len() as i32);
+
+    let dialog_weak = dialog.as_weak();
+    let cancel_flag = Rc::new(RefCell::new(CancellationFlag::default()));
+
+    {
+        let dialog_weak = dialog_weak.clone();
+        dialog.on_choose_output(move || {
+            let dialog_weak = dialog_weak.clone();
+            let _ = slint::spawn_local(async move {
+                if let Some(handle) = AsyncFileDialog::new()
+                    .set_title("Save Contact Sheet")
+                    .add_filter("PDF", &["pdf"])
+                    .set_file_name("contact-sheet.pdf")
+                    .save_file()
+                    .await
+                {
+                    if let Some(dialog) = dialog_weak.upgrade() {
+                        dialog.set_output_path(handle.path().to_string_lossy().to_string().into());
+                    }
+                }
+            });
+        });
+    }
+
+    {
+        let dialog_weak = dialog_weak.clone();
+        let cancel_flag = cancel_flag.clone();
+        let ui_weak = ui_weak.clone();
+        let engine = engine.clone();
+        dialog.on_start_sheet(move || {
+            let Some(dialog) = dialog_weak.upgrade() else {
+                return;
+            };
+            let settings = ContactSheetSettings {
+                title: dialog.get_sheet_title().trim().to_string(),
+                page_size: match dialog.get_page_size().as_str() {
+                    "Letter" => PageSize::Letter,
+                    "A3" => PageSize::A3,
+                    "Legal" => PageSize::Legal,
+                    _ => PageSize::A4,
+                },
+                orientation: match dialog.get_orientation().as_str() {
+                    "Landscape" => PageOrientation::Landscape,
+                    _ => PageOrientation::Portrait,
+                },
+                columns: dialog.get_columns().clamp(1, 12) as u32,
+                rows: dialog.get_rows().clamp(1, 16) as u32,
+                captions: ContactSheetCaptions {
+                    filename: dialog.get_caption_filename(),
+                    captured_at: dialog.get_caption_date(),
+                    rating: dialog.get_caption_rating(),
+                    color_label: dialog.get_caption_label(),
+                },
+                source: if dialog.get_render_edits() {
+                    ContactSheetSource::Renders
+                } else {
+                    ContactSheetSource::Thumbnails
+                },
+                ..ContactSheetSettings::default()
+            };
+            let output = PathBuf::from(dialog.get_output_path().as_str());
+
+            let cancel = CancellationFlag::default();
+            *cancel_flag.borrow_mut() = cancel.clone();
+            dialog.set_generating(true);
+            dialog.set_progress(0.0);
+            dialog.set_status_text("Creating contact sheet…".into());
+
+            let catalog_path = catalog_path.clone();
+            let image_ids = image_ids.clone();
+            let engine = engine.clone();
+            let dialog_weak = dialog_weak.clone();
+            let ui_weak = ui_weak.clone();
+            std::thread::spawn(move || {
+                let progress_weak = dialog_weak.clone();
+                let callbacks = ExportCallbacks {
+                    progress: Some(Arc::new(move |progress: ExportProgress| {
+                        let pct = progress.completed as f32 / progress.total.max(1) as f32;
+                        let label = progress.message.unwrap_or_default();
+                        let _ = progress_weak.upgrade_in_event_loop(move |dialog| {
+                            dialog.set_progress(pct);
+                            if !label.is_empty() {
+                                dialog.set_status_text(label.into());
+                            }
+                        });
+                    })),
+                    on_error: Some(Arc::new(|image_id, err| {
+                        eprintln!("Contact sheet image {image_id} failed: {err}");
+                    })),
+                    cancel,
+                };
+
+                let result = CatalogDb::open(&catalog_path.to_string_lossy())
+                    .map(CatalogService::new)
+                    .and_then(|service| {
+                        write_contact_sheet(
+                            &service, &engine, &image_ids, &output, &settings, callbacks,
+                        )
+                    });
+
+                let summary = match result {
+                    Ok(report) if report.canceled => "Contact sheet canceled".to_string(),
+                    Ok(report) => {
+                        let mut summary = format!(
+                            "Contact sheet with {} photo(s) on {} page(s) written to {}",
+                            report.images,
+                            report.pages,
+                            report.path.display()
+                        );
+                        if !report.failed.is_empty() {
+                            summary.push_str(&format!(", {} failed", report.failed.len()));
+                        }
+                        summary
+                    }
+                    Err(err) => format!("Contact sheet failed: {err}"),
+                };
+
+                let status = summary.clone();
+                let _ = dialog_weak.upgrade_in_event_loop(move |dialog| {
+                    dialog.set_generating(false);
+                    dialog.set_progress(1.0);
+                    dialog.set_status_text(summary.into());
+                });
+                let _ = ui_weak.upgrade_in_event_loop(move |ui| {
+                    ui.set_status_text(status.into());
+                });
+            });
+        });
     }
 
-    guard.apply_selection_flags();
+    {
+        let cancel_flag = cancel_flag.clone();
+        dialog.on_cancel_sheet(move || {
+            cancel_flag.borrow().cancel();
+        });
+    }
 
-    for idx in 0..guard.thumbnails.row_count() {
-        if let Some(mut thumb) = guard.thumbnails.row_data(idx) {
-            if guard.selection.selected_ids.contains(&thumb.id) && thumb.selectable {
-                thumb.checked = checked;
-                guard.thumbnails.set_row_data(idx, thumb);
+    {
+        let active_sheet = active_sheet.clone();
+        dialog.on_close(move || {
+            cancel_flag.borrow().cancel();
+            if let Some(dialog) = active_sheet.borrow_mut().take() {
+                dialog.hide().ok();
             }
-        }
+        });
     }
-    guard.rebuild_checked_paths();
+
+    dialog.show().ok();
+    *active_sheet.borrow_mut() = Some(dialog);
 }
 
-fn start_scan_for_directory(
-    import_ui: &slint::Weak<ImportPhotosScreen>,
-    state: &Rc<RefCell<ImportViewState>>,
-    catalog_path: PathBuf,
-    path: PathBuf,
+fn open_capture_time_dialog(
+    ui_weak: &slint::Weak<MainWindow>,
+    catalog_state: &CatalogState,
+    folio_state: &Rc<RefCell<FolioState>>,
+    config_store: &ConfigStore,
+    active_dialog: &Rc<RefCell<Option<CaptureTimeDialog>>>,
 ) {
-    if !path.exists() {
-        if let Some(ui) = import_ui.upgrade() {
-            ui.set_status_text("Folder not found".into());
-        }
+    if let Some(existing) = active_dialog.borrow().as_ref() {
+        existing.show().ok();
         return;
     }
 
-    {
-        let mut guard = state.borrow_mut();
-        guard.reset_for_scan();
-        refresh_directory_model(&guard.directories, &path);
+    let image_ids: Vec<i64> = folio_state
+        .borrow()
+        .selection
+        .iter()
+        .map(|id| *id as i64)
+        .collect();
+    if image_ids.is_empty() || catalog_state.borrow().is_none() {
+        return;
     }
 
-    if let Some(ui) = import_ui.upgrade() {
-        ui.set_selected_directory(path.to_string_lossy().to_string().into());
-        ui.set_status_text("Scanning for images…".into());
-        ui.set_importing(true);
-        ui.set_progress(0.0);
+    let dialog = match CaptureTimeDialog::new() {
+        Ok(dialog) => dialog,
+        Err(err) => {
+            eprintln!("Failed to open capture time dialog: {err}");
+            return;
+        }
+    };
+    dialog.set_image_count(image_ids.len() as i32);
+    refresh_capture_time_dialog(&dialog, catalog_state, &image_ids);
+    let earliest = dialog.get_earliest_time();
+    if !earliest.is_empty() {
+        dialog.set_first_time_text(earliest);
     }
 
-    let db_path = catalog_path.to_string_lossy().to_string();
-    let catalog_for_scan = CatalogDb::open(&db_path)
-        .map(CatalogService::new)
-        .map(Rc::new)
-        .map_err(|err| {
-            if let Some(ui) = import_ui.upgrade() {
-                ui.set_status_text(format!("Unable to check duplicates: {err}").into());
-            }
-            err
-        })
-        .ok();
+    let dialog_weak = dialog.as_weak();
 
-    let cancel_flag = { state.borrow().scan_cancel.clone() };
-    let state_for_candidates = state.clone();
-    let ui_weak = import_ui.clone();
-    let seen = Rc::new(RefCell::new(HashSet::<PathBuf>::new()));
-    let scan_opts = ScanOptions {
-        on_candidate: Some(Arc::new({
-            let seen = seen.clone();
-            let catalog_for_scan = catalog_for_scan.clone();
-            move |candidate| {
-                if !seen.borrow_mut().insert(candidate.path.clone()) {
-                    return;
+    {
+        let dialog_weak = dialog_weak.clone();
+        dialog.on_choose_track(move || {
+            let dialog_weak = dialog_weak.clone();
+            let _ = slint::spawn_local(async move {
+                if let Some(handle) = AsyncFileDialog::new()
+                    .set_title("Choose GPS Track")
+                    .add_filter("GPX", &["gpx"])
+                    .pick_file()
+                    .await
+                {
+                    if let Some(dialog) = dialog_weak.upgrade() {
+                        dialog.set_track_path(handle.path().to_string_lossy().to_string().into());
+                    }
                 }
+            });
+        });
+    }
 
-                let already_imported = catalog_for_scan
-                    .as_ref()
-                    .map(|svc| is_already_imported(svc, &candidate.path))
-                    .unwrap_or(false);
-                let selectable = !already_imported;
-                let checked = selectable;
-                let display_thumb = candidate.thumb.unwrap_or_else(placeholder_image);
-                let path_text: SharedString = candidate.path.to_string_lossy().to_string().into();
-
-                let mut guard = state_for_candidates.borrow_mut();
-                let id = guard.next_image_id;
-                guard.next_image_id += 1;
-
-                if selectable && guard.selection.selected_ids.is_empty() {
-                    guard.selection.selected_ids.insert(id);
-                    guard.selection.anchor_id = Some(id);
+    {
+        let dialog_weak = dialog_weak.clone();
+        let ui_weak = ui_weak.clone();
+        let catalog_state = catalog_state.clone();
+        let folio_state = folio_state.clone();
+        let config_store = config_store.clone();
+        let image_ids = image_ids.clone();
+        dialog.on_apply(move || {
+            let Some(dialog) = dialog_weak.upgrade() else {
+                return;
+            };
+            let adjustment = match dialog.get_mode().as_str() {
+                "Set first photo to" => parse_time(&dialog.get_first_time_text())
+                    .map(|time| CaptureTimeAdjustment::SetFirst { time }),
+                "Time zone" => parse_zone(&dialog.get_camera_zone()).and_then(|camera| {
+                    Ok(CaptureTimeAdjustment::TimeZone {
+                        camera_minutes: camera,
+                        actual_minutes: parse_zone(&dialog.get_actual_zone())?,
+                    })
+                }),
+                "Sync to GPS track" => Ok(CaptureTimeAdjustment::SyncToTrack {
+                    track: PathBuf::from(dialog.get_track_path().as_str()),
+                }),
+                _ => parse_offset(&dialog.get_offset_text())
+                    .map(|seconds| CaptureTimeAdjustment::Shift { seconds }),
+            };
+            let settings = match adjustment {
+                Ok(adjustment) => CaptureTimeSettings {
+                    adjustment,
+                    write_sidecars: dialog.get_write_sidecars(),
+                },
+                Err(err) => {
+                    dialog.set_status_text(format!("{err}").into());
+                    return;
                 }
-
-                let is_selected = guard.selection.selected_ids.contains(&id);
-
-                guard.thumbnails.push(ImportThumbnail {
-                    id,
-                    path: path_text.clone(),
-                    display_thumb,
-                    selected: is_selected,
-                    checked,
-                    already_imported,
-                    selectable,
-                });
-                guard.apply_selection_flags();
-                guard.rebuild_checked_paths();
-                if let Some(ui) = ui_weak.upgrade() {
-                    ui.set_status_text(format!("Queued {}", candidate.path.display()).into());
+            };
+            let result = match catalog_state.borrow().as_ref() {
+                Some(session) => correct_capture_times(&session.service, &image_ids, &settings),
+                None => return,
+            };
+            let status = match result {
+                Ok(report) if report.correction_id.is_none() => {
+                    "No capture times changed".to_string()
+                }
+                Ok(report) => {
+                    let mut status = format!(
+                        "Shifted {} photo(s) by {}",
+                        report.updated,
+                        format_offset(report.offset_seconds)
+                    );
+                    if report.sidecars_written > 0 {
+                        status.push_str(&format!(", wrote {} sidecar(s)", report.sidecars_written));
+                    }
+                    if !report.failed.is_empty() {
+                        status.push_str(&format!(", {} sidecar(s) failed", report.failed.len()));
+                    }
+                    if !report.skipped.is_empty() {
+                        status.push_str(&format!(
+                            ", skipped {} without a capture time",
+                            report.skipped.len()
+                        ));
+                    }
+                    reload_current_selection(&catalog_state, &folio_state, &ui_weak, &config_store);
+                    refresh_timeline(&ui_weak, &catalog_state, &folio_state);
+                    status
                 }
+                Err(err) => format!("Capture time correction failed: {err}"),
+            };
+            refresh_capture_time_dialog(&dialog, &catalog_state, &image_ids);
+            dialog.set_status_text(status.clone().into());
+            if let Some(ui) = ui_weak.upgrade() {
+                ui.set_status_text(status.into());
             }
-        })),
-        cancel: cancel_flag.clone(),
-    };
-    let ui_done = import_ui.clone();
-    let state_done = state.clone();
-    let _ = slint::spawn_local(async move {
-        let result = scan_directory_with_options(&path, scan_opts).await;
-        if let Some(ui) = ui_done.upgrade() {
-            ui.set_importing(false);
-            match result {
-                Ok(list) => ui.set_status_text(format!("Found {} photos", list.len()).into()),
-                Err(err) => ui.set_status_text(format!("Scan failed: {err}").into()),
+        });
+    }
+
+    {
+        let dialog_weak = dialog_weak.clone();
+        let ui_weak = ui_weak.clone();
+        let catalog_state = catalog_state.clone();
+        let folio_state = folio_state.clone();
+        let config_store = config_store.clone();
+        dialog.on_undo_last(move || {
+            let Some(dialog) = dialog_weak.upgrade() else {
+                return;
+            };
+            let result = match catalog_state.borrow().as_ref() {
+                Some(session) => undo_capture_time_correction(&session.service, None),
+                None => return,
+            };
+            let status = match result {
+                Ok(undo) => {
+                    reload_current_selection(&catalog_state, &folio_state, &ui_weak, &config_store);
+                    refresh_timeline(&ui_weak, &catalog_state, &folio_state);
+                    let mut status = format!(
+                        "Undid \"{}\": restored {} photo(s)",
+                        undo.correction.description, undo.restored
+                    );
+                    if undo.changed_since > 0 {
+                        status.push_str(&format!(
+                            ", {} changed again since were left alone",
+                            undo.changed_since
+                        ));
+                    }
+                    status
+                }
+                Err(err) => format!("Undo failed: {err}"),
+            };
+            refresh_capture_time_dialog(&dialog, &catalog_state, &image_ids);
+            dialog.set_status_text(status.into());
+        });
+    }
+
+    {
+        let active_dialog = active_dialog.clone();
+        dialog.on_close(move || {
+            if let Some(dialog) = active_dialog.borrow_mut().take() {
+                dialog.hide().ok();
             }
-        }
-        let mut guard = state_done.borrow_mut();
-        guard.apply_selection_flags();
-        guard.rebuild_checked_paths();
-    });
+        });
+    }
+
+    dialog.show().ok();
+    *active_dialog.borrow_mut() = Some(dialog);
 }
 
-fn stage_label(stage: &ImportStage) -> &'static str {
-    match stage {
-        ImportStage::Scanning => "Scanning",
-        ImportStage::Copying => "Copying",
-        ImportStage::Moving => "Moving",
-        ImportStage::Cataloging => "Cataloging",
-        ImportStage::Thumbnailing => "Thumbnails",
-        ImportStage::Keywords => "Keywords",
-    }
+/// Show the earliest capture time of the images and the correction "Undo Last" reverts.
+fn refresh_capture_time_dialog(
+    dialog: &CaptureTimeDialog,
+    catalog_state: &CatalogState,
+    image_ids: &[i64],
+) {
+    let guard = catalog_state.borrow();
+    let Some(session) = guard.as_ref() else {
+        return;
+    };
+    let earliest = image_ids
+        .iter()
+        .filter_map(|id| CatalogImage::load(&session.service.db, *id).ok())
+        .filter_map(|image| image.captured_at.or(image.file_modified_at))
+        .min();
+    dialog.set_earliest_time(
+        earliest
+            .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default()
+            .into(),
+    );
+    let last = list_capture_time_corrections(&session.service)
+        .ok()
+        .and_then(|corrections| corrections.into_iter().next())
+        .map(|correction| correction.description)
+        .unwrap_or_default();
+    dialog.set_last_correction(last.into());
 }
 
-fn begin_import(
-    session_path: PathBuf,
-    import_ui: &slint::Weak<ImportPhotosScreen>,
-    state: Rc<RefCell<ImportViewState>>,
-    active_import: Rc<RefCell<Option<ImportPhotosScreen>>>,
-    file_paths: Vec<PathBuf>,
-    keywords: Vec<String>,
-    method: ImportMethod,
-    destination_directory: Option<PathBuf>,
-    allow_duplicates: bool,
-    ui_weak: slint::Weak<MainWindow>,
-    catalog_state: CatalogState,
-    folio_state: Rc<RefCell<FolioState>>,
-    config_store: ConfigStore,
+fn open_geotag_dialog(
+    ui_weak: &slint::Weak<MainWindow>,
+    catalog_state: &CatalogState,
+    folio_state: &Rc<RefCell<FolioState>>,
+    config_store: &ConfigStore,
+    active_dialog: &Rc<RefCell<Option<GeotagDialog>>>,
 ) {
-    if file_paths.is_empty() {
-        if let Some(ui) = import_ui.upgrade() {
-            ui.set_status_text("Select at least one photo to import".into());
-        }
+    if let Some(existing) = active_dialog.borrow().as_ref() {
+        existing.show().ok();
+        return;
+    }
+
+    let image_ids: Vec<i64> = folio_state
+        .borrow()
+        .selection
+        .iter()
+        .map(|id| *id as i64)
+        .collect();
+    if image_ids.is_empty() || catalog_state.borrow().is_none() {
         return;
     }
 
-    if matches!(method, ImportMethod::Copy | ImportMethod::Move) && destination_directory.is_none()
+    let dialog = match GeotagDialog::new() {
+        Ok(dialog) => dialog,
+        Err(err) => {
+            eprintln!("Failed to open geotag dialog: {err}");
+            return;
+        }
+    };
+    dialog.set_image_count(image_ids.len() as i32);
+    refresh_geotag_dialog(&dialog, catalog_state);
+
+    let dialog_weak = dialog.as_weak();
+    // The preview "Apply" writes, so the catalog gets exactly what was shown.
+    let preview: Rc<RefCell<Option<GeotagPreview>>> = Rc::new(RefCell::new(None));
+
+    {
+        let dialog_weak = dialog_weak.clone();
+        dialog.on_choose_track(move || {
+            let dialog_weak = dialog_weak.clone();
+            let _ = slint::spawn_local(async move {
+                if let Some(handle) = AsyncFileDialog::new()
+                    .set_title("Choose GPS Track")
+                    .add_filter("GPX", &["gpx"])
+                    .pick_file()
+                    .await
+                {
+                    if let Some(dialog) = dialog_weak.upgrade() {
+                        dialog.set_track_path(handle.path().to_string_lossy().to_string().into());
+                        dialog.set_previewed(false);
+                    }
+                }
+            });
+        });
+    }
+
+    {
+        let dialog_weak = dialog_weak.clone();
+        let catalog_state = catalog_state.clone();
+        let preview = preview.clone();
+        let image_ids = image_ids.clone();
+        dialog.on_preview(move || {
+            let Some(dialog) = dialog_weak.upgrade() else {
+                return;
+            };
+            let settings = parse_offset(&dialog.get_offset_text()).and_then(|offset| {
+                Ok(GeotagSettings {
+                    clock_offset_seconds: offset,
+                    max_gap_seconds: parse_offset(&dialog.get_max_gap_text())?,
+                    overwrite: dialog.get_overwrite(),
+                })
+            });
+            let result = settings.and_then(|settings| {
+                let track = read_gpx(Path::new(dialog.get_track_path().as_str()))?;
+                match catalog_state.borrow().as_ref() {
+                    Some(session) => {
+                        preview_geotags(&session.service, &track, &image_ids, &settings)
+                    }
+                    None => Err(anyhow!("no catalog is open")),
+                }
+            });
+            let geotags = match result {
+                Ok(result) => result,
+                Err(err) => {
+                    *preview.borrow_mut() = None;
+                    dialog.set_rows(Rc::new(VecModel::<GeotagRow>::default()).into());
+                    dialog.set_matched_count(0);
+                    dialog.set_previewed(false);
+                    dialog.set_status_text(format!("{err}").into());
+                    return;
+                }
+            };
+            let file_name = |path: &Path| {
+                path.file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_else(|| path.display().to_string())
+            };
+            let mut rows: Vec<GeotagRow> = geotags
+                .matched
+                .iter()
+                .map(|hit| GeotagRow {
+                    name: file_name(&hit.path).into(),
+                    detail: format!("{:.5}, {:.5}", hit.latitude, hit.longitude).into(),
+                    matched: true,
+                })
+                .collect();
+            rows.extend(geotags.unmatched.iter().map(|miss| GeotagRow {
+                name: file_name(&miss.path).into(),
+                detail: miss.reason.label().into(),
+                matched: false,
+            }));
+            dialog.set_rows(Rc::new(VecModel::from(rows)).into());
+            dialog.set_matched_count(geotags.matched.len() as i32);
+            dialog.set_previewed(true);
+            dialog.set_status_text(
+                format!(
+                    "{} photo(s) match the track, {} don't",
+                    geotags.matched.len(),
+                    geotags.unmatched.len()
+                )
+                .into(),
+            );
+            *preview.borrow_mut() = Some(geotags);
+        });
+    }
+
+    {
+        let dialog_weak = dialog_weak.clone();
+        let ui_weak = ui_weak.clone();
+        let catalog_state = catalog_state.clone();
+        let folio_state = folio_state.clone();
+        let config_store = config_store.clone();
+        let preview = preview.clone();
+        dialog.on_apply(move || {
+            let Some(dialog) = dialog_weak.upgrade() else {
+                return;
+            };
+            let Some(geotags) = preview.borrow_mut().take() else {
+                return;
+            };
+            let track_path = dialog.get_track_path();
+            let track_name = Path::new(track_path.as_str())
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| track_path.to_string());
+            let description = format!(
+                "Geotagged {} photo(s) from {track_name}",
+                geotags.matched.len()
+            );
+            let result = match catalog_state.borrow().as_ref() {
+                Some(session) => apply_geotags(&session.service, &geotags, &description),
+                None => return,
+            };
+            let status = match result {
+                Ok(Some(_)) => {
+                    reload_current_selection(&catalog_state, &folio_state, &ui_weak, &config_store);
+                    description
+                }
+                Ok(None) => "No photos matched the track".to_string(),
+                Err(err) => format!("Geotagging failed: {err}"),
+            };
+            dialog.set_previewed(false);
+            refresh_geotag_dialog(&dialog, &catalog_state);
+            dialog.set_status_text(status.clone().into());
+            if let Some(ui) = ui_weak.upgrade() {
+                ui.set_status_text(status.into());
+            }
+        });
+    }
+
     {
-        if let Some(ui) = import_ui.upgrade() {
-            ui.set_status_text("Choose a destination for Copy/Move imports".into());
-        }
-        return;
+        let dialog_weak = dialog_weak.clone();
+        let ui_weak = ui_weak.clone();
+        let catalog_state = catalog_state.clone();
+        let folio_state = folio_state.clone();
+        let config_store = config_store.clone();
+        dialog.on_undo_last(move || {
+            let Some(dialog) = dialog_weak.upgrade() else {
+                return;
+            };
+            let result = match catalog_state.borrow().as_ref() {
+                Some(session) => undo_geotags(&session.service, None),
+                None => return,
+            };
+            let status = match result {
+                Ok(undo) => {
+                    reload_current_selection(&catalog_state, &folio_state, &ui_weak, &config_store);
+                    let mut status = format!(
+                        "Undid \"{}\": restored {} photo(s)",
+                        undo.batch.description, undo.restored
+                    );
+                    if undo.changed_since > 0 {
+                        status.push_str(&format!(
+                            ", {} moved again since were left alone",
+                            undo.changed_since
+                        ));
+                    }
+                    status
+                }
+                Err(err) => format!("Undo failed: {err}"),
+            };
+            dialog.set_previewed(false);
+            refresh_geotag_dialog(&dialog, &catalog_state);
+            dialog.set_status_text(status.into());
+        });
     }
 
-    let cancel_flag = {
-        let mut guard = state.borrow_mut();
-        guard.import_cancel.cancel();
-        guard.import_cancel = CancellationFlag::default();
-        guard.import_cancel.clone()
-    };
-
-    if let Some(ui) = import_ui.upgrade() {
-        ui.set_importing(true);
-        ui.set_progress(0.0);
-        ui.set_status_text("Importing…".into());
+    {
+        let active_dialog = active_dialog.clone();
+        dialog.on_close(move || {
+            if let Some(dialog) = active_dialog.borrow_mut().take() {
+                dialog.hide().ok();
+            }
+        });
     }
 
-    let duplicate_strategy = if allow_duplicates {
-        DuplicateStrategy::ImportAnyway
-    } else {
-        DuplicateStrategy::Skip
+    dialog.show().ok();
+    *active_dialog.borrow_mut() = Some(dialog);
+}
+
+/// Show the geotagging run "Undo Last" reverts.
+fn refresh_geotag_dialog(dialog: &GeotagDialog, catalog_state: &CatalogState) {
+    let guard = catalog_state.borrow();
+    let Some(session) = guard.as_ref() else {
+        return;
     };
+    let last = list_geotag_batches(&session.service)
+        .ok()
+        .and_then(|batches| batches.into_iter().next())
+        .map(|batch| batch.description)
+        .unwrap_or_default();
+    dialog.set_last_batch(last.into());
+}
 
-    let errors_model = state.borrow().errors.clone();
-    let ui_for_progress = import_ui.clone();
-    let state_for_progress = state.clone();
-    let active_import = active_import.clone();
+/// Pins currently on the map tab and the photos selected through them.
+#[derive(Default)]
+struct MapState {
+    pins: Rc<VecModel<MapPin>>,
+    clusters: Vec<GeoCluster>,
+    viewport: Option<Viewport>,
+    /// Positions of the photos picked on the map, used to highlight their pins.
+    selected: Vec<(f64, f64)>,
+    /// Pin thumbnails by image id, so panning doesn't decode them again.
+    thumbs: HashMap<i64, slint::Image>,
+}
 
-    let progress_cb = Arc::new(move |progress: ImportProgress| {
-        if let Some(ui) = ui_for_progress.upgrade() {
-            let total = progress.total.max(1);
-            let pct = (progress.completed as f32) / (total as f32);
-            let label = progress
-                .message
-                .as_deref()
-                .unwrap_or(stage_label(&progress.stage));
-            ui.set_progress(pct);
-            ui.set_status_text(
-                format!("{} ({}/{})", label, progress.completed, progress.total).into(),
-            );
-        }
-    });
+impl MapState {
+    fn clear_selection(&mut self) {
+        self.selected.clear();
+        self.mark_selected_pins();
+    }
 
-    let error_ui = import_ui.clone();
-    let on_error_cb = Arc::new(move |path: PathBuf, msg: String| {
-        let msg_clone = msg.clone();
-        let error_text = SharedString::from(format!("{}: {}", path.to_string_lossy(), msg_clone));
-        errors_model.push(error_text);
-        if let Some(ui) = error_ui.upgrade() {
-            ui.set_status_text(format!("Error: {msg_clone}").into());
+    fn mark_selected_pins(&self) {
+        for (idx, cluster) in self.clusters.iter().enumerate() {
+            let selected = self
+                .selected
+                .iter()
+                .any(|&(latitude, longitude)| cluster.cell.contains(latitude, longitude));
+            if let Some(mut pin) = self.pins.row_data(idx) {
+                if pin.selected != selected {
+                    pin.selected = selected;
+                    self.pins.set_row_data(idx, pin);
+                }
+            }
         }
-    });
+    }
+}
 
-    let callbacks = ImportCallbacks {
-        progress: Some(progress_cb),
-        on_error: Some(on_error_cb),
-        duplicate_strategy,
-        cancel: cancel_flag.clone(),
+/// Cluster the geotagged photos around the map's viewport into pins.
+fn refresh_map_pins(
+    catalog_state: &CatalogState,
+    map_state: &Rc<RefCell<MapState>>,
+    ui_weak: &slint::Weak<MainWindow>,
+) -> anyhow::Result<()> {
+    let mut state = map_state.borrow_mut();
+    let Some(viewport) = state.viewport else {
+        return Ok(());
     };
-
-    let catalog_state_for_refresh = catalog_state.clone();
-    let folio_state_for_refresh = folio_state.clone();
-    let config_store_for_refresh = config_store.clone();
-    let ui_refresh = ui_weak.clone();
-    let session_path_for_refresh = session_path.clone();
-
-    let ui_done = import_ui.clone();
-    let _ = slint::spawn_local(async move {
-        let db_path = session_path.to_string_lossy().to_string();
-        let service = match CatalogDb::open(&db_path).map(CatalogService::new) {
-            Ok(service) => service,
-            Err(err) => {
-                if let Some(ui) = ui_done.upgrade() {
-                    ui.set_importing(false);
-                    ui.set_status_text(format!("Unable to open catalog: {err}").into());
-                }
-                return;
+    let guard = catalog_state.borrow();
+    let session = guard.as_ref().context("No catalog open")?;
+    let clusters = session
+        .service
+        .cluster_images(&viewport.bounds(), viewport.cluster_zoom())?;
+
+    let mut pins = Vec::with_capacity(clusters.len());
+    for cluster in &clusters {
+        let thumb = match state.thumbs.get(&cluster.image_id) {
+            Some(thumb) => thumb.clone(),
+            None => {
+                let thumb = session
+                    .service
+                    .load_metadata(cluster.image_id)
+                    .ok()
+                    .and_then(|meta| {
+                        load_or_generate_thumbnail(
+                            &session.service,
+                            meta.image.id,
+                            &meta.image.original_path,
+                        )
+                    })
+                    .unwrap_or_else(placeholder_image);
+                state.thumbs.insert(cluster.image_id, thumb.clone());
+                thumb
             }
         };
+        let (x, y) = map::to_map(cluster.latitude, cluster.longitude);
+        pins.push(MapPin {
+            x: x as f32,
+            y: y as f32,
+            count: cluster.count as i32,
+            thumb,
+            selected: false,
+        });
+    }
+    let located: usize = clusters.iter().map(|cluster| cluster.count).sum();
+    state.pins.set_vec(pins);
+    state.clusters = clusters;
+    state.mark_selected_pins();
 
-        let result = import_images_with_callbacks(
-            &service,
-            &file_paths,
-            &keywords,
-            method,
-            destination_directory.clone(),
-            callbacks,
-        )
-        .await;
+    if let Some(ui) = ui_weak.upgrade() {
+        ui.set_map_summary(match located {
+            0 => "No geotagged photos here".into(),
+            1 => "1 geotagged photo nearby".into(),
+            n => format!("{n} geotagged photos nearby").into(),
+        });
+    }
+    Ok(())
+}
 
-        if let Some(ui) = ui_done.upgrade() {
-            ui.set_importing(false);
-            match result {
-                Ok(report) => {
-                    let mut summary = format!("Imported {}", report.imported);
-                    if !report.duplicates.is_empty() {
-                        summary.push_str(&format!(", skipped {}", report.duplicates.len()));
-                    }
-                    if !report.failed.is_empty() {
-                        summary.push_str(&format!(", {} failed", report.failed.len()));
-                        for (path, msg) in report.failed {
-                            state_for_progress
-                                .borrow()
-                                .errors
-                                .push(format!("{}: {}", path.to_string_lossy(), msg).into());
-                        }
-                    }
-                    if report.canceled {
-                        summary.push_str(" (canceled)");
-                    }
+/// Select the photos under a pin, adding them to the selection when `extend` is set.
+fn select_map_pin(
+    catalog_state: &CatalogState,
+    folio_state: &Rc<RefCell<FolioState>>,
+    map_state: &Rc<RefCell<MapState>>,
+    ui_weak: &slint::Weak<MainWindow>,
+    index: usize,
+    extend: bool,
+) -> anyhow::Result<()> {
+    let mut state = map_state.borrow_mut();
+    let Some(cluster) = state.clusters.get(index) else {
+        return Ok(());
+    };
+    let images = {
+        let guard = catalog_state.borrow();
+        let session = guard.as_ref().context("No catalog open")?;
+        session.service.images_in_bounds(&cluster.cell)?
+    };
 
-                    let last_import_ts = report.batch_started_at.map(|ts| ts.to_rfc3339());
-                    if let Some(ts) = &last_import_ts {
-                        if let Err(err) =
-                            config_store_for_refresh.record_last_import(&session_path_for_refresh, ts)
-                        {
-                            eprintln!("Failed to persist last import timestamp: {err}");
-                        }
-                    }
-                    if let Some(ts) = last_import_ts {
-                        folio_state_for_refresh
-                            .borrow_mut()
-                            .last_import_timestamp = Some(ts);
-                    }
-                    refresh_folio_tree(&ui_refresh, &catalog_state_for_refresh, &folio_state_for_refresh);
-                    reload_current_selection(
-                        &catalog_state_for_refresh,
-                        &folio_state_for_refresh,
-                        &ui_refresh,
-                        &config_store_for_refresh,
-                    );
+    if !extend {
+        state.selected.clear();
+    }
+    state.selected.extend(
+        images
+            .iter()
+            .filter_map(|image| Some((image.gps_latitude?, image.gps_longitude?))),
+    );
+    state.mark_selected_pins();
+    drop(state);
 
-                    ui.set_status_text(summary.clone().into());
-                    ui.set_progress(1.0);
-                    ui.hide().ok();
-                    active_import.borrow_mut().take();
-                }
-                Err(err) => {
-                    ui.set_status_text(format!("Import failed: {err}").into());
-                    state_for_progress
-                        .borrow()
-                        .errors
-                        .push(format!("Import failed: {err}").into());
-                }
+    let mut guard = folio_state.borrow_mut();
+    if !extend {
+        guard.selection.clear();
+    }
+    for image in &images {
+        let id = image.id as i32;
+        if !guard.selection.contains(&id) {
+            guard.selection.push(id);
+        }
+    }
+    for i in 0..guard.thumbnails.row_count() {
+        if let Some(mut thumb) = guard.thumbnails.row_data(i) {
+            let selected = guard.selection.contains(&thumb.id);
+            if thumb.selected != selected {
+                thumb.selected = selected;
+                guard.thumbnails.set_row_data(i, thumb);
             }
         }
-    });
+    }
+    guard.selection_anchor = None;
+    let selected_first = guard.selection.first().copied();
+    let selected_len = guard.selection.len();
+    drop(guard);
+
+    if let Some(ui) = ui_weak.upgrade() {
+        ui.set_folio_selected_count(selected_len as i32);
+        ui.set_status_text(format!("Selected {selected_len} photo(s) from the map").into());
+        if let Some(first) = selected_first {
+            ui.set_selected_image_id(first);
+            refresh_metadata_panel(catalog_state, ui_weak, first as i64)?;
+        }
+    }
+    Ok(())
 }
 
-fn launch_import_flow(
-    ui_weak: &slint::Weak<MainWindow>,
+/// Geotag the photos dropped at `(x, y)`, a fraction of the map's width and height: the
+/// whole selection when the dragged thumbnail is part of it, otherwise just that photo.
+fn place_dropped_photos(
     catalog_state: &CatalogState,
-    active_import: &Rc<RefCell<Option<ImportPhotosScreen>>>,
     folio_state: &Rc<RefCell<FolioState>>,
-    config_store: &ConfigStore,
-) {
-    let catalog_path = {
-        let guard = catalog_state.borrow();
-        let Some(session) = guard.as_ref() else {
-            eprintln!("No catalog open; cannot import photos");
-            return;
-        };
-        session.path.clone()
-    };
-
-    let import_ui = match ImportPhotosScreen::new() {
-        Ok(ui) => ui,
-        Err(err) => {
-            eprintln!("Failed to open import window: {err}");
-            return;
+    image_id: i32,
+    x: f64,
+    y: f64,
+) -> anyhow::Result<String> {
+    if !(0.0..=1.0).contains(&x) || !(0.0..=1.0).contains(&y) {
+        return Ok("Dropped outside the map".to_string());
+    }
+    let image_ids: Vec<i64> = {
+        let guard = folio_state.borrow();
+        if guard.selection.contains(&image_id) {
+            guard.selection.iter().map(|id| *id as i64).collect()
+        } else {
+            vec![image_id as i64]
         }
     };
-
-    import_ui.set_importing(false);
-    import_ui.set_progress(0.0);
-    import_ui.set_status_text("Select a folder to begin".into());
-    import_ui.set_keywords("".into());
-    import_ui.set_destination_directory("".into());
-    import_ui.set_allow_duplicates(false);
-
-    let view_state = Rc::new(RefCell::new(ImportViewState::new()));
-    {
-        let guard = view_state.borrow();
-        import_ui.set_thumbnails(guard.thumbnails.clone().into());
-        import_ui.set_selected_paths(guard.selected_paths.clone().into());
-        import_ui.set_error_messages(guard.errors.clone().into());
-        import_ui.set_directories(guard.directories.clone().into());
+    let (latitude, longitude) = map::from_map(x, y);
+    let description = format!(
+        "Placed {} photo(s) at {latitude:.5}, {longitude:.5}",
+        image_ids.len()
+    );
+    let guard = catalog_state.borrow();
+    let session = guard.as_ref().context("No catalog open")?;
+    place_images(
+        &session.service,
+        &image_ids,
+        latitude,
+        longitude,
+        &description,
+    )?;
+    Ok(description)
+}
+
+/// Show the outcome of placing or un-placing photos on the map and redraw its pins.
+fn after_map_geotag(
+    catalog_state: &CatalogState,
+    folio_state: &Rc<RefCell<FolioState>>,
+    map_state: &Rc<RefCell<MapState>>,
+    ui_weak: &slint::Weak<MainWindow>,
+    status: String,
+) {
+    map_state.borrow_mut().selected.clear();
+    if let Err(err) = refresh_map_pins(catalog_state, map_state, ui_weak) {
+        eprintln!("Failed to load map pins: {err}");
+    }
+    let selected_first = folio_state.borrow().selection.first().copied();
+    if let Some(first) = selected_first {
+        if let Err(err) = refresh_metadata_panel(catalog_state, ui_weak, first as i64) {
+            eprintln!("Failed to refresh metadata panel: {err}");
+        }
+    }
+    if let Some(ui) = ui_weak.upgrade() {
+        ui.set_status_text(status.into());
     }
+}
 
-    let import_ui_weak = import_ui.as_weak();
+fn rgba_to_image(image: &image::RgbaImage) -> slint::Image {
+    let (width, height) = image.dimensions();
+    slint::Image::from_rgba8(SharedPixelBuffer::<Rgba8Pixel>::clone_from_slice(
+        image.as_raw(),
+        width,
+        height,
+    ))
+}
 
-    // Prefill with the user's home directory for convenience.
-    if let Some(home) = directories::BaseDirs::new().map(|d| d.home_dir().to_path_buf()) {
-        refresh_directory_model(&view_state.borrow().directories, &home);
-        import_ui.set_selected_directory(home.to_string_lossy().to_string().into());
+fn open_watermark_dialog(
+    export_weak: &slint::Weak<ExportDialog>,
+    catalog_state: &CatalogState,
+    active: &Rc<RefCell<Option<WatermarkDialog>>>,
+) {
+    if let Some(existing) = active.borrow().as_ref() {
+        existing.show().ok();
+        return;
     }
+    let dialog = match WatermarkDialog::new() {
+        Ok(dialog) => dialog,
+        Err(err) => {
+            eprintln!("Failed to open watermark dialog: {err}");
+            return;
+        }
+    };
+    refresh_watermark_editor_names(&dialog, catalog_state);
+    let dialog_weak = dialog.as_weak();
 
     {
-        let import_ui_weak = import_ui_weak.clone();
-        let view_state = view_state.clone();
-        let catalog_path = catalog_path.clone();
-        import_ui.on_browse_for_directory(move || {
-            let import_ui_weak = import_ui_weak.clone();
-            let view_state = view_state.clone();
-            let catalog_path = catalog_path.clone();
-            let _ = slint::spawn_local(async move {
-                if let Some(handle) = AsyncFileDialog::new().pick_folder().await {
-                    start_scan_for_directory(
-                        &import_ui_weak,
-                        &view_state,
-                        catalog_path.clone(),
-                        handle.path().to_path_buf(),
-                    );
+        let dialog_weak = dialog_weak.clone();
+        let catalog_state = catalog_state.clone();
+        dialog.on_watermark_selected(move |name| {
+            let Some(dialog) = dialog_weak.upgrade() else {
+                return;
+            };
+            let found = match catalog_state.borrow().as_ref() {
+                Some(session) => session.service.find_watermark(&name),
+                None => return,
+            };
+            match found {
+                Ok(Some(watermark)) => apply_watermark_to_dialog(&dialog, &watermark),
+                Ok(None) => {
+                    dialog.set_status_text(format!("Watermark {name} no longer exists").into())
                 }
-            });
-        });
-    }
-
-    {
-        let import_ui_weak = import_ui_weak.clone();
-        let view_state = view_state.clone();
-        let catalog_path = catalog_path.clone();
-        import_ui.on_directory_selected(move |path| {
-            start_scan_for_directory(
-                &import_ui_weak,
-                &view_state,
-                catalog_path.clone(),
-                PathBuf::from(path.as_str()),
-            );
+                Err(err) => {
+                    dialog.set_status_text(format!("Failed to load watermark: {err}").into())
+                }
+            }
         });
     }
 
     {
-        let import_ui_weak = import_ui_weak.clone();
-        import_ui.on_browse_for_destination(move || {
-            let import_ui_weak = import_ui_weak.clone();
+        let dialog_weak = dialog_weak.clone();
+        dialog.on_choose_logo(move || {
+            let dialog_weak = dialog_weak.clone();
             let _ = slint::spawn_local(async move {
-                if let Some(handle) = AsyncFileDialog::new().pick_folder().await {
-                    if let Some(ui) = import_ui_weak.upgrade() {
-                        ui.set_destination_directory(
-                            handle.path().to_string_lossy().to_string().into(),
-                        );
+                if let Some(handle) = AsyncFileDialog::new()
+                    .set_title("Choose Watermark Logo")
+                    .add_filter("PNG Image", &["png"])
+                    .pick_file()
+                    .await
+                {
+                    if let Some(dialog) = dialog_weak.upgrade() {
+                        dialog.set_logo_path(handle.path().to_string_lossy().to_string().into());
                     }
                 }
             });
@@ -1359,127 +4218,125 @@ fn launch_import_flow(
     }
 
     {
-        let view_state = view_state.clone();
-        import_ui.on_select_all_requested(move |all_selected| {
-            set_all_checked(&view_state, all_selected);
+        let dialog_weak = dialog_weak.clone();
+        let export_weak = export_weak.clone();
+        let catalog_state = catalog_state.clone();
+        dialog.on_save_watermark(move || {
+            let Some(dialog) = dialog_weak.upgrade() else {
+                return;
+            };
+            let watermark = watermark_from_dialog(&dialog);
+            let result = match catalog_state.borrow().as_ref() {
+                Some(session) => session.service.save_watermark(&watermark),
+                None => return,
+            };
+            match result {
+                Ok(()) => {
+                    refresh_watermark_editor_names(&dialog, &catalog_state);
+                    dialog.set_selected_watermark(watermark.name.clone().into());
+                    if let Some(export) = export_weak.upgrade() {
+                        refresh_watermark_names(&export, &catalog_state);
+                    }
+                    dialog.set_status_text(format!("Saved watermark {}", watermark.name).into());
+                }
+                Err(err) => {
+                    dialog.set_status_text(format!("Failed to save watermark: {err}").into())
+                }
+            }
         });
     }
 
     {
-        let view_state = view_state.clone();
-        import_ui.on_thumbnail_clicked(move |image_id, shift, ctrl| {
-            if shift {
-                select_range(&view_state, image_id);
-            } else if ctrl {
-                toggle_single(&view_state, image_id);
-            } else {
-                select_single(&view_state, image_id);
+        let dialog_weak = dialog_weak.clone();
+        let export_weak = export_weak.clone();
+        let catalog_state = catalog_state.clone();
+        dialog.on_delete_watermark(move |name| {
+            let Some(dialog) = dialog_weak.upgrade() else {
+                return;
+            };
+            let result = match catalog_state.borrow().as_ref() {
+                Some(session) => session.service.delete_watermark(&name),
+                None => return,
+            };
+            if let Err(err) = result {
+                dialog.set_status_text(err.to_string().into());
+                return;
             }
+            refresh_watermark_editor_names(&dialog, &catalog_state);
+            dialog.set_selected_watermark("".into());
+            if let Some(export) = export_weak.upgrade() {
+                refresh_watermark_names(&export, &catalog_state);
+            }
+            dialog.set_status_text(format!("Deleted watermark {name}").into());
         });
     }
 
     {
-        let view_state = view_state.clone();
-        import_ui.on_checkbox_clicked(move |checked| {
-            apply_checkbox_to_selection(&view_state, checked);
+        let active = active.clone();
+        dialog.on_close(move || {
+            if let Some(dialog) = active.borrow_mut().take() {
+                dialog.hide().ok();
+            }
         });
     }
 
-    {
-        let view_state = view_state.clone();
-        import_ui.on_keyboard_select_all(move || {
-            select_all_allowed(&view_state);
-        });
-    }
+    dialog.show().ok();
+    *active.borrow_mut() = Some(dialog);
+}
 
-    {
-        let view_state = view_state.clone();
-        import_ui.on_keyboard_clear_selection(move || {
-            clear_selection(&view_state);
-        });
-    }
+fn refresh_watermark_editor_names(dialog: &WatermarkDialog, catalog_state: &CatalogState) {
+    let watermarks = match catalog_state.borrow().as_ref() {
+        Some(session) => session.service.list_watermarks().unwrap_or_else(|err| {
+            eprintln!("Failed to list watermarks: {err}");
+            Vec::new()
+        }),
+        None => Vec::new(),
+    };
+    let names: Vec<SharedString> = watermarks.into_iter().map(|w| w.name.into()).collect();
+    dialog.set_watermark_names(Rc::new(VecModel::from(names)).into());
+}
 
-    {
-        let import_ui_weak = import_ui_weak.clone();
-        let view_state = view_state.clone();
-        let active_import = active_import.clone();
-        import_ui.on_cancel_import(move || {
-            {
-                let guard = view_state.borrow();
-                guard.scan_cancel.cancel();
-                guard.import_cancel.cancel();
-            }
-            if let Some(ui) = import_ui_weak.upgrade() {
-                ui.hide().ok();
-            }
-            active_import.borrow_mut().take();
-        });
+fn apply_watermark_to_dialog(dialog: &WatermarkDialog, watermark: &WatermarkDefinition) {
+    dialog.set_watermark_name(watermark.name.clone().into());
+    match &watermark.kind {
+        WatermarkKind::Text { text, color } => {
+            dialog.set_kind("Text".into());
+            dialog.set_watermark_text(text.clone().into());
+            dialog.set_text_color(color.clone().into());
+        }
+        WatermarkKind::Logo { path } => {
+            dialog.set_kind("Logo".into());
+            dialog.set_logo_path(path.to_string_lossy().to_string().into());
+        }
     }
+    dialog.set_anchor(watermark.anchor.label().into());
+    dialog.set_size_percent(watermark.size.round() as i32);
+    dialog.set_opacity_percent(watermark.opacity.round() as i32);
+    dialog.set_margin_percent(watermark.margin.round() as i32);
+    dialog.set_status_text("".into());
+}
 
-    {
-        let import_ui_weak = import_ui_weak.clone();
-        let view_state = view_state.clone();
-        let active_import = active_import.clone();
-        let catalog_path = catalog_path.clone();
-        let catalog_state = catalog_state.clone();
-        let folio_state = folio_state.clone();
-        let config_store = config_store.clone();
-        let ui_main = ui_weak.clone();
-        import_ui.on_perform_import(
-            move |_source_dir, destination_dir, paths, keyword_text, method_str| {
-                let allow_duplicates = import_ui_weak
-                    .upgrade()
-                    .map(|ui| ui.get_allow_duplicates())
-                    .unwrap_or(false);
-                let mut seen_files = HashSet::new();
-                let mut files: Vec<PathBuf> = Vec::new();
-                for p in paths.iter() {
-                    let path_buf = PathBuf::from(p.as_str());
-                    if seen_files.insert(path_buf.clone()) {
-                        files.push(path_buf);
-                    }
-                }
-                let keywords = parse_keywords(keyword_text.as_str());
-                let method = match method_str.as_str() {
-                    "Copy" => ImportMethod::Copy,
-                    "Move" => ImportMethod::Move,
-                    _ => ImportMethod::Add,
-                };
-                let destination = match method {
-                    ImportMethod::Copy | ImportMethod::Move => {
-                        if destination_dir.is_empty() {
-                            None
-                        } else {
-                            Some(PathBuf::from(destination_dir.as_str()))
-                        }
-                    }
-                    ImportMethod::Add => None,
-                };
-                begin_import(
-                    catalog_path.clone(),
-                    &import_ui_weak,
-                    view_state.clone(),
-                    active_import.clone(),
-                    files,
-                    keywords,
-                    method,
-                    destination,
-                    allow_duplicates,
-                    ui_main.clone(),
-                    catalog_state.clone(),
-                    folio_state.clone(),
-                    config_store.clone(),
-                );
+fn watermark_from_dialog(dialog: &WatermarkDialog) -> WatermarkDefinition {
+    let anchor_label = dialog.get_anchor();
+    WatermarkDefinition {
+        name: dialog.get_watermark_name().trim().to_string(),
+        kind: match dialog.get_kind().as_str() {
+            "Logo" => WatermarkKind::Logo {
+                path: PathBuf::from(dialog.get_logo_path().as_str()),
             },
-        );
-    }
-
-    if let Some(ui) = ui_weak.upgrade() {
-        ui.set_status_text("Import window opened".into());
+            _ => WatermarkKind::Text {
+                text: dialog.get_watermark_text().to_string(),
+                color: dialog.get_text_color().trim().to_string(),
+            },
+        },
+        anchor: WatermarkAnchor::ALL
+            .into_iter()
+            .find(|anchor| anchor.label() == anchor_label.as_str())
+            .unwrap_or_default(),
+        size: dialog.get_size_percent() as f32,
+        opacity: dialog.get_opacity_percent() as f32,
+        margin: dialog.get_margin_percent() as f32,
     }
-
-    import_ui.show().ok();
-    *active_import.borrow_mut() = Some(import_ui);
 }
 
 fn refresh_recent_model(model: &Rc<VecModel<SharedString>>, entries: &[PathBuf]) {
@@ -1496,7 +4353,13 @@ fn open_catalog_service(path: &Path) -> anyhow::Result<(CatalogService, PathBuf)
         .to_str()
         .ok_or_else(|| anyhow!("invalid catalog path"))?;
     let db = CatalogDb::open(db_path)?;
-    Ok((CatalogService::new(db), normalized_path))
+    let service = CatalogService::new(db).with_home_dir(home_dir());
+    service.assign_folder_roots()?;
+    Ok((service, normalized_path))
+}
+
+fn home_dir() -> Option<PathBuf> {
+    directories::BaseDirs::new().map(|dirs| dirs.home_dir().to_path_buf())
 }
 
 fn create_catalog_service(path: &Path) -> anyhow::Result<(CatalogService, PathBuf)> {
@@ -1511,6 +4374,7 @@ fn create_catalog_service(path: &Path) -> anyhow::Result<(CatalogService, PathBu
 
 fn prompt_for_catalog_dialog(
     initial_error: Option<String>,
+    restore_hint: Option<PathBuf>,
 ) -> Result<Option<(CatalogService, PathBuf)>, slint::PlatformError> {
     let dialog = CatalogDialog::new()?;
     if let Some(msg) = initial_error {
@@ -1519,6 +4383,21 @@ fn prompt_for_catalog_dialog(
 
     let selected: Rc<RefCell<Option<(CatalogService, PathBuf)>>> = Rc::new(RefCell::new(None));
 
+    {
+        let selected = selected.clone();
+        attach_restore_handlers(
+            &dialog,
+            restore_hint,
+            |_| {},
+            move |service, path, restored| {
+                if restored {
+                    *selected.borrow_mut() = Some((service, path));
+                    slint::quit_event_loop().ok();
+                }
+            },
+        );
+    }
+
     {
         let dialog_weak = dialog.as_weak();
         let selected = selected.clone();
@@ -1583,13 +4462,17 @@ fn refresh_folio_tree(
     catalog_state: &CatalogState,
     folio_state: &Rc<RefCell<FolioState>>,
 ) {
-    let folders = {
+    let (folders, roots) = {
         let guard = catalog_state.borrow();
         let Some(session) = guard.as_ref() else {
             return;
         };
-        match session.service.list_folders() {
-            Ok(list) => list,
+        match session
+            .service
+            .list_folders()
+            .and_then(|folders| Ok((folders, session.service.list_roots()?)))
+        {
+            Ok(lists) => lists,
             Err(err) => {
                 eprintln!("Failed to list folders: {err}");
                 return;
@@ -1602,7 +4485,7 @@ fn refresh_folio_tree(
         collect_expanded_state(&guard.volume_tree)
     };
 
-    let tree = build_volume_tree(&folders, &expanded);
+    let tree = build_volume_tree(&folders, &roots, &expanded);
     let volumes = {
         let mut guard = folio_state.borrow_mut();
         guard.volume_tree = tree;
@@ -1613,6 +4496,91 @@ fn refresh_folio_tree(
     if let Some(ui) = ui_weak.upgrade() {
         ui.set_volumes(volumes.into());
     }
+
+    refresh_collections(ui_weak, catalog_state, folio_state);
+    refresh_timeline(ui_weak, catalog_state, folio_state);
+}
+
+/// List the catalog's collections in the Folio sidebar, alongside the virtual ones.
+fn refresh_collections(
+    ui_weak: &slint::Weak<MainWindow>,
+    catalog_state: &CatalogState,
+    folio_state: &Rc<RefCell<FolioState>>,
+) {
+    let collections = {
+        let guard = catalog_state.borrow();
+        let Some(session) = guard.as_ref() else {
+            return;
+        };
+        match session.service.list_collections() {
+            Ok(collections) => collections,
+            Err(err) => {
+                eprintln!("Failed to list collections: {err}");
+                return;
+            }
+        }
+    };
+    let items: Vec<VirtualCollectionItem> = collections
+        .into_iter()
+        .map(|collection| VirtualCollectionItem {
+            id: format!("collection:{}", collection.id).into(),
+            label: collection.name.into(),
+        })
+        .collect();
+    let model = {
+        let guard = folio_state.borrow();
+        guard.collections.set_vec(items);
+        guard.collections.clone()
+    };
+    if let Some(ui) = ui_weak.upgrade() {
+        ui.set_collections(model.into());
+    }
+}
+
+/// Reload the per-day image counts behind the timeline and its calendar.
+fn refresh_timeline(
+    ui_weak: &slint::Weak<MainWindow>,
+    catalog_state: &CatalogState,
+    folio_state: &Rc<RefCell<FolioState>>,
+) {
+    let counts = {
+        let guard = catalog_state.borrow();
+        let Some(session) = guard.as_ref() else {
+            return;
+        };
+        match session.service.count_images_per_day() {
+            Ok(counts) => counts,
+            Err(err) => {
+                eprintln!("Failed to count images per day: {err}");
+                return;
+            }
+        }
+    };
+    {
+        let mut guard = folio_state.borrow_mut();
+        let shown = guard.calendar_year;
+        if let Some(latest) = counts.last() {
+            if !counts.iter().any(|day| day.date.year() == shown) {
+                guard.calendar_year = latest.date.year();
+            }
+        }
+        guard.date_counts = counts;
+    }
+    show_timeline(ui_weak, folio_state);
+}
+
+fn show_timeline(ui_weak: &slint::Weak<MainWindow>, folio_state: &Rc<RefCell<FolioState>>) {
+    let guard = folio_state.borrow();
+    let years = catalog::db::timeline::date_hierarchy(&guard.date_counts);
+    guard
+        .date_nodes
+        .set_vec(timeline::date_rows(&years, &guard.expanded_dates));
+    let (days, months) = timeline::calendar(&guard.date_counts, guard.calendar_year);
+    if let Some(ui) = ui_weak.upgrade() {
+        ui.set_calendar_year(guard.calendar_year);
+        ui.set_calendar_days(Rc::new(VecModel::from(days)).into());
+        ui.set_calendar_months(Rc::new(VecModel::from(months)).into());
+    }
 }
 
 fn collect_expanded_state(volumes: &[VolumeTree]) -> HashMap<String, bool> {
@@ -1631,15 +4599,39 @@ fn collect_folder_expanded(nodes: &[FolderTreeNode], out: &mut HashMap<String, b
     }
 }
 
-fn build_volume_tree(folders: &[Folder], expanded: &HashMap<String, bool>) -> Vec<VolumeTree> {
+fn build_volume_tree(
+    folders: &[Folder],
+    roots: &[RootSummary],
+    expanded: &HashMap<String, bool>,
+) -> Vec<VolumeTree> {
     let mut grouped: BTreeMap<String, VolumeBuilder> = BTreeMap::new();
 
     for folder in folders {
         let path = PathBuf::from(&folder.path);
-        let (volume_name, volume_root, parts) = split_volume_components(&path);
+        let root = folder
+            .root_id
+            .and_then(|id| roots.iter().find(|summary| summary.root.id == id));
+        let (root_id, title, root_path, relative) = match (root, &folder.relative_path) {
+            (Some(summary), Some(relative)) => (
+                summary.root.id,
+                root_title(summary),
+                PathBuf::from(&summary.root.path),
+                relative.clone(),
+            ),
+            _ => {
+                let (name, volume) = volume_for_path(&path, home_dir().as_deref());
+                let relative = relative_path(&volume, &path);
+                (0, name, volume, relative)
+            }
+        };
+        let parts: Vec<String> = relative
+            .split('/')
+            .filter(|part| !part.is_empty())
+            .map(str::to_string)
+            .collect();
         let builder = grouped
-            .entry(volume_root.to_string_lossy().to_string())
-            .or_insert_with(|| VolumeBuilder::new(volume_name.clone(), volume_root.clone()));
+            .entry(root_path.to_string_lossy().to_string())
+            .or_insert_with(|| VolumeBuilder::new(root_id, title, root_path.clone()));
         builder.insert(&parts);
     }
 
@@ -1651,6 +4643,7 @@ fn build_volume_tree(folders: &[Folder], expanded: &HashMap<String, bool>) -> Ve
 
 #[derive(Clone)]
 struct VolumeTree {
+    root_id: i64,
     name: String,
     root_path: String,
     expanded: bool,
@@ -1671,6 +4664,7 @@ fn flatten_volume_tree(volume: &VolumeTree) -> VolumeNode {
     let row_model: Rc<VecModel<FolderTreeRow>> = Rc::new(VecModel::from(rows));
     VolumeNode {
         name: SharedString::from(volume.name.clone()),
+        root_id: volume.root_id as i32,
         rows: row_model.into(),
     }
 }
@@ -1777,96 +4771,37 @@ fn volume_label_for_drive(_drive: &str) -> Option<String> {
     None
 }
 
-fn split_volume_components(path: &Path) -> (String, PathBuf, Vec<String>) {
-    #[cfg(target_os = "windows")]
-    {
-        use std::path::{Component, Prefix};
-
-        let mut comps = path.components().peekable();
-        let mut drive: Option<String> = None;
-        let mut root = PathBuf::new();
-
-        while let Some(comp) = comps.peek() {
-            match comp {
-                Component::Prefix(prefix) => {
-                    let name = match prefix.kind() {
-                        Prefix::Disk(letter) | Prefix::VerbatimDisk(letter) => {
-                            format!("{}:", (letter as char).to_ascii_uppercase())
-                        }
-                        _ => prefix.as_os_str().to_string_lossy().to_string(),
-                    };
-                    drive = Some(name);
-                    root.push(prefix.as_os_str());
-                    comps.next();
-                }
-                Component::RootDir => {
-                    root.push(comp.as_os_str());
-                    comps.next();
-                }
-                _ => break,
-            }
-        }
-
-        let mut parts = Vec::new();
-        for comp in comps {
-            if let Component::Normal(name) = comp {
-                parts.push(name.to_string_lossy().to_string());
-            }
-        }
-
-        let drive_name = drive.unwrap_or_else(|| root.to_string_lossy().to_string());
-        let title = match volume_label_for_drive(&drive_name) {
-            Some(label) if !label.is_empty() => format!("{label} ({drive_name})"),
-            _ => drive_name.clone(),
-        };
-        let volume_root = if root.as_os_str().is_empty() {
-            PathBuf::from(format!("{drive_name}\\"))
-        } else {
-            root
-        };
-
-        return (title, volume_root, parts);
-    }
-
-    #[cfg(not(target_os = "windows"))]
-    {
-        use std::path::Component;
-
-        let mut comps = path.components();
-        let mut has_root = false;
-        let mut parts = Vec::new();
-
-        while let Some(comp) = comps.next() {
-            match comp {
-                Component::RootDir => {
-                    has_root = true;
-                }
-                Component::Normal(name) => parts.push(name.to_string_lossy().to_string()),
-                _ => {}
-            }
-        }
-
-        if has_root {
-            ("/".to_string(), PathBuf::from("/"), parts)
-        } else if let Some(first) = parts.first().cloned() {
-            let mut remaining = parts;
-            remaining.remove(0);
-            (first.clone(), PathBuf::from(first), remaining)
-        } else {
-            ("/".to_string(), PathBuf::from("/"), Vec::new())
-        }
+/// Heading for a root in the folder tree: the drive label for Windows drive roots, and
+/// a note when the root's location is not mounted.
+fn root_title(summary: &RootSummary) -> String {
+    let name = &summary.root.name;
+    let label = if name.len() == 2 && name.ends_with(':') {
+        volume_label_for_drive(name)
+    } else {
+        None
+    };
+    let title = match label {
+        Some(label) if !label.is_empty() => format!("{label} ({name})"),
+        _ => name.clone(),
+    };
+    if summary.online {
+        title
+    } else {
+        format!("{title} (offline)")
     }
 }
 
 struct VolumeBuilder {
+    root_id: i64,
     display_name: String,
     root: PathBuf,
     children: BTreeMap<String, FolderTreeBuilder>,
 }
 
 impl VolumeBuilder {
-    fn new(display_name: String, root: PathBuf) -> Self {
+    fn new(root_id: i64, display_name: String, root: PathBuf) -> Self {
         Self {
+            root_id,
             display_name,
             root,
             children: BTreeMap::new(),
@@ -1894,6 +4829,7 @@ impl VolumeBuilder {
             .collect();
         let is_expanded = expanded.get(&root_path).copied().unwrap_or(true);
         VolumeTree {
+            root_id: self.root_id,
             name: self.display_name,
             root_path,
             expanded: is_expanded,
@@ -1952,9 +4888,21 @@ fn selection_to_config(selection: &FolioSelection) -> FolioLastSelection {
         _ => None,
     };
 
+    let date = match selection {
+        FolioSelection::Date(period) => Some(period.to_string()),
+        _ => None,
+    };
+
+    let collection_id = match selection {
+        FolioSelection::Collection(id) => Some(*id),
+        _ => None,
+    };
+
     FolioLastSelection {
         kind: selection.kind().to_string(),
         folder_path,
+        date,
+        collection_id,
     }
 }
 
@@ -1962,10 +4910,17 @@ fn config_to_selection(snapshot: &FolioLastSelection) -> Option<FolioSelection>
     match snapshot.kind.as_str() {
         "all_photos" => Some(FolioSelection::AllPhotos),
         "last_import" => Some(FolioSelection::LastImport),
+        "duplicates" => Some(FolioSelection::Duplicates),
         "folder" => snapshot
             .folder_path
             .as_ref()
             .map(|p| FolioSelection::Folder(p.to_string_lossy().to_string())),
+        "date" => snapshot
+            .date
+            .as_deref()
+            .and_then(|raw| raw.parse().ok())
+            .map(FolioSelection::Date),
+        "collection" => snapshot.collection_id.map(FolioSelection::Collection),
         _ => None,
     }
 }
@@ -1980,6 +4935,18 @@ fn folder_exists_in_catalog(catalog_state: &CatalogState, path: &str) -> bool {
         .unwrap_or(false)
 }
 
+fn collection_exists_in_catalog(catalog_state: &CatalogState, id: i64) -> bool {
+    let guard = catalog_state.borrow();
+    let Some(session) = guard.as_ref() else {
+        return false;
+    };
+    session
+        .service
+        .list_collections()
+        .map(|collections| collections.iter().any(|collection| collection.id == id))
+        .unwrap_or(false)
+}
+
 fn current_catalog_path(catalog_state: &CatalogState) -> Option<PathBuf> {
     catalog_state
         .borrow()
@@ -2009,20 +4976,26 @@ fn apply_selection(
     ui_weak: &slint::Weak<MainWindow>,
     config_store: &ConfigStore,
 ) {
+    let sort = current_catalog_path(catalog_state)
+        .and_then(|catalog_path| config_store.folio_sort(&catalog_path, &selection.sort_scope()))
+        .unwrap_or_default();
     {
         let mut guard = folio_state.borrow_mut();
         guard.current_selection = Some(selection.clone());
+        guard.sort = sort;
     }
 
     if let Err(err) = config_store.set_folio_selection(selection_to_config(&selection)) {
         eprintln!("Failed to persist folio selection: {err}");
     }
+    show_sort(ui_weak, &selection, sort);
 
     match selection.clone() {
         FolioSelection::AllPhotos => {
             if let Some(ui) = ui_weak.upgrade() {
                 ui.set_selected_virtual_collection("all_photos".into());
                 ui.set_selected_folder_path("".into());
+                ui.set_selected_date("".into());
             }
             load_all_photos(catalog_state, folio_state, ui_weak);
         }
@@ -2030,15 +5003,25 @@ fn apply_selection(
             if let Some(ui) = ui_weak.upgrade() {
                 ui.set_selected_virtual_collection("last_import".into());
                 ui.set_selected_folder_path("".into());
+                ui.set_selected_date("".into());
             }
             let since =
                 last_import_timestamp_for_catalog(catalog_state, folio_state, config_store);
             load_last_import(catalog_state, folio_state, ui_weak, since.as_deref());
         }
+        FolioSelection::Duplicates => {
+            if let Some(ui) = ui_weak.upgrade() {
+                ui.set_selected_virtual_collection("duplicates".into());
+                ui.set_selected_folder_path("".into());
+                ui.set_selected_date("".into());
+            }
+            load_near_duplicates(catalog_state, folio_state, ui_weak);
+        }
         FolioSelection::Folder(path) => {
             if let Some(ui) = ui_weak.upgrade() {
                 ui.set_selected_folder_path(path.clone().into());
                 ui.set_selected_virtual_collection("".into());
+                ui.set_selected_date("".into());
             }
             load_folder_thumbnails(
                 catalog_state,
@@ -2047,9 +5030,61 @@ fn apply_selection(
                 Path::new(&path),
             );
         }
+        FolioSelection::Date(period) => {
+            let year = match period {
+                DatePeriod::Year(year) | DatePeriod::Month(year, _) => year,
+                DatePeriod::Day(date) => date.year(),
+            };
+            if folio_state.borrow().calendar_year != year {
+                folio_state.borrow_mut().calendar_year = year;
+                show_timeline(ui_weak, folio_state);
+            }
+            if let Some(ui) = ui_weak.upgrade() {
+                ui.set_selected_date(period.to_string().into());
+                ui.set_selected_folder_path("".into());
+                ui.set_selected_virtual_collection("".into());
+            }
+            load_date_thumbnails(catalog_state, folio_state, ui_weak, &period);
+        }
+        FolioSelection::Collection(id) => {
+            if let Some(ui) = ui_weak.upgrade() {
+                ui.set_selected_virtual_collection(format!("collection:{id}").into());
+                ui.set_selected_folder_path("".into());
+                ui.set_selected_date("".into());
+            }
+            load_collection_thumbnails(catalog_state, folio_state, ui_weak, id);
+        }
     }
 }
 
+/// Offer the sort keys that make sense for `selection` and show `sort` as the current one.
+/// Near-duplicate review keeps its cluster order, and only collections have a manual order.
+fn show_sort(ui_weak: &slint::Weak<MainWindow>, selection: &FolioSelection, sort: ImageSort) {
+    let Some(ui) = ui_weak.upgrade() else {
+        return;
+    };
+    let keys: Vec<SharedString> = SortKey::ALL
+        .iter()
+        .filter(|key| {
+            **key != SortKey::Manual || matches!(selection, FolioSelection::Collection(_))
+        })
+        .map(|key| SharedString::from(key.label()))
+        .collect();
+    let index = keys
+        .iter()
+        .position(|label| label.as_str() == sort.key.label())
+        .unwrap_or(0);
+    ui.set_sort_keys(Rc::new(VecModel::from(keys)).into());
+    ui.set_sort_index(index as i32);
+    ui.set_sort_descending(sort.descending);
+    ui.set_sort_enabled(!matches!(selection, FolioSelection::Duplicates));
+    ui.set_folio_reorder_enabled(
+        matches!(selection, FolioSelection::Collection(_))
+            && sort.key == SortKey::Manual
+            && !sort.descending,
+    );
+}
+
 fn reload_current_selection(
     catalog_state: &CatalogState,
     folio_state: &Rc<RefCell<FolioState>>,
@@ -2079,97 +5114,294 @@ fn restore_last_selection(
             selection = FolioSelection::AllPhotos;
         }
     }
+    if let FolioSelection::Collection(id) = selection {
+        if !collection_exists_in_catalog(catalog_state, id) {
+            selection = FolioSelection::AllPhotos;
+        }
+    }
 
     apply_selection(selection, catalog_state, folio_state, ui_weak, config_store);
 }
 
-fn build_thumbnail_items(
-    images: Vec<CatalogImage>,
-    filters: &FilterState,
-    service: &CatalogService,
-) -> (Vec<ThumbnailItem>, u64) {
-    let mut total_size = 0u64;
-    let mut items = Vec::new();
-    for img in images {
-        if !passes_filters(&img, filters) {
-            continue;
+/// Images fetched per query while the grid fills.
+const FOLIO_PAGE_SIZE: usize = 200;
+/// Rows kept loaded past the last one on screen, so scrolling rarely waits on a query.
+const FOLIO_PREFETCH_ROWS: usize = 120;
+/// Rows assumed on screen before the grid has reported what it shows.
+const FOLIO_INITIAL_ROWS: usize = 48;
+
+/// The list behind the Folio grid, read a page at a time as the grid scrolls.
+struct FolioFeed {
+    /// `None` when every image was handed over up front.
+    scope: Option<ImageScope>,
+    sort: ImageSort,
+    filters: FilterState,
+    collapse_stacks: bool,
+    memberships: HashMap<i64, StackMembership>,
+    /// Place names, which searching also matches.
+    locations: HashMap<i64, String>,
+    /// Row and file size of the image shown for each collapsed stack so far.
+    stack_rows: HashMap<i64, (usize, u64)>,
+    next: Option<PageCursor>,
+    done: bool,
+    total_size: u64,
+    /// Images in the scope before filtering, while more pages remain.
+    scope_count: Option<usize>,
+}
+
+impl FolioFeed {
+    fn new(
+        service: &CatalogService,
+        scope: Option<ImageScope>,
+        sort: ImageSort,
+        filters: FilterState,
+        collapse_stacks: bool,
+    ) -> Self {
+        let memberships = service.stack_memberships().unwrap_or_else(|err| {
+            eprintln!("Failed to load stacks: {err}");
+            HashMap::new()
+        });
+        let locations: HashMap<i64, String> = if filters.search.is_empty() {
+            HashMap::new()
+        } else {
+            ImageLocation::load_all(&service.db)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|location| (location.image_id, location.label()))
+                .collect()
+        };
+        let scope_count = scope.as_ref().and_then(|scope| {
+            service
+                .count_images_in(scope)
+                .map_err(|err| eprintln!("Failed to count images: {err}"))
+                .ok()
+        });
+        Self {
+            done: scope.is_none(),
+            scope,
+            sort,
+            filters,
+            collapse_stacks,
+            memberships,
+            locations,
+            stack_rows: HashMap::new(),
+            next: None,
+            total_size: 0,
+            scope_count,
         }
+    }
 
-        if let Some(sz) = img.filesize {
-            total_size += sz as u64;
+    /// Append the images that pass the filters to `model`. A collapsed stack takes one
+    /// row, where its first visible member sorts, and shows its top image once that turns
+    /// up.
+    fn add_images(&mut self, images: Vec<CatalogImage>, model: &FolioModel) {
+        let start = model.row_count();
+        let mut items: Vec<ThumbnailItem> = Vec::new();
+        for img in images {
+            if !passes_filters(&img, &self.filters, self.locations.get(&img.id)) {
+                continue;
+            }
+            let membership = self.memberships.get(&img.id);
+            let size = img.filesize.unwrap_or(0).max(0) as u64;
+            if let Some(m) = membership.filter(|m| self.collapse_stacks && m.collapsed) {
+                if let Some(&(row, shown_size)) = self.stack_rows.get(&m.stack_id) {
+                    if img.id == m.top_image_id {
+                        let item = thumbnail_item(&img, membership);
+                        if row < start {
+                            model.replace_row(row, item);
+                        } else {
+                            items[row - start] = item;
+                        }
+                        self.total_size = self.total_size - shown_size + size;
+                        self.stack_rows.insert(m.stack_id, (row, size));
+                    }
+                    continue;
+                }
+                self.stack_rows
+                    .insert(m.stack_id, (start + items.len(), size));
+            }
+            self.total_size += size;
+            items.push(thumbnail_item(&img, membership));
         }
+        model.append(items);
+    }
 
-        let display_thumb = load_or_generate_thumbnail(service, &img).unwrap_or_else(placeholder_image);
-        let flag = normalize_flag_value(img.flag.as_ref());
-        let color_label = normalize_color_label_value(img.color_label.as_ref());
+    /// Read pages until `model` has at least `rows` rows or the list runs out.
+    fn fill(&mut self, service: &CatalogService, model: &FolioModel, rows: usize) {
+        while !self.done && model.row_count() < rows {
+            let Some(scope) = &self.scope else {
+                break;
+            };
+            match service.list_images_page(scope, &self.sort, self.next.as_ref(), FOLIO_PAGE_SIZE) {
+                Ok(page) => {
+                    self.next = page.next;
+                    self.done = self.next.is_none();
+                    self.add_images(page.images, model);
+                }
+                Err(err) => {
+                    eprintln!("Failed to load images: {err}");
+                    self.done = true;
+                }
+            }
+        }
+    }
+}
 
-        items.push(ThumbnailItem {
-            id: img.id as i32,
-            path: SharedString::from(img.original_path.clone()),
-            display_thumb,
-            selected: false,
-            rating: img.rating.unwrap_or(0) as i32,
-            flag: SharedString::from(flag),
-            color_label: SharedString::from(color_label),
-        });
+fn thumbnail_item(img: &CatalogImage, membership: Option<&StackMembership>) -> ThumbnailItem {
+    ThumbnailItem {
+        id: img.id as i32,
+        path: SharedString::from(img.original_path.clone()),
+        display_thumb: slint::Image::default(),
+        selected: false,
+        rating: img.rating.unwrap_or(0) as i32,
+        flag: SharedString::from(normalize_flag_value(img.flag.as_ref())),
+        color_label: SharedString::from(normalize_color_label_value(img.color_label.as_ref())),
+        stack_id: membership.map(|m| m.stack_id as i32).unwrap_or(-1),
+        stack_size: membership.map(|m| m.size as i32).unwrap_or(0),
+        stack_top: membership
+            .map(|m| m.top_image_id == img.id)
+            .unwrap_or(false),
+        stack_collapsed: membership.map(|m| m.collapsed).unwrap_or(false),
+        duplicate_group: -1,
+        duplicate_keep: false,
     }
-    (items, total_size)
 }
 
+/// Show the rows `feed` has added to the now emptied grid, loading the first screenful.
 fn apply_thumbnail_view(
-    items: Vec<ThumbnailItem>,
-    total_size: u64,
+    feed: FolioFeed,
+    catalog_state: &CatalogState,
     folio_state: &Rc<RefCell<FolioState>>,
     ui_weak: &slint::Weak<MainWindow>,
 ) {
-    let count = items.len();
-    {
+    let keep_scroll = {
         let mut guard = folio_state.borrow_mut();
-        guard.thumbnails.set_vec(items);
+        guard.feed = Some(feed);
         guard.reset_selection();
-    }
+        let keep_scroll = std::mem::take(&mut guard.keep_scroll_on_reload);
+        if !keep_scroll {
+            guard.visible_rows.0 = 0;
+        }
+        keep_scroll
+    };
 
     if let Some(ui) = ui_weak.upgrade() {
         ui.set_thumbnails(folio_state.borrow().thumbnails.clone().into());
-        ui.set_folio_total_count(count as i32);
         ui.set_folio_selected_count(0);
-        ui.set_folio_size_summary(human_size(total_size).into());
         ui.set_metadata(empty_metadata());
         ui.set_keywords_text("".into());
         ui.set_selected_image_id(-1);
-        ui.invoke_reset_thumbnail_scroll();
+        if !keep_scroll {
+            ui.invoke_reset_thumbnail_scroll();
+        }
+    }
+    show_visible_thumbnails(catalog_state, folio_state, ui_weak);
+}
+
+/// Load the rows the grid shows, reading more pages as it nears the end of what it has,
+/// and decode their thumbnails.
+fn show_visible_thumbnails(
+    catalog_state: &CatalogState,
+    folio_state: &Rc<RefCell<FolioState>>,
+    ui_weak: &slint::Weak<MainWindow>,
+) {
+    let (model, mut feed, (first, count)) = {
+        let mut guard = folio_state.borrow_mut();
+        (
+            guard.thumbnails.clone(),
+            guard.feed.take(),
+            guard.visible_rows,
+        )
+    };
+    let count = if count == 0 {
+        FOLIO_INITIAL_ROWS
+    } else {
+        count
+    };
+    {
+        let guard = catalog_state.borrow();
+        if let Some(session) = guard.as_ref() {
+            if let Some(feed) = feed.as_mut() {
+                feed.fill(
+                    &session.service,
+                    &model,
+                    first + count + FOLIO_PREFETCH_ROWS,
+                );
+            }
+            model.decode_rows(first..first + count, |image_id, path| {
+                load_or_generate_thumbnail(&session.service, image_id, path)
+            });
+        }
+    }
+    let (total_size, estimate) = feed
+        .as_ref()
+        .map(|feed| {
+            let estimate = if feed.done { None } else { feed.scope_count };
+            (feed.total_size, estimate)
+        })
+        .unwrap_or((0, None));
+    folio_state.borrow_mut().feed = feed;
+
+    if let Some(ui) = ui_weak.upgrade() {
+        ui.set_folio_total_count(model.row_count() as i32);
+        ui.set_folio_total_estimate(estimate.map(|total| total as i32).unwrap_or(-1));
+        ui.set_folio_size_summary(human_size(total_size).into());
     }
 }
 
+/// Read every remaining page, for commands that act on the whole list.
+fn load_all_rows(service: &CatalogService, folio_state: &Rc<RefCell<FolioState>>) {
+    let (model, feed) = {
+        let mut guard = folio_state.borrow_mut();
+        (guard.thumbnails.clone(), guard.feed.take())
+    };
+    let Some(mut feed) = feed else {
+        return;
+    };
+    feed.fill(service, &model, usize::MAX);
+    folio_state.borrow_mut().feed = Some(feed);
+}
+
 fn parse_timestamp(raw: &str) -> Option<DateTime<Utc>> {
     DateTime::parse_from_rfc3339(raw)
         .map(|dt| dt.with_timezone(&Utc))
         .ok()
 }
 
-fn load_folder_thumbnails(
+/// Start the grid over on `scope`, in the current sort order and filters. A `None` scope
+/// shows an empty grid.
+fn load_scope(
     catalog_state: &CatalogState,
     folio_state: &Rc<RefCell<FolioState>>,
     ui_weak: &slint::Weak<MainWindow>,
-    folder_path: &Path,
+    scope: Option<ImageScope>,
 ) {
-    let (items, total_size) = {
+    let feed = {
         let guard = catalog_state.borrow();
         let Some(session) = guard.as_ref() else {
             return;
         };
-        let filters = folio_state.borrow().filters.clone();
-        let images = match session.service.list_images_recursively(folder_path) {
-            Ok(list) => list,
-            Err(err) => {
-                eprintln!("Failed to list images: {err}");
-                return;
-            }
-        };
-        build_thumbnail_items(images, &filters, &session.service)
+        let state = folio_state.borrow();
+        state.thumbnails.set_vec(Vec::new());
+        FolioFeed::new(
+            &session.service,
+            scope,
+            state.sort,
+            state.filters.clone(),
+            true,
+        )
     };
+    apply_thumbnail_view(feed, catalog_state, folio_state, ui_weak);
+}
 
-    apply_thumbnail_view(items, total_size, folio_state, ui_weak);
+fn load_folder_thumbnails(
+    catalog_state: &CatalogState,
+    folio_state: &Rc<RefCell<FolioState>>,
+    ui_weak: &slint::Weak<MainWindow>,
+    folder_path: &Path,
+) {
+    let scope = ImageScope::Folder(folder_path.to_string_lossy().to_string());
+    load_scope(catalog_state, folio_state, ui_weak, Some(scope));
 }
 
 fn load_all_photos(
@@ -2177,23 +5409,27 @@ fn load_all_photos(
     folio_state: &Rc<RefCell<FolioState>>,
     ui_weak: &slint::Weak<MainWindow>,
 ) {
-    let (items, total_size) = {
-        let guard = catalog_state.borrow();
-        let Some(session) = guard.as_ref() else {
-            return;
-        };
-        let filters = folio_state.borrow().filters.clone();
-        let images = match session.service.list_all_photos() {
-            Ok(list) => list,
-            Err(err) => {
-                eprintln!("Failed to list all photos: {err}");
-                return;
-            }
-        };
-        build_thumbnail_items(images, &filters, &session.service)
-    };
+    load_scope(catalog_state, folio_state, ui_weak, Some(ImageScope::All));
+}
 
-    apply_thumbnail_view(items, total_size, folio_state, ui_weak);
+fn load_date_thumbnails(
+    catalog_state: &CatalogState,
+    folio_state: &Rc<RefCell<FolioState>>,
+    ui_weak: &slint::Weak<MainWindow>,
+    period: &DatePeriod,
+) {
+    let scope = ImageScope::TakenIn(*period);
+    load_scope(catalog_state, folio_state, ui_weak, Some(scope));
+}
+
+fn load_collection_thumbnails(
+    catalog_state: &CatalogState,
+    folio_state: &Rc<RefCell<FolioState>>,
+    ui_weak: &slint::Weak<MainWindow>,
+    collection_id: i64,
+) {
+    let scope = ImageScope::Collection(collection_id);
+    load_scope(catalog_state, folio_state, ui_weak, Some(scope));
 }
 
 fn load_last_import(
@@ -2202,27 +5438,86 @@ fn load_last_import(
     ui_weak: &slint::Weak<MainWindow>,
     since: Option<&str>,
 ) {
-    let (items, total_size) = {
+    let cutoff = match since.and_then(parse_timestamp) {
+        Some(cutoff) => Some(cutoff),
+        None => {
+            let guard = catalog_state.borrow();
+            let Some(session) = guard.as_ref() else {
+                return;
+            };
+            session
+                .service
+                .last_import_timestamp()
+                .unwrap_or_else(|err| {
+                    eprintln!("Failed to find the last import: {err}");
+                    None
+                })
+        }
+    };
+    let scope = cutoff.map(ImageScope::ImportedSince);
+    load_scope(catalog_state, folio_state, ui_weak, scope);
+}
+
+/// Show near-duplicate clusters back to back, each led by its suggested keeper. Stacks stay
+/// expanded so every member of a cluster can be compared.
+fn load_near_duplicates(
+    catalog_state: &CatalogState,
+    folio_state: &Rc<RefCell<FolioState>>,
+    ui_weak: &slint::Weak<MainWindow>,
+) {
+    let feed = {
         let guard = catalog_state.borrow();
         let Some(session) = guard.as_ref() else {
             return;
         };
-        let filters = folio_state.borrow().filters.clone();
-        let since_dt = since.and_then(parse_timestamp);
-        let images = match session.service.list_last_import(since_dt) {
-            Ok(list) => list,
+        let (model, sort, filters, threshold) = {
+            let state = folio_state.borrow();
+            (
+                state.thumbnails.clone(),
+                state.sort,
+                state.filters.clone(),
+                state.duplicate_threshold,
+            )
+        };
+        let clusters = match session.service.find_near_duplicates(threshold) {
+            Ok(clusters) => clusters,
             Err(err) => {
-                eprintln!("Failed to list last import: {err}");
+                eprintln!("Failed to find near duplicates: {err}");
                 return;
             }
         };
-        build_thumbnail_items(images, &filters, &session.service)
+
+        let mut groups: HashMap<i64, (i32, bool)> = HashMap::new();
+        let mut images = Vec::new();
+        for (group, cluster) in clusters.into_iter().enumerate() {
+            let mut members = cluster.images;
+            members.sort_by_key(|img| img.id != cluster.keep_image_id);
+            for img in members {
+                groups.insert(img.id, (group as i32, img.id == cluster.keep_image_id));
+                images.push(img);
+            }
+        }
+
+        let mut feed = FolioFeed::new(&session.service, None, sort, filters, false);
+        model.set_vec(Vec::new());
+        feed.add_images(images, &model);
+        for row in 0..model.row_count() {
+            let Some(mut item) = model.row_data(row) else {
+                continue;
+            };
+            if let Some(&(group, keep)) = groups.get(&(item.id as i64)) {
+                item.duplicate_group = group;
+                item.duplicate_keep = keep;
+                model.set_row_data(row, item);
+            }
+        }
+        feed
     };
 
-    apply_thumbnail_view(items, total_size, folio_state, ui_weak);
+    apply_thumbnail_view(feed, catalog_state, folio_state, ui_weak);
 }
 
-fn passes_filters(image: &CatalogImage, filters: &FilterState) -> bool {
+fn passes_filters(image: &CatalogImage, filters: &FilterState, location: Option<&String>) -> bool {
     if filters.rating > 0 && image.rating.unwrap_or(0) < filters.rating as i64 {
         return false;
     }
@@ -2250,9 +5545,10 @@ fn passes_filters(image: &CatalogImage, filters: &FilterState) -> bool {
     if !filters.search.is_empty() {
         let needle = filters.search.to_ascii_lowercase();
         let haystack = format!(
-            "{} {}",
+            "{} {} {}",
             image.filename.to_ascii_lowercase(),
-            image.original_path.to_ascii_lowercase()
+            image.original_path.to_ascii_lowercase(),
+            location.map(|l| l.to_lowercase()).unwrap_or_default()
         );
         if !haystack.contains(&needle) {
             return false;
@@ -2264,16 +5560,17 @@ fn passes_filters(image: &CatalogImage, filters: &FilterState) -> bool {
 
 fn load_or_generate_thumbnail(
     service: &CatalogService,
-    image: &CatalogImage,
+    image_id: i64,
+    original_path: &str,
 ) -> Option<slint::Image> {
-    if let Ok(Some(thumb)) = service.load_thumbnail(image.id) {
+    if let Ok(Some(thumb)) = service.load_thumbnail(image_id) {
         if let Some(img) = thumbnail_to_image(&thumb) {
             return Some(img);
         }
     }
 
-    let path = PathBuf::from(&image.original_path);
-    if let Ok(Some(thumb)) = service.generate_thumbnail(image.id, &path) {
+    let path = PathBuf::from(original_path);
+    if let Ok(Some(thumb)) = service.generate_thumbnail(image_id, &path) {
         return thumbnail_to_image(&thumb);
     }
 
@@ -2419,8 +5716,9 @@ fn refresh_thumbnail(
             .service
             .load_metadata(image_id)
             .with_context(|| format!("Failed to load metadata for image_id={image_id}"))?;
-        let display_thumb = load_or_generate_thumbnail(&session.service, &meta.image)
-            .unwrap_or_else(placeholder_image);
+        let display_thumb =
+            load_or_generate_thumbnail(&session.service, meta.image.id, &meta.image.original_path)
+                .unwrap_or_else(placeholder_image);
         (meta.image, display_thumb)
     };
 
@@ -2437,20 +5735,32 @@ fn refresh_thumbnail(
 
     if let Some(idx) = target_idx {
         let is_selected = guard.selection.contains(&(image_id as i32));
+        let Some(existing) = guard.thumbnails.row_data(idx) else {
+            return Ok(());
+        };
         guard.thumbnails.set_row_data(
             idx,
             ThumbnailItem {
                 id: image.id as i32,
                 path: SharedString::from(image.original_path.clone()),
-                display_thumb,
+                display_thumb: slint::Image::default(),
                 rating: image.rating.unwrap_or(0) as i32,
                 flag: SharedString::from(normalize_flag_value(image.flag.as_ref())),
                 color_label: SharedString::from(normalize_color_label_value(
                     image.color_label.as_ref(),
                 )),
                 selected: is_selected,
+                stack_id: existing.stack_id,
+                stack_size: existing.stack_size,
+                stack_top: existing.stack_top,
+                stack_collapsed: existing.stack_collapsed,
+                duplicate_group: existing.duplicate_group,
+                duplicate_keep: existing.duplicate_keep,
             },
         );
+        guard
+            .thumbnails
+            .set_thumbnail(image.id as i32, display_thumb);
     }
 
     Ok(())
@@ -2523,6 +5833,12 @@ fn refresh_metadata_panel(
         iso: iso.into(),
         gps_lat: gps_lat.into(),
         gps_lon: gps_lon.into(),
+        location: meta
+            .location
+            .as_ref()
+            .map(|location| location.label())
+            .unwrap_or_default()
+            .into(),
         rating: image.rating.unwrap_or(0) as i32,
         flag: normalize_flag_value(image.flag.as_ref()).into(),
         color_label: normalize_color_label_value(image.color_label.as_ref()).into(),
@@ -2538,6 +5854,167 @@ fn refresh_metadata_panel(
     Ok(())
 }
 
+/// Grid-order ids of the selected thumbnails, or of every visible thumbnail when nothing is
+/// selected and `fallback_to_all` is set.
+fn selected_ids_in_grid_order(
+    folio_state: &Rc<RefCell<FolioState>>,
+    fallback_to_all: bool,
+) -> Vec<i64> {
+    let guard = folio_state.borrow();
+    let use_all = fallback_to_all && guard.selection.len() < 2;
+    guard
+        .thumbnails
+        .iter()
+        .filter(|thumb| use_all || thumb.selected)
+        .map(|thumb| thumb.id as i64)
+        .collect()
+}
+
+/// Move the dragged image, or the whole selection when it is part of it, in front of the
+/// thumbnail at grid `index` (or after the last one), keeping the moved images' own order.
+fn move_in_collection(
+    catalog_state: &CatalogState,
+    folio_state: &Rc<RefCell<FolioState>>,
+    image_id: i32,
+    index: i32,
+) -> anyhow::Result<()> {
+    let Some(FolioSelection::Collection(collection_id)) =
+        folio_state.borrow().current_selection.clone()
+    else {
+        return Ok(());
+    };
+    let (moving, before, after) = {
+        let guard = folio_state.borrow();
+        let dragged_selected = guard
+            .thumbnails
+            .iter()
+            .any(|thumb| thumb.id == image_id && thumb.selected);
+        let moving: HashSet<i64> = guard
+            .thumbnails
+            .iter()
+            .filter(|thumb| {
+                if dragged_selected {
+                    thumb.selected
+                } else {
+                    thumb.id == image_id
+                }
+            })
+            .map(|thumb| thumb.id as i64)
+            .collect();
+        let staying: Vec<(usize, i64)> = guard
+            .thumbnails
+            .iter()
+            .map(|thumb| thumb.id as i64)
+            .enumerate()
+            .filter(|(_, id)| !moving.contains(id))
+            .collect();
+        let index = index.max(0) as usize;
+        let before = staying
+            .iter()
+            .find(|(row, _)| *row >= index)
+            .map(|(_, id)| *id);
+        let after = staying.last().map(|(_, id)| *id);
+        (moving, before, after)
+    };
+
+    let guard = catalog_state.borrow();
+    let session = guard.as_ref().context("No catalog open")?;
+    let members: Vec<i64> = session
+        .service
+        .list_images_in_collection(collection_id)?
+        .into_iter()
+        .map(|image| image.id)
+        .collect();
+    let (moved, mut order): (Vec<i64>, Vec<i64>) =
+        members.into_iter().partition(|id| moving.contains(id));
+    let at = match (before, after) {
+        (Some(id), _) => order.iter().position(|other| *other == id),
+        (None, Some(id)) => order
+            .iter()
+            .position(|other| *other == id)
+            .map(|pos| pos + 1),
+        (None, None) => return Ok(()),
+    }
+    .unwrap_or(order.len());
+    order.splice(at..at, moved);
+    session.service.reorder_collection(collection_id, &order)
+}
+
+fn apply_stack_command(
+    command: &str,
+    image_id: i32,
+    gap_seconds: i32,
+    catalog_state: &CatalogState,
+    folio_state: &Rc<RefCell<FolioState>>,
+) -> anyhow::Result<()> {
+    let guard = catalog_state.borrow();
+    let session = guard.as_ref().context("No catalog open")?;
+    let service = &session.service;
+    let memberships: HashMap<i64, StackMembership> = service.stack_memberships()?;
+    let stack_of = |id: i64| memberships.get(&id).map(|m| m.stack_id);
+
+    match command {
+        "stack" => {
+            let ids = selected_ids_in_grid_order(folio_state, false);
+            service.create_stack(&ids, None)?;
+        }
+        "unstack" => {
+            let mut stack_ids: Vec<i64> = selected_ids_in_grid_order(folio_state, false)
+                .into_iter()
+                .filter_map(stack_of)
+                .collect();
+            stack_ids.sort_unstable();
+            stack_ids.dedup();
+            for stack_id in stack_ids {
+                service.unstack(stack_id)?;
+            }
+        }
+        "remove" => {
+            service.remove_from_stack(&selected_ids_in_grid_order(folio_state, false))?;
+        }
+        "make_top" => {
+            let stack_id = stack_of(image_id as i64).context("Image is not in a stack")?;
+            service.set_stack_top(stack_id, image_id as i64)?;
+        }
+        "toggle" => {
+            let membership = memberships
+                .get(&(image_id as i64))
+                .context("Image is not in a stack")?;
+            service.set_stack_collapsed(membership.stack_id, !membership.collapsed)?;
+        }
+        "auto" => {
+            if folio_state.borrow().selection.len() < 2 {
+                load_all_rows(service, folio_state);
+            }
+            let ids = selected_ids_in_grid_order(folio_state, true);
+            let gap = chrono::Duration::seconds(gap_seconds.max(0) as i64);
+            service.auto_stack_by_capture_gap(&ids, gap)?;
+        }
+        other => return Err(anyhow!("Unknown stack command: {other}")),
+    }
+    Ok(())
+}
+
+/// Flag every listed near-duplicate except each cluster's suggested keeper as rejected.
+fn reject_duplicate_extras(
+    catalog_state: &CatalogState,
+    folio_state: &Rc<RefCell<FolioState>>,
+) -> anyhow::Result<()> {
+    let guard = catalog_state.borrow();
+    let session = guard.as_ref().context("No catalog open")?;
+    let extras: Vec<i64> = folio_state
+        .borrow()
+        .thumbnails
+        .iter()
+        .filter(|item| item.duplicate_group >= 0 && !item.duplicate_keep)
+        .map(|item| item.id as i64)
+        .collect();
+    for image_id in extras {
+        session.service.update_flag(image_id, "rejected")?;
+    }
+    Ok(())
+}
+
 fn apply_rating_change(
     catalog_state: &CatalogState,
     folio_state: &Rc<RefCell<FolioState>>,
@@ -0,0 +1,220 @@
+//! Basemap and projection for the map tab.
+//!
+//! The map uses an equirectangular projection, the same latitude/longitude grid the
+//! catalog clusters images on, and is rasterised from the coarse outlines bundled in
+//! `data/world_land.txt` so no tile server is needed. Positions on the map are fractions of
+//! the world's width and height, which the UI scales to the current zoom level.
+
+use catalog::db::BoundingBox;
+use image::{Rgba, RgbaImage};
+
+const WORLD_LAND: &str = include_str!("../data/world_land.txt");
+
+const OCEAN: Rgba<u8> = Rgba([18, 27, 38, 255]);
+const GRATICULE: Rgba<u8> = Rgba([28, 40, 54, 255]);
+const LAND: Rgba<u8> = Rgba([52, 58, 52, 255]);
+const COAST: Rgba<u8> = Rgba([92, 100, 90, 255]);
+
+/// Width of the rendered basemap in pixels; its height is half of it.
+pub const BASEMAP_WIDTH: u32 = 2048;
+
+/// Roughly how wide a cluster cell is on screen.
+const CLUSTER_CELL_PX: f64 = 96.0;
+
+struct Outline {
+    lake: bool,
+    /// `(longitude, latitude)` vertices.
+    points: Vec<(f64, f64)>,
+}
+
+fn parse_outlines(text: &str) -> Vec<Outline> {
+    let mut outlines: Vec<Outline> = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some((kind, _name)) = line.split_once(' ') {
+            if kind == "land" || kind == "lake" {
+                outlines.push(Outline {
+                    lake: kind == "lake",
+                    points: Vec::new(),
+                });
+                continue;
+            }
+        }
+        let Some(outline) = outlines.last_mut() else {
+            continue;
+        };
+        outline
+            .points
+            .extend(line.split_whitespace().filter_map(|pair| {
+                let (lon, lat) = pair.split_once(',')?;
+                Some((lon.parse().ok()?, lat.parse().ok()?))
+            }));
+    }
+    outlines
+}
+
+/// Draw the world `width` pixels wide: land over a graticule of 30° lines, with lakes cut
+/// out and a lighter rim where land meets water.
+pub fn render_basemap(width: u32) -> RgbaImage {
+    let width = width.max(2);
+    let height = width / 2;
+    let mut land = vec![false; (width * height) as usize];
+    for outline in parse_outlines(WORLD_LAND) {
+        fill_outline(&outline.points, width, height, |index| {
+            land[index] = !outline.lake;
+        });
+    }
+
+    let is_land = |x: u32, y: u32| land[(y * width + x) as usize];
+    let on_graticule = |x: u32, y: u32| {
+        let cell = width / 12;
+        cell > 0 && (x.is_multiple_of(cell) || y.is_multiple_of(cell))
+    };
+    RgbaImage::from_fn(width, height, |x, y| {
+        if !is_land(x, y) {
+            return if on_graticule(x, y) { GRATICULE } else { OCEAN };
+        }
+        let coast = (x > 0 && !is_land(x - 1, y))
+            || (x + 1 < width && !is_land(x + 1, y))
+            || (y > 0 && !is_land(x, y - 1))
+            || (y + 1 < height && !is_land(x, y + 1));
+        if coast {
+            COAST
+        } else {
+            LAND
+        }
+    })
+}
+
+/// Call `paint` with the index of every pixel whose centre lies inside `points`.
+fn fill_outline(points: &[(f64, f64)], width: u32, height: u32, mut paint: impl FnMut(usize)) {
+    let mut crossings = Vec::new();
+    for y in 0..height {
+        let latitude = 90.0 - (f64::from(y) + 0.5) / f64::from(height) * 180.0;
+        crossings.clear();
+        for (i, &(lon_a, lat_a)) in points.iter().enumerate() {
+            let (lon_b, lat_b) = points[(i + 1) % points.len()];
+            if (lat_a > latitude) != (lat_b > latitude) {
+                let longitude = lon_a + (latitude - lat_a) * (lon_b - lon_a) / (lat_b - lat_a);
+                crossings.push((longitude + 180.0) / 360.0 * f64::from(width));
+            }
+        }
+        crossings.sort_by(f64::total_cmp);
+        for span in crossings.chunks_exact(2) {
+            let start = (span[0] - 0.5).ceil().max(0.0) as u32;
+            let end = ((span[1] - 0.5).floor() as i64).min(i64::from(width) - 1);
+            for x in start as i64..=end {
+                paint((y * width) as usize + x as usize);
+            }
+        }
+    }
+}
+
+/// A position as fractions of the map's width and height from its top-left corner.
+pub fn to_map(latitude: f64, longitude: f64) -> (f64, f64) {
+    ((longitude + 180.0) / 360.0, (90.0 - latitude) / 180.0)
+}
+
+/// The latitude and longitude under a point of the map, clamped to the world.
+pub fn from_map(x: f64, y: f64) -> (f64, f64) {
+    (
+        (90.0 - y * 180.0).clamp(-90.0, 90.0),
+        (x * 360.0 - 180.0).clamp(-180.0, 180.0),
+    )
+}
+
+/// The part of the world the map tab shows, in the UI's logical pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    /// The centre of the view as fractions of the map's width and height.
+    pub center_x: f64,
+    pub center_y: f64,
+    /// Width of the whole world at the current zoom level.
+    pub world_width: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Viewport {
+    /// The catalog's clustering level whose cells are about [`CLUSTER_CELL_PX`] wide.
+    pub fn cluster_zoom(&self) -> u8 {
+        (self.world_width / CLUSTER_CELL_PX)
+            .log2()
+            .floor()
+            .clamp(0.0, 24.0) as u8
+    }
+
+    /// The area to cluster: the view and half a view around it, so pins just off screen
+    /// are ready when panning, widened to whole cluster cells so counts don't change with
+    /// the exact pan position.
+    pub fn bounds(&self) -> BoundingBox {
+        let world_height = self.world_width / 2.0;
+        let half_x = self.width / self.world_width;
+        let half_y = self.height / world_height;
+        let (north, west) = from_map(self.center_x - half_x, self.center_y - half_y);
+        let (south, east) = from_map(self.center_x + half_x, self.center_y + half_y);
+
+        let cells = f64::from(1u32 << self.cluster_zoom());
+        let (lon_size, lat_size) = (360.0 / cells, 180.0 / cells);
+        let snap_down =
+            |value: f64, origin: f64, size: f64| origin + ((value - origin) / size).floor() * size;
+        let snap_up =
+            |value: f64, origin: f64, size: f64| origin + ((value - origin) / size).ceil() * size;
+        BoundingBox {
+            south: snap_down(south, -90.0, lat_size).max(-90.0),
+            west: snap_down(west, -180.0, lon_size).max(-180.0),
+            north: snap_up(north, -90.0, lat_size).min(90.0),
+            east: snap_up(east, -180.0, lon_size).min(180.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_land_and_water() {
+        let map = render_basemap(720);
+        let pixel = |latitude: f64, longitude: f64| {
+            let (x, y) = to_map(latitude, longitude);
+            *map.get_pixel((x * 720.0) as u32, (y * 360.0) as u32)
+        };
+        assert_eq!(pixel(23.0, 10.0), LAND, "Sahara");
+        assert_eq!(pixel(-25.0, 135.0), LAND, "Australia");
+        assert_eq!(pixel(5.0, -25.0), OCEAN, "Atlantic");
+        assert_eq!(pixel(42.0, 51.0), OCEAN, "Caspian Sea");
+    }
+
+    #[test]
+    fn viewport_snaps_to_cluster_cells() {
+        let (x, y) = to_map(38.72, -9.14);
+        let (latitude, longitude) = from_map(x, y);
+        assert!((latitude - 38.72).abs() < 1e-9 && (longitude + 9.14).abs() < 1e-9);
+
+        let world = Viewport {
+            center_x: 0.5,
+            center_y: 0.5,
+            world_width: 1200.0,
+            width: 1200.0,
+            height: 600.0,
+        };
+        assert_eq!(world.cluster_zoom(), 3);
+        assert_eq!(world.bounds(), BoundingBox::WORLD);
+
+        let zoomed = Viewport {
+            center_x: x,
+            center_y: y,
+            world_width: 1200.0 * 16.0,
+            ..world
+        };
+        assert_eq!(zoomed.cluster_zoom(), 7);
+        let bounds = zoomed.bounds();
+        assert!(bounds.contains(38.72, -9.14));
+        assert!(bounds.east - bounds.west < 60.0);
+        let cell = 360.0 / 128.0;
+        assert!(((bounds.west + 180.0) / cell).fract().abs() < 1e-9);
+    }
+}
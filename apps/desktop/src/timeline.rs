@@ -0,0 +1,171 @@
+//! Rows for the Folio sidebar's timeline and cells for its calendar heatmap.
+//!
+//! Both are built from the catalog's per-day image counts. Nodes and cells are keyed by
+//! the `YYYY`, `YYYY-MM` or `YYYY-MM-DD` period they cover, which is what selecting one
+//! hands back to [`catalog::db::DatePeriod`].
+
+use std::collections::{HashMap, HashSet};
+
+use catalog::db::{DayCount, YearCount};
+use chrono::{Datelike, NaiveDate};
+use slint::SharedString;
+
+use crate::{CalendarDay, CalendarMonth, DateNode};
+
+/// Flatten the year > month > day tree into sidebar rows, showing the children of the
+/// nodes whose keys are in `expanded`. Newest years come first.
+pub fn date_rows(years: &[YearCount], expanded: &HashSet<String>) -> Vec<DateNode> {
+    let mut rows = Vec::new();
+    for year in years.iter().rev() {
+        let year_key = format!("{:04}", year.year);
+        let year_open = expanded.contains(&year_key);
+        rows.push(DateNode {
+            key: year_key.clone().into(),
+            label: year.year.to_string().into(),
+            count: year.count as i32,
+            level: 0,
+            expanded: year_open,
+            has_children: !year.months.is_empty(),
+        });
+        if !year_open {
+            continue;
+        }
+        for month in &year.months {
+            let month_key = format!("{year_key}-{:02}", month.month);
+            let month_open = expanded.contains(&month_key);
+            let label = month
+                .days
+                .first()
+                .map(|day| day.date.format("%B").to_string())
+                .unwrap_or_default();
+            rows.push(DateNode {
+                key: month_key.clone().into(),
+                label: label.into(),
+                count: month.count as i32,
+                level: 1,
+                expanded: month_open,
+                has_children: !month.days.is_empty(),
+            });
+            if !month_open {
+                continue;
+            }
+            rows.extend(month.days.iter().map(|day| DateNode {
+                key: day.date.format("%Y-%m-%d").to_string().into(),
+                label: day.date.format("%a %-d").to_string().into(),
+                count: day.count as i32,
+                level: 2,
+                expanded: false,
+                has_children: false,
+            }));
+        }
+    }
+    rows
+}
+
+/// Every day of `year` as a cell of a week-column grid starting on Mondays, shaded from 0
+/// (nothing taken) to 4 (the year's busiest days), with a label over each month's first
+/// week.
+pub fn calendar(days: &[DayCount], year: i32) -> (Vec<CalendarDay>, Vec<CalendarMonth>) {
+    let Some(first) = NaiveDate::from_ymd_opt(year, 1, 1) else {
+        return (Vec::new(), Vec::new());
+    };
+    let counts: HashMap<NaiveDate, usize> = days
+        .iter()
+        .filter(|day| day.date.year() == year)
+        .map(|day| (day.date, day.count))
+        .collect();
+    let busiest = counts.values().copied().max().unwrap_or(0);
+    let offset = first.weekday().num_days_from_monday();
+    let week_of = |date: NaiveDate| ((date.ordinal0() + offset) / 7) as i32;
+
+    let mut cells = Vec::new();
+    let mut months = Vec::new();
+    for date in first.iter_days().take_while(|date| date.year() == year) {
+        if date.day() == 1 {
+            months.push(CalendarMonth {
+                label: date.format("%b").to_string().into(),
+                week: week_of(date),
+            });
+        }
+        let count = counts.get(&date).copied().unwrap_or(0);
+        let level = if count == 0 {
+            0
+        } else {
+            (count * 4).div_ceil(busiest) as i32
+        };
+        cells.push(CalendarDay {
+            key: SharedString::from(date.format("%Y-%m-%d").to_string()),
+            week: week_of(date),
+            weekday: date.weekday().num_days_from_monday() as i32,
+            count: count as i32,
+            level,
+        });
+    }
+    (cells, months)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use catalog::db::timeline::date_hierarchy;
+
+    fn day(raw: &str, count: usize) -> DayCount {
+        DayCount {
+            date: raw.parse().unwrap(),
+            count,
+        }
+    }
+
+    #[test]
+    fn expands_only_open_nodes() {
+        let days = [
+            day("2023-07-14", 3),
+            day("2024-05-01", 2),
+            day("2024-05-02", 5),
+            day("2024-06-30", 1),
+        ];
+        let years = date_hierarchy(&days);
+
+        let closed = date_rows(&years, &HashSet::new());
+        let keys: Vec<&str> = closed.iter().map(|row| row.key.as_str()).collect();
+        assert_eq!(keys, ["2024", "2023"]);
+        assert_eq!(closed[0].count, 8);
+
+        let open: HashSet<String> = ["2024".to_string(), "2024-05".to_string()].into();
+        let rows = date_rows(&years, &open);
+        let keys: Vec<&str> = rows.iter().map(|row| row.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            [
+                "2024",
+                "2024-05",
+                "2024-05-01",
+                "2024-05-02",
+                "2024-06",
+                "2023"
+            ]
+        );
+        assert_eq!(rows[1].label, "May");
+        assert_eq!(rows[2].label, "Wed 1");
+        assert_eq!(rows[2].level, 2);
+    }
+
+    #[test]
+    fn shades_calendar_days_by_activity() {
+        let days = [
+            day("2024-01-01", 1),
+            day("2024-03-10", 8),
+            day("2023-12-31", 40),
+        ];
+        let (cells, months) = calendar(&days, 2024);
+        assert_eq!(cells.len(), 366);
+        assert_eq!(months.len(), 12);
+
+        // 1 January 2024 was a Monday, 10 March a Sunday.
+        assert_eq!((cells[0].week, cells[0].weekday, cells[0].level), (0, 0, 1));
+        let march = cells.iter().find(|cell| cell.key == "2024-03-10").unwrap();
+        assert_eq!((march.week, march.weekday, march.level), (9, 6, 4));
+        assert_eq!(cells.iter().filter(|cell| cell.count > 0).count(), 2);
+        assert_eq!(cells.last().unwrap().week, 52);
+    }
+}
@@ -21,3 +21,12 @@ pub struct PreviewImage {
     /// RGBA8, row-major.
     pub data: Vec<u8>,
 }
+
+/// Full-resolution render produced by the engine for export.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenderedImage {
+    pub width: u32,
+    pub height: u32,
+    /// RGB16, row-major.
+    pub data: Vec<u16>,
+}
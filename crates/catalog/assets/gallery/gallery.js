@@ -0,0 +1,120 @@
+(function () {
+  "use strict";
+  var data = JSON.parse(document.getElementById("gallery-data").textContent);
+  var storageKey = "zenithphoto-picks-" + data.gallery_id;
+  var picks = {};
+  try {
+    (JSON.parse(localStorage.getItem(storageKey)) || []).forEach(function (id) { picks[id] = true; });
+  } catch (err) { picks = {}; }
+
+  var grid = document.getElementById("grid");
+  var lightbox = document.getElementById("lightbox");
+  var lightboxImage = document.getElementById("lightbox-image");
+  var lightboxCaption = document.getElementById("lightbox-caption");
+  var lightboxPick = document.getElementById("lightbox-pick");
+  var tiles = [];
+  var current = -1;
+
+  function captionText(entry) {
+    var parts = [];
+    if (entry.caption.filename) { parts.push(entry.caption.filename); }
+    if (entry.caption.rating) { parts.push("★".repeat(entry.caption.rating)); }
+    if (entry.caption.keywords && entry.caption.keywords.length) { parts.push(entry.caption.keywords.join(", ")); }
+    return parts.join(" · ");
+  }
+
+  function save() {
+    var ids = Object.keys(picks).filter(function (id) { return picks[id]; });
+    try { localStorage.setItem(storageKey, JSON.stringify(ids.map(Number))); } catch (err) { /* private mode */ }
+    document.getElementById("pick-count").textContent = ids.length + " picked";
+    tiles.forEach(function (tile, index) {
+      var picked = !!picks[data.images[index].image_id];
+      tile.classList.toggle("picked", picked);
+      tile.querySelector(".pick").textContent = picked ? "Picked" : "Pick";
+    });
+    if (current >= 0) {
+      var picked = !!picks[data.images[current].image_id];
+      lightboxPick.classList.toggle("picked", picked);
+      lightboxPick.textContent = picked ? "Picked" : "Pick";
+    }
+  }
+
+  function toggle(index) {
+    var id = data.images[index].image_id;
+    picks[id] = !picks[id];
+    save();
+  }
+
+  function show(index) {
+    current = (index + data.images.length) % data.images.length;
+    var entry = data.images[current];
+    lightboxImage.src = entry.image;
+    lightboxImage.alt = entry.filename;
+    lightboxCaption.textContent = captionText(entry);
+    lightbox.hidden = false;
+    save();
+  }
+
+  function hide() {
+    lightbox.hidden = true;
+    current = -1;
+  }
+
+  data.images.forEach(function (entry, index) {
+    var tile = document.createElement("figure");
+    tile.className = "tile";
+    var img = document.createElement("img");
+    img.src = entry.thumbnail;
+    img.alt = entry.filename;
+    img.loading = "lazy";
+    img.addEventListener("click", function () { show(index); });
+    var pick = document.createElement("button");
+    pick.type = "button";
+    pick.className = "pick";
+    pick.addEventListener("click", function () { toggle(index); });
+    tile.appendChild(img);
+    tile.appendChild(pick);
+    var text = captionText(entry);
+    if (text) {
+      var caption = document.createElement("figcaption");
+      caption.textContent = text;
+      tile.appendChild(caption);
+    }
+    grid.appendChild(tile);
+    tiles.push(tile);
+  });
+
+  lightbox.querySelector(".close").addEventListener("click", hide);
+  lightbox.querySelector(".prev").addEventListener("click", function () { show(current - 1); });
+  lightbox.querySelector(".next").addEventListener("click", function () { show(current + 1); });
+  lightboxPick.addEventListener("click", function () { toggle(current); });
+  lightbox.addEventListener("click", function (event) { if (event.target === lightbox) { hide(); } });
+  document.addEventListener("keydown", function (event) {
+    if (lightbox.hidden) { return; }
+    if (event.key === "Escape") { hide(); }
+    else if (event.key === "ArrowLeft") { show(current - 1); }
+    else if (event.key === "ArrowRight") { show(current + 1); }
+    else if (event.key === "p" || event.key === " ") { event.preventDefault(); toggle(current); }
+  });
+
+  document.getElementById("show-picks").addEventListener("click", function (event) {
+    var only = document.body.classList.toggle("picks-only");
+    event.target.textContent = only ? "Show all" : "Show picks only";
+  });
+
+  document.getElementById("download-picks").addEventListener("click", function () {
+    var chosen = data.images
+      .filter(function (entry) { return picks[entry.image_id]; })
+      .map(function (entry) { return { image_id: entry.image_id, filename: entry.filename }; });
+    var payload = { version: data.version, gallery_id: data.gallery_id, title: data.title, picks: chosen };
+    var blob = new Blob([JSON.stringify(payload, null, 2)], { type: "application/json" });
+    var link = document.createElement("a");
+    link.href = URL.createObjectURL(blob);
+    link.download = "picks.json";
+    document.body.appendChild(link);
+    link.click();
+    setTimeout(function () { URL.revokeObjectURL(link.href); link.remove(); }, 0);
+  });
+
+  save();
+})();
@@ -18,6 +18,14 @@ BEGIN
     WHERE id = NEW.id;
 END;
 
+CREATE TABLE IF NOT EXISTS roots (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+    path TEXT NOT NULL UNIQUE,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+);
+
 CREATE TABLE IF NOT EXISTS folders (
     id INTEGER PRIMARY KEY,
     path TEXT NOT NULL UNIQUE,
@@ -27,6 +35,15 @@ CREATE TABLE IF NOT EXISTS folders (
 
 CREATE UNIQUE INDEX IF NOT EXISTS idx_folders_path ON folders(path);
 
+-- Where each folder lives below a root; folders.path is the resolved absolute path.
+CREATE TABLE IF NOT EXISTS folder_roots (
+    folder_id INTEGER PRIMARY KEY REFERENCES folders(id) ON DELETE CASCADE,
+    root_id INTEGER NOT NULL REFERENCES roots(id) ON DELETE RESTRICT,
+    relative_path TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_folder_roots_root_id ON folder_roots(root_id);
+
 CREATE TRIGGER IF NOT EXISTS folders_touch_updated_at
 AFTER UPDATE ON folders
 FOR EACH ROW
@@ -190,6 +207,118 @@ BEGIN
     WHERE image_id = NEW.image_id;
 END;
 
+-- 64-bit difference hash of thumb_256, stored as a signed integer.
+CREATE TABLE IF NOT EXISTS perceptual_hashes (
+    image_id INTEGER PRIMARY KEY REFERENCES images(id) ON DELETE CASCADE,
+    dhash INTEGER NOT NULL,
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+);
+
+CREATE TABLE IF NOT EXISTS export_presets (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+    settings_json TEXT NOT NULL CHECK (json_valid(settings_json)),
+    destination TEXT,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+);
+
+CREATE TABLE IF NOT EXISTS watermarks (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+    definition_json TEXT NOT NULL CHECK (json_valid(definition_json)),
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+);
+
+-- Batch capture time corrections; changes keep the original values for undo.
+CREATE TABLE IF NOT EXISTS capture_time_corrections (
+    id INTEGER PRIMARY KEY,
+    description TEXT NOT NULL,
+    offset_seconds INTEGER NOT NULL,
+    wrote_sidecars INTEGER NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+);
+
+CREATE TABLE IF NOT EXISTS capture_time_changes (
+    correction_id INTEGER NOT NULL REFERENCES capture_time_corrections(id) ON DELETE CASCADE,
+    image_id INTEGER NOT NULL REFERENCES images(id) ON DELETE CASCADE,
+    previous_captured_at TEXT,
+    corrected_captured_at TEXT NOT NULL,
+    PRIMARY KEY (correction_id, image_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_capture_time_changes_image_id ON capture_time_changes(image_id);
+
+-- Geotagging runs from GPS tracks; changes keep the previous positions for undo.
+CREATE TABLE IF NOT EXISTS geotag_batches (
+    id INTEGER PRIMARY KEY,
+    description TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+);
+
+CREATE TABLE IF NOT EXISTS geotag_changes (
+    batch_id INTEGER NOT NULL REFERENCES geotag_batches(id) ON DELETE CASCADE,
+    image_id INTEGER NOT NULL REFERENCES images(id) ON DELETE CASCADE,
+    previous_latitude REAL,
+    previous_longitude REAL,
+    previous_altitude REAL,
+    latitude REAL NOT NULL,
+    longitude REAL NOT NULL,
+    altitude REAL,
+    PRIMARY KEY (batch_id, image_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_geotag_changes_image_id ON geotag_changes(image_id);
+
+-- Places looked up from GPS positions by the offline gazetteer.
+CREATE TABLE IF NOT EXISTS image_locations (
+    image_id INTEGER PRIMARY KEY REFERENCES images(id) ON DELETE CASCADE,
+    city TEXT NOT NULL,
+    region TEXT NOT NULL,
+    country TEXT NOT NULL,
+    country_code TEXT NOT NULL,
+    distance_km REAL NOT NULL,
+    located_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_image_locations_country ON image_locations(country_code);
+
+CREATE TABLE IF NOT EXISTS stacks (
+    id INTEGER PRIMARY KEY,
+    top_image_id INTEGER REFERENCES images(id) ON DELETE SET NULL,
+    collapsed INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+);
+
+CREATE TRIGGER IF NOT EXISTS stacks_touch_updated_at
+AFTER UPDATE ON stacks
+FOR EACH ROW
+BEGIN
+    UPDATE stacks
+    SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ','now')
+    WHERE id = NEW.id;
+END;
+
+CREATE TABLE IF NOT EXISTS stack_images (
+    stack_id INTEGER NOT NULL REFERENCES stacks(id) ON DELETE CASCADE,
+    image_id INTEGER NOT NULL UNIQUE REFERENCES images(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (stack_id, image_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_stack_images_stack_id
+    ON stack_images(stack_id, position);
+
+-- A stack without members is meaningless; drop it with its last image.
+CREATE TRIGGER IF NOT EXISTS stack_images_prune_empty
+AFTER DELETE ON stack_images
+WHEN NOT EXISTS (SELECT 1 FROM stack_images WHERE stack_id = OLD.stack_id)
+BEGIN
+    DELETE FROM stacks WHERE id = OLD.stack_id;
+END;
+
 -- Full-text search virtual tables (contentless).
 CREATE VIRTUAL TABLE IF NOT EXISTS fts_keywords
 USING fts5(keyword, content='', tokenize='unicode61');
@@ -206,6 +335,13 @@ USING fts5(
 CREATE VIRTUAL TABLE IF NOT EXISTS fts_folders
 USING fts5(path, content='', tokenize='unicode61');
 
+CREATE VIRTUAL TABLE IF NOT EXISTS fts_locations
+USING fts5(city, region, country, content='', tokenize='unicode61');
+
+-- Spatial index over image positions.
+CREATE VIRTUAL TABLE IF NOT EXISTS rtree_images
+USING rtree(id, min_latitude, max_latitude, min_longitude, max_longitude);
+
 -- FTS triggers for keywords.
 CREATE TRIGGER IF NOT EXISTS keywords_fts_ai
 AFTER INSERT ON keywords
@@ -268,14 +404,62 @@ BEGIN
     INSERT INTO fts_folders(rowid, path) VALUES (new.id, new.path);
 END;
 
+-- FTS triggers for image locations.
+CREATE TRIGGER IF NOT EXISTS image_locations_fts_ai
+AFTER INSERT ON image_locations
+BEGIN
+    INSERT INTO fts_locations(rowid, city, region, country)
+    VALUES (new.image_id, new.city, new.region, new.country);
+END;
+
+CREATE TRIGGER IF NOT EXISTS image_locations_fts_ad
+AFTER DELETE ON image_locations
+BEGIN
+    INSERT INTO fts_locations(fts_locations, rowid, city, region, country)
+    VALUES ('delete', old.image_id, old.city, old.region, old.country);
+END;
+
+CREATE TRIGGER IF NOT EXISTS image_locations_fts_au
+AFTER UPDATE ON image_locations
+BEGIN
+    INSERT INTO fts_locations(fts_locations, rowid, city, region, country)
+    VALUES ('delete', old.image_id, old.city, old.region, old.country);
+    INSERT INTO fts_locations(rowid, city, region, country)
+    VALUES (new.image_id, new.city, new.region, new.country);
+END;
+
+-- Spatial index triggers for image positions.
+CREATE TRIGGER IF NOT EXISTS images_rtree_ai
+AFTER INSERT ON images
+WHEN new.gps_latitude IS NOT NULL AND new.gps_longitude IS NOT NULL
+BEGIN
+    INSERT INTO rtree_images (id, min_latitude, max_latitude, min_longitude, max_longitude)
+    VALUES (new.id, new.gps_latitude, new.gps_latitude, new.gps_longitude, new.gps_longitude);
+END;
+
+CREATE TRIGGER IF NOT EXISTS images_rtree_ad
+AFTER DELETE ON images
+BEGIN
+    DELETE FROM rtree_images WHERE id = old.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS images_rtree_au
+AFTER UPDATE OF gps_latitude, gps_longitude ON images
+BEGIN
+    DELETE FROM rtree_images WHERE id = old.id;
+    INSERT INTO rtree_images (id, min_latitude, max_latitude, min_longitude, max_longitude)
+    SELECT new.id, new.gps_latitude, new.gps_latitude, new.gps_longitude, new.gps_longitude
+    WHERE new.gps_latitude IS NOT NULL AND new.gps_longitude IS NOT NULL;
+END;
+
 INSERT INTO catalog_metadata (id, schema_version, created_at, updated_at, last_opened)
 VALUES (
     1,
-    5,
+    14,
     strftime('%Y-%m-%dT%H:%M:%fZ','now'),
     strftime('%Y-%m-%dT%H:%M:%fZ','now'),
     NULL
 )
 ON CONFLICT(id) DO NOTHING;
 
-PRAGMA user_version = 5;
+PRAGMA user_version = 14;
@@ -0,0 +1,130 @@
+use crate::db::{
+    parse_datetime, parse_datetime_opt, query_all, query_optional, to_rfc3339, to_rfc3339_opt,
+    DbHandle, DbResult,
+};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+/// One batch "Edit capture time" operation; its changes hold the original values so the
+/// batch can be undone.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureTimeCorrection {
+    pub id: i64,
+    pub description: String,
+    pub offset_seconds: i64,
+    pub wrote_sidecars: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureTimeChange {
+    pub correction_id: i64,
+    pub image_id: i64,
+    pub previous_captured_at: Option<DateTime<Utc>>,
+    pub corrected_captured_at: DateTime<Utc>,
+}
+
+impl CaptureTimeCorrection {
+    pub fn insert<H: DbHandle>(&self, db: &H) -> DbResult<i64> {
+        db.execute(
+            "INSERT INTO capture_time_corrections
+                (description, offset_seconds, wrote_sidecars, created_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                self.description,
+                self.offset_seconds,
+                self.wrote_sidecars,
+                to_rfc3339(self.created_at)
+            ],
+        )
+        .context("failed to insert capture time correction")?;
+        Ok(db.last_insert_rowid())
+    }
+
+    /// Newest first.
+    pub fn load_all<H: DbHandle>(db: &H) -> DbResult<Vec<Self>> {
+        query_all(
+            db,
+            "SELECT id, description, offset_seconds, wrote_sidecars, created_at
+             FROM capture_time_corrections ORDER BY id DESC",
+            [],
+            CaptureTimeCorrection::from_row,
+        )
+    }
+
+    pub fn load<H: DbHandle>(db: &H, id: i64) -> DbResult<Option<Self>> {
+        query_optional(
+            db,
+            "SELECT id, description, offset_seconds, wrote_sidecars, created_at
+             FROM capture_time_corrections WHERE id = ?1",
+            params![id],
+            CaptureTimeCorrection::from_row,
+        )
+    }
+
+    pub fn delete<H: DbHandle>(db: &H, id: i64) -> DbResult<()> {
+        db.execute(
+            "DELETE FROM capture_time_corrections WHERE id = ?1",
+            params![id],
+        )
+        .with_context(|| format!("failed to delete capture time correction id={id}"))?;
+        Ok(())
+    }
+
+    fn from_row(row: &rusqlite::Row<'_>) -> DbResult<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            description: row.get(1)?,
+            offset_seconds: row.get(2)?,
+            wrote_sidecars: row.get(3)?,
+            created_at: parse_datetime(row.get::<_, String>(4)?, "created_at")?,
+        })
+    }
+}
+
+impl CaptureTimeChange {
+    pub fn insert<H: DbHandle>(&self, db: &H) -> DbResult<()> {
+        db.execute(
+            "INSERT INTO capture_time_changes
+                (correction_id, image_id, previous_captured_at, corrected_captured_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                self.correction_id,
+                self.image_id,
+                to_rfc3339_opt(self.previous_captured_at),
+                to_rfc3339(self.corrected_captured_at)
+            ],
+        )
+        .with_context(|| {
+            format!(
+                "failed to log capture time change for image_id={}",
+                self.image_id
+            )
+        })?;
+        Ok(())
+    }
+
+    pub fn list_for_correction<H: DbHandle>(db: &H, correction_id: i64) -> DbResult<Vec<Self>> {
+        query_all(
+            db,
+            "SELECT correction_id, image_id, previous_captured_at, corrected_captured_at
+             FROM capture_time_changes WHERE correction_id = ?1 ORDER BY image_id",
+            params![correction_id],
+            CaptureTimeChange::from_row,
+        )
+    }
+
+    fn from_row(row: &rusqlite::Row<'_>) -> DbResult<Self> {
+        Ok(Self {
+            correction_id: row.get(0)?,
+            image_id: row.get(1)?,
+            previous_captured_at: parse_datetime_opt(row.get(2)?, "previous_captured_at")?,
+            corrected_captured_at: parse_datetime(
+                row.get::<_, String>(3)?,
+                "corrected_captured_at",
+            )?,
+        })
+    }
+}
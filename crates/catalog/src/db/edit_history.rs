@@ -51,6 +51,17 @@ impl EditHistory {
         )
     }
 
+    /// Oldest first, so inserting them in order keeps their ids chronological.
+    pub fn list_for_image<H: DbHandle>(db: &H, image_id: i64) -> DbResult<Vec<Self>> {
+        query_all(
+            db,
+            "SELECT id, image_id, edits_json, created_at FROM edit_history
+             WHERE image_id = ?1 ORDER BY created_at, id",
+            params![image_id],
+            EditHistory::from_row,
+        )
+    }
+
     pub fn update<H: DbHandle>(&self, db: &H) -> DbResult<()> {
         let edits_json = to_json(&self.edits_json)?;
         db.execute(
@@ -96,6 +107,8 @@ mod tests {
         let folder = Folder {
             id: 0,
             path: "/photos".into(),
+            root_id: None,
+            relative_path: None,
             created_at: Utc::now(),
             updated_at: Utc::now(),
         };
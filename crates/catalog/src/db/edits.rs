@@ -1,6 +1,6 @@
 use crate::db::{
-    from_json, parse_datetime_opt, query_all, query_one, to_json, to_rfc3339_opt, DbHandle,
-    DbResult,
+    from_json, parse_datetime_opt, query_all, query_one, query_optional, to_json, to_rfc3339_opt,
+    DbHandle, DbResult,
 };
 use anyhow::Context;
 use chrono::{DateTime, Utc};
@@ -92,6 +92,20 @@ impl Edit {
         .with_context(|| format!("failed to load edits id={id}"))
     }
 
+    pub fn load_for_image<H: DbHandle>(db: &H, image_id: i64) -> DbResult<Option<Self>> {
+        query_optional(
+            db,
+            "SELECT
+                id, image_id, exposure, contrast, highlights, shadows, whites, blacks,
+                vibrance, saturation, temperature, tint, texture, clarity, dehaze,
+                parametric_curve_json, color_grading_json, crop_json, masking_json, updated_at
+             FROM edits WHERE image_id = ?1",
+            params![image_id],
+            Edit::from_row,
+        )
+        .with_context(|| format!("failed to load edits for image_id={image_id}"))
+    }
+
     pub fn load_all<H: DbHandle>(db: &H) -> DbResult<Vec<Self>> {
         query_all(
             db,
@@ -0,0 +1,105 @@
+use crate::db::{
+    from_json, parse_datetime, query_all, query_one, query_optional, to_json, to_rfc3339, DbHandle,
+    DbResult,
+};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Named export settings; `settings_json` holds a serialized `ExportSettings`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportPreset {
+    pub id: i64,
+    pub name: String,
+    pub settings_json: Value,
+    pub destination: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl ExportPreset {
+    pub fn insert<H: DbHandle>(&self, db: &H) -> DbResult<i64> {
+        db.execute(
+            "INSERT INTO export_presets (name, settings_json, destination, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                self.name,
+                to_json(&self.settings_json)?,
+                self.destination,
+                to_rfc3339(self.created_at),
+                to_rfc3339(self.updated_at)
+            ],
+        )
+        .with_context(|| format!("failed to insert export preset {}", self.name))?;
+        Ok(db.last_insert_rowid())
+    }
+
+    pub fn load<H: DbHandle>(db: &H, id: i64) -> DbResult<Self> {
+        query_one(
+            db,
+            "SELECT id, name, settings_json, destination, created_at, updated_at
+             FROM export_presets WHERE id = ?1",
+            params![id],
+            ExportPreset::from_row,
+        )
+        .with_context(|| format!("failed to load export preset id={id}"))
+    }
+
+    /// Case-insensitive lookup by preset name.
+    pub fn find_by_name<H: DbHandle>(db: &H, name: &str) -> DbResult<Option<Self>> {
+        query_optional(
+            db,
+            "SELECT id, name, settings_json, destination, created_at, updated_at
+             FROM export_presets WHERE name = ?1",
+            params![name],
+            ExportPreset::from_row,
+        )
+        .with_context(|| format!("failed to look up export preset {name}"))
+    }
+
+    pub fn load_all<H: DbHandle>(db: &H) -> DbResult<Vec<Self>> {
+        query_all(
+            db,
+            "SELECT id, name, settings_json, destination, created_at, updated_at
+             FROM export_presets ORDER BY name COLLATE NOCASE",
+            [],
+            ExportPreset::from_row,
+        )
+    }
+
+    pub fn update<H: DbHandle>(&self, db: &H) -> DbResult<()> {
+        db.execute(
+            "UPDATE export_presets
+             SET name = ?1, settings_json = ?2, destination = ?3, updated_at = ?4
+             WHERE id = ?5",
+            params![
+                self.name,
+                to_json(&self.settings_json)?,
+                self.destination,
+                to_rfc3339(self.updated_at),
+                self.id
+            ],
+        )
+        .with_context(|| format!("failed to update export preset id={}", self.id))?;
+        Ok(())
+    }
+
+    pub fn delete<H: DbHandle>(db: &H, id: i64) -> DbResult<()> {
+        db.execute("DELETE FROM export_presets WHERE id = ?1", params![id])
+            .with_context(|| format!("failed to delete export preset id={id}"))?;
+        Ok(())
+    }
+
+    fn from_row(row: &rusqlite::Row<'_>) -> DbResult<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            settings_json: from_json(&row.get::<_, String>(2)?)?,
+            destination: row.get(3)?,
+            created_at: parse_datetime(row.get::<_, String>(4)?, "created_at")?,
+            updated_at: parse_datetime(row.get::<_, String>(5)?, "updated_at")?,
+        })
+    }
+}
@@ -10,11 +10,17 @@ use serde::{Deserialize, Serialize};
 pub struct Folder {
     pub id: i64,
     pub path: String,
+    pub root_id: Option<i64>,
+    /// `path` below the root, with `/` separators.
+    pub relative_path: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
 impl Folder {
+    /// Inserts the folder under `root_id`. Without one the folder stays unrooted until
+    /// [`crate::services::CatalogService::assign_folder_roots`] places it, since only the
+    /// service knows the user's home folder.
     pub fn insert<H: DbHandle>(&self, db: &H) -> DbResult<i64> {
         db.execute(
             "INSERT INTO folders (path, created_at, updated_at) VALUES (?1, ?2, ?3)",
@@ -25,13 +31,19 @@ impl Folder {
             ],
         )
         .with_context(|| format!("failed to insert folder path={}", self.path))?;
-        Ok(db.last_insert_rowid())
+        let id = db.last_insert_rowid();
+        if let Some(root_id) = self.root_id {
+            let relative_path = self.relative_path.as_deref().unwrap_or_default();
+            Folder::set_root(db, id, root_id, relative_path)?;
+        }
+        Ok(id)
     }
 
     pub fn load<H: DbHandle>(db: &H, id: i64) -> DbResult<Self> {
         query_one(
             db,
-            "SELECT id, path, created_at, updated_at FROM folders WHERE id = ?1",
+            "SELECT f.id, f.path, r.root_id, r.relative_path, f.created_at, f.updated_at
+             FROM folders f LEFT JOIN folder_roots r ON r.folder_id = f.id WHERE f.id = ?1",
             params![id],
             Folder::from_row,
         )
@@ -41,7 +53,8 @@ impl Folder {
     pub fn load_all<H: DbHandle>(db: &H) -> DbResult<Vec<Self>> {
         query_all(
             db,
-            "SELECT id, path, created_at, updated_at FROM folders ORDER BY id",
+            "SELECT f.id, f.path, r.root_id, r.relative_path, f.created_at, f.updated_at
+             FROM folders f LEFT JOIN folder_roots r ON r.folder_id = f.id ORDER BY f.id",
             [],
             Folder::from_row,
         )
@@ -58,6 +71,26 @@ impl Folder {
             ],
         )
         .with_context(|| format!("failed to update folder id={}", self.id))?;
+        if let Some(root_id) = self.root_id {
+            let relative_path = self.relative_path.as_deref().unwrap_or_default();
+            Folder::set_root(db, self.id, root_id, relative_path)?;
+        }
+        Ok(())
+    }
+
+    pub fn set_root<H: DbHandle>(
+        db: &H,
+        id: i64,
+        root_id: i64,
+        relative_path: &str,
+    ) -> DbResult<()> {
+        db.execute(
+            "INSERT INTO folder_roots (folder_id, root_id, relative_path) VALUES (?1, ?2, ?3)
+             ON CONFLICT(folder_id) DO UPDATE
+             SET root_id = excluded.root_id, relative_path = excluded.relative_path",
+            params![id, root_id, relative_path],
+        )
+        .with_context(|| format!("failed to set root of folder id={id}"))?;
         Ok(())
     }
 
@@ -70,7 +103,8 @@ impl Folder {
     pub fn find_by_path<H: DbHandle>(db: &H, path: &str) -> DbResult<Option<Self>> {
         query_optional(
             db,
-            "SELECT id, path, created_at, updated_at FROM folders WHERE path = ?1",
+            "SELECT f.id, f.path, r.root_id, r.relative_path, f.created_at, f.updated_at
+             FROM folders f LEFT JOIN folder_roots r ON r.folder_id = f.id WHERE f.path = ?1",
             params![path],
             Folder::from_row,
         )
@@ -80,8 +114,10 @@ impl Folder {
         Ok(Self {
             id: row.get(0)?,
             path: row.get(1)?,
-            created_at: parse_datetime(row.get::<_, String>(2)?, "created_at")?,
-            updated_at: parse_datetime(row.get::<_, String>(3)?, "updated_at")?,
+            root_id: row.get(2)?,
+            relative_path: row.get(3)?,
+            created_at: parse_datetime(row.get::<_, String>(4)?, "created_at")?,
+            updated_at: parse_datetime(row.get::<_, String>(5)?, "updated_at")?,
         })
     }
 }
@@ -100,6 +136,8 @@ mod tests {
         let folder = Folder {
             id: 0,
             path: "/photos/2024".into(),
+            root_id: None,
+            relative_path: None,
             created_at: Utc::now(),
             updated_at: Utc::now(),
         };
@@ -120,6 +158,8 @@ mod tests {
         let folder = Folder {
             id: 0,
             path: "/tmp".into(),
+            root_id: None,
+            relative_path: None,
             created_at: Utc::now(),
             updated_at: Utc::now(),
         };
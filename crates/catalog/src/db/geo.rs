@@ -0,0 +1,325 @@
+use crate::db::{query_all, CatalogDb, DbHandle, DbResult, Image};
+use anyhow::{bail, Context};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+const EARTH_RADIUS_KM: f64 = 6371.0;
+const KM_PER_DEGREE: f64 = EARTH_RADIUS_KM * std::f64::consts::PI / 180.0;
+
+/// A latitude/longitude rectangle. `west` greater than `east` means the box crosses the
+/// antimeridian.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BoundingBox {
+    pub south: f64,
+    pub west: f64,
+    pub north: f64,
+    pub east: f64,
+}
+
+impl BoundingBox {
+    pub const WORLD: BoundingBox = BoundingBox {
+        south: -90.0,
+        west: -180.0,
+        north: 90.0,
+        east: 180.0,
+    };
+
+    pub fn new(south: f64, west: f64, north: f64, east: f64) -> DbResult<Self> {
+        if !(-90.0..=90.0).contains(&south) || !(-90.0..=90.0).contains(&north) {
+            bail!("latitudes must be between -90 and 90");
+        }
+        if !(-180.0..=180.0).contains(&west) || !(-180.0..=180.0).contains(&east) {
+            bail!("longitudes must be between -180 and 180");
+        }
+        if south > north {
+            bail!("south ({south}) is north of north ({north})");
+        }
+        Ok(Self {
+            south,
+            west,
+            north,
+            east,
+        })
+    }
+
+    /// The box around a circle of `radius_km`, widened to every longitude near the poles.
+    pub fn around(latitude: f64, longitude: f64, radius_km: f64) -> Self {
+        let dlat = radius_km / KM_PER_DEGREE;
+        let south = (latitude - dlat).max(-90.0);
+        let north = (latitude + dlat).min(90.0);
+        let widest = latitude.abs().max(south.abs()).max(north.abs());
+        let dlon = dlat / widest.to_radians().cos();
+        if !dlon.is_finite() || dlon >= 180.0 || widest >= 90.0 {
+            return Self {
+                south,
+                west: -180.0,
+                north,
+                east: 180.0,
+            };
+        }
+        let wrap = |lon: f64| {
+            if lon < -180.0 {
+                lon + 360.0
+            } else if lon > 180.0 {
+                lon - 360.0
+            } else {
+                lon
+            }
+        };
+        Self {
+            south,
+            west: wrap(longitude - dlon),
+            north,
+            east: wrap(longitude + dlon),
+        }
+    }
+
+    pub fn contains(&self, latitude: f64, longitude: f64) -> bool {
+        let in_longitude = if self.west <= self.east {
+            (self.west..=self.east).contains(&longitude)
+        } else {
+            longitude >= self.west || longitude <= self.east
+        };
+        in_longitude && (self.south..=self.north).contains(&latitude)
+    }
+
+    /// Conditions on the R*Tree's `r` columns and the images' exact position for `?1`..`?4`
+    /// bound to south, west, north, east. The R*Tree stores 32-bit floats rounded outwards,
+    /// so it only narrows the candidates.
+    fn where_clause(&self) -> &'static str {
+        if self.west <= self.east {
+            "r.max_latitude >= ?1 AND r.min_latitude <= ?3
+             AND r.max_longitude >= ?2 AND r.min_longitude <= ?4
+             AND i.gps_latitude BETWEEN ?1 AND ?3 AND i.gps_longitude BETWEEN ?2 AND ?4"
+        } else {
+            "r.max_latitude >= ?1 AND r.min_latitude <= ?3
+             AND (r.max_longitude >= ?2 OR r.min_longitude <= ?4)
+             AND i.gps_latitude BETWEEN ?1 AND ?3
+             AND (i.gps_longitude >= ?2 OR i.gps_longitude <= ?4)"
+        }
+    }
+}
+
+/// Images counted together in one grid cell of a map.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GeoCluster {
+    /// Mean position of the images in the cell.
+    pub latitude: f64,
+    pub longitude: f64,
+    pub count: usize,
+    /// The cell the images fall in.
+    pub cell: BoundingBox,
+    /// One image to show for the cluster, the most recently captured.
+    pub image_id: i64,
+}
+
+/// Images whose position lies inside `bounds`.
+pub fn images_in_bounds(db: &CatalogDb, bounds: &BoundingBox) -> DbResult<Vec<Image>> {
+    query_all(
+        db,
+        &format!(
+            "SELECT
+                i.id, i.folder_id, i.filename, i.original_path, i.sidecar_path, i.sidecar_hash,
+                i.filesize, i.file_hash, i.file_modified_at, i.imported_at, i.captured_at,
+                i.camera_make, i.camera_model, i.lens_model, i.focal_length, i.aperture,
+                i.shutter_speed, i.iso, i.orientation, i.gps_latitude, i.gps_longitude,
+                i.gps_altitude, i.rating, i.flag, i.color_label, i.metadata_json,
+                i.created_at, i.updated_at
+             FROM rtree_images r
+             JOIN images i ON i.id = r.id
+             WHERE {}
+             ORDER BY i.captured_at, i.id",
+            bounds.where_clause()
+        ),
+        params![bounds.south, bounds.west, bounds.north, bounds.east],
+        Image::from_row,
+    )
+}
+
+/// Images within `radius_km` of a point with their distance in kilometres, nearest first.
+pub fn images_within_radius(
+    db: &CatalogDb,
+    latitude: f64,
+    longitude: f64,
+    radius_km: f64,
+) -> DbResult<Vec<(Image, f64)>> {
+    let bounds = BoundingBox::around(latitude, longitude, radius_km);
+    let mut found: Vec<(Image, f64)> = images_in_bounds(db, &bounds)?
+        .into_iter()
+        .filter_map(|image| {
+            let distance = haversine_km(
+                latitude,
+                longitude,
+                image.gps_latitude?,
+                image.gps_longitude?,
+            );
+            (distance <= radius_km).then_some((image, distance))
+        })
+        .collect();
+    found.sort_by(|a, b| a.1.total_cmp(&b.1));
+    Ok(found)
+}
+
+/// Count the images inside `bounds` per cell of a grid that halves with each zoom level:
+/// at zoom 0 a cell spans 360° of longitude and 180° of latitude.
+pub fn cluster_images(db: &CatalogDb, bounds: &BoundingBox, zoom: u8) -> DbResult<Vec<GeoCluster>> {
+    if zoom > 24 {
+        bail!("zoom must be at most 24, got {zoom}");
+    }
+    let cells = f64::from(1u32 << zoom);
+    let (lon_size, lat_size) = (360.0 / cells, 180.0 / cells);
+    query_all(
+        db,
+        &format!(
+            "SELECT
+                MIN(CAST((i.gps_latitude + 90.0) / ?5 AS INTEGER), ?7 - 1) AS cell_row,
+                MIN(CAST((i.gps_longitude + 180.0) / ?6 AS INTEGER), ?7 - 1) AS cell_col,
+                AVG(i.gps_latitude), AVG(i.gps_longitude), COUNT(*),
+                -- With MAX(), SQLite takes the bare i.id from the row holding the maximum.
+                MAX(COALESCE(i.captured_at, '')), i.id
+             FROM rtree_images r
+             JOIN images i ON i.id = r.id
+             WHERE {}
+             GROUP BY cell_row, cell_col
+             ORDER BY cell_row, cell_col",
+            bounds.where_clause()
+        ),
+        params![
+            bounds.south,
+            bounds.west,
+            bounds.north,
+            bounds.east,
+            lat_size,
+            lon_size,
+            cells as i64
+        ],
+        |row| {
+            let (cell_row, cell_col): (i64, i64) = (row.get(0)?, row.get(1)?);
+            let south = -90.0 + cell_row as f64 * lat_size;
+            let west = -180.0 + cell_col as f64 * lon_size;
+            Ok(GeoCluster {
+                latitude: row.get(2)?,
+                longitude: row.get(3)?,
+                count: row.get::<_, i64>(4)? as usize,
+                cell: BoundingBox {
+                    south,
+                    west,
+                    north: south + lat_size,
+                    east: west + lon_size,
+                },
+                image_id: row.get(6)?,
+            })
+        },
+    )
+}
+
+/// Refill the R*Tree from the images' positions.
+pub fn rebuild_spatial_index<H: DbHandle>(db: &H) -> DbResult<()> {
+    db.execute("DELETE FROM rtree_images", [])
+        .context("failed to clear spatial index")?;
+    db.execute(
+        "INSERT INTO rtree_images (id, min_latitude, max_latitude, min_longitude, max_longitude)
+         SELECT id, gps_latitude, gps_latitude, gps_longitude, gps_longitude
+         FROM images
+         WHERE gps_latitude IS NOT NULL AND gps_longitude IS NOT NULL",
+        [],
+    )
+    .context("failed to rebuild spatial index")?;
+    Ok(())
+}
+
+/// Great-circle distance in kilometres.
+pub fn haversine_km(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let dphi = (lat2 - lat1).to_radians();
+    let dlambda = (lon2 - lon1).to_radians();
+    let a = (dphi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (dlambda / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().min(1.0).asin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{test_folder, test_image};
+
+    fn ids(images: &[Image]) -> Vec<i64> {
+        images.iter().map(|image| image.id).collect()
+    }
+
+    #[test]
+    fn finds_images_by_area_and_distance() {
+        let db = CatalogDb::in_memory().unwrap();
+        let folder_id = test_folder("/photos/geo").insert(&db).unwrap();
+        let [lisbon, porto, fiji, samoa] = [
+            ("lisbon.jpg", 38.7223, -9.1393),
+            ("porto.jpg", 41.1579, -8.6291),
+            ("fiji.jpg", -17.7134, 178.065),
+            ("samoa.jpg", -13.759, -172.1046),
+        ]
+        .map(|(name, latitude, longitude)| {
+            Image {
+                gps_latitude: Some(latitude),
+                gps_longitude: Some(longitude),
+                ..test_image(folder_id, &format!("/photos/geo/{name}"))
+            }
+            .insert(&db)
+            .unwrap()
+        });
+        test_image(folder_id, "/photos/geo/nowhere.jpg")
+            .insert(&db)
+            .unwrap();
+
+        let iberia = BoundingBox::new(36.0, -10.0, 44.0, 4.0).unwrap();
+        assert_eq!(
+            ids(&images_in_bounds(&db, &iberia).unwrap()),
+            [lisbon, porto]
+        );
+        let pacific = BoundingBox::new(-20.0, 170.0, -10.0, -170.0).unwrap();
+        assert_eq!(
+            ids(&images_in_bounds(&db, &pacific).unwrap()),
+            [fiji, samoa]
+        );
+        assert!(BoundingBox::new(10.0, 0.0, -10.0, 1.0).is_err());
+
+        let near: Vec<i64> = images_within_radius(&db, 38.7, -9.1, 300.0)
+            .unwrap()
+            .iter()
+            .map(|(image, _)| image.id)
+            .collect();
+        assert_eq!(near, [lisbon, porto]);
+        assert_eq!(
+            images_within_radius(&db, 38.7, -9.1, 100.0).unwrap().len(),
+            1
+        );
+        let distance = haversine_km(38.7223, -9.1393, 41.1579, -8.6291);
+        assert!((distance - 274.0).abs() < 2.0, "{distance}");
+
+        let world = cluster_images(&db, &BoundingBox::WORLD, 0).unwrap();
+        assert_eq!(world.len(), 1);
+        assert_eq!(world[0].count, 4);
+        assert_eq!(world[0].cell, BoundingBox::WORLD);
+        let regions = cluster_images(&db, &BoundingBox::WORLD, 3).unwrap();
+        let counts: Vec<usize> = regions.iter().map(|cluster| cluster.count).collect();
+        assert_eq!(counts, [1, 1, 2]);
+        let portugal = &regions[2];
+        assert!(portugal
+            .cell
+            .contains(portugal.latitude, portugal.longitude));
+        assert!([lisbon, porto].contains(&portugal.image_id));
+
+        // The triggers keep the R*Tree in step with the images.
+        let mut image = Image::load(&db, porto).unwrap();
+        image.gps_latitude = None;
+        image.update(&db).unwrap();
+        assert_eq!(ids(&images_in_bounds(&db, &iberia).unwrap()), [lisbon]);
+        image.gps_latitude = Some(40.4168);
+        image.gps_longitude = Some(-3.7038);
+        image.update(&db).unwrap();
+        assert_eq!(
+            ids(&images_in_bounds(&db, &iberia).unwrap()),
+            [lisbon, porto]
+        );
+        Image::delete(&db, lisbon).unwrap();
+        assert_eq!(ids(&images_in_bounds(&db, &iberia).unwrap()), [porto]);
+    }
+}
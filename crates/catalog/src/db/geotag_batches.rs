@@ -0,0 +1,111 @@
+use crate::db::{parse_datetime, query_all, query_optional, to_rfc3339, DbHandle, DbResult};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+/// One geotagging run; its changes hold the previous positions so the run can be undone.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeotagBatch {
+    pub id: i64,
+    pub description: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A latitude, longitude and altitude as stored on an image.
+pub type GpsPosition = (Option<f64>, Option<f64>, Option<f64>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeotagChange {
+    pub batch_id: i64,
+    pub image_id: i64,
+    pub previous: GpsPosition,
+    pub tagged: GpsPosition,
+}
+
+impl GeotagBatch {
+    pub fn insert<H: DbHandle>(&self, db: &H) -> DbResult<i64> {
+        db.execute(
+            "INSERT INTO geotag_batches (description, created_at) VALUES (?1, ?2)",
+            params![self.description, to_rfc3339(self.created_at)],
+        )
+        .context("failed to insert geotag batch")?;
+        Ok(db.last_insert_rowid())
+    }
+
+    /// Newest first.
+    pub fn load_all<H: DbHandle>(db: &H) -> DbResult<Vec<Self>> {
+        query_all(
+            db,
+            "SELECT id, description, created_at FROM geotag_batches ORDER BY id DESC",
+            [],
+            GeotagBatch::from_row,
+        )
+    }
+
+    pub fn load<H: DbHandle>(db: &H, id: i64) -> DbResult<Option<Self>> {
+        query_optional(
+            db,
+            "SELECT id, description, created_at FROM geotag_batches WHERE id = ?1",
+            params![id],
+            GeotagBatch::from_row,
+        )
+    }
+
+    pub fn delete<H: DbHandle>(db: &H, id: i64) -> DbResult<()> {
+        db.execute("DELETE FROM geotag_batches WHERE id = ?1", params![id])
+            .with_context(|| format!("failed to delete geotag batch id={id}"))?;
+        Ok(())
+    }
+
+    fn from_row(row: &rusqlite::Row<'_>) -> DbResult<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            description: row.get(1)?,
+            created_at: parse_datetime(row.get::<_, String>(2)?, "created_at")?,
+        })
+    }
+}
+
+impl GeotagChange {
+    pub fn insert<H: DbHandle>(&self, db: &H) -> DbResult<()> {
+        db.execute(
+            "INSERT INTO geotag_changes (
+                batch_id, image_id, previous_latitude, previous_longitude, previous_altitude,
+                latitude, longitude, altitude
+             ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                self.batch_id,
+                self.image_id,
+                self.previous.0,
+                self.previous.1,
+                self.previous.2,
+                self.tagged.0,
+                self.tagged.1,
+                self.tagged.2
+            ],
+        )
+        .with_context(|| format!("failed to log geotag for image_id={}", self.image_id))?;
+        Ok(())
+    }
+
+    pub fn list_for_batch<H: DbHandle>(db: &H, batch_id: i64) -> DbResult<Vec<Self>> {
+        query_all(
+            db,
+            "SELECT batch_id, image_id, previous_latitude, previous_longitude, previous_altitude,
+                latitude, longitude, altitude
+             FROM geotag_changes WHERE batch_id = ?1 ORDER BY image_id",
+            params![batch_id],
+            GeotagChange::from_row,
+        )
+    }
+
+    fn from_row(row: &rusqlite::Row<'_>) -> DbResult<Self> {
+        Ok(Self {
+            batch_id: row.get(0)?,
+            image_id: row.get(1)?,
+            previous: (row.get(2)?, row.get(3)?, row.get(4)?),
+            tagged: (row.get(5)?, row.get(6)?, row.get(7)?),
+        })
+    }
+}
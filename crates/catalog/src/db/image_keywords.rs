@@ -125,6 +125,8 @@ mod tests {
         let folder = Folder {
             id: 0,
             path: "/photos".into(),
+            root_id: None,
+            relative_path: None,
             created_at: Utc::now(),
             updated_at: Utc::now(),
         };
@@ -0,0 +1,97 @@
+use crate::db::{parse_datetime, query_all, query_optional, to_rfc3339, DbHandle, DbResult};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+/// Where an image was taken, looked up from its GPS position in the offline gazetteer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImageLocation {
+    pub image_id: i64,
+    pub city: String,
+    pub region: String,
+    pub country: String,
+    pub country_code: String,
+    /// Distance from the image's position to the city, in kilometres.
+    pub distance_km: f64,
+    pub located_at: DateTime<Utc>,
+}
+
+impl ImageLocation {
+    pub fn upsert<H: DbHandle>(&self, db: &H) -> DbResult<()> {
+        db.execute(
+            "INSERT INTO image_locations
+                (image_id, city, region, country, country_code, distance_km, located_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT(image_id) DO UPDATE SET
+                city = excluded.city,
+                region = excluded.region,
+                country = excluded.country,
+                country_code = excluded.country_code,
+                distance_km = excluded.distance_km,
+                located_at = excluded.located_at",
+            params![
+                self.image_id,
+                self.city,
+                self.region,
+                self.country,
+                self.country_code,
+                self.distance_km,
+                to_rfc3339(self.located_at)
+            ],
+        )
+        .with_context(|| format!("failed to store location for image_id={}", self.image_id))?;
+        Ok(())
+    }
+
+    pub fn load<H: DbHandle>(db: &H, image_id: i64) -> DbResult<Option<Self>> {
+        query_optional(
+            db,
+            "SELECT image_id, city, region, country, country_code, distance_km, located_at
+             FROM image_locations WHERE image_id = ?1",
+            params![image_id],
+            ImageLocation::from_row,
+        )
+    }
+
+    pub fn load_all<H: DbHandle>(db: &H) -> DbResult<Vec<Self>> {
+        query_all(
+            db,
+            "SELECT image_id, city, region, country, country_code, distance_km, located_at
+             FROM image_locations ORDER BY image_id",
+            [],
+            ImageLocation::from_row,
+        )
+    }
+
+    pub fn delete<H: DbHandle>(db: &H, image_id: i64) -> DbResult<()> {
+        db.execute(
+            "DELETE FROM image_locations WHERE image_id = ?1",
+            params![image_id],
+        )
+        .with_context(|| format!("failed to delete location for image_id={image_id}"))?;
+        Ok(())
+    }
+
+    /// "City, Region, Country", leaving out a region named like its city.
+    pub fn label(&self) -> String {
+        let mut parts = vec![self.city.as_str()];
+        if !self.region.is_empty() && self.region != self.city {
+            parts.push(&self.region);
+        }
+        parts.push(&self.country);
+        parts.join(", ")
+    }
+
+    fn from_row(row: &rusqlite::Row<'_>) -> DbResult<Self> {
+        Ok(Self {
+            image_id: row.get(0)?,
+            city: row.get(1)?,
+            region: row.get(2)?,
+            country: row.get(3)?,
+            country_code: row.get(4)?,
+            distance_km: row.get(5)?,
+            located_at: parse_datetime(row.get::<_, String>(6)?, "located_at")?,
+        })
+    }
+}
@@ -107,6 +107,17 @@ impl Image {
         .with_context(|| format!("failed to load image id={id}"))
     }
 
+    /// The body serial is not part of [`Image`]; only metadata export reads it.
+    pub fn load_camera_serial<H: DbHandle>(db: &H, id: i64) -> DbResult<Option<String>> {
+        query_one(
+            db,
+            "SELECT camera_serial FROM images WHERE id = ?1",
+            params![id],
+            |row| Ok(row.get(0)?),
+        )
+        .with_context(|| format!("failed to load camera serial for image id={id}"))
+    }
+
     pub fn load_all<H: DbHandle>(db: &H) -> DbResult<Vec<Self>> {
         query_all(
             db,
@@ -304,6 +315,8 @@ mod tests {
         let folder = crate::db::Folder {
             id: 0,
             path: "/photos/2024".into(),
+            root_id: None,
+            relative_path: None,
             created_at: Utc::now(),
             updated_at: Utc::now(),
         };
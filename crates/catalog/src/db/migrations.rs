@@ -187,9 +187,244 @@ pub const MIGRATIONS: &[Migration] = &[
             INSERT INTO fts_folders(rowid, path) SELECT id, path FROM folders;
         "#,
     },
+    // Burst/time-proximity stacks with a designated top image.
+    Migration {
+        from: 5,
+        to: 6,
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS stacks (
+                id INTEGER PRIMARY KEY,
+                top_image_id INTEGER REFERENCES images(id) ON DELETE SET NULL,
+                collapsed INTEGER NOT NULL DEFAULT 1,
+                created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+                updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+            );
+
+            CREATE TRIGGER IF NOT EXISTS stacks_touch_updated_at
+            AFTER UPDATE ON stacks
+            FOR EACH ROW
+            BEGIN
+                UPDATE stacks
+                SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ','now')
+                WHERE id = NEW.id;
+            END;
+
+            CREATE TABLE IF NOT EXISTS stack_images (
+                stack_id INTEGER NOT NULL REFERENCES stacks(id) ON DELETE CASCADE,
+                image_id INTEGER NOT NULL UNIQUE REFERENCES images(id) ON DELETE CASCADE,
+                position INTEGER NOT NULL DEFAULT 0,
+                PRIMARY KEY (stack_id, image_id)
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_stack_images_stack_id
+                ON stack_images(stack_id, position);
+
+            CREATE TRIGGER IF NOT EXISTS stack_images_prune_empty
+            AFTER DELETE ON stack_images
+            WHEN NOT EXISTS (SELECT 1 FROM stack_images WHERE stack_id = OLD.stack_id)
+            BEGIN
+                DELETE FROM stacks WHERE id = OLD.stack_id;
+            END;
+        "#,
+    },
+    // Perceptual hashes for near-duplicate detection.
+    Migration {
+        from: 6,
+        to: 7,
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS perceptual_hashes (
+                image_id INTEGER PRIMARY KEY REFERENCES images(id) ON DELETE CASCADE,
+                dhash INTEGER NOT NULL,
+                updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+            );
+        "#,
+    },
+    // Named export presets shared between the desktop app and the CLI.
+    Migration {
+        from: 7,
+        to: 8,
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS export_presets (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+                settings_json TEXT NOT NULL CHECK (json_valid(settings_json)),
+                destination TEXT,
+                created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+                updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+            );
+        "#,
+    },
+    // Watermark definitions referenced by export presets.
+    Migration {
+        from: 8,
+        to: 9,
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS watermarks (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+                definition_json TEXT NOT NULL CHECK (json_valid(definition_json)),
+                created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+                updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+            );
+        "#,
+    },
+    // Named roots; folders store their path relative to one so a root can be relocated.
+    // Existing folders are assigned roots when the catalog is opened.
+    Migration {
+        from: 9,
+        to: 10,
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS roots (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+                path TEXT NOT NULL UNIQUE,
+                created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+                updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+            );
+
+            CREATE TABLE IF NOT EXISTS folder_roots (
+                folder_id INTEGER PRIMARY KEY REFERENCES folders(id) ON DELETE CASCADE,
+                root_id INTEGER NOT NULL REFERENCES roots(id) ON DELETE RESTRICT,
+                relative_path TEXT NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_folder_roots_root_id ON folder_roots(root_id);
+        "#,
+    },
+    // Batch capture time corrections, keeping the original values for undo.
+    Migration {
+        from: 10,
+        to: 11,
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS capture_time_corrections (
+                id INTEGER PRIMARY KEY,
+                description TEXT NOT NULL,
+                offset_seconds INTEGER NOT NULL,
+                wrote_sidecars INTEGER NOT NULL DEFAULT 0,
+                created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+            );
+
+            CREATE TABLE IF NOT EXISTS capture_time_changes (
+                correction_id INTEGER NOT NULL REFERENCES capture_time_corrections(id) ON DELETE CASCADE,
+                image_id INTEGER NOT NULL REFERENCES images(id) ON DELETE CASCADE,
+                previous_captured_at TEXT,
+                corrected_captured_at TEXT NOT NULL,
+                PRIMARY KEY (correction_id, image_id)
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_capture_time_changes_image_id ON capture_time_changes(image_id);
+        "#,
+    },
+    // Geotagging runs from GPS tracks, keeping the previous positions for undo.
+    Migration {
+        from: 11,
+        to: 12,
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS geotag_batches (
+                id INTEGER PRIMARY KEY,
+                description TEXT NOT NULL,
+                created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+            );
+
+            CREATE TABLE IF NOT EXISTS geotag_changes (
+                batch_id INTEGER NOT NULL REFERENCES geotag_batches(id) ON DELETE CASCADE,
+                image_id INTEGER NOT NULL REFERENCES images(id) ON DELETE CASCADE,
+                previous_latitude REAL,
+                previous_longitude REAL,
+                previous_altitude REAL,
+                latitude REAL NOT NULL,
+                longitude REAL NOT NULL,
+                altitude REAL,
+                PRIMARY KEY (batch_id, image_id)
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_geotag_changes_image_id ON geotag_changes(image_id);
+        "#,
+    },
+    // Places looked up from GPS positions, searchable through their own FTS index.
+    Migration {
+        from: 12,
+        to: 13,
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS image_locations (
+                image_id INTEGER PRIMARY KEY REFERENCES images(id) ON DELETE CASCADE,
+                city TEXT NOT NULL,
+                region TEXT NOT NULL,
+                country TEXT NOT NULL,
+                country_code TEXT NOT NULL,
+                distance_km REAL NOT NULL,
+                located_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_image_locations_country ON image_locations(country_code);
+
+            CREATE VIRTUAL TABLE IF NOT EXISTS fts_locations
+            USING fts5(city, region, country, content='', tokenize='unicode61');
+
+            CREATE TRIGGER IF NOT EXISTS image_locations_fts_ai
+            AFTER INSERT ON image_locations
+            BEGIN
+                INSERT INTO fts_locations(rowid, city, region, country)
+                VALUES (new.image_id, new.city, new.region, new.country);
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS image_locations_fts_ad
+            AFTER DELETE ON image_locations
+            BEGIN
+                INSERT INTO fts_locations(fts_locations, rowid, city, region, country)
+                VALUES ('delete', old.image_id, old.city, old.region, old.country);
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS image_locations_fts_au
+            AFTER UPDATE ON image_locations
+            BEGIN
+                INSERT INTO fts_locations(fts_locations, rowid, city, region, country)
+                VALUES ('delete', old.image_id, old.city, old.region, old.country);
+                INSERT INTO fts_locations(rowid, city, region, country)
+                VALUES (new.image_id, new.city, new.region, new.country);
+            END;
+        "#,
+    },
+    // R*Tree over image positions for bounding-box, radius and clustering queries.
+    Migration {
+        from: 13,
+        to: 14,
+        sql: r#"
+            CREATE VIRTUAL TABLE IF NOT EXISTS rtree_images
+            USING rtree(id, min_latitude, max_latitude, min_longitude, max_longitude);
+
+            CREATE TRIGGER IF NOT EXISTS images_rtree_ai
+            AFTER INSERT ON images
+            WHEN new.gps_latitude IS NOT NULL AND new.gps_longitude IS NOT NULL
+            BEGIN
+                INSERT INTO rtree_images (id, min_latitude, max_latitude, min_longitude, max_longitude)
+                VALUES (new.id, new.gps_latitude, new.gps_latitude, new.gps_longitude, new.gps_longitude);
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS images_rtree_ad
+            AFTER DELETE ON images
+            BEGIN
+                DELETE FROM rtree_images WHERE id = old.id;
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS images_rtree_au
+            AFTER UPDATE OF gps_latitude, gps_longitude ON images
+            BEGIN
+                DELETE FROM rtree_images WHERE id = old.id;
+                INSERT INTO rtree_images (id, min_latitude, max_latitude, min_longitude, max_longitude)
+                SELECT new.id, new.gps_latitude, new.gps_latitude, new.gps_longitude, new.gps_longitude
+                WHERE new.gps_latitude IS NOT NULL AND new.gps_longitude IS NOT NULL;
+            END;
+
+            INSERT INTO rtree_images (id, min_latitude, max_latitude, min_longitude, max_longitude)
+            SELECT id, gps_latitude, gps_latitude, gps_longitude, gps_longitude
+            FROM images
+            WHERE gps_latitude IS NOT NULL AND gps_longitude IS NOT NULL;
+        "#,
+    },
 ];
 
-pub const LATEST_SCHEMA_VERSION: i32 = 5;
+pub const LATEST_SCHEMA_VERSION: i32 = 14;
 
 pub fn current_schema_version(db: &CatalogDb) -> DbResult<i32> {
     current_schema_version_for_conn(db.conn())
@@ -6,35 +6,61 @@ use rusqlite::{Connection, Row, Transaction};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
+pub mod capture_time_corrections;
 pub mod catalog_metadata;
 pub mod collection_images;
 pub mod collections;
 pub mod db;
 pub mod edit_history;
 pub mod edits;
+pub mod export_presets;
 pub mod folders;
+pub mod geo;
+pub mod geotag_batches;
 pub mod image_keywords;
+pub mod image_locations;
 pub mod images;
 pub mod keywords;
 pub mod migrations;
+pub mod perceptual_hashes;
 pub mod previews;
+pub mod roots;
 pub mod search;
+pub mod stack_images;
+pub mod stacks;
+#[cfg(test)]
+pub(crate) mod test_support;
 pub mod thumbnails;
+pub mod timeline;
+pub mod watermarks;
 
+pub use capture_time_corrections::{CaptureTimeChange, CaptureTimeCorrection};
 pub use catalog_metadata::CatalogMetadata;
 pub use collection_images::CollectionImage;
 pub use collections::Collection;
 pub use db::CatalogDb;
 pub use edit_history::EditHistory;
 pub use edits::Edit;
+pub use export_presets::ExportPreset;
 pub use folders::Folder;
+pub use geo::{rebuild_spatial_index, BoundingBox, GeoCluster};
+pub use geotag_batches::{GeotagBatch, GeotagChange, GpsPosition};
 pub use image_keywords::ImageKeyword;
+pub use image_locations::ImageLocation;
 pub use images::Image;
 pub use keywords::Keyword;
 pub use migrations::{Migration, MIGRATIONS};
+pub use perceptual_hashes::PerceptualHash;
 pub use previews::Preview;
+pub use roots::Root;
 pub use search::{rebuild_fts, search_folders, search_images, search_keywords};
+pub use stack_images::StackImage;
+pub use stacks::Stack;
+#[cfg(test)]
+pub(crate) use test_support::{test_folder, test_image};
 pub use thumbnails::Thumbnail;
+pub use timeline::{DatePeriod, DayCount, MonthCount, YearCount};
+pub use watermarks::Watermark;
 
 pub type DbResult<T> = anyhow::Result<T>;
 
@@ -132,6 +158,25 @@ where
     Ok(out)
 }
 
+/// Run `f` inside a savepoint of the open transaction on `conn`. When it fails its writes
+/// are rolled back and the error returned, while the rest of the transaction carries on.
+pub fn in_savepoint<T>(conn: &Connection, f: impl FnOnce() -> DbResult<T>) -> DbResult<T> {
+    conn.execute_batch("SAVEPOINT item")
+        .context("failed to start savepoint")?;
+    match f() {
+        Ok(value) => {
+            conn.execute_batch("RELEASE item")
+                .context("failed to release savepoint")?;
+            Ok(value)
+        }
+        Err(err) => {
+            conn.execute_batch("ROLLBACK TO item; RELEASE item")
+                .context("failed to roll back savepoint")?;
+            Err(err)
+        }
+    }
+}
+
 pub fn to_rfc3339(ts: DateTime<Utc>) -> String {
     ts.to_rfc3339_opts(SecondsFormat::Millis, true)
 }
@@ -0,0 +1,111 @@
+use crate::db::{parse_datetime, query_all, query_one, to_rfc3339, DbHandle, DbResult};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+/// Perceptual (difference) hash of an image's 256px thumbnail.
+///
+/// SQLite integers are signed, so the 64 hash bits are stored reinterpreted as `i64`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PerceptualHash {
+    pub image_id: i64,
+    pub dhash: u64,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl PerceptualHash {
+    pub fn insert<H: DbHandle>(&self, db: &H) -> DbResult<i64> {
+        db.execute(
+            "INSERT INTO perceptual_hashes (image_id, dhash, updated_at) VALUES (?1, ?2, ?3)",
+            params![
+                self.image_id,
+                self.dhash as i64,
+                to_rfc3339(self.updated_at)
+            ],
+        )
+        .with_context(|| {
+            format!(
+                "failed to insert perceptual hash for image_id={}",
+                self.image_id
+            )
+        })?;
+        Ok(db.last_insert_rowid())
+    }
+
+    pub fn upsert<H: DbHandle>(&self, db: &H) -> DbResult<()> {
+        db.execute(
+            "INSERT INTO perceptual_hashes (image_id, dhash, updated_at)
+             VALUES (?1, ?2, ?3)
+             ON CONFLICT(image_id) DO UPDATE SET
+                dhash = excluded.dhash,
+                updated_at = excluded.updated_at",
+            params![
+                self.image_id,
+                self.dhash as i64,
+                to_rfc3339(self.updated_at)
+            ],
+        )
+        .with_context(|| {
+            format!(
+                "failed to upsert perceptual hash for image_id={}",
+                self.image_id
+            )
+        })?;
+        Ok(())
+    }
+
+    pub fn load<H: DbHandle>(db: &H, image_id: i64) -> DbResult<Self> {
+        query_one(
+            db,
+            "SELECT image_id, dhash, updated_at FROM perceptual_hashes WHERE image_id = ?1",
+            params![image_id],
+            PerceptualHash::from_row,
+        )
+        .with_context(|| format!("failed to load perceptual hash for image_id={image_id}"))
+    }
+
+    pub fn load_all<H: DbHandle>(db: &H) -> DbResult<Vec<Self>> {
+        query_all(
+            db,
+            "SELECT image_id, dhash, updated_at FROM perceptual_hashes ORDER BY image_id",
+            [],
+            PerceptualHash::from_row,
+        )
+    }
+
+    pub fn update<H: DbHandle>(&self, db: &H) -> DbResult<()> {
+        db.execute(
+            "UPDATE perceptual_hashes SET dhash = ?1, updated_at = ?2 WHERE image_id = ?3",
+            params![
+                self.dhash as i64,
+                to_rfc3339(self.updated_at),
+                self.image_id
+            ],
+        )
+        .with_context(|| {
+            format!(
+                "failed to update perceptual hash for image_id={}",
+                self.image_id
+            )
+        })?;
+        Ok(())
+    }
+
+    pub fn delete<H: DbHandle>(db: &H, image_id: i64) -> DbResult<()> {
+        db.execute(
+            "DELETE FROM perceptual_hashes WHERE image_id = ?1",
+            params![image_id],
+        )
+        .with_context(|| format!("failed to delete perceptual hash for image_id={image_id}"))?;
+        Ok(())
+    }
+
+    pub(crate) fn from_row(row: &rusqlite::Row<'_>) -> DbResult<Self> {
+        Ok(Self {
+            image_id: row.get(0)?,
+            dhash: row.get::<_, i64>(1)? as u64,
+            updated_at: parse_datetime(row.get::<_, String>(2)?, "updated_at")?,
+        })
+    }
+}
@@ -0,0 +1,322 @@
+//! Named roots that folders are stored relative to.
+//!
+//! A root is a volume, mount point or library folder, e.g. "Photos" at `/mnt/nas/photos`.
+//! Folders keep their path relative to their root, so relocating a root moves every
+//! folder and image below it. `folders.path` and `images.original_path` still hold the
+//! resolved absolute paths the rest of the catalog reads.
+
+use std::path::{Component, Path, PathBuf, Prefix};
+
+use crate::db::{
+    parse_datetime, query_all, query_one, query_optional, to_rfc3339, DbHandle, DbResult,
+};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Root {
+    pub id: i64,
+    pub name: String,
+    pub path: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Root {
+    pub fn insert<H: DbHandle>(&self, db: &H) -> DbResult<i64> {
+        db.execute(
+            "INSERT INTO roots (name, path, created_at, updated_at) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                self.name,
+                self.path,
+                to_rfc3339(self.created_at),
+                to_rfc3339(self.updated_at)
+            ],
+        )
+        .with_context(|| format!("failed to insert root name={}", self.name))?;
+        Ok(db.last_insert_rowid())
+    }
+
+    pub fn load<H: DbHandle>(db: &H, id: i64) -> DbResult<Self> {
+        query_one(
+            db,
+            "SELECT id, name, path, created_at, updated_at FROM roots WHERE id = ?1",
+            params![id],
+            Root::from_row,
+        )
+        .with_context(|| format!("failed to load root id={id}"))
+    }
+
+    pub fn load_all<H: DbHandle>(db: &H) -> DbResult<Vec<Self>> {
+        query_all(
+            db,
+            "SELECT id, name, path, created_at, updated_at FROM roots ORDER BY name COLLATE NOCASE",
+            [],
+            Root::from_row,
+        )
+    }
+
+    pub fn update<H: DbHandle>(&self, db: &H) -> DbResult<()> {
+        db.execute(
+            "UPDATE roots SET name = ?1, path = ?2, created_at = ?3, updated_at = ?4 WHERE id = ?5",
+            params![
+                self.name,
+                self.path,
+                to_rfc3339(self.created_at),
+                to_rfc3339(self.updated_at),
+                self.id
+            ],
+        )
+        .with_context(|| format!("failed to update root id={}", self.id))?;
+        Ok(())
+    }
+
+    pub fn delete<H: DbHandle>(db: &H, id: i64) -> DbResult<()> {
+        db.execute("DELETE FROM roots WHERE id = ?1", params![id])
+            .with_context(|| format!("failed to delete root id={id}"))?;
+        Ok(())
+    }
+
+    pub fn find_by_name<H: DbHandle>(db: &H, name: &str) -> DbResult<Option<Self>> {
+        query_optional(
+            db,
+            "SELECT id, name, path, created_at, updated_at FROM roots WHERE name = ?1",
+            params![name],
+            Root::from_row,
+        )
+    }
+
+    pub fn find_by_path<H: DbHandle>(db: &H, path: &str) -> DbResult<Option<Self>> {
+        query_optional(
+            db,
+            "SELECT id, name, path, created_at, updated_at FROM roots WHERE path = ?1",
+            params![path],
+            Root::from_row,
+        )
+    }
+
+    /// The most specific root containing `path`, creating one for its volume when no
+    /// root at least as deep as the volume does, so a catch-all like `/` never swallows
+    /// a card or mount plugged in later. Returns the root and `path` relative to it.
+    /// `home` is the user's home folder as the caller sees it; see [`volume_for_path`].
+    pub fn resolve<H: DbHandle>(
+        db: &H,
+        path: &Path,
+        home: Option<&Path>,
+    ) -> DbResult<(Self, String)> {
+        let depth = |path: &Path| path.components().count();
+        let (name, volume) = volume_for_path(path, home);
+        let existing = Root::load_all(db)?
+            .into_iter()
+            .filter(|root| path.starts_with(&root.path))
+            .max_by_key(|root| depth(Path::new(&root.path)))
+            .filter(|root| depth(Path::new(&root.path)) >= depth(&volume));
+        let root = match existing {
+            Some(root) => root,
+            None => {
+                let volume = volume.to_string_lossy().into_owned();
+                match Root::find_by_path(db, &volume)? {
+                    Some(root) => root,
+                    None => {
+                        let now = Utc::now();
+                        let mut root = Root {
+                            id: 0,
+                            name: unique_name(db, &name)?,
+                            path: volume,
+                            created_at: now,
+                            updated_at: now,
+                        };
+                        root.id = root.insert(db)?;
+                        root
+                    }
+                }
+            }
+        };
+        let relative = relative_path(Path::new(&root.path), path);
+        Ok((root, relative))
+    }
+
+    /// The absolute path of `relative` below this root.
+    pub fn join(&self, relative: &str) -> PathBuf {
+        relative
+            .split('/')
+            .filter(|part| !part.is_empty())
+            .fold(PathBuf::from(&self.path), |path, part| path.join(part))
+    }
+
+    pub(crate) fn from_row(row: &rusqlite::Row<'_>) -> DbResult<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            path: row.get(2)?,
+            created_at: parse_datetime(row.get::<_, String>(3)?, "created_at")?,
+            updated_at: parse_datetime(row.get::<_, String>(4)?, "updated_at")?,
+        })
+    }
+}
+
+/// `path` below `root` with `/` separators, so it resolves the same on every platform.
+pub fn relative_path(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// `name`, or `name (2)`, `name (3)`… when a root already uses it.
+pub(crate) fn unique_name<H: DbHandle>(db: &H, name: &str) -> DbResult<String> {
+    let mut candidate = name.to_string();
+    let mut n = 2;
+    while Root::find_by_name(db, &candidate)?.is_some() {
+        candidate = format!("{name} ({n})");
+        n += 1;
+    }
+    Ok(candidate)
+}
+
+/// The volume or mount point `path` lives on and a display name for it: the drive on
+/// Windows, removable and network mounts on Linux and macOS, else `home` or the
+/// filesystem root. The caller passes `home` in, so the catalog layer never depends
+/// on the environment of the process that happens to open it.
+pub fn volume_for_path(path: &Path, home: Option<&Path>) -> (String, PathBuf) {
+    let mut components = path.components();
+    if let Some(Component::Prefix(prefix)) = path.components().next() {
+        let name = match prefix.kind() {
+            Prefix::Disk(letter) | Prefix::VerbatimDisk(letter) => {
+                format!("{}:", (letter as char).to_ascii_uppercase())
+            }
+            _ => prefix.as_os_str().to_string_lossy().into_owned(),
+        };
+        let mut root = PathBuf::from(prefix.as_os_str());
+        root.push(std::path::MAIN_SEPARATOR_STR);
+        return (name, root);
+    }
+
+    if !path.has_root() {
+        let first = components
+            .find_map(|c| match c {
+                Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .unwrap_or_else(|| ".".to_string());
+        return (first.clone(), PathBuf::from(first));
+    }
+
+    let names: Vec<String> = path
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect();
+    let mount = |depth: usize| -> (String, PathBuf) {
+        let root = names[..depth]
+            .iter()
+            .fold(PathBuf::from("/"), |path, part| path.join(part));
+        (names[depth - 1].clone(), root)
+    };
+    let parts: Vec<&str> = names.iter().map(String::as_str).collect();
+    // udisks mounts removable media under `/media/<user>/<label>`.
+    let user = home
+        .and_then(Path::file_name)
+        .map(|name| name.to_string_lossy().into_owned());
+    match parts.as_slice() {
+        ["Volumes", _, ..] | ["mnt", _, ..] => return mount(2),
+        ["run", "media", _, _, ..] => return mount(4),
+        ["media", owner, _, ..] if Some(*owner) == user.as_deref() => return mount(3),
+        ["media", _, ..] => return mount(2),
+        _ => {}
+    }
+
+    if let Some(home) = home {
+        if home != Path::new("/") && path.starts_with(home) {
+            return ("Home".to_string(), home.to_path_buf());
+        }
+    }
+    ("System".to_string(), PathBuf::from("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::CatalogDb;
+    use crate::schema::initialize_schema;
+
+    #[test]
+    fn folders_resolve_to_the_most_specific_root() {
+        let db = CatalogDb::in_memory().unwrap();
+        initialize_schema(db.conn()).unwrap();
+
+        let now = Utc::now();
+        let photos = Root {
+            id: 0,
+            name: "Photos".into(),
+            path: "/mnt/nas/photos".into(),
+            created_at: now,
+            updated_at: now,
+        };
+        let photos_id = photos.insert(&db).unwrap();
+
+        let (root, relative) =
+            Root::resolve(&db, Path::new("/mnt/nas/photos/2024/June"), None).unwrap();
+        assert_eq!(root.id, photos_id);
+        assert_eq!(relative, "2024/June");
+
+        // Outside every root, a root for the mount is created.
+        let (root, relative) = Root::resolve(&db, Path::new("/mnt/card/DCIM/100"), None).unwrap();
+        assert_eq!(
+            (root.name.as_str(), root.path.as_str()),
+            ("card", "/mnt/card")
+        );
+        assert_eq!(relative, "DCIM/100");
+        assert_eq!(root.join(&relative), PathBuf::from("/mnt/card/DCIM/100"));
+    }
+
+    #[test]
+    fn a_system_root_does_not_swallow_later_mounts() {
+        let db = CatalogDb::in_memory().unwrap();
+        initialize_schema(db.conn()).unwrap();
+
+        let (system, _) = Root::resolve(&db, Path::new("/srv/x"), None).unwrap();
+        assert_eq!(system.path, "/");
+        let (card, relative) = Root::resolve(&db, Path::new("/mnt/card/y"), None).unwrap();
+        assert_eq!(
+            (card.name.as_str(), card.path.as_str()),
+            ("card", "/mnt/card")
+        );
+        assert_eq!(relative, "y");
+        // A root as deep as the volume is still reused.
+        let (again, _) = Root::resolve(&db, Path::new("/srv/z"), None).unwrap();
+        assert_eq!(again.id, system.id);
+    }
+
+    #[test]
+    fn detects_mount_points() {
+        let cases = [
+            ("/Volumes/Card/DCIM", "Card", "/Volumes/Card"),
+            ("/mnt/nas/photos", "nas", "/mnt/nas"),
+            ("/run/media/jo/SD/DCIM", "SD", "/run/media/jo/SD"),
+            ("/media/Backup/2024", "Backup", "/media/Backup"),
+            ("/media/jo/SD/DCIM", "SD", "/media/jo/SD"),
+            ("/home/jo/Pictures", "Home", "/home/jo"),
+            ("/home/kim/Pictures", "System", "/"),
+            ("/srv/photos", "System", "/"),
+        ];
+        let home = Path::new("/home/jo");
+        for (path, name, root) in cases {
+            let (detected, volume) = volume_for_path(Path::new(path), Some(home));
+            assert_eq!(
+                (detected.as_str(), volume),
+                (name, PathBuf::from(root)),
+                "{path}"
+            );
+        }
+    }
+}
@@ -16,7 +16,8 @@ pub fn search_keywords(db: &CatalogDb, query: &str) -> DbResult<Vec<Keyword>> {
     )
 }
 
-/// Perform image filename/path/metadata search.
+/// Perform image filename/path/metadata search, also matching the place an image was
+/// taken.
 pub fn search_images(db: &CatalogDb, query: &str) -> DbResult<Vec<Image>> {
     query_all(
         db,
@@ -27,10 +28,16 @@ pub fn search_images(db: &CatalogDb, query: &str) -> DbResult<Vec<Image>> {
             i.shutter_speed, i.iso, i.orientation, i.gps_latitude, i.gps_longitude,
             i.gps_altitude, i.rating, i.flag, i.color_label, i.metadata_json,
             i.created_at, i.updated_at
-         FROM fts_images f
-         JOIN images i ON i.id = f.rowid
-         WHERE fts_images MATCH ?1
-         ORDER BY bm25(fts_images)",
+         FROM (
+            SELECT rowid AS id, bm25(fts_images) AS rank
+            FROM fts_images WHERE fts_images MATCH ?1
+            UNION ALL
+            SELECT rowid AS id, bm25(fts_locations) AS rank
+            FROM fts_locations WHERE fts_locations MATCH ?1
+         ) f
+         JOIN images i ON i.id = f.id
+         GROUP BY i.id
+         ORDER BY MIN(f.rank)",
         params![query],
         Image::from_row,
     )
@@ -40,9 +47,10 @@ pub fn search_images(db: &CatalogDb, query: &str) -> DbResult<Vec<Image>> {
 pub fn search_folders(db: &CatalogDb, query: &str) -> DbResult<Vec<Folder>> {
     query_all(
         db,
-        "SELECT f2.id, f2.path, f2.created_at, f2.updated_at
+        "SELECT f2.id, f2.path, r.root_id, r.relative_path, f2.created_at, f2.updated_at
          FROM fts_folders f
          JOIN folders f2 ON f2.id = f.rowid
+         LEFT JOIN folder_roots r ON r.folder_id = f2.id
          WHERE fts_folders MATCH ?1
          ORDER BY bm25(fts_folders)",
         params![query],
@@ -58,6 +66,7 @@ pub fn rebuild_fts(db: &CatalogDb) -> DbResult<()> {
         DROP TABLE IF EXISTS fts_keywords;
         DROP TABLE IF EXISTS fts_images;
         DROP TABLE IF EXISTS fts_folders;
+        DROP TABLE IF EXISTS fts_locations;
         CREATE VIRTUAL TABLE IF NOT EXISTS fts_keywords
             USING fts5(keyword, content='', tokenize='unicode61');
         CREATE VIRTUAL TABLE IF NOT EXISTS fts_images
@@ -70,6 +79,8 @@ pub fn rebuild_fts(db: &CatalogDb) -> DbResult<()> {
             );
         CREATE VIRTUAL TABLE IF NOT EXISTS fts_folders
             USING fts5(path, content='', tokenize='unicode61');
+        CREATE VIRTUAL TABLE IF NOT EXISTS fts_locations
+            USING fts5(city, region, country, content='', tokenize='unicode61');
         ",
     )?;
     db.conn().execute(
@@ -85,6 +96,11 @@ pub fn rebuild_fts(db: &CatalogDb) -> DbResult<()> {
         "INSERT INTO fts_folders(rowid, path) SELECT id, path FROM folders",
         [],
     )?;
+    db.conn().execute(
+        "INSERT INTO fts_locations(rowid, city, region, country)
+         SELECT image_id, city, region, country FROM image_locations",
+        [],
+    )?;
     db.conn()
         .execute_batch("COMMIT")
         .context("failed to rebuild FTS indexes")?;
@@ -110,6 +126,8 @@ mod tests {
         let folder = Folder {
             id: 0,
             path: "/photos/ftstest".into(),
+            root_id: None,
+            relative_path: None,
             created_at: Utc::now(),
             updated_at: Utc::now(),
         };
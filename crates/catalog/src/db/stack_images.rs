@@ -0,0 +1,89 @@
+use crate::db::{query_all, query_one, DbHandle, DbResult};
+use anyhow::Context;
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StackImage {
+    pub stack_id: i64,
+    pub image_id: i64,
+    pub position: i64,
+}
+
+impl StackImage {
+    pub fn insert<H: DbHandle>(&self, db: &H) -> DbResult<i64> {
+        db.execute(
+            "INSERT INTO stack_images (stack_id, image_id, position) VALUES (?1, ?2, ?3)",
+            params![self.stack_id, self.image_id, self.position],
+        )
+        .with_context(|| {
+            format!(
+                "failed to insert stack_image stack_id={} image_id={}",
+                self.stack_id, self.image_id
+            )
+        })?;
+        Ok(db.last_insert_rowid())
+    }
+
+    pub fn load<H: DbHandle>(db: &H, stack_id: i64, image_id: i64) -> DbResult<Self> {
+        query_one(
+            db,
+            "SELECT stack_id, image_id, position
+             FROM stack_images
+             WHERE stack_id = ?1 AND image_id = ?2",
+            params![stack_id, image_id],
+            StackImage::from_row,
+        )
+        .with_context(|| {
+            format!(
+                "failed to load stack_image stack_id={} image_id={}",
+                stack_id, image_id
+            )
+        })
+    }
+
+    pub fn load_all<H: DbHandle>(db: &H) -> DbResult<Vec<Self>> {
+        query_all(
+            db,
+            "SELECT stack_id, image_id, position FROM stack_images ORDER BY stack_id, position",
+            [],
+            StackImage::from_row,
+        )
+    }
+
+    pub fn update<H: DbHandle>(&self, db: &H) -> DbResult<()> {
+        db.execute(
+            "UPDATE stack_images SET position = ?1 WHERE stack_id = ?2 AND image_id = ?3",
+            params![self.position, self.stack_id, self.image_id],
+        )
+        .with_context(|| {
+            format!(
+                "failed to update stack_image stack_id={} image_id={}",
+                self.stack_id, self.image_id
+            )
+        })?;
+        Ok(())
+    }
+
+    pub fn delete<H: DbHandle>(db: &H, stack_id: i64, image_id: i64) -> DbResult<()> {
+        db.execute(
+            "DELETE FROM stack_images WHERE stack_id = ?1 AND image_id = ?2",
+            params![stack_id, image_id],
+        )
+        .with_context(|| {
+            format!(
+                "failed to delete stack_image stack_id={} image_id={}",
+                stack_id, image_id
+            )
+        })?;
+        Ok(())
+    }
+
+    pub(crate) fn from_row(row: &rusqlite::Row<'_>) -> DbResult<Self> {
+        Ok(Self {
+            stack_id: row.get(0)?,
+            image_id: row.get(1)?,
+            position: row.get(2)?,
+        })
+    }
+}
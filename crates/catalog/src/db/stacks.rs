@@ -0,0 +1,176 @@
+use crate::db::{
+    parse_datetime, query_all, query_one, query_optional, to_rfc3339, DbHandle, DbResult, Image,
+};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Stack {
+    pub id: i64,
+    pub top_image_id: Option<i64>,
+    pub collapsed: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Stack {
+    pub fn insert<H: DbHandle>(&self, db: &H) -> DbResult<i64> {
+        db.execute(
+            "INSERT INTO stacks (top_image_id, collapsed, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                self.top_image_id,
+                self.collapsed,
+                to_rfc3339(self.created_at),
+                to_rfc3339(self.updated_at)
+            ],
+        )
+        .context("failed to insert stack")?;
+        Ok(db.last_insert_rowid())
+    }
+
+    pub fn load<H: DbHandle>(db: &H, id: i64) -> DbResult<Self> {
+        query_one(
+            db,
+            "SELECT id, top_image_id, collapsed, created_at, updated_at FROM stacks WHERE id = ?1",
+            params![id],
+            Stack::from_row,
+        )
+        .with_context(|| format!("failed to load stack id={id}"))
+    }
+
+    pub fn load_all<H: DbHandle>(db: &H) -> DbResult<Vec<Self>> {
+        query_all(
+            db,
+            "SELECT id, top_image_id, collapsed, created_at, updated_at FROM stacks ORDER BY id",
+            [],
+            Stack::from_row,
+        )
+    }
+
+    pub fn update<H: DbHandle>(&self, db: &H) -> DbResult<()> {
+        db.execute(
+            "UPDATE stacks SET top_image_id = ?1, collapsed = ?2, created_at = ?3, updated_at = ?4
+             WHERE id = ?5",
+            params![
+                self.top_image_id,
+                self.collapsed,
+                to_rfc3339(self.created_at),
+                to_rfc3339(self.updated_at),
+                self.id
+            ],
+        )
+        .with_context(|| format!("failed to update stack id={}", self.id))?;
+        Ok(())
+    }
+
+    pub fn delete<H: DbHandle>(db: &H, id: i64) -> DbResult<()> {
+        db.execute("DELETE FROM stacks WHERE id = ?1", params![id])
+            .with_context(|| format!("failed to delete stack id={id}"))?;
+        Ok(())
+    }
+
+    /// Find the stack an image belongs to, if any. Images are members of at most one stack.
+    pub fn find_for_image<H: DbHandle>(db: &H, image_id: i64) -> DbResult<Option<Self>> {
+        query_optional(
+            db,
+            "SELECT s.id, s.top_image_id, s.collapsed, s.created_at, s.updated_at
+             FROM stacks s
+             INNER JOIN stack_images si ON si.stack_id = s.id
+             WHERE si.image_id = ?1",
+            params![image_id],
+            Stack::from_row,
+        )
+        .with_context(|| format!("failed to find stack for image_id={image_id}"))
+    }
+
+    pub fn add_image<H: DbHandle>(db: &H, stack_id: i64, image_id: i64) -> DbResult<()> {
+        let next_position: i64 = query_optional(
+            db,
+            "SELECT MAX(position) FROM stack_images WHERE stack_id = ?1",
+            params![stack_id],
+            |row| Ok(row.get::<_, Option<i64>>(0)?.unwrap_or(0)),
+        )?
+        .unwrap_or(0)
+            + 1;
+
+        db.execute(
+            "INSERT INTO stack_images (stack_id, image_id, position) VALUES (?1, ?2, ?3)",
+            params![stack_id, image_id, next_position],
+        )
+        .with_context(|| format!("failed to add image {image_id} to stack {stack_id}"))?;
+        Ok(())
+    }
+
+    pub fn list_images<H: DbHandle>(db: &H, stack_id: i64) -> DbResult<Vec<Image>> {
+        query_all(
+            db,
+            "SELECT
+                i.id, i.folder_id, i.filename, i.original_path, i.sidecar_path, i.sidecar_hash,
+                i.filesize, i.file_hash, i.file_modified_at, i.imported_at, i.captured_at,
+                i.camera_make, i.camera_model, i.lens_model, i.focal_length, i.aperture,
+                i.shutter_speed, i.iso, i.orientation, i.gps_latitude, i.gps_longitude,
+                i.gps_altitude, i.rating, i.flag, i.color_label, i.metadata_json,
+                i.created_at, i.updated_at
+             FROM images i
+             INNER JOIN stack_images si ON si.image_id = i.id
+             WHERE si.stack_id = ?1
+             ORDER BY si.position",
+            params![stack_id],
+            Image::from_row,
+        )
+    }
+
+    fn from_row(row: &rusqlite::Row<'_>) -> DbResult<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            top_image_id: row.get(1)?,
+            collapsed: row.get(2)?,
+            created_at: parse_datetime(row.get::<_, String>(3)?, "created_at")?,
+            updated_at: parse_datetime(row.get::<_, String>(4)?, "updated_at")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{test_folder, test_image, Image};
+    use crate::schema::initialize_schema;
+
+    #[test]
+    fn members_keep_order_and_empty_stacks_are_pruned() {
+        let db = crate::db::CatalogDb::in_memory().unwrap();
+        initialize_schema(db.conn()).unwrap();
+
+        let folder_id = test_folder("/burst").insert(&db).unwrap();
+        let first = test_image(folder_id, "/burst/a.dng").insert(&db).unwrap();
+        let second = test_image(folder_id, "/burst/b.dng").insert(&db).unwrap();
+
+        let stack = Stack {
+            id: 0,
+            top_image_id: Some(second),
+            collapsed: true,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        let stack_id = stack.insert(&db).unwrap();
+        Stack::add_image(&db, stack_id, first).unwrap();
+        Stack::add_image(&db, stack_id, second).unwrap();
+
+        let members = Stack::list_images(&db, stack_id).unwrap();
+        assert_eq!(members.len(), 2);
+        assert_eq!(members[0].id, first);
+        assert_eq!(
+            Stack::find_for_image(&db, second).unwrap().unwrap().id,
+            stack_id
+        );
+        assert!(Stack::add_image(&db, stack_id, first).is_err());
+
+        Image::delete(&db, first).unwrap();
+        Image::delete(&db, second).unwrap();
+        assert!(Stack::load_all(&db).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,58 @@
+//! Records for tests to insert. Everything optional is left empty, so a test sets only
+//! the fields it checks, e.g. `Image { rating: Some(3), ..test_image(folder_id, path) }`.
+
+use std::path::Path;
+
+use chrono::Utc;
+
+use super::{Folder, Image};
+
+pub fn test_folder(path: &str) -> Folder {
+    let now = Utc::now();
+    Folder {
+        id: 0,
+        path: path.into(),
+        root_id: None,
+        relative_path: None,
+        created_at: now,
+        updated_at: now,
+    }
+}
+
+/// An image at `path`, named after its last component.
+pub fn test_image(folder_id: i64, path: &str) -> Image {
+    let now = Utc::now();
+    Image {
+        id: 0,
+        folder_id,
+        filename: Path::new(path)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        original_path: path.into(),
+        sidecar_path: None,
+        sidecar_hash: None,
+        filesize: None,
+        file_hash: None,
+        file_modified_at: None,
+        imported_at: now,
+        captured_at: None,
+        camera_make: None,
+        camera_model: None,
+        lens_model: None,
+        focal_length: None,
+        aperture: None,
+        shutter_speed: None,
+        iso: None,
+        orientation: None,
+        gps_latitude: None,
+        gps_longitude: None,
+        gps_altitude: None,
+        rating: None,
+        flag: None,
+        color_label: None,
+        metadata_json: None,
+        created_at: now,
+        updated_at: now,
+    }
+}
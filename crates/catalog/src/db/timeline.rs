@@ -0,0 +1,240 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::db::{query_all, CatalogDb, DbResult, Image};
+use anyhow::{bail, Context};
+use chrono::{Datelike, NaiveDate};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+/// The `YYYY-MM-DD` an image was taken, or last modified when its capture time is unknown.
+pub(crate) const DAY_SQL: &str = "substr(COALESCE(captured_at, file_modified_at), 1, 10)";
+
+/// A year, month or day of the timeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DatePeriod {
+    Year(i32),
+    Month(i32, u32),
+    Day(NaiveDate),
+}
+
+impl DatePeriod {
+    /// The start of the period's `YYYY-MM-DD`, which every day in it shares.
+    pub(crate) fn prefix(&self) -> String {
+        match self {
+            DatePeriod::Year(year) => format!("{year:04}"),
+            DatePeriod::Month(year, month) => format!("{year:04}-{month:02}"),
+            DatePeriod::Day(date) => date.format("%Y-%m-%d").to_string(),
+        }
+    }
+}
+
+impl fmt::Display for DatePeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.prefix())
+    }
+}
+
+impl FromStr for DatePeriod {
+    type Err = anyhow::Error;
+
+    /// Parse `YYYY`, `YYYY-MM` or `YYYY-MM-DD`.
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = raw.trim().split('-').collect();
+        let number = |part: &str| -> DbResult<u32> {
+            part.parse()
+                .with_context(|| format!("'{raw}' is not a YYYY, YYYY-MM or YYYY-MM-DD date"))
+        };
+        match parts[..] {
+            [year] => Ok(DatePeriod::Year(number(year)? as i32)),
+            [year, month] => {
+                let month = number(month)?;
+                if !(1..=12).contains(&month) {
+                    bail!("month must be between 1 and 12, got {month}");
+                }
+                Ok(DatePeriod::Month(number(year)? as i32, month))
+            }
+            [year, month, day] => {
+                let date =
+                    NaiveDate::from_ymd_opt(number(year)? as i32, number(month)?, number(day)?)
+                        .with_context(|| format!("'{raw}' is not a valid date"))?;
+                Ok(DatePeriod::Day(date))
+            }
+            _ => bail!("'{raw}' is not a YYYY, YYYY-MM or YYYY-MM-DD date"),
+        }
+    }
+}
+
+/// Images taken on one day.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DayCount {
+    pub date: NaiveDate,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MonthCount {
+    pub month: u32,
+    pub count: usize,
+    pub days: Vec<DayCount>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct YearCount {
+    pub year: i32,
+    pub count: usize,
+    pub months: Vec<MonthCount>,
+}
+
+/// Count the images taken on each day, oldest first. Images without any date are left out.
+pub fn count_images_per_day(db: &CatalogDb) -> DbResult<Vec<DayCount>> {
+    let rows: Vec<(String, usize)> = query_all(
+        db,
+        &format!(
+            "SELECT {DAY_SQL} AS day, COUNT(*)
+             FROM images
+             WHERE day IS NOT NULL
+             GROUP BY day
+             ORDER BY day"
+        ),
+        [],
+        |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as usize)),
+    )?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|(day, count)| {
+            let date = NaiveDate::parse_from_str(&day, "%Y-%m-%d").ok()?;
+            Some(DayCount { date, count })
+        })
+        .collect())
+}
+
+/// Group per-day counts, oldest first, into years and months.
+pub fn date_hierarchy(days: &[DayCount]) -> Vec<YearCount> {
+    let mut years: Vec<YearCount> = Vec::new();
+    for day in days {
+        let (year, month) = (day.date.year(), day.date.month());
+        if years.last().map(|last| last.year) != Some(year) {
+            years.push(YearCount {
+                year,
+                count: 0,
+                months: Vec::new(),
+            });
+        }
+        let entry = years.last_mut().expect("pushed above");
+        entry.count += day.count;
+        if entry.months.last().map(|last| last.month) != Some(month) {
+            entry.months.push(MonthCount {
+                month,
+                count: 0,
+                days: Vec::new(),
+            });
+        }
+        let months = entry.months.last_mut().expect("pushed above");
+        months.count += day.count;
+        months.days.push(day.clone());
+    }
+    years
+}
+
+/// Images taken during `period`, in capture order.
+pub fn images_taken_in(db: &CatalogDb, period: &DatePeriod) -> DbResult<Vec<Image>> {
+    query_all(
+        db,
+        &format!(
+            "SELECT
+                id, folder_id, filename, original_path, sidecar_path, sidecar_hash, filesize,
+                file_hash, file_modified_at, imported_at, captured_at, camera_make,
+                camera_model, lens_model, focal_length, aperture, shutter_speed, iso,
+                orientation, gps_latitude, gps_longitude, gps_altitude, rating, flag,
+                color_label, metadata_json, created_at, updated_at
+             FROM images
+             WHERE {DAY_SQL} LIKE ?1 || '%'
+             ORDER BY COALESCE(captured_at, file_modified_at), id"
+        ),
+        params![period.prefix()],
+        Image::from_row,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{test_folder, test_image};
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn groups_images_by_day_month_and_year() {
+        let db = CatalogDb::in_memory().unwrap();
+        let folder_id = test_folder("/photos/days").insert(&db).unwrap();
+        let at = |y, m, d, h| Some(Utc.with_ymd_and_hms(y, m, d, h, 0, 0).unwrap());
+        let morning = Image {
+            captured_at: at(2023, 12, 31, 9),
+            ..test_image(folder_id, "/photos/days/a.jpg")
+        }
+        .insert(&db)
+        .unwrap();
+        let evening = Image {
+            captured_at: at(2023, 12, 31, 21),
+            ..test_image(folder_id, "/photos/days/b.jpg")
+        }
+        .insert(&db)
+        .unwrap();
+        let modified = Image {
+            file_modified_at: at(2024, 2, 3, 12),
+            ..test_image(folder_id, "/photos/days/c.jpg")
+        }
+        .insert(&db)
+        .unwrap();
+        let later = Image {
+            captured_at: at(2024, 2, 20, 8),
+            file_modified_at: at(2023, 1, 1, 0),
+            ..test_image(folder_id, "/photos/days/d.jpg")
+        }
+        .insert(&db)
+        .unwrap();
+        test_image(folder_id, "/photos/days/undated.jpg")
+            .insert(&db)
+            .unwrap();
+
+        let days = count_images_per_day(&db).unwrap();
+        let counts: Vec<(String, usize)> = days
+            .iter()
+            .map(|day| (day.date.to_string(), day.count))
+            .collect();
+        assert_eq!(
+            counts,
+            [
+                ("2023-12-31".to_string(), 2),
+                ("2024-02-03".to_string(), 1),
+                ("2024-02-20".to_string(), 1)
+            ]
+        );
+
+        let years = date_hierarchy(&days);
+        assert_eq!(years.len(), 2);
+        assert_eq!((years[0].year, years[0].count), (2023, 2));
+        assert_eq!((years[1].year, years[1].count), (2024, 2));
+        assert_eq!(years[1].months.len(), 1);
+        assert_eq!(years[1].months[0].month, 2);
+        assert_eq!(years[1].months[0].days.len(), 2);
+
+        let ids = |period: &str| -> Vec<i64> {
+            images_taken_in(&db, &period.parse().unwrap())
+                .unwrap()
+                .iter()
+                .map(|image| image.id)
+                .collect()
+        };
+        assert_eq!(ids("2023"), [morning, evening]);
+        assert_eq!(ids("2024-02"), [modified, later]);
+        assert_eq!(ids("2024-02-20"), [later]);
+        assert!(ids("2024-03").is_empty());
+        assert!("2024-13".parse::<DatePeriod>().is_err());
+        assert!("2024-02-30".parse::<DatePeriod>().is_err());
+        assert_eq!(
+            "2024-2".parse::<DatePeriod>().unwrap().to_string(),
+            "2024-02"
+        );
+    }
+}
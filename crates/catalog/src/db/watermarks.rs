@@ -0,0 +1,99 @@
+use crate::db::{
+    from_json, parse_datetime, query_all, query_one, query_optional, to_json, to_rfc3339, DbHandle,
+    DbResult,
+};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Named watermark; `definition_json` holds a serialized `WatermarkDefinition`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Watermark {
+    pub id: i64,
+    pub name: String,
+    pub definition_json: Value,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Watermark {
+    pub fn insert<H: DbHandle>(&self, db: &H) -> DbResult<i64> {
+        db.execute(
+            "INSERT INTO watermarks (name, definition_json, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                self.name,
+                to_json(&self.definition_json)?,
+                to_rfc3339(self.created_at),
+                to_rfc3339(self.updated_at)
+            ],
+        )
+        .with_context(|| format!("failed to insert watermark {}", self.name))?;
+        Ok(db.last_insert_rowid())
+    }
+
+    pub fn load<H: DbHandle>(db: &H, id: i64) -> DbResult<Self> {
+        query_one(
+            db,
+            "SELECT id, name, definition_json, created_at, updated_at
+             FROM watermarks WHERE id = ?1",
+            params![id],
+            Watermark::from_row,
+        )
+        .with_context(|| format!("failed to load watermark id={id}"))
+    }
+
+    /// Case-insensitive lookup by watermark name.
+    pub fn find_by_name<H: DbHandle>(db: &H, name: &str) -> DbResult<Option<Self>> {
+        query_optional(
+            db,
+            "SELECT id, name, definition_json, created_at, updated_at
+             FROM watermarks WHERE name = ?1",
+            params![name],
+            Watermark::from_row,
+        )
+        .with_context(|| format!("failed to look up watermark {name}"))
+    }
+
+    pub fn load_all<H: DbHandle>(db: &H) -> DbResult<Vec<Self>> {
+        query_all(
+            db,
+            "SELECT id, name, definition_json, created_at, updated_at
+             FROM watermarks ORDER BY name COLLATE NOCASE",
+            [],
+            Watermark::from_row,
+        )
+    }
+
+    pub fn update<H: DbHandle>(&self, db: &H) -> DbResult<()> {
+        db.execute(
+            "UPDATE watermarks SET name = ?1, definition_json = ?2, updated_at = ?3 WHERE id = ?4",
+            params![
+                self.name,
+                to_json(&self.definition_json)?,
+                to_rfc3339(self.updated_at),
+                self.id
+            ],
+        )
+        .with_context(|| format!("failed to update watermark id={}", self.id))?;
+        Ok(())
+    }
+
+    pub fn delete<H: DbHandle>(db: &H, id: i64) -> DbResult<()> {
+        db.execute("DELETE FROM watermarks WHERE id = ?1", params![id])
+            .with_context(|| format!("failed to delete watermark id={id}"))?;
+        Ok(())
+    }
+
+    fn from_row(row: &rusqlite::Row<'_>) -> DbResult<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            definition_json: from_json(&row.get::<_, String>(2)?)?,
+            created_at: parse_datetime(row.get::<_, String>(3)?, "created_at")?,
+            updated_at: parse_datetime(row.get::<_, String>(4)?, "updated_at")?,
+        })
+    }
+}
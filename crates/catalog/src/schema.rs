@@ -237,6 +237,8 @@ fn ensure_folder_id(conn: &Connection, path: &str, now: DateTime<Utc>) -> Result
     let folder = Folder {
         id: 0,
         path: path.to_string(),
+        root_id: None,
+        relative_path: None,
         created_at: now,
         updated_at: now,
     };
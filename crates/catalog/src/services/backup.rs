@@ -0,0 +1,430 @@
+//! Online catalog backups, retention rotation and restore.
+//!
+//! Backups are written with the SQLite backup API so they are consistent while the catalog is
+//! open in WAL mode. Each backup file sits next to a small JSON manifest recording when it was
+//! taken and how many images it holds, so restore pickers can list backups without opening them.
+
+use std::fs;
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rusqlite::{Connection, DatabaseName};
+use serde::{Deserialize, Serialize};
+
+const BACKUP_PREFIX: &str = "backup-";
+const PLAIN_EXTENSION: &str = "sqlite";
+const COMPRESSED_EXTENSION: &str = "sqlite.gz";
+const MANIFEST_EXTENSION: &str = "json";
+
+/// When scheduled backups should run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupSchedule {
+    Never,
+    OnExit,
+    Daily,
+    #[default]
+    Weekly,
+}
+
+impl BackupSchedule {
+    /// Decide whether a backup is due given the newest existing backup.
+    ///
+    /// Interval schedules are checked both on open and on exit; `OnExit` only fires on exit.
+    pub fn is_due(
+        self,
+        last_backup: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+        on_exit: bool,
+    ) -> bool {
+        let interval = match self {
+            BackupSchedule::Never => return false,
+            BackupSchedule::OnExit => return on_exit,
+            BackupSchedule::Daily => Duration::days(1),
+            BackupSchedule::Weekly => Duration::weeks(1),
+        };
+        last_backup.is_none_or(|last| now - last >= interval)
+    }
+}
+
+/// User-facing backup preferences.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupSettings {
+    #[serde(default)]
+    pub schedule: BackupSchedule,
+    /// Number of backups kept per catalog; older ones are deleted after each new backup.
+    #[serde(default = "default_keep")]
+    pub keep: usize,
+    #[serde(default = "default_compress")]
+    pub compress: bool,
+}
+
+fn default_keep() -> usize {
+    5
+}
+
+fn default_compress() -> bool {
+    true
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        Self {
+            schedule: BackupSchedule::default(),
+            keep: default_keep(),
+            compress: default_compress(),
+        }
+    }
+}
+
+/// Sidecar manifest stored next to each backup file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub created_at: DateTime<Utc>,
+    pub image_count: i64,
+    pub schema_version: i64,
+    pub compressed: bool,
+}
+
+/// A backup discovered on disk.
+#[derive(Debug, Clone)]
+pub struct BackupInfo {
+    pub path: PathBuf,
+    pub created_at: DateTime<Utc>,
+    /// `None` when the manifest is missing and the count could not be read cheaply.
+    pub image_count: Option<i64>,
+    pub compressed: bool,
+    pub size_bytes: u64,
+}
+
+/// Default backup directory for a catalog: `Backups/<catalog stem>` beside the catalog file.
+pub fn backup_dir_for(catalog_path: &Path) -> PathBuf {
+    let stem = catalog_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "catalog".into());
+    catalog_path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join("Backups")
+        .join(stem)
+}
+
+/// Write a verified backup of the connection's main database into `backup_dir`.
+pub fn write_backup(conn: &Connection, backup_dir: &Path, compress: bool) -> Result<BackupInfo> {
+    fs::create_dir_all(backup_dir)
+        .with_context(|| format!("failed to create backup directory {}", backup_dir.display()))?;
+
+    let created_at = Utc::now();
+    let stem = format!("{BACKUP_PREFIX}{}", created_at.format("%Y%m%d-%H%M%S%3f"));
+    let partial = backup_dir.join(format!("{stem}.partial"));
+
+    conn.backup(DatabaseName::Main, &partial, None)
+        .with_context(|| format!("failed to back up catalog to {}", partial.display()))?;
+
+    let verified = (|| -> Result<(i64, i64)> {
+        let copy = Connection::open(&partial)?;
+        verify_integrity(&copy)?;
+        // Backups copy the WAL flag from the live catalog; switch to a self-contained file.
+        copy.pragma_update(None, "journal_mode", "DELETE")?;
+        let image_count = copy.query_row("SELECT COUNT(*) FROM images", [], |row| row.get(0))?;
+        let schema_version = copy
+            .query_row(
+                "SELECT schema_version FROM catalog_metadata WHERE id = 1",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap_or(0);
+        Ok((image_count, schema_version))
+    })();
+    let (image_count, schema_version) = match verified {
+        Ok(counts) => counts,
+        Err(err) => {
+            let _ = fs::remove_file(&partial);
+            return Err(err.context("backup failed verification"));
+        }
+    };
+
+    let extension = if compress {
+        COMPRESSED_EXTENSION
+    } else {
+        PLAIN_EXTENSION
+    };
+    let path = backup_dir.join(format!("{stem}.{extension}"));
+    if compress {
+        let result = gzip_file(&partial, &path);
+        let _ = fs::remove_file(&partial);
+        result?;
+    } else {
+        fs::rename(&partial, &path)
+            .with_context(|| format!("failed to finalize backup {}", path.display()))?;
+    }
+
+    let manifest = BackupManifest {
+        created_at,
+        image_count,
+        schema_version,
+        compressed: compress,
+    };
+    fs::write(
+        manifest_path(backup_dir, &stem),
+        serde_json::to_vec_pretty(&manifest)?,
+    )
+    .context("failed to write backup manifest")?;
+
+    let size_bytes = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    Ok(BackupInfo {
+        path,
+        created_at,
+        image_count: Some(image_count),
+        compressed: compress,
+        size_bytes,
+    })
+}
+
+/// List backups in `backup_dir`, newest first. A missing directory yields no backups.
+pub fn list_backups(backup_dir: &Path) -> Result<Vec<BackupInfo>> {
+    if !backup_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(backup_dir)
+        .with_context(|| format!("failed to read backups in {}", backup_dir.display()))?
+    {
+        let path = entry?.path();
+        let Some((stem, compressed)) = backup_stem(&path) else {
+            continue;
+        };
+        let metadata = fs::metadata(&path)?;
+        let manifest = fs::read(manifest_path(backup_dir, &stem))
+            .ok()
+            .and_then(|raw| serde_json::from_slice::<BackupManifest>(&raw).ok());
+
+        let (created_at, image_count) = match manifest {
+            Some(manifest) => (manifest.created_at, Some(manifest.image_count)),
+            None => (
+                metadata
+                    .modified()
+                    .map(DateTime::<Utc>::from)
+                    .unwrap_or_else(|_| Utc::now()),
+                None,
+            ),
+        };
+        backups.push(BackupInfo {
+            path,
+            created_at,
+            image_count,
+            compressed,
+            size_bytes: metadata.len(),
+        });
+    }
+
+    backups.sort_by_key(|info| std::cmp::Reverse(info.created_at));
+    Ok(backups)
+}
+
+/// Delete all but the newest `keep` backups, returning the removed backup files.
+pub fn rotate_backups(backup_dir: &Path, keep: usize) -> Result<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    for backup in list_backups(backup_dir)?.into_iter().skip(keep.max(1)) {
+        fs::remove_file(&backup.path)
+            .with_context(|| format!("failed to remove old backup {}", backup.path.display()))?;
+        if let Some((stem, _)) = backup_stem(&backup.path) {
+            let _ = fs::remove_file(manifest_path(backup_dir, &stem));
+        }
+        removed.push(backup.path);
+    }
+    Ok(removed)
+}
+
+/// Replace `catalog_path` with the contents of `backup_path`.
+///
+/// The catalog must be closed. The backup is unpacked and integrity-checked before anything is
+/// touched, and the replaced catalog (with its WAL files) is kept beside it with a
+/// `.pre-restore-<timestamp>` suffix. Returns the path of that safety copy, if one was made.
+pub fn restore_backup(backup_path: &Path, catalog_path: &Path) -> Result<Option<PathBuf>> {
+    let Some((_, compressed)) = backup_stem(backup_path) else {
+        bail!("{} is not a catalog backup", backup_path.display());
+    };
+
+    let staging = sibling_with_suffix(catalog_path, ".restoring");
+    if compressed {
+        gunzip_file(backup_path, &staging)?;
+    } else {
+        fs::copy(backup_path, &staging)
+            .with_context(|| format!("failed to copy backup {}", backup_path.display()))?;
+    }
+
+    let checked = Connection::open(&staging)
+        .map_err(anyhow::Error::from)
+        .and_then(|conn| verify_integrity(&conn));
+    if let Err(err) = checked {
+        let _ = fs::remove_file(&staging);
+        return Err(err.context(format!("backup {} is damaged", backup_path.display())));
+    }
+
+    let safety_copy = if catalog_path.exists() {
+        let suffix = format!(".pre-restore-{}", Utc::now().format("%Y%m%d-%H%M%S"));
+        let safety = sibling_with_suffix(catalog_path, &suffix);
+        fs::rename(catalog_path, &safety)
+            .with_context(|| format!("failed to set aside {}", catalog_path.display()))?;
+        for wal_suffix in ["-wal", "-shm"] {
+            let wal = sibling_with_suffix(catalog_path, wal_suffix);
+            if wal.exists() {
+                fs::rename(&wal, sibling_with_suffix(&safety, wal_suffix))?;
+            }
+        }
+        Some(safety)
+    } else {
+        None
+    };
+
+    fs::rename(&staging, catalog_path)
+        .with_context(|| format!("failed to restore into {}", catalog_path.display()))?;
+    Ok(safety_copy)
+}
+
+fn verify_integrity(conn: &Connection) -> Result<()> {
+    let result: String = conn.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+    if result != "ok" {
+        bail!("integrity check failed: {result}");
+    }
+    Ok(())
+}
+
+fn gzip_file(src: &Path, dest: &Path) -> Result<()> {
+    let mut input = BufReader::new(fs::File::open(src)?);
+    let output = BufWriter::new(
+        fs::File::create(dest)
+            .with_context(|| format!("failed to create backup {}", dest.display()))?,
+    );
+    let mut encoder = GzEncoder::new(output, Compression::default());
+    io::copy(&mut input, &mut encoder).context("failed to compress backup")?;
+    encoder
+        .finish()?
+        .into_inner()
+        .map_err(|err| err.into_error())?;
+    Ok(())
+}
+
+fn gunzip_file(src: &Path, dest: &Path) -> Result<()> {
+    let mut decoder = GzDecoder::new(BufReader::new(
+        fs::File::open(src).with_context(|| format!("failed to open backup {}", src.display()))?,
+    ));
+    let mut output = fs::File::create(dest)?;
+    io::copy(&mut decoder, &mut output).context("failed to decompress backup")?;
+    Ok(())
+}
+
+/// Split a backup file name into its stem and whether it is compressed.
+fn backup_stem(path: &Path) -> Option<(String, bool)> {
+    let name = path.file_name()?.to_str()?;
+    if !name.starts_with(BACKUP_PREFIX) {
+        return None;
+    }
+    if let Some(stem) = name.strip_suffix(&format!(".{COMPRESSED_EXTENSION}")) {
+        return Some((stem.to_string(), true));
+    }
+    name.strip_suffix(&format!(".{PLAIN_EXTENSION}"))
+        .map(|stem| (stem.to_string(), false))
+}
+
+fn manifest_path(backup_dir: &Path, stem: &str) -> PathBuf {
+    backup_dir.join(format!("{stem}.{MANIFEST_EXTENSION}"))
+}
+
+fn sibling_with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::CatalogDb;
+    use crate::services::CatalogService;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_dir(label: &str) -> PathBuf {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("catalog_backup_{label}_{suffix}"));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn image_count(path: &Path) -> i64 {
+        Connection::open(path)
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM images", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn backup_rotate_and_restore() {
+        let dir = temp_dir("roundtrip");
+        let catalog_path = dir.join("Main.zenithphotocatalog");
+        let backup_dir = backup_dir_for(&catalog_path);
+        let photo = dir.join("photo.jpg");
+        fs::write(&photo, b"not really a jpeg").unwrap();
+
+        let service = CatalogService::new(CatalogDb::open(catalog_path.to_str().unwrap()).unwrap());
+        service.import_image(&photo).unwrap();
+
+        let plain = service.create_backup(&backup_dir, false).unwrap();
+        let compressed = service.create_backup(&backup_dir, true).unwrap();
+        assert_eq!(plain.image_count, Some(1));
+        assert!(compressed.path.to_string_lossy().ends_with(".sqlite.gz"));
+
+        let listed = list_backups(&backup_dir).unwrap();
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].path, compressed.path);
+        assert_eq!(listed[1].image_count, Some(1));
+
+        let settings = BackupSettings {
+            schedule: BackupSchedule::OnExit,
+            keep: 1,
+            compress: true,
+        };
+        assert!(service
+            .backup_if_due(&backup_dir, &settings, false)
+            .unwrap()
+            .is_none());
+        let latest = service
+            .backup_if_due(&backup_dir, &settings, true)
+            .unwrap()
+            .unwrap();
+        let remaining = list_backups(&backup_dir).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].path, latest.path);
+
+        // Drop the image after the backup, then restore the earlier state.
+        service.db.conn().execute("DELETE FROM images", []).unwrap();
+        drop(service);
+        assert_eq!(image_count(&catalog_path), 0);
+
+        let safety = restore_backup(&latest.path, &catalog_path)
+            .unwrap()
+            .unwrap();
+        assert_eq!(image_count(&catalog_path), 1);
+        assert_eq!(image_count(&safety), 0);
+    }
+
+    #[test]
+    fn interval_schedules() {
+        let now = Utc::now();
+        assert!(BackupSchedule::Weekly.is_due(None, now, false));
+        assert!(!BackupSchedule::Weekly.is_due(Some(now - Duration::days(3)), now, true));
+        assert!(BackupSchedule::Daily.is_due(Some(now - Duration::days(3)), now, false));
+        assert!(!BackupSchedule::Never.is_due(None, now, true));
+    }
+}
@@ -0,0 +1,597 @@
+//! Batch correction of capture times.
+//!
+//! Every mode moves the selected images by one offset, so the intervals between them are
+//! kept: a fixed shift, the offset that puts the earliest image at a given time, the
+//! difference between the time zone the camera clock was set to and the one it was used
+//! in, or the offset that lines the images up with a GPS track's UTC times. Each
+//! correction logs the original times so it can be undone, and can be written to XMP
+//! sidecars as well.
+
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+
+use crate::db::{CaptureTimeChange, CaptureTimeCorrection, Image};
+use crate::services::external_catalog::parse_capture_time;
+use crate::services::gpx::{read_gpx, GpxTrack};
+use crate::services::CatalogService;
+
+/// Photos up to this far outside a track segment still count as taken during it.
+const TRACK_TOLERANCE_MINUTES: i64 = 5;
+/// Time zone offsets tried when syncing to a track, in quarter hours either way.
+const MAX_TRACK_OFFSET_QUARTERS: i64 = 14 * 4;
+
+const DATE_PROPERTIES: [&str; 3] = [
+    "exif:DateTimeOriginal",
+    "xmp:CreateDate",
+    "photoshop:DateCreated",
+];
+
+#[derive(Debug, Clone)]
+pub enum CaptureTimeAdjustment {
+    Shift {
+        seconds: i64,
+    },
+    /// Put the earliest selected image at `time`.
+    SetFirst {
+        time: DateTime<Utc>,
+    },
+    /// The camera clock was set to `camera_minutes` east of UTC while shooting in a
+    /// zone `actual_minutes` east of UTC.
+    TimeZone {
+        camera_minutes: i32,
+        actual_minutes: i32,
+    },
+    SyncToTrack {
+        track: PathBuf,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct CaptureTimeSettings {
+    pub adjustment: CaptureTimeAdjustment,
+    pub write_sidecars: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CaptureTimeReport {
+    /// The logged correction, or `None` when nothing changed.
+    pub correction_id: Option<i64>,
+    pub offset_seconds: i64,
+    pub updated: usize,
+    /// Images with neither a capture time nor a file modification time.
+    pub skipped: Vec<PathBuf>,
+    pub sidecars_written: usize,
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureTimeUndo {
+    pub correction: CaptureTimeCorrection,
+    pub restored: usize,
+    /// Images whose capture time was changed again since, which are left alone.
+    pub changed_since: usize,
+    pub sidecars_written: usize,
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+/// Correct the capture times of `image_ids` in one transaction. Sidecars are written once
+/// it has committed, so a failed correction never leaves shifted times on disk.
+pub fn correct_capture_times(
+    service: &CatalogService,
+    image_ids: &[i64],
+    settings: &CaptureTimeSettings,
+) -> Result<CaptureTimeReport> {
+    let mut report = CaptureTimeReport::default();
+    let mut images = Vec::new();
+    for &id in image_ids {
+        let image = Image::load(&service.db, id)?;
+        match image.captured_at.or(image.file_modified_at) {
+            Some(time) => images.push((image, time)),
+            None => report.skipped.push(PathBuf::from(&image.original_path)),
+        }
+    }
+    let Some(earliest) = images.iter().map(|(_, time)| *time).min() else {
+        return Ok(report);
+    };
+
+    let (offset, description) = match &settings.adjustment {
+        CaptureTimeAdjustment::Shift { seconds } => (*seconds, String::new()),
+        CaptureTimeAdjustment::SetFirst { time } => (
+            (*time - earliest).num_seconds(),
+            format!(" to start at {}", time.format("%Y-%m-%d %H:%M:%S")),
+        ),
+        CaptureTimeAdjustment::TimeZone {
+            camera_minutes,
+            actual_minutes,
+        } => (
+            i64::from(actual_minutes - camera_minutes) * 60,
+            format!(
+                " from UTC{} to UTC{}",
+                format_zone(*camera_minutes),
+                format_zone(*actual_minutes)
+            ),
+        ),
+        CaptureTimeAdjustment::SyncToTrack { track } => {
+            let gpx = read_gpx(track)?;
+            let times: Vec<_> = images.iter().map(|(_, time)| *time).collect();
+            let offset = track_offset(&times, &gpx)
+                .with_context(|| format!("no capture times fall within {}", track.display()))?;
+            let name = track.file_name().unwrap_or(track.as_os_str());
+            (offset, format!(" to match {}", name.to_string_lossy()))
+        }
+    };
+    report.offset_seconds = offset;
+    if offset == 0 {
+        return Ok(report);
+    }
+
+    let tx = service
+        .db
+        .conn()
+        .unchecked_transaction()
+        .context("failed to begin capture time correction")?;
+    let correction_id = CaptureTimeCorrection {
+        id: 0,
+        description: format!(
+            "Shifted {} image(s) by {}{description}",
+            images.len(),
+            format_offset(offset)
+        ),
+        offset_seconds: offset,
+        wrote_sidecars: settings.write_sidecars,
+        created_at: Utc::now(),
+    }
+    .insert(&tx)?;
+    let mut sidecars = Vec::new();
+    for (mut image, time) in images {
+        let corrected = time + Duration::seconds(offset);
+        CaptureTimeChange {
+            correction_id,
+            image_id: image.id,
+            previous_captured_at: image.captured_at,
+            corrected_captured_at: corrected,
+        }
+        .insert(&tx)?;
+        image.captured_at = Some(corrected);
+        image.updated_at = Utc::now();
+        image.update(&tx)?;
+        report.updated += 1;
+        if settings.write_sidecars {
+            sidecars.push((image, corrected));
+        }
+    }
+    tx.commit()
+        .context("failed to commit capture time correction")?;
+    report.correction_id = Some(correction_id);
+
+    for (mut image, time) in sidecars {
+        match update_sidecar(service, &mut image, time) {
+            Ok(()) => report.sidecars_written += 1,
+            Err(err) => report
+                .failed
+                .push((PathBuf::from(&image.original_path), format!("{err:#}"))),
+        }
+    }
+    Ok(report)
+}
+
+/// Restore the times a correction replaced, the most recent one when `correction_id` is
+/// `None`, and forget the correction. Sidecars it wrote are rewritten for images that
+/// had a capture time before.
+pub fn undo_capture_time_correction(
+    service: &CatalogService,
+    correction_id: Option<i64>,
+) -> Result<CaptureTimeUndo> {
+    let correction = match correction_id {
+        Some(id) => CaptureTimeCorrection::load(&service.db, id)?
+            .with_context(|| format!("no capture time correction with id {id}"))?,
+        None => match CaptureTimeCorrection::load_all(&service.db)?
+            .into_iter()
+            .next()
+        {
+            Some(correction) => correction,
+            None => bail!("there is no capture time correction to undo"),
+        },
+    };
+
+    let tx = service
+        .db
+        .conn()
+        .unchecked_transaction()
+        .context("failed to begin undoing capture time correction")?;
+    let mut undo = CaptureTimeUndo {
+        correction: correction.clone(),
+        restored: 0,
+        changed_since: 0,
+        sidecars_written: 0,
+        failed: Vec::new(),
+    };
+    let mut sidecars = Vec::new();
+    for change in CaptureTimeChange::list_for_correction(&tx, correction.id)? {
+        let mut image = Image::load(&tx, change.image_id)?;
+        if image.captured_at != Some(change.corrected_captured_at) {
+            undo.changed_since += 1;
+            continue;
+        }
+        image.captured_at = change.previous_captured_at;
+        image.updated_at = Utc::now();
+        image.update(&tx)?;
+        undo.restored += 1;
+        if let (true, Some(previous)) = (correction.wrote_sidecars, change.previous_captured_at) {
+            sidecars.push((image, previous));
+        }
+    }
+    CaptureTimeCorrection::delete(&tx, correction.id)?;
+    tx.commit()
+        .context("failed to commit undoing capture time correction")?;
+
+    for (mut image, time) in sidecars {
+        match update_sidecar(service, &mut image, time) {
+            Ok(()) => undo.sidecars_written += 1,
+            Err(err) => undo
+                .failed
+                .push((PathBuf::from(&image.original_path), format!("{err:#}"))),
+        }
+    }
+    Ok(undo)
+}
+
+/// Corrections that can be undone, newest first.
+pub fn list_capture_time_corrections(
+    service: &CatalogService,
+) -> Result<Vec<CaptureTimeCorrection>> {
+    CaptureTimeCorrection::load_all(&service.db).context("failed to list capture time corrections")
+}
+
+/// Parse an offset such as `+9h`, `-1h30m`, `2d` or `90s` into seconds.
+pub fn parse_offset(raw: &str) -> Result<i64> {
+    let raw = raw.trim();
+    let (sign, rest) = match raw.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, raw.strip_prefix('+').unwrap_or(raw)),
+    };
+    let mut seconds = 0i64;
+    let mut number = String::new();
+    for c in rest.chars().filter(|c| !c.is_whitespace()) {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c.to_ascii_lowercase() {
+            'd' => 86_400,
+            'h' => 3_600,
+            'm' => 60,
+            's' => 1,
+            _ => bail!("unknown unit {c:?} in offset {raw:?}"),
+        };
+        let value: i64 = number
+            .parse()
+            .with_context(|| format!("expected a number before {c:?} in offset {raw:?}"))?;
+        seconds = value
+            .checked_mul(unit)
+            .and_then(|value| seconds.checked_add(value))
+            .with_context(|| format!("offset {raw:?} is too large"))?;
+        number.clear();
+    }
+    if !number.is_empty() || rest.is_empty() {
+        bail!("expected an offset such as +9h or -1h30m, got {raw:?}");
+    }
+    Ok(sign * seconds)
+}
+
+/// Parse a UTC offset such as `+09:00`, `-5`, `+0530` or `UTC` into minutes east of UTC.
+pub fn parse_zone(raw: &str) -> Result<i32> {
+    let raw = raw.trim();
+    let trimmed = raw
+        .strip_prefix("UTC")
+        .or_else(|| raw.strip_prefix("GMT"))
+        .unwrap_or(raw);
+    if trimmed.is_empty() || trimmed == "Z" {
+        return Ok(0);
+    }
+    let (sign, rest) = match trimmed.chars().next() {
+        Some('-') => (-1, &trimmed[1..]),
+        Some('+') => (1, &trimmed[1..]),
+        _ => (1, trimmed),
+    };
+    let (hours, minutes) = match rest.split_once(':') {
+        Some((hours, minutes)) => (hours, minutes),
+        None if rest.len() == 4 && rest.is_ascii() => rest.split_at(2),
+        None => (rest, "0"),
+    };
+    match (hours.parse::<i32>(), minutes.parse::<i32>()) {
+        (Ok(hours), Ok(minutes)) if hours <= 14 && minutes < 60 => {
+            Ok(sign * (hours * 60 + minutes))
+        }
+        _ => bail!("expected a UTC offset such as +09:00 or -5, got {raw:?}"),
+    }
+}
+
+/// Parse a capture time; times without a zone are taken as stored, in UTC.
+pub fn parse_time(raw: &str) -> Result<DateTime<Utc>> {
+    parse_capture_time(raw)
+        .with_context(|| format!("expected a time such as 2024-05-17 09:30:00, got {raw:?}"))
+}
+
+/// The quarter-hour offset that puts the most `times` within the track's segments,
+/// preferring the smallest offset on ties. `None` when no offset puts any inside.
+pub fn track_offset(times: &[DateTime<Utc>], track: &GpxTrack) -> Option<i64> {
+    let tolerance = Duration::minutes(TRACK_TOLERANCE_MINUTES);
+    let spans: Vec<_> = track
+        .spans()
+        .map(|(start, end)| (start - tolerance, end + tolerance))
+        .collect();
+    (-MAX_TRACK_OFFSET_QUARTERS..=MAX_TRACK_OFFSET_QUARTERS)
+        .map(|quarters| quarters * 15 * 60)
+        .map(|offset| {
+            let inside = times
+                .iter()
+                .map(|time| *time + Duration::seconds(offset))
+                .filter(|time| {
+                    spans
+                        .iter()
+                        .any(|(start, end)| start <= time && time <= end)
+                })
+                .count();
+            (inside, offset)
+        })
+        .filter(|(inside, _)| *inside > 0)
+        .max_by_key(|(inside, offset)| (*inside, -offset.abs()))
+        .map(|(_, offset)| offset)
+}
+
+/// `+9h`, `-1h 30m`, `+2d 45s`.
+pub fn format_offset(seconds: i64) -> String {
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let total = seconds.unsigned_abs();
+    let parts: Vec<String> = [
+        (total / 86_400, 'd'),
+        (total / 3600 % 24, 'h'),
+        (total / 60 % 60, 'm'),
+        (total % 60, 's'),
+    ]
+    .into_iter()
+    .filter(|(value, _)| *value > 0)
+    .map(|(value, unit)| format!("{value}{unit}"))
+    .collect();
+    if parts.is_empty() {
+        "0s".to_string()
+    } else {
+        format!("{sign}{}", parts.join(" "))
+    }
+}
+
+fn format_zone(minutes: i32) -> String {
+    let sign = if minutes < 0 { '-' } else { '+' };
+    let minutes = minutes.unsigned_abs();
+    format!("{sign}{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// The image's sidecar: the recorded one, else `photo.ext.xmp`. Keeping the extension
+/// stops the two halves of a RAW+JPEG pair from sharing, and overwriting, one sidecar.
+fn sidecar_path(image: &Image) -> PathBuf {
+    if let Some(path) = &image.sidecar_path {
+        return PathBuf::from(path);
+    }
+    let mut appended = image.original_path.clone();
+    appended.push_str(".xmp");
+    PathBuf::from(appended)
+}
+
+/// Write `time` into the image's XMP sidecar, creating one when there is none, and
+/// record the sidecar on `image`. Capture times are the camera's local clock, so the
+/// stamp carries no zone; with a `Z` other tools would shift it by their own offset.
+fn write_sidecar(image: &mut Image, time: DateTime<Utc>) -> Result<()> {
+    let path = sidecar_path(image);
+    let stamp = time.format("%Y-%m-%dT%H:%M:%S").to_string();
+    let xmp = if path.is_file() {
+        let existing = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read sidecar {}", path.display()))?;
+        set_xmp_capture_time(&existing, &stamp)
+            .with_context(|| format!("failed to update sidecar {}", path.display()))?
+    } else {
+        new_sidecar(&stamp)
+    };
+    fs::write(&path, xmp).with_context(|| format!("failed to write sidecar {}", path.display()))?;
+    image.sidecar_hash = Some(CatalogService::compute_file_hash(&path)?);
+    image.sidecar_path = Some(path.to_string_lossy().into_owned());
+    Ok(())
+}
+
+/// Write `time` to the sidecar of an image whose correction has been committed, and
+/// record the sidecar in the catalog.
+fn update_sidecar(service: &CatalogService, image: &mut Image, time: DateTime<Utc>) -> Result<()> {
+    write_sidecar(image, time)?;
+    image
+        .update(&service.db)
+        .with_context(|| format!("failed to record sidecar of image {}", image.id))
+}
+
+/// Replace every capture date property in `xmp`, in attribute or element form, adding
+/// `exif:DateTimeOriginal` to the first description when none is present.
+fn set_xmp_capture_time(xmp: &str, stamp: &str) -> Result<String> {
+    let mut out = xmp.to_string();
+    let mut found = false;
+    for property in DATE_PROPERTIES {
+        let attribute = format!("{property}=\"");
+        if let Some(start) = out.find(&attribute).map(|at| at + attribute.len()) {
+            if let Some(len) = out[start..].find('"') {
+                out.replace_range(start..start + len, stamp);
+                found = true;
+                continue;
+            }
+        }
+        let open = format!("<{property}>");
+        let close = format!("</{property}>");
+        if let Some(start) = out.find(&open).map(|at| at + open.len()) {
+            if let Some(len) = out[start..].find(&close) {
+                out.replace_range(start..start + len, stamp);
+                found = true;
+            }
+        }
+    }
+    if !found {
+        let at = out
+            .find("<rdf:Description")
+            .context("no rdf:Description element")?
+            + "<rdf:Description".len();
+        let mut attributes = String::new();
+        if !out.contains("xmlns:exif=") {
+            attributes.push_str(" xmlns:exif=\"http://ns.adobe.com/exif/1.0/\"");
+        }
+        attributes.push_str(&format!(" exif:DateTimeOriginal=\"{stamp}\""));
+        out.insert_str(at, &attributes);
+    }
+    Ok(out)
+}
+
+fn new_sidecar(stamp: &str) -> String {
+    format!(
+        "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n \
+         <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n  \
+         <rdf:Description rdf:about=\"\"\n    \
+         xmlns:exif=\"http://ns.adobe.com/exif/1.0/\"\n    \
+         xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\"\n    \
+         exif:DateTimeOriginal=\"{stamp}\"\n    \
+         xmp:CreateDate=\"{stamp}\"/>\n \
+         </rdf:RDF>\n\
+         </x:xmpmeta>\n"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{test_folder, test_image, CatalogDb};
+    use crate::services::gpx::parse_gpx;
+    use chrono::TimeZone;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn captured(service: &CatalogService, id: i64) -> DateTime<Utc> {
+        Image::load(&service.db, id).unwrap().captured_at.unwrap()
+    }
+
+    #[test]
+    fn corrections_shift_every_image_and_can_be_undone() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let dir = std::env::temp_dir().join(format!("catalog_capture_time_{suffix}"));
+        fs::create_dir_all(&dir).unwrap();
+        let service = CatalogService::new(CatalogDb::in_memory().unwrap());
+        let folder_id = test_folder(&dir.to_string_lossy())
+            .insert(&service.db)
+            .unwrap();
+        let start = Utc.with_ymd_and_hms(2024, 5, 17, 8, 0, 0).unwrap();
+        let [a, b] =
+            [("a.jpg", start), ("b.jpg", start + Duration::minutes(10))].map(|(name, at)| {
+                Image {
+                    captured_at: Some(at),
+                    ..test_image(folder_id, &dir.join(name).to_string_lossy())
+                }
+                .insert(&service.db)
+                .unwrap()
+            });
+        // b already has a darktable-style sidecar.
+        fs::write(
+            dir.join("b.jpg.xmp"),
+            "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"><rdf:RDF><rdf:Description rdf:about=\"\" \
+             xmp:Rating=\"3\"><exif:DateTimeOriginal>2024-05-17T08:10:00Z</exif:DateTimeOriginal>\
+             </rdf:Description></rdf:RDF></x:xmpmeta>",
+        )
+        .unwrap();
+
+        let report = correct_capture_times(
+            &service,
+            &[a, b],
+            &CaptureTimeSettings {
+                adjustment: CaptureTimeAdjustment::SetFirst {
+                    time: start + Duration::hours(9),
+                },
+                write_sidecars: true,
+            },
+        )
+        .unwrap();
+        assert_eq!((report.updated, report.sidecars_written), (2, 2));
+        assert_eq!(report.offset_seconds, 9 * 3600);
+        assert_eq!(
+            captured(&service, b),
+            start + Duration::hours(9) + Duration::minutes(10)
+        );
+        let recorded = Image::load(&service.db, a).unwrap().sidecar_path;
+        assert_eq!(recorded.map(PathBuf::from), Some(dir.join("a.jpg.xmp")));
+        let created = fs::read_to_string(dir.join("a.jpg.xmp")).unwrap();
+        assert!(!dir.join("a.xmp").exists());
+        assert!(created.contains("exif:DateTimeOriginal=\"2024-05-17T17:00:00\""));
+        let updated = fs::read_to_string(dir.join("b.jpg.xmp")).unwrap();
+        assert!(updated.contains(">2024-05-17T17:10:00</exif:DateTimeOriginal>"));
+        assert!(updated.contains("xmp:Rating=\"3\""));
+
+        let zone = correct_capture_times(
+            &service,
+            &[a],
+            &CaptureTimeSettings {
+                adjustment: CaptureTimeAdjustment::TimeZone {
+                    camera_minutes: 60,
+                    actual_minutes: -300,
+                },
+                write_sidecars: false,
+            },
+        )
+        .unwrap();
+        assert_eq!(zone.offset_seconds, -6 * 3600);
+
+        // Undoing the first correction leaves `a`, corrected again since, alone.
+        let undo = undo_capture_time_correction(&service, report.correction_id).unwrap();
+        assert_eq!((undo.restored, undo.changed_since), (1, 1));
+        assert_eq!(captured(&service, b), start + Duration::minutes(10));
+        let restored = fs::read_to_string(dir.join("b.jpg.xmp")).unwrap();
+        assert!(restored.contains(">2024-05-17T08:10:00<"));
+
+        let undo = undo_capture_time_correction(&service, None).unwrap();
+        assert_eq!(undo.restored, 1);
+        assert_eq!(captured(&service, a), start + Duration::hours(9));
+        assert!(undo_capture_time_correction(&service, None).is_err());
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn finds_the_time_zone_of_a_track() {
+        let track = parse_gpx(
+            r#"<gpx><trk><trkseg>
+                <trkpt lat="35.0" lon="139.0"><time>2024-05-17T00:00:00Z</time></trkpt>
+                <trkpt lat="35.1" lon="139.1"><time>2024-05-17T03:00:00Z</time></trkpt>
+            </trkseg></trk></gpx>"#,
+        )
+        .unwrap();
+        // The second photo is after the track ends, so the camera clock is ahead.
+        let times = [
+            Utc.with_ymd_and_hms(2024, 5, 17, 2, 30, 0).unwrap(),
+            Utc.with_ymd_and_hms(2024, 5, 17, 4, 0, 0).unwrap(),
+        ];
+        let offset = track_offset(&times, &track).unwrap();
+        // The smallest shift that puts both inside the track.
+        assert_eq!(offset, -3600);
+        assert_eq!(format_offset(offset), "-1h");
+        assert_eq!(format_offset(5430), "+1h 30m 30s");
+        assert_eq!(parse_offset("-1h30m").unwrap(), -5400);
+        assert_eq!(parse_offset("+2d 3s").unwrap(), 172_803);
+        assert!(parse_offset("9").is_err());
+        assert!(parse_offset("9223372036854775807d").is_err());
+        assert!(parse_offset("106751991167300d 106751991167300d").is_err());
+        assert_eq!(parse_zone("+05:30").unwrap(), 330);
+        assert_eq!(parse_zone("UTC-5").unwrap(), -300);
+        assert_eq!(parse_zone("+0930").unwrap(), 570);
+        assert!(parse_zone("+€1").is_err());
+        assert!(track_offset(
+            &[Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap()],
+            &track
+        )
+        .is_none());
+    }
+}
@@ -3,19 +3,29 @@ use std::fs;
 use std::io::{Cursor, Read};
 use std::path::{Path, PathBuf};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use blake3::Hasher;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use image::imageops::{overlay, FilterType};
 use image::{DynamicImage, ImageOutputFormat, RgbaImage};
 use rusqlite::params;
 use serde_json::Value;
 
-use crate::db::search;
+use crate::db::{geo, search, timeline};
 use crate::db::{
-    query_all, query_one, query_optional, to_json, to_rfc3339, to_rfc3339_opt, CatalogDb,
-    Collection, DbHandle, Folder, Image, ImageKeyword, Keyword, Preview, Thumbnail,
+    query_all, query_one, query_optional, to_json, to_rfc3339, to_rfc3339_opt, BoundingBox,
+    CatalogDb, Collection, DatePeriod, DayCount, DbHandle, Folder, GeoCluster, Image,
+    ImageKeyword, ImageLocation, Keyword, PerceptualHash, Preview, Root, Stack, StackImage,
+    Thumbnail, YearCount,
 };
+use crate::services::backup::{self, BackupInfo, BackupSettings};
+use crate::services::export_presets::{self, NamedExportPreset};
+use crate::services::paging::{self, ImagePage, ImageScope, PageCursor};
+use crate::services::perceptual_hash;
+use crate::services::roots::{self, RootRelocation, RootSummary};
+use crate::services::sort::ImageSort;
+use crate::services::verify::{self, RepairSummary, VerifyReport};
+use crate::services::watermarks::{self, WatermarkDefinition};
 
 /// Alias the low-level edit record for service consumers.
 pub type Edits = crate::db::Edit;
@@ -25,22 +35,104 @@ pub type Edits = crate::db::Edit;
 pub struct ImageDetails {
     pub image: Image,
     pub keywords: Vec<String>,
+    pub location: Option<ImageLocation>,
+}
+
+/// Stack membership for a single image, used by grid views to collapse stacks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackMembership {
+    pub stack_id: i64,
+    pub top_image_id: i64,
+    pub size: usize,
+    pub collapsed: bool,
+}
+
+/// A group of visually near-identical images, with a suggested image to keep.
+#[derive(Debug, Clone)]
+pub struct DuplicateCluster {
+    pub images: Vec<Image>,
+    pub keep_image_id: i64,
 }
 
 /// High-level catalog operations that sit above the raw ORM bindings.
 pub struct CatalogService {
     pub db: CatalogDb,
+    /// The user's home folder, which new folders below it are rooted at.
+    home_dir: Option<PathBuf>,
 }
 
 impl CatalogService {
     pub fn new(db: CatalogDb) -> Self {
-        Self { db }
+        Self { db, home_dir: None }
+    }
+
+    /// Root new folders below `home` at it rather than at the filesystem root.
+    pub fn with_home_dir(mut self, home: Option<PathBuf>) -> Self {
+        self.home_dir = home;
+        self
+    }
+
+    /// The home folder new folders are rooted below, if the app passed one in.
+    pub fn home_dir(&self) -> Option<&Path> {
+        self.home_dir.as_deref()
+    }
+
+    /// Root every folder that has none yet, using the home folder the app passed in.
+    /// Apps call this after opening a catalog, so upgraded catalogs get their roots.
+    pub fn assign_folder_roots(&self) -> Result<usize> {
+        let tx = self
+            .db
+            .conn()
+            .unchecked_transaction()
+            .context("failed to begin assigning folder roots")?;
+        let assigned = roots::assign_folder_roots(&tx, self.home_dir.as_deref())?;
+        tx.commit().context("failed to commit folder roots")?;
+        Ok(assigned)
     }
 
     pub fn list_folders(&self) -> Result<Vec<Folder>> {
         Folder::load_all(&self.db).context("failed to list folders")
     }
 
+    pub fn list_roots(&self) -> Result<Vec<RootSummary>> {
+        roots::list_roots(&self.db)
+    }
+
+    pub fn find_root(&self, name_or_id: &str) -> Result<Root> {
+        roots::find_root(&self.db, name_or_id)
+    }
+
+    pub fn add_root(&self, name: &str, path: &Path) -> Result<Root> {
+        let tx = self
+            .db
+            .conn()
+            .unchecked_transaction()
+            .context("failed to begin adding root")?;
+        let root = roots::add_root(&tx, name, path)?;
+        tx.commit().context("failed to commit new root")?;
+        Ok(root)
+    }
+
+    pub fn suggest_root_name(&self, path: &Path) -> Result<String> {
+        roots::suggest_root_name(&self.db, path)
+    }
+
+    pub fn rename_root(&self, root_id: i64, name: &str) -> Result<Root> {
+        roots::rename_root(&self.db, root_id, name)
+    }
+
+    /// Point a root at a new location, moving every folder and image below it.
+    pub fn relocate_root(&self, root_id: i64, new_path: &Path) -> Result<RootRelocation> {
+        let tx = self
+            .db
+            .conn()
+            .unchecked_transaction()
+            .context("failed to begin relocating root")?;
+        let relocation = roots::relocate_root(&tx, root_id, new_path)?;
+        tx.commit().context("failed to commit root relocation")?;
+        Ok(relocation)
+    }
+
     pub fn list_images_in_folder(&self, folder_path: &Path) -> Result<Vec<Image>> {
         let normalized = folder_path.to_string_lossy().to_string();
         let Some(folder) = Folder::find_by_path(&self.db, &normalized)? else {
@@ -51,15 +143,7 @@ impl CatalogService {
     }
 
     pub fn list_images_recursively(&self, folder_path: &Path) -> Result<Vec<Image>> {
-        let normalized = folder_path.to_string_lossy().to_string();
-        let mut prefix = normalized.clone();
-        if !normalized.ends_with(std::path::MAIN_SEPARATOR)
-            && !normalized.ends_with('/')
-            && !normalized.ends_with('\\')
-        {
-            prefix.push(std::path::MAIN_SEPARATOR);
-        }
-        prefix.push('%');
+        let (normalized, prefix) = paging::folder_patterns(folder_path);
 
         query_all(
             &self.db,
@@ -96,23 +180,34 @@ impl CatalogService {
         .context("failed to list all photos")
     }
 
+    /// Up to `limit` images of `scope` in `sort` order, continuing after `after`. Pass the
+    /// returned page's `next` cursor to fetch the page that follows.
+    pub fn list_images_page(
+        &self,
+        scope: &ImageScope,
+        sort: &ImageSort,
+        after: Option<&PageCursor>,
+        limit: usize,
+    ) -> Result<ImagePage> {
+        paging::images_page(&self.db, scope, sort, after, limit)
+    }
+
+    pub fn count_images_in(&self, scope: &ImageScope) -> Result<usize> {
+        paging::count_images(&self.db, scope)
+    }
+
     pub fn last_import_timestamp(&self) -> Result<Option<DateTime<Utc>>> {
-        query_one(
-            &self.db,
-            "SELECT MAX(imported_at) FROM images",
-            [],
-            |row| {
-                let raw: Option<String> = row.get(0)?;
-                if let Some(raw) = raw {
-                    let parsed = DateTime::parse_from_rfc3339(&raw)
-                        .map(|dt| dt.with_timezone(&Utc))
-                        .with_context(|| format!("failed to parse imported_at timestamp {raw}"))?;
-                    Ok(Some(parsed))
-                } else {
-                    Ok(None)
-                }
-            },
-        )
+        query_one(&self.db, "SELECT MAX(imported_at) FROM images", [], |row| {
+            let raw: Option<String> = row.get(0)?;
+            if let Some(raw) = raw {
+                let parsed = DateTime::parse_from_rfc3339(&raw)
+                    .map(|dt| dt.with_timezone(&Utc))
+                    .with_context(|| format!("failed to parse imported_at timestamp {raw}"))?;
+                Ok(Some(parsed))
+            } else {
+                Ok(None)
+            }
+        })
         .context("failed to read last import timestamp")
     }
 
@@ -157,7 +252,14 @@ impl CatalogService {
             .map(|k| k.keyword)
             .collect();
 
-        Ok(ImageDetails { image, keywords })
+        let location =
+            ImageLocation::load(&self.db, image_id).context("failed to load location for image")?;
+
+        Ok(ImageDetails {
+            image,
+            keywords,
+            location,
+        })
     }
 
     pub fn update_keywords(&self, image_id: i64, keywords: &[String]) -> Result<()> {
@@ -252,6 +354,30 @@ impl CatalogService {
             None => self.scan_raw_metadata(path)?,
         };
 
+        let image = Image {
+            filesize: Some(metadata.len() as i64),
+            file_hash: Some(file_hash),
+            file_modified_at: Self::modified_time(&metadata),
+            metadata_json,
+            ..Self::new_image_record(folder.id, path, imported_at)?
+        };
+
+        let id = image.insert(&self.db)?;
+        let mut saved = image;
+        saved.id = id;
+        Ok(saved)
+    }
+
+    /// Catalog an image whose file is not reachable right now, such as one on a
+    /// disconnected drive. File size and hash stay empty until the file is seen again.
+    pub fn register_offline_image(&self, path: &Path, imported_at: DateTime<Utc>) -> Result<Image> {
+        let folder = self.ensure_folder(&Self::parent_path(path))?;
+        let image = Self::new_image_record(folder.id, path, imported_at)?;
+        let id = image.insert(&self.db)?;
+        Ok(Image { id, ..image })
+    }
+
+    fn new_image_record(folder_id: i64, path: &Path, imported_at: DateTime<Utc>) -> Result<Image> {
         let filename = path
             .file_name()
             .and_then(|s| s.to_str())
@@ -259,16 +385,16 @@ impl CatalogService {
             .to_string();
 
         let now = Utc::now();
-        let image = Image {
+        Ok(Image {
             id: 0,
-            folder_id: folder.id,
+            folder_id,
             filename,
             original_path: path.to_string_lossy().to_string(),
             sidecar_path: None,
             sidecar_hash: None,
-            filesize: Some(metadata.len() as i64),
-            file_hash: Some(file_hash),
-            file_modified_at: Self::modified_time(&metadata),
+            filesize: None,
+            file_hash: None,
+            file_modified_at: None,
             imported_at,
             captured_at: None,
             camera_make: None,
@@ -285,15 +411,18 @@ impl CatalogService {
             rating: None,
             flag: None,
             color_label: None,
-            metadata_json,
+            metadata_json: None,
             created_at: now,
             updated_at: now,
-        };
+        })
+    }
 
-        let id = image.insert(&self.db)?;
-        let mut saved = image;
-        saved.id = id;
-        Ok(saved)
+    pub fn load_camera_serial(&self, image_id: i64) -> Result<Option<String>> {
+        Image::load_camera_serial(&self.db, image_id)
+    }
+
+    pub fn load_edits(&self, image_id: i64) -> Result<Option<Edits>> {
+        Edits::load_for_image(&self.db, image_id)
     }
 
     pub fn apply_edits(&self, image_id: i64, edits: Edits) -> Result<()> {
@@ -406,6 +535,10 @@ impl CatalogService {
         Ok(())
     }
 
+    pub fn list_collections(&self) -> Result<Vec<Collection>> {
+        Collection::load_all(&self.db).context("failed to list collections")
+    }
+
     pub fn list_images_in_collection(&self, collection_id: i64) -> Result<Vec<Image>> {
         Collection::list_images(&self.db, collection_id)
             .with_context(|| format!("failed to list images for collection {collection_id}"))
@@ -434,10 +567,315 @@ impl CatalogService {
         })
     }
 
+    /// Set a collection's manual order: `image_ids` first, in that order, then the members
+    /// not listed in the order they had.
+    pub fn reorder_collection(&self, collection_id: i64, image_ids: &[i64]) -> Result<()> {
+        let tx = self
+            .db
+            .conn()
+            .unchecked_transaction()
+            .context("failed to begin reordering collection")?;
+        let members: Vec<i64> = query_all(
+            &tx,
+            "SELECT image_id FROM collection_images
+             WHERE collection_id = ?1
+             ORDER BY position, image_id",
+            params![collection_id],
+            |row| Ok(row.get(0)?),
+        )
+        .with_context(|| format!("failed to list members of collection {collection_id}"))?;
+        let known: HashSet<i64> = members.iter().copied().collect();
+        if let Some(stray) = image_ids.iter().find(|id| !known.contains(id)) {
+            bail!("image {stray} is not in collection {collection_id}");
+        }
+
+        let mut placed = HashSet::new();
+        let order: Vec<i64> = image_ids
+            .iter()
+            .chain(&members)
+            .copied()
+            .filter(|id| placed.insert(*id))
+            .collect();
+        for (index, image_id) in order.iter().enumerate() {
+            tx.execute(
+                "UPDATE collection_images SET position = ?1
+                 WHERE collection_id = ?2 AND image_id = ?3",
+                params![index as i64 + 1, collection_id, image_id],
+            )
+            .with_context(|| {
+                format!("failed to move image {image_id} in collection {collection_id}")
+            })?;
+        }
+        tx.commit().context("failed to commit collection order")
+    }
+
+    /// Group images into a new collapsed stack. Images that already belong to a stack bring
+    /// their whole stack along, so stacking a collapsed stack with loose frames merges them.
+    pub fn create_stack(&self, image_ids: &[i64], top_image_id: Option<i64>) -> Result<Stack> {
+        let tx = self
+            .db
+            .conn()
+            .unchecked_transaction()
+            .context("failed to begin stack transaction")?;
+        let stack = Self::create_stack_in(&tx, image_ids, top_image_id)?;
+        tx.commit().context("failed to commit stack")?;
+        Ok(stack)
+    }
+
+    /// Dissolve a stack, leaving its images in place.
+    pub fn unstack(&self, stack_id: i64) -> Result<()> {
+        Stack::delete(&self.db, stack_id)
+            .with_context(|| format!("failed to unstack stack_id={stack_id}"))
+    }
+
+    /// Pull individual images out of whatever stacks they belong to. Stacks left with a single
+    /// image are dissolved and stacks that lose their top image promote the next member.
+    pub fn remove_from_stack(&self, image_ids: &[i64]) -> Result<()> {
+        let tx = self
+            .db
+            .conn()
+            .unchecked_transaction()
+            .context("failed to begin stack transaction")?;
+        let mut touched = Vec::new();
+        for &image_id in image_ids {
+            if let Some(stack) = Stack::find_for_image(&tx, image_id)? {
+                StackImage::delete(&tx, stack.id, image_id)?;
+                if !touched.contains(&stack.id) {
+                    touched.push(stack.id);
+                }
+            }
+        }
+
+        for stack_id in touched {
+            let members = Stack::list_images(&tx, stack_id)?;
+            if members.len() < 2 {
+                Stack::delete(&tx, stack_id)?;
+                continue;
+            }
+            let mut stack = Stack::load(&tx, stack_id)?;
+            if !members.iter().any(|img| Some(img.id) == stack.top_image_id) {
+                stack.top_image_id = Some(members[0].id);
+                stack.updated_at = Utc::now();
+                stack.update(&tx)?;
+            }
+        }
+
+        tx.commit().context("failed to commit stack removal")?;
+        Ok(())
+    }
+
+    pub fn set_stack_top(&self, stack_id: i64, image_id: i64) -> Result<()> {
+        StackImage::load(&self.db, stack_id, image_id)
+            .with_context(|| format!("image {image_id} is not a member of stack {stack_id}"))?;
+        let mut stack = Stack::load(&self.db, stack_id)?;
+        stack.top_image_id = Some(image_id);
+        stack.updated_at = Utc::now();
+        stack.update(&self.db)
+    }
+
+    pub fn set_stack_collapsed(&self, stack_id: i64, collapsed: bool) -> Result<()> {
+        self.db
+            .execute(
+                "UPDATE stacks SET collapsed = ?1, updated_at = ?2 WHERE id = ?3",
+                params![collapsed, to_rfc3339(Utc::now()), stack_id],
+            )
+            .with_context(|| format!("failed to update collapsed state for stack_id={stack_id}"))?;
+        Ok(())
+    }
+
+    pub fn list_stack_images(&self, stack_id: i64) -> Result<Vec<Image>> {
+        Stack::list_images(&self.db, stack_id)
+            .with_context(|| format!("failed to list images for stack {stack_id}"))
+    }
+
+    /// Map every stacked image to its stack, resolving a missing top to the first member.
+    pub fn stack_memberships(&self) -> Result<HashMap<i64, StackMembership>> {
+        let rows: Vec<(i64, StackMembership)> = query_all(
+            &self.db,
+            "SELECT
+                si.image_id,
+                s.id,
+                COALESCE(
+                    s.top_image_id,
+                    (SELECT f.image_id FROM stack_images f
+                     WHERE f.stack_id = s.id ORDER BY f.position LIMIT 1)
+                ),
+                (SELECT COUNT(*) FROM stack_images c WHERE c.stack_id = s.id),
+                s.collapsed
+             FROM stack_images si
+             INNER JOIN stacks s ON s.id = si.stack_id",
+            [],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    StackMembership {
+                        stack_id: row.get(1)?,
+                        top_image_id: row.get(2)?,
+                        size: row.get::<_, i64>(3)? as usize,
+                        collapsed: row.get(4)?,
+                    },
+                ))
+            },
+        )
+        .context("failed to load stack memberships")?;
+        Ok(rows.into_iter().collect())
+    }
+
+    /// Stack runs of images whose consecutive `captured_at` values are at most `max_gap` apart.
+    ///
+    /// Images without a capture time or already in a stack are left alone so manual stacks
+    /// survive re-running the operation. The earliest frame of each run becomes the top.
+    pub fn auto_stack_by_capture_gap(
+        &self,
+        image_ids: &[i64],
+        max_gap: Duration,
+    ) -> Result<Vec<Stack>> {
+        let tx = self
+            .db
+            .conn()
+            .unchecked_transaction()
+            .context("failed to begin stack transaction")?;
+
+        let mut candidates: Vec<(DateTime<Utc>, i64)> = Vec::new();
+        for &image_id in image_ids {
+            let image = Image::load(&tx, image_id)?;
+            let Some(captured_at) = image.captured_at else {
+                continue;
+            };
+            if Stack::find_for_image(&tx, image_id)?.is_none() {
+                candidates.push((captured_at, image_id));
+            }
+        }
+        candidates.sort();
+        candidates.dedup_by_key(|(_, id)| *id);
+
+        let mut runs: Vec<Vec<i64>> = Vec::new();
+        let mut previous: Option<DateTime<Utc>> = None;
+        for (captured_at, image_id) in candidates {
+            match (previous, runs.last_mut()) {
+                (Some(prev), Some(run)) if captured_at - prev <= max_gap => run.push(image_id),
+                _ => runs.push(vec![image_id]),
+            }
+            previous = Some(captured_at);
+        }
+
+        let mut stacks = Vec::new();
+        for run in runs.into_iter().filter(|run| run.len() > 1) {
+            stacks.push(Self::create_stack_in(&tx, &run, None)?);
+        }
+
+        tx.commit().context("failed to commit auto-stack")?;
+        Ok(stacks)
+    }
+
+    fn create_stack_in<H: DbHandle>(
+        db: &H,
+        image_ids: &[i64],
+        top_image_id: Option<i64>,
+    ) -> Result<Stack> {
+        let mut members: Vec<i64> = Vec::new();
+        let mut absorbed: Vec<i64> = Vec::new();
+        for &image_id in image_ids {
+            match Stack::find_for_image(db, image_id)? {
+                Some(existing) if !absorbed.contains(&existing.id) => {
+                    absorbed.push(existing.id);
+                    for img in Stack::list_images(db, existing.id)? {
+                        if !members.contains(&img.id) {
+                            members.push(img.id);
+                        }
+                    }
+                }
+                Some(_) => {}
+                None => {
+                    if !members.contains(&image_id) {
+                        members.push(image_id);
+                    }
+                }
+            }
+        }
+
+        if members.len() < 2 {
+            bail!("a stack needs at least two images");
+        }
+        let top = match top_image_id {
+            Some(id) if members.contains(&id) => id,
+            Some(id) => bail!("top image {id} is not part of the new stack"),
+            None => members[0],
+        };
+
+        for stack_id in absorbed {
+            Stack::delete(db, stack_id)?;
+        }
+
+        let now = Utc::now();
+        let stack = Stack {
+            id: 0,
+            top_image_id: Some(top),
+            collapsed: true,
+            created_at: now,
+            updated_at: now,
+        };
+        let id = stack.insert(db)?;
+        for image_id in members {
+            Stack::add_image(db, id, image_id)?;
+        }
+        Ok(Stack { id, ..stack })
+    }
+
     pub fn search(&self, query: &str) -> Result<Vec<Image>> {
         search::search_images(&self.db, query).context("failed to run image search")
     }
 
+    /// Images positioned inside `bounds`, by capture time.
+    pub fn images_in_bounds(&self, bounds: &BoundingBox) -> Result<Vec<Image>> {
+        geo::images_in_bounds(&self.db, bounds).context("failed to search images by area")
+    }
+
+    /// Images within `radius_km` of a point with their distance in kilometres, nearest first.
+    pub fn images_near(
+        &self,
+        latitude: f64,
+        longitude: f64,
+        radius_km: f64,
+    ) -> Result<Vec<(Image, f64)>> {
+        geo::images_within_radius(&self.db, latitude, longitude, radius_km)
+            .context("failed to search images by distance")
+    }
+
+    /// Image counts per map grid cell inside `bounds` at `zoom`.
+    pub fn cluster_images(&self, bounds: &BoundingBox, zoom: u8) -> Result<Vec<GeoCluster>> {
+        geo::cluster_images(&self.db, bounds, zoom).context("failed to cluster images")
+    }
+
+    /// Image counts per day taken, falling back to the file's modification time.
+    pub fn count_images_per_day(&self) -> Result<Vec<DayCount>> {
+        timeline::count_images_per_day(&self.db).context("failed to count images per day")
+    }
+
+    /// The years, months and days images were taken, with their image counts.
+    pub fn date_hierarchy(&self) -> Result<Vec<YearCount>> {
+        Ok(timeline::date_hierarchy(&self.count_images_per_day()?))
+    }
+
+    /// Images taken during `period`, by capture time.
+    pub fn list_images_taken_in(&self, period: &DatePeriod) -> Result<Vec<Image>> {
+        timeline::images_taken_in(&self.db, period)
+            .with_context(|| format!("failed to list images taken in {period}"))
+    }
+
+    /// Rebuild the full-text indexes and the spatial index.
+    pub fn rebuild_search_index(&self) -> Result<()> {
+        search::rebuild_fts(&self.db).context("failed to rebuild search index")?;
+        geo::rebuild_spatial_index(&self.db).context("failed to rebuild spatial index")
+    }
+
+    pub fn vacuum(&self) -> Result<()> {
+        self.db
+            .conn()
+            .execute_batch("PRAGMA optimize; VACUUM;")
+            .context("failed to vacuum catalog")
+    }
+
     pub fn count_images(&self) -> Result<i64> {
         query_one(&self.db, "SELECT COUNT(*) FROM images", [], |row| {
             Ok(row.get::<_, i64>(0)?)
@@ -563,6 +1001,7 @@ impl CatalogService {
                 let thumb_1024 = Self::thumbnail_bytes(&img, 1024)
                     .context("failed to encode 1024px thumbnail")?;
 
+                self.update_perceptual_hash(image_id, &thumb_256)?;
                 let thumb = self.upsert_thumbnail(image_id, Some(thumb_256), Some(thumb_1024))?;
                 Ok(Some(thumb))
             }
@@ -573,6 +1012,207 @@ impl CatalogService {
         }
     }
 
+    /// Hash an encoded 256px thumbnail and store the result for near-duplicate lookups.
+    pub fn update_perceptual_hash(
+        &self,
+        image_id: i64,
+        thumb_256: &[u8],
+    ) -> Result<PerceptualHash> {
+        let img = image::load_from_memory(thumb_256)
+            .with_context(|| format!("failed to decode thumbnail for image_id={image_id}"))?;
+        let hash = PerceptualHash {
+            image_id,
+            dhash: perceptual_hash::dhash(&img),
+            updated_at: Utc::now(),
+        };
+        hash.upsert(&self.db)?;
+        Ok(hash)
+    }
+
+    /// Hash stored thumbnails that predate perceptual hashing. Returns how many were added.
+    pub fn compute_missing_perceptual_hashes(&self) -> Result<usize> {
+        let pending: Vec<(i64, Vec<u8>)> = query_all(
+            &self.db,
+            "SELECT t.image_id, t.thumb_256
+             FROM thumbnails t
+             LEFT JOIN perceptual_hashes p ON p.image_id = t.image_id
+             WHERE p.image_id IS NULL AND t.thumb_256 IS NOT NULL",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .context("failed to list thumbnails without perceptual hashes")?;
+
+        let mut computed = 0;
+        for (image_id, thumb_256) in pending {
+            match self.update_perceptual_hash(image_id, &thumb_256) {
+                Ok(_) => computed += 1,
+                Err(err) => eprintln!("Perceptual hash failed for image {image_id}: {err:#}"),
+            }
+        }
+        Ok(computed)
+    }
+
+    /// Cluster images whose perceptual hashes differ by at most `max_distance` bits.
+    ///
+    /// Clusters are transitive, so A~B and B~C groups all three. The suggested keeper is
+    /// the highest rated image, then the largest file, then the first imported.
+    pub fn find_near_duplicates(&self, max_distance: u32) -> Result<Vec<DuplicateCluster>> {
+        self.compute_missing_perceptual_hashes()?;
+
+        let entries: Vec<(i64, u64)> = PerceptualHash::load_all(&self.db)?
+            .into_iter()
+            .map(|hash| (hash.image_id, hash.dhash))
+            .collect();
+
+        let mut clusters = Vec::new();
+        for ids in perceptual_hash::cluster_by_distance(&entries, max_distance) {
+            let images = ids
+                .iter()
+                .map(|&id| Image::load(&self.db, id))
+                .collect::<Result<Vec<_>>>()?;
+            let keep_image_id = images
+                .iter()
+                .max_by(|a, b| {
+                    a.rating
+                        .unwrap_or(0)
+                        .cmp(&b.rating.unwrap_or(0))
+                        .then(a.filesize.unwrap_or(0).cmp(&b.filesize.unwrap_or(0)))
+                        .then(b.id.cmp(&a.id))
+                })
+                .map(|image| image.id)
+                .unwrap_or(ids[0]);
+            clusters.push(DuplicateCluster {
+                images,
+                keep_image_id,
+            });
+        }
+        Ok(clusters)
+    }
+
+    /// Write a verified, optionally compressed backup of the catalog into `backup_dir`.
+    pub fn create_backup(&self, backup_dir: &Path, compress: bool) -> Result<BackupInfo> {
+        backup::write_backup(self.db.conn(), backup_dir, compress)
+    }
+
+    /// Back up when `settings.schedule` says a backup is due, then rotate old backups.
+    pub fn backup_if_due(
+        &self,
+        backup_dir: &Path,
+        settings: &BackupSettings,
+        on_exit: bool,
+    ) -> Result<Option<BackupInfo>> {
+        let last_backup = backup::list_backups(backup_dir)?
+            .first()
+            .map(|info| info.created_at);
+        if !settings.schedule.is_due(last_backup, Utc::now(), on_exit) {
+            return Ok(None);
+        }
+
+        let info = self.create_backup(backup_dir, settings.compress)?;
+        backup::rotate_backups(backup_dir, settings.keep)?;
+        Ok(Some(info))
+    }
+
+    /// Check the catalog for corruption, dangling references, stale search indexes and bad JSON.
+    pub fn verify_catalog(&self) -> Result<VerifyReport> {
+        verify::verify_catalog(self.db.conn())
+    }
+
+    /// Apply the repairs attached to a report's findings.
+    pub fn repair_catalog(&self, report: &VerifyReport) -> Result<RepairSummary> {
+        let summary = verify::repair_findings(&self.db, &report.findings)?;
+        // Folders recreated for relinked images are rooted like any other new folder.
+        self.assign_folder_roots()?;
+        Ok(summary)
+    }
+
+    pub fn list_export_presets(&self) -> Result<Vec<NamedExportPreset>> {
+        export_presets::list_presets(&self.db)
+    }
+
+    pub fn find_export_preset(&self, name: &str) -> Result<Option<NamedExportPreset>> {
+        export_presets::find_preset(&self.db, name)
+    }
+
+    /// Store `preset`, replacing any preset with the same name.
+    pub fn save_export_preset(&self, preset: &NamedExportPreset) -> Result<()> {
+        export_presets::save_preset(&self.db, preset)
+    }
+
+    pub fn delete_export_preset(&self, name: &str) -> Result<bool> {
+        export_presets::delete_preset(&self.db, name)
+    }
+
+    /// Load every preset in a shared JSON file, returning the imported names. Watermarks
+    /// bundled with the presets are saved first, replacing any with the same name.
+    pub fn import_export_presets(&self, path: &Path) -> Result<Vec<String>> {
+        let (presets, bundled) = export_presets::read_preset_file(path)?;
+        let tx = self
+            .db
+            .conn()
+            .unchecked_transaction()
+            .context("failed to begin preset import")?;
+        for watermark in &bundled {
+            watermarks::save_watermark(&tx, watermark)?;
+        }
+        for preset in &presets {
+            export_presets::save_preset(&tx, preset)?;
+        }
+        tx.commit().context("failed to commit preset import")?;
+        Ok(presets.into_iter().map(|preset| preset.name).collect())
+    }
+
+    /// Write the named presets (all of them when `names` is empty) to a shared JSON file.
+    pub fn write_export_presets(&self, path: &Path, names: &[String]) -> Result<usize> {
+        let mut presets = self.list_export_presets()?;
+        if !names.is_empty() {
+            for name in names {
+                if !presets
+                    .iter()
+                    .any(|p| p.name.eq_ignore_ascii_case(name.trim()))
+                {
+                    bail!("no export preset named {name}");
+                }
+            }
+            presets.retain(|p| names.iter().any(|n| p.name.eq_ignore_ascii_case(n.trim())));
+        }
+        let mut bundled: Vec<WatermarkDefinition> = Vec::new();
+        for name in presets
+            .iter()
+            .filter_map(|p| p.settings.watermark.as_deref())
+        {
+            if bundled
+                .iter()
+                .any(|w| w.name.eq_ignore_ascii_case(name.trim()))
+            {
+                continue;
+            }
+            if let Some(watermark) = watermarks::find_watermark(&self.db, name)? {
+                bundled.push(watermark);
+            }
+        }
+        export_presets::write_preset_file(path, &presets, &bundled)?;
+        Ok(presets.len())
+    }
+
+    pub fn list_watermarks(&self) -> Result<Vec<WatermarkDefinition>> {
+        watermarks::list_watermarks(&self.db)
+    }
+
+    pub fn find_watermark(&self, name: &str) -> Result<Option<WatermarkDefinition>> {
+        watermarks::find_watermark(&self.db, name)
+    }
+
+    /// Store `definition`, replacing any watermark with the same name.
+    pub fn save_watermark(&self, definition: &WatermarkDefinition) -> Result<()> {
+        watermarks::save_watermark(&self.db, definition)
+    }
+
+    /// Delete a watermark; fails while an export preset still references it.
+    pub fn delete_watermark(&self, name: &str) -> Result<bool> {
+        watermarks::delete_watermark(&self.db, name)
+    }
+
     /// Placeholder for future RAW/sidecar parsing.
     pub fn scan_raw_metadata(&self, _path: &Path) -> Result<Option<Value>> {
         // TODO: Plug in RAW parsers (cr2/nef/raf/arw) and surface metadata here.
@@ -607,15 +1247,18 @@ impl CatalogService {
             return Ok(existing);
         }
 
+        let (root, relative) = Root::resolve(&self.db, path, self.home_dir.as_deref())?;
         let now = Utc::now();
         let folder = Folder {
             id: 0,
             path: path_str.clone(),
+            root_id: Some(root.id),
+            relative_path: Some(relative),
             created_at: now,
             updated_at: now,
         };
         let id = folder.insert(&self.db)?;
-        Ok(Folder { id, ..folder })
+        Folder::load(&self.db, id)
     }
 
     pub fn compute_file_hash(path: &Path) -> Result<String> {
@@ -652,14 +1295,15 @@ impl CatalogService {
     }
 
     fn letterboxed_thumbnail(img: &DynamicImage, max_dim: u32) -> RgbaImage {
-        let resized = img.resize(max_dim, max_dim, FilterType::Lanczos3).to_rgba8();
+        let resized = img
+            .resize(max_dim, max_dim, FilterType::Lanczos3)
+            .to_rgba8();
         let (w, h) = resized.dimensions();
         if w == max_dim && h == max_dim {
             return resized;
         }
 
-        let mut canvas =
-            RgbaImage::from_pixel(max_dim, max_dim, image::Rgba([16, 16, 16, 255]));
+        let mut canvas = RgbaImage::from_pixel(max_dim, max_dim, image::Rgba([16, 16, 16, 255]));
         let offset_x = (max_dim - w) / 2;
         let offset_y = (max_dim - h) / 2;
         overlay(&mut canvas, &resized, offset_x.into(), offset_y.into());
@@ -686,6 +1330,7 @@ impl CatalogService {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::{test_folder, test_image};
     use crate::schema::initialize_schema;
     use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
@@ -727,6 +1372,8 @@ mod tests {
         let folder = Folder {
             id: 0,
             path: "/keywords".into(),
+            root_id: None,
+            relative_path: None,
             created_at: now,
             updated_at: now,
         };
@@ -784,6 +1431,8 @@ mod tests {
         let folder = Folder {
             id: 0,
             path: "/collections".into(),
+            root_id: None,
+            relative_path: None,
             created_at: now,
             updated_at: now,
         };
@@ -840,6 +1489,8 @@ mod tests {
         let folder = Folder {
             id: 0,
             path: "/aggregate".into(),
+            root_id: None,
+            relative_path: None,
             created_at: now,
             updated_at: now,
         };
@@ -902,6 +1553,8 @@ mod tests {
         let folder = Folder {
             id: 0,
             path: "/edits".into(),
+            root_id: None,
+            relative_path: None,
             created_at: now,
             updated_at: now,
         };
@@ -998,6 +1651,8 @@ mod tests {
         let folder = Folder {
             id: 0,
             path: "/list".into(),
+            root_id: None,
+            relative_path: None,
             created_at: now,
             updated_at: now,
         };
@@ -1052,6 +1707,8 @@ mod tests {
         let folder = Folder {
             id: 0,
             path: "/helpers".into(),
+            root_id: None,
+            relative_path: None,
             created_at: now,
             updated_at: now,
         };
@@ -1102,4 +1759,115 @@ mod tests {
         assert!(details.keywords.contains(&"sky".into()));
         assert!(details.keywords.contains(&"mountain".into()));
     }
+
+    #[test]
+    fn manual_and_auto_stacks() {
+        let service = service_with_fresh_db();
+        let now = Utc::now();
+
+        let folder_id = test_folder("/burst").insert(&service.db).unwrap();
+
+        // Two bursts of three frames 200ms apart, separated by a minute, plus one loose frame.
+        let offsets_ms = [0, 200, 400, 60_000, 60_200, 60_400, 300_000];
+        let ids: Vec<i64> = offsets_ms
+            .iter()
+            .enumerate()
+            .map(|(idx, offset)| {
+                Image {
+                    captured_at: Some(now + chrono::Duration::milliseconds(*offset)),
+                    ..test_image(folder_id, &format!("/burst/f{idx}.dng"))
+                }
+                .insert(&service.db)
+                .unwrap()
+            })
+            .collect();
+
+        let stacks = service
+            .auto_stack_by_capture_gap(&ids, chrono::Duration::seconds(1))
+            .unwrap();
+        assert_eq!(stacks.len(), 2);
+        assert_eq!(stacks[0].top_image_id, Some(ids[0]));
+        assert_eq!(service.list_stack_images(stacks[1].id).unwrap().len(), 3);
+
+        let memberships = service.stack_memberships().unwrap();
+        assert_eq!(memberships.len(), 6);
+        assert!(!memberships.contains_key(&ids[6]));
+
+        // Re-running leaves existing stacks untouched.
+        assert!(service
+            .auto_stack_by_capture_gap(&ids, chrono::Duration::seconds(1))
+            .unwrap()
+            .is_empty());
+
+        // Stacking a stacked frame with the loose one merges the whole stack.
+        let merged = service
+            .create_stack(&[ids[3], ids[6]], Some(ids[6]))
+            .unwrap();
+        assert_eq!(service.list_stack_images(merged.id).unwrap().len(), 4);
+        assert_eq!(
+            service.stack_memberships().unwrap()[&ids[4]].top_image_id,
+            ids[6]
+        );
+
+        service.remove_from_stack(&[ids[6]]).unwrap();
+        let memberships = service.stack_memberships().unwrap();
+        assert_eq!(memberships[&ids[4]].top_image_id, ids[3]);
+        assert_eq!(memberships[&ids[4]].size, 3);
+
+        service.set_stack_top(merged.id, ids[5]).unwrap();
+        assert!(service.set_stack_top(merged.id, ids[0]).is_err());
+        service.set_stack_collapsed(merged.id, false).unwrap();
+        assert!(!service.stack_memberships().unwrap()[&ids[5]].collapsed);
+
+        service.unstack(stacks[0].id).unwrap();
+        assert_eq!(service.stack_memberships().unwrap().len(), 3);
+        assert!(service.create_stack(&[ids[0]], None).is_err());
+    }
+
+    #[test]
+    fn near_duplicates_cluster_and_suggest_keeper() {
+        let service = service_with_fresh_db();
+        let dir = write_temp_image("seed.bin").parent().unwrap().to_path_buf();
+
+        let scene = image::RgbImage::from_fn(400, 300, |x, y| {
+            image::Rgb([(x * 255 / 400) as u8, (y * 255 / 300) as u8, 96])
+        });
+        let checker = image::RgbImage::from_fn(400, 300, |x, y| {
+            let v = if (x / 50 + y / 50) % 2 == 0 { 230 } else { 20 };
+            image::Rgb([v, v, v])
+        });
+        let variants = [
+            ("scene.png", DynamicImage::ImageRgb8(scene.clone())),
+            (
+                "scene_small.png",
+                DynamicImage::ImageRgb8(scene).resize(200, 150, FilterType::Triangle),
+            ),
+            ("checker.png", DynamicImage::ImageRgb8(checker)),
+        ];
+
+        let mut ids = Vec::new();
+        for (name, img) in &variants {
+            let path = dir.join(name);
+            img.save(&path).unwrap();
+            let image = service.import_image(&path).unwrap();
+            service
+                .generate_thumbnail(image.id, &path)
+                .unwrap()
+                .unwrap();
+            ids.push(image.id);
+        }
+        assert!(PerceptualHash::load(&service.db, ids[0]).is_ok());
+
+        service.update_rating(ids[1], 4).unwrap();
+        let clusters = service.find_near_duplicates(6).unwrap();
+        assert_eq!(clusters.len(), 1);
+        let mut members: Vec<i64> = clusters[0].images.iter().map(|image| image.id).collect();
+        members.sort_unstable();
+        assert_eq!(members, vec![ids[0], ids[1]]);
+        assert_eq!(clusters[0].keep_image_id, ids[1]);
+
+        // Hashes missing from older catalogs are backfilled from stored thumbnails.
+        PerceptualHash::delete(&service.db, ids[2]).unwrap();
+        assert_eq!(service.compute_missing_perceptual_hashes().unwrap(), 1);
+    }
 }
@@ -0,0 +1,632 @@
+//! Multi-page PDF contact sheets (proof sheets) for a set of images.
+//!
+//! Each page holds a grid of photos with optional captions built from catalog fields.
+//! Photos come from the stored 1024px thumbnails by default, which keeps large sheets
+//! fast; rendering through the engine instead picks up the current edits.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use engine::ImageEngine;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageBuffer, Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+
+use crate::db::Image;
+use crate::services::catalog_service::CatalogService;
+use crate::services::export::{
+    adjustments_from_edit, exif_orientation, ExportCallbacks, ExportProgress,
+};
+use crate::services::pdf::{fit_text, text_width, Font, ImageRef, Page, PdfDocument};
+
+/// Padding color `CatalogService` uses to letterbox thumbnails to a square.
+const THUMBNAIL_PADDING: [u8; 3] = [16, 16, 16];
+const MARGIN: f32 = 36.0;
+const GAP: f32 = 10.0;
+const HEADER_HEIGHT: f32 = 28.0;
+const CAPTION_SIZE: f32 = 7.5;
+const CAPTION_LINE: f32 = 10.0;
+/// Embedded photos carry about two pixels per point, enough for a sharp print.
+const PIXELS_PER_POINT: f32 = 2.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PageSize {
+    #[default]
+    A4,
+    Letter,
+    A3,
+    Legal,
+}
+
+impl PageSize {
+    pub const ALL: [PageSize; 4] = [
+        PageSize::A4,
+        PageSize::Letter,
+        PageSize::A3,
+        PageSize::Legal,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PageSize::A4 => "A4",
+            PageSize::Letter => "Letter",
+            PageSize::A3 => "A3",
+            PageSize::Legal => "Legal",
+        }
+    }
+
+    /// Portrait width and height in points.
+    fn dimensions(self) -> (f32, f32) {
+        match self {
+            PageSize::A4 => (595.28, 841.89),
+            PageSize::Letter => (612.0, 792.0),
+            PageSize::A3 => (841.89, 1190.55),
+            PageSize::Legal => (612.0, 1008.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PageOrientation {
+    #[default]
+    Portrait,
+    Landscape,
+}
+
+/// Which catalog fields are printed under each photo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContactSheetCaptions {
+    pub filename: bool,
+    pub captured_at: bool,
+    pub rating: bool,
+    pub color_label: bool,
+}
+
+impl Default for ContactSheetCaptions {
+    fn default() -> Self {
+        Self {
+            filename: true,
+            captured_at: true,
+            rating: true,
+            color_label: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContactSheetSource {
+    /// Stored 1024px thumbnails, rendering only images that have none.
+    #[default]
+    Thumbnails,
+    /// Full engine renders with the current edits applied.
+    Renders,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContactSheetSettings {
+    /// Printed at the top of every page; left out when empty.
+    pub title: String,
+    pub page_size: PageSize,
+    pub orientation: PageOrientation,
+    pub columns: u32,
+    pub rows: u32,
+    pub captions: ContactSheetCaptions,
+    pub source: ContactSheetSource,
+    /// JPEG quality of the embedded photos, 1-100.
+    pub quality: u8,
+}
+
+impl Default for ContactSheetSettings {
+    fn default() -> Self {
+        Self {
+            title: "Contact Sheet".to_string(),
+            page_size: PageSize::default(),
+            orientation: PageOrientation::default(),
+            columns: 4,
+            rows: 5,
+            captions: ContactSheetCaptions::default(),
+            source: ContactSheetSource::default(),
+            quality: 85,
+        }
+    }
+}
+
+impl ContactSheetSettings {
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=12).contains(&self.columns) || !(1..=16).contains(&self.rows) {
+            bail!("contact sheet grid must be 1-12 columns by 1-16 rows");
+        }
+        if !(1..=100).contains(&self.quality) {
+            bail!("contact sheet quality must be between 1 and 100");
+        }
+        let layout = Layout::new(self);
+        if layout.photo_width < 24.0 || layout.photo_height < 24.0 {
+            bail!(
+                "a {}x{} grid leaves no room for photos on a {} page",
+                self.columns,
+                self.rows,
+                self.page_size.label()
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContactSheetReport {
+    pub path: PathBuf,
+    pub pages: usize,
+    pub images: usize,
+    pub failed: Vec<(i64, String)>,
+    /// Set when the run was canceled; no file is written in that case.
+    pub canceled: bool,
+}
+
+/// Write a contact sheet PDF for `image_ids` to `path`, replacing any existing file.
+///
+/// Images that cannot be loaded are left off the sheet and reported.
+pub fn write_contact_sheet(
+    service: &CatalogService,
+    engine: &ImageEngine,
+    image_ids: &[i64],
+    path: &Path,
+    settings: &ContactSheetSettings,
+    callbacks: ExportCallbacks,
+) -> Result<ContactSheetReport> {
+    settings.validate()?;
+    let mut seen = HashSet::new();
+    let ids: Vec<i64> = image_ids
+        .iter()
+        .copied()
+        .filter(|id| seen.insert(*id))
+        .collect();
+    if ids.is_empty() {
+        bail!("no images to put on the contact sheet");
+    }
+
+    let layout = Layout::new(settings);
+    let max_pixels = (layout.photo_width.max(layout.photo_height) * PIXELS_PER_POINT).ceil() as u32;
+    let title = settings.title.trim();
+    let mut doc = PdfDocument::new((!title.is_empty()).then(|| title.to_string()));
+    let mut cells = Vec::new();
+    let mut failed = Vec::new();
+    let total = ids.len();
+    for (idx, &image_id) in ids.iter().enumerate() {
+        if callbacks.cancel.is_canceled() {
+            return Ok(ContactSheetReport {
+                path: path.to_path_buf(),
+                pages: 0,
+                images: 0,
+                failed,
+                canceled: true,
+            });
+        }
+        if let Some(cb) = &callbacks.progress {
+            cb(ExportProgress {
+                completed: idx,
+                total,
+                message: Some(format!("Placing image {} of {total}", idx + 1)),
+            });
+        }
+
+        match load_photo(service, engine, image_id, settings, max_pixels) {
+            Ok((image, photo)) => {
+                let (width, height) = photo.dimensions();
+                let jpeg = encode_jpeg(&photo, settings.quality)?;
+                cells.push(Cell {
+                    image,
+                    photo: doc.add_jpeg(width, height, jpeg),
+                    aspect: width as f32 / height as f32,
+                });
+            }
+            Err(err) => {
+                let message = format!("{err:#}");
+                if let Some(cb) = &callbacks.on_error {
+                    cb(image_id, message.clone());
+                }
+                failed.push((image_id, message));
+            }
+        }
+    }
+    if cells.is_empty() {
+        bail!("none of the images could be placed on the contact sheet");
+    }
+
+    let per_page = (settings.columns * settings.rows) as usize;
+    let page_count = cells.len().div_ceil(per_page);
+    for (page_idx, chunk) in cells.chunks(per_page).enumerate() {
+        let mut page = Page::default();
+        draw_header(&mut page, &layout, title, page_idx + 1, page_count);
+        for (slot, cell) in chunk.iter().enumerate() {
+            let column = slot as u32 % settings.columns;
+            let row = slot as u32 / settings.columns;
+            draw_cell(&mut page, &layout, &settings.captions, column, row, cell);
+        }
+        doc.add_page(layout.page_width, layout.page_height, page);
+    }
+
+    let pages = doc.page_count();
+    let bytes = doc.finish()?;
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create folder {}", parent.display()))?;
+    }
+    fs::write(path, bytes).with_context(|| format!("failed to write {}", path.display()))?;
+
+    if let Some(cb) = &callbacks.progress {
+        cb(ExportProgress {
+            completed: total,
+            total,
+            message: None,
+        });
+    }
+    Ok(ContactSheetReport {
+        path: path.to_path_buf(),
+        pages,
+        images: cells.len(),
+        failed,
+        canceled: false,
+    })
+}
+
+struct Cell {
+    image: Image,
+    photo: ImageRef,
+    aspect: f32,
+}
+
+/// Page geometry in points; rows are counted from the top of the page.
+struct Layout {
+    page_width: f32,
+    page_height: f32,
+    cell_width: f32,
+    cell_height: f32,
+    photo_width: f32,
+    photo_height: f32,
+    caption_lines: u32,
+}
+
+impl Layout {
+    fn new(settings: &ContactSheetSettings) -> Self {
+        let (short, long) = settings.page_size.dimensions();
+        let (page_width, page_height) = match settings.orientation {
+            PageOrientation::Portrait => (short, long),
+            PageOrientation::Landscape => (long, short),
+        };
+        let columns = settings.columns.max(1) as f32;
+        let rows = settings.rows.max(1) as f32;
+        let cell_width = (page_width - 2.0 * MARGIN - (columns - 1.0) * GAP) / columns;
+        let cell_height = (page_height - 2.0 * MARGIN - HEADER_HEIGHT - (rows - 1.0) * GAP) / rows;
+        let captions = settings.captions;
+        let caption_lines = captions.filename as u32
+            + captions.captured_at as u32
+            + (captions.rating || captions.color_label) as u32;
+        let caption_height = if caption_lines > 0 {
+            caption_lines as f32 * CAPTION_LINE + 3.0
+        } else {
+            0.0
+        };
+        Self {
+            page_width,
+            page_height,
+            cell_width,
+            cell_height,
+            photo_width: cell_width,
+            photo_height: cell_height - caption_height,
+            caption_lines,
+        }
+    }
+
+    /// Bottom-left corner of the cell at `column`, `row`.
+    fn cell_origin(&self, column: u32, row: u32) -> (f32, f32) {
+        let x = MARGIN + column as f32 * (self.cell_width + GAP);
+        let top = self.page_height - MARGIN - HEADER_HEIGHT - row as f32 * (self.cell_height + GAP);
+        (x, top - self.cell_height)
+    }
+}
+
+fn draw_header(page: &mut Page, layout: &Layout, title: &str, number: usize, count: usize) {
+    let baseline = layout.page_height - MARGIN - 14.0;
+    let counter = format!("Page {number} of {count}");
+    let counter_width = text_width(&counter, 9.0);
+    page.fill_rgb([110, 110, 110]);
+    page.text(
+        Font::Regular,
+        9.0,
+        layout.page_width - MARGIN - counter_width,
+        baseline,
+        &counter,
+    );
+    if !title.is_empty() {
+        let available = layout.page_width - 2.0 * MARGIN - counter_width - GAP;
+        page.fill_rgb([20, 20, 20]);
+        page.text(
+            Font::Bold,
+            14.0,
+            MARGIN,
+            baseline,
+            &fit_text(title, 14.0, available),
+        );
+    }
+}
+
+fn draw_cell(
+    page: &mut Page,
+    layout: &Layout,
+    captions: &ContactSheetCaptions,
+    column: u32,
+    row: u32,
+    cell: &Cell,
+) {
+    let (x, y) = layout.cell_origin(column, row);
+    let photo_y = y + layout.cell_height - layout.photo_height;
+    page.fill_rgb([238, 238, 238]);
+    page.rect(x, photo_y, layout.photo_width, layout.photo_height);
+    let box_aspect = layout.photo_width / layout.photo_height;
+    let (width, height) = if cell.aspect >= box_aspect {
+        (layout.photo_width, layout.photo_width / cell.aspect)
+    } else {
+        (layout.photo_height * cell.aspect, layout.photo_height)
+    };
+    page.image(
+        cell.photo,
+        x + (layout.photo_width - width) / 2.0,
+        photo_y + (layout.photo_height - height) / 2.0,
+        width,
+        height,
+    );
+
+    if layout.caption_lines == 0 {
+        return;
+    }
+    let image = &cell.image;
+    let mut baseline = photo_y - CAPTION_LINE;
+    page.fill_rgb([40, 40, 40]);
+    if captions.filename {
+        let name = fit_text(&image.filename, CAPTION_SIZE, layout.cell_width);
+        page.text(Font::Bold, CAPTION_SIZE, x, baseline, &name);
+        baseline -= CAPTION_LINE;
+    }
+    if captions.captured_at {
+        let captured = image
+            .captured_at
+            .map(|at| at.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|| "No capture date".to_string());
+        page.fill_rgb([100, 100, 100]);
+        page.text(Font::Regular, CAPTION_SIZE, x, baseline, &captured);
+        baseline -= CAPTION_LINE;
+    }
+    if captions.rating {
+        let rating = image.rating.unwrap_or(0).clamp(0, 5);
+        for star in 0..5 {
+            page.fill_rgb(if star < rating {
+                [60, 60, 60]
+            } else {
+                [205, 205, 205]
+            });
+            page.polygon(&star_points(
+                x + 3.5 + star as f32 * 8.0,
+                baseline + 2.5,
+                3.5,
+            ));
+        }
+    }
+    if captions.color_label {
+        if let Some(label) = image.color_label.as_deref() {
+            let size = 7.0;
+            page.fill_rgb(label_color(label));
+            page.rect(x + layout.cell_width - size, baseline - 1.0, size, size);
+        }
+    }
+}
+
+/// Five-pointed star centered on (`cx`, `cy`) with outer radius `radius`.
+fn star_points(cx: f32, cy: f32, radius: f32) -> Vec<(f32, f32)> {
+    (0..10)
+        .map(|idx| {
+            let r = if idx % 2 == 0 { radius } else { radius * 0.4 };
+            let angle = std::f32::consts::FRAC_PI_2 + idx as f32 * std::f32::consts::PI / 5.0;
+            (cx + r * angle.cos(), cy + r * angle.sin())
+        })
+        .collect()
+}
+
+/// Matches the label swatches in the Folio grid.
+fn label_color(label: &str) -> [u8; 3] {
+    match label {
+        "red" => [0xef, 0x44, 0x44],
+        "yellow" => [0xf5, 0x9e, 0x0b],
+        "green" => [0x22, 0xc5, 0x5e],
+        "blue" => [0x3b, 0x82, 0xf6],
+        "purple" => [0xa8, 0x55, 0xf7],
+        _ => [0x66, 0x66, 0x66],
+    }
+}
+
+fn load_photo(
+    service: &CatalogService,
+    engine: &ImageEngine,
+    image_id: i64,
+    settings: &ContactSheetSettings,
+    max_pixels: u32,
+) -> Result<(Image, RgbImage)> {
+    let image = service.load_metadata(image_id)?.image;
+    let thumbnail = match settings.source {
+        ContactSheetSource::Thumbnails => service
+            .load_thumbnail(image_id)?
+            .and_then(|thumb| thumb.thumb_1024),
+        ContactSheetSource::Renders => None,
+    };
+    let photo = match thumbnail {
+        Some(bytes) => {
+            let decoded = image::load_from_memory(&bytes)
+                .with_context(|| format!("failed to decode thumbnail for image_id={image_id}"))?;
+            trim_letterbox(decoded.to_rgb8())
+        }
+        None => {
+            let adjustments = service
+                .load_edits(image_id)?
+                .map(|edit| adjustments_from_edit(&edit))
+                .unwrap_or_default();
+            let rendered = engine
+                .render(&image.original_path, &adjustments, exif_orientation(&image))
+                .with_context(|| format!("failed to render {}", image.original_path))?;
+            let buffer: ImageBuffer<Rgb<u16>, Vec<u16>> =
+                ImageBuffer::from_raw(rendered.width, rendered.height, rendered.data)
+                    .ok_or_else(|| anyhow!("engine returned a malformed buffer"))?;
+            DynamicImage::ImageRgb16(buffer).to_rgb8()
+        }
+    };
+    let photo = if photo.width().max(photo.height()) > max_pixels {
+        DynamicImage::ImageRgb8(photo)
+            .resize(max_pixels, max_pixels, FilterType::Triangle)
+            .to_rgb8()
+    } else {
+        photo
+    };
+    Ok((image, photo))
+}
+
+/// Crop the solid bars `CatalogService` adds around non-square thumbnails.
+fn trim_letterbox(thumb: RgbImage) -> RgbImage {
+    let (width, height) = thumb.dimensions();
+    let is_padding = |x: u32, y: u32| thumb.get_pixel(x, y).0 == THUMBNAIL_PADDING;
+    let row_is_padding = |y: u32| (0..width).all(|x| is_padding(x, y));
+    let column_is_padding = |x: u32| (0..height).all(|y| is_padding(x, y));
+
+    let top = (0..height).find(|&y| !row_is_padding(y)).unwrap_or(0);
+    let bottom = (0..height)
+        .rev()
+        .find(|&y| !row_is_padding(y))
+        .unwrap_or(height - 1);
+    let left = (0..width).find(|&x| !column_is_padding(x)).unwrap_or(0);
+    let right = (0..width)
+        .rev()
+        .find(|&x| !column_is_padding(x))
+        .unwrap_or(width - 1);
+    if (top, left, bottom, right) == (0, 0, height - 1, width - 1) || bottom < top || right < left {
+        return thumb;
+    }
+    image::imageops::crop_imm(&thumb, left, top, right - left + 1, bottom - top + 1).to_image()
+}
+
+fn encode_jpeg(photo: &RgbImage, quality: u8) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut out, quality)
+        .encode(
+            photo.as_raw(),
+            photo.width(),
+            photo.height(),
+            image::ColorType::Rgb8,
+        )
+        .context("failed to encode contact sheet photo")?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::CatalogDb;
+    use crate::schema::initialize_schema;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn contact_sheet_spans_pages_with_captions() {
+        let db = CatalogDb::in_memory().unwrap();
+        initialize_schema(db.conn()).unwrap();
+        let service = CatalogService::new(db);
+        let engine = ImageEngine::new();
+
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let dir = std::env::temp_dir().join(format!("catalog_contact_sheet_{suffix}"));
+        fs::create_dir_all(&dir).unwrap();
+        let mut ids = Vec::new();
+        for idx in 0..5u8 {
+            let source = dir.join(format!("photo_{idx}.png"));
+            image::RgbImage::from_pixel(600, 300, Rgb([200, 40 * idx, 90]))
+                .save(&source)
+                .unwrap();
+            let id = service.import_image(&source).unwrap().id;
+            // The last photo has no stored thumbnail and is rendered instead.
+            if idx < 4 {
+                service.generate_thumbnail(id, &source).unwrap();
+            }
+            ids.push(id);
+        }
+        service.update_rating(ids[0], 3).unwrap();
+        service.update_color_label(ids[0], "green").unwrap();
+
+        // Stored thumbnails are letterboxed squares; the sheet uses the photo area only.
+        let thumb = service.load_thumbnail(ids[0]).unwrap().unwrap();
+        let decoded = image::load_from_memory(&thumb.thumb_1024.unwrap()).unwrap();
+        assert_eq!(decoded.width(), decoded.height());
+        assert_eq!(trim_letterbox(decoded.to_rgb8()).dimensions(), (1024, 512));
+
+        let path = dir.join("sheets").join("proof.pdf");
+        let settings = ContactSheetSettings {
+            title: "Proofs".into(),
+            columns: 2,
+            rows: 2,
+            ..ContactSheetSettings::default()
+        };
+        let mut with_missing = ids.clone();
+        with_missing.push(9999);
+        let report = write_contact_sheet(
+            &service,
+            &engine,
+            &with_missing,
+            &path,
+            &settings,
+            ExportCallbacks::default(),
+        )
+        .unwrap();
+        assert_eq!((report.pages, report.images), (2, 5));
+        assert_eq!(report.failed.len(), 1);
+
+        let bytes = fs::read(&path).unwrap();
+        let text = String::from_utf8_lossy(&bytes);
+        assert!(text.starts_with("%PDF-"));
+        assert_eq!(text.matches("/Type /Page ").count(), 2);
+        assert_eq!(text.matches("/Subtype /Image").count(), 5);
+        assert!(text.contains("/Title (Proofs)"));
+
+        let renders = ContactSheetSettings {
+            source: ContactSheetSource::Renders,
+            orientation: PageOrientation::Landscape,
+            page_size: PageSize::Letter,
+            ..settings.clone()
+        };
+        let report = write_contact_sheet(
+            &service,
+            &engine,
+            &ids[..1],
+            &path,
+            &renders,
+            ExportCallbacks::default(),
+        )
+        .unwrap();
+        assert_eq!((report.pages, report.images), (1, 1));
+        assert!(String::from_utf8_lossy(&fs::read(&path).unwrap()).contains("[0 0 792.00 612.00]"));
+
+        let crowded = ContactSheetSettings {
+            columns: 12,
+            rows: 16,
+            page_size: PageSize::Letter,
+            orientation: PageOrientation::Landscape,
+            ..settings
+        };
+        assert!(crowded.validate().is_err());
+
+        fs::remove_dir_all(dir).ok();
+    }
+}
@@ -0,0 +1,402 @@
+//! Reader for darktable libraries (`library.db`).
+//!
+//! Film rolls are directories, so they become folders through the image paths.
+//! Ratings, rejects, color labels, capture metadata and tags are read; tag names live
+//! in the `data.db` next to the library and already use `|` between levels.
+//! darktable has no manual collections, and its processing history is stored as
+//! binary module parameters, so edited images are only counted as unmapped.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use rusqlite::types::Value;
+use rusqlite::Connection;
+
+use crate::db::query_all;
+use crate::services::external_catalog::{
+    open_read_only, parse_capture_time, table_columns, table_exists, unsupported_type,
+    ExternalCatalog, ExternalImage,
+};
+use crate::services::import::has_supported_extension;
+
+/// `flags & RATING_MASK` is the star rating; 6 marked rejects before darktable 3.0.
+const RATING_MASK: i64 = 0x7;
+const LEGACY_REJECT: i64 = 6;
+const REJECTED: i64 = 0x8;
+
+/// Read a darktable library without modifying it.
+pub fn read_darktable_library(library: &Path) -> Result<ExternalCatalog> {
+    let conn = open_read_only(library, "darktable library")?;
+    if !table_exists(&conn, "images")? || !table_exists(&conn, "film_rolls")? {
+        bail!("{} is not a darktable library", library.display());
+    }
+
+    let mut catalog = ExternalCatalog::default();
+    read_images(&conn, &mut catalog)?;
+    let index: HashMap<i64, usize> = catalog
+        .images
+        .iter()
+        .enumerate()
+        .map(|(idx, image)| (image.source_id, idx))
+        .collect();
+
+    if table_exists(&conn, "color_labels")? {
+        read_color_labels(&conn, &mut catalog, &index)?;
+    }
+    if table_exists(&conn, "tagged_images")? {
+        read_tags(&conn, library, &mut catalog, &index)?;
+    }
+    if table_exists(&conn, "history")? {
+        let edited: Vec<i64> = query_all(
+            &conn,
+            "SELECT DISTINCT imgid FROM history WHERE enabled = 1",
+            [],
+            |row| Ok(row.get(0)?),
+        )
+        .context("failed to read darktable history")?;
+        for image_id in edited {
+            if index.contains_key(&image_id) {
+                catalog.note_unmapped("Processing history");
+            }
+        }
+    }
+    Ok(catalog)
+}
+
+fn read_images(conn: &Connection, catalog: &mut ExternalCatalog) -> Result<()> {
+    let columns = table_columns(conn, "images")?;
+    let optional = |name: &str| {
+        if columns.contains(name) {
+            format!("i.{name}")
+        } else {
+            "NULL".to_string()
+        }
+    };
+    // darktable 4.4 moved maker, model and lens names into lookup tables.
+    let interned = |column: &str, id_column: &str, table: &str| -> Result<String> {
+        Ok(if columns.contains(column) {
+            format!("i.{column}")
+        } else if columns.contains(id_column) && table_exists(conn, table)? {
+            format!("(SELECT name FROM {table} WHERE id = i.{id_column})")
+        } else {
+            "NULL".to_string()
+        })
+    };
+    let sql = format!(
+        "SELECT i.id, f.folder, i.filename, i.flags, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}
+         FROM images i
+         JOIN film_rolls f ON f.id = i.film_id
+         ORDER BY i.id",
+        optional("version"),
+        optional("datetime_taken"),
+        interned("maker", "maker_id", "makers")?,
+        interned("model", "model_id", "models")?,
+        interned("lens", "lens_id", "lenses")?,
+        optional("exposure"),
+        optional("aperture"),
+        optional("iso"),
+        optional("focal_length"),
+        optional("latitude"),
+        optional("longitude"),
+    );
+    let rows = query_all(conn, &sql, [], |row| {
+        let folder: String = row.get(1)?;
+        let filename: String = row.get(2)?;
+        Ok((
+            row.get::<_, i64>(0)?,
+            Path::new(&folder).join(filename),
+            row.get::<_, Option<i64>>(3)?.unwrap_or(0),
+            row.get::<_, Option<i64>>(4)?.unwrap_or(0),
+            row.get::<_, Value>(5)?,
+            [
+                row.get::<_, Option<String>>(6)?,
+                row.get::<_, Option<String>>(7)?,
+                row.get::<_, Option<String>>(8)?,
+            ],
+            [
+                row.get::<_, Option<f64>>(9)?,
+                row.get::<_, Option<f64>>(10)?,
+                row.get::<_, Option<f64>>(11)?,
+                row.get::<_, Option<f64>>(12)?,
+                row.get::<_, Option<f64>>(13)?,
+                row.get::<_, Option<f64>>(14)?,
+            ],
+        ))
+    })
+    .context("failed to read darktable images")?;
+
+    for (id, path, flags, version, taken, [make, model, lens], numbers) in rows {
+        let [exposure, aperture, iso, focal_length, latitude, longitude] = numbers;
+        if version > 0 {
+            catalog.note_unmapped("Duplicates");
+            continue;
+        }
+        if !has_supported_extension(&path) {
+            catalog.note_unmapped(unsupported_type(&path));
+            continue;
+        }
+        let rating = flags & RATING_MASK;
+        let rejected = flags & REJECTED != 0 || rating == LEGACY_REJECT;
+        let known = |value: Option<String>| value.filter(|v| !v.trim().is_empty());
+        catalog.images.push(ExternalImage {
+            source_id: id,
+            path,
+            captured_at: parse_taken(taken),
+            camera_make: known(make),
+            camera_model: known(model),
+            lens_model: known(lens),
+            focal_length: focal_length.filter(|f| *f > 0.0),
+            aperture: aperture.filter(|a| *a > 0.0),
+            shutter_speed: exposure.filter(|e| *e > 0.0),
+            iso: iso.map(|iso| iso.round() as i64).filter(|iso| *iso > 0),
+            gps_latitude: latitude.filter(|_| longitude.is_some()),
+            gps_longitude: longitude.filter(|_| latitude.is_some()),
+            rating: (!rejected && (1..=5).contains(&rating)).then_some(rating),
+            flag: rejected.then(|| "rejected".to_string()),
+            ..ExternalImage::default()
+        });
+    }
+    Ok(())
+}
+
+/// Capture times were text before darktable 3.6 and microseconds since 0001-01-01 since.
+fn parse_taken(value: Value) -> Option<DateTime<Utc>> {
+    match value {
+        Value::Integer(micros) if micros > 0 => {
+            let epoch = NaiveDate::from_ymd_opt(1, 1, 1)?.and_hms_opt(0, 0, 0)?;
+            epoch
+                .checked_add_signed(Duration::microseconds(micros))
+                .map(|naive| naive.and_utc())
+        }
+        Value::Text(text) => parse_capture_time(&text),
+        _ => None,
+    }
+}
+
+fn read_color_labels(
+    conn: &Connection,
+    catalog: &mut ExternalCatalog,
+    index: &HashMap<i64, usize>,
+) -> Result<()> {
+    let labels: Vec<(i64, i64)> = query_all(
+        conn,
+        "SELECT imgid, color FROM color_labels ORDER BY imgid, color",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .context("failed to read darktable color labels")?;
+    for (image_id, color) in labels {
+        let Some(image) = index.get(&image_id).map(|idx| &mut catalog.images[*idx]) else {
+            continue;
+        };
+        let name = match color {
+            0 => "red",
+            1 => "yellow",
+            2 => "green",
+            3 => "blue",
+            4 => "purple",
+            _ => continue,
+        };
+        // darktable allows several labels per image; Folio has one, so red beats yellow and so on.
+        if image.color_label.is_some() {
+            catalog.note_unmapped("Additional color labels");
+        } else {
+            image.color_label = Some(name.to_string());
+        }
+    }
+    Ok(())
+}
+
+fn read_tags(
+    conn: &Connection,
+    library: &Path,
+    catalog: &mut ExternalCatalog,
+    index: &HashMap<i64, usize>,
+) -> Result<()> {
+    let names = if table_exists(conn, "tags")? {
+        tag_names(conn)?
+    } else {
+        let data = data_db_path(library);
+        if !data.is_file() {
+            catalog.note_unmapped("Tags (data.db not found next to the library)");
+            return Ok(());
+        }
+        tag_names(&open_read_only(&data, "darktable data.db")?)?
+    };
+
+    let tagged: Vec<(i64, i64)> = query_all(
+        conn,
+        "SELECT imgid, tagid FROM tagged_images ORDER BY imgid, tagid",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .context("failed to read darktable tagged images")?;
+    for (image_id, tag_id) in tagged {
+        let (Some(idx), Some(name)) = (index.get(&image_id), names.get(&tag_id)) else {
+            continue;
+        };
+        catalog.images[*idx].keywords.push(name.clone());
+    }
+    Ok(())
+}
+
+fn data_db_path(library: &Path) -> PathBuf {
+    library.with_file_name("data.db")
+}
+
+/// Tag names by id, leaving out darktable's own bookkeeping tags.
+fn tag_names(conn: &Connection) -> Result<HashMap<i64, String>> {
+    let tags: Vec<(i64, String)> = query_all(conn, "SELECT id, name FROM tags", [], |row| {
+        Ok((row.get(0)?, row.get(1)?))
+    })
+    .context("failed to read darktable tags")?;
+    Ok(tags
+        .into_iter()
+        .filter(|(_, name)| !name.starts_with("darktable|"))
+        .map(|(id, name)| {
+            let levels: Vec<&str> = name
+                .split('|')
+                .map(str::trim)
+                .filter(|level| !level.is_empty())
+                .collect();
+            (id, levels.join("|"))
+        })
+        .filter(|(_, name)| !name.is_empty())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::CatalogDb;
+    use crate::schema::initialize_schema;
+    use crate::services::catalog_service::CatalogService;
+    use crate::services::external_catalog::{
+        migrate_external_catalog, ExternalSource, ImageChangeKind,
+    };
+    use crate::services::import::ImportCallbacks;
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn migrates_darktable_library_with_dry_run() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let dir = std::env::temp_dir().join(format!("catalog_darktable_{suffix}"));
+        let roll = dir.join("2024").join("Coast");
+        fs::create_dir_all(&roll).unwrap();
+        image::RgbImage::new(8, 8).save(roll.join("a.png")).unwrap();
+
+        let library = dir.join("library.db");
+        let conn = Connection::open(&library).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE film_rolls (id INTEGER PRIMARY KEY, access_timestamp INTEGER, folder TEXT);
+             CREATE TABLE images (id INTEGER PRIMARY KEY, group_id INTEGER, film_id INTEGER,
+                 filename TEXT, maker_id INTEGER, model_id INTEGER, lens_id INTEGER, exposure REAL,
+                 aperture REAL, iso REAL, focal_length REAL, datetime_taken INTEGER, flags INTEGER,
+                 longitude REAL, latitude REAL, version INTEGER);
+             CREATE TABLE makers (id INTEGER PRIMARY KEY, name TEXT);
+             CREATE TABLE models (id INTEGER PRIMARY KEY, name TEXT);
+             CREATE TABLE lenses (id INTEGER PRIMARY KEY, name TEXT);
+             CREATE TABLE color_labels (imgid INTEGER, color INTEGER);
+             CREATE TABLE tagged_images (imgid INTEGER, tagid INTEGER, position INTEGER);
+             CREATE TABLE history (imgid INTEGER, num INTEGER, operation TEXT, enabled INTEGER);
+             INSERT INTO makers VALUES (1, 'Nikon');
+             INSERT INTO models VALUES (1, 'Z 6');
+             INSERT INTO lenses VALUES (1, '');
+             -- 2024-05-04 12:30:00 as microseconds since 0001-01-01.
+             INSERT INTO images VALUES
+                 (1, 1, 1, 'a.png', 1, 1, 1, 0.004, 8, 100, 35, 63850422600000000, 3, -1.5, 47.2, 0),
+                 (2, 2, 1, 'b.nef', NULL, NULL, NULL, NULL, NULL, NULL, NULL, NULL, 8 | 2, NULL, NULL, 0),
+                 (3, 1, 1, 'a.png', 1, 1, 1, NULL, NULL, NULL, NULL, NULL, 5, NULL, NULL, 1),
+                 (4, 4, 1, 'c.xmp', NULL, NULL, NULL, NULL, NULL, NULL, NULL, NULL, 0, NULL, NULL, 0);
+             INSERT INTO color_labels VALUES (1, 2), (1, 0), (2, 4);
+             INSERT INTO tagged_images VALUES (1, 10, 0), (1, 11, 0), (2, 12, 0);
+             INSERT INTO history VALUES (1, 0, 'exposure', 1), (2, 0, 'rawprepare', 0);",
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO film_rolls VALUES (1, 0, ?1)",
+            [roll.to_string_lossy()],
+        )
+        .unwrap();
+        let data = Connection::open(dir.join("data.db")).unwrap();
+        data.execute_batch(
+            "CREATE TABLE tags (id INTEGER PRIMARY KEY, name TEXT, synonyms TEXT, flags INTEGER);
+             INSERT INTO tags VALUES (10, 'places|Brittany', NULL, 0),
+                 (11, 'darktable|format|png', NULL, 0), (12, 'seascape', NULL, 0);",
+        )
+        .unwrap();
+
+        let db = CatalogDb::in_memory().unwrap();
+        initialize_schema(db.conn()).unwrap();
+        let service = CatalogService::new(db);
+        let migrate = |dry_run| {
+            migrate_external_catalog(
+                &service,
+                ExternalSource::Darktable,
+                &library,
+                &ImportCallbacks::default(),
+                dry_run,
+            )
+            .unwrap()
+        };
+
+        let preview = migrate(true);
+        assert!(preview.dry_run);
+        assert_eq!((preview.imported, preview.offline.len()), (2, 1));
+        assert_eq!(preview.changes.len(), 2);
+        assert_eq!(preview.changes[0].kind, ImageChangeKind::New);
+        assert!(preview.changes[0]
+            .fields
+            .contains(&"keyword: +places|Brittany".to_string()));
+        assert_eq!(service.count_images().unwrap(), 0);
+        assert!(service.list_folders().unwrap().is_empty());
+
+        let report = migrate(false);
+        assert_eq!((report.imported, report.keywords), (2, 2));
+        for feature in [
+            "Duplicates",
+            "Unsupported file type .xmp",
+            "Additional color labels",
+            "Processing history",
+        ] {
+            assert_eq!(report.unmapped.get(feature), Some(&1), "{feature}");
+        }
+
+        let a = service
+            .find_image_by_original_path(&roll.join("a.png"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(a.rating, Some(3));
+        assert_eq!(a.color_label.as_deref(), Some("red"));
+        assert_eq!(a.camera_make.as_deref(), Some("Nikon"));
+        assert_eq!(a.lens_model, None);
+        assert_eq!(a.shutter_speed, Some(0.004));
+        assert_eq!(
+            a.captured_at.unwrap().to_rfc3339(),
+            "2024-05-04T12:30:00+00:00"
+        );
+        assert_eq!(
+            service.load_metadata(a.id).unwrap().keywords,
+            vec!["places|Brittany"]
+        );
+        let b = service
+            .find_image_by_original_path(&roll.join("b.nef"))
+            .unwrap()
+            .unwrap();
+        assert_eq!((b.rating, b.flag.as_deref()), (None, Some("rejected")));
+        assert_eq!(b.color_label.as_deref(), Some("purple"));
+
+        // Nothing left to change on a second run.
+        let again = migrate(true);
+        assert_eq!((again.imported, again.updated), (0, 2));
+        assert!(again.changes.is_empty());
+
+        fs::remove_dir_all(dir).ok();
+    }
+}
@@ -0,0 +1,466 @@
+//! Reader for digiKam databases (`digikam4.db`).
+//!
+//! Albums are directories below an album root, so they become folders through the
+//! image paths; album categories become collections. Ratings, capture metadata,
+//! positions and the tag tree are read, and digiKam's internal color and pick label
+//! tags are turned into Folio labels and flags.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use rusqlite::Connection;
+
+use crate::db::query_all;
+use crate::services::external_catalog::{
+    open_read_only, parse_capture_time, table_exists, unsupported_type, ExternalCatalog,
+    ExternalCollection, ExternalImage,
+};
+use crate::services::import::has_supported_extension;
+
+const INTERNAL_TAG_PREFIX: &str = "_Digikam_";
+/// `Images.status` of images that are neither hidden, trashed nor obsolete.
+const VISIBLE: i64 = 1;
+/// The category digiKam gives albums that were never categorized.
+const UNCATEGORIZED: &str = "Uncategorized Album";
+
+/// Read a digiKam database without modifying it.
+pub fn read_digikam_database(database: &Path) -> Result<ExternalCatalog> {
+    let conn = open_read_only(database, "digiKam database")?;
+    for table in [
+        "AlbumRoots",
+        "Albums",
+        "Images",
+        "ImageInformation",
+        "ImageMetadata",
+        "ImagePositions",
+    ] {
+        if !table_exists(&conn, table)? {
+            bail!("{} is not a digiKam database", database.display());
+        }
+    }
+
+    let mut catalog = ExternalCatalog::default();
+    let roots = read_album_roots(&conn, &mut catalog)?;
+    let categories = read_images(&conn, &roots, &mut catalog)?;
+    let index: HashMap<i64, usize> = catalog
+        .images
+        .iter()
+        .enumerate()
+        .map(|(idx, image)| (image.source_id, idx))
+        .collect();
+    if table_exists(&conn, "Tags")? && table_exists(&conn, "ImageTags")? {
+        read_tags(&conn, &mut catalog, &index)?;
+    }
+
+    for (position, (name, image_ids)) in categories.into_iter().enumerate() {
+        catalog.collections.push(ExternalCollection {
+            source_id: position as i64 + 1,
+            name,
+            parent: None,
+            image_ids,
+        });
+    }
+    Ok(catalog)
+}
+
+fn read_album_roots(
+    conn: &Connection,
+    catalog: &mut ExternalCatalog,
+) -> Result<HashMap<i64, PathBuf>> {
+    let rows: Vec<(i64, Option<String>, Option<String>)> = query_all(
+        conn,
+        "SELECT id, identifier, specificPath FROM AlbumRoots",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )
+    .context("failed to read digiKam album roots")?;
+    let mut roots = HashMap::new();
+    for (id, identifier, specific) in rows {
+        let specific = specific.unwrap_or_default();
+        let path = match album_root_path(identifier.as_deref().unwrap_or(""), &specific) {
+            Some(path) => path,
+            None => {
+                // Keep the images, cataloged offline under the path digiKam recorded.
+                catalog.note_unmapped("Album roots on volumes that are not mounted");
+                PathBuf::from(&specific)
+            }
+        };
+        roots.insert(id, path);
+    }
+    Ok(roots)
+}
+
+/// Resolve an album root from its volume identifier, such as
+/// `volumeid:?path=%2Fhome%2Fann%2FPictures` or `volumeid:?uuid=1234-ABCD`.
+fn album_root_path(identifier: &str, specific: &str) -> Option<PathBuf> {
+    let query = identifier.split_once('?').map(|(_, q)| q).unwrap_or("");
+    let params: HashMap<&str, String> = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (key, percent_decode(value)))
+        .collect();
+    let base = if let Some(path) = params.get("path").or_else(|| params.get("mountpath")) {
+        PathBuf::from(path)
+    } else {
+        mount_point_for_uuid(params.get("uuid")?)?
+    };
+    let specific = specific.trim_start_matches('/');
+    Some(if specific.is_empty() {
+        base
+    } else {
+        base.join(specific)
+    })
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Where the filesystem with `uuid` is mounted right now; only known on Linux.
+fn mount_point_for_uuid(uuid: &str) -> Option<PathBuf> {
+    let device = fs::canonicalize(Path::new("/dev/disk/by-uuid").join(uuid)).ok()?;
+    let mounts = fs::read_to_string("/proc/mounts").ok()?;
+    mounts.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        let source = fields.next()?;
+        let target = fields.next()?;
+        (fs::canonicalize(source).ok()? == device)
+            .then(|| PathBuf::from(target.replace("\\040", " ")))
+    })
+}
+
+/// Read images and return the members of each album category.
+fn read_images(
+    conn: &Connection,
+    roots: &HashMap<i64, PathBuf>,
+    catalog: &mut ExternalCatalog,
+) -> Result<BTreeMap<String, Vec<i64>>> {
+    let rows = query_all(
+        conn,
+        "SELECT i.id, a.albumRoot, a.relativePath, i.name, a.collection,
+                ii.rating, ii.creationDate, ii.orientation,
+                m.make, m.model, m.lens, m.aperture, m.focalLength, m.exposureTime,
+                m.sensitivity, p.latitudeNumber, p.longitudeNumber, p.altitude
+         FROM Images i
+         JOIN Albums a ON a.id = i.album
+         LEFT JOIN ImageInformation ii ON ii.imageid = i.id
+         LEFT JOIN ImageMetadata m ON m.imageid = i.id
+         LEFT JOIN ImagePositions p ON p.imageid = i.id
+         WHERE i.status = ?1
+         ORDER BY i.id",
+        [VISIBLE],
+        |row| {
+            let root: i64 = row.get(1)?;
+            let relative: String = row.get(2)?;
+            let name: String = row.get(3)?;
+            let mut path = roots.get(&root).cloned().unwrap_or_default();
+            path.extend(relative.split('/').filter(|part| !part.is_empty()));
+            path.push(name);
+            let text = |idx| row.get::<_, Option<String>>(idx);
+            let number = |idx| row.get::<_, Option<f64>>(idx);
+            Ok((
+                ExternalImage {
+                    source_id: row.get(0)?,
+                    path,
+                    rating: row
+                        .get::<_, Option<i64>>(5)?
+                        .filter(|rating| (1..=5).contains(rating)),
+                    captured_at: text(6)?.as_deref().and_then(parse_capture_time),
+                    orientation: row
+                        .get::<_, Option<i64>>(7)?
+                        .filter(|o| (1..=8).contains(o)),
+                    camera_make: text(8)?.filter(|v| !v.trim().is_empty()),
+                    camera_model: text(9)?.filter(|v| !v.trim().is_empty()),
+                    lens_model: text(10)?.filter(|v| !v.trim().is_empty()),
+                    aperture: number(11)?.filter(|v| *v > 0.0),
+                    focal_length: number(12)?.filter(|v| *v > 0.0),
+                    shutter_speed: number(13)?.filter(|v| *v > 0.0),
+                    iso: number(14)?.map(|v| v.round() as i64).filter(|v| *v > 0),
+                    gps_latitude: number(15)?,
+                    gps_longitude: number(16)?,
+                    gps_altitude: number(17)?,
+                    ..ExternalImage::default()
+                },
+                text(4)?,
+            ))
+        },
+    )
+    .context("failed to read digiKam images")?;
+
+    let mut categories: BTreeMap<String, Vec<i64>> = BTreeMap::new();
+    for (image, category) in rows {
+        if !has_supported_extension(&image.path) {
+            catalog.note_unmapped(unsupported_type(&image.path));
+            continue;
+        }
+        if let Some(category) = category
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty() && c != UNCATEGORIZED)
+        {
+            categories
+                .entry(category)
+                .or_default()
+                .push(image.source_id);
+        }
+        catalog.images.push(image);
+    }
+    Ok(categories)
+}
+
+fn read_tags(
+    conn: &Connection,
+    catalog: &mut ExternalCatalog,
+    index: &HashMap<i64, usize>,
+) -> Result<()> {
+    let tree: HashMap<i64, (String, i64)> =
+        query_all(conn, "SELECT id, pid, name FROM Tags", [], |row| {
+            Ok((row.get(0)?, (row.get(2)?, row.get(1)?)))
+        })
+        .context("failed to read digiKam tags")?
+        .into_iter()
+        .collect();
+    // Face recognition keeps placeholder people tags that are not keywords.
+    let hidden: HashSet<i64> = if table_exists(conn, "TagProperties")? {
+        query_all(
+            conn,
+            "SELECT tagid FROM TagProperties
+             WHERE property IN ('internalTag', 'unknownPerson', 'unconfirmedPerson')",
+            [],
+            |row| Ok(row.get(0)?),
+        )
+        .context("failed to read digiKam tag properties")?
+        .into_iter()
+        .collect()
+    } else {
+        HashSet::new()
+    };
+
+    let assignments: Vec<(i64, i64)> = query_all(
+        conn,
+        "SELECT imageid, tagid FROM ImageTags ORDER BY imageid, tagid",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .context("failed to read digiKam image tags")?;
+    for (image_id, tag_id) in assignments {
+        let Some(idx) = index.get(&image_id).copied() else {
+            continue;
+        };
+        let Some(levels) = tag_levels(&tree, tag_id) else {
+            continue;
+        };
+        if levels
+            .first()
+            .is_some_and(|root| root.starts_with(INTERNAL_TAG_PREFIX))
+        {
+            apply_internal_tag(
+                catalog,
+                idx,
+                levels.last().map(String::as_str).unwrap_or(""),
+            );
+        } else if !hidden.contains(&tag_id) {
+            catalog.images[idx].keywords.push(levels.join("|"));
+        }
+    }
+    Ok(())
+}
+
+/// Names from the top of the tree down to `tag`; `None` for a broken tree.
+fn tag_levels(tree: &HashMap<i64, (String, i64)>, tag: i64) -> Option<Vec<String>> {
+    let mut levels = Vec::new();
+    let mut current = tag;
+    for _ in 0..64 {
+        let Some((name, parent)) = tree.get(&current) else {
+            levels.reverse();
+            return (!levels.is_empty()).then_some(levels);
+        };
+        let name = name.trim();
+        // Newer databases have an explicit root tag above the top level.
+        let root = name.starts_with(INTERNAL_TAG_PREFIX) && *parent < 0;
+        if !root && !name.is_empty() {
+            levels.push(name.replace('|', "/"));
+        }
+        current = *parent;
+    }
+    None
+}
+
+fn apply_internal_tag(catalog: &mut ExternalCatalog, idx: usize, name: &str) {
+    let image = &mut catalog.images[idx];
+    if let Some(color) = name.strip_prefix("Color Label ") {
+        let label = match color {
+            "Red" => "red",
+            "Yellow" => "yellow",
+            "Green" => "green",
+            "Blue" => "blue",
+            "Magenta" => "purple",
+            "None" => return,
+            _ => {
+                catalog.note_unmapped("Color labels without a Folio equivalent");
+                return;
+            }
+        };
+        image.color_label = Some(label.to_string());
+    } else if let Some(pick) = name.strip_prefix("Pick Label ") {
+        image.flag = match pick {
+            "Accepted" => Some("picked".to_string()),
+            "Rejected" => Some("rejected".to_string()),
+            "Pending" => {
+                catalog.note_unmapped("Pending pick labels");
+                return;
+            }
+            _ => return,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::CatalogDb;
+    use crate::schema::initialize_schema;
+    use crate::services::catalog_service::CatalogService;
+    use crate::services::external_catalog::{migrate_external_catalog, ExternalSource};
+    use crate::services::import::ImportCallbacks;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn migrates_digikam_database() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let dir = std::env::temp_dir().join(format!("catalog_digikam_{suffix}"));
+        let trip = dir.join("My Pictures").join("Trip");
+        fs::create_dir_all(&trip).unwrap();
+        image::RgbImage::new(8, 8).save(trip.join("a.png")).unwrap();
+
+        let database = dir.join("digikam4.db");
+        let conn = Connection::open(&database).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE AlbumRoots (id INTEGER PRIMARY KEY, label TEXT, status INTEGER,
+                 type INTEGER, identifier TEXT, specificPath TEXT);
+             CREATE TABLE Albums (id INTEGER PRIMARY KEY, albumRoot INTEGER, relativePath TEXT,
+                 date DATE, caption TEXT, collection TEXT, icon INTEGER);
+             CREATE TABLE Images (id INTEGER PRIMARY KEY, album INTEGER, name TEXT, status INTEGER,
+                 category INTEGER);
+             CREATE TABLE ImageInformation (imageid INTEGER PRIMARY KEY, rating INTEGER,
+                 creationDate DATETIME, orientation INTEGER);
+             CREATE TABLE ImageMetadata (imageid INTEGER PRIMARY KEY, make TEXT, model TEXT,
+                 lens TEXT, aperture REAL, focalLength REAL, exposureTime REAL, sensitivity INTEGER);
+             CREATE TABLE ImagePositions (imageid INTEGER PRIMARY KEY, latitudeNumber REAL,
+                 longitudeNumber REAL, altitude REAL);
+             CREATE TABLE Tags (id INTEGER PRIMARY KEY, pid INTEGER, name TEXT);
+             CREATE TABLE ImageTags (imageid INTEGER, tagid INTEGER);
+             CREATE TABLE TagProperties (tagid INTEGER, property TEXT, value TEXT);
+             INSERT INTO Albums VALUES (1, 1, '/Trip', NULL, NULL, 'Travel', NULL),
+                 (2, 1, '/Trip/Day 2', NULL, NULL, 'Uncategorized Album', NULL);
+             INSERT INTO Images VALUES (1, 1, 'a.png', 1, 1), (2, 2, 'b.jpg', 1, 1),
+                 (3, 1, 'clip.mp4', 1, 2), (4, 1, 'gone.jpg', 3, 1);
+             INSERT INTO ImageInformation VALUES (1, 4, '2022-08-14T09:05:00.000', 1),
+                 (2, -1, NULL, 0);
+             INSERT INTO ImageMetadata VALUES (1, 'Canon', 'EOS R6', 'RF35mm', 1.8, 35, 0.002, 200);
+             INSERT INTO ImagePositions VALUES (1, 45.5, 6.1, 1200);
+             INSERT INTO Tags VALUES (0, -1, '_Digikam_root_tag_'),
+                 (1, 0, '_Digikam_Internal_Tags_'), (2, 1, 'Color Label Magenta'),
+                 (3, 1, 'Pick Label Accepted'), (4, 1, 'Color Label Orange'),
+                 (5, 0, 'Animals'), (6, 5, 'Birds'), (7, 0, 'People'), (8, 7, 'Unknown');
+             INSERT INTO TagProperties VALUES (8, 'unknownPerson', NULL);
+             INSERT INTO ImageTags VALUES (1, 2), (1, 3), (1, 6), (1, 8), (2, 4);",
+        )
+        .unwrap();
+        let encoded = dir.to_string_lossy().replace('/', "%2F");
+        conn.execute(
+            "INSERT INTO AlbumRoots VALUES (1, 'Pictures', 0, 1, ?1, '/My Pictures')",
+            [format!("volumeid:?path={encoded}")],
+        )
+        .unwrap();
+
+        let db = CatalogDb::in_memory().unwrap();
+        initialize_schema(db.conn()).unwrap();
+        let service = CatalogService::new(db);
+        let report = migrate_external_catalog(
+            &service,
+            ExternalSource::Digikam,
+            &database,
+            &ImportCallbacks::default(),
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(report.imported, 2);
+        assert_eq!(report.offline, vec![trip.join("Day 2").join("b.jpg")]);
+        assert_eq!(report.collections, 1);
+        assert_eq!(report.unmapped.len(), 2);
+        assert_eq!(report.unmapped["Unsupported file type .mp4"], 1);
+        assert_eq!(
+            report.unmapped["Color labels without a Folio equivalent"],
+            1
+        );
+
+        let a = service
+            .find_image_by_original_path(&trip.join("a.png"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(a.rating, Some(4));
+        assert_eq!(a.flag.as_deref(), Some("picked"));
+        assert_eq!(a.color_label.as_deref(), Some("purple"));
+        assert_eq!(a.lens_model.as_deref(), Some("RF35mm"));
+        assert_eq!(a.iso, Some(200));
+        assert_eq!((a.gps_latitude, a.gps_altitude), (Some(45.5), Some(1200.0)));
+        assert_eq!(
+            service.load_metadata(a.id).unwrap().keywords,
+            vec!["Animals|Birds"]
+        );
+
+        let collections = service.list_collections().unwrap();
+        assert_eq!(collections.len(), 1);
+        assert_eq!(collections[0].name, "Travel");
+        assert_eq!(
+            service
+                .list_images_in_collection(collections[0].id)
+                .unwrap()
+                .len(),
+            1
+        );
+
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn resolves_album_roots() {
+        assert_eq!(
+            album_root_path("volumeid:?path=%2Fhome%2Fann%2FPictures", "/"),
+            Some(PathBuf::from("/home/ann/Pictures"))
+        );
+        assert_eq!(
+            album_root_path("networkshareid:?mountpath=%2Fmnt%2Fnas", "/photos"),
+            Some(PathBuf::from("/mnt/nas/photos"))
+        );
+        assert_eq!(
+            album_root_path("volumeid:?uuid=not-a-real-volume", "/photos"),
+            None
+        );
+    }
+}
@@ -0,0 +1,865 @@
+//! Render catalog images through the engine and write them out as finished files.
+
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Context, Result};
+use core_types::RenderedImage;
+use engine::{Adjustments, ImageEngine, Watermark};
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageBuffer, ImageOutputFormat, Rgb};
+use serde::{Deserialize, Serialize};
+
+use crate::db::{Edit, Image};
+use crate::services::catalog_service::CatalogService;
+use crate::services::export_metadata::{
+    embed_in_jpeg, embed_in_tiff, ExportMetadata, MetadataPolicy, RightsInfo,
+};
+use crate::services::import::CancellationFlag;
+use crate::services::watermarks::to_engine_watermark;
+
+pub const DEFAULT_FILENAME_TEMPLATE: &str = "{filename}";
+
+type Rgb16Image = ImageBuffer<Rgb<u16>, Vec<u16>>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    #[default]
+    Jpeg,
+    Png,
+    Tiff,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Jpeg => "jpg",
+            ExportFormat::Png => "png",
+            ExportFormat::Tiff => "tif",
+        }
+    }
+}
+
+/// Bits per channel for PNG and TIFF output; JPEG is always 8-bit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BitDepth {
+    #[default]
+    #[serde(rename = "8")]
+    Eight,
+    #[serde(rename = "16")]
+    Sixteen,
+}
+
+/// How the rendered image is scaled before encoding. Images are never upscaled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum ResizeMode {
+    #[default]
+    Original,
+    LongEdge {
+        pixels: u32,
+    },
+    Megapixels {
+        megapixels: f64,
+    },
+    FitBox {
+        width: u32,
+        height: u32,
+    },
+}
+
+impl ResizeMode {
+    /// Output dimensions for a `width` x `height` source.
+    pub fn target_size(&self, width: u32, height: u32) -> (u32, u32) {
+        let scale = match *self {
+            ResizeMode::Original => 1.0,
+            ResizeMode::LongEdge { pixels } => pixels as f64 / width.max(height) as f64,
+            ResizeMode::Megapixels { megapixels } => {
+                (megapixels * 1_000_000.0 / (width as f64 * height as f64)).sqrt()
+            }
+            ResizeMode::FitBox {
+                width: box_w,
+                height: box_h,
+            } => (box_w as f64 / width as f64).min(box_h as f64 / height as f64),
+        };
+
+        if !scale.is_finite() || scale <= 0.0 || scale >= 1.0 {
+            return (width, height);
+        }
+        (
+            ((width as f64 * scale).round() as u32).max(1),
+            ((height as f64 * scale).round() as u32).max(1),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SharpenMedium {
+    #[default]
+    Screen,
+    MattePaper,
+    GlossyPaper,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SharpenAmount {
+    Low,
+    #[default]
+    Standard,
+    High,
+}
+
+/// Sharpening applied after resizing, tuned to the output medium and resolution.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputSharpening {
+    pub medium: SharpenMedium,
+    pub amount: SharpenAmount,
+    /// Printing resolution in pixels per inch; only used for paper media.
+    pub ppi: u32,
+}
+
+impl Default for OutputSharpening {
+    fn default() -> Self {
+        Self {
+            medium: SharpenMedium::default(),
+            amount: SharpenAmount::default(),
+            ppi: 300,
+        }
+    }
+}
+
+impl OutputSharpening {
+    fn to_engine(self) -> engine::OutputSharpening {
+        engine::OutputSharpening {
+            medium: match self.medium {
+                SharpenMedium::Screen => engine::SharpenMedium::Screen,
+                SharpenMedium::MattePaper => engine::SharpenMedium::MattePaper,
+                SharpenMedium::GlossyPaper => engine::SharpenMedium::GlossyPaper,
+            },
+            amount: match self.amount {
+                SharpenAmount::Low => engine::SharpenAmount::Low,
+                SharpenAmount::Standard => engine::SharpenAmount::Standard,
+                SharpenAmount::High => engine::SharpenAmount::High,
+            },
+            ppi: self.ppi,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportSettings {
+    pub format: ExportFormat,
+    /// JPEG quality, 1-100.
+    pub quality: u8,
+    pub bit_depth: BitDepth,
+    pub resize: ResizeMode,
+    /// Output name without extension; see [`render_filename`] for tokens.
+    pub filename_template: String,
+    /// Replace existing files instead of adding a numeric suffix.
+    pub overwrite: bool,
+    /// Catalog metadata written into JPEG and TIFF files.
+    pub metadata: MetadataPolicy,
+    pub rights: RightsInfo,
+    /// Output sharpening; `None` leaves the resized image as is.
+    pub sharpening: Option<OutputSharpening>,
+    /// Name of a saved watermark to composite after resizing.
+    pub watermark: Option<String>,
+}
+
+impl Default for ExportSettings {
+    fn default() -> Self {
+        Self {
+            format: ExportFormat::Jpeg,
+            quality: 90,
+            bit_depth: BitDepth::Eight,
+            resize: ResizeMode::Original,
+            filename_template: DEFAULT_FILENAME_TEMPLATE.to_string(),
+            overwrite: false,
+            metadata: MetadataPolicy::default(),
+            rights: RightsInfo::default(),
+            sharpening: None,
+            watermark: None,
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct ExportCallbacks {
+    pub progress: Option<Arc<dyn Fn(ExportProgress)>>,
+    pub on_error: Option<Arc<dyn Fn(i64, String)>>,
+    pub cancel: CancellationFlag,
+}
+
+#[derive(Clone, Debug)]
+pub struct ExportProgress {
+    pub completed: usize,
+    pub total: usize,
+    pub message: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExportedFile {
+    pub image_id: i64,
+    pub path: PathBuf,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ExportReport {
+    pub exported: Vec<ExportedFile>,
+    pub failed: Vec<(i64, String)>,
+    /// Images exported with part of their metadata left out.
+    #[serde(default)]
+    pub warnings: Vec<(i64, String)>,
+    pub canceled: bool,
+}
+
+/// Render each image with its saved edits and write it into `destination`.
+///
+/// Failures on individual images are reported and skipped; only invalid settings or
+/// an unusable destination abort the whole export.
+pub fn export_images(
+    service: &CatalogService,
+    engine: &ImageEngine,
+    image_ids: &[i64],
+    destination: &Path,
+    settings: &ExportSettings,
+    callbacks: ExportCallbacks,
+) -> Result<ExportReport> {
+    validate_settings(settings)?;
+    let watermark = match settings.watermark.as_deref() {
+        Some(name) => {
+            let definition = service
+                .find_watermark(name)?
+                .ok_or_else(|| anyhow!("no watermark named {name}"))?;
+            Some(to_engine_watermark(engine, &definition)?)
+        }
+        None => None,
+    };
+    fs::create_dir_all(destination)
+        .with_context(|| format!("failed to create export folder {}", destination.display()))?;
+
+    let mut report = ExportReport::default();
+    let total = image_ids.len();
+    for (idx, &image_id) in image_ids.iter().enumerate() {
+        if callbacks.cancel.is_canceled() {
+            report.canceled = true;
+            break;
+        }
+
+        if let Some(cb) = &callbacks.progress {
+            cb(ExportProgress {
+                completed: idx,
+                total,
+                message: Some(format!("Exporting image {} of {total}", idx + 1)),
+            });
+        }
+
+        let target = ExportTarget {
+            sequence: idx + 1,
+            destination,
+            settings,
+            watermark: watermark.as_ref(),
+        };
+        match export_one(service, engine, image_id, &target) {
+            Ok((path, warning)) => {
+                if let Some(warning) = warning {
+                    report.warnings.push((image_id, warning));
+                }
+                report.exported.push(ExportedFile { image_id, path });
+            }
+            Err(err) => {
+                let message = format!("{err:#}");
+                if let Some(cb) = &callbacks.on_error {
+                    cb(image_id, message.clone());
+                }
+                report.failed.push((image_id, message));
+            }
+        }
+    }
+
+    if let Some(cb) = &callbacks.progress {
+        cb(ExportProgress {
+            completed: report.exported.len() + report.failed.len(),
+            total,
+            message: None,
+        });
+    }
+
+    Ok(report)
+}
+
+/// Per-export state shared by every image in a batch.
+struct ExportTarget<'a> {
+    sequence: usize,
+    destination: &'a Path,
+    settings: &'a ExportSettings,
+    watermark: Option<&'a Watermark>,
+}
+
+fn export_one(
+    service: &CatalogService,
+    engine: &ImageEngine,
+    image_id: i64,
+    target: &ExportTarget<'_>,
+) -> Result<(PathBuf, Option<String>)> {
+    let settings = target.settings;
+    let details = service.load_metadata(image_id)?;
+    let image = details.image;
+    let adjustments = service
+        .load_edits(image_id)?
+        .map(|edit| adjustments_from_edit(&edit))
+        .unwrap_or_default();
+
+    let rendered = engine
+        .render(&image.original_path, &adjustments, exif_orientation(&image))
+        .with_context(|| format!("failed to render {}", image.original_path))?;
+    let buffer = Rgb16Image::from_raw(rendered.width, rendered.height, rendered.data)
+        .ok_or_else(|| anyhow!("engine returned a malformed buffer"))?;
+
+    let (width, height) = settings.resize.target_size(buffer.width(), buffer.height());
+    let buffer = if (width, height) == buffer.dimensions() {
+        buffer
+    } else {
+        image::imageops::resize(&buffer, width, height, FilterType::Lanczos3)
+    };
+    // Sharpen at the final size, then watermark so the overlay itself stays crisp.
+    let buffer = if settings.sharpening.is_some() || target.watermark.is_some() {
+        let (width, height) = buffer.dimensions();
+        let mut rendered = RenderedImage {
+            width,
+            height,
+            data: buffer.into_raw(),
+        };
+        if let Some(sharpening) = settings.sharpening {
+            engine.sharpen_for_output(&mut rendered, &sharpening.to_engine());
+        }
+        if let Some(watermark) = target.watermark {
+            engine
+                .apply_watermark(&mut rendered, watermark)
+                .context("failed to apply watermark")?;
+        }
+        Rgb16Image::from_raw(rendered.width, rendered.height, rendered.data)
+            .ok_or_else(|| anyhow!("output processing returned a malformed buffer"))?
+    } else {
+        buffer
+    };
+
+    let stem = render_filename(&settings.filename_template, &image, target.sequence)?;
+    let path = output_path(
+        target.destination,
+        &stem,
+        settings.format.extension(),
+        settings.overwrite,
+    );
+    let mut metadata = match settings.metadata {
+        MetadataPolicy::Strip => ExportMetadata::default(),
+        policy => ExportMetadata::collect(
+            policy,
+            &settings.rights,
+            &image,
+            service.load_camera_serial(image_id)?,
+            &details.keywords,
+        ),
+    };
+    let mut warning = None;
+    if settings.format == ExportFormat::Jpeg {
+        let dropped = metadata.fit_jpeg_segments();
+        if dropped > 0 {
+            warning = Some(format!(
+                "left {dropped} keyword(s) out of the metadata, which is too large for a JPEG"
+            ));
+        }
+    }
+    let bytes = encode_image(buffer, settings, &metadata)?;
+    if let Err(err) = fs::write(&path, bytes) {
+        let _ = fs::remove_file(&path);
+        return Err(err).with_context(|| format!("failed to write {}", path.display()));
+    }
+    Ok((path, warning))
+}
+
+/// Reject settings that could never produce a file, before any image is rendered.
+pub fn validate_settings(settings: &ExportSettings) -> Result<()> {
+    if !(1..=100).contains(&settings.quality) {
+        bail!("JPEG quality must be between 1 and 100");
+    }
+    parse_template(&settings.filename_template)?;
+    if let Some(sharpening) = settings.sharpening {
+        if sharpening.medium != SharpenMedium::Screen && !(72..=2400).contains(&sharpening.ppi) {
+            bail!("print resolution must be between 72 and 2400 ppi");
+        }
+    }
+    Ok(())
+}
+
+/// The orientation recorded in the catalog, e.g. by a migration, as an EXIF code.
+///
+/// `None` lets the engine fall back to the orientation stored in the file itself.
+pub fn exif_orientation(image: &Image) -> Option<u8> {
+    image
+        .orientation
+        .and_then(|code| u8::try_from(code).ok())
+        .filter(|code| (1..=8).contains(code))
+}
+
+pub fn adjustments_from_edit(edit: &Edit) -> Adjustments {
+    let value = |v: Option<f64>| v.unwrap_or(0.0) as f32;
+    Adjustments {
+        exposure: value(edit.exposure),
+        contrast: value(edit.contrast),
+        highlights: value(edit.highlights),
+        shadows: value(edit.shadows),
+        whites: value(edit.whites),
+        blacks: value(edit.blacks),
+        vibrance: value(edit.vibrance),
+        saturation: value(edit.saturation),
+        temperature: value(edit.temperature),
+        tint: value(edit.tint),
+    }
+}
+
+fn encode_image(
+    buffer: Rgb16Image,
+    settings: &ExportSettings,
+    metadata: &ExportMetadata,
+) -> Result<Vec<u8>> {
+    let image = match (settings.format, settings.bit_depth) {
+        (ExportFormat::Jpeg, _) | (_, BitDepth::Eight) => {
+            DynamicImage::ImageRgb8(DynamicImage::ImageRgb16(buffer).to_rgb8())
+        }
+        (_, BitDepth::Sixteen) => DynamicImage::ImageRgb16(buffer),
+    };
+    let output_format = match settings.format {
+        ExportFormat::Jpeg => ImageOutputFormat::Jpeg(settings.quality),
+        ExportFormat::Png => ImageOutputFormat::Png,
+        ExportFormat::Tiff => ImageOutputFormat::Tiff,
+    };
+    let mut encoded = Cursor::new(Vec::new());
+    image
+        .write_to(&mut encoded, output_format)
+        .context("failed to encode image")?;
+    let encoded = encoded.into_inner();
+
+    match settings.format {
+        ExportFormat::Jpeg => embed_in_jpeg(&encoded, metadata),
+        ExportFormat::Tiff => embed_in_tiff(&encoded, metadata),
+        ExportFormat::Png => Ok(encoded),
+    }
+}
+
+/// Expand a filename template for one image.
+///
+/// Supported tokens: `{filename}` (original name without extension), `{id}`,
+/// `{seq}` or `{seq:N}` (1-based position in the export, zero-padded to N digits),
+/// `{date}` and `{time}` (capture time, falling back to import time), `{rating}`,
+/// `{camera}` and `{folder}` (name of the containing folder).
+pub fn render_filename(template: &str, image: &Image, sequence: usize) -> Result<String> {
+    let timestamp = image.captured_at.unwrap_or(image.imported_at);
+    let mut out = String::new();
+    for part in parse_template(template)? {
+        match part {
+            TemplatePart::Literal(text) => out.push_str(text),
+            TemplatePart::Filename => out.push_str(
+                &Path::new(&image.filename)
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_else(|| image.filename.clone()),
+            ),
+            TemplatePart::Id => out.push_str(&image.id.to_string()),
+            TemplatePart::Sequence(width) => out.push_str(&format!("{sequence:0width$}")),
+            TemplatePart::Date => out.push_str(&timestamp.format("%Y-%m-%d").to_string()),
+            TemplatePart::Time => out.push_str(&timestamp.format("%H%M%S").to_string()),
+            TemplatePart::Rating => out.push_str(&image.rating.unwrap_or(0).to_string()),
+            TemplatePart::Camera => out.push_str(
+                image
+                    .camera_model
+                    .as_deref()
+                    .or(image.camera_make.as_deref())
+                    .unwrap_or("Unknown"),
+            ),
+            TemplatePart::Folder => out.push_str(
+                &Path::new(&image.original_path)
+                    .parent()
+                    .and_then(|p| p.file_name())
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+            ),
+        }
+    }
+
+    let sanitized: String = out
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let sanitized = sanitized.trim().trim_matches('.').to_string();
+    if sanitized.is_empty() {
+        bail!("filename template produced an empty name");
+    }
+    Ok(sanitized)
+}
+
+enum TemplatePart<'a> {
+    Literal(&'a str),
+    Filename,
+    Id,
+    Sequence(usize),
+    Date,
+    Time,
+    Rating,
+    Camera,
+    Folder,
+}
+
+fn parse_template(template: &str) -> Result<Vec<TemplatePart<'_>>> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        if start > 0 {
+            parts.push(TemplatePart::Literal(&rest[..start]));
+        }
+        let end = rest[start..]
+            .find('}')
+            .map(|offset| start + offset)
+            .ok_or_else(|| anyhow!("unclosed '{{' in filename template"))?;
+        let token = &rest[start + 1..end];
+        parts.push(match token.split_once(':') {
+            Some(("seq", width)) => TemplatePart::Sequence(
+                width
+                    .parse()
+                    .map_err(|_| anyhow!("invalid padding in {{{token}}}"))?,
+            ),
+            Some(_) => bail!("unknown filename token {{{token}}}"),
+            None => match token {
+                "filename" => TemplatePart::Filename,
+                "id" => TemplatePart::Id,
+                "seq" => TemplatePart::Sequence(0),
+                "date" => TemplatePart::Date,
+                "time" => TemplatePart::Time,
+                "rating" => TemplatePart::Rating,
+                "camera" => TemplatePart::Camera,
+                "folder" => TemplatePart::Folder,
+                _ => bail!("unknown filename token {{{token}}}"),
+            },
+        });
+        rest = &rest[end + 1..];
+    }
+    if !rest.is_empty() {
+        parts.push(TemplatePart::Literal(rest));
+    }
+    Ok(parts)
+}
+
+/// Pick `<stem>.<ext>` in `dir`, adding `-2`, `-3`, ... when the name is taken.
+fn output_path(dir: &Path, stem: &str, extension: &str, overwrite: bool) -> PathBuf {
+    let candidate = dir.join(format!("{stem}.{extension}"));
+    if overwrite || !candidate.exists() {
+        return candidate;
+    }
+    (2..)
+        .map(|n| dir.join(format!("{stem}-{n}.{extension}")))
+        .find(|path| !path.exists())
+        .expect("unbounded suffix search")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::CatalogDb;
+    use crate::schema::initialize_schema;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_dir(label: &str) -> PathBuf {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let dir = std::env::temp_dir().join(format!("catalog_export_{label}_{suffix}"));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn resize_modes_never_upscale() {
+        assert_eq!(ResizeMode::Original.target_size(4000, 3000), (4000, 3000));
+        assert_eq!(
+            ResizeMode::LongEdge { pixels: 2000 }.target_size(4000, 3000),
+            (2000, 1500)
+        );
+        assert_eq!(
+            ResizeMode::LongEdge { pixels: 8000 }.target_size(4000, 3000),
+            (4000, 3000)
+        );
+        assert_eq!(
+            ResizeMode::Megapixels { megapixels: 3.0 }.target_size(4000, 3000),
+            (2000, 1500)
+        );
+        assert_eq!(
+            ResizeMode::FitBox {
+                width: 1000,
+                height: 1000
+            }
+            .target_size(4000, 3000),
+            (1000, 750)
+        );
+    }
+
+    /// A JPEG whose EXIF block carries only an Orientation tag.
+    fn jpeg_with_orientation(path: &Path, pixels: &image::RgbImage, orientation: u16) {
+        let mut encoded = Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(pixels.clone())
+            .write_to(&mut encoded, ImageOutputFormat::Jpeg(95))
+            .unwrap();
+        let encoded = encoded.into_inner();
+
+        let mut app1 = b"Exif\0\0II*\0".to_vec();
+        app1.extend_from_slice(&8u32.to_le_bytes());
+        app1.extend_from_slice(&1u16.to_le_bytes());
+        app1.extend_from_slice(&0x0112u16.to_le_bytes());
+        app1.extend_from_slice(&3u16.to_le_bytes());
+        app1.extend_from_slice(&1u32.to_le_bytes());
+        app1.extend_from_slice(&orientation.to_le_bytes());
+        app1.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+
+        let mut jpeg = encoded[..2].to_vec();
+        jpeg.extend_from_slice(&[0xFF, 0xE1]);
+        jpeg.extend_from_slice(&(app1.len() as u16 + 2).to_be_bytes());
+        jpeg.extend_from_slice(&app1);
+        jpeg.extend_from_slice(&encoded[2..]);
+        fs::write(path, jpeg).unwrap();
+    }
+
+    #[test]
+    fn exports_are_turned_upright_before_resizing() {
+        let db = CatalogDb::in_memory().unwrap();
+        initialize_schema(db.conn()).unwrap();
+        let service = CatalogService::new(db);
+        let engine = ImageEngine::new();
+
+        // Stored sideways: red on the left, blue on the right. Orientation 6 means the
+        // camera was turned clockwise, so upright the red half is on top.
+        let src_dir = temp_dir("orientation_src");
+        let source = src_dir.join("portrait.jpg");
+        let pixels = image::RgbImage::from_fn(400, 200, |x, _| {
+            if x < 200 {
+                Rgb([220, 30, 30])
+            } else {
+                Rgb([30, 30, 220])
+            }
+        });
+        jpeg_with_orientation(&source, &pixels, 6);
+        let image = service.import_image(&source).unwrap();
+
+        let out_dir = temp_dir("orientation_out");
+        let settings = ExportSettings {
+            format: ExportFormat::Png,
+            resize: ResizeMode::FitBox {
+                width: 100,
+                height: 200,
+            },
+            ..ExportSettings::default()
+        };
+        let report = export_images(
+            &service,
+            &engine,
+            &[image.id],
+            &out_dir,
+            &settings,
+            ExportCallbacks::default(),
+        )
+        .unwrap();
+        assert!(report.failed.is_empty(), "{:?}", report.failed);
+
+        let written = image::open(&report.exported[0].path).unwrap().to_rgb8();
+        assert_eq!(written.dimensions(), (100, 200));
+        assert!(written.get_pixel(50, 20)[0] > 150);
+        assert!(written.get_pixel(50, 180)[2] > 150);
+
+        // An orientation recorded in the catalog wins over the file's own.
+        let upright = engine
+            .render(&source, &Adjustments::default(), Some(1))
+            .unwrap();
+        assert_eq!((upright.width, upright.height), (400, 200));
+
+        fs::remove_dir_all(src_dir).ok();
+        fs::remove_dir_all(out_dir).ok();
+    }
+
+    #[test]
+    fn output_sharpening_adds_edge_contrast_scaled_to_output() {
+        let db = CatalogDb::in_memory().unwrap();
+        initialize_schema(db.conn()).unwrap();
+        let service = CatalogService::new(db);
+        let engine = ImageEngine::new();
+
+        let src_dir = temp_dir("sharpen_src");
+        let source = src_dir.join("edge.png");
+        image::RgbImage::from_fn(400, 200, |x, _| {
+            if x < 200 {
+                Rgb([80, 80, 80])
+            } else {
+                Rgb([170, 170, 170])
+            }
+        })
+        .save(&source)
+        .unwrap();
+        let image = service.import_image(&source).unwrap();
+
+        let out_dir = temp_dir("sharpen_out");
+        let export_row = |template: &str, sharpening: Option<OutputSharpening>| {
+            let settings = ExportSettings {
+                format: ExportFormat::Png,
+                bit_depth: BitDepth::Sixteen,
+                filename_template: template.into(),
+                sharpening,
+                ..ExportSettings::default()
+            };
+            let report = export_images(
+                &service,
+                &engine,
+                &[image.id],
+                &out_dir,
+                &settings,
+                ExportCallbacks::default(),
+            )
+            .unwrap();
+            let written = image::open(&report.exported[0].path).unwrap().to_rgb16();
+            (0..400)
+                .map(|x| written.get_pixel(x, 100)[0])
+                .collect::<Vec<_>>()
+        };
+        let plain = export_row("plain", None);
+        let sharpened = export_row("sharp", Some(OutputSharpening::default()));
+
+        // The dark side of the edge gets darker and the bright side brighter.
+        assert!(sharpened[199] < plain[199]);
+        assert!(sharpened[200] > plain[200]);
+        // Flat areas away from the edge are untouched.
+        assert_eq!(sharpened[10], plain[10]);
+        assert_eq!(sharpened[390], plain[390]);
+
+        let print = |ppi| OutputSharpening {
+            medium: SharpenMedium::MattePaper,
+            ppi,
+            ..OutputSharpening::default()
+        };
+        let radius = |sharpening: OutputSharpening| sharpening.to_engine().parameters(3000, 2000).0;
+        assert!(radius(print(600)) > radius(print(240)));
+        let screen = OutputSharpening::default().to_engine();
+        assert!(screen.parameters(4000, 3000).0 > screen.parameters(800, 600).0);
+
+        let bad = ExportSettings {
+            sharpening: Some(print(10)),
+            ..ExportSettings::default()
+        };
+        assert!(validate_settings(&bad).is_err());
+
+        fs::remove_dir_all(src_dir).ok();
+        fs::remove_dir_all(out_dir).ok();
+    }
+
+    #[test]
+    fn exports_edited_images_with_templates() {
+        let db = CatalogDb::in_memory().unwrap();
+        initialize_schema(db.conn()).unwrap();
+        let service = CatalogService::new(db);
+        let engine = ImageEngine::new();
+
+        let src_dir = temp_dir("src");
+        let source = src_dir.join("beach.png");
+        image::RgbImage::from_pixel(400, 200, Rgb([100, 120, 140]))
+            .save(&source)
+            .unwrap();
+        let image = service.import_image(&source).unwrap();
+        service
+            .apply_edits(
+                image.id,
+                Edit {
+                    id: 0,
+                    image_id: image.id,
+                    exposure: Some(1.0),
+                    contrast: None,
+                    highlights: None,
+                    shadows: None,
+                    whites: None,
+                    blacks: None,
+                    vibrance: None,
+                    saturation: None,
+                    temperature: None,
+                    tint: None,
+                    texture: None,
+                    clarity: None,
+                    dehaze: None,
+                    parametric_curve_json: None,
+                    color_grading_json: None,
+                    crop_json: None,
+                    masking_json: None,
+                    updated_at: None,
+                },
+            )
+            .unwrap();
+
+        let out_dir = temp_dir("out");
+        let settings = ExportSettings {
+            format: ExportFormat::Tiff,
+            bit_depth: BitDepth::Sixteen,
+            resize: ResizeMode::LongEdge { pixels: 100 },
+            filename_template: "{seq:3}_{filename}".into(),
+            ..ExportSettings::default()
+        };
+        let report = export_images(
+            &service,
+            &engine,
+            &[image.id, image.id, 9999],
+            &out_dir,
+            &settings,
+            ExportCallbacks::default(),
+        )
+        .unwrap();
+
+        assert_eq!(report.exported.len(), 2);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.exported[0].path, out_dir.join("001_beach.tif"));
+        assert_eq!(report.exported[1].path, out_dir.join("002_beach.tif"));
+
+        let written = image::open(&report.exported[0].path).unwrap();
+        assert_eq!((written.width(), written.height()), (100, 50));
+        assert!(matches!(written, DynamicImage::ImageRgb16(_)));
+        // One stop of exposure brightens the flat source.
+        assert!(written.to_rgb8().get_pixel(50, 25)[0] > 100);
+
+        let cancel = CancellationFlag::default();
+        cancel.cancel();
+        let canceled = export_images(
+            &service,
+            &engine,
+            &[image.id],
+            &out_dir,
+            &ExportSettings::default(),
+            ExportCallbacks {
+                cancel,
+                ..ExportCallbacks::default()
+            },
+        )
+        .unwrap();
+        assert!(canceled.canceled);
+        assert!(canceled.exported.is_empty());
+
+        assert!(
+            render_filename("{nope}", &service.load_metadata(image.id).unwrap().image, 1).is_err()
+        );
+
+        fs::remove_dir_all(src_dir).ok();
+        fs::remove_dir_all(out_dir).ok();
+    }
+}
@@ -0,0 +1,1000 @@
+//! EXIF, XMP and IPTC blocks written into exported JPEG and TIFF files.
+//!
+//! The encoder output never carries metadata, so everything here comes from the
+//! catalog: [`ExportMetadata::collect`] picks the fields a [`MetadataPolicy`] allows,
+//! and [`embed_in_jpeg`] / [`embed_in_tiff`] splice the encoded blocks into the file.
+//! Keywords containing `|` or `>` are treated as hierarchies ("Places|France|Paris"):
+//! every level goes into `dc:subject` and IPTC keywords, the full path into
+//! `lr:hierarchicalSubject`. PNG exports carry no metadata.
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::db::Image;
+
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const PHOTOSHOP_HEADER: &[u8] = b"Photoshop 3.0\0";
+/// Largest payload a JPEG APPn segment can hold.
+const MAX_SEGMENT_PAYLOAD: usize = 65533;
+
+/// Which catalog metadata is written into exported files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataPolicy {
+    /// No metadata at all.
+    Strip,
+    /// Creator, copyright notice and contact only.
+    CopyrightOnly,
+    /// Everything except GPS coordinates and the camera body serial number.
+    #[default]
+    AllExceptLocation,
+    /// Everything, including keywords, rating, location and camera serial. Never a
+    /// default: exports only carry where a photo was taken when asked to.
+    All,
+}
+
+impl MetadataPolicy {
+    fn includes_descriptive(self) -> bool {
+        matches!(
+            self,
+            MetadataPolicy::AllExceptLocation | MetadataPolicy::All
+        )
+    }
+
+    fn includes_private(self) -> bool {
+        self == MetadataPolicy::All
+    }
+}
+
+/// Rights information stamped on exports; empty fields are left out.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RightsInfo {
+    pub creator: Option<String>,
+    pub copyright: Option<String>,
+    /// Email address or URL.
+    pub contact: Option<String>,
+}
+
+/// Catalog metadata selected for one exported file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExportMetadata {
+    pub creator: Option<String>,
+    pub copyright: Option<String>,
+    pub contact: Option<String>,
+    pub captured_at: Option<DateTime<Utc>>,
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub camera_serial: Option<String>,
+    pub lens_model: Option<String>,
+    pub focal_length: Option<f64>,
+    pub aperture: Option<f64>,
+    /// Exposure time in seconds.
+    pub shutter_speed: Option<f64>,
+    pub iso: Option<i64>,
+    pub gps_latitude: Option<f64>,
+    pub gps_longitude: Option<f64>,
+    pub gps_altitude: Option<f64>,
+    pub rating: Option<i64>,
+    /// Keyword paths as stored in the catalog.
+    pub keywords: Vec<String>,
+}
+
+impl ExportMetadata {
+    pub fn collect(
+        policy: MetadataPolicy,
+        rights: &RightsInfo,
+        image: &Image,
+        camera_serial: Option<String>,
+        keywords: &[String],
+    ) -> Self {
+        if policy == MetadataPolicy::Strip {
+            return Self::default();
+        }
+
+        let non_empty = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+        };
+        let mut metadata = Self {
+            creator: non_empty(&rights.creator),
+            copyright: non_empty(&rights.copyright),
+            contact: non_empty(&rights.contact),
+            ..Self::default()
+        };
+
+        if policy.includes_descriptive() {
+            metadata.captured_at = image.captured_at;
+            metadata.camera_make = non_empty(&image.camera_make);
+            metadata.camera_model = non_empty(&image.camera_model);
+            metadata.lens_model = non_empty(&image.lens_model);
+            metadata.focal_length = image.focal_length.filter(|v| *v > 0.0);
+            metadata.aperture = image.aperture.filter(|v| *v > 0.0);
+            metadata.shutter_speed = image.shutter_speed.filter(|v| *v > 0.0);
+            metadata.iso = image.iso.filter(|v| *v > 0);
+            metadata.rating = image.rating;
+            metadata.keywords = keywords
+                .iter()
+                .map(|kw| kw.trim().to_string())
+                .filter(|kw| !kw.is_empty())
+                .collect();
+        }
+
+        if policy.includes_private() {
+            metadata.camera_serial = non_empty(&camera_serial);
+            if let (Some(lat), Some(lon)) = (image.gps_latitude, image.gps_longitude) {
+                metadata.gps_latitude = Some(lat);
+                metadata.gps_longitude = Some(lon);
+                metadata.gps_altitude = image.gps_altitude;
+            }
+        }
+
+        metadata
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Leave out trailing keywords until the XMP and IPTC blocks each fit in one JPEG
+    /// segment, rather than failing the file. Returns how many keywords were dropped.
+    pub fn fit_jpeg_segments(&mut self) -> usize {
+        let fits = |metadata: &Self| {
+            XMP_HEADER.len() + metadata.xmp().len() <= MAX_SEGMENT_PAYLOAD
+                && PHOTOSHOP_HEADER.len() + photoshop_iptc_resource(&metadata.iptc()).len()
+                    <= MAX_SEGMENT_PAYLOAD
+        };
+        if fits(self) {
+            return 0;
+        }
+        // Binary search for the longest prefix of keywords that still fits.
+        let keywords = std::mem::take(&mut self.keywords);
+        let (mut low, mut high) = (0, keywords.len());
+        while low < high {
+            let mid = (low + high).div_ceil(2);
+            self.keywords = keywords[..mid].to_vec();
+            if fits(self) {
+                low = mid;
+            } else {
+                high = mid - 1;
+            }
+        }
+        self.keywords = keywords[..low].to_vec();
+        keywords.len() - low
+    }
+
+    /// Every keyword level, deduplicated, for flat keyword fields.
+    fn flat_keywords(&self) -> Vec<String> {
+        let mut out: Vec<String> = Vec::new();
+        for path in &self.keywords {
+            for level in keyword_levels(path) {
+                if !out.iter().any(|existing| existing == level) {
+                    out.push(level.to_string());
+                }
+            }
+        }
+        out
+    }
+
+    /// Keyword hierarchies in Lightroom's `A|B|C` form.
+    fn hierarchical_keywords(&self) -> Vec<String> {
+        self.keywords
+            .iter()
+            .map(|path| keyword_levels(path).collect::<Vec<_>>().join("|"))
+            .filter(|path| path.contains('|'))
+            .collect()
+    }
+
+    fn ifd0_entries(&self) -> Vec<Entry> {
+        let mut entries = Vec::new();
+        push_ascii(&mut entries, TAG_MAKE, &self.camera_make);
+        push_ascii(&mut entries, TAG_MODEL, &self.camera_model);
+        push_ascii(&mut entries, TAG_ARTIST, &self.creator);
+        push_ascii(&mut entries, TAG_COPYRIGHT, &self.copyright);
+        entries
+    }
+
+    fn exif_entries(&self) -> Vec<Entry> {
+        let mut entries = Vec::new();
+        if let Some(seconds) = self.shutter_speed {
+            entries.push(Entry::new(
+                TAG_EXPOSURE_TIME,
+                Value::Rational(vec![exposure_rational(seconds)]),
+            ));
+        }
+        if let Some(aperture) = self.aperture {
+            entries.push(Entry::new(
+                TAG_F_NUMBER,
+                Value::Rational(vec![decimal_rational(aperture)]),
+            ));
+        }
+        if let Some(iso) = self.iso {
+            entries.push(Entry::new(
+                TAG_ISO,
+                Value::Short(vec![iso.clamp(1, u16::MAX as i64) as u16]),
+            ));
+        }
+        if let Some(captured_at) = self.captured_at {
+            let stamp = captured_at.format("%Y:%m:%d %H:%M:%S").to_string();
+            entries.push(Entry::new(TAG_DATE_TIME_ORIGINAL, Value::Ascii(stamp)));
+            entries.push(Entry::new(
+                TAG_OFFSET_TIME_ORIGINAL,
+                Value::Ascii("+00:00".into()),
+            ));
+        }
+        if let Some(focal_length) = self.focal_length {
+            entries.push(Entry::new(
+                TAG_FOCAL_LENGTH,
+                Value::Rational(vec![decimal_rational(focal_length)]),
+            ));
+        }
+        push_ascii(&mut entries, TAG_BODY_SERIAL, &self.camera_serial);
+        push_ascii(&mut entries, TAG_LENS_MODEL, &self.lens_model);
+        if !entries.is_empty() {
+            entries.push(Entry::new(
+                TAG_EXIF_VERSION,
+                Value::Undefined(b"0232".to_vec()),
+            ));
+        }
+        entries
+    }
+
+    fn gps_entries(&self) -> Vec<Entry> {
+        let (Some(lat), Some(lon)) = (self.gps_latitude, self.gps_longitude) else {
+            return Vec::new();
+        };
+        let mut entries = vec![
+            Entry::new(TAG_GPS_VERSION, Value::Byte(vec![2, 3, 0, 0])),
+            Entry::new(
+                TAG_GPS_LATITUDE_REF,
+                Value::Ascii(if lat < 0.0 { "S" } else { "N" }.into()),
+            ),
+            Entry::new(TAG_GPS_LATITUDE, Value::Rational(dms_rationals(lat))),
+            Entry::new(
+                TAG_GPS_LONGITUDE_REF,
+                Value::Ascii(if lon < 0.0 { "W" } else { "E" }.into()),
+            ),
+            Entry::new(TAG_GPS_LONGITUDE, Value::Rational(dms_rationals(lon))),
+        ];
+        if let Some(altitude) = self.gps_altitude {
+            entries.push(Entry::new(
+                TAG_GPS_ALTITUDE_REF,
+                Value::Byte(vec![u8::from(altitude < 0.0)]),
+            ));
+            entries.push(Entry::new(
+                TAG_GPS_ALTITUDE,
+                Value::Rational(vec![decimal_rational(altitude.abs())]),
+            ));
+        }
+        entries
+    }
+
+    /// A standalone big-endian TIFF structure, as stored in a JPEG APP1 segment.
+    pub fn exif(&self) -> Vec<u8> {
+        let order = ByteOrder::Big;
+        let mut out = Vec::new();
+        out.extend_from_slice(b"MM");
+        out.extend_from_slice(&order.u16(42));
+        out.extend_from_slice(&order.u32(8));
+        append_ifds(&mut out, order, self.ifd0_entries(), self, 0);
+        out
+    }
+
+    pub fn xmp(&self) -> String {
+        let mut body = String::new();
+        if let Some(creator) = &self.creator {
+            body.push_str(&format!(
+                "   <dc:creator><rdf:Seq><rdf:li>{}</rdf:li></rdf:Seq></dc:creator>\n",
+                xml_escape(creator)
+            ));
+        }
+        if let Some(copyright) = &self.copyright {
+            body.push_str(&format!(
+                "   <dc:rights><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:rights>\n",
+                xml_escape(copyright)
+            ));
+            body.push_str("   <xmpRights:Marked>True</xmpRights:Marked>\n");
+        }
+        if let Some(contact) = &self.contact {
+            let field = if contact.contains('@') {
+                "CiEmailWork"
+            } else {
+                "CiUrlWork"
+            };
+            body.push_str(&format!(
+                "   <Iptc4xmpCore:CreatorContactInfo rdf:parseType=\"Resource\"><Iptc4xmpCore:{field}>{}</Iptc4xmpCore:{field}></Iptc4xmpCore:CreatorContactInfo>\n",
+                xml_escape(contact)
+            ));
+        }
+        if let Some(captured_at) = self.captured_at {
+            body.push_str(&format!(
+                "   <photoshop:DateCreated>{}</photoshop:DateCreated>\n",
+                captured_at.to_rfc3339_opts(SecondsFormat::Secs, true)
+            ));
+        }
+        if let Some(rating) = self.rating {
+            body.push_str(&format!("   <xmp:Rating>{rating}</xmp:Rating>\n"));
+        }
+        let flat = self.flat_keywords();
+        if !flat.is_empty() {
+            body.push_str(&xmp_bag("dc:subject", &flat));
+        }
+        let hierarchical = self.hierarchical_keywords();
+        if !hierarchical.is_empty() {
+            body.push_str(&xmp_bag("lr:hierarchicalSubject", &hierarchical));
+        }
+
+        format!(
+            "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+             <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n \
+             <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n  \
+             <rdf:Description rdf:about=\"\"\n    \
+             xmlns:dc=\"http://purl.org/dc/elements/1.1/\"\n    \
+             xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\"\n    \
+             xmlns:xmpRights=\"http://ns.adobe.com/xap/1.0/rights/\"\n    \
+             xmlns:photoshop=\"http://ns.adobe.com/photoshop/1.0/\"\n    \
+             xmlns:lr=\"http://ns.adobe.com/lightroom/1.0/\"\n    \
+             xmlns:Iptc4xmpCore=\"http://iptc.org/std/Iptc4xmpCore/1.0/xmlns/\">\n\
+             {body}  </rdf:Description>\n \
+             </rdf:RDF>\n\
+             </x:xmpmeta>\n\
+             <?xpacket end=\"w\"?>"
+        )
+    }
+
+    /// IPTC-IIM datasets (records 1 and 2), UTF-8 encoded.
+    pub fn iptc(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        // 1:90 coded character set: ESC % G selects UTF-8.
+        push_dataset(&mut out, 1, 90, b"\x1b%G");
+        push_dataset(&mut out, 2, 0, &[0, 4]);
+        for keyword in self.flat_keywords() {
+            push_dataset(&mut out, 2, 25, truncate_utf8(&keyword, 64).as_bytes());
+        }
+        if let Some(captured_at) = self.captured_at {
+            push_dataset(
+                &mut out,
+                2,
+                55,
+                captured_at.format("%Y%m%d").to_string().as_bytes(),
+            );
+            push_dataset(
+                &mut out,
+                2,
+                60,
+                captured_at.format("%H%M%S+0000").to_string().as_bytes(),
+            );
+        }
+        if let Some(creator) = &self.creator {
+            push_dataset(&mut out, 2, 80, truncate_utf8(creator, 32).as_bytes());
+        }
+        if let Some(copyright) = &self.copyright {
+            push_dataset(&mut out, 2, 116, truncate_utf8(copyright, 128).as_bytes());
+        }
+        if let Some(contact) = &self.contact {
+            push_dataset(&mut out, 2, 118, truncate_utf8(contact, 128).as_bytes());
+        }
+        out
+    }
+}
+
+/// Insert EXIF (APP1), XMP (APP1) and IPTC (APP13) segments after the JFIF header.
+pub fn embed_in_jpeg(jpeg: &[u8], metadata: &ExportMetadata) -> Result<Vec<u8>> {
+    if jpeg.len() < 4 || jpeg[0..2] != [0xFF, 0xD8] {
+        bail!("encoder did not produce a JPEG stream");
+    }
+    if metadata.is_empty() {
+        return Ok(jpeg.to_vec());
+    }
+
+    // Keep SOI and an APP0 (JFIF) segment at the front, as readers expect.
+    let mut insert_at = 2;
+    if jpeg[2..4] == [0xFF, 0xE0] && jpeg.len() >= 6 {
+        insert_at = 4 + u16::from_be_bytes([jpeg[4], jpeg[5]]) as usize;
+    }
+
+    let mut segments = Vec::new();
+    push_segment(&mut segments, 0xE1, &[EXIF_HEADER, &metadata.exif()])?;
+    push_segment(
+        &mut segments,
+        0xE1,
+        &[XMP_HEADER, metadata.xmp().as_bytes()],
+    )?;
+    push_segment(
+        &mut segments,
+        0xED,
+        &[PHOTOSHOP_HEADER, &photoshop_iptc_resource(&metadata.iptc())],
+    )?;
+
+    let mut out = Vec::with_capacity(jpeg.len() + segments.len());
+    out.extend_from_slice(&jpeg[..insert_at]);
+    out.extend_from_slice(&segments);
+    out.extend_from_slice(&jpeg[insert_at..]);
+    Ok(out)
+}
+
+/// Rewrite the first IFD of a TIFF file with metadata tags added.
+///
+/// The new IFD and its EXIF/GPS sub-IFDs are appended to the file; strip offsets and
+/// other existing entries still point into the untouched original data.
+pub fn embed_in_tiff(tiff: &[u8], metadata: &ExportMetadata) -> Result<Vec<u8>> {
+    let order = match tiff.get(0..2) {
+        Some(b"II") => ByteOrder::Little,
+        Some(b"MM") => ByteOrder::Big,
+        _ => bail!("encoder did not produce a TIFF file"),
+    };
+    if metadata.is_empty() {
+        return Ok(tiff.to_vec());
+    }
+
+    let ifd_offset = order.read_u32(tiff, 4)? as usize;
+    let count = order.read_u16(tiff, ifd_offset)? as usize;
+    let mut entries = Vec::with_capacity(count);
+    for idx in 0..count {
+        let at = ifd_offset + 2 + idx * 12;
+        let raw = tiff
+            .get(at..at + 12)
+            .ok_or_else(|| anyhow::anyhow!("TIFF directory is truncated"))?;
+        entries.push(Entry::new(
+            order.read_u16(raw, 0)?,
+            Value::Raw {
+                kind: order.read_u16(raw, 2)?,
+                count: order.read_u32(raw, 4)?,
+                inline: [raw[8], raw[9], raw[10], raw[11]],
+            },
+        ));
+    }
+    let next_ifd = order.read_u32(tiff, ifd_offset + 2 + count * 12)?;
+
+    let mut added = metadata.ifd0_entries();
+    added.push(Entry::new(
+        TAG_XMP,
+        Value::Byte(metadata.xmp().into_bytes()),
+    ));
+    added.push(Entry::new(TAG_IPTC, Value::Undefined(metadata.iptc())));
+    entries.retain(|entry| {
+        !added.iter().any(|new| new.tag == entry.tag)
+            && entry.tag != TAG_EXIF_IFD
+            && entry.tag != TAG_GPS_IFD
+    });
+    entries.extend(added);
+
+    let mut out = tiff.to_vec();
+    if out.len() % 2 == 1 {
+        out.push(0);
+    }
+    let new_offset = out.len() as u32;
+    append_ifds(&mut out, order, entries, metadata, next_ifd);
+    out[4..8].copy_from_slice(&order.u32(new_offset));
+    Ok(out)
+}
+
+// TIFF/EXIF tag numbers.
+const TAG_MAKE: u16 = 271;
+const TAG_MODEL: u16 = 272;
+const TAG_ARTIST: u16 = 315;
+const TAG_XMP: u16 = 700;
+const TAG_COPYRIGHT: u16 = 33432;
+const TAG_EXPOSURE_TIME: u16 = 33434;
+const TAG_F_NUMBER: u16 = 33437;
+const TAG_IPTC: u16 = 33723;
+const TAG_EXIF_IFD: u16 = 34665;
+const TAG_GPS_IFD: u16 = 34853;
+const TAG_ISO: u16 = 34855;
+const TAG_EXIF_VERSION: u16 = 36864;
+const TAG_DATE_TIME_ORIGINAL: u16 = 36867;
+const TAG_OFFSET_TIME_ORIGINAL: u16 = 36881;
+const TAG_FOCAL_LENGTH: u16 = 37386;
+const TAG_BODY_SERIAL: u16 = 42033;
+const TAG_LENS_MODEL: u16 = 42036;
+const TAG_GPS_VERSION: u16 = 0;
+const TAG_GPS_LATITUDE_REF: u16 = 1;
+const TAG_GPS_LATITUDE: u16 = 2;
+const TAG_GPS_LONGITUDE_REF: u16 = 3;
+const TAG_GPS_LONGITUDE: u16 = 4;
+const TAG_GPS_ALTITUDE_REF: u16 = 5;
+const TAG_GPS_ALTITUDE: u16 = 6;
+
+#[derive(Debug, Clone, Copy)]
+enum ByteOrder {
+    Little,
+    Big,
+}
+
+impl ByteOrder {
+    fn u16(self, value: u16) -> [u8; 2] {
+        match self {
+            ByteOrder::Little => value.to_le_bytes(),
+            ByteOrder::Big => value.to_be_bytes(),
+        }
+    }
+
+    fn u32(self, value: u32) -> [u8; 4] {
+        match self {
+            ByteOrder::Little => value.to_le_bytes(),
+            ByteOrder::Big => value.to_be_bytes(),
+        }
+    }
+
+    fn read_u16(self, bytes: &[u8], at: usize) -> Result<u16> {
+        let Some(raw) = bytes.get(at..at + 2) else {
+            bail!("TIFF data is truncated");
+        };
+        let raw = [raw[0], raw[1]];
+        Ok(match self {
+            ByteOrder::Little => u16::from_le_bytes(raw),
+            ByteOrder::Big => u16::from_be_bytes(raw),
+        })
+    }
+
+    fn read_u32(self, bytes: &[u8], at: usize) -> Result<u32> {
+        let Some(raw) = bytes.get(at..at + 4) else {
+            bail!("TIFF data is truncated");
+        };
+        let raw = [raw[0], raw[1], raw[2], raw[3]];
+        Ok(match self {
+            ByteOrder::Little => u32::from_le_bytes(raw),
+            ByteOrder::Big => u32::from_be_bytes(raw),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Byte(Vec<u8>),
+    Ascii(String),
+    Short(Vec<u16>),
+    Long(Vec<u32>),
+    Rational(Vec<(u32, u32)>),
+    Undefined(Vec<u8>),
+    /// An entry copied verbatim from an existing directory.
+    Raw {
+        kind: u16,
+        count: u32,
+        inline: [u8; 4],
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    tag: u16,
+    value: Value,
+}
+
+impl Entry {
+    fn new(tag: u16, value: Value) -> Self {
+        Self { tag, value }
+    }
+
+    fn kind_and_count(&self) -> (u16, u32) {
+        match &self.value {
+            Value::Byte(v) => (1, v.len() as u32),
+            Value::Ascii(s) => (2, s.len() as u32 + 1),
+            Value::Short(v) => (3, v.len() as u32),
+            Value::Long(v) => (4, v.len() as u32),
+            Value::Rational(v) => (5, v.len() as u32),
+            Value::Undefined(v) => (7, v.len() as u32),
+            Value::Raw { kind, count, .. } => (*kind, *count),
+        }
+    }
+
+    fn data(&self, order: ByteOrder) -> Vec<u8> {
+        match &self.value {
+            Value::Byte(v) | Value::Undefined(v) => v.clone(),
+            Value::Ascii(s) => {
+                let mut bytes = s.as_bytes().to_vec();
+                bytes.push(0);
+                bytes
+            }
+            Value::Short(v) => v.iter().flat_map(|n| order.u16(*n)).collect(),
+            Value::Long(v) => v.iter().flat_map(|n| order.u32(*n)).collect(),
+            Value::Rational(v) => v
+                .iter()
+                .flat_map(|(num, den)| {
+                    let mut pair = order.u32(*num).to_vec();
+                    pair.extend_from_slice(&order.u32(*den));
+                    pair
+                })
+                .collect(),
+            Value::Raw { inline, .. } => inline.to_vec(),
+        }
+    }
+}
+
+/// Append IFD0 plus the EXIF and GPS sub-IFDs the metadata needs, linking them up.
+fn append_ifds(
+    out: &mut Vec<u8>,
+    order: ByteOrder,
+    mut ifd0: Vec<Entry>,
+    metadata: &ExportMetadata,
+    next_ifd: u32,
+) {
+    let exif = metadata.exif_entries();
+    let gps = metadata.gps_entries();
+    let base = out.len() as u32;
+
+    // Pointer entries are inline LONGs, so their size is known before their value.
+    if !exif.is_empty() {
+        ifd0.push(Entry::new(TAG_EXIF_IFD, Value::Long(vec![0])));
+    }
+    if !gps.is_empty() {
+        ifd0.push(Entry::new(TAG_GPS_IFD, Value::Long(vec![0])));
+    }
+    let exif_offset = base + ifd_size(&ifd0, order);
+    let gps_offset = exif_offset
+        + if exif.is_empty() {
+            0
+        } else {
+            ifd_size(&exif, order)
+        };
+    for entry in &mut ifd0 {
+        match entry.tag {
+            TAG_EXIF_IFD => entry.value = Value::Long(vec![exif_offset]),
+            TAG_GPS_IFD => entry.value = Value::Long(vec![gps_offset]),
+            _ => {}
+        }
+    }
+
+    write_ifd(out, order, ifd0, next_ifd);
+    if !exif.is_empty() {
+        write_ifd(out, order, exif, 0);
+    }
+    if !gps.is_empty() {
+        write_ifd(out, order, gps, 0);
+    }
+}
+
+fn ifd_size(entries: &[Entry], order: ByteOrder) -> u32 {
+    let values: usize = entries
+        .iter()
+        .map(|entry| entry.data(order).len())
+        .filter(|len| *len > 4)
+        .map(|len| len + len % 2)
+        .sum();
+    (2 + entries.len() * 12 + 4 + values) as u32
+}
+
+fn write_ifd(out: &mut Vec<u8>, order: ByteOrder, mut entries: Vec<Entry>, next_ifd: u32) {
+    entries.sort_by_key(|entry| entry.tag);
+    let start = out.len();
+    let mut value_offset = start + 2 + entries.len() * 12 + 4;
+    let mut values = Vec::new();
+
+    out.extend_from_slice(&order.u16(entries.len() as u16));
+    for entry in &entries {
+        let (kind, count) = entry.kind_and_count();
+        let data = entry.data(order);
+        out.extend_from_slice(&order.u16(entry.tag));
+        out.extend_from_slice(&order.u16(kind));
+        out.extend_from_slice(&order.u32(count));
+        if data.len() <= 4 {
+            let mut inline = [0u8; 4];
+            inline[..data.len()].copy_from_slice(&data);
+            out.extend_from_slice(&inline);
+        } else {
+            out.extend_from_slice(&order.u32(value_offset as u32));
+            value_offset += data.len() + data.len() % 2;
+            values.extend_from_slice(&data);
+            if data.len() % 2 == 1 {
+                values.push(0);
+            }
+        }
+    }
+    out.extend_from_slice(&order.u32(next_ifd));
+    out.extend_from_slice(&values);
+}
+
+fn push_ascii(entries: &mut Vec<Entry>, tag: u16, value: &Option<String>) {
+    if let Some(value) = value {
+        entries.push(Entry::new(tag, Value::Ascii(value.clone())));
+    }
+}
+
+fn push_segment(out: &mut Vec<u8>, marker: u8, parts: &[&[u8]]) -> Result<()> {
+    let len: usize = parts.iter().map(|part| part.len()).sum();
+    if len > MAX_SEGMENT_PAYLOAD {
+        bail!("metadata block is too large for a JPEG segment ({len} bytes)");
+    }
+    out.extend_from_slice(&[0xFF, marker]);
+    out.extend_from_slice(&((len + 2) as u16).to_be_bytes());
+    for part in parts {
+        out.extend_from_slice(part);
+    }
+    Ok(())
+}
+
+/// Wrap IPTC data in the Photoshop image resource (8BIM 0x0404) used by APP13.
+fn photoshop_iptc_resource(iptc: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(iptc.len() + 13);
+    out.extend_from_slice(b"8BIM");
+    out.extend_from_slice(&0x0404u16.to_be_bytes());
+    // Empty Pascal-string name, padded to an even length.
+    out.extend_from_slice(&[0, 0]);
+    out.extend_from_slice(&(iptc.len() as u32).to_be_bytes());
+    out.extend_from_slice(iptc);
+    if iptc.len() % 2 == 1 {
+        out.push(0);
+    }
+    out
+}
+
+fn push_dataset(out: &mut Vec<u8>, record: u8, dataset: u8, data: &[u8]) {
+    out.extend_from_slice(&[0x1C, record, dataset]);
+    out.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    out.extend_from_slice(data);
+}
+
+fn keyword_levels(path: &str) -> impl Iterator<Item = &str> {
+    path.split(['|', '>'])
+        .map(str::trim)
+        .filter(|level| !level.is_empty())
+}
+
+fn xmp_bag(property: &str, items: &[String]) -> String {
+    let items: String = items
+        .iter()
+        .map(|item| format!("<rdf:li>{}</rdf:li>", xml_escape(item)))
+        .collect();
+    format!("   <{property}><rdf:Bag>{items}</rdf:Bag></{property}>\n")
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn truncate_utf8(value: &str, max_bytes: usize) -> &str {
+    if value.len() <= max_bytes {
+        return value;
+    }
+    let mut end = max_bytes;
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    &value[..end]
+}
+
+fn decimal_rational(value: f64) -> (u32, u32) {
+    (
+        (value * 100.0).round().clamp(0.0, u32::MAX as f64) as u32,
+        100,
+    )
+}
+
+fn exposure_rational(seconds: f64) -> (u32, u32) {
+    if seconds < 1.0 {
+        (1, (1.0 / seconds).round().max(1.0) as u32)
+    } else {
+        decimal_rational(seconds)
+    }
+}
+
+fn dms_rationals(degrees: f64) -> Vec<(u32, u32)> {
+    let degrees = degrees.abs();
+    let whole = degrees.trunc();
+    let minutes = ((degrees - whole) * 60.0).trunc();
+    let seconds = ((degrees - whole) * 60.0 - minutes) * 60.0;
+    vec![
+        (whole as u32, 1),
+        (minutes as u32, 1),
+        ((seconds * 100.0).round() as u32, 100),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_image;
+    use crate::services::export::ExportSettings;
+    use chrono::TimeZone;
+    use image::{DynamicImage, ImageOutputFormat, RgbImage};
+    use std::io::Cursor;
+
+    fn sample_image() -> Image {
+        Image {
+            id: 1,
+            captured_at: Some(Utc.with_ymd_and_hms(2024, 5, 17, 9, 30, 0).unwrap()),
+            camera_make: Some("ACME".into()),
+            camera_model: Some("Zoom 5".into()),
+            lens_model: Some("35mm f/1.4".into()),
+            focal_length: Some(35.0),
+            aperture: Some(2.8),
+            shutter_speed: Some(1.0 / 250.0),
+            iso: Some(400),
+            gps_latitude: Some(48.8584),
+            gps_longitude: Some(-2.2945),
+            gps_altitude: Some(35.0),
+            rating: Some(4),
+            ..test_image(1, "/a.jpg")
+        }
+    }
+
+    fn rights() -> RightsInfo {
+        RightsInfo {
+            creator: Some("Ada Example".into()),
+            copyright: Some("© 2024 Ada Example".into()),
+            contact: Some("ada@example.com".into()),
+        }
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack
+            .windows(needle.len())
+            .any(|window| window == needle)
+    }
+
+    fn encode(format: ImageOutputFormat) -> Vec<u8> {
+        let mut out = Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(8, 6, image::Rgb([10, 20, 30])))
+            .write_to(&mut out, format)
+            .unwrap();
+        out.into_inner()
+    }
+
+    /// Tags in IFD0 of a TIFF stream plus the tags of its EXIF and GPS sub-IFDs.
+    fn tiff_tags(tiff: &[u8]) -> Vec<u16> {
+        let order = if &tiff[0..2] == b"II" {
+            ByteOrder::Little
+        } else {
+            ByteOrder::Big
+        };
+        let read_ifd = |offset: usize| -> Vec<(u16, u32)> {
+            let count = order.read_u16(tiff, offset).unwrap() as usize;
+            (0..count)
+                .map(|idx| {
+                    let at = offset + 2 + idx * 12;
+                    (
+                        order.read_u16(tiff, at).unwrap(),
+                        order.read_u32(tiff, at + 8).unwrap(),
+                    )
+                })
+                .collect()
+        };
+        let ifd0 = read_ifd(order.read_u32(tiff, 4).unwrap() as usize);
+        let mut tags: Vec<u16> = ifd0.iter().map(|(tag, _)| *tag).collect();
+        for (tag, value) in &ifd0 {
+            if *tag == TAG_EXIF_IFD || *tag == TAG_GPS_IFD {
+                tags.extend(read_ifd(*value as usize).iter().map(|(tag, _)| *tag));
+            }
+        }
+        tags
+    }
+
+    #[test]
+    fn policies_select_fields() {
+        let image = sample_image();
+        let keywords = vec!["Places|France|Paris".to_string(), "travel".to_string()];
+        let serial = Some("SN123".to_string());
+
+        let strip = ExportMetadata::collect(
+            MetadataPolicy::Strip,
+            &rights(),
+            &image,
+            serial.clone(),
+            &keywords,
+        );
+        assert!(strip.is_empty());
+
+        let copyright = ExportMetadata::collect(
+            MetadataPolicy::CopyrightOnly,
+            &rights(),
+            &image,
+            serial.clone(),
+            &keywords,
+        );
+        assert_eq!(copyright.creator.as_deref(), Some("Ada Example"));
+        assert!(copyright.camera_make.is_none() && copyright.keywords.is_empty());
+
+        let no_location = ExportMetadata::collect(
+            MetadataPolicy::AllExceptLocation,
+            &rights(),
+            &image,
+            serial.clone(),
+            &keywords,
+        );
+        assert_eq!(no_location.rating, Some(4));
+        assert!(no_location.gps_latitude.is_none() && no_location.camera_serial.is_none());
+        assert!(no_location.gps_entries().is_empty());
+        // Settings saved before the policy existed get the default, which leaks neither.
+        let settings: ExportSettings = serde_json::from_str(r#"{"quality": 80}"#).unwrap();
+        assert_eq!(settings.metadata, MetadataPolicy::AllExceptLocation);
+
+        let full =
+            ExportMetadata::collect(MetadataPolicy::All, &rights(), &image, serial, &keywords);
+        assert_eq!(full.camera_serial.as_deref(), Some("SN123"));
+        assert_eq!(
+            full.flat_keywords(),
+            vec!["Places", "France", "Paris", "travel"]
+        );
+        assert_eq!(full.hierarchical_keywords(), vec!["Places|France|Paris"]);
+        let xmp = full.xmp();
+        assert!(xmp.contains("<xmp:Rating>4</xmp:Rating>"));
+        assert!(xmp.contains("<rdf:li>Places|France|Paris</rdf:li>"));
+        assert!(xmp.contains("CiEmailWork"));
+        assert_eq!(dms_rationals(-2.2945), vec![(2, 1), (17, 1), (4020, 100)]);
+    }
+
+    #[test]
+    fn embeds_blocks_in_jpeg_and_tiff() {
+        let image = sample_image();
+        let keywords = vec!["Places>France".to_string()];
+
+        let full = ExportMetadata::collect(
+            MetadataPolicy::All,
+            &rights(),
+            &image,
+            Some("SN123".into()),
+            &keywords,
+        );
+        let jpeg = embed_in_jpeg(&encode(ImageOutputFormat::Jpeg(80)), &full).unwrap();
+        assert!(contains(&jpeg, b"Exif\0\0MM"));
+        assert!(contains(&jpeg, XMP_HEADER));
+        assert!(contains(&jpeg, b"Photoshop 3.0\08BIM"));
+        assert!(contains(&jpeg, b"SN123"));
+        assert!(contains(&jpeg, "© 2024 Ada Example".as_bytes()));
+        let decoded = image::load_from_memory(&jpeg).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (8, 6));
+
+        let private_free = ExportMetadata::collect(
+            MetadataPolicy::AllExceptLocation,
+            &rights(),
+            &image,
+            Some("SN123".into()),
+            &keywords,
+        );
+        let tiff = embed_in_tiff(&encode(ImageOutputFormat::Tiff), &private_free).unwrap();
+        let tags = tiff_tags(&tiff);
+        for tag in [
+            TAG_MAKE,
+            TAG_ARTIST,
+            TAG_COPYRIGHT,
+            TAG_XMP,
+            TAG_IPTC,
+            TAG_DATE_TIME_ORIGINAL,
+        ] {
+            assert!(tags.contains(&tag), "missing tag {tag}");
+        }
+        assert!(!tags.contains(&TAG_GPS_IFD) && !tags.contains(&TAG_BODY_SERIAL));
+        assert!(!contains(&tiff, b"SN123"));
+        let decoded = image::load_from_memory(&tiff).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (8, 6));
+
+        let untouched = encode(ImageOutputFormat::Tiff);
+        assert_eq!(
+            embed_in_tiff(&untouched, &ExportMetadata::default()).unwrap(),
+            untouched
+        );
+    }
+
+    #[test]
+    fn drops_keywords_that_overflow_a_jpeg_segment() {
+        let keywords: Vec<String> = (0..4000)
+            .map(|idx| format!("Places|Region {idx}|Spot {idx}"))
+            .collect();
+        let mut metadata = ExportMetadata::collect(
+            MetadataPolicy::All,
+            &rights(),
+            &sample_image(),
+            None,
+            &keywords,
+        );
+        let jpeg = encode(ImageOutputFormat::Jpeg(80));
+        assert!(embed_in_jpeg(&jpeg, &metadata).is_err());
+
+        let dropped = metadata.fit_jpeg_segments();
+        assert!(dropped > 0 && dropped < keywords.len());
+        assert_eq!(metadata.keywords, keywords[..keywords.len() - dropped]);
+        let embedded = embed_in_jpeg(&jpeg, &metadata).unwrap();
+        assert!(contains(&embedded, "© 2024 Ada Example".as_bytes()));
+        assert!(image::load_from_memory(&embedded).is_ok());
+        assert_eq!(metadata.fit_jpeg_segments(), 0);
+    }
+}
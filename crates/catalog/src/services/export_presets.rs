@@ -0,0 +1,237 @@
+//! Named export presets stored in the catalog and shared between machines as JSON files.
+//!
+//! Presets persist the whole serialized [`ExportSettings`], so options added to the
+//! export pipeline later are saved without schema changes. Names are unique without
+//! regard to case; saving under an existing name replaces that preset. Shared files
+//! also carry the watermark definitions their presets reference.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::db::{DbHandle, ExportPreset};
+use crate::services::export::{validate_settings, ExportSettings};
+use crate::services::watermarks::{self, WatermarkDefinition};
+
+/// Version written to shared preset files.
+pub const PRESET_FILE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NamedExportPreset {
+    pub name: String,
+    /// Default export folder; callers may still override it per export.
+    #[serde(default)]
+    pub destination: Option<PathBuf>,
+    #[serde(default)]
+    pub settings: ExportSettings,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PresetFile {
+    version: u32,
+    presets: Vec<NamedExportPreset>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    watermarks: Vec<WatermarkDefinition>,
+}
+
+pub fn list_presets<H: DbHandle>(db: &H) -> Result<Vec<NamedExportPreset>> {
+    ExportPreset::load_all(db)
+        .context("failed to list export presets")?
+        .into_iter()
+        .map(from_record)
+        .collect()
+}
+
+pub fn find_preset<H: DbHandle>(db: &H, name: &str) -> Result<Option<NamedExportPreset>> {
+    ExportPreset::find_by_name(db, name.trim())?
+        .map(from_record)
+        .transpose()
+}
+
+/// Insert `preset`, or replace the stored preset with the same name.
+pub fn save_preset<H: DbHandle>(db: &H, preset: &NamedExportPreset) -> Result<()> {
+    let name = preset.name.trim();
+    if name.is_empty() {
+        bail!("export preset name cannot be empty");
+    }
+    validate_settings(&preset.settings)
+        .with_context(|| format!("invalid settings in export preset {name}"))?;
+    if let Some(watermark) = &preset.settings.watermark {
+        if watermarks::find_watermark(db, watermark)?.is_none() {
+            bail!("export preset {name} uses unknown watermark {watermark}");
+        }
+    }
+
+    let settings_json =
+        serde_json::to_value(&preset.settings).context("failed to serialize export settings")?;
+    let destination = preset
+        .destination
+        .as_ref()
+        .map(|path| path.to_string_lossy().into_owned());
+    let now = Utc::now();
+
+    match ExportPreset::find_by_name(db, name)? {
+        Some(mut existing) => {
+            existing.name = name.to_string();
+            existing.settings_json = settings_json;
+            existing.destination = destination;
+            existing.updated_at = now;
+            existing.update(db)
+        }
+        None => ExportPreset {
+            id: 0,
+            name: name.to_string(),
+            settings_json,
+            destination,
+            created_at: now,
+            updated_at: now,
+        }
+        .insert(db)
+        .map(|_| ()),
+    }
+}
+
+/// Returns false when no preset has that name.
+pub fn delete_preset<H: DbHandle>(db: &H, name: &str) -> Result<bool> {
+    let Some(existing) = ExportPreset::find_by_name(db, name.trim())? else {
+        return Ok(false);
+    };
+    ExportPreset::delete(db, existing.id)?;
+    Ok(true)
+}
+
+pub fn write_preset_file(
+    path: &Path,
+    presets: &[NamedExportPreset],
+    watermarks: &[WatermarkDefinition],
+) -> Result<()> {
+    let file = PresetFile {
+        version: PRESET_FILE_VERSION,
+        presets: presets.to_vec(),
+        watermarks: watermarks.to_vec(),
+    };
+    let json = serde_json::to_string_pretty(&file).context("failed to serialize presets")?;
+    fs::write(path, json).with_context(|| format!("failed to write preset file {}", path.display()))
+}
+
+/// Read a preset file and the watermarks bundled with it; a bare preset object is
+/// accepted as a file holding one preset.
+pub fn read_preset_file(path: &Path) -> Result<(Vec<NamedExportPreset>, Vec<WatermarkDefinition>)> {
+    let raw = fs::read_to_string(path)
+        .with_context(|| format!("failed to read preset file {}", path.display()))?;
+    let value: serde_json::Value = serde_json::from_str(&raw)
+        .with_context(|| format!("{} is not valid JSON", path.display()))?;
+
+    let contents = if value.get("presets").is_some() {
+        let file: PresetFile = serde_json::from_value(value)
+            .with_context(|| format!("{} is not an export preset file", path.display()))?;
+        if file.version > PRESET_FILE_VERSION {
+            bail!(
+                "{} uses preset file version {}, newer than supported version {}",
+                path.display(),
+                file.version,
+                PRESET_FILE_VERSION
+            );
+        }
+        (file.presets, file.watermarks)
+    } else {
+        let preset = serde_json::from_value(value)
+            .with_context(|| format!("{} is not an export preset", path.display()))?;
+        (vec![preset], Vec::new())
+    };
+    Ok(contents)
+}
+
+fn from_record(record: ExportPreset) -> Result<NamedExportPreset> {
+    let settings = serde_json::from_value(record.settings_json)
+        .with_context(|| format!("export preset {} has unreadable settings", record.name))?;
+    Ok(NamedExportPreset {
+        name: record.name,
+        destination: record.destination.map(PathBuf::from),
+        settings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::CatalogDb;
+    use crate::schema::initialize_schema;
+    use crate::services::export::{ExportFormat, ResizeMode};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn presets_round_trip_through_catalog_and_files() {
+        let db = CatalogDb::in_memory().unwrap();
+        initialize_schema(db.conn()).unwrap();
+
+        let web = NamedExportPreset {
+            name: "Web 2048px".into(),
+            destination: Some(PathBuf::from("/exports/web")),
+            settings: ExportSettings {
+                quality: 85,
+                resize: ResizeMode::LongEdge { pixels: 2048 },
+                ..ExportSettings::default()
+            },
+        };
+        save_preset(&db, &web).unwrap();
+
+        let mut print = NamedExportPreset {
+            name: "Print TIFF".into(),
+            destination: None,
+            settings: ExportSettings {
+                format: ExportFormat::Tiff,
+                ..ExportSettings::default()
+            },
+        };
+        save_preset(&db, &print).unwrap();
+
+        // Saving under the same name in another case replaces the preset.
+        print.name = "print tiff".into();
+        print.settings.filename_template = "{date}-{seq:3}".into();
+        save_preset(&db, &print).unwrap();
+
+        let names: Vec<_> = list_presets(&db)
+            .unwrap()
+            .into_iter()
+            .map(|p| p.name)
+            .collect();
+        assert_eq!(names, vec!["print tiff", "Web 2048px"]);
+        assert_eq!(find_preset(&db, "WEB 2048PX").unwrap(), Some(web.clone()));
+
+        let invalid = NamedExportPreset {
+            name: "Broken".into(),
+            destination: None,
+            settings: ExportSettings {
+                filename_template: "{nope}".into(),
+                ..ExportSettings::default()
+            },
+        };
+        assert!(save_preset(&db, &invalid).is_err());
+
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let path = std::env::temp_dir().join(format!("catalog_presets_{suffix}.json"));
+        write_preset_file(&path, &list_presets(&db).unwrap(), &[]).unwrap();
+
+        let other = CatalogDb::in_memory().unwrap();
+        initialize_schema(other.conn()).unwrap();
+        let (presets, watermarks) = read_preset_file(&path).unwrap();
+        assert!(watermarks.is_empty());
+        for preset in presets {
+            save_preset(&other, &preset).unwrap();
+        }
+        assert_eq!(list_presets(&other).unwrap(), list_presets(&db).unwrap());
+
+        assert!(delete_preset(&other, "Web 2048px").unwrap());
+        assert!(!delete_preset(&other, "Web 2048px").unwrap());
+        assert_eq!(list_presets(&other).unwrap().len(), 1);
+
+        fs::remove_file(path).ok();
+    }
+}
@@ -0,0 +1,541 @@
+//! Migration of other applications' catalogs into ZenithPhoto.
+//!
+//! Readers for Lightroom, darktable and digiKam translate a foreign catalog into an
+//! [`ExternalCatalog`], and [`apply_external_catalog`] writes it through
+//! `CatalogService`. Photos are referenced where they are; files on disconnected
+//! drives are still cataloged so their ratings and keywords are not lost. Running a
+//! migration again updates the images it created earlier instead of duplicating them.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rusqlite::{Connection, OpenFlags};
+use serde::Serialize;
+
+use crate::db::{in_savepoint, query_all, query_optional, Collection};
+use crate::services::catalog_service::{CatalogService, Edits};
+use crate::services::import::{ImportCallbacks, ImportProgress, ImportStage};
+use crate::services::{darktable, digikam, lightroom};
+
+/// Applications whose catalogs can be migrated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExternalSource {
+    /// Lightroom Classic `.lrcat`.
+    Lightroom,
+    /// darktable `library.db`; tags are read from the `data.db` next to it.
+    Darktable,
+    /// digiKam `digikam4.db`.
+    Digikam,
+}
+
+impl ExternalSource {
+    pub fn label(self) -> &'static str {
+        match self {
+            ExternalSource::Lightroom => "Lightroom Classic",
+            ExternalSource::Darktable => "darktable",
+            ExternalSource::Digikam => "digiKam",
+        }
+    }
+
+    /// Read the source catalog at `path` without modifying it.
+    pub fn read(self, path: &Path) -> Result<ExternalCatalog> {
+        match self {
+            ExternalSource::Lightroom => lightroom::read_lightroom_catalog(path),
+            ExternalSource::Darktable => darktable::read_darktable_library(path),
+            ExternalSource::Digikam => digikam::read_digikam_database(path),
+        }
+    }
+}
+
+/// One photo as described by the source catalog.
+#[derive(Debug, Clone, Default)]
+pub struct ExternalImage {
+    /// The source catalog's own id, used to resolve collection membership.
+    pub source_id: i64,
+    pub path: PathBuf,
+    pub captured_at: Option<DateTime<Utc>>,
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub lens_model: Option<String>,
+    pub focal_length: Option<f64>,
+    /// F-number.
+    pub aperture: Option<f64>,
+    /// Exposure time in seconds.
+    pub shutter_speed: Option<f64>,
+    pub iso: Option<i64>,
+    /// EXIF orientation, 1-8.
+    pub orientation: Option<i64>,
+    pub gps_latitude: Option<f64>,
+    pub gps_longitude: Option<f64>,
+    pub gps_altitude: Option<f64>,
+    pub rating: Option<i64>,
+    /// `picked` or `rejected`.
+    pub flag: Option<String>,
+    /// One of the Folio label names (`red`, `yellow`, `green`, `blue`, `purple`).
+    pub color_label: Option<String>,
+    /// Hierarchical keywords use `|` between levels ("Places|France|Paris").
+    pub keywords: Vec<String>,
+    pub edits: Option<Edits>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ExternalCollection {
+    pub source_id: i64,
+    pub name: String,
+    /// `source_id` of the containing collection or collection set.
+    pub parent: Option<i64>,
+    /// `source_id`s of the member images.
+    pub image_ids: Vec<i64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ExternalCatalog {
+    pub images: Vec<ExternalImage>,
+    pub collections: Vec<ExternalCollection>,
+    /// Source features that have no ZenithPhoto equivalent, with how often they occur.
+    pub unmapped: BTreeMap<String, usize>,
+}
+
+impl ExternalCatalog {
+    /// Record one occurrence of a feature that could not be migrated.
+    pub fn note_unmapped(&mut self, feature: impl Into<String>) {
+        *self.unmapped.entry(feature.into()).or_default() += 1;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageChangeKind {
+    New,
+    /// New, but the file was not found on disk.
+    Offline,
+    Updated,
+}
+
+/// What a migration did, or would do, to one image.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageChange {
+    pub path: PathBuf,
+    pub kind: ImageChangeKind,
+    /// Human-readable field changes, e.g. `rating: 3 -> 4` or `keyword: +Travel`.
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ExternalImportReport {
+    /// Nothing was written; the report describes what the migration would do.
+    pub dry_run: bool,
+    /// Images newly added to the catalog.
+    pub imported: usize,
+    /// Images that were already cataloged and had their metadata updated.
+    pub updated: usize,
+    /// Images cataloged without their file, which was not found on disk.
+    pub offline: Vec<PathBuf>,
+    pub collections: usize,
+    pub keywords: usize,
+    /// Images that received develop settings.
+    pub edits: usize,
+    pub unmapped: BTreeMap<String, usize>,
+    /// New images and images whose metadata changed; unchanged images are left out.
+    pub changes: Vec<ImageChange>,
+    pub failed: Vec<(PathBuf, String)>,
+    pub canceled: bool,
+}
+
+/// Read the catalog at `path` and migrate it into the catalog behind `service`.
+pub fn migrate_external_catalog(
+    service: &CatalogService,
+    source: ExternalSource,
+    path: &Path,
+    callbacks: &ImportCallbacks,
+    dry_run: bool,
+) -> Result<ExternalImportReport> {
+    let catalog = source.read(path)?;
+    apply_external_catalog(service, &catalog, callbacks, dry_run)
+}
+
+/// Write `catalog` into the ZenithPhoto catalog behind `service` in one transaction.
+///
+/// Progress is reported in the `Cataloging` stage; per-image failures are rolled back and
+/// go to `callbacks.on_error` and the report while the rest of the migration continues.
+/// With `dry_run` the same work is done and then rolled back, so the report is exact.
+pub fn apply_external_catalog(
+    service: &CatalogService,
+    catalog: &ExternalCatalog,
+    callbacks: &ImportCallbacks,
+    dry_run: bool,
+) -> Result<ExternalImportReport> {
+    let tx = service
+        .db
+        .conn()
+        .unchecked_transaction()
+        .context("failed to start migration transaction")?;
+    let mut report = ExternalImportReport {
+        dry_run,
+        unmapped: catalog.unmapped.clone(),
+        ..ExternalImportReport::default()
+    };
+    let mut image_ids: HashMap<i64, i64> = HashMap::new();
+    let mut keywords: HashSet<&str> = HashSet::new();
+    let imported_at = Utc::now();
+    let total = catalog.images.len();
+
+    for (idx, source) in catalog.images.iter().enumerate() {
+        if callbacks.cancel.is_canceled() {
+            report.canceled = true;
+            break;
+        }
+        if let Some(cb) = &callbacks.progress {
+            cb(ImportProgress {
+                stage: ImportStage::Cataloging,
+                completed: idx,
+                total,
+                message: Some(source.path.display().to_string()),
+            });
+        }
+
+        let applied = in_savepoint(service.db.conn(), || {
+            apply_image(service, source, imported_at, &mut report)
+        });
+        match applied {
+            Ok(image_id) => {
+                image_ids.insert(source.source_id, image_id);
+                keywords.extend(source.keywords.iter().map(String::as_str));
+            }
+            Err(err) => {
+                let message = format!("{err:#}");
+                if let Some(cb) = &callbacks.on_error {
+                    cb(source.path.clone(), message.clone());
+                }
+                report.failed.push((source.path.clone(), message));
+            }
+        }
+    }
+    report.keywords = keywords.len();
+
+    if !report.canceled {
+        report.collections = apply_collections(service, &catalog.collections, &image_ids)?;
+    }
+    if dry_run {
+        tx.rollback().context("failed to roll back dry run")?;
+    } else {
+        tx.commit().context("failed to commit migration")?;
+    }
+
+    if let Some(cb) = &callbacks.progress {
+        cb(ImportProgress {
+            stage: ImportStage::Cataloging,
+            completed: report.imported + report.updated + report.failed.len(),
+            total,
+            message: None,
+        });
+    }
+    Ok(report)
+}
+
+fn apply_image(
+    service: &CatalogService,
+    source: &ExternalImage,
+    imported_at: DateTime<Utc>,
+    report: &mut ExternalImportReport,
+) -> Result<i64> {
+    let (mut image, kind) = match service.find_image_by_original_path(&source.path)? {
+        Some(existing) => (existing, ImageChangeKind::Updated),
+        None if source.path.is_file() => (
+            service.import_image_at(&source.path, imported_at)?,
+            ImageChangeKind::New,
+        ),
+        None => (
+            service.register_offline_image(&source.path, imported_at)?,
+            ImageChangeKind::Offline,
+        ),
+    };
+    let before = image.clone();
+    let existing_keywords: HashSet<String> = service
+        .load_metadata(image.id)?
+        .keywords
+        .into_iter()
+        .collect();
+
+    // Values from the source win, but never erase what the catalog already knows.
+    image.captured_at = source.captured_at.or(image.captured_at);
+    image.camera_make = source.camera_make.clone().or(image.camera_make.take());
+    image.camera_model = source.camera_model.clone().or(image.camera_model.take());
+    image.lens_model = source.lens_model.clone().or(image.lens_model.take());
+    image.focal_length = source.focal_length.or(image.focal_length);
+    image.aperture = source.aperture.or(image.aperture);
+    image.shutter_speed = source.shutter_speed.or(image.shutter_speed);
+    image.iso = source.iso.or(image.iso);
+    image.orientation = source.orientation.or(image.orientation);
+    image.gps_latitude = source.gps_latitude.or(image.gps_latitude);
+    image.gps_longitude = source.gps_longitude.or(image.gps_longitude);
+    image.gps_altitude = source.gps_altitude.or(image.gps_altitude);
+    image.rating = source.rating.or(image.rating);
+    image.flag = source.flag.clone().or(image.flag.take());
+    image.color_label = source.color_label.clone().or(image.color_label.take());
+    image.updated_at = Utc::now();
+    image.update(&service.db)?;
+
+    let mut fields = Vec::new();
+    let mut note = |name: &str, old: Option<String>, new: Option<String>| {
+        if old != new {
+            let show = |value: Option<String>| value.unwrap_or_else(|| "none".to_string());
+            fields.push(format!("{name}: {} -> {}", show(old), show(new)));
+        }
+    };
+    note(
+        "captured",
+        before.captured_at.map(|t| t.to_rfc3339()),
+        image.captured_at.map(|t| t.to_rfc3339()),
+    );
+    note(
+        "rating",
+        before.rating.map(|r| r.to_string()),
+        image.rating.map(|r| r.to_string()),
+    );
+    note("flag", before.flag.clone(), image.flag.clone());
+    note(
+        "label",
+        before.color_label.clone(),
+        image.color_label.clone(),
+    );
+    if (before.gps_latitude, before.gps_longitude) != (image.gps_latitude, image.gps_longitude) {
+        fields.push("location".to_string());
+    }
+    if before.camera_model != image.camera_model || before.lens_model != image.lens_model {
+        fields.push("camera and lens".to_string());
+    }
+
+    for keyword in &source.keywords {
+        if !existing_keywords.contains(keyword.trim()) {
+            fields.push(format!("keyword: +{keyword}"));
+        }
+        service.add_keyword_to_image(image.id, keyword)?;
+    }
+    if let Some(edits) = &source.edits {
+        service.apply_edits(
+            image.id,
+            Edits {
+                image_id: image.id,
+                ..edits.clone()
+            },
+        )?;
+        report.edits += 1;
+        fields.push("develop settings".to_string());
+    }
+
+    match kind {
+        ImageChangeKind::New => report.imported += 1,
+        ImageChangeKind::Offline => {
+            report.imported += 1;
+            report.offline.push(source.path.clone());
+        }
+        ImageChangeKind::Updated => report.updated += 1,
+    }
+    if kind != ImageChangeKind::Updated || !fields.is_empty() {
+        report.changes.push(ImageChange {
+            path: source.path.clone(),
+            kind,
+            fields,
+        });
+    }
+    Ok(image.id)
+}
+
+/// Create collections parents-first, reusing same-named ones under the same parent.
+fn apply_collections(
+    service: &CatalogService,
+    collections: &[ExternalCollection],
+    image_ids: &HashMap<i64, i64>,
+) -> Result<usize> {
+    let mut writer = CollectionWriter {
+        service,
+        by_source: collections.iter().map(|c| (c.source_id, c)).collect(),
+        image_ids,
+        existing: Collection::load_all(&service.db).context("failed to list collections")?,
+        created: HashMap::new(),
+    };
+    for collection in collections {
+        writer.ensure(collection.source_id, 0)?;
+    }
+    Ok(writer.created.len())
+}
+
+struct CollectionWriter<'a> {
+    service: &'a CatalogService,
+    by_source: HashMap<i64, &'a ExternalCollection>,
+    image_ids: &'a HashMap<i64, i64>,
+    existing: Vec<Collection>,
+    /// Source collection id to catalog collection id.
+    created: HashMap<i64, i64>,
+}
+
+impl CollectionWriter<'_> {
+    fn ensure(&mut self, source_id: i64, depth: usize) -> Result<Option<i64>> {
+        if let Some(id) = self.created.get(&source_id) {
+            return Ok(Some(*id));
+        }
+        let Some(entry) = self.by_source.get(&source_id).copied() else {
+            return Ok(None);
+        };
+        // The depth limit guards against parent cycles in a damaged source catalog.
+        let parent_id = match entry.parent {
+            Some(parent) if depth < 64 => self.ensure(parent, depth + 1)?,
+            _ => None,
+        };
+
+        let found = self
+            .existing
+            .iter()
+            .find(|c| c.name == entry.name && c.parent_id == parent_id)
+            .map(|c| c.id);
+        let id = match found {
+            Some(id) => id,
+            None => {
+                let now = Utc::now();
+                let mut collection = Collection {
+                    id: 0,
+                    name: entry.name.clone(),
+                    parent_id,
+                    created_at: now,
+                    updated_at: now,
+                };
+                collection.id = collection.insert(&self.service.db)?;
+                let id = collection.id;
+                self.existing.push(collection);
+                id
+            }
+        };
+        self.created.insert(source_id, id);
+
+        for source_image in &entry.image_ids {
+            if let Some(image_id) = self.image_ids.get(source_image) {
+                Collection::add_image(&self.service.db, id, *image_id)?;
+            }
+        }
+        Ok(Some(id))
+    }
+}
+
+/// Open a foreign SQLite catalog without risking changes to it.
+pub(crate) fn open_read_only(path: &Path, kind: &str) -> Result<Connection> {
+    Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .with_context(|| format!("failed to open {kind} {}", path.display()))
+}
+
+pub(crate) fn table_exists(conn: &Connection, name: &str) -> Result<bool> {
+    Ok(query_optional(
+        conn,
+        "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [name],
+        |_| Ok(()),
+    )?
+    .is_some())
+}
+
+/// The unmapped-feature label for a file the importer cannot catalog.
+pub(crate) fn unsupported_type(path: &Path) -> String {
+    let kind = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    format!("Unsupported file type .{kind}")
+}
+
+/// Parse a capture time as stored by the source applications.
+///
+/// Most of them store the camera's wall-clock time without a zone; that time is kept
+/// as is, the same way the importer treats EXIF dates.
+pub(crate) fn parse_capture_time(raw: &str) -> Option<DateTime<Utc>> {
+    let raw = raw.trim();
+    if let Ok(parsed) = DateTime::parse_from_rfc3339(raw) {
+        return Some(parsed.with_timezone(&Utc));
+    }
+    [
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M:%S%.f",
+        "%Y:%m:%d %H:%M:%S%.f",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(raw, format).ok())
+    .map(|naive| naive.and_utc())
+}
+
+/// Column names of `table`, for schemas that changed between application versions.
+pub(crate) fn table_columns(conn: &Connection, table: &str) -> Result<HashSet<String>> {
+    query_all(
+        conn,
+        "SELECT name FROM pragma_table_info(?1)",
+        [table],
+        |row| Ok(row.get(0)?),
+    )
+    .map(|names| names.into_iter().collect())
+    .with_context(|| format!("failed to read columns of {table}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{CatalogDb, Keyword};
+
+    #[test]
+    fn failed_images_are_rolled_back() {
+        let service = CatalogService::new(CatalogDb::in_memory().unwrap());
+        // Edits can't be stored, so the first image fails after its rating and keyword
+        // were written.
+        service
+            .db
+            .conn()
+            .execute_batch(
+                "CREATE TEMP TRIGGER no_edits BEFORE INSERT ON edits
+                 BEGIN SELECT RAISE(ABORT, 'edits are read-only'); END;",
+            )
+            .unwrap();
+        let edited = PathBuf::from("/offline/edited.nef");
+        let plain = PathBuf::from("/offline/plain.nef");
+        let catalog = ExternalCatalog {
+            images: vec![
+                ExternalImage {
+                    source_id: 1,
+                    path: edited.clone(),
+                    rating: Some(4),
+                    keywords: vec!["Trip".to_string()],
+                    edits: Some(
+                        serde_json::from_str(r#"{"id": 0, "image_id": 0, "exposure": 0.5}"#)
+                            .unwrap(),
+                    ),
+                    ..ExternalImage::default()
+                },
+                ExternalImage {
+                    source_id: 2,
+                    path: plain.clone(),
+                    rating: Some(2),
+                    ..ExternalImage::default()
+                },
+            ],
+            ..ExternalCatalog::default()
+        };
+
+        let report =
+            apply_external_catalog(&service, &catalog, &ImportCallbacks::default(), false).unwrap();
+        let failed: Vec<&PathBuf> = report.failed.iter().map(|(path, _)| path).collect();
+        assert_eq!(failed, [&edited]);
+        assert_eq!((report.imported, report.edits), (1, 0));
+        assert!(service
+            .find_image_by_original_path(&edited)
+            .unwrap()
+            .is_none());
+        assert!(Keyword::load_all(&service.db).unwrap().is_empty());
+        let image = service
+            .find_image_by_original_path(&plain)
+            .unwrap()
+            .unwrap();
+        assert_eq!(image.rating, Some(2));
+    }
+}
@@ -0,0 +1,589 @@
+//! Self-contained static HTML galleries for client proofing.
+//!
+//! A gallery folder holds `index.html` (styles, script and image list inlined so it
+//! opens straight from disk), resized images under `images/`, thumbnails under
+//! `thumbs/` and a `manifest.json`. Clients mark picks in the browser and download a
+//! `picks.json`, which [`import_gallery_picks`] applies to the catalog as
+//! `flag = 'picked'`.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use engine::ImageEngine;
+use serde::{Deserialize, Serialize};
+
+use crate::services::catalog_service::CatalogService;
+use crate::services::export::{
+    export_images, ExportCallbacks, ExportFormat, ExportProgress, ExportSettings, OutputSharpening,
+    ResizeMode,
+};
+use crate::services::export_metadata::{MetadataPolicy, RightsInfo};
+
+/// Version written to gallery manifests and expected in picks files.
+pub const GALLERY_MANIFEST_VERSION: u32 = 1;
+
+const PAGE: &str = include_str!("../../assets/gallery/page.html");
+const BASE_STYLE: &str = include_str!("../../assets/gallery/base.css");
+const SCRIPT: &str = include_str!("../../assets/gallery/gallery.js");
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GalleryTemplate {
+    /// Light background, square cropped tiles on cards.
+    #[default]
+    Classic,
+    /// Dark background, tight 3:2 tiles with overlaid captions.
+    Dark,
+    /// White masonry columns with captions on hover.
+    Minimal,
+}
+
+impl GalleryTemplate {
+    pub const ALL: [GalleryTemplate; 3] = [
+        GalleryTemplate::Classic,
+        GalleryTemplate::Dark,
+        GalleryTemplate::Minimal,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            GalleryTemplate::Classic => "Classic",
+            GalleryTemplate::Dark => "Dark",
+            GalleryTemplate::Minimal => "Minimal",
+        }
+    }
+
+    fn slug(self) -> &'static str {
+        match self {
+            GalleryTemplate::Classic => "classic",
+            GalleryTemplate::Dark => "dark",
+            GalleryTemplate::Minimal => "minimal",
+        }
+    }
+
+    fn stylesheet(self) -> &'static str {
+        match self {
+            GalleryTemplate::Classic => include_str!("../../assets/gallery/classic.css"),
+            GalleryTemplate::Dark => include_str!("../../assets/gallery/dark.css"),
+            GalleryTemplate::Minimal => include_str!("../../assets/gallery/minimal.css"),
+        }
+    }
+}
+
+/// Which catalog fields make up each image's caption.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GalleryCaptions {
+    pub filename: bool,
+    pub keywords: bool,
+    pub rating: bool,
+}
+
+impl Default for GalleryCaptions {
+    fn default() -> Self {
+        Self {
+            filename: true,
+            keywords: true,
+            rating: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GallerySettings {
+    pub title: String,
+    pub template: GalleryTemplate,
+    /// Long edge of the lightbox images in pixels.
+    pub image_size: u32,
+    /// Long edge of the grid thumbnails in pixels.
+    pub thumbnail_size: u32,
+    /// JPEG quality for images and thumbnails, 1-100.
+    pub quality: u8,
+    pub captions: GalleryCaptions,
+    /// Metadata embedded in the gallery JPEGs; defaults to creator and copyright only.
+    pub metadata: MetadataPolicy,
+    pub rights: RightsInfo,
+    pub sharpening: Option<OutputSharpening>,
+    /// Name of a saved watermark applied to the lightbox images.
+    pub watermark: Option<String>,
+}
+
+impl Default for GallerySettings {
+    fn default() -> Self {
+        Self {
+            title: "Gallery".to_string(),
+            template: GalleryTemplate::default(),
+            image_size: 1600,
+            thumbnail_size: 400,
+            quality: 85,
+            captions: GalleryCaptions::default(),
+            metadata: MetadataPolicy::CopyrightOnly,
+            rights: RightsInfo::default(),
+            sharpening: Some(OutputSharpening::default()),
+            watermark: None,
+        }
+    }
+}
+
+/// Caption fields as shown in the gallery; absent fields were disabled in the settings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GalleryCaption {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keywords: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GalleryEntry {
+    pub image_id: i64,
+    pub filename: String,
+    /// Paths relative to the gallery folder.
+    pub image: String,
+    pub thumbnail: String,
+    pub caption: GalleryCaption,
+    /// Set by hand in an edited manifest; the browser records picks separately.
+    #[serde(default)]
+    pub picked: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GalleryManifest {
+    pub version: u32,
+    pub gallery_id: String,
+    pub title: String,
+    pub generated_at: DateTime<Utc>,
+    pub images: Vec<GalleryEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GalleryReport {
+    pub index: PathBuf,
+    pub manifest: PathBuf,
+    pub images: usize,
+    pub failed: Vec<(i64, String)>,
+    pub canceled: bool,
+}
+
+/// Outcome of applying a picks file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PicksImport {
+    pub picked: Vec<i64>,
+    /// Ids that are not in this catalog, or whose filename is missing or no longer matches.
+    pub skipped: Vec<i64>,
+}
+
+/// Render `image_ids` into a gallery folder at `destination`, replacing earlier output.
+///
+/// Images that fail to render are left out of the gallery and reported.
+pub fn generate_gallery(
+    service: &CatalogService,
+    engine: &ImageEngine,
+    image_ids: &[i64],
+    destination: &Path,
+    settings: &GallerySettings,
+    callbacks: ExportCallbacks,
+) -> Result<GalleryReport> {
+    if settings.title.trim().is_empty() {
+        bail!("gallery title cannot be empty");
+    }
+    if settings.image_size == 0 || settings.thumbnail_size == 0 {
+        bail!("gallery image and thumbnail sizes must be positive");
+    }
+    let mut seen = HashSet::new();
+    let ids: Vec<i64> = image_ids
+        .iter()
+        .copied()
+        .filter(|id| seen.insert(*id))
+        .collect();
+    if ids.is_empty() {
+        bail!("no images to put in the gallery");
+    }
+
+    let base = ExportSettings {
+        format: ExportFormat::Jpeg,
+        quality: settings.quality,
+        filename_template: "{seq:4}".to_string(),
+        overwrite: true,
+        metadata: settings.metadata,
+        rights: settings.rights.clone(),
+        sharpening: settings.sharpening,
+        ..ExportSettings::default()
+    };
+    let full = ExportSettings {
+        resize: ResizeMode::LongEdge {
+            pixels: settings.image_size,
+        },
+        watermark: settings.watermark.clone(),
+        ..base.clone()
+    };
+    let thumbs = ExportSettings {
+        resize: ResizeMode::LongEdge {
+            pixels: settings.thumbnail_size,
+        },
+        metadata: MetadataPolicy::Strip,
+        ..base
+    };
+
+    // Report progress across both passes as one run.
+    let total = ids.len() * 2;
+    let pass_callbacks = |offset: usize, label: &'static str| ExportCallbacks {
+        progress: callbacks.progress.clone().map(|cb| {
+            let forward: Box<dyn Fn(ExportProgress)> = Box::new(move |progress: ExportProgress| {
+                cb(ExportProgress {
+                    completed: offset + progress.completed,
+                    total,
+                    message: progress.message.map(|_| {
+                        format!("{label} {} of {}", progress.completed + 1, progress.total)
+                    }),
+                })
+            });
+            Arc::from(forward)
+        }),
+        on_error: callbacks.on_error.clone(),
+        cancel: callbacks.cancel.clone(),
+    };
+
+    // Clear the previous run so images dropped from the gallery do not linger.
+    let images_dir = destination.join("images");
+    let thumbs_dir = destination.join("thumbs");
+    for dir in [&images_dir, &thumbs_dir] {
+        if dir.exists() {
+            fs::remove_dir_all(dir)
+                .with_context(|| format!("failed to clear {}", dir.display()))?;
+        }
+    }
+    let full_report = export_images(
+        service,
+        engine,
+        &ids,
+        &images_dir,
+        &full,
+        pass_callbacks(0, "Rendering image"),
+    )?;
+    let rendered: Vec<i64> = full_report.exported.iter().map(|f| f.image_id).collect();
+    let thumb_report = export_images(
+        service,
+        engine,
+        &rendered,
+        &thumbs_dir,
+        &thumbs,
+        pass_callbacks(ids.len(), "Rendering thumbnail"),
+    )?;
+
+    let mut failed = full_report.failed;
+    failed.extend(thumb_report.failed);
+    let mut entries = Vec::new();
+    for file in &full_report.exported {
+        let Some(thumb) = thumb_report
+            .exported
+            .iter()
+            .find(|t| t.image_id == file.image_id)
+        else {
+            continue;
+        };
+        let details = service.load_metadata(file.image_id)?;
+        let caption = GalleryCaption {
+            filename: settings
+                .captions
+                .filename
+                .then(|| details.image.filename.clone()),
+            keywords: if settings.captions.keywords {
+                details.keywords
+            } else {
+                Vec::new()
+            },
+            rating: details
+                .image
+                .rating
+                .filter(|rating| settings.captions.rating && *rating > 0),
+        };
+        entries.push(GalleryEntry {
+            image_id: file.image_id,
+            filename: details.image.filename,
+            image: relative_url("images", &file.path),
+            thumbnail: relative_url("thumbs", &thumb.path),
+            caption,
+            picked: false,
+        });
+    }
+
+    let generated_at = Utc::now();
+    let manifest = GalleryManifest {
+        version: GALLERY_MANIFEST_VERSION,
+        gallery_id: gallery_id(&settings.title, generated_at),
+        title: settings.title.trim().to_string(),
+        generated_at,
+        images: entries,
+    };
+    let manifest_path = destination.join("manifest.json");
+    let json = serde_json::to_string_pretty(&manifest).context("failed to serialize manifest")?;
+    fs::write(&manifest_path, json)
+        .with_context(|| format!("failed to write {}", manifest_path.display()))?;
+
+    let index_path = destination.join("index.html");
+    fs::write(&index_path, render_page(&manifest, settings.template)?)
+        .with_context(|| format!("failed to write {}", index_path.display()))?;
+
+    Ok(GalleryReport {
+        index: index_path,
+        manifest: manifest_path,
+        images: manifest.images.len(),
+        failed,
+        canceled: full_report.canceled || thumb_report.canceled,
+    })
+}
+
+/// Flag the images listed in a client's `picks.json` (or a manifest with `picked`
+/// entries) as picked. Entries without a filename, or whose filename does not match the
+/// catalog, are skipped so a picks file from another catalog cannot flag unrelated photos.
+pub fn import_gallery_picks(service: &CatalogService, path: &Path) -> Result<PicksImport> {
+    let raw = fs::read_to_string(path)
+        .with_context(|| format!("failed to read picks file {}", path.display()))?;
+    let file: PicksFile = serde_json::from_str(&raw)
+        .with_context(|| format!("{} is not a gallery picks file", path.display()))?;
+    if file.version > GALLERY_MANIFEST_VERSION {
+        bail!(
+            "{} uses gallery version {}, newer than supported version {}",
+            path.display(),
+            file.version,
+            GALLERY_MANIFEST_VERSION
+        );
+    }
+
+    let chosen: Vec<PickEntry> = match (file.picks, file.images) {
+        (Some(picks), _) => picks,
+        (None, Some(images)) => images
+            .into_iter()
+            .filter(|entry| entry.picked)
+            .map(|entry| PickEntry {
+                image_id: entry.image_id,
+                filename: Some(entry.filename),
+            })
+            .collect(),
+        (None, None) => bail!("{} has no picks or images", path.display()),
+    };
+
+    let mut result = PicksImport::default();
+    for PickEntry { image_id, filename } in chosen {
+        if result.picked.contains(&image_id) {
+            continue;
+        }
+        let matches = match (filename, service.load_metadata(image_id)) {
+            (Some(name), Ok(details)) => name == details.image.filename,
+            _ => false,
+        };
+        if matches {
+            service.update_flag(image_id, "picked")?;
+            result.picked.push(image_id);
+        } else {
+            result.skipped.push(image_id);
+        }
+    }
+    Ok(result)
+}
+
+/// Accepts both the browser's picks download and a hand-edited manifest.
+#[derive(Deserialize)]
+struct PicksFile {
+    #[serde(default = "default_version")]
+    version: u32,
+    #[serde(default)]
+    picks: Option<Vec<PickEntry>>,
+    #[serde(default)]
+    images: Option<Vec<GalleryEntry>>,
+}
+
+#[derive(Deserialize)]
+struct PickEntry {
+    image_id: i64,
+    #[serde(default)]
+    filename: Option<String>,
+}
+
+fn default_version() -> u32 {
+    GALLERY_MANIFEST_VERSION
+}
+
+fn render_page(manifest: &GalleryManifest, template: GalleryTemplate) -> Result<String> {
+    // `</` would end the inline script element early.
+    let data = serde_json::to_string(manifest)
+        .context("failed to serialize gallery data")?
+        .replace("</", "<\\/");
+    let title = escape_html(&manifest.title);
+    // Fill placeholders in one pass so inserted text is never scanned again.
+    let mut page = String::with_capacity(PAGE.len() + data.len() + SCRIPT.len());
+    let mut rest = PAGE;
+    while let Some(start) = rest.find("{{") {
+        let end = rest[start..]
+            .find("}}")
+            .map(|end| start + end + 2)
+            .context("unterminated placeholder in gallery page")?;
+        page.push_str(&rest[..start]);
+        page.push_str(match &rest[start + 2..end - 2] {
+            "BASE_STYLE" => BASE_STYLE,
+            "TEMPLATE_STYLE" => template.stylesheet(),
+            "TEMPLATE" => template.slug(),
+            "SCRIPT" => SCRIPT,
+            "TITLE" => &title,
+            "DATA" => &data,
+            other => bail!("unknown placeholder {other} in gallery page"),
+        });
+        rest = &rest[end..];
+    }
+    page.push_str(rest);
+    Ok(page)
+}
+
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+fn relative_url(dir: &str, path: &Path) -> String {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    format!("{dir}/{name}")
+}
+
+fn gallery_id(title: &str, generated_at: DateTime<Utc>) -> String {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(title.as_bytes());
+    hasher.update(generated_at.to_rfc3339().as_bytes());
+    hasher.finalize().to_hex()[..16].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::CatalogDb;
+    use crate::schema::initialize_schema;
+    use image::Rgb;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn gallery_renders_and_picks_round_trip() {
+        let db = CatalogDb::in_memory().unwrap();
+        initialize_schema(db.conn()).unwrap();
+        let service = CatalogService::new(db);
+        let engine = ImageEngine::new();
+
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let dir = std::env::temp_dir().join(format!("catalog_gallery_{suffix}"));
+        fs::create_dir_all(&dir).unwrap();
+        let mut ids = Vec::new();
+        for (name, shade) in [("a.png", 40), ("b.png", 140), ("c.png", 240)] {
+            let source = dir.join(name);
+            image::RgbImage::from_pixel(600, 400, Rgb([shade, shade, shade]))
+                .save(&source)
+                .unwrap();
+            ids.push(service.import_image(&source).unwrap().id);
+        }
+        service.update_rating(ids[0], 4).unwrap();
+        service
+            .update_keywords(ids[0], &["beach".to_string()])
+            .unwrap();
+
+        let out = dir.join("gallery");
+        let settings = GallerySettings {
+            title: "Smith </script> Wedding".into(),
+            template: GalleryTemplate::Dark,
+            image_size: 300,
+            thumbnail_size: 100,
+            ..GallerySettings::default()
+        };
+        let report = generate_gallery(
+            &service,
+            &engine,
+            &[ids[0], ids[1], ids[2], ids[0], 9999],
+            &out,
+            &settings,
+            ExportCallbacks::default(),
+        )
+        .unwrap();
+        assert_eq!(report.images, 3);
+        assert_eq!(report.failed.len(), 1);
+
+        let html = fs::read_to_string(&report.index).unwrap();
+        assert!(html.contains("Smith &lt;/script&gt; Wedding"));
+        assert!(html.contains("template-dark"));
+        assert_eq!(html.matches("</script>").count(), 2);
+
+        let manifest: GalleryManifest =
+            serde_json::from_str(&fs::read_to_string(&report.manifest).unwrap()).unwrap();
+        let first = &manifest.images[0];
+        assert_eq!(first.caption.rating, Some(4));
+        assert_eq!(first.caption.keywords, vec!["beach"]);
+        assert_eq!(manifest.images[1].caption.rating, None);
+        let image = image::open(out.join(&first.image)).unwrap();
+        assert_eq!((image.width(), image.height()), (300, 200));
+        let thumb = image::open(out.join(&first.thumbnail)).unwrap();
+        assert_eq!((thumb.width(), thumb.height()), (100, 67));
+
+        // A browser download: one good pick, one renamed, one without a filename and
+        // one unknown.
+        let picks = dir.join("picks.json");
+        let payload = serde_json::json!({
+            "version": 1,
+            "gallery_id": manifest.gallery_id,
+            "picks": [
+                { "image_id": ids[1], "filename": "b.png" },
+                { "image_id": ids[2], "filename": "renamed.png" },
+                { "image_id": ids[0] },
+                { "image_id": 9999, "filename": "b.png" }
+            ]
+        });
+        fs::write(&picks, payload.to_string()).unwrap();
+        let imported = import_gallery_picks(&service, &picks).unwrap();
+        assert_eq!(imported.picked, vec![ids[1]]);
+        assert_eq!(imported.skipped, vec![ids[2], ids[0], 9999]);
+        let flag = |id| service.load_metadata(id).unwrap().image.flag;
+        assert_eq!(flag(ids[1]).as_deref(), Some("picked"));
+        assert_eq!(flag(ids[2]), None);
+
+        // A hand-edited manifest works too.
+        let mut edited = manifest.clone();
+        edited.images[2].picked = true;
+        fs::write(&picks, serde_json::to_string(&edited).unwrap()).unwrap();
+        let imported = import_gallery_picks(&service, &picks).unwrap();
+        assert_eq!(imported.picked, vec![ids[2]]);
+        assert_eq!(flag(ids[2]).as_deref(), Some("picked"));
+
+        // Regenerating with fewer images leaves no stale renders behind.
+        generate_gallery(
+            &service,
+            &engine,
+            &[ids[1]],
+            &out,
+            &settings,
+            ExportCallbacks::default(),
+        )
+        .unwrap();
+        assert_eq!(fs::read_dir(out.join("images")).unwrap().count(), 1);
+        assert_eq!(fs::read_dir(out.join("thumbs")).unwrap().count(), 1);
+
+        fs::remove_dir_all(dir).ok();
+    }
+}
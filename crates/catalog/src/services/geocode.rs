@@ -0,0 +1,415 @@
+//! Offline reverse geocoding of GPS positions to city, region and country.
+//!
+//! Places come from a gazetteer bundled with the crate (`data/cities.tsv`), so no network
+//! calls are made. They are indexed in a k-d tree over points on the unit sphere, where
+//! the nearest point by straight-line distance is also the nearest along the surface.
+
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use rusqlite::params;
+use serde::Serialize;
+
+use crate::db::{query_optional, to_rfc3339, Image, ImageKeyword, ImageLocation, Keyword};
+use crate::services::CatalogService;
+
+const EARTH_RADIUS_KM: f64 = 6371.0;
+const BUNDLED_GAZETTEER: &str = include_str!("../../data/cities.tsv");
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Place {
+    pub name: String,
+    pub region: String,
+    pub country_code: String,
+    pub country: String,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// Places with a spatial index for nearest-place lookups.
+#[derive(Debug, Clone)]
+pub struct Gazetteer {
+    places: Vec<Place>,
+    points: Vec<[f64; 3]>,
+    /// Indexes into `places`, ordered as an implicit k-d tree: the median of each range
+    /// splits it on the axis for its depth.
+    tree: Vec<usize>,
+}
+
+impl Gazetteer {
+    /// The gazetteer bundled with the crate, parsed on first use.
+    pub fn bundled() -> &'static Gazetteer {
+        static BUNDLED: OnceLock<Gazetteer> = OnceLock::new();
+        BUNDLED.get_or_init(|| {
+            Gazetteer::parse(BUNDLED_GAZETTEER).expect("bundled gazetteer is valid")
+        })
+    }
+
+    /// Parse tab-separated lines of name, region, country code, country, latitude and
+    /// longitude; `#` starts a comment line.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut places = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').map(str::trim).collect();
+            let [name, region, country_code, country, latitude, longitude] = fields[..] else {
+                bail!("line {}: expected 6 tab-separated fields", number + 1);
+            };
+            let coordinate = |value: &str| {
+                value
+                    .parse::<f64>()
+                    .with_context(|| format!("line {}: invalid coordinate {value:?}", number + 1))
+            };
+            places.push(Place {
+                name: name.to_string(),
+                region: region.to_string(),
+                country_code: country_code.to_string(),
+                country: country.to_string(),
+                latitude: coordinate(latitude)?,
+                longitude: coordinate(longitude)?,
+            });
+        }
+        Ok(Self::new(places))
+    }
+
+    pub fn new(places: Vec<Place>) -> Self {
+        let points = places
+            .iter()
+            .map(|place| unit_vector(place.latitude, place.longitude))
+            .collect();
+        let mut gazetteer = Self {
+            tree: (0..places.len()).collect(),
+            places,
+            points,
+        };
+        let points = &gazetteer.points;
+        build(&mut gazetteer.tree, points, 0);
+        gazetteer
+    }
+
+    pub fn len(&self) -> usize {
+        self.places.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.places.is_empty()
+    }
+
+    /// The place nearest to a position and its distance in kilometres.
+    pub fn nearest(&self, latitude: f64, longitude: f64) -> Option<(&Place, f64)> {
+        let target = unit_vector(latitude, longitude);
+        let mut best = None;
+        self.search(0, self.tree.len(), 0, &target, &mut best);
+        best.map(|(index, chord_squared)| {
+            let angle = 2.0 * (chord_squared.sqrt() / 2.0).min(1.0).asin();
+            (&self.places[index], angle * EARTH_RADIUS_KM)
+        })
+    }
+
+    fn search(
+        &self,
+        lo: usize,
+        hi: usize,
+        depth: usize,
+        target: &[f64; 3],
+        best: &mut Option<(usize, f64)>,
+    ) {
+        if lo >= hi {
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let index = self.tree[mid];
+        let point = &self.points[index];
+        let distance = squared_distance(point, target);
+        if best.is_none_or(|(_, nearest)| distance < nearest) {
+            *best = Some((index, distance));
+        }
+        let axis = depth % 3;
+        let offset = target[axis] - point[axis];
+        let (near, far) = if offset < 0.0 {
+            ((lo, mid), (mid + 1, hi))
+        } else {
+            ((mid + 1, hi), (lo, mid))
+        };
+        self.search(near.0, near.1, depth + 1, target, best);
+        if best.is_none_or(|(_, nearest)| offset * offset < nearest) {
+            self.search(far.0, far.1, depth + 1, target, best);
+        }
+    }
+}
+
+fn build(tree: &mut [usize], points: &[[f64; 3]], depth: usize) {
+    if tree.len() <= 1 {
+        return;
+    }
+    let axis = depth % 3;
+    let mid = tree.len() / 2;
+    tree.select_nth_unstable_by(mid, |a, b| points[*a][axis].total_cmp(&points[*b][axis]));
+    let (left, right) = tree.split_at_mut(mid);
+    build(left, points, depth + 1);
+    build(&mut right[1..], points, depth + 1);
+}
+
+fn unit_vector(latitude: f64, longitude: f64) -> [f64; 3] {
+    let (lat, lon) = (latitude.to_radians(), longitude.to_radians());
+    [lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin()]
+}
+
+fn squared_distance(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    (0..3).map(|axis| (a[axis] - b[axis]).powi(2)).sum()
+}
+
+#[derive(Debug, Clone)]
+pub struct GeocodeSettings {
+    /// Images further than this from every place in the gazetteer are left unlocated.
+    pub max_distance_km: f64,
+    /// Also assign a `Places|Country|Region|City` keyword.
+    pub add_keywords: bool,
+    /// Look up images that already have a location again.
+    pub overwrite: bool,
+}
+
+impl Default for GeocodeSettings {
+    fn default() -> Self {
+        Self {
+            max_distance_km: 50.0,
+            add_keywords: true,
+            overwrite: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GeocodeReport {
+    pub located: Vec<ImageLocation>,
+    pub already_located: usize,
+    pub without_position: usize,
+    /// Images with a position too far from any known place.
+    pub too_far: usize,
+    pub keywords_added: usize,
+}
+
+/// Look up the place each image was taken from its GPS position and store it.
+pub fn geocode_images(
+    service: &CatalogService,
+    image_ids: &[i64],
+    settings: &GeocodeSettings,
+) -> Result<GeocodeReport> {
+    geocode_images_with(service, Gazetteer::bundled(), image_ids, settings)
+}
+
+pub fn geocode_images_with(
+    service: &CatalogService,
+    gazetteer: &Gazetteer,
+    image_ids: &[i64],
+    settings: &GeocodeSettings,
+) -> Result<GeocodeReport> {
+    let tx = service
+        .db
+        .conn()
+        .unchecked_transaction()
+        .context("failed to begin reverse geocoding")?;
+    let mut report = GeocodeReport::default();
+    let mut seen = HashSet::new();
+    for &id in image_ids {
+        if !seen.insert(id) {
+            continue;
+        }
+        let previous = ImageLocation::load(&tx, id)?;
+        if previous.is_some() && !settings.overwrite {
+            report.already_located += 1;
+            continue;
+        }
+        let image = Image::load(&tx, id)?;
+        let position = image.gps_latitude.zip(image.gps_longitude);
+        let nearest = position
+            .and_then(|(latitude, longitude)| gazetteer.nearest(latitude, longitude))
+            .filter(|(_, distance)| *distance <= settings.max_distance_km);
+        let stale_keyword = previous.as_ref().map(location_keyword);
+        let Some((place, distance_km)) = nearest else {
+            if position.is_none() {
+                report.without_position += 1;
+            } else {
+                report.too_far += 1;
+            }
+            // The image was moved away from the place it was located at before.
+            if let Some(stale) = stale_keyword {
+                ImageLocation::delete(&tx, id)?;
+                remove_keyword(&tx, id, &stale)?;
+            }
+            continue;
+        };
+        let location = ImageLocation {
+            image_id: id,
+            city: place.name.clone(),
+            region: place.region.clone(),
+            country: place.country.clone(),
+            country_code: place.country_code.clone(),
+            distance_km,
+            located_at: Utc::now(),
+        };
+        location.upsert(&tx)?;
+        let keyword = location_keyword(&location);
+        if let Some(stale) = stale_keyword.filter(|stale| *stale != keyword) {
+            remove_keyword(&tx, id, &stale)?;
+        }
+        if settings.add_keywords && add_keyword(&tx, id, &keyword)? {
+            report.keywords_added += 1;
+        }
+        report.located.push(location);
+    }
+    tx.commit().context("failed to commit locations")?;
+    Ok(report)
+}
+
+/// The hierarchical keyword for a location, leaving out a region named like its city.
+pub fn location_keyword(location: &ImageLocation) -> String {
+    let mut levels = vec!["Places", location.country.as_str()];
+    if !location.region.is_empty() && location.region != location.city {
+        levels.push(&location.region);
+    }
+    levels.push(&location.city);
+    levels.join("|")
+}
+
+/// Forget the place an image was located at, and its keyword, once its position changes.
+pub(crate) fn forget_location(tx: &rusqlite::Transaction<'_>, image_id: i64) -> Result<()> {
+    if let Some(location) = ImageLocation::load(tx, image_id)? {
+        ImageLocation::delete(tx, image_id)?;
+        remove_keyword(tx, image_id, &location_keyword(&location))?;
+    }
+    Ok(())
+}
+
+fn add_keyword(tx: &rusqlite::Transaction<'_>, image_id: i64, keyword: &str) -> Result<bool> {
+    let keyword = Keyword::get_or_create(tx, keyword)?;
+    let added = tx
+        .execute(
+            "INSERT OR IGNORE INTO image_keywords (image_id, keyword_id, assigned_at)
+             VALUES (?1, ?2, ?3)",
+            params![image_id, keyword.id, to_rfc3339(Utc::now())],
+        )
+        .with_context(|| format!("failed to add location keyword to image {image_id}"))?;
+    Ok(added > 0)
+}
+
+fn remove_keyword(tx: &rusqlite::Transaction<'_>, image_id: i64, keyword: &str) -> Result<()> {
+    let existing = query_optional(
+        tx,
+        "SELECT id, keyword FROM keywords WHERE keyword = ?1",
+        params![keyword],
+        Keyword::from_row,
+    )?;
+    match existing {
+        Some(keyword) => ImageKeyword::delete(tx, image_id, keyword.id),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{test_folder, test_image, CatalogDb};
+
+    #[test]
+    fn nearest_place_matches_a_full_scan() {
+        let gazetteer = Gazetteer::bundled();
+        assert!(gazetteer.len() > 300);
+        let (lisbon, distance) = gazetteer.nearest(38.7223, -9.1393).unwrap();
+        assert_eq!(
+            (lisbon.name.as_str(), lisbon.country_code.as_str()),
+            ("Lisbon", "PT")
+        );
+        assert!(distance < 2.0, "{distance}");
+
+        // Both sides of the antimeridian and near the poles.
+        for (latitude, longitude) in [
+            (-17.0, 179.9),
+            (-17.0, -179.9),
+            (78.0, 20.0),
+            (-60.0, -60.0),
+            (0.0, 0.0),
+            (51.0, -0.5),
+            (35.0, 136.0),
+        ] {
+            let (place, distance) = gazetteer.nearest(latitude, longitude).unwrap();
+            let target = unit_vector(latitude, longitude);
+            let closest = gazetteer
+                .places
+                .iter()
+                .min_by(|a, b| {
+                    let a = squared_distance(&unit_vector(a.latitude, a.longitude), &target);
+                    let b = squared_distance(&unit_vector(b.latitude, b.longitude), &target);
+                    a.total_cmp(&b)
+                })
+                .unwrap();
+            assert_eq!(place, closest, "at {latitude}, {longitude} ({distance} km)");
+        }
+    }
+
+    #[test]
+    fn located_images_are_found_by_place_name() {
+        let service = CatalogService::new(CatalogDb::in_memory().unwrap());
+        let folder_id = test_folder("/photos/trip").insert(&service.db).unwrap();
+        let [tram, ocean] = [("tram.jpg", 38.7110, -9.1400), ("ocean.jpg", 30.0, -40.0)].map(
+            |(name, latitude, longitude)| {
+                Image {
+                    gps_latitude: Some(latitude),
+                    gps_longitude: Some(longitude),
+                    ..test_image(folder_id, &format!("/photos/trip/{name}"))
+                }
+                .insert(&service.db)
+                .unwrap()
+            },
+        );
+        let indoors = test_image(folder_id, "/photos/trip/indoors.jpg")
+            .insert(&service.db)
+            .unwrap();
+
+        let settings = GeocodeSettings::default();
+        let report = geocode_images(&service, &[tram, ocean, indoors], &settings).unwrap();
+        assert_eq!(report.located.len(), 1);
+        assert_eq!(report.located[0].label(), "Lisbon, Portugal");
+        assert_eq!((report.too_far, report.without_position), (1, 1));
+        assert_eq!(report.keywords_added, 1);
+
+        let found: Vec<i64> = service
+            .search("lisbon")
+            .unwrap()
+            .iter()
+            .map(|i| i.id)
+            .collect();
+        assert_eq!(found, vec![tram]);
+        assert_eq!(service.search("portugal").unwrap().len(), 1);
+        let details = service.load_metadata(tram).unwrap();
+        assert_eq!(details.keywords, vec!["Places|Portugal|Lisbon"]);
+
+        // Moving the image and looking it up again replaces the place and its keyword.
+        let mut image = Image::load(&service.db, tram).unwrap();
+        (image.gps_latitude, image.gps_longitude) = (Some(41.15), Some(-8.61));
+        image.update(&service.db).unwrap();
+        let report = geocode_images(&service, &[tram], &settings).unwrap();
+        assert_eq!(report.already_located, 1);
+        let overwrite = GeocodeSettings {
+            overwrite: true,
+            ..settings
+        };
+        geocode_images(&service, &[tram], &overwrite).unwrap();
+        assert!(service.search("lisbon").unwrap().is_empty());
+        assert_eq!(service.search("porto").unwrap().len(), 1);
+        let details = service.load_metadata(tram).unwrap();
+        assert_eq!(details.keywords, vec!["Places|Portugal|Porto"]);
+
+        let mut image = Image::load(&service.db, tram).unwrap();
+        (image.gps_latitude, image.gps_longitude) = (None, None);
+        image.update(&service.db).unwrap();
+        geocode_images(&service, &[tram], &overwrite).unwrap();
+        assert_eq!(ImageLocation::load(&service.db, tram).unwrap(), None);
+        assert!(service.load_metadata(tram).unwrap().keywords.is_empty());
+        assert!(service.search("porto").unwrap().is_empty());
+    }
+}
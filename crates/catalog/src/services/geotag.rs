@@ -0,0 +1,400 @@
+//! Geotagging images from a GPS track.
+//!
+//! Each image's `captured_at`, corrected by the camera clock's offset from UTC, is looked
+//! up on the track with [`GpxTrack::position_at`]. A preview lists the matched and
+//! unmatched images first; applying it writes the positions in one transaction and logs
+//! the previous ones so the run can be undone. Images placed by hand with [`place_images`]
+//! are logged the same way. Whenever an image's position changes, the place it was
+//! located at is forgotten until it is looked up again.
+
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+
+use crate::db::{GeotagBatch, GeotagChange, GpsPosition, Image};
+use crate::services::geocode::forget_location;
+use crate::services::gpx::GpxTrack;
+use crate::services::CatalogService;
+
+#[derive(Debug, Clone)]
+pub struct GeotagSettings {
+    /// Added to `captured_at` to get UTC, e.g. -2h for a camera set to UTC+2.
+    pub clock_offset_seconds: i64,
+    /// Longest time between two track points to interpolate across, and between an
+    /// image and the nearest point otherwise.
+    pub max_gap_seconds: i64,
+    /// Also tag images that already have a position.
+    pub overwrite: bool,
+}
+
+impl Default for GeotagSettings {
+    fn default() -> Self {
+        Self {
+            clock_offset_seconds: 0,
+            max_gap_seconds: 300,
+            overwrite: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GeotagMatch {
+    pub image_id: i64,
+    pub path: PathBuf,
+    /// The corrected, UTC time the position was taken at.
+    pub time: DateTime<Utc>,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GeotagMiss {
+    NoCaptureTime,
+    OutsideTrack,
+    AlreadyTagged,
+}
+
+impl GeotagMiss {
+    pub fn label(self) -> &'static str {
+        match self {
+            GeotagMiss::NoCaptureTime => "no capture time",
+            GeotagMiss::OutsideTrack => "outside the track",
+            GeotagMiss::AlreadyTagged => "already geotagged",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GeotagUnmatched {
+    pub image_id: i64,
+    pub path: PathBuf,
+    pub reason: GeotagMiss,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GeotagPreview {
+    pub matched: Vec<GeotagMatch>,
+    pub unmatched: Vec<GeotagUnmatched>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GeotagUndo {
+    pub batch: GeotagBatch,
+    pub restored: usize,
+    /// Images whose position was changed again since, which are left alone.
+    pub changed_since: usize,
+}
+
+/// Match `image_ids` against `track` without changing anything.
+pub fn preview_geotags(
+    service: &CatalogService,
+    track: &GpxTrack,
+    image_ids: &[i64],
+    settings: &GeotagSettings,
+) -> Result<GeotagPreview> {
+    let offset = Duration::seconds(settings.clock_offset_seconds);
+    let max_gap = Duration::seconds(settings.max_gap_seconds);
+    let mut preview = GeotagPreview::default();
+    for &id in image_ids {
+        let image = Image::load(&service.db, id)?;
+        let path = PathBuf::from(&image.original_path);
+        let miss = |reason| GeotagUnmatched {
+            image_id: id,
+            path: path.clone(),
+            reason,
+        };
+        if !settings.overwrite && image.gps_latitude.is_some() && image.gps_longitude.is_some() {
+            preview.unmatched.push(miss(GeotagMiss::AlreadyTagged));
+            continue;
+        }
+        let Some(captured_at) = image.captured_at else {
+            preview.unmatched.push(miss(GeotagMiss::NoCaptureTime));
+            continue;
+        };
+        match track.position_at(captured_at + offset, max_gap) {
+            Some(point) => preview.matched.push(GeotagMatch {
+                image_id: id,
+                path,
+                time: point.time,
+                latitude: point.latitude,
+                longitude: point.longitude,
+                altitude: point.elevation,
+            }),
+            None => preview.unmatched.push(miss(GeotagMiss::OutsideTrack)),
+        }
+    }
+    Ok(preview)
+}
+
+/// Write the matched positions of `preview` in one transaction, logged under
+/// `description`. Returns the batch to undo, or `None` when nothing matched.
+pub fn apply_geotags(
+    service: &CatalogService,
+    preview: &GeotagPreview,
+    description: &str,
+) -> Result<Option<i64>> {
+    write_positions(
+        service,
+        description,
+        preview.matched.iter().map(|matched| {
+            (
+                matched.image_id,
+                (
+                    Some(matched.latitude),
+                    Some(matched.longitude),
+                    matched.altitude,
+                ),
+            )
+        }),
+    )
+}
+
+/// Put `image_ids` at one point, such as where they were dropped on a map, logged like a
+/// track run so it can be undone. Any altitude they had is cleared.
+pub fn place_images(
+    service: &CatalogService,
+    image_ids: &[i64],
+    latitude: f64,
+    longitude: f64,
+    description: &str,
+) -> Result<Option<i64>> {
+    if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+        bail!("{latitude}, {longitude} is not a valid position");
+    }
+    write_positions(
+        service,
+        description,
+        image_ids
+            .iter()
+            .map(|&id| (id, (Some(latitude), Some(longitude), None))),
+    )
+}
+
+fn write_positions(
+    service: &CatalogService,
+    description: &str,
+    positions: impl IntoIterator<Item = (i64, GpsPosition)>,
+) -> Result<Option<i64>> {
+    let mut positions = positions.into_iter().peekable();
+    if positions.peek().is_none() {
+        return Ok(None);
+    }
+    let tx = service
+        .db
+        .conn()
+        .unchecked_transaction()
+        .context("failed to begin geotagging")?;
+    let batch_id = GeotagBatch {
+        id: 0,
+        description: description.to_string(),
+        created_at: Utc::now(),
+    }
+    .insert(&tx)?;
+    for (image_id, tagged) in positions {
+        let mut image = Image::load(&tx, image_id)?;
+        let previous = position(&image);
+        GeotagChange {
+            batch_id,
+            image_id,
+            previous,
+            tagged,
+        }
+        .insert(&tx)?;
+        if previous != tagged {
+            forget_location(&tx, image_id)?;
+        }
+        (image.gps_latitude, image.gps_longitude, image.gps_altitude) = tagged;
+        image.updated_at = Utc::now();
+        image.update(&tx)?;
+    }
+    tx.commit().context("failed to commit geotags")?;
+    Ok(Some(batch_id))
+}
+
+/// Restore the positions a geotagging run replaced, the most recent run when `batch_id`
+/// is `None`, and forget the run.
+pub fn undo_geotags(service: &CatalogService, batch_id: Option<i64>) -> Result<GeotagUndo> {
+    let batch = match batch_id {
+        Some(id) => GeotagBatch::load(&service.db, id)?
+            .with_context(|| format!("no geotagging run with id {id}"))?,
+        None => match GeotagBatch::load_all(&service.db)?.into_iter().next() {
+            Some(batch) => batch,
+            None => bail!("there is no geotagging run to undo"),
+        },
+    };
+
+    let tx = service
+        .db
+        .conn()
+        .unchecked_transaction()
+        .context("failed to begin undoing geotags")?;
+    let mut undo = GeotagUndo {
+        batch: batch.clone(),
+        restored: 0,
+        changed_since: 0,
+    };
+    for change in GeotagChange::list_for_batch(&tx, batch.id)? {
+        let mut image = Image::load(&tx, change.image_id)?;
+        if position(&image) != change.tagged {
+            undo.changed_since += 1;
+            continue;
+        }
+        if change.previous != change.tagged {
+            forget_location(&tx, image.id)?;
+        }
+        (image.gps_latitude, image.gps_longitude, image.gps_altitude) = change.previous;
+        image.updated_at = Utc::now();
+        image.update(&tx)?;
+        undo.restored += 1;
+    }
+    GeotagBatch::delete(&tx, batch.id)?;
+    tx.commit().context("failed to commit undoing geotags")?;
+    Ok(undo)
+}
+
+/// Geotagging runs that can be undone, newest first.
+pub fn list_geotag_batches(service: &CatalogService) -> Result<Vec<GeotagBatch>> {
+    GeotagBatch::load_all(&service.db).context("failed to list geotagging runs")
+}
+
+fn position(image: &Image) -> GpsPosition {
+    (image.gps_latitude, image.gps_longitude, image.gps_altitude)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{test_folder, test_image, CatalogDb, ImageLocation};
+    use crate::services::geocode::{geocode_images, GeocodeSettings};
+    use crate::services::gpx::parse_gpx;
+    use chrono::TimeZone;
+
+    #[test]
+    fn previews_applies_and_undoes_geotags() {
+        let service = CatalogService::new(CatalogDb::in_memory().unwrap());
+        let folder_id = test_folder("/photos/walk").insert(&service.db).unwrap();
+        let at = |h, m| Utc.with_ymd_and_hms(2024, 5, 17, h, m, 0).unwrap();
+        // The camera was set to UTC+2, so 12:05 on the camera is 10:05 UTC.
+        let [inside, outside] =
+            [("inside.jpg", at(12, 5)), ("outside.jpg", at(15, 0))].map(|(name, captured_at)| {
+                Image {
+                    captured_at: Some(captured_at),
+                    ..test_image(folder_id, &format!("/photos/walk/{name}"))
+                }
+                .insert(&service.db)
+                .unwrap()
+            });
+        let untimed = test_image(folder_id, "/photos/walk/untimed.jpg")
+            .insert(&service.db)
+            .unwrap();
+        let tagged = Image {
+            captured_at: Some(at(12, 5)),
+            gps_latitude: Some(1.0),
+            gps_longitude: Some(2.0),
+            ..test_image(folder_id, "/photos/walk/tagged.jpg")
+        }
+        .insert(&service.db)
+        .unwrap();
+
+        let track = parse_gpx(
+            r#"<gpx><trk><trkseg>
+                <trkpt lat="10.0" lon="20.0"><ele>100</ele><time>2024-05-17T10:00:00Z</time></trkpt>
+                <trkpt lat="11.0" lon="22.0"><ele>200</ele><time>2024-05-17T10:10:00Z</time></trkpt>
+            </trkseg></trk></gpx>"#,
+        )
+        .unwrap();
+        let settings = GeotagSettings {
+            clock_offset_seconds: -2 * 3600,
+            max_gap_seconds: 15 * 60,
+            overwrite: false,
+        };
+        let ids = [inside, outside, untimed, tagged];
+        let preview = preview_geotags(&service, &track, &ids, &settings).unwrap();
+        assert_eq!(preview.matched.len(), 1);
+        let matched = &preview.matched[0];
+        assert_eq!(matched.image_id, inside);
+        assert_eq!((matched.latitude, matched.longitude), (10.5, 21.0));
+        assert_eq!(matched.altitude, Some(150.0));
+        let reasons: Vec<_> = preview
+            .unmatched
+            .iter()
+            .map(|miss| (miss.image_id, miss.reason))
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                (outside, GeotagMiss::OutsideTrack),
+                (untimed, GeotagMiss::NoCaptureTime),
+                (tagged, GeotagMiss::AlreadyTagged),
+            ]
+        );
+        // Previewing changed nothing.
+        assert_eq!(Image::load(&service.db, inside).unwrap().gps_latitude, None);
+
+        let overwrite = GeotagSettings {
+            overwrite: true,
+            ..settings
+        };
+        let preview = preview_geotags(&service, &track, &ids, &overwrite).unwrap();
+        assert_eq!(preview.matched.len(), 2);
+        let batch = apply_geotags(&service, &preview, "walk.gpx")
+            .unwrap()
+            .unwrap();
+        let image = Image::load(&service.db, tagged).unwrap();
+        assert_eq!(position(&image), (Some(10.5), Some(21.0), Some(150.0)));
+        assert_eq!(list_geotag_batches(&service).unwrap()[0].id, batch);
+
+        let undo = undo_geotags(&service, None).unwrap();
+        assert_eq!((undo.restored, undo.changed_since), (2, 0));
+        let image = Image::load(&service.db, tagged).unwrap();
+        assert_eq!(position(&image), (Some(1.0), Some(2.0), None));
+        assert_eq!(Image::load(&service.db, inside).unwrap().gps_latitude, None);
+        assert!(undo_geotags(&service, None).is_err());
+
+        place_images(&service, &[inside, tagged], 48.85, 2.35, "Dropped on map")
+            .unwrap()
+            .unwrap();
+        let image = Image::load(&service.db, tagged).unwrap();
+        assert_eq!(position(&image), (Some(48.85), Some(2.35), None));
+        assert!(place_images(&service, &[inside], 91.0, 0.0, "Off the map").is_err());
+        let undo = undo_geotags(&service, None).unwrap();
+        assert_eq!(undo.restored, 2);
+        let image = Image::load(&service.db, tagged).unwrap();
+        assert_eq!(position(&image), (Some(1.0), Some(2.0), None));
+    }
+
+    #[test]
+    fn moving_an_image_forgets_its_place() {
+        let service = CatalogService::new(CatalogDb::in_memory().unwrap());
+        let folder_id = test_folder("/photos/moved").insert(&service.db).unwrap();
+        let tram = test_image(folder_id, "/photos/moved/tram.jpg")
+            .insert(&service.db)
+            .unwrap();
+        let located = |service: &CatalogService| {
+            ImageLocation::load(&service.db, tram)
+                .unwrap()
+                .map(|location| location.label())
+        };
+
+        place_images(&service, &[tram], 38.711, -9.14, "Lisbon").unwrap();
+        geocode_images(&service, &[tram], &GeocodeSettings::default()).unwrap();
+        assert_eq!(located(&service).as_deref(), Some("Lisbon, Portugal"));
+
+        place_images(&service, &[tram], 48.85, 2.35, "Dropped on Paris").unwrap();
+        assert_eq!(located(&service), None);
+        assert!(service.search("lisbon").unwrap().is_empty());
+        assert!(service.load_metadata(tram).unwrap().keywords.is_empty());
+
+        // Undoing a run moves the image too, so the place it gained in between goes.
+        geocode_images(&service, &[tram], &GeocodeSettings::default()).unwrap();
+        assert_eq!(service.search("paris").unwrap().len(), 1);
+        undo_geotags(&service, None).unwrap();
+        assert_eq!(located(&service), None);
+        assert!(service.search("paris").unwrap().is_empty());
+        assert!(service.load_metadata(tram).unwrap().keywords.is_empty());
+    }
+}
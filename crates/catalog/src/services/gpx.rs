@@ -0,0 +1,218 @@
+//! Reader for GPS tracks in GPX format.
+//!
+//! Only track points are read: their position, elevation and UTC time. Points without
+//! a time are skipped since they cannot be matched to photos. Each `<trkseg>` becomes a
+//! segment; a gap in recording starts a new one in the file, so photos taken between
+//! segments are not interpolated across it.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct TrackPoint {
+    pub time: DateTime<Utc>,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub elevation: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GpxTrack {
+    /// Points of each segment in time order; empty segments are dropped.
+    pub segments: Vec<Vec<TrackPoint>>,
+}
+
+impl GpxTrack {
+    pub fn point_count(&self) -> usize {
+        self.segments.iter().map(Vec::len).sum()
+    }
+
+    /// First and last time of each segment.
+    pub fn spans(&self) -> impl Iterator<Item = (DateTime<Utc>, DateTime<Utc>)> + '_ {
+        self.segments
+            .iter()
+            .filter_map(|points| Some((points.first()?.time, points.last()?.time)))
+    }
+
+    /// The position at `time`: interpolated between the surrounding points of a segment
+    /// when they are at most `max_gap` apart, else the nearer of them within `max_gap`.
+    pub fn position_at(&self, time: DateTime<Utc>, max_gap: Duration) -> Option<TrackPoint> {
+        for points in &self.segments {
+            let after = points.partition_point(|point| point.time <= time);
+            let before = after.checked_sub(1).map(|at| points[at]);
+            let after = points.get(after).copied();
+            let position = match (before, after) {
+                (Some(before), _) if before.time == time => Some(before),
+                (Some(before), Some(after)) if after.time - before.time <= max_gap => {
+                    Some(interpolate(before, after, time))
+                }
+                _ => [before, after]
+                    .into_iter()
+                    .flatten()
+                    .filter(|point| (point.time - time).abs() <= max_gap)
+                    .min_by_key(|point| (point.time - time).abs()),
+            };
+            if let Some(point) = position {
+                return Some(TrackPoint { time, ..point });
+            }
+        }
+        None
+    }
+}
+
+fn interpolate(before: TrackPoint, after: TrackPoint, time: DateTime<Utc>) -> TrackPoint {
+    let span = (after.time - before.time).num_milliseconds() as f64;
+    let t = (time - before.time).num_milliseconds() as f64 / span;
+    let lerp = |a: f64, b: f64| a + (b - a) * t;
+    TrackPoint {
+        time,
+        latitude: lerp(before.latitude, after.latitude),
+        longitude: lerp(before.longitude, after.longitude),
+        elevation: match (before.elevation, after.elevation) {
+            (Some(a), Some(b)) => Some(lerp(a, b)),
+            (a, b) => a.or(b),
+        },
+    }
+}
+
+pub fn read_gpx(path: &Path) -> Result<GpxTrack> {
+    let text =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    parse_gpx(&text).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+pub fn parse_gpx(text: &str) -> Result<GpxTrack> {
+    if !text.contains("<gpx") {
+        bail!("not a GPX file");
+    }
+    let mut track = GpxTrack::default();
+    // Content before the first <trkseg> is ignored; files without segments still
+    // count as one.
+    let segments: Vec<&str> = if text.contains("<trkseg") {
+        text.split("<trkseg").skip(1).collect()
+    } else {
+        vec![text]
+    };
+    for segment in segments {
+        let segment = segment.split("</trkseg>").next().unwrap_or(segment);
+        let mut points = Vec::new();
+        for chunk in segment.split("<trkpt").skip(1) {
+            let tag_end = chunk.find('>').unwrap_or(chunk.len());
+            let tag = &chunk[..tag_end];
+            let body = if tag.ends_with('/') {
+                ""
+            } else {
+                let body = &chunk[tag_end..];
+                body.split("</trkpt>").next().unwrap_or(body)
+            };
+            let (Some(latitude), Some(longitude)) = (
+                attribute(tag, "lat").and_then(|v| v.parse().ok()),
+                attribute(tag, "lon").and_then(|v| v.parse().ok()),
+            ) else {
+                continue;
+            };
+            let Some(time) = element(body, "time")
+                .and_then(|v| DateTime::parse_from_rfc3339(v).ok())
+                .map(|t| t.with_timezone(&Utc))
+            else {
+                continue;
+            };
+            points.push(TrackPoint {
+                time,
+                latitude,
+                longitude,
+                elevation: element(body, "ele").and_then(|v| v.parse().ok()),
+            });
+        }
+        points.sort_by_key(|point| point.time);
+        if !points.is_empty() {
+            track.segments.push(points);
+        }
+    }
+    if track.segments.is_empty() {
+        bail!("no timed track points");
+    }
+    Ok(track)
+}
+
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    for quote in ['"', '\''] {
+        let needle = format!(" {name}={quote}");
+        if let Some(start) = tag.find(&needle).map(|at| at + needle.len()) {
+            let end = tag[start..].find(quote)?;
+            return Some(tag[start..start + end].trim());
+        }
+    }
+    None
+}
+
+fn element<'a>(body: &'a str, name: &str) -> Option<&'a str> {
+    let start = body.find(&format!("<{name}>"))? + name.len() + 2;
+    let end = body[start..].find(&format!("</{name}>"))?;
+    Some(body[start..start + end].trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_segments_and_skips_untimed_points() {
+        let gpx = r#"<?xml version="1.0"?>
+<gpx version="1.1" creator="test">
+  <trk><name>Walk</name>
+    <trkseg>
+      <trkpt lat="48.8584" lon="2.2945"><ele>35.0</ele><time>2024-05-17T09:31:00Z</time></trkpt>
+      <trkpt lat="48.8600" lon="2.2950"><time>2024-05-17T09:30:00Z</time></trkpt>
+      <trkpt lat="48.8610" lon="2.2960"/>
+    </trkseg>
+    <trkseg>
+      <trkpt lon='2.3499' lat='48.8530'><time>2024-05-17T11:00:00+02:00</time></trkpt>
+    </trkseg>
+  </trk>
+</gpx>"#;
+        let track = parse_gpx(gpx).unwrap();
+        assert_eq!(track.segments.len(), 2);
+        assert_eq!(track.point_count(), 3);
+        let first = track.segments[0][0];
+        assert_eq!(first.time.to_rfc3339(), "2024-05-17T09:30:00+00:00");
+        assert_eq!(first.elevation, None);
+        assert_eq!(track.segments[0][1].elevation, Some(35.0));
+        let second = track.segments[1][0];
+        assert_eq!((second.latitude, second.longitude), (48.853, 2.3499));
+        assert_eq!(second.time.to_rfc3339(), "2024-05-17T09:00:00+00:00");
+        assert!(parse_gpx("<html></html>").is_err());
+    }
+
+    #[test]
+    fn interpolates_positions_within_the_gap() {
+        let track = parse_gpx(
+            r#"<gpx><trk><trkseg>
+                <trkpt lat="10.0" lon="20.0"><ele>100</ele><time>2024-05-17T10:00:00Z</time></trkpt>
+                <trkpt lat="11.0" lon="22.0"><ele>200</ele><time>2024-05-17T10:10:00Z</time></trkpt>
+                <trkpt lat="30.0" lon="40.0"><time>2024-05-17T12:00:00Z</time></trkpt>
+            </trkseg></trk></gpx>"#,
+        )
+        .unwrap();
+        let at = |h, m| {
+            DateTime::parse_from_rfc3339(&format!("2024-05-17T{h:02}:{m:02}:00Z"))
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let gap = Duration::minutes(15);
+
+        let middle = track.position_at(at(10, 5), gap).unwrap();
+        assert_eq!((middle.latitude, middle.longitude), (10.5, 21.0));
+        assert_eq!(middle.elevation, Some(150.0));
+        // Between points too far apart, the nearer one is used if close enough.
+        let near_end = track.position_at(at(10, 20), gap).unwrap();
+        assert_eq!((near_end.latitude, near_end.elevation), (11.0, Some(200.0)));
+        assert!(track.position_at(at(11, 0), gap).is_none());
+        assert!(track.position_at(at(9, 0), gap).is_none());
+        assert_eq!(track.position_at(at(12, 0), gap).unwrap().latitude, 30.0);
+    }
+}
@@ -0,0 +1,404 @@
+//! Display-independent import pipeline shared by the desktop app and the CLI.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use walkdir::WalkDir;
+
+use crate::services::catalog_service::CatalogService;
+
+pub const SUPPORTED_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "png", "tiff", "tif", "jxl", "heif", "heic", "dng", "cr2", "nef", "raf", "arw",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportMethod {
+    Add,
+    Copy,
+    Move,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicateStrategy {
+    #[default]
+    Skip,
+    ImportAnyway,
+}
+
+#[derive(Clone, Default)]
+pub struct ImportCallbacks {
+    pub progress: Option<Arc<dyn Fn(ImportProgress)>>,
+    pub on_error: Option<Arc<dyn Fn(PathBuf, String)>>,
+    pub duplicate_strategy: DuplicateStrategy,
+    pub cancel: CancellationFlag,
+}
+
+impl ImportCallbacks {
+    fn emit_progress(
+        &self,
+        stage: ImportStage,
+        completed: usize,
+        total: usize,
+        message: impl Into<Option<String>>,
+    ) {
+        if let Some(cb) = &self.progress {
+            cb(ImportProgress {
+                stage,
+                completed,
+                total,
+                message: message.into(),
+            });
+        }
+    }
+
+    fn emit_error(&self, path: PathBuf, err: impl Into<String>) {
+        if let Some(cb) = &self.on_error {
+            cb(path, err.into());
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub duplicates: Vec<PathBuf>,
+    pub failed: Vec<(PathBuf, String)>,
+    pub canceled: bool,
+    pub batch_started_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ImportProgress {
+    pub stage: ImportStage,
+    pub completed: usize,
+    pub total: usize,
+    pub message: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportStage {
+    Scanning,
+    Copying,
+    Moving,
+    Cataloging,
+    Thumbnailing,
+    Keywords,
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct CancellationFlag(Arc<AtomicBool>);
+
+impl CancellationFlag {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_canceled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Walk `root` and return every file with a supported image extension.
+pub fn collect_image_paths(root: &Path, cancel: &CancellationFlag) -> Vec<PathBuf> {
+    let mut out = Vec::new();
+    for entry in WalkDir::new(root).into_iter().filter_map(|e| e.ok()) {
+        if cancel.is_canceled() {
+            break;
+        }
+
+        if entry.file_type().is_file() && has_supported_extension(entry.path()) {
+            out.push(entry.path().to_path_buf());
+        }
+    }
+    out
+}
+
+pub fn has_supported_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|s| s.to_str())
+        .map(is_supported_extension)
+        .unwrap_or(false)
+}
+
+pub fn is_supported_extension(ext: &str) -> bool {
+    SUPPORTED_EXTENSIONS
+        .iter()
+        .any(|candidate| candidate.eq_ignore_ascii_case(ext))
+}
+
+pub fn import_files(
+    service: &CatalogService,
+    file_paths: &[PathBuf],
+    keywords: &[String],
+    method: ImportMethod,
+    destination: Option<PathBuf>,
+    callbacks: ImportCallbacks,
+) -> Result<ImportReport> {
+    let mut report = ImportReport::default();
+    let batch_started_at = Utc::now();
+    let normalized_keywords = normalize_keywords(keywords);
+    let dest_dir = match method {
+        ImportMethod::Add => None,
+        ImportMethod::Copy | ImportMethod::Move => Some(
+            destination
+                .as_ref()
+                .map(PathBuf::from)
+                .ok_or_else(|| anyhow!("destination directory is required for copy or move"))?,
+        ),
+    };
+
+    if let Some(dest_dir) = dest_dir.as_ref() {
+        fs::create_dir_all(dest_dir)
+            .with_context(|| format!("failed to create destination {}", dest_dir.display()))?;
+    }
+
+    let total = file_paths.len();
+    for (idx, src) in file_paths.iter().enumerate() {
+        if callbacks.cancel.is_canceled() {
+            report.canceled = true;
+            break;
+        }
+
+        callbacks.emit_progress(
+            ImportStage::Scanning,
+            idx,
+            total,
+            format!("Hashing {}", src.display()),
+        );
+
+        let hash = CatalogService::compute_file_hash(src)
+            .with_context(|| format!("failed to hash file {}", src.display()))?;
+
+        if let Some(existing) = service.find_image_by_hash(&hash)? {
+            report.duplicates.push(src.clone());
+            callbacks.emit_error(
+                src.clone(),
+                format!("duplicate detected (matches image id={})", existing.id),
+            );
+
+            if callbacks.duplicate_strategy == DuplicateStrategy::Skip {
+                continue;
+            }
+        }
+
+        let target_path = match method {
+            ImportMethod::Add => src.to_path_buf(),
+            ImportMethod::Copy | ImportMethod::Move => {
+                let dest_dir = dest_dir.as_ref().expect("validated destination");
+                callbacks.emit_progress(
+                    ImportStage::Copying,
+                    idx,
+                    total,
+                    format!("Copying {}", src.display()),
+                );
+                copy_into_destination(src, dest_dir)?
+            }
+        };
+
+        if let Some(existing) = service.find_image_by_original_path(&target_path)? {
+            report.duplicates.push(target_path.clone());
+            callbacks.emit_error(
+                target_path.clone(),
+                format!("already imported as image id={}", existing.id),
+            );
+            continue;
+        }
+
+        callbacks.emit_progress(
+            ImportStage::Cataloging,
+            idx,
+            total,
+            format!("Cataloging {}", target_path.display()),
+        );
+
+        let image = match service.import_image_at(&target_path, batch_started_at) {
+            Ok(img) => img,
+            Err(err) => {
+                callbacks.emit_error(target_path.clone(), err.to_string());
+                report
+                    .failed
+                    .push((target_path.clone(), format!("import failed: {err}")));
+                if matches!(method, ImportMethod::Copy | ImportMethod::Move) {
+                    let _ = fs::remove_file(&target_path);
+                }
+                continue;
+            }
+        };
+
+        callbacks.emit_progress(
+            ImportStage::Thumbnailing,
+            idx,
+            total,
+            format!("Generating thumbnail for {}", target_path.display()),
+        );
+        if let Err(err) = service.generate_thumbnail(image.id, &target_path) {
+            callbacks.emit_error(target_path.clone(), format!("thumbnail failed: {err}"));
+        }
+
+        callbacks.emit_progress(
+            ImportStage::Keywords,
+            idx,
+            total,
+            Some("Applying keywords".to_string()),
+        );
+        for kw in &normalized_keywords {
+            if let Err(err) = service.add_keyword_to_image(image.id, kw) {
+                callbacks.emit_error(target_path.clone(), format!("keyword '{kw}' failed: {err}"));
+            }
+        }
+
+        if method == ImportMethod::Move {
+            callbacks.emit_progress(
+                ImportStage::Moving,
+                idx,
+                total,
+                format!("Removing {}", src.display()),
+            );
+            if let Err(err) = fs::remove_file(src) {
+                callbacks.emit_error(src.clone(), format!("failed to remove source: {err}"));
+            }
+        }
+
+        report.imported += 1;
+        report.batch_started_at.get_or_insert(batch_started_at);
+    }
+
+    Ok(report)
+}
+
+pub fn parse_keywords(raw: &str) -> Vec<String> {
+    raw.split(',')
+        .flat_map(|chunk| chunk.split('\n'))
+        .map(|kw| kw.trim().to_string())
+        .filter(|kw| !kw.is_empty())
+        .collect()
+}
+
+/// Returns true when the file already exists in the catalog by path or hash.
+pub fn is_already_imported(service: &CatalogService, path: &Path) -> bool {
+    if let Ok(Some(_)) = service.find_image_by_original_path(path) {
+        return true;
+    }
+
+    if let Ok(hash) = CatalogService::compute_file_hash(path) {
+        if let Ok(Some(_)) = service.find_image_by_hash(&hash) {
+            return true;
+        }
+    }
+
+    false
+}
+
+fn normalize_keywords(keywords: &[String]) -> Vec<String> {
+    let mut out = Vec::new();
+    for kw in keywords {
+        let trimmed = kw.trim();
+        if !trimmed.is_empty() && !out.iter().any(|existing: &String| existing == trimmed) {
+            out.push(trimmed.to_string());
+        }
+    }
+    out
+}
+
+fn copy_into_destination(src: &Path, dest_dir: &Path) -> Result<PathBuf> {
+    let filename = src
+        .file_name()
+        .ok_or_else(|| anyhow!("source file is missing a filename: {}", src.display()))?;
+    let dest_path = dest_dir.join(filename);
+    fs::create_dir_all(dest_dir)
+        .with_context(|| format!("failed to create destination {}", dest_dir.display()))?;
+    fs::copy(src, &dest_path).with_context(|| {
+        format!(
+            "failed to copy {} to {}",
+            src.display(),
+            dest_path.display()
+        )
+    })?;
+    Ok(dest_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::CatalogDb;
+    use crate::schema::initialize_schema;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_dir(label: &str) -> PathBuf {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let dir = std::env::temp_dir().join(format!("catalog_import_{label}_{suffix}"));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_test_image(path: &Path, seed: u8) {
+        let img = image::RgbaImage::from_pixel(32, 32, image::Rgba([seed, 40, 200, 255]));
+        img.save(path).unwrap();
+    }
+
+    #[test]
+    fn collects_supported_files_and_skips_duplicates() {
+        let db = CatalogDb::in_memory().unwrap();
+        initialize_schema(db.conn()).unwrap();
+        let service = CatalogService::new(db);
+
+        let dir = temp_dir("collect");
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        write_test_image(&dir.join("a.PNG"), 10);
+        write_test_image(&dir.join("nested/b.png"), 90);
+        fs::write(dir.join("notes.txt"), b"skip").unwrap();
+
+        let mut paths = collect_image_paths(&dir, &CancellationFlag::default());
+        paths.sort();
+        assert_eq!(paths.len(), 2);
+
+        let keywords = parse_keywords("trip, trip\nbeach");
+        let report = import_files(
+            &service,
+            &paths,
+            &keywords,
+            ImportMethod::Add,
+            None,
+            ImportCallbacks::default(),
+        )
+        .unwrap();
+        assert_eq!(report.imported, 2);
+        assert!(is_already_imported(&service, &paths[0]));
+
+        let details = service
+            .load_metadata(report_image_id(&service, &paths[0]))
+            .unwrap();
+        assert_eq!(details.keywords.len(), 2);
+
+        let again = import_files(
+            &service,
+            &paths,
+            &[],
+            ImportMethod::Add,
+            None,
+            ImportCallbacks::default(),
+        )
+        .unwrap();
+        assert_eq!(again.imported, 0);
+        assert_eq!(again.duplicates.len(), 2);
+
+        fs::remove_dir_all(dir).ok();
+    }
+
+    fn report_image_id(service: &CatalogService, path: &Path) -> i64 {
+        service
+            .find_image_by_original_path(path)
+            .unwrap()
+            .expect("imported image")
+            .id
+    }
+}
@@ -0,0 +1,879 @@
+//! Reader for Lightroom Classic catalogs (`.lrcat`).
+//!
+//! An lrcat is a SQLite database. Folders, images, ratings, picks, color labels,
+//! keyword hierarchies, collections and harvested EXIF data are read directly; develop
+//! settings are stored as a Lua table per image, of which the basic panel, presence,
+//! white balance offsets and crop are mapped onto `edits`. Anything else that was used
+//! is counted in [`ExternalCatalog::unmapped`].
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use rusqlite::Connection;
+use serde_json::json;
+
+use crate::db::query_all;
+use crate::services::catalog_service::Edits;
+use crate::services::external_catalog::{
+    open_read_only, parse_capture_time, table_exists, unsupported_type, ExternalCatalog,
+    ExternalCollection, ExternalImage,
+};
+use crate::services::import::has_supported_extension;
+
+const REGULAR_COLLECTION: &str = "com.adobe.ag.library.collection";
+const COLLECTION_SET: &str = "com.adobe.ag.library.group";
+const SMART_COLLECTION: &str = "com.adobe.ag.library.smart_collection";
+
+/// Read a Lightroom Classic catalog without modifying it.
+pub fn read_lightroom_catalog(lrcat: &Path) -> Result<ExternalCatalog> {
+    let conn = open_read_only(lrcat, "Lightroom catalog")?;
+    if !table_exists(&conn, "Adobe_images")? || !table_exists(&conn, "AgLibraryFile")? {
+        bail!("{} is not a Lightroom Classic catalog", lrcat.display());
+    }
+
+    let mut catalog = ExternalCatalog::default();
+    read_images(&conn, &mut catalog)?;
+    let index: HashMap<i64, usize> = catalog
+        .images
+        .iter()
+        .enumerate()
+        .map(|(idx, image)| (image.source_id, idx))
+        .collect();
+
+    if table_exists(&conn, "AgHarvestedExifMetadata")? {
+        read_exif(&conn, &mut catalog, &index)?;
+    }
+    if table_exists(&conn, "AgLibraryKeyword")? && table_exists(&conn, "AgLibraryKeywordImage")? {
+        read_keywords(&conn, &mut catalog, &index)?;
+    }
+    if table_exists(&conn, "AgLibraryCollection")? {
+        read_collections(&conn, &mut catalog)?;
+    }
+    if table_exists(&conn, "Adobe_imageDevelopSettings")? {
+        read_develop_settings(&conn, &mut catalog, &index)?;
+    }
+    if table_exists(&conn, "AgLibraryFolderStackImage")? {
+        let stacked: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM AgLibraryFolderStackImage",
+                [],
+                |row| row.get(0),
+            )
+            .context("failed to count stacked images")?;
+        for _ in 0..stacked {
+            catalog.note_unmapped("Stacks");
+        }
+    }
+    Ok(catalog)
+}
+
+struct ImageRow {
+    id: i64,
+    path: PathBuf,
+    captured_at: Option<String>,
+    rating: Option<f64>,
+    pick: Option<f64>,
+    color_label: Option<String>,
+    orientation: Option<String>,
+    virtual_copy: bool,
+    file_format: Option<String>,
+}
+
+fn read_images(conn: &Connection, catalog: &mut ExternalCatalog) -> Result<()> {
+    let rows = query_all(
+        conn,
+        "SELECT i.id_local, r.absolutePath, f.pathFromRoot, fi.baseName, fi.extension,
+                i.captureTime, i.rating, i.pick, i.colorLabels, i.orientation,
+                i.masterImage IS NOT NULL, i.fileFormat
+         FROM Adobe_images i
+         JOIN AgLibraryFile fi ON fi.id_local = i.rootFile
+         JOIN AgLibraryFolder f ON f.id_local = fi.folder
+         JOIN AgLibraryRootFolder r ON r.id_local = f.rootFolder
+         ORDER BY i.id_local",
+        [],
+        |row| {
+            let root: String = row.get(1)?;
+            let folder: Option<String> = row.get(2)?;
+            let base: String = row.get(3)?;
+            let extension: Option<String> = row.get(4)?;
+            let filename = match extension.filter(|ext| !ext.is_empty()) {
+                Some(ext) => format!("{base}.{ext}"),
+                None => base,
+            };
+            Ok(ImageRow {
+                id: row.get(0)?,
+                path: join_lightroom_path(&root, folder.as_deref().unwrap_or(""), &filename),
+                captured_at: row.get(5)?,
+                rating: row.get(6)?,
+                pick: row.get(7)?,
+                color_label: row.get(8)?,
+                orientation: row.get(9)?,
+                virtual_copy: row.get(10)?,
+                file_format: row.get(11)?,
+            })
+        },
+    )
+    .context("failed to read Lightroom images")?;
+
+    for row in rows {
+        if row.virtual_copy {
+            catalog.note_unmapped("Virtual copies");
+            continue;
+        }
+        if row.file_format.as_deref() == Some("VIDEO") || !has_supported_extension(&row.path) {
+            catalog.note_unmapped(unsupported_type(&row.path));
+            continue;
+        }
+
+        let color_label = row
+            .color_label
+            .as_deref()
+            .map(str::trim)
+            .filter(|label| !label.is_empty())
+            .and_then(|label| match label.to_ascii_lowercase().as_str() {
+                known @ ("red" | "yellow" | "green" | "blue" | "purple") => Some(known.to_string()),
+                _ => {
+                    catalog.note_unmapped("Custom color label names");
+                    None
+                }
+            });
+        let orientation = row.orientation.as_deref().and_then(|code| match code {
+            "AB" => Some(1),
+            "BC" => Some(6),
+            "CD" => Some(3),
+            "DA" => Some(8),
+            _ => {
+                catalog.note_unmapped("Mirrored orientation");
+                None
+            }
+        });
+        catalog.images.push(ExternalImage {
+            source_id: row.id,
+            path: row.path,
+            captured_at: row.captured_at.as_deref().and_then(parse_capture_time),
+            rating: row
+                .rating
+                .map(|rating| rating.round() as i64)
+                .filter(|rating| *rating > 0),
+            flag: row.pick.and_then(|pick| {
+                if pick > 0.0 {
+                    Some("picked".to_string())
+                } else if pick < 0.0 {
+                    Some("rejected".to_string())
+                } else {
+                    None
+                }
+            }),
+            color_label,
+            orientation,
+            ..ExternalImage::default()
+        });
+    }
+    Ok(())
+}
+
+/// Lightroom stores folders with forward slashes and a trailing separator.
+fn join_lightroom_path(root: &str, folder: &str, filename: &str) -> PathBuf {
+    let mut path = PathBuf::from(root);
+    for part in folder.split('/').filter(|part| !part.is_empty()) {
+        path.push(part);
+    }
+    path.push(filename);
+    path
+}
+
+fn read_exif(
+    conn: &Connection,
+    catalog: &mut ExternalCatalog,
+    index: &HashMap<i64, usize>,
+) -> Result<()> {
+    let camera = table_exists(conn, "AgInternedExifCameraModel")?;
+    let lens = table_exists(conn, "AgInternedExifLens")?;
+    let sql = format!(
+        "SELECT h.image, h.aperture, h.shutterSpeed, h.focalLength, h.isoSpeedRating,
+                h.hasGPS, h.gpsLatitude, h.gpsLongitude, {}, {}
+         FROM AgHarvestedExifMetadata h {} {}",
+        if camera { "cm.value" } else { "NULL" },
+        if lens { "l.value" } else { "NULL" },
+        if camera {
+            "LEFT JOIN AgInternedExifCameraModel cm ON cm.id_local = h.cameraModelRef"
+        } else {
+            ""
+        },
+        if lens {
+            "LEFT JOIN AgInternedExifLens l ON l.id_local = h.lensRef"
+        } else {
+            ""
+        },
+    );
+    let rows = query_all(conn, &sql, [], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, Option<f64>>(1)?,
+            row.get::<_, Option<f64>>(2)?,
+            row.get::<_, Option<f64>>(3)?,
+            row.get::<_, Option<f64>>(4)?,
+            row.get::<_, Option<i64>>(5)?,
+            row.get::<_, Option<f64>>(6)?,
+            row.get::<_, Option<f64>>(7)?,
+            row.get::<_, Option<String>>(8)?,
+            row.get::<_, Option<String>>(9)?,
+        ))
+    })
+    .context("failed to read Lightroom EXIF metadata")?;
+
+    for (id, aperture, shutter, focal, iso, has_gps, lat, lon, camera, lens) in rows {
+        let Some(image) = index.get(&id).map(|idx| &mut catalog.images[*idx]) else {
+            continue;
+        };
+        // Aperture and shutter speed are harvested as APEX values.
+        image.aperture = aperture.map(|av| (2f64.powf(av / 2.0) * 10.0).round() / 10.0);
+        image.shutter_speed = shutter.map(|tv| 2f64.powf(-tv));
+        image.focal_length = focal.filter(|f| *f > 0.0);
+        image.iso = iso.map(|iso| iso.round() as i64).filter(|iso| *iso > 0);
+        if has_gps == Some(1) {
+            image.gps_latitude = lat;
+            image.gps_longitude = lon;
+        }
+        image.camera_model = camera.filter(|c| !c.is_empty());
+        image.lens_model = lens.filter(|l| !l.is_empty());
+    }
+    Ok(())
+}
+
+fn read_keywords(
+    conn: &Connection,
+    catalog: &mut ExternalCatalog,
+    index: &HashMap<i64, usize>,
+) -> Result<()> {
+    let tree: HashMap<i64, (Option<String>, Option<i64>)> = query_all(
+        conn,
+        "SELECT id_local, name, parent FROM AgLibraryKeyword",
+        [],
+        |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?))),
+    )
+    .context("failed to read Lightroom keywords")?
+    .into_iter()
+    .collect();
+
+    let mut paths: HashMap<i64, Option<String>> = HashMap::new();
+    let assignments: Vec<(i64, i64)> = query_all(
+        conn,
+        "SELECT image, tag FROM AgLibraryKeywordImage ORDER BY image, tag",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .context("failed to read Lightroom keyword assignments")?;
+    for (image_id, tag) in assignments {
+        let Some(idx) = index.get(&image_id) else {
+            continue;
+        };
+        let path = paths
+            .entry(tag)
+            .or_insert_with(|| keyword_path(&tree, tag))
+            .clone();
+        if let Some(path) = path {
+            catalog.images[*idx].keywords.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Join a keyword and its ancestors with `|`; the unnamed root is left out.
+fn keyword_path(tree: &HashMap<i64, (Option<String>, Option<i64>)>, tag: i64) -> Option<String> {
+    let mut levels = Vec::new();
+    let mut current = Some(tag);
+    // The step limit guards against parent cycles in a damaged catalog.
+    for _ in 0..64 {
+        let Some((name, parent)) = current.and_then(|id| tree.get(&id)) else {
+            break;
+        };
+        if let Some(name) = name.as_deref().map(str::trim).filter(|n| !n.is_empty()) {
+            levels.push(name.replace('|', "/"));
+        }
+        current = *parent;
+    }
+    levels.reverse();
+    (!levels.is_empty()).then(|| levels.join("|"))
+}
+
+/// `id_local`, `name`, `parent` and `creationId` of an `AgLibraryCollection` row.
+type CollectionRow = (i64, Option<String>, Option<i64>, Option<String>);
+
+fn read_collections(conn: &Connection, catalog: &mut ExternalCatalog) -> Result<()> {
+    let rows: Vec<CollectionRow> = query_all(
+        conn,
+        "SELECT id_local, name, parent, creationId FROM AgLibraryCollection ORDER BY id_local",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+    )
+    .context("failed to read Lightroom collections")?;
+    let mut members: HashMap<i64, Vec<i64>> = HashMap::new();
+    if table_exists(conn, "AgLibraryCollectionImage")? {
+        let pairs: Vec<(i64, i64)> = query_all(
+            conn,
+            "SELECT collection, image FROM AgLibraryCollectionImage
+             ORDER BY collection, positionInCollection, image",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .context("failed to read Lightroom collection members")?;
+        for (collection, image) in pairs {
+            members.entry(collection).or_default().push(image);
+        }
+    }
+
+    for (id, name, parent, kind) in rows {
+        let Some(name) = name.filter(|n| !n.trim().is_empty()) else {
+            continue;
+        };
+        match kind.as_deref() {
+            Some(REGULAR_COLLECTION) | Some(COLLECTION_SET) => {
+                catalog.collections.push(ExternalCollection {
+                    source_id: id,
+                    name,
+                    parent,
+                    image_ids: members.remove(&id).unwrap_or_default(),
+                });
+            }
+            Some(SMART_COLLECTION) => catalog.note_unmapped("Smart collections"),
+            _ => catalog.note_unmapped("Publish services and output collections"),
+        }
+    }
+    Ok(())
+}
+
+fn read_develop_settings(
+    conn: &Connection,
+    catalog: &mut ExternalCatalog,
+    index: &HashMap<i64, usize>,
+) -> Result<()> {
+    let rows: Vec<(i64, Option<String>)> = query_all(
+        conn,
+        "SELECT image, text FROM Adobe_imageDevelopSettings",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .context("failed to read Lightroom develop settings")?;
+    for (image_id, text) in rows {
+        let (Some(idx), Some(text)) = (index.get(&image_id), text) else {
+            continue;
+        };
+        let settings = parse_lua_table(&text);
+        let (edits, unmapped) = map_develop_settings(&settings);
+        catalog.images[*idx].edits = edits;
+        for feature in unmapped {
+            catalog.note_unmapped(feature);
+        }
+    }
+    Ok(())
+}
+
+/// Top-level value in a develop settings table. Nested tables keep their raw text.
+#[derive(Debug, Clone, PartialEq)]
+enum LuaValue {
+    Number(f64),
+    Bool(bool),
+    Text(String),
+    Table(String),
+}
+
+impl LuaValue {
+    fn number(&self) -> Option<f64> {
+        match self {
+            LuaValue::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn is_non_empty_table(&self) -> bool {
+        matches!(self, LuaValue::Table(raw) if raw.chars().any(|c| !c.is_whitespace() && c != ','))
+    }
+}
+
+/// Parse the `s = { Key = value, ... }` form Lightroom uses for develop settings.
+/// Parsing stops quietly at anything unexpected, keeping the entries read so far.
+fn parse_lua_table(text: &str) -> HashMap<String, LuaValue> {
+    let mut out = HashMap::new();
+    let Some(start) = text.find('{') else {
+        return out;
+    };
+    let chars: Vec<char> = text[start + 1..].chars().collect();
+    let mut pos = 0;
+    let skip = |pos: &mut usize| {
+        while *pos < chars.len() && (chars[*pos].is_whitespace() || chars[*pos] == ',') {
+            *pos += 1;
+        }
+    };
+
+    loop {
+        skip(&mut pos);
+        if pos >= chars.len() || chars[pos] == '}' {
+            break;
+        }
+        let key_start = pos;
+        while pos < chars.len() && (chars[pos].is_alphanumeric() || chars[pos] == '_') {
+            pos += 1;
+        }
+        let key: String = chars[key_start..pos].iter().collect();
+        while pos < chars.len() && chars[pos].is_whitespace() {
+            pos += 1;
+        }
+        if key.is_empty() || chars.get(pos) != Some(&'=') {
+            break;
+        }
+        pos += 1;
+        while pos < chars.len() && chars[pos].is_whitespace() {
+            pos += 1;
+        }
+
+        let value = match chars.get(pos) {
+            Some('"') => {
+                pos += 1;
+                let mut value = String::new();
+                while pos < chars.len() && chars[pos] != '"' {
+                    if chars[pos] == '\\' && pos + 1 < chars.len() {
+                        pos += 1;
+                        value.push(match chars[pos] {
+                            'n' => '\n',
+                            't' => '\t',
+                            other => other,
+                        });
+                    } else {
+                        value.push(chars[pos]);
+                    }
+                    pos += 1;
+                }
+                pos += 1;
+                LuaValue::Text(value)
+            }
+            Some('{') => {
+                let inner_start = pos + 1;
+                let mut depth = 0;
+                let mut in_string = false;
+                while pos < chars.len() {
+                    match chars[pos] {
+                        '\\' if in_string => pos += 1,
+                        '"' => in_string = !in_string,
+                        '{' if !in_string => depth += 1,
+                        '}' if !in_string => {
+                            depth -= 1;
+                            if depth == 0 {
+                                break;
+                            }
+                        }
+                        _ => {}
+                    }
+                    pos += 1;
+                }
+                let inner: String = chars[inner_start..pos.min(chars.len())].iter().collect();
+                pos += 1;
+                LuaValue::Table(inner)
+            }
+            Some(_) => {
+                let token_start = pos;
+                while pos < chars.len() && !matches!(chars[pos], ',' | '}' | '\n') {
+                    pos += 1;
+                }
+                let token: String = chars[token_start..pos].iter().collect();
+                match token.trim() {
+                    "true" => LuaValue::Bool(true),
+                    "false" => LuaValue::Bool(false),
+                    other => match other.parse() {
+                        Ok(number) => LuaValue::Number(number),
+                        Err(_) => LuaValue::Text(other.to_string()),
+                    },
+                }
+            }
+            None => break,
+        };
+        out.insert(key, value);
+    }
+    out
+}
+
+/// Map develop settings onto `edits`, returning features that were used but not mapped.
+fn map_develop_settings(settings: &HashMap<String, LuaValue>) -> (Option<Edits>, Vec<String>) {
+    let number = |key: &str| settings.get(key).and_then(LuaValue::number);
+    let nonzero = |key: &str| number(key).filter(|value| *value != 0.0);
+    let mut unmapped = Vec::new();
+
+    let legacy = number("Exposure2012").is_none()
+        && ["Exposure", "Brightness", "FillLight", "Recovery"]
+            .iter()
+            .any(|key| nonzero(key).is_some());
+    if legacy {
+        unmapped.push("Develop settings from process version 2010 or older".to_string());
+    }
+
+    let custom_white_balance = settings
+        .get("WhiteBalance")
+        .is_some_and(|wb| !matches!(wb, LuaValue::Text(name) if name == "As Shot"));
+    if custom_white_balance && number("Temperature").is_some() {
+        unmapped.push("Custom white balance in Kelvin".to_string());
+    }
+
+    let crop = (settings.get("HasCrop") == Some(&LuaValue::Bool(true))).then(|| {
+        json!({
+            "left": number("CropLeft").unwrap_or(0.0),
+            "top": number("CropTop").unwrap_or(0.0),
+            "right": number("CropRight").unwrap_or(1.0),
+            "bottom": number("CropBottom").unwrap_or(1.0),
+            "angle": number("CropAngle").unwrap_or(0.0),
+        })
+    });
+
+    let edits = Edits {
+        id: 0,
+        image_id: 0,
+        exposure: nonzero("Exposure2012"),
+        contrast: nonzero("Contrast2012"),
+        highlights: nonzero("Highlights2012"),
+        shadows: nonzero("Shadows2012"),
+        whites: nonzero("Whites2012"),
+        blacks: nonzero("Blacks2012"),
+        vibrance: nonzero("Vibrance"),
+        saturation: nonzero("Saturation"),
+        temperature: nonzero("IncrementalTemperature"),
+        tint: nonzero("IncrementalTint"),
+        texture: nonzero("Texture"),
+        clarity: nonzero("Clarity2012"),
+        dehaze: nonzero("Dehaze"),
+        parametric_curve_json: None,
+        color_grading_json: None,
+        crop_json: crop,
+        masking_json: None,
+        updated_at: None,
+    };
+
+    let linear_curve = |key: &str| match settings.get(key) {
+        Some(LuaValue::Table(raw)) => {
+            let points: Vec<f64> = raw
+                .split(',')
+                .filter_map(|part| part.trim().parse().ok())
+                .collect();
+            points.is_empty() || points == [0.0, 0.0, 255.0, 255.0]
+        }
+        _ => true,
+    };
+    let prefixed_nonzero = |prefixes: &[&str], skip: &[&str]| {
+        settings.iter().any(|(key, value)| {
+            prefixes.iter().any(|prefix| key.starts_with(prefix))
+                && !skip.iter().any(|suffix| key.ends_with(suffix))
+                && value.number().is_some_and(|v| v != 0.0)
+        })
+    };
+    let any_table = |keys: &[&str]| {
+        keys.iter()
+            .any(|key| settings.get(*key).is_some_and(LuaValue::is_non_empty_table))
+    };
+
+    let checks = [
+        (
+            "Tone curve",
+            ![
+                "ToneCurvePV2012",
+                "ToneCurvePV2012Red",
+                "ToneCurvePV2012Green",
+                "ToneCurvePV2012Blue",
+            ]
+            .iter()
+            .all(|key| linear_curve(key))
+                || prefixed_nonzero(&["Parametric"], &["Split"]),
+        ),
+        (
+            "HSL and color mixer",
+            prefixed_nonzero(
+                &[
+                    "HueAdjustment",
+                    "SaturationAdjustment",
+                    "LuminanceAdjustment",
+                ],
+                &[],
+            ),
+        ),
+        (
+            "Color grading and split toning",
+            prefixed_nonzero(
+                &["SplitToning", "ColorGrade"],
+                &["Hue", "Balance", "Blending"],
+            ),
+        ),
+        (
+            "Black and white conversion",
+            settings.get("ConvertToGrayscale") == Some(&LuaValue::Bool(true)),
+        ),
+        (
+            "Sharpening",
+            number("Sharpness").is_some_and(|v| ![0.0, 25.0, 40.0].contains(&v)),
+        ),
+        ("Noise reduction", nonzero("LuminanceSmoothing").is_some()),
+        (
+            "Vignetting and grain",
+            ["PostCropVignetteAmount", "VignetteAmount", "GrainAmount"]
+                .iter()
+                .any(|key| nonzero(key).is_some()),
+        ),
+        (
+            "Lens corrections",
+            number("LensProfileEnable") == Some(1.0)
+                || nonzero("LensManualDistortionAmount").is_some(),
+        ),
+        (
+            "Transform and perspective",
+            [
+                "PerspectiveVertical",
+                "PerspectiveHorizontal",
+                "PerspectiveRotate",
+                "PerspectiveAspect",
+            ]
+            .iter()
+            .any(|key| nonzero(key).is_some()),
+        ),
+        (
+            "Local adjustments and masks",
+            any_table(&[
+                "GradientBasedCorrections",
+                "CircularGradientBasedCorrections",
+                "PaintBasedCorrections",
+                "MaskGroupBasedCorrections",
+            ]),
+        ),
+        (
+            "Spot removal and red eye",
+            any_table(&["RetouchInfo", "RetouchAreas", "RedEyeInfo"]),
+        ),
+    ];
+    unmapped.extend(
+        checks
+            .iter()
+            .filter(|(_, used)| *used)
+            .map(|(label, _)| label.to_string()),
+    );
+
+    let has_edits = [
+        edits.exposure,
+        edits.contrast,
+        edits.highlights,
+        edits.shadows,
+        edits.whites,
+        edits.blacks,
+        edits.vibrance,
+        edits.saturation,
+        edits.temperature,
+        edits.tint,
+        edits.texture,
+        edits.clarity,
+        edits.dehaze,
+    ]
+    .iter()
+    .any(Option::is_some)
+        || edits.crop_json.is_some();
+    (has_edits.then_some(edits), unmapped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::CatalogDb;
+    use crate::schema::initialize_schema;
+    use crate::services::catalog_service::CatalogService;
+    use crate::services::external_catalog::{migrate_external_catalog, ExternalSource};
+    use crate::services::import::ImportCallbacks;
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn write_lrcat(path: &Path, root: &Path) {
+        let conn = Connection::open(path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE AgLibraryRootFolder (id_local INTEGER PRIMARY KEY, absolutePath TEXT, name TEXT);
+             CREATE TABLE AgLibraryFolder (id_local INTEGER PRIMARY KEY, pathFromRoot TEXT, rootFolder INTEGER);
+             CREATE TABLE AgLibraryFile (id_local INTEGER PRIMARY KEY, baseName TEXT, extension TEXT, folder INTEGER);
+             CREATE TABLE Adobe_images (id_local INTEGER PRIMARY KEY, rootFile INTEGER, captureTime TEXT,
+                 rating REAL, pick REAL, colorLabels TEXT, orientation TEXT, masterImage INTEGER, fileFormat TEXT);
+             CREATE TABLE AgHarvestedExifMetadata (image INTEGER, aperture REAL, shutterSpeed REAL,
+                 focalLength REAL, isoSpeedRating REAL, hasGPS INTEGER, gpsLatitude REAL, gpsLongitude REAL,
+                 cameraModelRef INTEGER, lensRef INTEGER);
+             CREATE TABLE AgInternedExifCameraModel (id_local INTEGER PRIMARY KEY, value TEXT);
+             CREATE TABLE AgInternedExifLens (id_local INTEGER PRIMARY KEY, value TEXT);
+             CREATE TABLE AgLibraryKeyword (id_local INTEGER PRIMARY KEY, name TEXT, parent INTEGER);
+             CREATE TABLE AgLibraryKeywordImage (image INTEGER, tag INTEGER);
+             CREATE TABLE AgLibraryCollection (id_local INTEGER PRIMARY KEY, name TEXT, parent INTEGER, creationId TEXT);
+             CREATE TABLE AgLibraryCollectionImage (collection INTEGER, image INTEGER, positionInCollection TEXT);
+             CREATE TABLE Adobe_imageDevelopSettings (image INTEGER, text TEXT);
+
+             INSERT INTO AgLibraryFolder VALUES (10, '2023/Shoot/', 1);
+             INSERT INTO AgLibraryFile VALUES (20, 'a', 'png', 10), (21, 'b', 'NEF', 10), (22, 'clip', 'mov', 10);
+             INSERT INTO Adobe_images VALUES
+                 (100, 20, '2023-06-01T10:15:30.25', 4, 1, 'Green', 'BC', NULL, 'PNG'),
+                 (101, 21, '2023-06-01T10:16:00', 0, -1, 'Wedding', 'AB', NULL, 'RAW'),
+                 (102, 20, '2023-06-01T10:15:30', 2, 0, '', 'AB', 100, 'PNG'),
+                 (103, 22, NULL, NULL, 0, NULL, NULL, NULL, 'VIDEO');
+             INSERT INTO AgInternedExifCameraModel VALUES (1, 'X-T5');
+             INSERT INTO AgInternedExifLens VALUES (1, 'XF 33mm');
+             INSERT INTO AgHarvestedExifMetadata VALUES (100, 5.0, 7.0, 33, 400, 1, 48.85, 2.35, 1, 1);
+             INSERT INTO AgLibraryKeyword VALUES (1, NULL, NULL), (2, 'Places', 1), (3, 'France', 2),
+                 (4, 'Paris', 3), (5, 'beach', 1);
+             INSERT INTO AgLibraryKeywordImage VALUES (100, 4), (100, 5), (101, 5);
+             INSERT INTO AgLibraryCollection VALUES
+                 (30, 'Clients', NULL, 'com.adobe.ag.library.group'),
+                 (31, 'Smith', 30, 'com.adobe.ag.library.collection'),
+                 (32, 'Five stars', NULL, 'com.adobe.ag.library.smart_collection');
+             INSERT INTO AgLibraryCollectionImage VALUES (31, 100, 'a'), (31, 101, 'b');",
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO AgLibraryRootFolder VALUES (1, ?1, 'Photos')",
+            [format!("{}/", root.display())],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO Adobe_imageDevelopSettings VALUES (100, ?1)",
+            [r#"s = { Blacks2012 = -5,
+	Contrast2012 = 20,
+	CameraProfile = "Adobe \"Standard\"",
+	CropAngle = 0,
+	CropBottom = 0.9,
+	CropLeft = 0.1,
+	CropRight = 1,
+	CropTop = 0,
+	Exposure2012 = 0.5,
+	HasCrop = true,
+	IncrementalTemperature = 10,
+	Look = { Name = "Adobe Color", Parameters = { Version = 3, }, },
+	GradientBasedCorrections = { { What = "Correction", CorrectionAmount = 1, }, },
+	RetouchInfo = {},
+	ToneCurvePV2012 = { 0, 0, 64, 80, 255, 255, },
+	WhiteBalance = "As Shot",
+}
+"#],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO Adobe_imageDevelopSettings VALUES (101, ?1)",
+            ["s = { Exposure = 1.5, ToneCurvePV2012 = { 0, 0, 255, 255 }, }"],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn migrates_lightroom_catalog_and_reports_the_rest() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let dir = std::env::temp_dir().join(format!("catalog_lightroom_{suffix}"));
+        let shoot = dir.join("photos").join("2023").join("Shoot");
+        fs::create_dir_all(&shoot).unwrap();
+        image::RgbImage::new(8, 8)
+            .save(shoot.join("a.png"))
+            .unwrap();
+        let lrcat = dir.join("Archive.lrcat");
+        write_lrcat(&lrcat, &dir.join("photos"));
+
+        let db = CatalogDb::in_memory().unwrap();
+        initialize_schema(db.conn()).unwrap();
+        let service = CatalogService::new(db);
+        let report = migrate_external_catalog(
+            &service,
+            ExternalSource::Lightroom,
+            &lrcat,
+            &ImportCallbacks::default(),
+            false,
+        )
+        .unwrap();
+
+        assert_eq!((report.imported, report.updated), (2, 0));
+        assert_eq!(report.offline, vec![shoot.join("b.NEF")]);
+        assert_eq!(
+            (report.collections, report.keywords, report.edits),
+            (2, 2, 1)
+        );
+        for feature in [
+            "Virtual copies",
+            "Unsupported file type .mov",
+            "Custom color label names",
+            "Smart collections",
+            "Tone curve",
+            "Local adjustments and masks",
+            "Develop settings from process version 2010 or older",
+        ] {
+            assert_eq!(report.unmapped.get(feature), Some(&1), "{feature}");
+        }
+        assert!(!report.unmapped.contains_key("Spot removal and red eye"));
+
+        let a = service
+            .find_image_by_original_path(&shoot.join("a.png"))
+            .unwrap()
+            .unwrap();
+        assert!(a.file_hash.is_some());
+        assert_eq!(a.rating, Some(4));
+        assert_eq!(a.flag.as_deref(), Some("picked"));
+        assert_eq!(a.color_label.as_deref(), Some("green"));
+        assert_eq!(a.orientation, Some(6));
+        assert_eq!(
+            a.captured_at.unwrap().to_rfc3339(),
+            "2023-06-01T10:15:30.250+00:00"
+        );
+        assert_eq!(a.aperture, Some(5.7));
+        assert_eq!(a.shutter_speed, Some(1.0 / 128.0));
+        assert_eq!(a.camera_model.as_deref(), Some("X-T5"));
+        assert_eq!(a.gps_latitude, Some(48.85));
+        let mut keywords = service.load_metadata(a.id).unwrap().keywords;
+        keywords.sort();
+        assert_eq!(keywords, vec!["Places|France|Paris", "beach"]);
+
+        let edits = service.load_edits(a.id).unwrap().unwrap();
+        assert_eq!(edits.exposure, Some(0.5));
+        assert_eq!(edits.contrast, Some(20.0));
+        assert_eq!(edits.blacks, Some(-5.0));
+        assert_eq!(edits.temperature, Some(10.0));
+        assert_eq!(edits.crop_json.unwrap()["left"], 0.1);
+
+        let b = service
+            .find_image_by_original_path(&shoot.join("b.NEF"))
+            .unwrap()
+            .unwrap();
+        assert!(b.file_hash.is_none());
+        assert_eq!((b.rating, b.flag.as_deref()), (None, Some("rejected")));
+        assert!(service.load_edits(b.id).unwrap().is_none());
+
+        let collections = service.list_collections().unwrap();
+        let clients = collections.iter().find(|c| c.name == "Clients").unwrap();
+        let smith = collections.iter().find(|c| c.name == "Smith").unwrap();
+        assert_eq!(smith.parent_id, Some(clients.id));
+        assert_eq!(
+            service.list_images_in_collection(smith.id).unwrap().len(),
+            2
+        );
+
+        // Running the migration again updates instead of duplicating.
+        let again = migrate_external_catalog(
+            &service,
+            ExternalSource::Lightroom,
+            &lrcat,
+            &ImportCallbacks::default(),
+            false,
+        )
+        .unwrap();
+        assert_eq!((again.imported, again.updated), (0, 2));
+        assert_eq!(service.count_images().unwrap(), 2);
+        assert_eq!(service.list_collections().unwrap().len(), 2);
+
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn parses_lua_settings_tables() {
+        let parsed = parse_lua_table(
+            "s = { A = 1.5, B = \"x, \\\"y\\\" }\", C = { { D = \"}\" }, }, E = false, F = -2 }",
+        );
+        assert_eq!(parsed["A"], LuaValue::Number(1.5));
+        assert_eq!(parsed["B"], LuaValue::Text("x, \"y\" }".into()));
+        assert!(parsed["C"].is_non_empty_table());
+        assert_eq!(parsed["E"], LuaValue::Bool(false));
+        assert_eq!(parsed["F"], LuaValue::Number(-2.0));
+        assert!(parse_lua_table("garbage").is_empty());
+    }
+}
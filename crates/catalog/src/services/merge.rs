@@ -0,0 +1,849 @@
+//! Merging another ZenithPhoto catalog into this one.
+//!
+//! The source catalog is copied with the SQLite backup API and upgraded in a temporary
+//! file, so a catalog from an older version can be merged and the original is never
+//! written. Source images are matched to existing ones by their remapped path, then by
+//! `file_hash`; unmatched images are added together with their keywords, edits, edit
+//! history, thumbnails, previews and perceptual hashes. Where both sides have a value,
+//! the [`ConflictPolicy`] for that area decides. Stacks are not merged.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, DatabaseName, OpenFlags};
+use serde::{Deserialize, Serialize};
+
+use crate::db::{
+    in_savepoint, query_optional, CatalogDb, Collection, CollectionImage, DbHandle, Edit,
+    EditHistory, Image, ImageKeyword, Keyword,
+};
+use crate::services::catalog_service::CatalogService;
+use crate::services::import::{ImportCallbacks, ImportProgress, ImportStage};
+
+/// Which side wins when both catalogs have a value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// The side with the newer `updated_at`; ties keep this catalog's value.
+    #[default]
+    Newest,
+    PreferSource,
+    PreferTarget,
+}
+
+impl ConflictPolicy {
+    fn source_wins(self, source: Option<DateTime<Utc>>, target: Option<DateTime<Utc>>) -> bool {
+        match self {
+            ConflictPolicy::Newest => source > target,
+            ConflictPolicy::PreferSource => true,
+            ConflictPolicy::PreferTarget => false,
+        }
+    }
+}
+
+/// Rewrites source paths that start with `from` to start with `to` instead, e.g. a
+/// laptop's card folder to where the files now live on the NAS.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathRemap {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MergeSettings {
+    /// Applied in order; the first matching prefix wins.
+    pub remaps: Vec<PathRemap>,
+    /// Rating, pick flag and color label.
+    pub ratings: ConflictPolicy,
+    pub keywords: ConflictPolicy,
+    pub edits: ConflictPolicy,
+    /// Membership of collections that exist in both catalogs.
+    pub collections: ConflictPolicy,
+}
+
+impl MergeSettings {
+    pub fn remap(&self, path: &Path) -> PathBuf {
+        self.remaps
+            .iter()
+            .find_map(|remap| {
+                path.strip_prefix(&remap.from)
+                    .ok()
+                    .map(|rest| remap.to.join(rest))
+            })
+            .unwrap_or_else(|| path.to_path_buf())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MergeReport {
+    pub added: usize,
+    pub matched_by_path: usize,
+    pub matched_by_hash: usize,
+    pub ratings_updated: usize,
+    pub keywords_updated: usize,
+    pub edits_updated: usize,
+    pub collections_created: usize,
+    pub collections_updated: usize,
+    pub thumbnails_copied: usize,
+    pub failed: Vec<(PathBuf, String)>,
+    pub canceled: bool,
+}
+
+/// Merge the catalog at `source` into the catalog behind `service` in one transaction.
+pub fn merge_catalog(
+    service: &CatalogService,
+    source: &Path,
+    settings: &MergeSettings,
+    callbacks: &ImportCallbacks,
+) -> Result<MergeReport> {
+    if let Some(target) = service.db.conn().path().filter(|p| !p.is_empty()) {
+        if fs::canonicalize(target).ok() == fs::canonicalize(source).ok() {
+            bail!("cannot merge a catalog into itself");
+        }
+    }
+    let snapshot = SourceSnapshot::open(source)?;
+    let src = snapshot.db.conn();
+
+    let tx = service
+        .db
+        .conn()
+        .unchecked_transaction()
+        .context("failed to start merge transaction")?;
+    let mut merger = Merger {
+        service,
+        src,
+        settings,
+        report: MergeReport::default(),
+        by_path: HashMap::new(),
+        by_hash: HashMap::new(),
+        target_keywords: keyword_sets(&service.db)?,
+        source_keywords: keyword_sets(src)?,
+        image_ids: HashMap::new(),
+        added: HashSet::new(),
+    };
+    for image in Image::load_all(&service.db).context("failed to list images")? {
+        if let Some(hash) = image.file_hash.clone() {
+            merger.by_hash.entry(hash).or_insert(image.id);
+        }
+        merger.by_path.insert(image.original_path.clone(), image);
+    }
+
+    let images = Image::load_all(src).context("failed to read source images")?;
+    let total = images.len();
+    for (idx, image) in images.iter().enumerate() {
+        if callbacks.cancel.is_canceled() {
+            merger.report.canceled = true;
+            break;
+        }
+        if let Some(cb) = &callbacks.progress {
+            cb(ImportProgress {
+                stage: ImportStage::Cataloging,
+                completed: idx,
+                total,
+                message: Some(image.original_path.clone()),
+            });
+        }
+        // An image that fails leaves nothing behind, not even half of its metadata.
+        if let Err(err) = in_savepoint(service.db.conn(), || merger.merge_image(image)) {
+            let path = PathBuf::from(&image.original_path);
+            let message = format!("{err:#}");
+            if let Some(cb) = &callbacks.on_error {
+                cb(path.clone(), message.clone());
+            }
+            merger.report.failed.push((path, message));
+        }
+    }
+    if !merger.report.canceled {
+        merger.merge_collections()?;
+    }
+    let report = merger.report;
+    tx.commit().context("failed to commit merge")?;
+
+    if let Some(cb) = &callbacks.progress {
+        cb(ImportProgress {
+            stage: ImportStage::Cataloging,
+            completed: total,
+            total,
+            message: None,
+        });
+    }
+    Ok(report)
+}
+
+/// An upgraded copy of the source catalog, deleted when dropped.
+struct SourceSnapshot {
+    db: CatalogDb,
+    path: PathBuf,
+}
+
+impl SourceSnapshot {
+    fn open(source: &Path) -> Result<Self> {
+        if !source.is_file() {
+            bail!("catalog not found at {}", source.display());
+        }
+        let original = Connection::open_with_flags(
+            source,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .with_context(|| format!("failed to open catalog {}", source.display()))?;
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let path = std::env::temp_dir().join(format!("zenithphoto-merge-{suffix}.db"));
+        original
+            .backup(DatabaseName::Main, &path, None)
+            .with_context(|| format!("failed to copy catalog {}", source.display()))?;
+        let db = CatalogDb::open(&path.to_string_lossy());
+        let snapshot = db.map(|db| Self {
+            db,
+            path: path.clone(),
+        });
+        if snapshot.is_err() {
+            remove_database_files(&path);
+        }
+        snapshot.with_context(|| format!("{} is not a ZenithPhoto catalog", source.display()))
+    }
+}
+
+impl Drop for SourceSnapshot {
+    fn drop(&mut self) {
+        remove_database_files(&self.path);
+    }
+}
+
+fn remove_database_files(path: &Path) {
+    for suffix in ["", "-wal", "-shm"] {
+        let mut file = path.as_os_str().to_owned();
+        file.push(suffix);
+        fs::remove_file(PathBuf::from(file)).ok();
+    }
+}
+
+/// Keywords of every image with the time the latest one was assigned.
+fn keyword_sets<H: DbHandle>(db: &H) -> Result<HashMap<i64, KeywordSet>> {
+    let names: HashMap<i64, String> = Keyword::load_all(db)
+        .context("failed to list keywords")?
+        .into_iter()
+        .map(|keyword| (keyword.id, keyword.keyword))
+        .collect();
+    let mut sets: HashMap<i64, KeywordSet> = HashMap::new();
+    for link in ImageKeyword::load_all(db).context("failed to list image keywords")? {
+        let Some(name) = names.get(&link.keyword_id) else {
+            continue;
+        };
+        let set = sets.entry(link.image_id).or_default();
+        set.keywords.insert(name.clone());
+        set.assigned_at = set.assigned_at.max(Some(link.assigned_at));
+    }
+    Ok(sets)
+}
+
+#[derive(Default)]
+struct KeywordSet {
+    keywords: BTreeSet<String>,
+    assigned_at: Option<DateTime<Utc>>,
+}
+
+struct Merger<'a> {
+    service: &'a CatalogService,
+    src: &'a Connection,
+    settings: &'a MergeSettings,
+    report: MergeReport,
+    by_path: HashMap<String, Image>,
+    by_hash: HashMap<String, i64>,
+    target_keywords: HashMap<i64, KeywordSet>,
+    source_keywords: HashMap<i64, KeywordSet>,
+    /// Source image id to target image id.
+    image_ids: HashMap<i64, i64>,
+    /// Source ids of images this merge added.
+    added: HashSet<i64>,
+}
+
+impl Merger<'_> {
+    /// Merge one source image. The report and lookups only change once nothing more can
+    /// fail, so a failed image can be rolled back without a trace.
+    fn merge_image(&mut self, source: &Image) -> Result<()> {
+        let path = self
+            .settings
+            .remap(Path::new(&source.original_path))
+            .to_string_lossy()
+            .into_owned();
+        let existing = match self.by_path.get(&path) {
+            Some(image) => Some((image.clone(), false)),
+            None => match source.file_hash.as_ref().and_then(|h| self.by_hash.get(h)) {
+                Some(id) => Some((Image::load(&self.service.db, *id)?, true)),
+                None => None,
+            },
+        };
+        let target_id = match existing {
+            Some((target, by_hash)) => {
+                self.merge_into(source, target)?;
+                if by_hash {
+                    self.report.matched_by_hash += 1;
+                } else {
+                    self.report.matched_by_path += 1;
+                }
+                return Ok(());
+            }
+            None => self.add(source, Path::new(&path))?,
+        };
+        self.image_ids.insert(source.id, target_id);
+        self.added.insert(source.id);
+        self.report.added += 1;
+        Ok(())
+    }
+
+    fn add(&mut self, source: &Image, path: &Path) -> Result<i64> {
+        let image = copy_image(self.src, source, self.service, path)?;
+        if copy_thumbnails(self.src, source.id, self.service, image.id)? {
+            self.report.thumbnails_copied += 1;
+        }
+        if let Some(hash) = &image.file_hash {
+            self.by_hash.entry(hash.clone()).or_insert(image.id);
+        }
+        let id = image.id;
+        self.by_path.insert(image.original_path.clone(), image);
+        Ok(id)
+    }
+
+    fn merge_into(&mut self, source: &Image, mut target: Image) -> Result<()> {
+        let settings = self.settings;
+        let mut counts = MergeReport::default();
+
+        let labels = |image: &Image| (image.rating, image.flag.clone(), image.color_label.clone());
+        if labels(source) != labels(&target)
+            && settings
+                .ratings
+                .source_wins(Some(source.updated_at), Some(target.updated_at))
+        {
+            target.rating = source.rating;
+            target.flag = source.flag.clone();
+            target.color_label = source.color_label.clone();
+            target.update(&self.service.db)?;
+            counts.ratings_updated += 1;
+        }
+
+        let empty = KeywordSet::default();
+        let source_keywords = self.source_keywords.get(&source.id).unwrap_or(&empty);
+        let target_keywords = self.target_keywords.get(&target.id).unwrap_or(&empty);
+        if source_keywords.keywords != target_keywords.keywords
+            && settings.keywords.source_wins(
+                source_keywords.assigned_at.or(Some(source.updated_at)),
+                target_keywords.assigned_at.or(Some(target.updated_at)),
+            )
+        {
+            let keywords: Vec<String> = source_keywords.keywords.iter().cloned().collect();
+            self.service.update_keywords(target.id, &keywords)?;
+            counts.keywords_updated += 1;
+        }
+
+        if let Some(source_edits) = Edit::load_for_image(self.src, source.id)? {
+            let apply = match self.service.load_edits(target.id)? {
+                None => true,
+                Some(target_edits) => {
+                    source_edits.updated_at != target_edits.updated_at
+                        && settings
+                            .edits
+                            .source_wins(source_edits.updated_at, target_edits.updated_at)
+                }
+            };
+            if apply {
+                self.service.apply_edits(target.id, source_edits)?;
+                counts.edits_updated += 1;
+            }
+        }
+
+        let has_thumbnail = query_optional(
+            &self.service.db,
+            "SELECT 1 FROM thumbnails WHERE image_id = ?1 AND thumb_256 IS NOT NULL",
+            [target.id],
+            |_| Ok(()),
+        )?
+        .is_some();
+        if !has_thumbnail && copy_thumbnails(self.src, source.id, self.service, target.id)? {
+            counts.thumbnails_copied += 1;
+        }
+
+        self.image_ids.insert(source.id, target.id);
+        self.report.ratings_updated += counts.ratings_updated;
+        self.report.keywords_updated += counts.keywords_updated;
+        self.report.edits_updated += counts.edits_updated;
+        self.report.thumbnails_copied += counts.thumbnails_copied;
+        Ok(())
+    }
+
+    /// Match collections by their name path, create missing ones parents-first and
+    /// reconcile membership of the ones both catalogs have.
+    fn merge_collections(&mut self) -> Result<()> {
+        let db = &self.service.db;
+        let source = Collection::load_all(self.src).context("failed to read source collections")?;
+        let mut target = Collection::load_all(db).context("failed to list collections")?;
+        let mut members: HashMap<i64, BTreeSet<i64>> = HashMap::new();
+        for link in CollectionImage::load_all(self.src)? {
+            members
+                .entry(link.collection_id)
+                .or_default()
+                .insert(link.image_id);
+        }
+
+        let source_paths = collection_paths(&source);
+        let mut ordered: Vec<&Collection> = source.iter().collect();
+        ordered.sort_by_key(|c| source_paths.get(&c.id).map(Vec::len).unwrap_or(0));
+        let mut created: HashMap<i64, i64> = HashMap::new();
+
+        for collection in ordered {
+            // A parent that is part of a cycle in the source was never created; such
+            // collections are attached at the top level.
+            let parent_id = collection
+                .parent_id
+                .and_then(|parent| created.get(&parent).copied());
+            let existing = target
+                .iter()
+                .find(|c| c.name == collection.name && c.parent_id == parent_id)
+                .cloned();
+            let source_members = members.remove(&collection.id).unwrap_or_default();
+
+            let target_id = match existing {
+                Some(existing) => {
+                    let current: BTreeSet<i64> = Collection::list_images(db, existing.id)?
+                        .into_iter()
+                        .map(|image| image.id)
+                        .collect();
+                    let source_wins = self
+                        .settings
+                        .collections
+                        .source_wins(Some(collection.updated_at), Some(existing.updated_at));
+                    let mut changed = false;
+                    for (source_image, target_image) in &self.image_ids {
+                        let in_source = source_members.contains(source_image);
+                        let in_target = current.contains(target_image);
+                        let newly_added = self.added.contains(source_image);
+                        if in_source && !in_target && (source_wins || newly_added) {
+                            Collection::add_image(db, existing.id, *target_image)?;
+                            changed = true;
+                        } else if !in_source && in_target && source_wins && !newly_added {
+                            CollectionImage::delete(db, existing.id, *target_image)?;
+                            changed = true;
+                        }
+                    }
+                    if changed {
+                        self.report.collections_updated += 1;
+                    }
+                    existing.id
+                }
+                None => {
+                    let now = Utc::now();
+                    let mut new = Collection {
+                        id: 0,
+                        name: collection.name.clone(),
+                        parent_id,
+                        created_at: collection.created_at,
+                        updated_at: now,
+                    };
+                    new.id = new.insert(db)?;
+                    for source_image in &source_members {
+                        if let Some(image_id) = self.image_ids.get(source_image) {
+                            Collection::add_image(db, new.id, *image_id)?;
+                        }
+                    }
+                    self.report.collections_created += 1;
+                    let id = new.id;
+                    target.push(new);
+                    id
+                }
+            };
+            created.insert(collection.id, target_id);
+        }
+        Ok(())
+    }
+}
+
+/// Catalog `source` from the catalog `src` at `path` in `target`, with its keywords,
+/// edits and edit history.
+pub(crate) fn copy_image<H: DbHandle>(
+    src: &H,
+    source: &Image,
+    target: &CatalogService,
+    path: &Path,
+) -> Result<Image> {
+    let created = target.register_offline_image(path, source.imported_at)?;
+    let image = Image {
+        id: created.id,
+        folder_id: created.folder_id,
+        filename: created.filename,
+        original_path: created.original_path,
+        ..source.clone()
+    };
+    image.update(&target.db)?;
+
+    for keyword in ImageKeyword::list_keywords_for_image(src, source.id)? {
+        target.add_keyword_to_image(image.id, &keyword.keyword)?;
+    }
+    if let Some(edits) = Edit::load_for_image(src, source.id)? {
+        target.apply_edits(image.id, edits)?;
+    }
+    for entry in EditHistory::list_for_image(src, source.id)? {
+        EditHistory {
+            image_id: image.id,
+            ..entry
+        }
+        .insert(&target.db)?;
+    }
+    Ok(image)
+}
+
+/// Copy thumbnails, the preview and the perceptual hash as stored in `src`. Returns
+/// whether there was a thumbnail to copy.
+pub(crate) fn copy_thumbnails<H: DbHandle>(
+    src: &H,
+    source_id: i64,
+    target: &CatalogService,
+    target_id: i64,
+) -> Result<bool> {
+    let thumbnails = query_optional(
+        src,
+        "SELECT thumb_256, thumb_1024 FROM thumbnails WHERE image_id = ?1",
+        [source_id],
+        |row| Ok((row.get::<_, Option<Vec<u8>>>(0)?, row.get(1)?)),
+    )?;
+    let Some((thumb_256, thumb_1024)) = thumbnails.filter(|(small, _)| small.is_some()) else {
+        return Ok(false);
+    };
+    target.upsert_thumbnail(target_id, thumb_256, thumb_1024)?;
+
+    let preview: Option<Option<Vec<u8>>> = query_optional(
+        src,
+        "SELECT preview_blob FROM previews WHERE image_id = ?1",
+        [source_id],
+        |row| Ok(row.get(0)?),
+    )?;
+    if let Some(blob) = preview {
+        target
+            .db
+            .execute(
+                "INSERT INTO previews (image_id, preview_blob) VALUES (?1, ?2)
+                 ON CONFLICT(image_id) DO UPDATE SET preview_blob = excluded.preview_blob",
+                params![target_id, blob],
+            )
+            .with_context(|| format!("failed to copy preview for image_id={target_id}"))?;
+    }
+    let dhash: Option<i64> = query_optional(
+        src,
+        "SELECT dhash FROM perceptual_hashes WHERE image_id = ?1",
+        [source_id],
+        |row| Ok(row.get(0)?),
+    )?;
+    if let Some(dhash) = dhash {
+        target
+            .db
+            .execute(
+                "INSERT INTO perceptual_hashes (image_id, dhash) VALUES (?1, ?2)
+                 ON CONFLICT(image_id) DO UPDATE SET dhash = excluded.dhash",
+                params![target_id, dhash],
+            )
+            .with_context(|| format!("failed to copy perceptual hash for image_id={target_id}"))?;
+    }
+    Ok(true)
+}
+
+/// Names from the top level down to each collection, used to order parents first.
+pub(crate) fn collection_paths(collections: &[Collection]) -> HashMap<i64, Vec<String>> {
+    let by_id: HashMap<i64, &Collection> = collections.iter().map(|c| (c.id, c)).collect();
+    collections
+        .iter()
+        .map(|collection| {
+            let mut path = Vec::new();
+            let mut current = Some(collection);
+            // The step limit guards against parent cycles.
+            for _ in 0..64 {
+                let Some(c) = current else {
+                    break;
+                };
+                path.push(c.name.clone());
+                current = c.parent_id.and_then(|id| by_id.get(&id).copied());
+            }
+            path.reverse();
+            (collection.id, path)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Thumbnail;
+    use crate::services::import::{import_files, ImportMethod};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let dir = std::env::temp_dir().join(format!("catalog_merge_{name}_{suffix}"));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn open(path: &Path) -> CatalogService {
+        CatalogService::new(CatalogDb::open(&path.to_string_lossy()).unwrap())
+    }
+
+    fn import(service: &CatalogService, paths: &[PathBuf]) -> Vec<i64> {
+        import_files(
+            service,
+            paths,
+            &[],
+            ImportMethod::Add,
+            None,
+            ImportCallbacks::default(),
+        )
+        .unwrap();
+        paths
+            .iter()
+            .map(|p| service.find_image_by_original_path(p).unwrap().unwrap().id)
+            .collect()
+    }
+
+    #[test]
+    fn merges_laptop_catalog_into_main() {
+        let dir = temp_dir("laptop");
+        let card = dir.join("card");
+        let nas = dir.join("nas");
+        fs::create_dir_all(&card).unwrap();
+        fs::create_dir_all(&nas).unwrap();
+        for (idx, name) in ["a.png", "b.png", "c.png"].iter().enumerate() {
+            let pixel = image::Rgb([idx as u8 * 80, 10, 10]);
+            let img = image::RgbImage::from_pixel(8, 8, pixel);
+            img.save(card.join(name)).unwrap();
+            img.save(nas.join(name)).unwrap();
+        }
+
+        // Main catalog knows a.png on the NAS and b.png under a different name.
+        fs::rename(nas.join("b.png"), nas.join("b-renamed.png")).unwrap();
+        let main = open(&dir.join("main.zenithphotocatalog"));
+        let main_ids = import(&main, &[nas.join("a.png"), nas.join("b-renamed.png")]);
+        main.update_rating(main_ids[0], 2).unwrap();
+        main.update_keywords(main_ids[1], &["keep".to_string()])
+            .unwrap();
+        let mut picks = Collection {
+            id: 0,
+            name: "Picks".to_string(),
+            parent_id: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        picks.id = picks.insert(&main.db).unwrap();
+        Collection::add_image(&main.db, picks.id, main_ids[1]).unwrap();
+
+        // The laptop catalog was built from the card, and edited later.
+        let laptop_path = dir.join("laptop.zenithphotocatalog");
+        let laptop = open(&laptop_path);
+        let laptop_ids = import(
+            &laptop,
+            &[card.join("a.png"), card.join("b.png"), card.join("c.png")],
+        );
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        laptop.update_rating(laptop_ids[0], 5).unwrap();
+        laptop
+            .update_keywords(laptop_ids[1], &["Trip|Day 1".to_string()])
+            .unwrap();
+        laptop.apply_edits(laptop_ids[2], sample_edits()).unwrap();
+        let mut trip = Collection {
+            id: 0,
+            name: "Trip".to_string(),
+            parent_id: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        trip.id = trip.insert(&laptop.db).unwrap();
+        let mut laptop_picks = Collection {
+            id: 0,
+            name: "Picks".to_string(),
+            parent_id: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now() - chrono::Duration::days(1),
+        };
+        laptop_picks.id = laptop_picks.insert(&laptop.db).unwrap();
+        for id in &laptop_ids {
+            Collection::add_image(&laptop.db, trip.id, *id).unwrap();
+        }
+        Collection::add_image(&laptop.db, laptop_picks.id, laptop_ids[2]).unwrap();
+        drop(laptop);
+
+        let settings = MergeSettings {
+            remaps: vec![PathRemap {
+                from: card.clone(),
+                to: nas.clone(),
+            }],
+            keywords: ConflictPolicy::PreferTarget,
+            ..MergeSettings::default()
+        };
+        let report =
+            merge_catalog(&main, &laptop_path, &settings, &ImportCallbacks::default()).unwrap();
+
+        assert_eq!(
+            (report.matched_by_path, report.matched_by_hash, report.added),
+            (1, 1, 1)
+        );
+        assert_eq!(report.ratings_updated, 1);
+        assert_eq!(report.keywords_updated, 0);
+        assert_eq!(report.edits_updated, 0);
+        assert_eq!(report.thumbnails_copied, 1);
+        assert_eq!(
+            (report.collections_created, report.collections_updated),
+            (1, 1)
+        );
+
+        let a = main.load_metadata(main_ids[0]).unwrap();
+        assert_eq!(a.image.rating, Some(5));
+        assert_eq!(
+            main.load_metadata(main_ids[1]).unwrap().keywords,
+            vec!["keep"]
+        );
+        let c = main
+            .find_image_by_original_path(&nas.join("c.png"))
+            .unwrap()
+            .unwrap();
+        assert!(c.file_hash.is_some());
+        assert_eq!(main.load_edits(c.id).unwrap().unwrap().exposure, Some(0.7));
+        assert!(Thumbnail::load(&main.db, c.id)
+            .unwrap()
+            .thumb_1024
+            .is_some());
+
+        let collections = main.list_collections().unwrap();
+        let trip = collections.iter().find(|c| c.name == "Trip").unwrap();
+        assert_eq!(main.list_images_in_collection(trip.id).unwrap().len(), 3);
+        // The laptop's Picks is older, so only the new image joins the main one.
+        let picks: BTreeSet<i64> = main
+            .list_images_in_collection(picks.id)
+            .unwrap()
+            .into_iter()
+            .map(|image| image.id)
+            .collect();
+        assert_eq!(picks, BTreeSet::from([main_ids[1], c.id]));
+
+        // Merging the same catalog again adds nothing.
+        let again =
+            merge_catalog(&main, &laptop_path, &settings, &ImportCallbacks::default()).unwrap();
+        assert_eq!((again.added, again.collections_created), (0, 0));
+        assert_eq!(main.count_images().unwrap(), 3);
+
+        assert!(merge_catalog(
+            &main,
+            &dir.join("main.zenithphotocatalog"),
+            &settings,
+            &ImportCallbacks::default()
+        )
+        .is_err());
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn failed_images_leave_nothing_behind() {
+        let dir = temp_dir("failed");
+        let paths: Vec<PathBuf> = ["a.png", "b.png", "c.png"]
+            .iter()
+            .map(|name| dir.join(name))
+            .collect();
+        for (idx, path) in paths.iter().enumerate() {
+            image::RgbImage::from_pixel(8, 8, image::Rgb([idx as u8 * 80, 10, 10]))
+                .save(path)
+                .unwrap();
+        }
+        let main = open(&dir.join("main.zenithphotocatalog"));
+        let main_a = import(&main, &paths[..1])[0];
+
+        let laptop_path = dir.join("laptop.zenithphotocatalog");
+        let laptop = open(&laptop_path);
+        let laptop_ids = import(&laptop, &paths);
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        laptop.update_rating(laptop_ids[0], 5).unwrap();
+        laptop
+            .update_keywords(laptop_ids[1], &["Trip".to_string()])
+            .unwrap();
+        // The edits of a and b can't be read back, so both fail after some of their
+        // metadata was already written.
+        for id in &laptop_ids[..2] {
+            laptop.apply_edits(*id, sample_edits()).unwrap();
+        }
+        laptop
+            .db
+            .execute("UPDATE edits SET updated_at = 'yesterday'", [])
+            .unwrap();
+        drop(laptop);
+
+        let report = merge_catalog(
+            &main,
+            &laptop_path,
+            &MergeSettings::default(),
+            &ImportCallbacks::default(),
+        )
+        .unwrap();
+        let failed: Vec<&PathBuf> = report.failed.iter().map(|(path, _)| path).collect();
+        assert_eq!(failed, [&paths[0], &paths[1]]);
+        assert_eq!((report.matched_by_path, report.added), (0, 1));
+        assert_eq!(report.ratings_updated, 0);
+
+        assert_eq!(main.load_metadata(main_a).unwrap().image.rating, None);
+        assert!(main
+            .find_image_by_original_path(&paths[1])
+            .unwrap()
+            .is_none());
+        assert!(main
+            .find_image_by_original_path(&paths[2])
+            .unwrap()
+            .is_some());
+        assert!(Keyword::load_all(&main.db).unwrap().is_empty());
+        fs::remove_dir_all(dir).ok();
+    }
+
+    fn sample_edits() -> Edit {
+        Edit {
+            id: 0,
+            image_id: 0,
+            exposure: Some(0.7),
+            contrast: None,
+            highlights: None,
+            shadows: None,
+            whites: None,
+            blacks: None,
+            vibrance: None,
+            saturation: None,
+            temperature: None,
+            tint: None,
+            texture: None,
+            clarity: None,
+            dehaze: None,
+            parametric_curve_json: None,
+            color_grading_json: None,
+            crop_json: None,
+            masking_json: None,
+            updated_at: None,
+        }
+    }
+
+    #[test]
+    fn remaps_path_prefixes() {
+        let settings = MergeSettings {
+            remaps: vec![PathRemap {
+                from: PathBuf::from("/Volumes/Card"),
+                to: PathBuf::from("/nas/photos"),
+            }],
+            ..MergeSettings::default()
+        };
+        assert_eq!(
+            settings.remap(Path::new("/Volumes/Card/2024/a.nef")),
+            PathBuf::from("/nas/photos/2024/a.nef")
+        );
+        assert_eq!(
+            settings.remap(Path::new("/Volumes/Cards/a.nef")),
+            PathBuf::from("/Volumes/Cards/a.nef")
+        );
+    }
+}
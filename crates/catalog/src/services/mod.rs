@@ -1,3 +1,50 @@
+pub mod backup;
+pub mod capture_time;
 pub mod catalog_service;
+pub mod contact_sheet;
+pub mod darktable;
+pub mod digikam;
+pub mod export;
+pub mod export_metadata;
+pub mod export_presets;
+pub mod external_catalog;
+pub mod gallery;
+pub mod geocode;
+pub mod geotag;
+pub mod gpx;
+pub mod import;
+pub mod lightroom;
+pub mod merge;
+pub mod paging;
+mod pdf;
+pub mod perceptual_hash;
+pub mod portable;
+pub mod roots;
+pub mod sort;
+pub mod verify;
+pub mod watermarks;
 
-pub use catalog_service::{CatalogService, Edits};
+pub use backup::{BackupInfo, BackupSchedule, BackupSettings};
+pub use capture_time::{
+    CaptureTimeAdjustment, CaptureTimeReport, CaptureTimeSettings, CaptureTimeUndo,
+};
+pub use catalog_service::{CatalogService, DuplicateCluster, Edits, StackMembership};
+pub use contact_sheet::{
+    ContactSheetCaptions, ContactSheetSettings, ContactSheetSource, PageOrientation, PageSize,
+};
+pub use export::{
+    ExportFormat, ExportSettings, OutputSharpening, ResizeMode, SharpenAmount, SharpenMedium,
+};
+pub use export_metadata::{MetadataPolicy, RightsInfo};
+pub use export_presets::NamedExportPreset;
+pub use external_catalog::{ExternalImportReport, ExternalSource};
+pub use gallery::{GalleryCaptions, GallerySettings, GalleryTemplate};
+pub use geocode::{GeocodeReport, GeocodeSettings};
+pub use geotag::{GeotagMatch, GeotagMiss, GeotagPreview, GeotagSettings, GeotagUndo};
+pub use merge::{ConflictPolicy, MergeReport, MergeSettings, PathRemap};
+pub use paging::{ImagePage, ImageScope, PageCursor};
+pub use portable::{PortableCatalogReport, PortableCatalogSettings};
+pub use roots::{RootRelocation, RootSummary};
+pub use sort::{ImageSort, SortKey};
+pub use verify::{Finding, FindingKind, Repair, RepairSummary, VerifyReport};
+pub use watermarks::{WatermarkAnchor, WatermarkDefinition, WatermarkKind};
@@ -0,0 +1,383 @@
+//! Keyset pagination over the image lists the Folio browses.
+//!
+//! A page ends with a [`PageCursor`] holding the sort values of its last image; the next
+//! page starts strictly after them, so paging stays cheap deep into a large catalog and
+//! doesn't skip or repeat images when rows are added in between. Pages come out in the
+//! same order [`sort_images`](super::sort::sort_images) puts a full list in.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::params_from_iter;
+use rusqlite::types::Value;
+
+use crate::db::timeline::DAY_SQL;
+use crate::db::{query_all, query_one, to_rfc3339, CatalogDb, DatePeriod, Image};
+use crate::services::sort::{ImageSort, SortKey, LABEL_ORDER};
+
+const IMAGE_COLUMNS: &str = "i.id, i.folder_id, i.filename, i.original_path, i.sidecar_path,
+    i.sidecar_hash, i.filesize, i.file_hash, i.file_modified_at, i.imported_at, i.captured_at,
+    i.camera_make, i.camera_model, i.lens_model, i.focal_length, i.aperture, i.shutter_speed,
+    i.iso, i.orientation, i.gps_latitude, i.gps_longitude, i.gps_altitude, i.rating, i.flag,
+    i.color_label, i.metadata_json, i.created_at, i.updated_at";
+
+/// Number of columns in [`IMAGE_COLUMNS`]; the sort values follow them.
+const IMAGE_COLUMN_COUNT: usize = 28;
+
+/// The images a list is drawn from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImageScope {
+    All,
+    /// A folder and everything below it.
+    Folder(String),
+    ImportedSince(DateTime<Utc>),
+    TakenIn(DatePeriod),
+    Collection(i64),
+}
+
+/// Where the next page starts: the sort values of the last image handed out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageCursor {
+    values: Vec<Value>,
+    id: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImagePage {
+    pub images: Vec<Image>,
+    /// Set when there may be more images after this page.
+    pub next: Option<PageCursor>,
+}
+
+/// The `LIKE` patterns matching a folder's path and the paths below it.
+pub(crate) fn folder_patterns(folder_path: &Path) -> (String, String) {
+    let normalized = folder_path.to_string_lossy().to_string();
+    let mut prefix = normalized.clone();
+    if !normalized.ends_with(std::path::MAIN_SEPARATOR)
+        && !normalized.ends_with('/')
+        && !normalized.ends_with('\\')
+    {
+        prefix.push(std::path::MAIN_SEPARATOR);
+    }
+    prefix.push('%');
+    (normalized, prefix)
+}
+
+/// Collects bound values and hands out their `?N` placeholders.
+#[derive(Default)]
+struct Binds(Vec<Value>);
+
+impl Binds {
+    fn push(&mut self, value: impl Into<Value>) -> String {
+        self.0.push(value.into());
+        format!("?{}", self.0.len())
+    }
+}
+
+/// The `FROM ... WHERE ...` part selecting `scope`.
+fn scope_sql(scope: &ImageScope, binds: &mut Binds) -> String {
+    match scope {
+        ImageScope::All => "FROM images i WHERE 1".to_string(),
+        ImageScope::Folder(path) => {
+            let (path, prefix) = folder_patterns(Path::new(path));
+            format!(
+                "FROM images i
+                 INNER JOIN folders f ON f.id = i.folder_id
+                 WHERE (f.path = {} OR f.path LIKE {})",
+                binds.push(path),
+                binds.push(prefix)
+            )
+        }
+        ImageScope::ImportedSince(since) => format!(
+            "FROM images i WHERE i.imported_at >= {}",
+            binds.push(to_rfc3339(*since))
+        ),
+        ImageScope::TakenIn(period) => format!(
+            "FROM images i WHERE {DAY_SQL} LIKE {} || '%'",
+            binds.push(period.prefix())
+        ),
+        ImageScope::Collection(collection_id) => format!(
+            "FROM images i
+             INNER JOIN collection_images ci ON ci.image_id = i.id
+             WHERE ci.collection_id = {}",
+            binds.push(*collection_id)
+        ),
+    }
+}
+
+/// The expressions a list is ordered by, each with whether it runs descending. Missing
+/// values sort last, and the image id breaks the remaining ties.
+fn sort_columns(sort: &ImageSort, scope: &ImageScope) -> Vec<(String, bool)> {
+    let value = match sort.key {
+        SortKey::CaptureTime => Some("i.captured_at".to_string()),
+        SortKey::ImportTime => Some("i.imported_at".to_string()),
+        SortKey::Filename => Some("lower(i.filename)".to_string()),
+        SortKey::Rating => Some("COALESCE(i.rating, 0)".to_string()),
+        SortKey::ColorLabel => {
+            let cases: String = LABEL_ORDER
+                .iter()
+                .enumerate()
+                .map(|(index, label)| format!(" WHEN '{label}' THEN {index}"))
+                .collect();
+            Some(format!("(CASE lower(i.color_label){cases} END)"))
+        }
+        SortKey::FileSize => Some("i.filesize".to_string()),
+        SortKey::Camera => Some(
+            "NULLIF(lower(trim(COALESCE(trim(i.camera_make), '') || ' '
+                || COALESCE(trim(i.camera_model), ''))), '')"
+                .to_string(),
+        ),
+        SortKey::EditTime => {
+            Some("(SELECT MAX(e.updated_at) FROM edits e WHERE e.image_id = i.id)".to_string())
+        }
+        SortKey::Manual => match scope {
+            ImageScope::Collection(_) => Some("ci.position".to_string()),
+            _ => None,
+        },
+    };
+    value
+        .map(|expr| (expr, sort.descending))
+        .into_iter()
+        .chain([("i.captured_at".to_string(), false)])
+        .collect()
+}
+
+/// The condition matching the rows that sort after `cursor`.
+fn after_sql(columns: &[(String, bool)], cursor: &PageCursor, binds: &mut Binds) -> String {
+    let mut condition = format!("i.id > {}", binds.push(cursor.id));
+    for ((expr, descending), value) in columns.iter().zip(&cursor.values).rev() {
+        condition = if matches!(value, Value::Null) {
+            // Only more missing values can follow a missing one.
+            format!("({expr} IS NULL AND {condition})")
+        } else {
+            let placeholder = binds.push(value.clone());
+            let op = if *descending { "<" } else { ">" };
+            format!(
+                "(({expr} {op} {placeholder} OR {expr} IS NULL)
+                  OR ({expr} = {placeholder} AND {condition}))"
+            )
+        };
+    }
+    condition
+}
+
+/// Up to `limit` images of `scope` in `sort` order, starting after `after`.
+pub fn images_page(
+    db: &CatalogDb,
+    scope: &ImageScope,
+    sort: &ImageSort,
+    after: Option<&PageCursor>,
+    limit: usize,
+) -> Result<ImagePage> {
+    let mut binds = Binds::default();
+    let from = scope_sql(scope, &mut binds);
+    let columns = sort_columns(sort, scope);
+    let after = after
+        .map(|cursor| format!("AND {}", after_sql(&columns, cursor, &mut binds)))
+        .unwrap_or_default();
+    let selected: String = columns
+        .iter()
+        .map(|(expr, _)| format!(", {expr}"))
+        .collect();
+    let order: String = columns
+        .iter()
+        .map(|(expr, descending)| {
+            let direction = if *descending { " DESC" } else { "" };
+            format!("{expr} IS NULL, {expr}{direction}, ")
+        })
+        .collect();
+    let limit_sql = binds.push(limit as i64);
+    let sql = format!(
+        "SELECT {IMAGE_COLUMNS}{selected}
+         {from} {after}
+         ORDER BY {order}i.id
+         LIMIT {limit_sql}"
+    );
+
+    let rows: Vec<(Image, Vec<Value>)> = query_all(db, &sql, params_from_iter(binds.0), |row| {
+        let values = (0..columns.len())
+            .map(|index| row.get::<_, Value>(IMAGE_COLUMN_COUNT + index))
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok((Image::from_row(row)?, values))
+    })
+    .context("failed to load a page of images")?;
+
+    let next = match rows.last() {
+        Some((image, values)) if rows.len() == limit => Some(PageCursor {
+            values: values.clone(),
+            id: image.id,
+        }),
+        _ => None,
+    };
+    Ok(ImagePage {
+        images: rows.into_iter().map(|(image, _)| image).collect(),
+        next,
+    })
+}
+
+/// How many images `scope` holds.
+pub fn count_images(db: &CatalogDb, scope: &ImageScope) -> Result<usize> {
+    let mut binds = Binds::default();
+    let from = scope_sql(scope, &mut binds);
+    let count: i64 = query_one(
+        db,
+        &format!("SELECT COUNT(*) {from}"),
+        params_from_iter(binds.0),
+        |row| Ok(row.get(0)?),
+    )
+    .context("failed to count images")?;
+    Ok(count as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{test_folder, test_image, DbHandle};
+    use crate::services::sort::sort_images;
+    use crate::services::CatalogService;
+    use chrono::TimeZone;
+
+    #[test]
+    fn pages_match_the_full_sort_order() {
+        let service = CatalogService::new(CatalogDb::in_memory().unwrap());
+        let folder_id = test_folder("/photos/paged").insert(&service.db).unwrap();
+        let at = |day: i64| {
+            Utc.with_ymd_and_hms(2024, 5, 1 + day as u32, 9, 0, 0)
+                .single()
+        };
+        let images: Vec<Image> = (0..23)
+            .map(|index| {
+                let mut image = Image {
+                    filename: format!("IMG_{:02}.jpg", (index * 7) % 23),
+                    filesize: (index % 3 != 0).then_some(index % 4 * 100),
+                    captured_at: (index % 5 != 0).then(|| at(index % 4)).flatten(),
+                    camera_make: (index % 2 == 0).then(|| "Nikon".to_string()),
+                    rating: (index % 4 != 0).then_some(index % 3),
+                    color_label: ["red", "blue", "green", "orange"]
+                        .get(index as usize % 5)
+                        .map(|label| label.to_string()),
+                    ..test_image(folder_id, &format!("/photos/paged/IMG_{index}.jpg"))
+                };
+                image.id = image.insert(&service.db).unwrap();
+                image
+            })
+            .collect();
+        for image in images.iter().step_by(3) {
+            service
+                .db
+                .execute(
+                    "INSERT INTO edits (image_id, updated_at) VALUES (?1, ?2)",
+                    rusqlite::params![
+                        image.id,
+                        to_rfc3339(
+                            Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, image.id as u32)
+                                .unwrap()
+                        )
+                    ],
+                )
+                .unwrap();
+        }
+        let collection = service.create_collection("Paged").unwrap();
+        for image in images.iter().rev().step_by(2) {
+            service
+                .add_image_to_collection(collection.id, image.id)
+                .unwrap();
+        }
+
+        let scopes = [
+            ImageScope::All,
+            ImageScope::Folder("/photos/paged".into()),
+            ImageScope::Collection(collection.id),
+        ];
+        for scope in &scopes {
+            for key in SortKey::ALL {
+                for descending in [false, true] {
+                    let sort = ImageSort { key, descending };
+                    let mut expected = match scope {
+                        ImageScope::Collection(id) => service.list_images_in_collection(*id),
+                        _ => service.list_all_photos(),
+                    }
+                    .unwrap();
+                    let collection_id = match scope {
+                        ImageScope::Collection(id) => Some(*id),
+                        _ => None,
+                    };
+                    if key == SortKey::Manual && collection_id.is_none() {
+                        // Without positions the manual order is the loaded capture order.
+                        sort_images(&service, &mut expected, &ImageSort::default(), None).unwrap();
+                    } else {
+                        sort_images(&service, &mut expected, &sort, collection_id).unwrap();
+                    }
+
+                    let mut paged = Vec::new();
+                    let mut cursor = None;
+                    loop {
+                        let page =
+                            images_page(&service.db, scope, &sort, cursor.as_ref(), 4).unwrap();
+                        paged.extend(page.images.iter().map(|image| image.id));
+                        match page.next {
+                            Some(next) => cursor = Some(next),
+                            None => break,
+                        }
+                    }
+                    let expected: Vec<i64> = expected.iter().map(|image| image.id).collect();
+                    assert_eq!(paged, expected, "{scope:?} by {sort:?}");
+                }
+            }
+            let total = match scope {
+                ImageScope::Collection(_) => 12,
+                _ => 23,
+            };
+            assert_eq!(count_images(&service.db, scope).unwrap(), total);
+        }
+
+        let may = ImageScope::TakenIn("2024-05-02".parse().unwrap());
+        let page = images_page(&service.db, &may, &ImageSort::default(), None, 50).unwrap();
+        assert!(page.next.is_none());
+        assert_eq!(page.images.len(), count_images(&service.db, &may).unwrap());
+        assert!(!page.images.is_empty());
+    }
+
+    #[test]
+    fn manual_order_follows_reordering() {
+        let service = CatalogService::new(CatalogDb::in_memory().unwrap());
+        let folder_id = test_folder("/photos/paged").insert(&service.db).unwrap();
+        let ids: Vec<i64> = (0..5)
+            .map(|index| {
+                test_image(folder_id, &format!("/photos/paged/IMG_{index}.jpg"))
+                    .insert(&service.db)
+                    .unwrap()
+            })
+            .collect();
+        let collection = service.create_collection("Sequence").unwrap();
+        for id in &ids {
+            service.add_image_to_collection(collection.id, *id).unwrap();
+        }
+
+        // Listed images lead; the rest keep their order after them.
+        service
+            .reorder_collection(collection.id, &[ids[3], ids[0], ids[3]])
+            .unwrap();
+        assert!(service
+            .reorder_collection(collection.id, &[ids[1], 9999])
+            .is_err());
+
+        let scope = ImageScope::Collection(collection.id);
+        let manual = ImageSort {
+            key: SortKey::Manual,
+            descending: false,
+        };
+        let mut paged = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = images_page(&service.db, &scope, &manual, cursor.as_ref(), 2).unwrap();
+            paged.extend(page.images.iter().map(|image| image.id));
+            match page.next {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(paged, [ids[3], ids[0], ids[1], ids[2], ids[4]]);
+    }
+}
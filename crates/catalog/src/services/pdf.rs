@@ -0,0 +1,350 @@
+//! Minimal PDF 1.4 writer for generated documents such as contact sheets.
+//!
+//! Supports the two standard Helvetica fonts (WinAnsi text), JPEG images embedded as
+//! `DCTDecode` XObjects, filled rectangles and polygons. Content streams are
+//! Flate-compressed. Coordinates are PDF points with the origin at the bottom left.
+
+use std::fmt::Write as _;
+use std::io::Write as _;
+
+use anyhow::{Context, Result};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+const FONT_REGULAR: &str = "F1";
+const FONT_BOLD: &str = "F2";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    fn resource(self) -> &'static str {
+        match self {
+            Font::Regular => FONT_REGULAR,
+            Font::Bold => FONT_BOLD,
+        }
+    }
+}
+
+/// Handle to an image added with [`PdfDocument::add_jpeg`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ImageRef(usize);
+
+/// Drawing operations for one page.
+#[derive(Debug, Default)]
+pub(crate) struct Page {
+    ops: String,
+    images: Vec<usize>,
+}
+
+impl Page {
+    pub fn fill_rgb(&mut self, rgb: [u8; 3]) {
+        let [r, g, b] = rgb.map(|c| c as f32 / 255.0);
+        let _ = writeln!(self.ops, "{r:.3} {g:.3} {b:.3} rg");
+    }
+
+    pub fn rect(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        let _ = writeln!(self.ops, "{x:.2} {y:.2} {width:.2} {height:.2} re f");
+    }
+
+    pub fn polygon(&mut self, points: &[(f32, f32)]) {
+        let Some(((x0, y0), rest)) = points.split_first() else {
+            return;
+        };
+        let _ = writeln!(self.ops, "{x0:.2} {y0:.2} m");
+        for (x, y) in rest {
+            let _ = writeln!(self.ops, "{x:.2} {y:.2} l");
+        }
+        self.ops.push_str("h f\n");
+    }
+
+    pub fn text(&mut self, font: Font, size: f32, x: f32, y: f32, text: &str) {
+        let _ = writeln!(
+            self.ops,
+            "BT /{} {size:.1} Tf {x:.2} {y:.2} Td ({}) Tj ET",
+            font.resource(),
+            encode_text(text)
+        );
+    }
+
+    pub fn image(&mut self, image: ImageRef, x: f32, y: f32, width: f32, height: f32) {
+        if !self.images.contains(&image.0) {
+            self.images.push(image.0);
+        }
+        let _ = writeln!(
+            self.ops,
+            "q {width:.2} 0 0 {height:.2} {x:.2} {y:.2} cm /Im{} Do Q",
+            image.0
+        );
+    }
+}
+
+struct Jpeg {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+#[derive(Default)]
+pub(crate) struct PdfDocument {
+    title: Option<String>,
+    images: Vec<Jpeg>,
+    pages: Vec<(f32, f32, Page)>,
+}
+
+impl PdfDocument {
+    pub fn new(title: Option<String>) -> Self {
+        Self {
+            title,
+            ..Self::default()
+        }
+    }
+
+    /// Register baseline RGB JPEG data for use on any page.
+    pub fn add_jpeg(&mut self, width: u32, height: u32, data: Vec<u8>) -> ImageRef {
+        self.images.push(Jpeg {
+            width,
+            height,
+            data,
+        });
+        ImageRef(self.images.len() - 1)
+    }
+
+    pub fn add_page(&mut self, width: f32, height: f32, page: Page) {
+        self.pages.push((width, height, page));
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn finish(self) -> Result<Vec<u8>> {
+        // Fixed objects: 1 catalog, 2 page tree, 3-4 fonts, 5 info; then images, then
+        // a page and content stream per page.
+        let image_base = 6;
+        let page_base = image_base + self.images.len();
+        let page_ids: Vec<usize> = (0..self.pages.len())
+            .map(|idx| page_base + idx * 2)
+            .collect();
+
+        let mut objects: Vec<Vec<u8>> = Vec::new();
+        objects.push(b"<< /Type /Catalog /Pages 2 0 R >>".to_vec());
+        let kids: Vec<String> = page_ids.iter().map(|id| format!("{id} 0 R")).collect();
+        objects.push(
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                kids.join(" "),
+                page_ids.len()
+            )
+            .into_bytes(),
+        );
+        for base_font in ["Helvetica", "Helvetica-Bold"] {
+            objects.push(
+                format!(
+                    "<< /Type /Font /Subtype /Type1 /BaseFont /{base_font} \
+                     /Encoding /WinAnsiEncoding >>"
+                )
+                .into_bytes(),
+            );
+        }
+        let mut info = String::from("<< /Producer (ZenithPhoto)");
+        if let Some(title) = &self.title {
+            let _ = write!(info, " /Title ({})", encode_text(title));
+        }
+        info.push_str(" >>");
+        objects.push(info.into_bytes());
+
+        for image in &self.images {
+            objects.push(stream_object(
+                &format!(
+                    "/Type /XObject /Subtype /Image /Width {} /Height {} \
+                     /ColorSpace /DeviceRGB /BitsPerComponent 8 /Filter /DCTDecode",
+                    image.width, image.height
+                ),
+                &image.data,
+            ));
+        }
+
+        for (idx, (width, height, page)) in self.pages.iter().enumerate() {
+            let content_id = page_ids[idx] + 1;
+            let xobjects: Vec<String> = page
+                .images
+                .iter()
+                .map(|image| format!("/Im{image} {} 0 R", image_base + image))
+                .collect();
+            objects.push(
+                format!(
+                    "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {width:.2} {height:.2}] \
+                     /Resources << /Font << /{FONT_REGULAR} 3 0 R /{FONT_BOLD} 4 0 R >> \
+                     /XObject << {} >> >> /Contents {content_id} 0 R >>",
+                    xobjects.join(" ")
+                )
+                .into_bytes(),
+            );
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder
+                .write_all(page.ops.as_bytes())
+                .context("failed to compress page content")?;
+            let compressed = encoder
+                .finish()
+                .context("failed to compress page content")?;
+            objects.push(stream_object("/Filter /FlateDecode", &compressed));
+        }
+
+        let mut out = b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n".to_vec();
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (idx, body) in objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.extend_from_slice(format!("{} 0 obj\n", idx + 1).as_bytes());
+            out.extend_from_slice(body);
+            out.extend_from_slice(b"\nendobj\n");
+        }
+        let xref = out.len();
+        let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(trailer, "{offset:010} 00000 n ");
+        }
+        let _ = write!(
+            trailer,
+            "trailer\n<< /Size {} /Root 1 0 R /Info 5 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+            objects.len() + 1
+        );
+        out.extend_from_slice(trailer.as_bytes());
+        Ok(out)
+    }
+}
+
+fn stream_object(dict: &str, data: &[u8]) -> Vec<u8> {
+    let mut body = format!("<< {dict} /Length {} >>\nstream\n", data.len()).into_bytes();
+    body.extend_from_slice(data);
+    body.extend_from_slice(b"\nendstream");
+    body
+}
+
+/// Escape `text` for a PDF literal string in WinAnsi; unsupported characters become `?`.
+fn encode_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '(' | ')' | '\\' => {
+                out.push('\\');
+                out.push(ch);
+            }
+            ' '..='~' => out.push(ch),
+            '\u{a0}'..='\u{ff}' => {
+                let _ = write!(out, "\\{:03o}", ch as u32);
+            }
+            _ => out.push('?'),
+        }
+    }
+    out
+}
+
+/// Width of `text` in points when set in Helvetica at `size`.
+pub(crate) fn text_width(text: &str, size: f32) -> f32 {
+    let units: u32 = text.chars().map(glyph_width).sum();
+    units as f32 * size / 1000.0
+}
+
+/// Shorten `text` with a trailing ellipsis so it fits in `max_width`.
+pub(crate) fn fit_text(text: &str, size: f32, max_width: f32) -> String {
+    if text_width(text, size) <= max_width {
+        return text.to_string();
+    }
+    let mut fitted = String::new();
+    for ch in text.chars() {
+        let candidate = format!("{fitted}{ch}...");
+        if text_width(&candidate, size) > max_width {
+            break;
+        }
+        fitted.push(ch);
+    }
+    format!("{}...", fitted.trim_end())
+}
+
+/// Helvetica advance widths (1/1000 em) for printable ASCII, from the standard AFM.
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, // space-/
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, // 0-?
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778, // @-O
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556, // P-_
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556, // `-o
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584, // p-~
+];
+
+fn glyph_width(ch: char) -> u32 {
+    match ch {
+        ' '..='~' => HELVETICA_WIDTHS[ch as usize - 32] as u32,
+        // Accented Latin-1 letters are close to their base letters' widths.
+        _ => 556,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_well_formed_documents() {
+        let mut doc = PdfDocument::new(Some("Sheet (draft)".into()));
+        let jpeg = {
+            let mut data = Vec::new();
+            image::codecs::jpeg::JpegEncoder::new(&mut data)
+                .encode(&[200u8; 4 * 2 * 3], 4, 2, image::ColorType::Rgb8)
+                .unwrap();
+            data
+        };
+        let image = doc.add_jpeg(4, 2, jpeg);
+        for n in 1..=2 {
+            let mut page = Page::default();
+            page.fill_rgb([239, 68, 68]);
+            page.rect(10.0, 10.0, 20.0, 20.0);
+            page.polygon(&[(0.0, 0.0), (5.0, 10.0), (10.0, 0.0)]);
+            page.image(image, 50.0, 50.0, 100.0, 50.0);
+            page.text(
+                Font::Bold,
+                12.0,
+                40.0,
+                800.0,
+                &format!("Page {n} – café (1)"),
+            );
+            doc.add_page(595.28, 841.89, page);
+        }
+        assert_eq!(doc.page_count(), 2);
+        let bytes = doc.finish().unwrap();
+        let text = String::from_utf8_lossy(&bytes);
+
+        assert!(text.starts_with("%PDF-1.4"));
+        assert!(text.ends_with("%%EOF\n"));
+        assert!(text.contains("/Count 2"));
+        assert!(text.contains("/Title (Sheet \\(draft\\))"));
+        // Every xref entry points at the start of its object.
+        let xref_at: usize = text
+            .rsplit("startxref\n")
+            .next()
+            .and_then(|tail| tail.lines().next())
+            .unwrap()
+            .parse()
+            .unwrap();
+        let entries: Vec<usize> = String::from_utf8_lossy(&bytes[xref_at..])
+            .lines()
+            .skip(3)
+            .take_while(|line| line.ends_with(" n "))
+            .map(|line| line[..10].parse().unwrap())
+            .collect();
+        assert_eq!(entries.len(), 10);
+        for (idx, offset) in entries.iter().enumerate() {
+            assert!(bytes[*offset..].starts_with(format!("{} 0 obj", idx + 1).as_bytes()));
+        }
+
+        assert_eq!(encode_text("a(b)\\ é ✓"), "a\\(b\\)\\\\ \\351 ?");
+        assert_eq!(text_width("Hi", 10.0), (722.0 + 222.0) / 100.0);
+        assert_eq!(
+            fit_text("a very long filename.jpg", 10.0, 40.0),
+            "a very..."
+        );
+    }
+}
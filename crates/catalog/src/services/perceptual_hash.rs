@@ -0,0 +1,190 @@
+//! Difference hashing (dHash) and Hamming-distance clustering for near-duplicate detection.
+
+use image::imageops::FilterType;
+use image::DynamicImage;
+
+/// Compute a 64-bit difference hash.
+///
+/// The image is reduced to a 9x8 grayscale grid and each bit records whether a pixel is
+/// brighter than its right-hand neighbour, which survives re-encoding, resizing and mild
+/// tonal changes while staying cheap to compare.
+pub fn dhash(img: &DynamicImage) -> u64 {
+    let small = img.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = small.get_pixel(x, y)[0];
+            let right = small.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | u64::from(left > right);
+        }
+    }
+    hash
+}
+
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Group `(id, hash)` pairs into clusters whose members are transitively within
+/// `max_distance` bits of each other. Singletons are dropped; clusters keep input order.
+pub fn cluster_by_distance(entries: &[(i64, u64)], max_distance: u32) -> Vec<Vec<i64>> {
+    let mut tree = BkTree::default();
+    for (idx, (_, hash)) in entries.iter().enumerate() {
+        tree.insert(*hash, idx);
+    }
+
+    let mut parent: Vec<usize> = (0..entries.len()).collect();
+    for (idx, (_, hash)) in entries.iter().enumerate() {
+        for neighbour in tree.within(*hash, max_distance) {
+            union(&mut parent, idx, neighbour);
+        }
+    }
+
+    let mut clusters: Vec<Vec<i64>> = Vec::new();
+    let mut cluster_of_root: std::collections::HashMap<usize, usize> =
+        std::collections::HashMap::new();
+    for (idx, (id, _)) in entries.iter().enumerate() {
+        let root = find(&mut parent, idx);
+        let slot = *cluster_of_root.entry(root).or_insert_with(|| {
+            clusters.push(Vec::new());
+            clusters.len() - 1
+        });
+        clusters[slot].push(*id);
+    }
+    clusters.retain(|cluster| cluster.len() > 1);
+    clusters
+}
+
+fn find(parent: &mut [usize], mut idx: usize) -> usize {
+    while parent[idx] != idx {
+        parent[idx] = parent[parent[idx]];
+        idx = parent[idx];
+    }
+    idx
+}
+
+fn union(parent: &mut [usize], a: usize, b: usize) {
+    let root_a = find(parent, a);
+    let root_b = find(parent, b);
+    if root_a != root_b {
+        parent[root_b.max(root_a)] = root_a.min(root_b);
+    }
+}
+
+/// Burkhard-Keller tree over Hamming distance so lookups avoid comparing every pair.
+#[derive(Default)]
+struct BkTree {
+    nodes: Vec<BkNode>,
+}
+
+struct BkNode {
+    hash: u64,
+    items: Vec<usize>,
+    children: Vec<(u32, usize)>,
+}
+
+impl BkTree {
+    fn insert(&mut self, hash: u64, item: usize) {
+        if self.nodes.is_empty() {
+            self.nodes.push(BkNode {
+                hash,
+                items: vec![item],
+                children: Vec::new(),
+            });
+            return;
+        }
+
+        let mut current = 0;
+        loop {
+            let distance = hamming_distance(self.nodes[current].hash, hash);
+            if distance == 0 {
+                self.nodes[current].items.push(item);
+                return;
+            }
+            match self.nodes[current]
+                .children
+                .iter()
+                .find(|(d, _)| *d == distance)
+            {
+                Some((_, child)) => current = *child,
+                None => {
+                    self.nodes.push(BkNode {
+                        hash,
+                        items: vec![item],
+                        children: Vec::new(),
+                    });
+                    let new_idx = self.nodes.len() - 1;
+                    self.nodes[current].children.push((distance, new_idx));
+                    return;
+                }
+            }
+        }
+    }
+
+    fn within(&self, hash: u64, max_distance: u32) -> Vec<usize> {
+        let mut out = Vec::new();
+        if self.nodes.is_empty() {
+            return out;
+        }
+
+        let mut pending = vec![0usize];
+        while let Some(current) = pending.pop() {
+            let node = &self.nodes[current];
+            let distance = hamming_distance(node.hash, hash);
+            if distance <= max_distance {
+                out.extend_from_slice(&node.items);
+            }
+            let low = distance.saturating_sub(max_distance);
+            let high = distance + max_distance;
+            pending.extend(
+                node.children
+                    .iter()
+                    .filter(|(d, _)| *d >= low && *d <= high)
+                    .map(|(_, child)| *child),
+            );
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn gradient(width: u32, height: u32, phase: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            let v = (((x * 7 + y * 3 + phase) * 255) / (width * 7 + height * 3)) as u8;
+            Rgb([v, v.wrapping_mul(3), 255 - v])
+        }))
+    }
+
+    #[test]
+    fn resized_copies_hash_alike() {
+        let original = gradient(320, 240, 0);
+        let resized = original.resize_exact(160, 120, FilterType::Lanczos3);
+        let different = DynamicImage::ImageRgb8(RgbImage::from_fn(320, 240, |x, y| {
+            if (x / 40 + y / 40) % 2 == 0 {
+                Rgb([255, 255, 255])
+            } else {
+                Rgb([0, 0, 0])
+            }
+        }));
+
+        assert!(hamming_distance(dhash(&original), dhash(&resized)) <= 4);
+        assert!(hamming_distance(dhash(&original), dhash(&different)) > 10);
+    }
+
+    #[test]
+    fn clusters_are_transitive() {
+        let entries = [
+            (1, 0b0000u64),
+            (2, 0b0001),
+            (3, 0b0011),
+            (4, u64::MAX),
+            (5, 0xF0F0),
+        ];
+        let clusters = cluster_by_distance(&entries, 1);
+        assert_eq!(clusters, vec![vec![1, 2, 3]]);
+    }
+}
@@ -0,0 +1,500 @@
+//! Exporting a subset of the catalog as a new standalone catalog.
+//!
+//! The new catalog gets the selected images with their folders, keywords, edits, edit
+//! history, thumbnails and previews, plus every collection that contains one of them.
+//! Originals can be copied into an `<name> Originals` folder next to the catalog,
+//! keeping their folder layout below the deepest folder they share; the new catalog then
+//! points at the copies through a root of their own, so it can be relocated in one step
+//! on the other machine. Merging it back with the copy folder remapped to the original
+//! location (see [`crate::services::merge`]) matches every image again.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use serde::Serialize;
+
+use crate::db::{CatalogDb, Collection, DbHandle, Image};
+use crate::services::catalog_service::CatalogService;
+use crate::services::import::{ImportCallbacks, ImportProgress, ImportStage};
+use crate::services::merge::{collection_paths, copy_image, copy_thumbnails};
+use crate::services::roots;
+use crate::CatalogPath;
+
+#[derive(Debug, Clone, Default)]
+pub struct PortableCatalogSettings {
+    /// Copy the original files (and sidecars) next to the new catalog.
+    pub copy_originals: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PortableCatalogReport {
+    pub catalog: PathBuf,
+    pub images: usize,
+    pub collections: usize,
+    pub thumbnails: usize,
+    pub originals_copied: usize,
+    pub originals_dir: Option<PathBuf>,
+    /// Images whose original could not be copied; they keep their original path.
+    pub failed: Vec<(PathBuf, String)>,
+    pub canceled: bool,
+}
+
+/// Write the images in `image_ids` to a new catalog at `destination`.
+///
+/// Nothing is left behind when the export fails or is canceled.
+pub fn export_portable_catalog(
+    service: &CatalogService,
+    image_ids: &[i64],
+    destination: &Path,
+    settings: &PortableCatalogSettings,
+    callbacks: &ImportCallbacks,
+) -> Result<PortableCatalogReport> {
+    let destination = CatalogPath::new(destination).into_path();
+    if destination.exists() {
+        bail!("catalog already exists at {}", destination.display());
+    }
+    let mut images = Vec::with_capacity(image_ids.len());
+    let mut seen = BTreeSet::new();
+    for id in image_ids {
+        if seen.insert(*id) {
+            images.push(Image::load(&service.db, *id)?);
+        }
+    }
+    if images.is_empty() {
+        bail!("no images to export");
+    }
+    if let Some(parent) = destination.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create directory {}", parent.display()))?;
+    }
+
+    let originals_dir = settings.copy_originals.then(|| {
+        let stem = destination
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        destination.with_file_name(format!("{stem} Originals"))
+    });
+    let mut export = PortableExport {
+        report: PortableCatalogReport {
+            catalog: destination.clone(),
+            originals_dir: originals_dir.clone(),
+            ..PortableCatalogReport::default()
+        },
+        copied_files: Vec::new(),
+    };
+    let result = export.write(
+        service,
+        &images,
+        &destination,
+        originals_dir.as_deref(),
+        callbacks,
+    );
+    match result {
+        Ok(()) if !export.report.canceled => Ok(export.report),
+        result => {
+            export.clean_up(&destination, originals_dir.as_deref());
+            result.map(|_| export.report)
+        }
+    }
+}
+
+struct PortableExport {
+    report: PortableCatalogReport,
+    copied_files: Vec<PathBuf>,
+}
+
+impl PortableExport {
+    fn write(
+        &mut self,
+        service: &CatalogService,
+        images: &[Image],
+        destination: &Path,
+        originals_dir: Option<&Path>,
+        callbacks: &ImportCallbacks,
+    ) -> Result<()> {
+        let target = CatalogService::new(
+            CatalogDb::open(&destination.to_string_lossy())
+                .with_context(|| format!("failed to create {}", destination.display()))?,
+        )
+        .with_home_dir(service.home_dir().map(Path::to_path_buf));
+        let base = originals_dir.map(|dir| (dir, common_base(images)));
+        let tx = target
+            .db
+            .conn()
+            .unchecked_transaction()
+            .context("failed to start export transaction")?;
+        if let Some(dir) = originals_dir {
+            // One root for the copies, so moving the export needs a single relocation.
+            let name = dir
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "Originals".to_string());
+            roots::add_root(&target.db, &name, dir)?;
+        }
+
+        let total = images.len();
+        let mut image_ids = HashMap::new();
+        for (idx, image) in images.iter().enumerate() {
+            if callbacks.cancel.is_canceled() {
+                self.report.canceled = true;
+                return Ok(());
+            }
+            if let Some(cb) = &callbacks.progress {
+                cb(ImportProgress {
+                    stage: if base.is_some() {
+                        ImportStage::Copying
+                    } else {
+                        ImportStage::Cataloging
+                    },
+                    completed: idx,
+                    total,
+                    message: Some(image.original_path.clone()),
+                });
+            }
+
+            let mut source = image.clone();
+            if let Some((dir, common)) = &base {
+                match self.copy_original(image, dir, common) {
+                    Ok((path, sidecar)) => {
+                        source.original_path = path.to_string_lossy().into_owned();
+                        source.sidecar_path = sidecar.map(|p| p.to_string_lossy().into_owned());
+                        self.report.originals_copied += 1;
+                    }
+                    Err(err) => {
+                        let path = PathBuf::from(&image.original_path);
+                        let message = format!("{err:#}");
+                        if let Some(cb) = &callbacks.on_error {
+                            cb(path.clone(), message.clone());
+                        }
+                        self.report.failed.push((path, message));
+                    }
+                }
+            }
+            let copy = copy_image(
+                &service.db,
+                &source,
+                &target,
+                Path::new(&source.original_path),
+            )?;
+            if copy_thumbnails(&service.db, image.id, &target, copy.id)? {
+                self.report.thumbnails += 1;
+            }
+            image_ids.insert(image.id, copy.id);
+            self.report.images += 1;
+        }
+
+        self.report.collections = copy_collections(&service.db, &target.db, &image_ids)?;
+        tx.commit().context("failed to commit exported catalog")?;
+
+        if let Some(cb) = &callbacks.progress {
+            cb(ImportProgress {
+                stage: ImportStage::Cataloging,
+                completed: total,
+                total,
+                message: None,
+            });
+        }
+        Ok(())
+    }
+
+    /// Copy the original and its sidecar, keeping their modification times so the
+    /// copies are not reported as changed.
+    fn copy_original(
+        &mut self,
+        image: &Image,
+        dir: &Path,
+        common: &Path,
+    ) -> Result<(PathBuf, Option<PathBuf>)> {
+        let path = self.copy_file(Path::new(&image.original_path), dir, common)?;
+        let sidecar = image
+            .sidecar_path
+            .as_deref()
+            .map(Path::new)
+            .filter(|p| p.is_file())
+            .map(|p| self.copy_file(p, dir, common))
+            .transpose()?;
+        Ok((path, sidecar))
+    }
+
+    fn copy_file(&mut self, source: &Path, dir: &Path, common: &Path) -> Result<PathBuf> {
+        let relative = source.strip_prefix(common).unwrap_or(source);
+        let relative: PathBuf = relative
+            .components()
+            .filter(|c| matches!(c, std::path::Component::Normal(_)))
+            .collect();
+        let dest = dir.join(relative);
+        if dest.exists() {
+            bail!("{} already exists", dest.display());
+        }
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create directory {}", parent.display()))?;
+        }
+        fs::copy(source, &dest).with_context(|| format!("failed to copy {}", source.display()))?;
+        self.copied_files.push(dest.clone());
+        if let Ok(modified) = fs::metadata(source).and_then(|m| m.modified()) {
+            fs::File::options()
+                .write(true)
+                .open(&dest)
+                .and_then(|file| file.set_modified(modified))
+                .ok();
+        }
+        Ok(dest)
+    }
+
+    fn clean_up(&self, destination: &Path, originals_dir: Option<&Path>) {
+        for suffix in ["", "-wal", "-shm"] {
+            let mut file = destination.as_os_str().to_owned();
+            file.push(suffix);
+            fs::remove_file(PathBuf::from(file)).ok();
+        }
+        for file in &self.copied_files {
+            fs::remove_file(file).ok();
+        }
+        if let Some(dir) = originals_dir {
+            // Only removes folders this export created and left empty.
+            remove_empty_dirs(dir);
+        }
+    }
+}
+
+fn remove_empty_dirs(dir: &Path) {
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                remove_empty_dirs(&entry.path());
+            }
+        }
+    }
+    fs::remove_dir(dir).ok();
+}
+
+/// The parent of the deepest folder shared by all images, so the copies keep at least
+/// the name of the folder they came from.
+fn common_base(images: &[Image]) -> PathBuf {
+    let mut folders = images
+        .iter()
+        .filter_map(|image| Path::new(&image.original_path).parent());
+    let Some(first) = folders.next() else {
+        return PathBuf::new();
+    };
+    let mut common = first.to_path_buf();
+    for folder in folders {
+        while !folder.starts_with(&common) {
+            if !common.pop() {
+                break;
+            }
+        }
+    }
+    common.parent().map(Path::to_path_buf).unwrap_or(common)
+}
+
+/// Recreate the collections holding any of the exported images, with their parents,
+/// keeping the order of their members. Returns how many were created.
+fn copy_collections<S: DbHandle, T: DbHandle>(
+    source: &S,
+    target: &T,
+    image_ids: &HashMap<i64, i64>,
+) -> Result<usize> {
+    let collections = Collection::load_all(source).context("failed to list collections")?;
+    let by_id: HashMap<i64, &Collection> = collections.iter().map(|c| (c.id, c)).collect();
+    let mut members = HashMap::new();
+    let mut needed = BTreeSet::new();
+    for collection in &collections {
+        let images: Vec<i64> = Collection::list_images(source, collection.id)?
+            .into_iter()
+            .filter_map(|image| image_ids.get(&image.id).copied())
+            .collect();
+        if images.is_empty() {
+            continue;
+        }
+        members.insert(collection.id, images);
+        let mut current = Some(collection);
+        // The step limit guards against parent cycles.
+        for _ in 0..64 {
+            let Some(c) = current else {
+                break;
+            };
+            if !needed.insert(c.id) {
+                break;
+            }
+            current = c.parent_id.and_then(|id| by_id.get(&id).copied());
+        }
+    }
+
+    let paths = collection_paths(&collections);
+    let mut ordered: Vec<&Collection> = collections
+        .iter()
+        .filter(|c| needed.contains(&c.id))
+        .collect();
+    ordered.sort_by_key(|c| paths.get(&c.id).map(Vec::len).unwrap_or(0));
+    let mut created = HashMap::new();
+    for collection in ordered {
+        let new = Collection {
+            id: 0,
+            name: collection.name.clone(),
+            parent_id: collection
+                .parent_id
+                .and_then(|parent| created.get(&parent).copied()),
+            created_at: collection.created_at,
+            updated_at: Utc::now(),
+        };
+        let id = new.insert(target)?;
+        for image_id in members.get(&collection.id).into_iter().flatten() {
+            Collection::add_image(target, id, *image_id)?;
+        }
+        created.insert(collection.id, id);
+    }
+    Ok(created.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::import::{import_files, ImportMethod};
+    use crate::services::merge::{merge_catalog, MergeSettings, PathRemap};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let dir = std::env::temp_dir().join(format!("catalog_portable_{name}_{suffix}"));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn exports_subset_and_merges_back() {
+        let dir = temp_dir("job");
+        let photos = dir.join("photos");
+        fs::create_dir_all(photos.join("day1")).unwrap();
+        fs::create_dir_all(photos.join("day2")).unwrap();
+        let files = [
+            photos.join("day1/a.png"),
+            photos.join("day2/b.png"),
+            photos.join("day2/c.png"),
+        ];
+        for (idx, path) in files.iter().enumerate() {
+            image::RgbImage::from_pixel(8, 8, image::Rgb([idx as u8 * 80, 20, 20]))
+                .save(path)
+                .unwrap();
+        }
+        let service = CatalogService::new(
+            CatalogDb::open(&dir.join("main.zenithphotocatalog").to_string_lossy()).unwrap(),
+        );
+        import_files(
+            &service,
+            &files,
+            &["Smith".to_string()],
+            ImportMethod::Add,
+            None,
+            ImportCallbacks::default(),
+        )
+        .unwrap();
+        let ids: Vec<i64> = files
+            .iter()
+            .map(|p| service.find_image_by_original_path(p).unwrap().unwrap().id)
+            .collect();
+        service.update_rating(ids[0], 4).unwrap();
+        let mut parent = Collection {
+            id: 0,
+            name: "Jobs".to_string(),
+            parent_id: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        parent.id = parent.insert(&service.db).unwrap();
+        let job = Collection {
+            id: 0,
+            name: "Smith".to_string(),
+            parent_id: Some(parent.id),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        let job_id = job.insert(&service.db).unwrap();
+        for id in &ids {
+            Collection::add_image(&service.db, job_id, *id).unwrap();
+        }
+
+        let destination = dir.join("out/Smith job");
+        let report = export_portable_catalog(
+            &service,
+            &ids[..2],
+            &destination,
+            &PortableCatalogSettings {
+                copy_originals: true,
+            },
+            &ImportCallbacks::default(),
+        )
+        .unwrap();
+        assert_eq!(report.catalog, dir.join("out/Smith job.zenithphotocatalog"));
+        assert_eq!((report.images, report.collections), (2, 2));
+        assert_eq!((report.originals_copied, report.thumbnails), (2, 2));
+        let originals = dir.join("out/Smith job Originals");
+        assert!(originals.join("photos/day1/a.png").is_file());
+        assert!(originals.join("photos/day2/b.png").is_file());
+
+        let portable =
+            CatalogService::new(CatalogDb::open(&report.catalog.to_string_lossy()).unwrap());
+        assert_eq!(portable.count_images().unwrap(), 2);
+        let a = portable
+            .find_image_by_original_path(&originals.join("photos/day1/a.png"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(a.rating, Some(4));
+        assert_eq!(
+            portable.load_metadata(a.id).unwrap().keywords,
+            vec!["Smith"]
+        );
+        let collections = portable.list_collections().unwrap();
+        let smith = collections.iter().find(|c| c.name == "Smith").unwrap();
+        assert!(smith.parent_id.is_some());
+        let roots = portable.list_roots().unwrap();
+        assert_eq!(roots.len(), 1);
+        assert_eq!(roots[0].root.name, "Smith job Originals");
+        assert_eq!(
+            portable.list_images_in_collection(smith.id).unwrap().len(),
+            2
+        );
+
+        // On the other machine the editor rates b; merging back finds both by path.
+        let b = portable
+            .find_image_by_original_path(&originals.join("photos/day2/b.png"))
+            .unwrap()
+            .unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        portable.update_rating(b.id, 5).unwrap();
+        drop(portable);
+        let merged = merge_catalog(
+            &service,
+            &report.catalog,
+            &MergeSettings {
+                remaps: vec![PathRemap {
+                    from: originals.join("photos"),
+                    to: photos.clone(),
+                }],
+                ..MergeSettings::default()
+            },
+            &ImportCallbacks::default(),
+        )
+        .unwrap();
+        assert_eq!((merged.added, merged.matched_by_path), (0, 2));
+        assert_eq!(Image::load(&service.db, ids[1]).unwrap().rating, Some(5));
+
+        assert!(export_portable_catalog(
+            &service,
+            &ids,
+            &report.catalog,
+            &PortableCatalogSettings::default(),
+            &ImportCallbacks::default(),
+        )
+        .is_err());
+        fs::remove_dir_all(dir).ok();
+    }
+}
@@ -0,0 +1,372 @@
+//! Managing the named roots folders are stored under.
+//!
+//! Relocating a root rewrites the resolved paths of every folder and image below it in
+//! one transaction, e.g. after copying a photo drive to a NAS mount.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use rusqlite::params;
+use serde::Serialize;
+
+use crate::db::roots::{relative_path, unique_name};
+use crate::db::{query_all, DbHandle, Folder, Root};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RootSummary {
+    pub root: Root,
+    pub folders: usize,
+    pub images: usize,
+    /// Whether the root's path currently exists on this machine.
+    pub online: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RootRelocation {
+    pub folders: usize,
+    pub images: usize,
+    /// Images whose file is not at the new location.
+    pub missing: usize,
+}
+
+pub fn list_roots<H: DbHandle>(db: &H) -> Result<Vec<RootSummary>> {
+    let counts = query_all(
+        db,
+        "SELECT r.root_id, COUNT(DISTINCT r.folder_id), COUNT(i.id)
+         FROM folder_roots r
+         LEFT JOIN images i ON i.folder_id = r.folder_id
+         GROUP BY r.root_id",
+        [],
+        |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                (
+                    row.get::<_, i64>(1)? as usize,
+                    row.get::<_, i64>(2)? as usize,
+                ),
+            ))
+        },
+    )?
+    .into_iter()
+    .collect::<std::collections::HashMap<_, _>>();
+    Ok(Root::load_all(db)
+        .context("failed to list roots")?
+        .into_iter()
+        .map(|root| {
+            let (folders, images) = counts.get(&root.id).copied().unwrap_or_default();
+            RootSummary {
+                online: Path::new(&root.path).is_dir(),
+                root,
+                folders,
+                images,
+            }
+        })
+        .collect())
+}
+
+/// Place folders without a root, e.g. those of catalogs created before roots existed,
+/// under the root [`Root::resolve`] picks for them. Returns how many were placed.
+pub fn assign_folder_roots<H: DbHandle>(db: &H, home: Option<&Path>) -> Result<usize> {
+    let unassigned = query_all(
+        db,
+        "SELECT id, path FROM folders
+         WHERE id NOT IN (SELECT folder_id FROM folder_roots)",
+        [],
+        |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+    )?;
+    for (id, path) in &unassigned {
+        let (root, relative) = Root::resolve(db, Path::new(path), home)?;
+        Folder::set_root(db, *id, root.id, &relative)
+            .with_context(|| format!("failed to assign a root to folder {path}"))?;
+    }
+    Ok(unassigned.len())
+}
+
+/// Look a root up by name, or by id when `name_or_id` is a number.
+pub fn find_root<H: DbHandle>(db: &H, name_or_id: &str) -> Result<Root> {
+    if let Some(root) = Root::find_by_name(db, name_or_id)? {
+        return Ok(root);
+    }
+    match name_or_id.parse::<i64>() {
+        Ok(id) => Root::load(db, id),
+        Err(_) => bail!("no root named {name_or_id:?}"),
+    }
+}
+
+/// Add a root at `path` and move the folders below it from less specific roots, which
+/// are removed when that leaves them empty.
+pub fn add_root<H: DbHandle>(db: &H, name: &str, path: &Path) -> Result<Root> {
+    let name = name.trim();
+    if name.is_empty() {
+        bail!("root name must not be empty");
+    }
+    if Root::find_by_name(db, name)?.is_some() {
+        bail!("a root named {name:?} already exists");
+    }
+    let path_str = path.to_string_lossy().into_owned();
+    if let Some(existing) = Root::find_by_path(db, &path_str)? {
+        bail!("{} is already the root {:?}", path.display(), existing.name);
+    }
+
+    let now = Utc::now();
+    let mut root = Root {
+        id: 0,
+        name: name.to_string(),
+        path: path_str,
+        created_at: now,
+        updated_at: now,
+    };
+    root.id = root.insert(db)?;
+
+    let roots = Root::load_all(db)?;
+    let depth = |root: &Root| Path::new(&root.path).components().count();
+    let new_depth = depth(&root);
+    let mut previous = Vec::new();
+    for folder in Folder::load_all(db)? {
+        if !Path::new(&folder.path).starts_with(path) {
+            continue;
+        }
+        let current = roots.iter().find(|r| Some(r.id) == folder.root_id);
+        if current.is_some_and(|current| depth(current) >= new_depth) {
+            continue;
+        }
+        if let Some(current) = current {
+            previous.push(current.id);
+        }
+        let relative = relative_path(path, Path::new(&folder.path));
+        Folder::set_root(db, folder.id, root.id, &relative)?;
+    }
+    for id in previous {
+        db.execute(
+            "DELETE FROM roots
+             WHERE id = ?1 AND NOT EXISTS (SELECT 1 FROM folder_roots WHERE root_id = ?1)",
+            params![id],
+        )
+        .with_context(|| format!("failed to remove empty root id={id}"))?;
+    }
+    Ok(root)
+}
+
+pub fn rename_root<H: DbHandle>(db: &H, id: i64, name: &str) -> Result<Root> {
+    let name = name.trim();
+    if name.is_empty() {
+        bail!("root name must not be empty");
+    }
+    let mut root = Root::load(db, id)?;
+    if let Some(existing) = Root::find_by_name(db, name)? {
+        if existing.id != id {
+            bail!("a root named {name:?} already exists");
+        }
+    }
+    root.name = name.to_string();
+    root.updated_at = Utc::now();
+    root.update(db)?;
+    Ok(root)
+}
+
+/// Point the root at `new_path` and rewrite the paths of its folders and images.
+pub fn relocate_root<H: DbHandle>(db: &H, id: i64, new_path: &Path) -> Result<RootRelocation> {
+    let mut root = Root::load(db, id)?;
+    let old_path = PathBuf::from(&root.path);
+    let new_str = new_path.to_string_lossy().into_owned();
+    if let Some(existing) = Root::find_by_path(db, &new_str)? {
+        if existing.id != id {
+            bail!(
+                "{} is already the root {:?}",
+                new_path.display(),
+                existing.name
+            );
+        }
+    }
+    root.path = new_str;
+    root.updated_at = Utc::now();
+    root.update(db)?;
+
+    let mut relocation = RootRelocation::default();
+    let folders = query_all(
+        db,
+        "SELECT folder_id, relative_path FROM folder_roots WHERE root_id = ?1",
+        params![id],
+        |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+    )?;
+    for (folder_id, relative) in folders {
+        let path = root.join(&relative);
+        db.execute(
+            "UPDATE folders SET path = ?1 WHERE id = ?2",
+            params![path.to_string_lossy(), folder_id],
+        )
+        .with_context(|| format!("{} is already a cataloged folder", path.display()))?;
+        relocation.folders += 1;
+    }
+
+    let images = query_all(
+        db,
+        "SELECT i.id, i.original_path, i.sidecar_path
+         FROM images i
+         INNER JOIN folder_roots r ON r.folder_id = i.folder_id
+         WHERE r.root_id = ?1",
+        params![id],
+        |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        },
+    )?;
+    let moved = |path: &str| -> String {
+        match Path::new(path).strip_prefix(&old_path) {
+            Ok(rest) => new_path.join(rest).to_string_lossy().into_owned(),
+            Err(_) => path.to_string(),
+        }
+    };
+    for (image_id, original_path, sidecar_path) in images {
+        let original_path = moved(&original_path);
+        let sidecar_path = sidecar_path.as_deref().map(moved);
+        db.execute(
+            "UPDATE images SET original_path = ?1, sidecar_path = ?2 WHERE id = ?3",
+            params![original_path, sidecar_path, image_id],
+        )
+        .with_context(|| format!("{original_path} is already cataloged"))?;
+        relocation.images += 1;
+        if !Path::new(&original_path).exists() {
+            relocation.missing += 1;
+        }
+    }
+    Ok(relocation)
+}
+
+/// Name for a new root at `path`, unique among the existing roots.
+pub fn suggest_root_name<H: DbHandle>(db: &H, path: &Path) -> Result<String> {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.to_string_lossy().into_owned());
+    unique_name(db, &name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{test_folder, CatalogDb};
+    use crate::services::import::{import_files, ImportCallbacks, ImportMethod};
+    use crate::services::CatalogService;
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn relocating_a_root_moves_every_image_below_it() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let dir = std::env::temp_dir().join(format!("catalog_roots_{suffix}"));
+        let drive = dir.join("drive");
+        let nas = dir.join("nas");
+        for sub in ["2023", "2024/June"] {
+            fs::create_dir_all(drive.join(sub)).unwrap();
+        }
+        let files = [drive.join("2023/a.png"), drive.join("2024/June/b.png")];
+        for (idx, path) in files.iter().enumerate() {
+            image::RgbImage::from_pixel(4, 4, image::Rgb([idx as u8 * 90, 2, 3]))
+                .save(path)
+                .unwrap();
+        }
+
+        let service = CatalogService::new(CatalogDb::in_memory().unwrap());
+        let photos = service.add_root("Photos", &drive).unwrap();
+        import_files(
+            &service,
+            &files,
+            &[],
+            ImportMethod::Add,
+            None,
+            ImportCallbacks::default(),
+        )
+        .unwrap();
+        let folders = service.list_folders().unwrap();
+        assert!(folders.iter().all(|f| f.root_id == Some(photos.id)));
+        let mut relative: Vec<_> = folders
+            .iter()
+            .filter_map(|f| f.relative_path.clone())
+            .collect();
+        relative.sort();
+        assert_eq!(relative, vec!["2023", "2024/June"]);
+
+        // The drive was copied to the NAS.
+        fs::rename(&drive, &nas).unwrap();
+        let relocation = service.relocate_root(photos.id, &nas).unwrap();
+        assert_eq!((relocation.folders, relocation.images), (2, 2));
+        assert_eq!(relocation.missing, 0);
+        assert!(service
+            .find_image_by_original_path(&nas.join("2024/June/b.png"))
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            service
+                .list_images_in_folder(&nas.join("2023"))
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let summary = service.list_roots().unwrap();
+        let photos = summary.iter().find(|s| s.root.name == "Photos").unwrap();
+        assert_eq!((photos.folders, photos.images), (2, 2));
+        assert!(photos.online);
+        assert!(service.rename_root(photos.root.id, "NAS").is_ok());
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn new_folders_below_the_home_folder_are_rooted_there() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let home = std::env::temp_dir().join(format!("catalog_home_{suffix}"));
+        fs::create_dir_all(home.join("Pictures")).unwrap();
+        let file = home.join("Pictures/a.png");
+        image::RgbImage::from_pixel(4, 4, image::Rgb([1, 2, 3]))
+            .save(&file)
+            .unwrap();
+
+        let service =
+            CatalogService::new(CatalogDb::in_memory().unwrap()).with_home_dir(Some(home.clone()));
+        service.import_image(&file).unwrap();
+        let folder = &service.list_folders().unwrap()[0];
+        let root = Root::load(&service.db, folder.root_id.unwrap()).unwrap();
+        assert_eq!(
+            (root.name.as_str(), Path::new(&root.path)),
+            ("Home", home.as_path())
+        );
+        assert_eq!(folder.relative_path.as_deref(), Some("Pictures"));
+
+        // Folders left unrooted, e.g. by an upgrade, are placed the same way.
+        let scans = test_folder(&home.join("Scans").to_string_lossy())
+            .insert(&service.db)
+            .unwrap();
+        assert_eq!(service.assign_folder_roots().unwrap(), 1);
+        let scans = Folder::load(&service.db, scans).unwrap();
+        assert_eq!(scans.root_id, Some(root.id));
+        assert_eq!(scans.relative_path.as_deref(), Some("Scans"));
+        fs::remove_dir_all(home).ok();
+    }
+
+    #[test]
+    fn adding_a_root_adopts_folders_from_a_broader_one() {
+        let db = CatalogDb::in_memory().unwrap();
+        let folder_id = test_folder("/mnt/nas/photos/2024").insert(&db).unwrap();
+        assert_eq!(assign_folder_roots(&db, None).unwrap(), 1);
+        assert_eq!(Root::load_all(&db).unwrap()[0].name, "nas");
+
+        let root = add_root(&db, "Photos", Path::new("/mnt/nas/photos")).unwrap();
+        let folder = Folder::load(&db, folder_id).unwrap();
+        assert_eq!(folder.root_id, Some(root.id));
+        assert_eq!(folder.relative_path.as_deref(), Some("2024"));
+        // The mount root was left empty and removed.
+        assert_eq!(Root::load_all(&db).unwrap().len(), 1);
+        assert!(add_root(&db, "photos", Path::new("/elsewhere")).is_err());
+    }
+}
@@ -0,0 +1,273 @@
+//! Ordering image lists for display.
+//!
+//! Lists come back from the catalog in capture order; [`sort_images`] reorders them by one
+//! of the [`SortKey`]s. Images without a value for the key always go last, whichever the
+//! direction, and ties keep capture order so the grid doesn't shuffle between loads.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use crate::db::{parse_datetime, query_all, Image};
+use crate::services::CatalogService;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    #[default]
+    CaptureTime,
+    ImportTime,
+    Filename,
+    Rating,
+    ColorLabel,
+    FileSize,
+    Camera,
+    /// When the image's develop settings last changed.
+    EditTime,
+    /// A collection's own order; other lists keep the order they were loaded in.
+    Manual,
+}
+
+impl SortKey {
+    pub const ALL: [SortKey; 9] = [
+        SortKey::CaptureTime,
+        SortKey::ImportTime,
+        SortKey::Filename,
+        SortKey::Rating,
+        SortKey::ColorLabel,
+        SortKey::FileSize,
+        SortKey::Camera,
+        SortKey::EditTime,
+        SortKey::Manual,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortKey::CaptureTime => "Capture Time",
+            SortKey::ImportTime => "Import Time",
+            SortKey::Filename => "Filename",
+            SortKey::Rating => "Rating",
+            SortKey::ColorLabel => "Color Label",
+            SortKey::FileSize => "File Size",
+            SortKey::Camera => "Camera",
+            SortKey::EditTime => "Edit Time",
+            SortKey::Manual => "Manual Order",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageSort {
+    pub key: SortKey,
+    #[serde(default)]
+    pub descending: bool,
+}
+
+/// Color labels in the order the label picker lists them, then the ones only imported
+/// catalogs use.
+pub(crate) const LABEL_ORDER: [&str; 7] =
+    ["red", "yellow", "green", "blue", "purple", "orange", "teal"];
+
+/// A sortable value; `None` sorts after everything else.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum SortValue {
+    Time(Option<DateTime<Utc>>),
+    Number(Option<i64>),
+    Text(Option<String>),
+}
+
+impl SortValue {
+    fn is_missing(&self) -> bool {
+        matches!(
+            self,
+            SortValue::Time(None) | SortValue::Number(None) | SortValue::Text(None)
+        )
+    }
+}
+
+/// Reorder `images` by `sort`. `collection_id` supplies the positions for
+/// [`SortKey::Manual`]; without it a manual sort leaves the list as it is.
+pub fn sort_images(
+    service: &CatalogService,
+    images: &mut [Image],
+    sort: &ImageSort,
+    collection_id: Option<i64>,
+) -> Result<()> {
+    let edit_times = match sort.key {
+        SortKey::EditTime => edit_times(service)?,
+        _ => HashMap::new(),
+    };
+    let positions = match (sort.key, collection_id) {
+        (SortKey::Manual, Some(collection_id)) => collection_positions(service, collection_id)?,
+        (SortKey::Manual, None) => return Ok(()),
+        _ => HashMap::new(),
+    };
+
+    let value = |image: &Image| match sort.key {
+        SortKey::CaptureTime => SortValue::Time(image.captured_at),
+        SortKey::ImportTime => SortValue::Time(Some(image.imported_at)),
+        SortKey::Filename => SortValue::Text(Some(image.filename.to_ascii_lowercase())),
+        SortKey::Rating => SortValue::Number(Some(image.rating.unwrap_or(0))),
+        SortKey::ColorLabel => SortValue::Number(image.color_label.as_deref().and_then(|label| {
+            LABEL_ORDER
+                .iter()
+                .position(|known| known.eq_ignore_ascii_case(label))
+                .map(|index| index as i64)
+        })),
+        SortKey::FileSize => SortValue::Number(image.filesize),
+        SortKey::Camera => SortValue::Text(camera_name(image)),
+        SortKey::EditTime => SortValue::Time(edit_times.get(&image.id).copied()),
+        SortKey::Manual => SortValue::Number(positions.get(&image.id).copied()),
+    };
+    let mut keyed: Vec<(SortValue, Image)> = images
+        .iter()
+        .map(|image| (value(image), image.clone()))
+        .collect();
+    keyed.sort_by(|(a_value, a), (b_value, b)| {
+        let by_value = match (a_value.is_missing(), b_value.is_missing()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) if sort.descending => b_value.cmp(a_value),
+            (false, false) => a_value.cmp(b_value),
+        };
+        by_value
+            .then_with(|| {
+                (a.captured_at.is_none(), a.captured_at)
+                    .cmp(&(b.captured_at.is_none(), b.captured_at))
+            })
+            .then(a.id.cmp(&b.id))
+    });
+    for (slot, (_, image)) in images.iter_mut().zip(keyed) {
+        *slot = image;
+    }
+    Ok(())
+}
+
+fn camera_name(image: &Image) -> Option<String> {
+    let name = [image.camera_make.as_deref(), image.camera_model.as_deref()]
+        .into_iter()
+        .flatten()
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    (!name.is_empty()).then(|| name.to_ascii_lowercase())
+}
+
+fn edit_times(service: &CatalogService) -> Result<HashMap<i64, DateTime<Utc>>> {
+    let rows: Vec<(i64, DateTime<Utc>)> = query_all(
+        &service.db,
+        "SELECT image_id, MAX(updated_at) FROM edits
+         WHERE updated_at IS NOT NULL
+         GROUP BY image_id",
+        [],
+        |row| {
+            Ok((
+                row.get(0)?,
+                parse_datetime(row.get::<_, String>(1)?, "updated_at")?,
+            ))
+        },
+    )
+    .context("failed to load edit times")?;
+    Ok(rows.into_iter().collect())
+}
+
+fn collection_positions(service: &CatalogService, collection_id: i64) -> Result<HashMap<i64, i64>> {
+    let rows: Vec<(i64, i64)> = query_all(
+        &service.db,
+        "SELECT image_id, position FROM collection_images WHERE collection_id = ?1",
+        params![collection_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .with_context(|| format!("failed to load positions in collection {collection_id}"))?;
+    Ok(rows.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{test_folder, test_image, to_rfc3339, CatalogDb, DbHandle};
+    use chrono::TimeZone;
+
+    fn ids(images: &[Image]) -> Vec<i64> {
+        images.iter().map(|image| image.id).collect()
+    }
+
+    #[test]
+    fn sorts_by_each_key_with_missing_values_last() {
+        let service = CatalogService::new(CatalogDb::in_memory().unwrap());
+        let folder_id = test_folder("/photos/sorted").insert(&service.db).unwrap();
+        let images: Vec<Image> = [
+            Image {
+                filesize: Some(300),
+                camera_make: Some("Nikon".into()),
+                rating: Some(3),
+                color_label: Some("green".into()),
+                ..test_image(folder_id, "/photos/sorted/b.jpg")
+            },
+            Image {
+                filesize: Some(100),
+                color_label: Some("red".into()),
+                ..test_image(folder_id, "/photos/sorted/A.jpg")
+            },
+            Image {
+                camera_make: Some("Canon".into()),
+                rating: Some(5),
+                ..test_image(folder_id, "/photos/sorted/c.jpg")
+            },
+        ]
+        .into_iter()
+        .map(|mut image| {
+            image.id = image.insert(&service.db).unwrap();
+            image
+        })
+        .collect();
+        let [b, a, c] = [images[0].id, images[1].id, images[2].id];
+
+        let sorted = |key: SortKey, descending: bool| {
+            let mut list = images.clone();
+            sort_images(&service, &mut list, &ImageSort { key, descending }, None).unwrap();
+            ids(&list)
+        };
+        assert_eq!(sorted(SortKey::Filename, false), [a, b, c]);
+        assert_eq!(sorted(SortKey::Filename, true), [c, b, a]);
+        assert_eq!(sorted(SortKey::Rating, true), [c, b, a]);
+        assert_eq!(sorted(SortKey::ColorLabel, false), [a, b, c]);
+        assert_eq!(sorted(SortKey::ColorLabel, true), [b, a, c]);
+        assert_eq!(sorted(SortKey::FileSize, true), [b, a, c]);
+        assert_eq!(sorted(SortKey::Camera, false), [c, b, a]);
+        assert_eq!(sorted(SortKey::Manual, true), [b, a, c]);
+
+        for (image_id, month) in [(c, 1), (a, 6)] {
+            service
+                .db
+                .execute(
+                    "INSERT INTO edits (image_id, updated_at) VALUES (?1, ?2)",
+                    params![
+                        image_id,
+                        to_rfc3339(Utc.with_ymd_and_hms(2024, month, 1, 0, 0, 0).unwrap())
+                    ],
+                )
+                .unwrap();
+        }
+        assert_eq!(sorted(SortKey::EditTime, true), [a, c, b]);
+        assert_eq!(sorted(SortKey::EditTime, false), [c, a, b]);
+
+        let collection = service.create_collection("Picks").unwrap();
+        for id in [c, a, b] {
+            service.add_image_to_collection(collection.id, id).unwrap();
+        }
+        let mut list = images.clone();
+        let manual = ImageSort {
+            key: SortKey::Manual,
+            descending: false,
+        };
+        sort_images(&service, &mut list, &manual, Some(collection.id)).unwrap();
+        assert_eq!(ids(&list), [c, a, b]);
+    }
+}
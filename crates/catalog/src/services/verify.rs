@@ -0,0 +1,600 @@
+//! Catalog verification and repair.
+//!
+//! [`verify_catalog`] inspects a catalog without modifying it and returns a serializable
+//! [`VerifyReport`]. Every [`Finding`] carries the [`Repair`] that fixes it, which
+//! [`repair_findings`] applies. Physical corruption reported by `PRAGMA integrity_check`
+//! cannot be repaired in place and points at restoring a backup instead.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::db::{query_all, rebuild_fts, rebuild_spatial_index, CatalogDb, DbHandle, Folder};
+
+/// Tables whose rows belong to a single image through an `image_id` column.
+const IMAGE_CHILD_TABLES: &[&str] = &[
+    "thumbnails",
+    "previews",
+    "edits",
+    "edit_history",
+    "image_keywords",
+    "collection_images",
+    "perceptual_hashes",
+    "stack_images",
+];
+
+/// Full-text indexes and the base tables they mirror, as `(fts table, base table)`.
+const FTS_TABLES: &[(&str, &str)] = &[
+    ("fts_images", "images"),
+    ("fts_keywords", "keywords"),
+    ("fts_folders", "folders"),
+    ("fts_locations", "image_locations"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FindingKind {
+    Integrity,
+    Constraint,
+    ForeignKey,
+    OrphanRow,
+    MissingFolder,
+    FtsOutOfSync,
+    SpatialIndexOutOfSync,
+    InvalidJson,
+}
+
+/// How a finding is fixed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Repair {
+    /// The database file itself is damaged; restore a backup.
+    RestoreBackup,
+    /// Values break a schema constraint and need fixing by hand.
+    Manual,
+    DeleteRows {
+        table: String,
+        row_ids: Vec<i64>,
+    },
+    /// Point images back at a folder derived from their `original_path`.
+    RelinkFolders {
+        image_ids: Vec<i64>,
+    },
+    RebuildFts,
+    RebuildSpatialIndex,
+    /// Null out a nullable JSON column.
+    ClearJson {
+        table: String,
+        column: String,
+        row_ids: Vec<i64>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Finding {
+    pub kind: FindingKind,
+    pub table: String,
+    pub count: usize,
+    pub message: String,
+    pub repair: Repair,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifyReport {
+    pub checked_at: DateTime<Utc>,
+    pub findings: Vec<Finding>,
+}
+
+impl VerifyReport {
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    /// Whether every finding can be fixed by [`repair_findings`].
+    pub fn is_repairable(&self) -> bool {
+        self.findings
+            .iter()
+            .all(|finding| !matches!(finding.repair, Repair::RestoreBackup | Repair::Manual))
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("failed to serialize verify report")
+    }
+}
+
+/// Outcome of applying repairs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RepairSummary {
+    pub rows_deleted: usize,
+    pub images_relinked: usize,
+    pub json_cleared: usize,
+    pub fts_rebuilt: bool,
+    pub spatial_index_rebuilt: bool,
+    /// Findings that need manual work, such as restoring a backup.
+    pub skipped: usize,
+}
+
+/// Inspect the catalog and report every problem found.
+pub fn verify_catalog(conn: &Connection) -> Result<VerifyReport> {
+    let mut findings = Vec::new();
+    let constraint_failures = check_integrity(conn, &mut findings)?;
+    check_foreign_keys(conn, &mut findings)?;
+    check_orphans(conn, &mut findings)?;
+    check_missing_folders(conn, &mut findings)?;
+    check_fts(conn, &mut findings)?;
+    check_spatial_index(conn, &mut findings)?;
+    check_json_columns(conn, &mut findings)?;
+
+    // integrity_check also re-validates CHECK/NOT NULL constraints; JSON failures already have
+    // their own findings, anything else is reported for manual fixing.
+    for (table, message) in constraint_failures {
+        let explained = findings
+            .iter()
+            .any(|f| f.kind == FindingKind::InvalidJson && f.table == table);
+        if !explained {
+            findings.push(Finding {
+                kind: FindingKind::Constraint,
+                table,
+                count: 1,
+                message,
+                repair: Repair::Manual,
+            });
+        }
+    }
+
+    Ok(VerifyReport {
+        checked_at: Utc::now(),
+        findings,
+    })
+}
+
+/// Apply the repairs attached to `findings`.
+///
+/// Row-level repairs run in one transaction; the FTS and spatial index rebuilds, when
+/// requested, run after it commits so the indexes reflect the repaired tables.
+pub fn repair_findings(db: &CatalogDb, findings: &[Finding]) -> Result<RepairSummary> {
+    let mut summary = RepairSummary::default();
+    let mut needs_fts_rebuild = false;
+    let mut needs_spatial_rebuild = false;
+
+    let tx = db
+        .conn()
+        .unchecked_transaction()
+        .context("failed to begin repair transaction")?;
+    // Relinking updates image rows, which re-checks their json_valid constraints, so bad JSON
+    // has to be cleared first.
+    let mut ordered: Vec<&Finding> = findings.iter().collect();
+    ordered.sort_by_key(|finding| matches!(finding.repair, Repair::RelinkFolders { .. }));
+    for finding in ordered {
+        match &finding.repair {
+            Repair::RestoreBackup | Repair::Manual => summary.skipped += 1,
+            Repair::RebuildFts => needs_fts_rebuild = true,
+            Repair::RebuildSpatialIndex => needs_spatial_rebuild = true,
+            Repair::DeleteRows { table, row_ids } => {
+                for row_id in row_ids {
+                    summary.rows_deleted += tx
+                        .execute(
+                            &format!("DELETE FROM {} WHERE rowid = ?1", quote_ident(table)),
+                            params![row_id],
+                        )
+                        .with_context(|| format!("failed to delete {table} rowid={row_id}"))?;
+                }
+                needs_fts_rebuild |= FTS_TABLES.iter().any(|(_, base)| base == table);
+            }
+            Repair::RelinkFolders { image_ids } => {
+                for &image_id in image_ids {
+                    relink_folder(&tx, image_id)?;
+                    summary.images_relinked += 1;
+                }
+                needs_fts_rebuild = true;
+            }
+            Repair::ClearJson {
+                table,
+                column,
+                row_ids,
+            } => {
+                for row_id in row_ids {
+                    summary.json_cleared += tx
+                        .execute(
+                            &format!(
+                                "UPDATE {} SET {} = NULL WHERE rowid = ?1",
+                                quote_ident(table),
+                                quote_ident(column)
+                            ),
+                            params![row_id],
+                        )
+                        .with_context(|| {
+                            format!("failed to clear {table}.{column} rowid={row_id}")
+                        })?;
+                }
+            }
+        }
+    }
+    tx.commit().context("failed to commit catalog repairs")?;
+
+    if needs_fts_rebuild {
+        rebuild_fts(db)?;
+        summary.fts_rebuilt = true;
+    }
+    if needs_spatial_rebuild {
+        rebuild_spatial_index(db)?;
+        summary.spatial_index_rebuilt = true;
+    }
+    Ok(summary)
+}
+
+/// Record physical corruption and return constraint failures as `(table, message)` pairs.
+fn check_integrity(
+    conn: &Connection,
+    findings: &mut Vec<Finding>,
+) -> Result<Vec<(String, String)>> {
+    let messages: Vec<String> =
+        query_all(conn, "PRAGMA integrity_check", [], |row| Ok(row.get(0)?))
+            .context("failed to run integrity_check")?;
+
+    let mut problems = Vec::new();
+    let mut constraint_failures = Vec::new();
+    for message in messages.into_iter().filter(|m| m != "ok") {
+        let table = message
+            .strip_prefix("CHECK constraint failed in ")
+            .or_else(|| message.strip_prefix("NULL value in "))
+            .map(|rest| rest.split('.').next().unwrap_or(rest).to_string());
+        match table {
+            Some(table) => constraint_failures.push((table, message)),
+            None => problems.push(message),
+        }
+    }
+
+    if !problems.is_empty() {
+        findings.push(Finding {
+            kind: FindingKind::Integrity,
+            table: String::new(),
+            count: problems.len(),
+            message: problems.join("; "),
+            repair: Repair::RestoreBackup,
+        });
+    }
+    Ok(constraint_failures)
+}
+
+fn check_foreign_keys(conn: &Connection, findings: &mut Vec<Finding>) -> Result<()> {
+    let rows: Vec<(String, Option<i64>, String)> =
+        query_all(conn, "PRAGMA foreign_key_check", [], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })
+        .context("failed to run foreign_key_check")?;
+
+    let mut grouped: BTreeMap<(String, String), Vec<i64>> = BTreeMap::new();
+    for (table, row_id, parent) in rows {
+        let ids = grouped.entry((table, parent)).or_default();
+        if let Some(row_id) = row_id {
+            ids.push(row_id);
+        }
+    }
+
+    for ((table, parent), row_ids) in grouped {
+        // Orphaned image children and images without folders get dedicated findings below.
+        if parent == "images" && IMAGE_CHILD_TABLES.contains(&table.as_str()) {
+            continue;
+        }
+        if table == "images" && parent == "folders" {
+            continue;
+        }
+        findings.push(Finding {
+            kind: FindingKind::ForeignKey,
+            message: format!(
+                "{} {table} row(s) reference missing {parent} rows",
+                row_ids.len()
+            ),
+            count: row_ids.len(),
+            repair: Repair::DeleteRows {
+                table: table.clone(),
+                row_ids,
+            },
+            table,
+        });
+    }
+    Ok(())
+}
+
+fn check_orphans(conn: &Connection, findings: &mut Vec<Finding>) -> Result<()> {
+    for table in IMAGE_CHILD_TABLES {
+        let row_ids: Vec<i64> = query_all(
+            conn,
+            &format!(
+                "SELECT c.rowid FROM {table} c
+                 LEFT JOIN images i ON i.id = c.image_id
+                 WHERE i.id IS NULL"
+            ),
+            [],
+            |row| Ok(row.get(0)?),
+        )
+        .with_context(|| format!("failed to check {table} for orphans"))?;
+        if row_ids.is_empty() {
+            continue;
+        }
+        findings.push(Finding {
+            kind: FindingKind::OrphanRow,
+            table: table.to_string(),
+            count: row_ids.len(),
+            message: format!("{} {table} row(s) belong to deleted images", row_ids.len()),
+            repair: Repair::DeleteRows {
+                table: table.to_string(),
+                row_ids,
+            },
+        });
+    }
+    Ok(())
+}
+
+fn check_missing_folders(conn: &Connection, findings: &mut Vec<Finding>) -> Result<()> {
+    let image_ids: Vec<i64> = query_all(
+        conn,
+        "SELECT i.id FROM images i
+         LEFT JOIN folders f ON f.id = i.folder_id
+         WHERE f.id IS NULL
+         ORDER BY i.id",
+        [],
+        |row| Ok(row.get(0)?),
+    )
+    .context("failed to check image folders")?;
+    if !image_ids.is_empty() {
+        findings.push(Finding {
+            kind: FindingKind::MissingFolder,
+            table: "images".into(),
+            count: image_ids.len(),
+            message: format!(
+                "{} image(s) point at a folder that does not exist",
+                image_ids.len()
+            ),
+            repair: Repair::RelinkFolders { image_ids },
+        });
+    }
+    Ok(())
+}
+
+fn check_fts(conn: &Connection, findings: &mut Vec<Finding>) -> Result<()> {
+    for (fts, base) in FTS_TABLES {
+        let missing: i64 = conn
+            .query_row(
+                &format!(
+                    "SELECT COUNT(*) FROM (SELECT rowid FROM {base} EXCEPT SELECT rowid FROM {fts})"
+                ),
+                [],
+                |row| row.get(0),
+            )
+            .with_context(|| format!("failed to compare {fts} with {base}"))?;
+        let stale: i64 = conn
+            .query_row(
+                &format!(
+                    "SELECT COUNT(*) FROM (SELECT rowid FROM {fts} EXCEPT SELECT rowid FROM {base})"
+                ),
+                [],
+                |row| row.get(0),
+            )
+            .with_context(|| format!("failed to compare {fts} with {base}"))?;
+        if missing == 0 && stale == 0 {
+            continue;
+        }
+        findings.push(Finding {
+            kind: FindingKind::FtsOutOfSync,
+            table: fts.to_string(),
+            count: (missing + stale) as usize,
+            message: format!(
+                "{fts} is missing {missing} {base} row(s) and has {stale} stale entr(ies)"
+            ),
+            repair: Repair::RebuildFts,
+        });
+    }
+    Ok(())
+}
+
+fn check_spatial_index(conn: &Connection, findings: &mut Vec<Finding>) -> Result<()> {
+    const POSITIONED: &str =
+        "SELECT id FROM images WHERE gps_latitude IS NOT NULL AND gps_longitude IS NOT NULL";
+    let count = |sql: String| -> Result<i64> {
+        conn.query_row(&sql, [], |row| row.get(0))
+            .context("failed to compare rtree_images with images")
+    };
+    let missing = count(format!(
+        "SELECT COUNT(*) FROM ({POSITIONED} EXCEPT SELECT id FROM rtree_images)"
+    ))?;
+    let stale = count(format!(
+        "SELECT COUNT(*) FROM (SELECT id FROM rtree_images EXCEPT {POSITIONED})"
+    ))?;
+    if missing == 0 && stale == 0 {
+        return Ok(());
+    }
+    findings.push(Finding {
+        kind: FindingKind::SpatialIndexOutOfSync,
+        table: "rtree_images".to_string(),
+        count: (missing + stale) as usize,
+        message: format!(
+            "rtree_images is missing {missing} positioned image(s) and has {stale} stale entr(ies)"
+        ),
+        repair: Repair::RebuildSpatialIndex,
+    });
+    Ok(())
+}
+
+fn check_json_columns(conn: &Connection, findings: &mut Vec<Finding>) -> Result<()> {
+    let tables: Vec<String> = query_all(
+        conn,
+        "SELECT name FROM sqlite_master
+         WHERE type = 'table'
+           AND name NOT LIKE 'sqlite_%'
+           AND name NOT LIKE 'fts_%'
+           AND name NOT LIKE 'rtree_%'
+         ORDER BY name",
+        [],
+        |row| Ok(row.get(0)?),
+    )?;
+
+    for table in tables {
+        let columns: Vec<(String, bool)> = query_all(
+            conn,
+            &format!("PRAGMA table_info({})", quote_ident(&table)),
+            [],
+            |row| Ok((row.get(1)?, row.get(3)?)),
+        )?;
+        for (column, not_null) in columns.into_iter().filter(|(c, _)| c.ends_with("_json")) {
+            let row_ids: Vec<i64> = query_all(
+                conn,
+                &format!(
+                    "SELECT rowid FROM {t} WHERE {c} IS NOT NULL AND json_valid({c}) = 0",
+                    t = quote_ident(&table),
+                    c = quote_ident(&column)
+                ),
+                [],
+                |row| Ok(row.get(0)?),
+            )
+            .with_context(|| format!("failed to validate {table}.{column}"))?;
+            if row_ids.is_empty() {
+                continue;
+            }
+            let repair = if not_null {
+                Repair::DeleteRows {
+                    table: table.clone(),
+                    row_ids: row_ids.clone(),
+                }
+            } else {
+                Repair::ClearJson {
+                    table: table.clone(),
+                    column: column.clone(),
+                    row_ids: row_ids.clone(),
+                }
+            };
+            findings.push(Finding {
+                kind: FindingKind::InvalidJson,
+                table: table.clone(),
+                count: row_ids.len(),
+                message: format!(
+                    "{} row(s) hold invalid JSON in {table}.{column}",
+                    row_ids.len()
+                ),
+                repair,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn relink_folder<H: DbHandle>(db: &H, image_id: i64) -> Result<()> {
+    let original_path: String = db.query_row(
+        "SELECT original_path FROM images WHERE id = ?1",
+        params![image_id],
+        |row| Ok(row.get(0)?),
+    )?;
+    let parent = Path::new(&original_path)
+        .parent()
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|| "/".into());
+
+    let folder_id = match Folder::find_by_path(db, &parent)? {
+        Some(folder) => folder.id,
+        None => {
+            let now = Utc::now();
+            Folder {
+                id: 0,
+                path: parent,
+                root_id: None,
+                relative_path: None,
+                created_at: now,
+                updated_at: now,
+            }
+            .insert(db)?
+        }
+    };
+    db.execute(
+        "UPDATE images SET folder_id = ?1 WHERE id = ?2",
+        params![folder_id, image_id],
+    )
+    .with_context(|| format!("failed to relink folder for image_id={image_id}"))?;
+    Ok(())
+}
+
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::CatalogService;
+    use std::fs;
+
+    #[test]
+    fn finds_and_repairs_damage() {
+        let service = CatalogService::new(CatalogDb::in_memory().unwrap());
+        let photo = std::env::temp_dir().join(format!(
+            "verify_{}.jpg",
+            Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        fs::write(&photo, b"not decodable").unwrap();
+        let image = service.import_image(&photo).unwrap();
+        assert!(service.verify_catalog().unwrap().is_clean());
+
+        let conn = service.db.conn();
+        conn.execute_batch(
+            "PRAGMA foreign_keys = OFF;
+             PRAGMA ignore_check_constraints = ON;",
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO thumbnails (image_id, thumb_256, updated_at) VALUES (999, NULL, 'x')",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "UPDATE images SET folder_id = 4242, metadata_json = '{broken' WHERE id = ?1",
+            params![image.id],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO fts_folders(rowid, path) VALUES (777, '/ghost')",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO rtree_images VALUES (888, 1.0, 1.0, 2.0, 2.0)",
+            [],
+        )
+        .unwrap();
+        conn.execute_batch(
+            "PRAGMA foreign_keys = ON;
+             PRAGMA ignore_check_constraints = OFF;",
+        )
+        .unwrap();
+
+        let report = service.verify_catalog().unwrap();
+        let kinds: Vec<FindingKind> = report.findings.iter().map(|f| f.kind).collect();
+        assert!(kinds.contains(&FindingKind::OrphanRow));
+        assert!(kinds.contains(&FindingKind::MissingFolder));
+        assert!(kinds.contains(&FindingKind::InvalidJson));
+        assert!(kinds.contains(&FindingKind::FtsOutOfSync));
+        assert!(kinds.contains(&FindingKind::SpatialIndexOutOfSync));
+        assert!(report.is_repairable(), "{:?}", report.findings);
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        assert_eq!(json["findings"][0]["repair"]["action"], "delete_rows");
+
+        let summary = service.repair_catalog(&report).unwrap();
+        assert_eq!(summary.rows_deleted, 1);
+        assert_eq!(summary.images_relinked, 1);
+        assert_eq!(summary.json_cleared, 1);
+        assert!(summary.fts_rebuilt);
+        assert!(summary.spatial_index_rebuilt);
+
+        let after = service.verify_catalog().unwrap();
+        assert!(after.is_clean(), "{:?}", after.findings);
+        let relinked = service
+            .find_image_by_original_path(&photo)
+            .unwrap()
+            .unwrap();
+        let folder = Folder::load(&service.db, relinked.folder_id).unwrap();
+        assert_eq!(Path::new(&folder.path), photo.parent().unwrap());
+    }
+}
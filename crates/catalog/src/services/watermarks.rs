@@ -0,0 +1,403 @@
+//! Named watermark definitions stored in the catalog and referenced by export settings.
+//!
+//! Definitions keep sizes in percent so they read naturally in shared preset files;
+//! [`to_engine_watermark`] converts them to the engine's fractions and loads logos.
+
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use engine::{ImageEngine, WatermarkContent};
+use serde::{Deserialize, Serialize};
+
+use crate::db::{DbHandle, Watermark};
+use crate::services::export_presets;
+
+/// Where the watermark sits inside the image, before the margin is applied.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WatermarkAnchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    #[default]
+    BottomRight,
+}
+
+impl WatermarkAnchor {
+    pub const ALL: [WatermarkAnchor; 9] = [
+        WatermarkAnchor::TopLeft,
+        WatermarkAnchor::Top,
+        WatermarkAnchor::TopRight,
+        WatermarkAnchor::Left,
+        WatermarkAnchor::Center,
+        WatermarkAnchor::Right,
+        WatermarkAnchor::BottomLeft,
+        WatermarkAnchor::Bottom,
+        WatermarkAnchor::BottomRight,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            WatermarkAnchor::TopLeft => "Top Left",
+            WatermarkAnchor::Top => "Top",
+            WatermarkAnchor::TopRight => "Top Right",
+            WatermarkAnchor::Left => "Left",
+            WatermarkAnchor::Center => "Center",
+            WatermarkAnchor::Right => "Right",
+            WatermarkAnchor::BottomLeft => "Bottom Left",
+            WatermarkAnchor::Bottom => "Bottom",
+            WatermarkAnchor::BottomRight => "Bottom Right",
+        }
+    }
+
+    fn to_engine(self) -> engine::WatermarkAnchor {
+        match self {
+            WatermarkAnchor::TopLeft => engine::WatermarkAnchor::TopLeft,
+            WatermarkAnchor::Top => engine::WatermarkAnchor::Top,
+            WatermarkAnchor::TopRight => engine::WatermarkAnchor::TopRight,
+            WatermarkAnchor::Left => engine::WatermarkAnchor::Left,
+            WatermarkAnchor::Center => engine::WatermarkAnchor::Center,
+            WatermarkAnchor::Right => engine::WatermarkAnchor::Right,
+            WatermarkAnchor::BottomLeft => engine::WatermarkAnchor::BottomLeft,
+            WatermarkAnchor::Bottom => engine::WatermarkAnchor::Bottom,
+            WatermarkAnchor::BottomRight => engine::WatermarkAnchor::BottomRight,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum WatermarkKind {
+    /// Text rendered with the engine's bundled font; `color` is `#RRGGBB`.
+    Text {
+        text: String,
+        #[serde(default = "default_color")]
+        color: String,
+    },
+    /// A PNG (or any decodable image) composited with its own alpha.
+    Logo { path: PathBuf },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WatermarkDefinition {
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: WatermarkKind,
+    #[serde(default)]
+    pub anchor: WatermarkAnchor,
+    /// Percent of the short edge: text line height, or the logo's longer side.
+    #[serde(default = "default_size")]
+    pub size: f32,
+    /// Percent, 0-100.
+    #[serde(default = "default_opacity")]
+    pub opacity: f32,
+    /// Distance from the anchored edges in percent of the short edge.
+    #[serde(default = "default_margin")]
+    pub margin: f32,
+}
+
+impl WatermarkDefinition {
+    pub fn text(name: impl Into<String>, text: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            kind: WatermarkKind::Text {
+                text: text.into(),
+                color: default_color(),
+            },
+            anchor: WatermarkAnchor::default(),
+            size: default_size(),
+            opacity: default_opacity(),
+            margin: default_margin(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            bail!("watermark name cannot be empty");
+        }
+        match &self.kind {
+            WatermarkKind::Text { text, color } => {
+                if text.trim().is_empty() {
+                    bail!("watermark {} has no text", self.name);
+                }
+                parse_color(color)?;
+            }
+            WatermarkKind::Logo { path } => {
+                if path.as_os_str().is_empty() {
+                    bail!("watermark {} has no logo file", self.name);
+                }
+            }
+        }
+        if self.size <= 0.0 || self.size > 100.0 {
+            bail!("watermark size must be between 0 and 100 percent");
+        }
+        if !(0.0..=100.0).contains(&self.opacity) {
+            bail!("watermark opacity must be between 0 and 100 percent");
+        }
+        if !(0.0..50.0).contains(&self.margin) {
+            bail!("watermark margin must be below 50 percent");
+        }
+        Ok(())
+    }
+}
+
+fn default_color() -> String {
+    "#ffffff".to_string()
+}
+
+fn default_size() -> f32 {
+    5.0
+}
+
+fn default_opacity() -> f32 {
+    70.0
+}
+
+fn default_margin() -> f32 {
+    3.0
+}
+
+/// Parse `#RRGGBB` (the `#` is optional).
+pub fn parse_color(raw: &str) -> Result<[u8; 3]> {
+    let hex = raw.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("invalid color {raw:?}; expected #RRGGBB");
+    }
+    let channel = |at: usize| u8::from_str_radix(&hex[at..at + 2], 16).expect("validated hex");
+    Ok([channel(0), channel(2), channel(4)])
+}
+
+pub fn list_watermarks<H: DbHandle>(db: &H) -> Result<Vec<WatermarkDefinition>> {
+    Watermark::load_all(db)
+        .context("failed to list watermarks")?
+        .into_iter()
+        .map(from_record)
+        .collect()
+}
+
+pub fn find_watermark<H: DbHandle>(db: &H, name: &str) -> Result<Option<WatermarkDefinition>> {
+    Watermark::find_by_name(db, name.trim())?
+        .map(from_record)
+        .transpose()
+}
+
+/// Insert `definition`, or replace the stored watermark with the same name.
+pub fn save_watermark<H: DbHandle>(db: &H, definition: &WatermarkDefinition) -> Result<()> {
+    definition.validate()?;
+    let name = definition.name.trim().to_string();
+    let mut definition = definition.clone();
+    definition.name = name.clone();
+    let definition_json =
+        serde_json::to_value(&definition).context("failed to serialize watermark")?;
+    let now = Utc::now();
+
+    match Watermark::find_by_name(db, &name)? {
+        Some(mut existing) => {
+            existing.name = name;
+            existing.definition_json = definition_json;
+            existing.updated_at = now;
+            existing.update(db)
+        }
+        None => Watermark {
+            id: 0,
+            name,
+            definition_json,
+            created_at: now,
+            updated_at: now,
+        }
+        .insert(db)
+        .map(|_| ()),
+    }
+}
+
+/// Returns false when no watermark has that name. Watermarks still referenced by an
+/// export preset are not deleted.
+pub fn delete_watermark<H: DbHandle>(db: &H, name: &str) -> Result<bool> {
+    let Some(existing) = Watermark::find_by_name(db, name.trim())? else {
+        return Ok(false);
+    };
+    let users: Vec<String> = export_presets::list_presets(db)?
+        .into_iter()
+        .filter(|preset| {
+            preset
+                .settings
+                .watermark
+                .as_deref()
+                .is_some_and(|used| used.trim().eq_ignore_ascii_case(&existing.name))
+        })
+        .map(|preset| preset.name)
+        .collect();
+    if !users.is_empty() {
+        bail!(
+            "watermark {} is used by export preset(s): {}",
+            existing.name,
+            users.join(", ")
+        );
+    }
+    Watermark::delete(db, existing.id)?;
+    Ok(true)
+}
+
+/// Convert a definition into the engine's form, decoding the logo file if needed.
+pub fn to_engine_watermark(
+    engine: &ImageEngine,
+    definition: &WatermarkDefinition,
+) -> Result<engine::Watermark> {
+    definition.validate()?;
+    let content = match &definition.kind {
+        WatermarkKind::Text { text, color } => WatermarkContent::Text {
+            text: text.clone(),
+            color: parse_color(color)?,
+        },
+        WatermarkKind::Logo { path } => WatermarkContent::Logo(
+            engine
+                .load_logo(path)
+                .with_context(|| format!("failed to load watermark logo {}", path.display()))?,
+        ),
+    };
+    Ok(engine::Watermark {
+        content,
+        anchor: definition.anchor.to_engine(),
+        size: definition.size / 100.0,
+        opacity: definition.opacity / 100.0,
+        margin: definition.margin / 100.0,
+    })
+}
+
+fn from_record(record: Watermark) -> Result<WatermarkDefinition> {
+    let mut definition: WatermarkDefinition = serde_json::from_value(record.definition_json)
+        .with_context(|| format!("watermark {} has an unreadable definition", record.name))?;
+    definition.name = record.name;
+    Ok(definition)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::CatalogDb;
+    use crate::schema::initialize_schema;
+    use crate::services::export::{export_images, ExportCallbacks, ExportFormat, ExportSettings};
+    use crate::services::{CatalogService, NamedExportPreset};
+    use image::Rgb;
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn watermarks_composite_on_export_and_stay_while_referenced() {
+        let db = CatalogDb::in_memory().unwrap();
+        initialize_schema(db.conn()).unwrap();
+        let service = CatalogService::new(db);
+        let engine = ImageEngine::new();
+
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let dir = std::env::temp_dir().join(format!("catalog_watermark_{suffix}"));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("dark.png");
+        image::RgbImage::from_pixel(400, 300, Rgb([0, 0, 0]))
+            .save(&source)
+            .unwrap();
+        let image = service.import_image(&source).unwrap();
+
+        let mut signature = WatermarkDefinition::text("Signature", "© Zenith");
+        signature.size = 20.0;
+        signature.opacity = 100.0;
+        signature.anchor = WatermarkAnchor::TopLeft;
+        service.save_watermark(&signature).unwrap();
+        assert_eq!(
+            service.find_watermark("SIGNATURE").unwrap(),
+            Some(signature.clone())
+        );
+
+        let mut invalid = signature.clone();
+        invalid.kind = WatermarkKind::Text {
+            text: "x".into(),
+            color: "white".into(),
+        };
+        assert!(service.save_watermark(&invalid).is_err());
+
+        let settings = ExportSettings {
+            format: ExportFormat::Png,
+            filename_template: "{filename}".into(),
+            watermark: Some("Signature".into()),
+            ..ExportSettings::default()
+        };
+        let out_dir = dir.join("out");
+        let report = export_images(
+            &service,
+            &engine,
+            &[image.id],
+            &out_dir,
+            &settings,
+            ExportCallbacks::default(),
+        )
+        .unwrap();
+        assert_eq!(report.exported.len(), 1);
+        let written = image::open(&report.exported[0].path).unwrap().to_rgb8();
+        let brightest = |x0: u32, y0: u32| {
+            (x0..x0 + 200)
+                .flat_map(|x| (y0..y0 + 150).map(move |y| (x, y)))
+                .map(|(x, y)| written.get_pixel(x, y)[0])
+                .max()
+                .unwrap()
+        };
+        // Text lands in the anchored corner and nowhere else.
+        assert!(brightest(0, 0) > 200);
+        assert_eq!(brightest(200, 150), 0);
+
+        let unknown = ExportSettings {
+            watermark: Some("Missing".into()),
+            ..settings.clone()
+        };
+        assert!(export_images(
+            &service,
+            &engine,
+            &[image.id],
+            &out_dir,
+            &unknown,
+            ExportCallbacks::default(),
+        )
+        .is_err());
+
+        let preset = NamedExportPreset {
+            name: "Signed".into(),
+            destination: None,
+            settings: settings.clone(),
+        };
+        service.save_export_preset(&preset).unwrap();
+        assert!(service
+            .save_export_preset(&NamedExportPreset {
+                name: "Broken".into(),
+                destination: None,
+                settings: unknown,
+            })
+            .is_err());
+
+        // Shared preset files carry the watermark along.
+        let shared = dir.join("presets.json");
+        service.write_export_presets(&shared, &[]).unwrap();
+        let other = CatalogDb::in_memory().unwrap();
+        initialize_schema(other.conn()).unwrap();
+        let other = CatalogService::new(other);
+        assert_eq!(
+            other.import_export_presets(&shared).unwrap(),
+            vec!["Signed"]
+        );
+        assert_eq!(other.list_watermarks().unwrap(), vec![signature]);
+
+        assert!(service.delete_watermark("Signature").is_err());
+        assert!(service.delete_export_preset("Signed").unwrap());
+        assert!(service.delete_watermark("Signature").unwrap());
+        assert!(!service.delete_watermark("Signature").unwrap());
+
+        fs::remove_dir_all(dir).ok();
+    }
+}
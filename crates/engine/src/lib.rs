@@ -1,6 +1,14 @@
-use core_types::PreviewImage;
+use core_types::{PreviewImage, RenderedImage};
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageReader};
 use std::path::Path;
 
+mod sharpen;
+mod watermark;
+
+pub use sharpen::{OutputSharpening, SharpenAmount, SharpenMedium};
+pub use watermark::{Watermark, WatermarkAnchor, WatermarkContent};
+
 #[derive(Debug, thiserror::Error)]
 pub enum EngineError {
     #[error("I/O error: {0}")]
@@ -12,6 +20,93 @@ pub enum EngineError {
 
 pub type Result<T> = std::result::Result<T, EngineError>;
 
+/// Global tone and color adjustments in Refine slider units.
+///
+/// Exposure is in EV; every other control runs from -100 to 100 with 0 meaning
+/// "no change".
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Adjustments {
+    pub exposure: f32,
+    pub contrast: f32,
+    pub highlights: f32,
+    pub shadows: f32,
+    pub whites: f32,
+    pub blacks: f32,
+    pub vibrance: f32,
+    pub saturation: f32,
+    pub temperature: f32,
+    pub tint: f32,
+}
+
+impl Adjustments {
+    pub fn is_neutral(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Apply the adjustments to one gamma-encoded RGB pixel with channels in `0.0..=1.0`.
+    pub fn apply(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let mut c = rgb;
+
+        if self.exposure != 0.0 {
+            let gain = 2f32.powf(self.exposure);
+            c = c.map(|v| linear_to_srgb(srgb_to_linear(v) * gain));
+        }
+
+        // Temperature warms (red up, blue down); tint pushes toward magenta.
+        let warm = self.temperature / 100.0 * 0.15;
+        let magenta = self.tint / 100.0 * 0.15;
+        c[0] *= 1.0 + warm;
+        c[1] *= 1.0 - magenta;
+        c[2] *= 1.0 - warm;
+
+        // Blacks and whites move the end points of the tonal range.
+        let black_point = -self.blacks / 100.0 * 0.15;
+        let white_point = 1.0 - self.whites / 100.0 * 0.15;
+        let range = (white_point - black_point).max(1e-3);
+        c = c.map(|v| (v - black_point) / range);
+
+        // Shadows and highlights lift or pull tones weighted by luminance.
+        let luma = luminance(c).clamp(0.0, 1.0);
+        let shadow_weight = (1.0 - luma) * (1.0 - luma);
+        let highlight_weight = luma * luma;
+        let shift = self.shadows / 100.0 * 0.25 * shadow_weight
+            + self.highlights / 100.0 * 0.25 * highlight_weight;
+        c = c.map(|v| v + shift);
+
+        let contrast = 1.0 + self.contrast / 100.0;
+        c = c.map(|v| (v - 0.5) * contrast + 0.5);
+
+        // Vibrance boosts muted colors more than already saturated ones.
+        let luma = luminance(c);
+        let chroma = c[0].max(c[1]).max(c[2]) - c[0].min(c[1]).min(c[2]);
+        let saturation = (1.0 + self.saturation / 100.0)
+            * (1.0 + self.vibrance / 100.0 * (1.0 - chroma.clamp(0.0, 1.0)));
+        c = c.map(|v| luma + (v - luma) * saturation);
+
+        c.map(|v| v.clamp(0.0, 1.0))
+    }
+}
+
+fn luminance(c: [f32; 3]) -> f32 {
+    0.2126 * c[0] + 0.7152 * c[1] + 0.0722 * c[2]
+}
+
+fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
 pub struct ImageEngine;
 
 impl ImageEngine {
@@ -34,4 +129,68 @@ impl ImageEngine {
             data,
         })
     }
+
+    /// Decode a file at full resolution, turn it upright and apply `adjustments` to every pixel.
+    ///
+    /// `orientation` is an EXIF orientation code (1-8) that takes precedence over the one
+    /// stored in the file; pass `None` to use the file's own.
+    pub fn render<P: AsRef<Path>>(
+        &self,
+        path: P,
+        adjustments: &Adjustments,
+        orientation: Option<u8>,
+    ) -> Result<RenderedImage> {
+        let path = path.as_ref();
+        let decode_err = |e: image::ImageError| EngineError::Decode(e.to_string());
+        let mut decoder = ImageReader::open(path)?
+            .into_decoder()
+            .map_err(decode_err)?;
+        let orientation = match orientation.and_then(Orientation::from_exif) {
+            Some(orientation) => orientation,
+            None => decoder.orientation().map_err(decode_err)?,
+        };
+        let mut dyn_img = DynamicImage::from_decoder(decoder).map_err(decode_err)?;
+        dyn_img.apply_orientation(orientation);
+        let (width, height) = (dyn_img.width(), dyn_img.height());
+
+        let data = if adjustments.is_neutral() {
+            dyn_img.to_rgb16().into_raw()
+        } else {
+            dyn_img
+                .to_rgb32f()
+                .pixels()
+                .flat_map(|px| adjustments.apply(px.0))
+                .map(|v| (v * 65535.0).round() as u16)
+                .collect()
+        };
+
+        Ok(RenderedImage {
+            width,
+            height,
+            data,
+        })
+    }
+
+    /// Decode a watermark logo at full resolution, keeping its alpha channel.
+    pub fn load_logo<P: AsRef<Path>>(&self, path: P) -> Result<PreviewImage> {
+        let logo = image::open(path.as_ref())
+            .map_err(|e| EngineError::Decode(e.to_string()))?
+            .to_rgba8();
+        let (width, height) = logo.dimensions();
+        Ok(PreviewImage {
+            width,
+            height,
+            data: logo.into_raw(),
+        })
+    }
+
+    /// Apply output sharpening in place; call after resizing to the final size.
+    pub fn sharpen_for_output(&self, image: &mut RenderedImage, sharpening: &OutputSharpening) {
+        sharpen::sharpen(image, sharpening)
+    }
+
+    /// Alpha-blend `watermark` onto a rendered image in place.
+    pub fn apply_watermark(&self, image: &mut RenderedImage, watermark: &Watermark) -> Result<()> {
+        watermark::composite(image, watermark)
+    }
 }
@@ -0,0 +1,138 @@
+//! Output sharpening applied to rendered images after they are resized for export.
+//!
+//! Uses an unsharp mask on luminance so edges gain contrast without color fringes.
+//! Radius and strength follow the output medium and resolution: screen output scales
+//! with the pixel size of the image, print output with the printing resolution.
+
+use core_types::RenderedImage;
+
+/// What the exported file is intended for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SharpenMedium {
+    #[default]
+    Screen,
+    /// Matte paper absorbs ink and softens detail, so it gets the strongest pass.
+    MattePaper,
+    GlossyPaper,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SharpenAmount {
+    Low,
+    #[default]
+    Standard,
+    High,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputSharpening {
+    pub medium: SharpenMedium,
+    pub amount: SharpenAmount,
+    /// Printing resolution in pixels per inch; ignored for screen output.
+    pub ppi: u32,
+}
+
+impl Default for OutputSharpening {
+    fn default() -> Self {
+        Self {
+            medium: SharpenMedium::default(),
+            amount: SharpenAmount::default(),
+            ppi: 300,
+        }
+    }
+}
+
+impl OutputSharpening {
+    /// Gaussian radius (sigma, in pixels) and strength for an image of the given size.
+    pub fn parameters(&self, width: u32, height: u32) -> (f32, f32) {
+        let level = match self.amount {
+            SharpenAmount::Low => 0.6,
+            SharpenAmount::Standard => 1.0,
+            SharpenAmount::High => 1.5,
+        };
+        match self.medium {
+            SharpenMedium::Screen => {
+                // Small web images need fine, gentle sharpening; large ones tolerate more.
+                let scale = (width.max(height) as f32 / 2048.0).clamp(0.25, 2.0);
+                (0.5 + 0.25 * scale, 0.45 * level * scale.sqrt())
+            }
+            SharpenMedium::MattePaper | SharpenMedium::GlossyPaper => {
+                // A printed dot covers more pixels at higher resolutions.
+                let scale = (self.ppi.max(1) as f32 / 300.0).clamp(0.25, 3.0);
+                let (radius, strength) = match self.medium {
+                    SharpenMedium::MattePaper => (1.0, 1.2),
+                    _ => (0.8, 0.9),
+                };
+                (radius * scale, strength * level * scale.sqrt())
+            }
+        }
+    }
+}
+
+pub(crate) fn sharpen(image: &mut RenderedImage, sharpening: &OutputSharpening) {
+    let (width, height) = (image.width as usize, image.height as usize);
+    if width == 0 || height == 0 {
+        return;
+    }
+    let (sigma, strength) = sharpening.parameters(image.width, image.height);
+
+    let luma: Vec<f32> = image
+        .data
+        .chunks_exact(3)
+        .map(|px| (0.2126 * px[0] as f32 + 0.7152 * px[1] as f32 + 0.0722 * px[2] as f32) / 65535.0)
+        .collect();
+    let blurred = gaussian_blur(&luma, width, height, sigma);
+
+    // Ignore faint differences so noise and smooth gradients are left alone.
+    let threshold = 1.5 / 255.0;
+    for (idx, px) in image.data.chunks_exact_mut(3).enumerate() {
+        let detail = luma[idx] - blurred[idx];
+        if detail.abs() <= threshold {
+            continue;
+        }
+        let delta = detail * strength * 65535.0;
+        for channel in px.iter_mut() {
+            *channel = (*channel as f32 + delta).round().clamp(0.0, 65535.0) as u16;
+        }
+    }
+}
+
+/// Separable Gaussian blur of a single-channel plane with clamped edges.
+fn gaussian_blur(plane: &[f32], width: usize, height: usize, sigma: f32) -> Vec<f32> {
+    let radius = (sigma * 3.0).ceil().max(1.0) as isize;
+    let mut kernel: Vec<f32> = (-radius..=radius)
+        .map(|x| (-(x * x) as f32 / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let sum: f32 = kernel.iter().sum();
+    kernel.iter_mut().for_each(|k| *k /= sum);
+
+    let mut horizontal = vec![0.0; plane.len()];
+    for y in 0..height {
+        let row = &plane[y * width..(y + 1) * width];
+        for x in 0..width {
+            horizontal[y * width + x] = kernel
+                .iter()
+                .enumerate()
+                .map(|(k, weight)| {
+                    let sx = (x as isize + k as isize - radius).clamp(0, width as isize - 1);
+                    row[sx as usize] * weight
+                })
+                .sum();
+        }
+    }
+
+    let mut out = vec![0.0; plane.len()];
+    for y in 0..height {
+        for x in 0..width {
+            out[y * width + x] = kernel
+                .iter()
+                .enumerate()
+                .map(|(k, weight)| {
+                    let sy = (y as isize + k as isize - radius).clamp(0, height as isize - 1);
+                    horizontal[sy as usize * width + x] * weight
+                })
+                .sum();
+        }
+    }
+    out
+}
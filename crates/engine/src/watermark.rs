@@ -0,0 +1,221 @@
+//! Alpha-blended text and logo watermarks composited onto rendered images.
+
+use ab_glyph::{point, Font, FontRef, Glyph, PxScale, ScaleFont};
+use core_types::{PreviewImage, RenderedImage};
+use image::imageops::{resize, FilterType};
+use image::{Rgba, RgbaImage};
+
+use crate::{EngineError, Result};
+
+/// DejaVu Sans, see `assets/LICENSE-DejaVu.txt`.
+static FONT_DATA: &[u8] = include_bytes!("../assets/DejaVuSans.ttf");
+
+/// Where the watermark sits inside the image, before the margin is applied.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WatermarkAnchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    #[default]
+    BottomRight,
+}
+
+#[derive(Debug, Clone)]
+pub enum WatermarkContent {
+    /// Text drawn with the bundled font; lines are separated by `\n`.
+    Text { text: String, color: [u8; 3] },
+    /// An RGBA logo, usually loaded with [`crate::ImageEngine::load_logo`].
+    Logo(PreviewImage),
+}
+
+/// A watermark sized relative to the short edge of the image it is applied to.
+#[derive(Debug, Clone)]
+pub struct Watermark {
+    pub content: WatermarkContent,
+    pub anchor: WatermarkAnchor,
+    /// Text line height, or the logo's longer side, as a fraction of the short edge.
+    pub size: f32,
+    /// 0.0 (invisible) to 1.0 (opaque), multiplied with the content's own alpha.
+    pub opacity: f32,
+    /// Distance from the anchored edges as a fraction of the short edge.
+    pub margin: f32,
+}
+
+pub(crate) fn composite(image: &mut RenderedImage, watermark: &Watermark) -> Result<()> {
+    let opacity = watermark.opacity.clamp(0.0, 1.0);
+    let short_edge = image.width.min(image.height) as f32;
+    let margin = (watermark.margin.max(0.0) * short_edge).round() as u32;
+    let available_w = image.width.saturating_sub(margin * 2);
+    let available_h = image.height.saturating_sub(margin * 2);
+    if opacity == 0.0 || available_w == 0 || available_h == 0 {
+        return Ok(());
+    }
+
+    let target = (watermark.size.clamp(0.0, 1.0) * short_edge).max(1.0);
+    let layer = match &watermark.content {
+        WatermarkContent::Text { text, color } => {
+            match render_text(text, *color, target, watermark.anchor)? {
+                Some(layer) => layer,
+                None => return Ok(()),
+            }
+        }
+        WatermarkContent::Logo(logo) => {
+            let logo = RgbaImage::from_raw(logo.width, logo.height, logo.data.clone())
+                .ok_or_else(|| EngineError::Decode("malformed logo buffer".into()))?;
+            let scale = target / logo.width().max(logo.height()).max(1) as f32;
+            scale_layer(logo, scale)
+        }
+    };
+
+    // Shrink anything that would not fit between the margins.
+    let fit =
+        (available_w as f32 / layer.width() as f32).min(available_h as f32 / layer.height() as f32);
+    let layer = if fit < 1.0 {
+        scale_layer(layer, fit)
+    } else {
+        layer
+    };
+
+    let (x, y) = anchor_position(
+        watermark.anchor,
+        (image.width, image.height),
+        layer.dimensions(),
+        margin,
+    );
+    blend(image, &layer, x, y, opacity);
+    Ok(())
+}
+
+fn scale_layer(layer: RgbaImage, scale: f32) -> RgbaImage {
+    let width = ((layer.width() as f32 * scale).round() as u32).max(1);
+    let height = ((layer.height() as f32 * scale).round() as u32).max(1);
+    if (width, height) == layer.dimensions() {
+        layer
+    } else {
+        resize(&layer, width, height, FilterType::Lanczos3)
+    }
+}
+
+/// Rasterize `text` into a tightly sized layer whose alpha is the glyph coverage.
+fn render_text(
+    text: &str,
+    color: [u8; 3],
+    line_height: f32,
+    anchor: WatermarkAnchor,
+) -> Result<Option<RgbaImage>> {
+    let font = FontRef::try_from_slice(FONT_DATA)
+        .map_err(|err| EngineError::Decode(format!("bundled font is invalid: {err}")))?;
+    let scale = PxScale::from(line_height);
+    let scaled = font.as_scaled(scale);
+    let advance_y = scaled.height() + scaled.line_gap();
+
+    let lines: Vec<(Vec<Glyph>, f32)> = text
+        .lines()
+        .map(|line| {
+            let mut caret = 0.0f32;
+            let mut previous = None;
+            let mut glyphs = Vec::new();
+            for ch in line.chars().filter(|ch| !ch.is_control()) {
+                let id = font.glyph_id(ch);
+                if let Some(previous) = previous {
+                    caret += scaled.kern(previous, id);
+                }
+                glyphs.push(id.with_scale_and_position(scale, point(caret, 0.0)));
+                caret += scaled.h_advance(id);
+                previous = Some(id);
+            }
+            (glyphs, caret)
+        })
+        .collect();
+
+    let width = lines
+        .iter()
+        .map(|(_, line_width)| line_width.ceil() as u32)
+        .max()
+        .unwrap_or(0);
+    if width == 0 {
+        return Ok(None);
+    }
+    let height = (advance_y * (lines.len() - 1) as f32 + scaled.height()).ceil() as u32;
+
+    let mut layer = RgbaImage::from_pixel(width, height, Rgba([color[0], color[1], color[2], 0]));
+    for (row, (glyphs, line_width)) in lines.into_iter().enumerate() {
+        // Align lines toward the anchored side so multi-line text hugs the edge.
+        let offset_x = match anchor {
+            WatermarkAnchor::TopLeft | WatermarkAnchor::Left | WatermarkAnchor::BottomLeft => 0.0,
+            WatermarkAnchor::Top | WatermarkAnchor::Center | WatermarkAnchor::Bottom => {
+                (width as f32 - line_width) / 2.0
+            }
+            _ => width as f32 - line_width,
+        };
+        let baseline = scaled.ascent() + advance_y * row as f32;
+        for mut glyph in glyphs {
+            glyph.position = point(glyph.position.x + offset_x, baseline);
+            let Some(outlined) = font.outline_glyph(glyph) else {
+                continue;
+            };
+            let bounds = outlined.px_bounds();
+            outlined.draw(|gx, gy, coverage| {
+                let x = bounds.min.x as i64 + gx as i64;
+                let y = bounds.min.y as i64 + gy as i64;
+                if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+                    return;
+                }
+                let pixel = layer.get_pixel_mut(x as u32, y as u32);
+                let alpha = (coverage.clamp(0.0, 1.0) * 255.0).round() as u8;
+                pixel.0[3] = pixel.0[3].max(alpha);
+            });
+        }
+    }
+    Ok(Some(layer))
+}
+
+fn anchor_position(
+    anchor: WatermarkAnchor,
+    (image_w, image_h): (u32, u32),
+    (layer_w, layer_h): (u32, u32),
+    margin: u32,
+) -> (u32, u32) {
+    let left = margin;
+    let center_x = (image_w - layer_w) / 2;
+    let right = image_w - layer_w - margin;
+    let top = margin;
+    let center_y = (image_h - layer_h) / 2;
+    let bottom = image_h - layer_h - margin;
+    match anchor {
+        WatermarkAnchor::TopLeft => (left, top),
+        WatermarkAnchor::Top => (center_x, top),
+        WatermarkAnchor::TopRight => (right, top),
+        WatermarkAnchor::Left => (left, center_y),
+        WatermarkAnchor::Center => (center_x, center_y),
+        WatermarkAnchor::Right => (right, center_y),
+        WatermarkAnchor::BottomLeft => (left, bottom),
+        WatermarkAnchor::Bottom => (center_x, bottom),
+        WatermarkAnchor::BottomRight => (right, bottom),
+    }
+}
+
+/// Source-over blend of an 8-bit RGBA layer onto the RGB16 image at (`x`, `y`).
+fn blend(image: &mut RenderedImage, layer: &RgbaImage, x: u32, y: u32, opacity: f32) {
+    for (lx, ly, pixel) in layer.enumerate_pixels() {
+        let alpha = pixel.0[3] as f32 / 255.0 * opacity;
+        if alpha <= 0.0 {
+            continue;
+        }
+        let (px, py) = (x + lx, y + ly);
+        if px >= image.width || py >= image.height {
+            continue;
+        }
+        let base = ((py * image.width + px) * 3) as usize;
+        for channel in 0..3 {
+            let src = pixel.0[channel] as f32 * 257.0;
+            let dst = image.data[base + channel] as f32;
+            image.data[base + channel] = (dst + (src - dst) * alpha).round() as u16;
+        }
+    }
+}